@@ -1,6 +1,17 @@
 use anyhow::Result;
+use std::fs;
 use vergen::{vergen, Config};
 
 fn main() -> Result<()> {
+    // List the migrations embedded in the binary, so that the wallet can
+    // report which of them a database has yet to run.
+    println!("cargo:rerun-if-changed=migrations");
+    let mut migrations = fs::read_dir("migrations")?
+        .map(|entry| Ok(entry?.file_name().to_string_lossy().to_string()))
+        .collect::<Result<Vec<String>>>()?;
+    migrations.retain(|name| !name.starts_with('.'));
+    migrations.sort();
+    println!("cargo:rustc-env=WALLET_DB_MIGRATIONS={}", migrations.join(","));
+
     vergen(Config::default())
 }
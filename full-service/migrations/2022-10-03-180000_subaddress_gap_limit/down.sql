@@ -0,0 +1 @@
+ALTER TABLE accounts DROP COLUMN subaddress_gap_limit;
@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN subaddress_gap_limit UNSIGNED BIG INT NOT NULL DEFAULT 0;
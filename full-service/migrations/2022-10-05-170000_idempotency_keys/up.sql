@@ -0,0 +1,9 @@
+CREATE TABLE idempotency_keys (
+  account_id VARCHAR NOT NULL,
+  idempotency_key VARCHAR NOT NULL,
+  request_fingerprint VARCHAR NOT NULL,
+  transaction_log_id VARCHAR,
+  created_at UNSIGNED BIG INT NOT NULL,
+  PRIMARY KEY (account_id, idempotency_key),
+  FOREIGN KEY (account_id) REFERENCES accounts(id)
+);
@@ -0,0 +1,9 @@
+CREATE TABLE transaction_reports (
+  transaction_log_id VARCHAR NOT NULL PRIMARY KEY,
+  account_id VARCHAR NOT NULL,
+  record VARCHAR NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  last_error VARCHAR,
+  delivered_at UNSIGNED BIG INT,
+  created_at UNSIGNED BIG INT NOT NULL
+);
@@ -0,0 +1 @@
+ALTER TABLE accounts DROP COLUMN split_change_into;
@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN split_change_into UNSIGNED BIG INT NOT NULL DEFAULT 1;
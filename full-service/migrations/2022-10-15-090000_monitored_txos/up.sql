@@ -0,0 +1,9 @@
+CREATE TABLE monitored_txos (
+  public_key BLOB NOT NULL PRIMARY KEY,
+  transaction_log_id VARCHAR NOT NULL,
+  recipient_public_address_b58 VARCHAR NOT NULL,
+  value UNSIGNED BIG INT NOT NULL,
+  token_id UNSIGNED BIG INT NOT NULL,
+  landed_block_index UNSIGNED BIG INT,
+  FOREIGN KEY (transaction_log_id) REFERENCES transaction_logs(id)
+);
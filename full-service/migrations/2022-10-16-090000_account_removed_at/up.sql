@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN removed_at UNSIGNED BIG INT;
@@ -0,0 +1 @@
+ALTER TABLE transaction_logs DROP COLUMN block_version;
@@ -0,0 +1 @@
+ALTER TABLE transaction_logs ADD COLUMN block_version UNSIGNED BIG INT;
@@ -0,0 +1,2 @@
+DROP INDEX idx_transaction_output_txos__public_key;
+ALTER TABLE transaction_output_txos DROP COLUMN public_key;
@@ -0,0 +1,5 @@
+ALTER TABLE transaction_output_txos ADD COLUMN public_key BLOB;
+UPDATE transaction_output_txos SET public_key = (
+  SELECT txos.public_key FROM txos WHERE txos.id = transaction_output_txos.txo_id
+);
+CREATE INDEX idx_transaction_output_txos__public_key ON transaction_output_txos (public_key);
@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN min_confirmations UNSIGNED BIG INT;
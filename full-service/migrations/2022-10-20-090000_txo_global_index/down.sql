@@ -0,0 +1,2 @@
+DROP INDEX idx_txos__global_index;
+ALTER TABLE txos DROP COLUMN global_index;
@@ -0,0 +1,2 @@
+ALTER TABLE txos ADD COLUMN global_index BIGINT;
+CREATE INDEX idx_txos__global_index ON txos (global_index);
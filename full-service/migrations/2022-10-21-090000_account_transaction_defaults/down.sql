@@ -0,0 +1,3 @@
+ALTER TABLE accounts DROP COLUMN default_fee_value;
+ALTER TABLE accounts DROP COLUMN default_fee_token_id;
+ALTER TABLE accounts DROP COLUMN default_tombstone_offset;
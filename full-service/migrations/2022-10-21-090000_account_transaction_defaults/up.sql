@@ -0,0 +1,3 @@
+ALTER TABLE accounts ADD COLUMN default_fee_value UNSIGNED BIG INT;
+ALTER TABLE accounts ADD COLUMN default_fee_token_id UNSIGNED BIG INT;
+ALTER TABLE accounts ADD COLUMN default_tombstone_offset UNSIGNED BIG INT;
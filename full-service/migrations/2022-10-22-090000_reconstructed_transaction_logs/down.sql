@@ -0,0 +1,3 @@
+ALTER TABLE transaction_logs DROP COLUMN reconstructed;
+ALTER TABLE transaction_logs DROP COLUMN reconstructed_value;
+ALTER TABLE transaction_logs DROP COLUMN recipient_address_hash;
@@ -0,0 +1,3 @@
+ALTER TABLE transaction_logs ADD COLUMN reconstructed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE transaction_logs ADD COLUMN reconstructed_value BIGINT;
+ALTER TABLE transaction_logs ADD COLUMN recipient_address_hash BLOB;
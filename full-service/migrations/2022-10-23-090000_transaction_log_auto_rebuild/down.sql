@@ -0,0 +1,3 @@
+ALTER TABLE transaction_logs DROP COLUMN superseded_by;
+ALTER TABLE transaction_logs DROP COLUMN auto_rebuild_attempts_left;
+ALTER TABLE transaction_logs DROP COLUMN auto_rebuild_fee_bump_percent;
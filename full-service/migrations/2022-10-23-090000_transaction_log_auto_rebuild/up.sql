@@ -0,0 +1,3 @@
+ALTER TABLE transaction_logs ADD COLUMN superseded_by TEXT;
+ALTER TABLE transaction_logs ADD COLUMN auto_rebuild_attempts_left INTEGER;
+ALTER TABLE transaction_logs ADD COLUMN auto_rebuild_fee_bump_percent INTEGER;
@@ -0,0 +1,5 @@
+DROP INDEX idx_transaction_output_txos__recipient_public_address_b58;
+DROP INDEX idx_transaction_logs__fee_token_id;
+DROP INDEX idx_transaction_logs__account_id__created_at;
+DROP INDEX idx_transaction_logs__account_id__finalized_block_index;
+ALTER TABLE transaction_logs DROP COLUMN created_at;
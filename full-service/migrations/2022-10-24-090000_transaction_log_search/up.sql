@@ -0,0 +1,5 @@
+ALTER TABLE transaction_logs ADD COLUMN created_at BIGINT;
+CREATE INDEX idx_transaction_logs__account_id__finalized_block_index ON transaction_logs (account_id, finalized_block_index);
+CREATE INDEX idx_transaction_logs__account_id__created_at ON transaction_logs (account_id, created_at);
+CREATE INDEX idx_transaction_logs__fee_token_id ON transaction_logs (fee_token_id);
+CREATE INDEX idx_transaction_output_txos__recipient_public_address_b58 ON transaction_output_txos (recipient_public_address_b58);
@@ -0,0 +1,4 @@
+DROP INDEX idx_gift_codes__txo_public_key;
+DROP INDEX idx_gift_codes__account_id;
+ALTER TABLE gift_codes DROP COLUMN txo_public_key;
+ALTER TABLE gift_codes DROP COLUMN account_id;
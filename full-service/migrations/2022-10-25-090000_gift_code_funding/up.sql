@@ -0,0 +1,4 @@
+ALTER TABLE gift_codes ADD COLUMN account_id TEXT;
+ALTER TABLE gift_codes ADD COLUMN txo_public_key BLOB;
+CREATE INDEX idx_gift_codes__account_id ON gift_codes (account_id);
+CREATE INDEX idx_gift_codes__txo_public_key ON gift_codes (txo_public_key);
@@ -0,0 +1,2 @@
+ALTER TABLE transaction_logs DROP COLUMN payment_request_id;
+ALTER TABLE transaction_logs DROP COLUMN burn_redemption_memo;
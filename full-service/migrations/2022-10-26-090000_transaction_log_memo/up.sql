@@ -0,0 +1,2 @@
+ALTER TABLE transaction_logs ADD COLUMN payment_request_id BIGINT;
+ALTER TABLE transaction_logs ADD COLUMN burn_redemption_memo BLOB;
@@ -0,0 +1,2 @@
+DROP INDEX idx_txos__account_id__modified_seq;
+ALTER TABLE txos DROP COLUMN modified_seq;
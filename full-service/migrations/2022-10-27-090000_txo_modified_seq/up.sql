@@ -0,0 +1,2 @@
+ALTER TABLE txos ADD COLUMN modified_seq BIGINT NOT NULL DEFAULT 0;
+CREATE INDEX idx_txos__account_id__modified_seq ON txos (account_id, modified_seq, id);
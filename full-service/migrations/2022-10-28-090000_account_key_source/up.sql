@@ -0,0 +1,7 @@
+ALTER TABLE accounts ADD COLUMN key_source TEXT NOT NULL DEFAULT 'created';
+UPDATE accounts SET key_source = CASE
+    WHEN view_only THEN 'view_only_import'
+    WHEN key_derivation_version = 1 THEN 'imported_keys'
+    WHEN import_block_index IS NULL OR first_block_index >= import_block_index THEN 'created'
+    ELSE 'imported_mnemonic'
+END;
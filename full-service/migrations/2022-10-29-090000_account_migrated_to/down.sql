@@ -0,0 +1 @@
+ALTER TABLE accounts DROP COLUMN migrated_to_account_id;
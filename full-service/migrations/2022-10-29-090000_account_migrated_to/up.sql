@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN migrated_to_account_id TEXT;
@@ -0,0 +1 @@
+ALTER TABLE accounts ADD COLUMN first_activity_block_index BIGINT;
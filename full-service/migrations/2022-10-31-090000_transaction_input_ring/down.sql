@@ -0,0 +1 @@
+ALTER TABLE transaction_input_txos DROP COLUMN ring;
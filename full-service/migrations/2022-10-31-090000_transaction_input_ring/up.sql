@@ -0,0 +1 @@
+ALTER TABLE transaction_input_txos ADD COLUMN ring BLOB;
@@ -0,0 +1 @@
+ALTER TABLE idempotency_keys ADD COLUMN submission_error TEXT;
@@ -0,0 +1 @@
+DROP INDEX idx_txos__modified_seq;
@@ -0,0 +1 @@
+CREATE INDEX idx_txos__modified_seq ON txos (modified_seq);
@@ -7,22 +7,36 @@ use diesel::{prelude::*, SqliteConnection};
 use dotenv::dotenv;
 use mc_attest_verifier::{MrSignerVerifier, Verifier, DEBUG_ENCLAVE};
 use mc_common::logger::{create_app_logger, log, o, Logger};
-use mc_connection::ConnectionManager;
+use mc_connection::{BlockchainConnection, ConnectionManager, UserTxConnection};
 use mc_consensus_scp::QuorumSet;
-use mc_fog_report_validation::FogResolver;
+use mc_fog_report_validation::{FogPubkeyResolver, FogResolver};
 use mc_full_service::{
     check_host,
     config::APIConfig,
-    wallet::{consensus_backed_rocket, validator_backed_rocket, APIKeyState, WalletState},
-    ValidatorLedgerSyncThread, WalletDb, WalletService,
+    db::set_write_attempts,
+    json_rpc::{capability::ListenerCapabilities, scoped_api_key::ScopedApiKeysState},
+    service::{transaction::AutoRebuildThread, txo::TxoService, WalletServiceConfig},
+    util::logging::{JsonDrain, RedactingDrain},
+    wallet::{
+        consensus_backed_rocket, validator_backed_rocket, APIKeyState, SecretsAPIKeyState,
+        WalletState,
+    },
+    ReadOnlyLedgerThread, ValidatorLedgerSyncThread, WalletDb, WalletService,
 };
 use mc_ledger_sync::{LedgerSyncServiceThread, PollingNetworkState, ReqwestTransactionsFetcher};
 use mc_validator_api::ValidatorUri;
 use mc_validator_connection::ValidatorConnection;
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    iterator::Signals,
+};
+use slog::Drain;
 use std::{
     env,
     process::exit,
     sync::{Arc, RwLock},
+    thread,
+    time::Duration,
 };
 use structopt::StructOpt;
 
@@ -34,6 +48,8 @@ extern crate diesel_migrations;
 const EXIT_NO_DATABASE_CONNECTION: i32 = 2;
 const EXIT_WRONG_PASSWORD: i32 = 3;
 const EXIT_INVALID_HOST: i32 = 4;
+const EXIT_MIGRATION_FAILED: i32 = 5;
+const EXIT_SHUTDOWN_TIMED_OUT: i32 = 6;
 
 fn main() {
     dotenv().ok();
@@ -54,12 +70,14 @@ fn main() {
     }
 
     let (logger, _global_logger_guard) = create_app_logger(o!());
-
-    let rocket_config: rocket::Config =
-        rocket::Config::build(rocket::config::Environment::Development)
-            .address(&config.listen_host)
-            .port(config.listen_port)
-            .unwrap();
+    let logger = if config.log_json {
+        Logger::root(
+            RedactingDrain(JsonDrain::new(std::io::stdout())).fuse(),
+            o!(),
+        )
+    } else {
+        Logger::root(RedactingDrain(logger).fuse(), o!())
+    };
 
     // Connect to the database and run the migrations
     let conn =
@@ -68,37 +86,42 @@ fn main() {
             exit(EXIT_NO_DATABASE_CONNECTION);
         });
     WalletDb::set_db_encryption_key_from_env(&conn);
-    WalletDb::try_change_db_encryption_key_from_env(&conn);
+    WalletDb::try_change_db_encryption_key_from_env(&conn, &logger);
     if !WalletDb::check_database_connectivity(&conn) {
         eprintln!("Incorrect password for database {:?}.", config.wallet_db);
         exit(EXIT_WRONG_PASSWORD);
     };
-    WalletDb::run_migrations(&conn);
+    if let Err(e) = WalletDb::migrate(
+        &conn,
+        &config.wallet_db,
+        &config.get_migration_options(),
+        &logger,
+    ) {
+        eprintln!("Could not migrate database {:?}: {}", config.wallet_db, e);
+        exit(EXIT_MIGRATION_FAILED);
+    }
     log::info!(logger, "Connected to database.");
 
-    let wallet_db = WalletDb::new_from_url(
+    let wallet_db = WalletDb::new_from_url_with_options(
         config
             .wallet_db
             .to_str()
             .expect("Could not get wallet_db path"),
         10,
+        config.get_connection_options(),
     )
     .expect("Could not access wallet db");
+    set_write_attempts(config.wallet_db_write_attempts);
 
     // Start WalletService based on our configuration
     if let Some(validator_uri) = config.validator.as_ref() {
-        validator_backed_full_service(validator_uri, &config, wallet_db, rocket_config, logger)
+        validator_backed_full_service(validator_uri, &config, wallet_db, logger)
     } else {
-        consensus_backed_full_service(&config, wallet_db, rocket_config, logger)
+        consensus_backed_full_service(&config, wallet_db, logger)
     };
 }
 
-fn consensus_backed_full_service(
-    config: &APIConfig,
-    wallet_db: WalletDb,
-    rocket_config: rocket::Config,
-    logger: Logger,
-) {
+fn consensus_backed_full_service(config: &APIConfig, wallet_db: WalletDb, logger: Logger) {
     // Verifier
     let mut mr_signer_verifier =
         MrSignerVerifier::from(mc_consensus_enclave_measurement::sigstruct());
@@ -141,41 +164,73 @@ fn consensus_backed_full_service(
         &logger,
     );
 
-    // Start ledger sync thread unless running in offline mode.
-    let _ledger_sync_service_thread = if config.offline {
-        None
-    } else {
-        Some(LedgerSyncServiceThread::new(
-            ledger_db.clone(),
-            peer_manager.clone(),
-            network_state.clone(),
-            transactions_fetcher,
+    // Start ledger sync thread unless running in offline mode, or another
+    // process owns the ledger.
+    let ledger_sync_service_thread =
+        if config.offline || config.ledger_db_config.ledger_db_read_only {
+            None
+        } else {
+            Some(LedgerSyncServiceThread::new(
+                ledger_db.clone(),
+                peer_manager.clone(),
+                network_state.clone(),
+                transactions_fetcher,
+                config.poll_interval,
+                logger.clone(),
+            ))
+        };
+    let read_only_ledger_thread = if config.ledger_db_config.ledger_db_read_only {
+        Some(ReadOnlyLedgerThread::new(
             config.poll_interval,
+            ledger_db.clone(),
+            if config.offline {
+                None
+            } else {
+                Some(network_state.clone())
+            },
             logger.clone(),
         ))
+    } else {
+        None
     };
 
+    let service_config = config
+        .get_wallet_service_config()
+        .expect("Failed configuring the wallet service");
     let service = WalletService::new(
         wallet_db,
         ledger_db,
         peer_manager,
         network_state,
         config.get_fog_resolver_factory(logger.clone()),
-        config.offline,
+        service_config,
         logger,
     );
-    let state = WalletState { service };
-
-    let rocket = consensus_backed_rocket(rocket_config, state);
-    let api_key = env::var("MC_API_KEY").unwrap_or_default();
-    rocket.manage(APIKeyState(api_key)).launch();
+    if let Err(e) = service.backfill_txo_global_indices() {
+        log::error!(
+            service.logger,
+            "Could not start backfilling txo global indices: {}",
+            e
+        );
+    }
+    let service = Arc::new(service);
+    let auto_rebuild_thread = if config.offline {
+        None
+    } else {
+        Some(AutoRebuildThread::start(service.clone()))
+    };
+    handle_shutdown_signals(service.clone(), config.shutdown_grace_period, move || {
+        drop(auto_rebuild_thread);
+        drop(ledger_sync_service_thread);
+        drop(read_only_ledger_thread);
+    });
+    launch_listeners(config, service, consensus_backed_rocket);
 }
 
 fn validator_backed_full_service(
     validator_uri: &ValidatorUri,
     config: &APIConfig,
     wallet_db: WalletDb,
-    rocket_config: rocket::Config,
     logger: Logger,
 ) {
     let validator_conn = ValidatorConnection::new(validator_uri, logger.clone());
@@ -209,17 +264,37 @@ fn validator_backed_full_service(
         logger.clone(),
     )));
 
-    // Create the ledger sync thread.
-    let _ledger_sync_thread = ValidatorLedgerSyncThread::new(
-        validator_uri,
-        config.poll_interval,
-        ledger_db.clone(),
-        network_state.clone(),
-        logger.clone(),
-    );
+    // Create the ledger sync thread, or follow the ledger if another process
+    // owns it.
+    let (ledger_sync_thread, read_only_ledger_thread) =
+        if config.ledger_db_config.ledger_db_read_only {
+            let read_only_ledger_thread = ReadOnlyLedgerThread::new(
+                config.poll_interval,
+                ledger_db.clone(),
+                Some(network_state.clone()),
+                logger.clone(),
+            );
+            (None, Some(read_only_ledger_thread))
+        } else {
+            let ledger_sync_thread = ValidatorLedgerSyncThread::new(
+                validator_uri,
+                config.poll_interval,
+                ledger_db.clone(),
+                network_state.clone(),
+                logger.clone(),
+            );
+            (Some(ledger_sync_thread), None)
+        };
 
     let fog_ingest_verifier = config.get_fog_ingest_verifier();
     let logger2 = logger.clone();
+    let service_config = WalletServiceConfig {
+        offline: false,
+        validator_conn: Some(validator_conn.clone()),
+        ..config
+            .get_wallet_service_config()
+            .expect("Failed configuring the wallet service")
+    };
     let service = WalletService::new(
         wallet_db,
         ledger_db,
@@ -248,12 +323,118 @@ fn validator_backed_full_service(
                 )
             }
         }),
-        false,
+        service_config,
         logger,
     );
-    let state = WalletState { service };
+    if let Err(e) = service.backfill_txo_global_indices() {
+        log::error!(
+            service.logger,
+            "Could not start backfilling txo global indices: {}",
+            e
+        );
+    }
+    let service = Arc::new(service);
+    let auto_rebuild_thread = AutoRebuildThread::start(service.clone());
+    handle_shutdown_signals(service.clone(), config.shutdown_grace_period, move || {
+        drop(auto_rebuild_thread);
+        drop(ledger_sync_thread);
+        drop(read_only_ledger_thread);
+    });
+    launch_listeners(config, service, validator_backed_rocket);
+}
 
-    let rocket = validator_backed_rocket(rocket_config, state);
+/// Serve the JSON RPC API on every configured listener, each only serving the
+/// methods of its capabilities. The listener on listen-host and listen-port is
+/// served on the calling thread, and the others on their own threads.
+fn launch_listeners<T, FPR>(
+    config: &APIConfig,
+    service: Arc<WalletService<T, FPR>>,
+    build_rocket: fn(rocket::Config, WalletState<T, FPR>) -> rocket::Rocket,
+) where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
     let api_key = env::var("MC_API_KEY").unwrap_or_default();
-    rocket.manage(APIKeyState(api_key)).launch();
+    let secrets_api_key = env::var("MC_SECRETS_API_KEY").unwrap_or_default();
+    let scoped_api_keys = config
+        .get_scoped_api_keys()
+        .expect("Failed loading API keys");
+    if !scoped_api_keys.is_empty() && api_key.is_empty() {
+        log::warn!(
+            service.logger,
+            "API keys scoped to accounts are configured, but MC_API_KEY is not set, so every request needs a scoped key"
+        );
+    }
+
+    let mut rockets = config.get_listeners().into_iter().map(|listener| {
+        log::info!(
+            service.logger,
+            "Serving {} on {}:{}",
+            listener.capabilities,
+            listener.host,
+            listener.port
+        );
+        let rocket_config: rocket::Config =
+            rocket::Config::build(rocket::config::Environment::Development)
+                .address(&listener.host)
+                .port(listener.port)
+                .unwrap();
+        let state = WalletState {
+            service: service.clone(),
+            max_batch_size: config.max_batch_size,
+        };
+        build_rocket(rocket_config, state)
+            .manage(APIKeyState(api_key.clone()))
+            .manage(SecretsAPIKeyState(secrets_api_key.clone()))
+            .manage(ListenerCapabilities(listener.capabilities))
+            .manage(ScopedApiKeysState(scoped_api_keys.clone()))
+            .manage(config.get_health_config())
+            .manage(config.get_request_size_limits())
+    });
+    let main_rocket = rockets.next().expect("There is always a main listener");
+
+    for rocket in rockets {
+        let logger = service.logger.clone();
+        thread::Builder::new()
+            .name("listener".to_string())
+            .spawn(move || {
+                let err = rocket.launch();
+                log::error!(logger, "Could not launch listener: {}", err);
+            })
+            .expect("Could not spawn listener thread");
+    }
+
+    main_rocket.launch();
+}
+
+/// Shut down gracefully on SIGTERM or SIGINT: turn away new requests, stop the
+/// background threads with `stop_background_threads`, wait up to the grace
+/// period for the requests in flight, and exit. Rocket has no way to stop
+/// serving once launched, so the process exits from the signal thread.
+fn handle_shutdown_signals<T, FPR>(
+    service: Arc<WalletService<T, FPR>>,
+    grace_period: Duration,
+    stop_background_threads: impl FnOnce() + Send + 'static,
+) where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let mut signals =
+        Signals::new(&[SIGTERM, SIGINT]).expect("Could not register shutdown signal handlers");
+    thread::Builder::new()
+        .name("shutdown".to_string())
+        .spawn(move || {
+            if let Some(signal) = signals.forever().next() {
+                log::info!(service.logger, "Received signal {}", signal);
+                service.shutdown_coordinator.begin_shutdown();
+                stop_background_threads();
+                let code = if service.shutdown(grace_period) {
+                    0
+                } else {
+                    EXIT_SHUTDOWN_TIMED_OUT
+                };
+                exit(code);
+            }
+        })
+        .expect("Could not spawn shutdown thread");
 }
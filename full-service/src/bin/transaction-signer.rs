@@ -3,7 +3,10 @@ use mc_account_keys::AccountKey;
 use mc_account_keys_slip10::Slip10Key;
 use mc_common::HashMap;
 use mc_full_service::{
-    db::{account::AccountID, txo::TxoID},
+    db::{
+        account::{AccountID, AccountKeySource},
+        txo::TxoID,
+    },
     fog_resolver::FullServiceFogResolver,
     json_rpc::{
         json_rpc_request::JsonRPCRequest,
@@ -121,6 +124,12 @@ fn create_account(name: &str, mnemonic: Option<&str>) {
         account_key: Some(AccountKeyJSON::from(&account_key)),
         name: name.to_string(),
         view_account_key: None,
+        key_source: AccountKeySource::Created.to_string(),
+        exportable_secrets: AccountKeySource::Created
+            .exportable_secrets()
+            .iter()
+            .map(|secret| secret.to_string())
+            .collect(),
     };
 
     // Write secret mnemonic to file.
@@ -153,6 +162,7 @@ fn generate_view_only_import_package(secret_mnemonic: &str) {
         name: None,
         first_block_index: None,
         next_subaddress_index: None,
+        force: None,
     };
 
     // Write view private key and associated info to file.
@@ -255,11 +265,15 @@ fn sign_transaction(secret_mnemonic: &str, sign_request: &str) {
     .unwrap();
 
     let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
-    let tx_proposal_json = TxProposalJSON::try_from(&tx_proposal).unwrap();
+    let tx_proposal_json = TxProposalJSON::try_from(&tx_proposal)
+        .unwrap_or_else(|e| panic!("Could not encode signed transaction: {}", e));
     let json_command_request = JsonCommandRequest::submit_transaction {
         tx_proposal: tx_proposal_json,
         comment: None,
         account_id: Some(account_id.to_string()),
+        idempotency_key: None,
+        dry_run: None,
+        force_resubmit: None,
     };
 
     let filename = format!(
@@ -2,6 +2,24 @@
 
 //! Config definition and processing for Wallet Service.
 
+use crate::{
+    db::{ConnectionOptions, JournalMode, MigrationOptions, Synchronous, MAX_WRITE_ATTEMPTS},
+    json_rpc::{
+        capability::CapabilitySet,
+        request_limits::RequestSizeLimits,
+        scoped_api_key::{parse_scoped_api_keys, ScopedApiKey},
+    },
+    ledger_bootstrap::LedgerSnapshot,
+    service::{
+        address::AddressNetworkPolicy,
+        health::HealthConfig,
+        peer::PeerSelectionStrategy,
+        sync::SyncConfig,
+        transaction_report::{FileReportSink, HttpReportSink, ReportSink, TransactionReporter},
+        WalletServiceConfig,
+    },
+    util::build_guard::BuildGuard,
+};
 use mc_attest_verifier::{MrSignerVerifier, Verifier, DEBUG_ENCLAVE};
 use mc_blockchain_types::BlockData;
 use mc_common::{
@@ -14,13 +32,15 @@ use mc_fog_report_connection::GrpcFogReportConnection;
 use mc_fog_report_validation::FogResolver;
 use mc_ledger_db::{Ledger, LedgerDB};
 use mc_sgx_css::Signature;
+use mc_transaction_core::TokenId;
 use mc_util_parse::parse_duration_in_seconds;
 use mc_util_uri::{ConnectionUri, ConsensusClientUri, FogUri};
 use mc_validator_api::ValidatorUri;
 
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
-    fs,
+    env, fs,
     path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
@@ -38,10 +58,61 @@ pub struct APIConfig {
     #[structopt(long, default_value = "9090")]
     pub listen_port: u16,
 
+    /// Capabilities granted by the listener on listen-host and listen-port, as
+    /// a comma separated list of read, build, submit and admin, or all.
+    #[structopt(long, default_value = "all")]
+    pub listen_capabilities: CapabilitySet,
+
+    /// Additional listener for the JSON RPC API, given as
+    /// host:port=capabilities, such as 127.0.0.1:9091=read,build. May be
+    /// repeated.
+    #[structopt(long = "listener", parse(try_from_str=parse_listener), number_of_values = 1)]
+    pub listeners: Vec<ListenerConfig>,
+
+    /// JSON file of API keys scoped to some accounts and capabilities, as a
+    /// list of {"key", "capabilities", "account_ids"} objects. Keys with the
+    /// admin capability reach every account.
+    #[structopt(long, parse(from_os_str))]
+    pub api_keys_file: Option<PathBuf>,
+
     /// Path to WalletDb.
     #[structopt(long, parse(from_os_str))]
     pub wallet_db: PathBuf,
 
+    /// Refuse to start if the wallet db has pending migrations, instead of
+    /// running them.
+    #[structopt(long)]
+    pub skip_migrations: bool,
+
+    /// Number of copies of the wallet db made before running migrations to
+    /// keep, next to the wallet db. 0 disables the copies.
+    #[structopt(long, default_value = "3")]
+    pub migration_backups: usize,
+
+    /// Journal mode of the wallet db, wal or delete. The write-ahead log lets
+    /// the sync thread write while requests read.
+    #[structopt(long, default_value = "wal")]
+    pub wallet_db_journal_mode: JournalMode,
+
+    /// How often the wallet db waits for writes to reach the disk: off,
+    /// normal, full or extra.
+    #[structopt(long, default_value = "normal")]
+    pub wallet_db_synchronous: Synchronous,
+
+    /// How many seconds a wallet db statement waits for a lock held by
+    /// another connection before failing. 0 fails immediately.
+    #[structopt(long, default_value = "30", parse(try_from_str=parse_duration_in_seconds))]
+    pub wallet_db_busy_timeout: Duration,
+
+    /// Size of the page cache of each wallet db connection, in KiB.
+    #[structopt(long, default_value = "8192")]
+    pub wallet_db_cache_size_kib: u64,
+
+    /// Number of times starting a wallet db write is attempted while the
+    /// database is locked, backing off between attempts. Between 1 and 100.
+    #[structopt(long, default_value = "5", parse(try_from_str=parse_wallet_db_write_attempts))]
+    pub wallet_db_write_attempts: u32,
+
     #[structopt(flatten)]
     pub ledger_db_config: LedgerDbConfig,
 
@@ -65,6 +136,212 @@ pub struct APIConfig {
     /// network directly.
     #[structopt(long)]
     pub validator: Option<ValidatorUri>,
+
+    /// How many seconds an idempotency key is honored after its first use.
+    #[structopt(long, default_value = "86400", parse(try_from_str=parse_duration_in_seconds))]
+    pub idempotency_key_ttl: Duration,
+
+    /// How many seconds a shutdown on SIGTERM or SIGINT waits for requests in
+    /// flight to finish.
+    #[structopt(long, default_value = "30", parse(try_from_str=parse_duration_in_seconds))]
+    pub shutdown_grace_period: Duration,
+
+    /// Maximum number of transaction builds in progress across all accounts.
+    #[structopt(long, default_value = "8")]
+    pub max_concurrent_builds: usize,
+
+    /// Maximum number of transaction builds waiting behind the build in
+    /// progress for an account, after which requests are rejected as busy.
+    #[structopt(long, default_value = "4")]
+    pub max_queued_builds_per_account: usize,
+
+    /// Longest a transaction build waits for the other builds ahead of it,
+    /// in seconds, after which the request is rejected as busy.
+    #[structopt(long, default_value = "30", parse(try_from_str=parse_duration_in_seconds))]
+    pub build_wait_timeout: Duration,
+
+    /// Allow debug reports of transaction builds to be requested through the
+    /// API, for attaching to support tickets.
+    #[structopt(long)]
+    pub enable_debug_build_report: bool,
+
+    /// Maximum number of requests in a JSON RPC batch.
+    #[structopt(long, default_value = "50")]
+    pub max_batch_size: usize,
+
+    /// Largest request body accepted by routes without their own limit, in
+    /// bytes.
+    #[structopt(long, default_value = "1048576")]
+    pub request_size_limit: u64,
+
+    /// Largest request body accepted by the /wallet and /wallet/v2 routes, in
+    /// bytes. The default fits several TxProposals with 16 inputs.
+    #[structopt(long, default_value = "16777216")]
+    pub wallet_request_size_limit: u64,
+
+    /// Largest request body accepted by a route, given as path=bytes, such as
+    /// /wallet/v2/stream=65536. May be repeated.
+    #[structopt(long = "route-request-size-limit", parse(try_from_str=parse_route_request_size_limit), number_of_values = 1)]
+    pub route_request_size_limits: Vec<(String, u64)>,
+
+    /// Report submitted transactions paying at least this value of a token,
+    /// given as token_id:value in the smallest unit of the token. May be
+    /// repeated for multiple tokens. Requires a report sink.
+    #[structopt(long = "report-threshold", parse(try_from_str=parse_token_threshold), number_of_values = 1)]
+    pub report_thresholds: Vec<(TokenId, u64)>,
+
+    /// URL to POST transaction reports to, as JSON. Deliveries are signed
+    /// with HMAC-SHA256 when the MC_REPORT_SINK_SECRET environment variable is
+    /// set.
+    #[structopt(long, conflicts_with = "report-sink-file")]
+    pub report_sink_url: Option<String>,
+
+    /// File to append transaction reports to, one JSON object per line.
+    #[structopt(long, parse(from_os_str))]
+    pub report_sink_file: Option<PathBuf>,
+
+    /// Smallest value of an output of a token the wallet will build, given as
+    /// token_id:value in the smallest unit of the token. Defaults to the
+    /// network fee of the token. May be repeated for multiple tokens.
+    #[structopt(long = "dust-threshold", parse(try_from_str=parse_token_threshold), number_of_values = 1)]
+    pub dust_thresholds: Vec<(TokenId, u64)>,
+
+    /// Number of blocks a received txo must be in the ledger for before it
+    /// can be spent, for accounts which do not set their own. The txo's own
+    /// block counts as the first confirmation.
+    #[structopt(long, default_value = "0")]
+    pub min_confirmations: u64,
+
+    /// The network the wallet is connected to, such as mainnet or testnet.
+    /// Recipient addresses with fog are checked against the fog domains of
+    /// the network before building transactions to them.
+    #[structopt(long)]
+    pub network: Option<String>,
+
+    /// Domain of the fog report urls of addresses on the network. Replaces
+    /// the known fog domains of mainnet and testnet. May be repeated.
+    #[structopt(long = "allowed-fog-domain", number_of_values = 1)]
+    pub allowed_fog_domains: Vec<String>,
+
+    /// b58 address, or the start of one, which always passes the network
+    /// check. May be repeated.
+    #[structopt(long = "address-allowlist", number_of_values = 1)]
+    pub address_allowlist: Vec<String>,
+
+    /// b58 address, or the start of one, which always fails the network
+    /// check. May be repeated.
+    #[structopt(long = "address-denylist", number_of_values = 1)]
+    pub address_denylist: Vec<String>,
+
+    /// Number of blocks synced for an account in each database transaction.
+    #[structopt(long, default_value = "1000", parse(try_from_str=parse_sync_batch_size))]
+    pub sync_batch_size: u64,
+
+    /// Number of threads matching new blocks against account view keys.
+    /// Defaults to one per core.
+    #[structopt(long, default_value = "0")]
+    pub sync_workers: usize,
+
+    /// Longest a fog pubkey validated for a build is reused by later builds,
+    /// in seconds. 0 disables caching, so that every build fetches fog
+    /// reports.
+    #[structopt(long, default_value = "600", parse(try_from_str=parse_duration_in_seconds))]
+    pub fog_pubkey_cache_ttl: Duration,
+
+    /// How the peer a transaction is submitted to is chosen: "healthiest",
+    /// the healthy peer with the lowest latency and fewest failures, or
+    /// "round-robin".
+    #[structopt(long, default_value = "healthiest")]
+    pub peer_selection: PeerSelectionStrategy,
+
+    /// Write logs to stdout as JSON lines, one record per line, rather than
+    /// as text. Secrets are redacted from logs either way.
+    #[structopt(long)]
+    pub log_json: bool,
+
+    /// Longest the health checks wait for a wallet db connection, in seconds.
+    #[structopt(long, default_value = "2", parse(try_from_str=parse_duration_in_seconds))]
+    pub health_timeout: Duration,
+
+    /// Most blocks the ledger may be behind the network for /health/ready to
+    /// report the wallet ready.
+    #[structopt(long, default_value = "10")]
+    pub ready_max_ledger_lag: u64,
+
+    /// Longest since a peer or the validator last responded for /health/ready
+    /// to report the wallet ready, in seconds.
+    #[structopt(long, default_value = "60", parse(try_from_str=parse_duration_in_seconds))]
+    pub ready_peer_response_window: Duration,
+}
+
+/// A listener of the JSON RPC API, and the capabilities it grants.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListenerConfig {
+    pub host: String,
+    pub port: u16,
+    pub capabilities: CapabilitySet,
+}
+
+fn parse_listener(src: &str) -> Result<ListenerConfig, String> {
+    let (address, capabilities) = src
+        .split_once('=')
+        .ok_or_else(|| format!("Expected host:port=capabilities, got {}", src))?;
+    let (host, port) = address
+        .rsplit_once(':')
+        .ok_or_else(|| format!("Expected host:port, got {}", address))?;
+    let port = port
+        .parse::<u16>()
+        .map_err(|err| format!("Invalid listener port {}: {}", port, err))?;
+    Ok(ListenerConfig {
+        host: host.to_string(),
+        port,
+        capabilities: capabilities.parse()?,
+    })
+}
+
+fn parse_token_threshold(src: &str) -> Result<(TokenId, u64), String> {
+    let (token_id, value) = src
+        .split_once(':')
+        .ok_or_else(|| format!("Expected token_id:value, got {}", src))?;
+    let token_id = token_id
+        .parse::<u64>()
+        .map_err(|err| format!("Invalid token id {}: {}", token_id, err))?;
+    let value = value
+        .parse::<u64>()
+        .map_err(|err| format!("Invalid threshold value {}: {}", value, err))?;
+    Ok((TokenId::from(token_id), value))
+}
+
+fn parse_route_request_size_limit(src: &str) -> Result<(String, u64), String> {
+    let (path, limit) = src
+        .split_once('=')
+        .ok_or_else(|| format!("Expected path=bytes, got {}", src))?;
+    if !path.starts_with('/') {
+        return Err(format!("Route path must start with /, got {}", path));
+    }
+    let limit = limit
+        .parse::<u64>()
+        .map_err(|err| format!("Invalid request size limit {}: {}", limit, err))?;
+    Ok((path.to_string(), limit))
+}
+
+fn parse_sync_batch_size(src: &str) -> Result<u64, String> {
+    match src.parse::<u64>() {
+        Ok(0) => Err("Sync batch size must be at least 1".to_string()),
+        Ok(batch_size) => Ok(batch_size),
+        Err(err) => Err(format!("Invalid sync batch size {}: {}", src, err)),
+    }
+}
+
+fn parse_wallet_db_write_attempts(src: &str) -> Result<u32, String> {
+    match src.parse::<u32>() {
+        Ok(attempts) if (1..=MAX_WRITE_ATTEMPTS).contains(&attempts) => Ok(attempts),
+        Ok(_) => Err(format!(
+            "Wallet db write attempts must be between 1 and {}",
+            MAX_WRITE_ATTEMPTS
+        )),
+        Err(err) => Err(format!("Invalid wallet db write attempts {}: {}", src, err)),
+    }
 }
 
 fn parse_quorum_set_from_json(src: &str) -> Result<QuorumSet<ResponderId>, String> {
@@ -87,6 +364,139 @@ fn load_css_file(filename: &str) -> Result<Signature, String> {
 }
 
 impl APIConfig {
+    /// Get every listener of the JSON RPC API, starting with the one on
+    /// listen-host and listen-port.
+    pub fn get_listeners(&self) -> Vec<ListenerConfig> {
+        let mut listeners = vec![ListenerConfig {
+            host: self.listen_host.clone(),
+            port: self.listen_port,
+            capabilities: self.listen_capabilities.clone(),
+        }];
+        listeners.extend(self.listeners.iter().cloned());
+        listeners
+    }
+
+    /// Get the policy for checking that recipient addresses are on the
+    /// wallet's network.
+    pub fn get_address_network_policy(&self) -> AddressNetworkPolicy {
+        AddressNetworkPolicy::new(
+            self.network.clone(),
+            self.allowed_fog_domains.clone(),
+            self.address_allowlist.clone(),
+            self.address_denylist.clone(),
+        )
+    }
+
+    /// Get how the wallet db is brought up to date at startup.
+    pub fn get_migration_options(&self) -> MigrationOptions {
+        MigrationOptions {
+            skip_migrations: self.skip_migrations,
+            backup_retention: self.migration_backups,
+        }
+    }
+
+    /// Get the SQLite settings of the connections to the wallet db.
+    pub fn get_connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions {
+            journal_mode: self.wallet_db_journal_mode,
+            synchronous: self.wallet_db_synchronous,
+            enable_foreign_keys: true,
+            busy_timeout: Some(self.wallet_db_busy_timeout)
+                .filter(|busy_timeout| !busy_timeout.is_zero()),
+            cache_size_kib: Some(self.wallet_db_cache_size_kib),
+        }
+    }
+
+    /// Get how the sync thread processes blocks.
+    pub fn get_sync_config(&self) -> SyncConfig {
+        SyncConfig {
+            batch_size: self.sync_batch_size,
+            num_workers: self.sync_workers,
+            ..Default::default()
+        }
+    }
+
+    /// Get the settings of the wallet service.
+    pub fn get_wallet_service_config(&self) -> Result<WalletServiceConfig, String> {
+        Ok(WalletServiceConfig {
+            offline: self.offline,
+            ledger_db_path: Some(self.ledger_db_config.ledger_db.clone()),
+            validator_conn: None,
+            ledger_read_only: self.ledger_db_config.ledger_db_read_only,
+            idempotency_key_ttl: self.idempotency_key_ttl,
+            build_guard: BuildGuard::new(
+                self.max_concurrent_builds,
+                self.max_queued_builds_per_account,
+                self.build_wait_timeout,
+            ),
+            debug_build_report_enabled: self.enable_debug_build_report,
+            transaction_reporter: self.get_transaction_reporter()?,
+            dust_threshold_overrides: self.dust_thresholds.iter().copied().collect(),
+            min_confirmations: self.min_confirmations,
+            address_network_policy: self.get_address_network_policy(),
+            sync_config: self.get_sync_config(),
+            fog_pubkey_cache_ttl: self.fog_pubkey_cache_ttl,
+            peer_selection_strategy: self.peer_selection,
+        })
+    }
+
+    /// Get the thresholds of the liveness and readiness checks.
+    pub fn get_health_config(&self) -> HealthConfig {
+        HealthConfig {
+            timeout: self.health_timeout,
+            max_ledger_lag: self.ready_max_ledger_lag,
+            peer_response_window: self.ready_peer_response_window,
+        }
+    }
+
+    /// Get the largest request body accepted by each route.
+    pub fn get_request_size_limits(&self) -> RequestSizeLimits {
+        RequestSizeLimits::new(
+            self.request_size_limit,
+            self.wallet_request_size_limit,
+            &self.route_request_size_limits,
+        )
+    }
+
+    /// Get the API keys scoped to some accounts and capabilities, from the API
+    /// keys file if one is configured.
+    pub fn get_scoped_api_keys(&self) -> Result<Vec<ScopedApiKey>, String> {
+        match &self.api_keys_file {
+            Some(path) => {
+                let src = fs::read_to_string(path)
+                    .map_err(|err| format!("Failed reading file '{}': {}", path.display(), err))?;
+                parse_scoped_api_keys(&src)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Get the reporter for submitted transactions, or None if no thresholds
+    /// are configured.
+    pub fn get_transaction_reporter(&self) -> Result<Option<TransactionReporter>, String> {
+        if self.report_thresholds.is_empty() {
+            return Ok(None);
+        }
+
+        let sink: Arc<dyn ReportSink> = match (&self.report_sink_url, &self.report_sink_file) {
+            (Some(url), _) => Arc::new(HttpReportSink::new(
+                url,
+                env::var("MC_REPORT_SINK_SECRET")
+                    .ok()
+                    .filter(|secret| !secret.is_empty()),
+            )?),
+            (None, Some(path)) => Arc::new(FileReportSink::new(path.clone())),
+            (None, None) => {
+                return Err(
+                    "--report-threshold requires --report-sink-url or --report-sink-file"
+                        .to_string(),
+                )
+            }
+        };
+        let thresholds: BTreeMap<TokenId, u64> = self.report_thresholds.iter().cloned().collect();
+        Ok(Some(TransactionReporter::new(thresholds, sink)))
+    }
+
     /// Get the attestation verifier used to verify fog reports when sending to
     /// fog recipients.
     pub fn get_fog_ingest_verifier(&self) -> Option<Verifier> {
@@ -252,15 +662,48 @@ pub struct LedgerDbConfig {
     /// initializing new ledger dbs.
     #[structopt(long)]
     pub ledger_db_bootstrap: Option<String>,
+
+    /// URL of a trusted snapshot of the ledger, as a gzip compressed data.mdb,
+    /// used when initializing new ledger dbs. An interrupted download is
+    /// resumed when the wallet is restarted.
+    #[structopt(long, conflicts_with = "ledger-db-bootstrap", requires_all = &["ledger-snapshot-sha256", "ledger-snapshot-block-id"])]
+    pub ledger_snapshot_url: Option<String>,
+
+    /// Hex encoded SHA-256 hash of the ledger snapshot.
+    #[structopt(long, requires = "ledger-snapshot-url")]
+    pub ledger_snapshot_sha256: Option<String>,
+
+    /// Hex encoded ID of the latest block in the ledger snapshot.
+    #[structopt(long, requires = "ledger-snapshot-url")]
+    pub ledger_snapshot_block_id: Option<String>,
+
+    /// Open the ledger db read-only, for a ledger which another process, such
+    /// as mobilecoind or another full-service, owns and keeps in sync. The
+    /// ledger is not migrated, bootstrapped or synced by this wallet.
+    #[structopt(long, conflicts_with_all = &["ledger-db-bootstrap", "ledger-snapshot-url"])]
+    pub ledger_db_read_only: bool,
 }
 
 impl LedgerDbConfig {
+    /// The trusted ledger snapshot, if one is configured.
+    pub fn ledger_snapshot(&self) -> Option<LedgerSnapshot> {
+        Some(LedgerSnapshot {
+            url: self.ledger_snapshot_url.clone()?,
+            sha256: self.ledger_snapshot_sha256.clone()?,
+            block_id: self.ledger_snapshot_block_id.clone()?,
+        })
+    }
+
     pub fn create_or_open_ledger_db(
         &self,
         get_origin_block_and_transactions: impl Fn() -> Result<BlockData, String>,
         offline: bool,
         logger: &Logger,
     ) -> LedgerDB {
+        if self.ledger_db_read_only {
+            return self.open_read_only_ledger_db(logger);
+        }
+
         let ledger_db_file = Path::new(&self.ledger_db).join("data.mdb");
 
         // Attempt to run migrations if ledger is available.
@@ -285,10 +728,26 @@ impl LedgerDbConfig {
             }
         }
 
-        // Ledger doesn't exist, or is empty. Copy a bootstrapped ledger or try and get
-        // it from the network.
-        match &self.ledger_db_bootstrap {
-            Some(ledger_db_bootstrap) => {
+        // Ledger doesn't exist, or is empty. Download a snapshot, copy a bootstrapped
+        // ledger or try and get it from the network.
+        match (self.ledger_snapshot(), &self.ledger_db_bootstrap) {
+            (Some(ledger_snapshot), _) => {
+                log::info!(
+                    logger,
+                    "Ledger DB {:?} does not exist, bootstrapping from snapshot {}",
+                    self.ledger_db,
+                    ledger_snapshot.url
+                );
+                ledger_snapshot
+                    .download_and_unpack(&self.ledger_db, logger)
+                    .unwrap_or_else(|e| panic!("Failed bootstrapping ledger from snapshot: {}", e));
+                mc_ledger_migration::migrate(&self.ledger_db, logger);
+                ledger_snapshot
+                    .verify_latest_block(&self.ledger_db)
+                    .unwrap_or_else(|e| panic!("Failed verifying ledger snapshot: {}", e));
+                log::info!(logger, "Bootstrapping completed!");
+            }
+            (None, Some(ledger_db_bootstrap)) => {
                 log::debug!(
                     logger,
                     "Ledger DB {:?} does not exist, copying from {}",
@@ -313,7 +772,7 @@ impl LedgerDbConfig {
                     )
                 });
             }
-            None => {
+            (None, None) => {
                 std::fs::create_dir_all(self.ledger_db.clone())
                     .expect("Could not create ledger dir");
                 LedgerDB::create(&self.ledger_db).expect("Could not create ledger_db");
@@ -355,4 +814,37 @@ impl LedgerDbConfig {
 
         ledger_db
     }
+
+    /// Open a ledger db which another process owns. It is neither migrated nor
+    /// bootstrapped, as both would write to it, so it must already have
+    /// blocks.
+    fn open_read_only_ledger_db(&self, logger: &Logger) -> LedgerDB {
+        log::debug!(logger, "Opening read-only Ledger DB {:?}", self.ledger_db);
+        let ledger_db = LedgerDB::open(&self.ledger_db).unwrap_or_else(|_| {
+            panic!(
+                "Could not open read-only ledger db inside {:?}",
+                self.ledger_db
+            )
+        });
+
+        let num_blocks = ledger_db
+            .num_blocks()
+            .expect("Failed getting number of blocks");
+        if num_blocks == 0 {
+            panic!(
+                "Ledger DB {:?} is read-only and has no blocks. It must be created and synced by the process which owns it.",
+                self.ledger_db
+            );
+        }
+
+        log::info!(
+            logger,
+            "Ledger DB {:?} opened read-only: num_blocks={} num_txos={}",
+            self.ledger_db,
+            num_blocks,
+            ledger_db.num_txos().expect("Failed getting number of txos")
+        );
+
+        ledger_db
+    }
 }
@@ -5,32 +5,192 @@
 use crate::{
     db::{
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, AssignedSubaddress, NewAccount, TransactionLog, Txo},
+        idempotency_key::IdempotencyKeyModel,
+        models::{Account, AssignedSubaddress, IdempotencyKey, NewAccount, TransactionLog, Txo},
         transaction_log::TransactionLogModel,
         txo::TxoModel,
         Conn, WalletDbError,
     },
-    util::constants::{
-        DEFAULT_FIRST_BLOCK_INDEX, DEFAULT_NEXT_SUBADDRESS_INDEX, LEGACY_CHANGE_SUBADDRESS_INDEX,
-        MNEMONIC_KEY_DERIVATION_VERSION, ROOT_ENTROPY_KEY_DERIVATION_VERSION,
+    util::{
+        constants::{
+            DEFAULT_FIRST_BLOCK_INDEX, DEFAULT_NEXT_SUBADDRESS_INDEX,
+            LEGACY_CHANGE_SUBADDRESS_INDEX, MNEMONIC_KEY_DERIVATION_VERSION,
+            ROOT_ENTROPY_KEY_DERIVATION_VERSION,
+        },
+        encoding_helpers::is_subject_public_key_info,
     },
 };
 
 use bip39::Mnemonic;
-use diesel::prelude::*;
+use diesel::{prelude::*, sql_types::BigInt};
 use mc_account_keys::{
     AccountKey, PublicAddress, RootEntropy, RootIdentity, ViewAccountKey, CHANGE_SUBADDRESS_INDEX,
-    DEFAULT_SUBADDRESS_INDEX,
+    DEFAULT_SUBADDRESS_INDEX, GIFT_CODE_SUBADDRESS_INDEX,
 };
 use mc_account_keys_slip10::Slip10Key;
 use mc_crypto_digestible::{Digestible, MerlinTranscript};
 use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
 use mc_transaction_core::TokenId;
-use std::fmt;
+use mc_util_uri::FogUri;
+use std::{fmt, str::FromStr};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct AccountID(pub String);
 
+/// The number of accounts in each stage of sync, relative to a block height.
+#[derive(Debug, Default, Clone, PartialEq, Eq, QueryableByName)]
+pub struct AccountSyncCounts {
+    /// Accounts which have processed every block.
+    #[sql_type = "BigInt"]
+    pub synced: i64,
+
+    /// Accounts with blocks left to process, but no more than the threshold.
+    #[sql_type = "BigInt"]
+    pub syncing: i64,
+
+    /// Accounts with more blocks left to process than the threshold.
+    #[sql_type = "BigInt"]
+    pub behind: i64,
+}
+
+/// A subaddress index which the wallet reserves for its own use, rather than
+/// assigning it as an address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservedSubaddress {
+    /// The main address, which is not assigned to a single sender.
+    Main,
+
+    /// Where accounts without fog sent change before the change subaddress
+    /// was introduced.
+    LegacyChange,
+
+    /// Where change is sent.
+    Change,
+
+    /// Where gift codes are funded from.
+    GiftCode,
+}
+
+impl ReservedSubaddress {
+    /// The subaddress index of the reserved slot.
+    pub fn index(&self) -> u64 {
+        match self {
+            Self::Main => DEFAULT_SUBADDRESS_INDEX,
+            Self::LegacyChange => LEGACY_CHANGE_SUBADDRESS_INDEX,
+            Self::Change => CHANGE_SUBADDRESS_INDEX,
+            Self::GiftCode => GIFT_CODE_SUBADDRESS_INDEX,
+        }
+    }
+}
+
+impl fmt::Display for ReservedSubaddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Main => write!(f, "main"),
+            Self::LegacyChange => write!(f, "legacy_change"),
+            Self::Change => write!(f, "change"),
+            Self::GiftCode => write!(f, "gift_code"),
+        }
+    }
+}
+
+/// How the keys of an account entered the wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountKeySource {
+    /// Created by this wallet from a new mnemonic.
+    Created,
+
+    /// Imported from a mnemonic.
+    ImportedMnemonic,
+
+    /// Imported from legacy root entropy.
+    ImportedKeys,
+
+    /// Imported from a view private key and spend public key. The wallet can
+    /// not spend from it.
+    ViewOnlyImport,
+
+    /// Held by a hardware wallet, which signs its transactions. The wallet
+    /// only holds its view key.
+    Hardware,
+}
+
+impl AccountKeySource {
+    /// The source of an account which predates the recording of sources, as
+    /// the migration which added them inferred it. Accounts created by the
+    /// wallet start syncing at the network height, which imported accounts
+    /// are never past unless told to.
+    pub fn infer(
+        view_only: bool,
+        key_derivation_version: i32,
+        first_block_index: i64,
+        import_block_index: Option<i64>,
+    ) -> Self {
+        if view_only {
+            Self::ViewOnlyImport
+        } else if key_derivation_version == ROOT_ENTROPY_KEY_DERIVATION_VERSION as i32 {
+            Self::ImportedKeys
+        } else if import_block_index.map_or(true, |i| first_block_index >= i) {
+            Self::Created
+        } else {
+            Self::ImportedMnemonic
+        }
+    }
+
+    /// Whether the wallet holds the spend key of accounts from this source,
+    /// and can sign their transactions.
+    pub fn can_sign_locally(&self) -> bool {
+        match self {
+            Self::Created | Self::ImportedMnemonic | Self::ImportedKeys => true,
+            Self::ViewOnlyImport | Self::Hardware => false,
+        }
+    }
+
+    /// The secrets of accounts from this source which the wallet holds, and
+    /// can export.
+    pub fn exportable_secrets(&self) -> &'static [&'static str] {
+        match self {
+            Self::Created | Self::ImportedMnemonic => &["mnemonic", "account_key"],
+            Self::ImportedKeys => &["entropy", "account_key"],
+            Self::ViewOnlyImport | Self::Hardware => &["view_account_key"],
+        }
+    }
+}
+
+impl fmt::Display for AccountKeySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Created => write!(f, "created"),
+            Self::ImportedMnemonic => write!(f, "imported_mnemonic"),
+            Self::ImportedKeys => write!(f, "imported_keys"),
+            Self::ViewOnlyImport => write!(f, "view_only_import"),
+            Self::Hardware => write!(f, "hardware"),
+        }
+    }
+}
+
+impl FromStr for AccountKeySource {
+    type Err = WalletDbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(Self::Created),
+            "imported_mnemonic" => Ok(Self::ImportedMnemonic),
+            "imported_keys" => Ok(Self::ImportedKeys),
+            "view_only_import" => Ok(Self::ViewOnlyImport),
+            "hardware" => Ok(Self::Hardware),
+            _ => Err(WalletDbError::InvalidAccountKeySource(s.to_string())),
+        }
+    }
+}
+
+impl Account {
+    /// How the keys of this account entered the wallet.
+    pub fn key_source(&self) -> Result<AccountKeySource, WalletDbError> {
+        self.key_source.parse()
+    }
+}
+
 impl From<&AccountKey> for AccountID {
     fn from(src: &AccountKey) -> Self {
         let main_subaddress = src.subaddress(DEFAULT_SUBADDRESS_INDEX);
@@ -113,6 +273,7 @@ pub trait AccountModel {
         next_subaddress_index: Option<u64>,
         name: &str,
         fog_enabled: bool,
+        key_source: AccountKeySource,
         conn: &Conn,
     ) -> Result<(AccountID, String), WalletDbError>;
 
@@ -155,7 +316,23 @@ pub trait AccountModel {
         conn: &Conn,
     ) -> Result<Account, WalletDbError>;
 
-    /// List all accounts.
+    /// Recreate an account from its serialized key material, as recorded in a
+    /// backup. No subaddresses are assigned.
+    #[allow(clippy::too_many_arguments)]
+    fn restore(
+        account_key: &[u8],
+        entropy: Option<&[u8]>,
+        key_derivation_version: u8,
+        first_block_index: u64,
+        import_block_index: Option<u64>,
+        name: &str,
+        fog_enabled: bool,
+        view_only: bool,
+        key_source: AccountKeySource,
+        conn: &Conn,
+    ) -> Result<Account, WalletDbError>;
+
+    /// List all accounts which have not been removed.
     ///
     /// Returns:
     /// * Vector of all Accounts in the DB
@@ -165,12 +342,38 @@ pub trait AccountModel {
         limit: Option<u64>,
     ) -> Result<Vec<Account>, WalletDbError>;
 
-    /// Get a specific account.
+    /// List the accounts which have not been removed and which share the given
+    /// view private key, and so receive the same txos.
+    fn list_by_view_private_key(
+        view_private_key: &RistrettoPrivate,
+        conn: &Conn,
+    ) -> Result<Vec<Account>, WalletDbError>;
+
+    /// Get a specific account. Removed accounts are not found.
     ///
     /// Returns:
     /// * Account
     fn get(account_id: &AccountID, conn: &Conn) -> Result<Account, WalletDbError>;
 
+    /// Get a specific account, whether or not it has been removed.
+    fn get_including_removed(account_id: &AccountID, conn: &Conn)
+        -> Result<Account, WalletDbError>;
+
+    /// List the accounts removed at or before the given cutoff, in seconds
+    /// since the unix epoch.
+    fn list_removed_before(
+        removed_at_cutoff: i64,
+        conn: &Conn,
+    ) -> Result<Vec<Account>, WalletDbError>;
+
+    /// Count the accounts which have not been removed by how many of the
+    /// blocks below the given height they have left to process.
+    fn count_by_sync_state(
+        block_height: u64,
+        behind_threshold: u64,
+        conn: &Conn,
+    ) -> Result<AccountSyncCounts, WalletDbError>;
+
     /// Get the accounts associated with the given Txo.
     fn get_by_txo_id(txo_id_hex: &str, conn: &Conn) -> Result<Vec<Account>, WalletDbError>;
 
@@ -179,6 +382,46 @@ pub trait AccountModel {
     /// adding a new account, and deleting the existing if desired.
     fn update_name(&self, new_name: String, conn: &Conn) -> Result<(), WalletDbError>;
 
+    /// Record the account this legacy account's funds are moved to.
+    fn update_migrated_to_account_id(
+        &self,
+        migrated_to_account_id: &AccountID,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Update the subaddress gap limit for this account.
+    fn update_subaddress_gap_limit(
+        &self,
+        subaddress_gap_limit: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Update the number of outputs the change of each transaction built by
+    /// this account is divided between.
+    fn update_split_change_into(
+        &self,
+        split_change_into: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Update the number of blocks a received txo must be in the ledger for
+    /// before this account can spend it. None uses the wallet default.
+    fn update_min_confirmations(
+        &self,
+        min_confirmations: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Update the fee and tombstone offset used by transactions built by this
+    /// account which do not specify them. None uses the wallet defaults.
+    fn update_transaction_defaults(
+        &self,
+        default_fee_value: Option<u64>,
+        default_fee_token_id: Option<u64>,
+        default_tombstone_offset: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
     /// Update the next block index this account will need to sync.
     fn update_next_block_index(
         &self,
@@ -186,6 +429,29 @@ pub trait AccountModel {
         conn: &Conn,
     ) -> Result<(), WalletDbError>;
 
+    /// Update the index of the first block where this account may have held
+    /// funds.
+    fn update_first_block_index(
+        &self,
+        first_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Update the index of the first block in which sync found a txo received
+    /// by this account.
+    fn update_first_activity_block_index(
+        &self,
+        first_activity_block_index: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Hide an account from the wallet, recording when it was removed. Its
+    /// txos, subaddresses and transaction logs are kept until it is deleted.
+    fn mark_removed(&self, removed_at: i64, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// Return a removed account to the wallet.
+    fn unmark_removed(&self, conn: &Conn) -> Result<(), WalletDbError>;
+
     /// Delete an account.
     fn delete(self, conn: &Conn) -> Result<(), WalletDbError>;
 
@@ -195,6 +461,17 @@ pub trait AccountModel {
     /// Get main public address
     fn main_subaddress(self, conn: &Conn) -> Result<AssignedSubaddress, WalletDbError>;
 
+    /// Get the view private key, for both full and view only accounts.
+    fn view_private_key(&self) -> Result<RistrettoPrivate, WalletDbError>;
+
+    /// Get the public address at a subaddress index, whether or not it has
+    /// been assigned, for both full and view only accounts.
+    fn public_address(&self, subaddress_index: u64) -> Result<PublicAddress, WalletDbError>;
+
+    /// Get the subaddresses reserved for the account. The legacy change
+    /// subaddress is only reserved for accounts without fog.
+    fn reserved_subaddresses(&self) -> Vec<ReservedSubaddress>;
+
     /// Get all of the token ids present for the account
     fn get_token_ids(self, conn: &Conn) -> Result<Vec<TokenId>, WalletDbError>;
 
@@ -203,6 +480,74 @@ pub trait AccountModel {
     fn next_subaddress_index(self, conn: &Conn) -> Result<u64, WalletDbError>;
 }
 
+/// Importing an account which was removed replaces it, rather than colliding
+/// with the hidden copy.
+fn delete_if_removed(account_id: &AccountID, conn: &Conn) -> Result<(), WalletDbError> {
+    match Account::get_including_removed(account_id, conn) {
+        Ok(account) if account.removed_at.is_some() => account.delete(conn),
+        Ok(_) | Err(WalletDbError::AccountNotFound(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Record how the keys of an account entered the wallet, for the import paths
+/// which create accounts the same way as the wallet does.
+fn set_key_source(
+    account_id: &AccountID,
+    key_source: AccountKeySource,
+    conn: &Conn,
+) -> Result<(), WalletDbError> {
+    use crate::db::schema::accounts;
+
+    diesel::update(accounts::table.filter(accounts::id.eq(&account_id.0)))
+        .set(accounts::key_source.eq(key_source.to_string()))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Check the fog info of a new account, returning the decoded fog authority
+/// spki. Accounts without a fog report url have no fog info.
+fn fog_authority_spki_bytes(
+    fog_report_url: &str,
+    fog_report_id: &str,
+    fog_authority_spki: &str,
+) -> Result<Vec<u8>, WalletDbError> {
+    if fog_report_url.is_empty() {
+        if !fog_report_id.is_empty() || !fog_authority_spki.is_empty() {
+            return Err(WalletDbError::InvalidFogInfo(
+                "a fog report id or authority spki was given without a fog report url".to_string(),
+            ));
+        }
+        return Ok(vec![]);
+    }
+
+    FogUri::from_str(fog_report_url).map_err(|e| {
+        WalletDbError::InvalidFogInfo(format!(
+            "fog report url {} is not valid: {}",
+            fog_report_url, e
+        ))
+    })?;
+
+    let spki = base64::decode(fog_authority_spki)?;
+    if !is_subject_public_key_info(&spki) {
+        return Err(WalletDbError::InvalidFogInfo(
+            "fog authority spki is not a DER encoded subject public key info".to_string(),
+        ));
+    }
+    Ok(spki)
+}
+
+/// Check that the addresses of a fog enabled account carry a signature of the
+/// fog authority, which senders need to validate the account's fog reports.
+fn check_fog_authority_sig(account_key: &AccountKey) -> Result<(), WalletDbError> {
+    match account_key.default_subaddress().fog_authority_sig() {
+        Some(sig) if !sig.is_empty() => Ok(()),
+        _ => Err(WalletDbError::InvalidFogInfo(
+            "the account's addresses could not be signed for the fog authority".to_string(),
+        )),
+    }
+}
+
 impl AccountModel for Account {
     fn create_from_mnemonic(
         mnemonic: &Mnemonic,
@@ -216,12 +561,17 @@ impl AccountModel for Account {
         conn: &Conn,
     ) -> Result<(AccountID, String), WalletDbError> {
         let fog_enabled = !fog_report_url.is_empty();
+        let fog_authority_spki =
+            fog_authority_spki_bytes(&fog_report_url, &fog_report_id, &fog_authority_spki)?;
 
         let account_key = Slip10Key::from(mnemonic.clone()).try_into_account_key(
             &fog_report_url,
             &fog_report_id,
-            &base64::decode(fog_authority_spki)?,
+            &fog_authority_spki,
         )?;
+        if fog_enabled {
+            check_fog_authority_sig(&account_key)?;
+        }
 
         Account::create(
             mnemonic.entropy(),
@@ -232,6 +582,7 @@ impl AccountModel for Account {
             next_subaddress_index,
             name,
             fog_enabled,
+            AccountKeySource::Created,
             conn,
         )
     }
@@ -248,14 +599,19 @@ impl AccountModel for Account {
         conn: &Conn,
     ) -> Result<(AccountID, String), WalletDbError> {
         let fog_enabled = !fog_report_url.is_empty();
+        let fog_authority_spki =
+            fog_authority_spki_bytes(&fog_report_url, &fog_report_id, &fog_authority_spki)?;
 
         let root_id = RootIdentity {
             root_entropy: entropy.clone(),
             fog_report_url,
             fog_report_id,
-            fog_authority_spki: base64::decode(fog_authority_spki).expect("invalid spki"),
+            fog_authority_spki,
         };
         let account_key = AccountKey::from(&root_id);
+        if fog_enabled {
+            check_fog_authority_sig(&account_key)?;
+        }
 
         Account::create(
             &entropy.bytes,
@@ -266,6 +622,9 @@ impl AccountModel for Account {
             next_subaddress_index,
             name,
             fog_enabled,
+            // The wallet only creates new accounts from mnemonics, so root
+            // entropy always comes from elsewhere.
+            AccountKeySource::ImportedKeys,
             conn,
         )
     }
@@ -279,6 +638,7 @@ impl AccountModel for Account {
         next_subaddress_index: Option<u64>,
         name: &str,
         fog_enabled: bool,
+        key_source: AccountKeySource,
         conn: &Conn,
     ) -> Result<(AccountID, String), WalletDbError> {
         use crate::db::schema::accounts;
@@ -291,6 +651,12 @@ impl AccountModel for Account {
         let next_subaddress_index =
             next_subaddress_index.unwrap_or(DEFAULT_NEXT_SUBADDRESS_INDEX) as i64;
 
+        delete_if_removed(&account_id, conn)?;
+
+        if Account::get(&account_id, conn).is_ok() {
+            return Err(WalletDbError::AccountAlreadyExists(account_id.to_string()));
+        }
+
         let new_account = NewAccount {
             id: &account_id.to_string(),
             account_key: &mc_util_serial::encode(account_key),
@@ -302,6 +668,7 @@ impl AccountModel for Account {
             name,
             fog_enabled,
             view_only: false,
+            key_source: &key_source.to_string(),
         };
 
         diesel::insert_into(accounts::table)
@@ -351,6 +718,7 @@ impl AccountModel for Account {
             fog_authority_spki,
             conn,
         )?;
+        set_key_source(&account_id, AccountKeySource::ImportedMnemonic, conn)?;
         Account::get(&account_id, conn)
     }
 
@@ -399,6 +767,14 @@ impl AccountModel for Account {
         let next_subaddress_index =
             next_subaddress_index.unwrap_or(DEFAULT_NEXT_SUBADDRESS_INDEX) as i64;
 
+        delete_if_removed(&account_id, conn)?;
+
+        if Account::get(&account_id, conn).is_ok() {
+            return Err(WalletDbError::ViewOnlyAccountAlreadyExists(
+                account_id.to_string(),
+            ));
+        }
+
         let new_account = NewAccount {
             id: &account_id.to_string(),
             account_key: &mc_util_serial::encode(&view_account_key),
@@ -410,6 +786,7 @@ impl AccountModel for Account {
             name: &name.unwrap_or_else(|| "".to_string()),
             fog_enabled: false,
             view_only: true,
+            key_source: &AccountKeySource::ViewOnlyImport.to_string(),
         };
 
         diesel::insert_into(accounts::table)
@@ -449,6 +826,53 @@ impl AccountModel for Account {
         Account::get(&account_id, conn)
     }
 
+    fn restore(
+        account_key: &[u8],
+        entropy: Option<&[u8]>,
+        key_derivation_version: u8,
+        first_block_index: u64,
+        import_block_index: Option<u64>,
+        name: &str,
+        fog_enabled: bool,
+        view_only: bool,
+        key_source: AccountKeySource,
+        conn: &Conn,
+    ) -> Result<Account, WalletDbError> {
+        use crate::db::schema::accounts;
+
+        let account_id = if view_only {
+            let view_account_key: ViewAccountKey = mc_util_serial::decode(account_key)?;
+            AccountID::from(&view_account_key)
+        } else {
+            let account_key: AccountKey = mc_util_serial::decode(account_key)?;
+            AccountID::from(&account_key)
+        };
+
+        delete_if_removed(&account_id, conn)?;
+
+        // The txos of the account are not part of the backup, so it is synced
+        // again from the first block where it may have held funds.
+        let new_account = NewAccount {
+            id: &account_id.to_string(),
+            account_key,
+            entropy,
+            key_derivation_version: key_derivation_version as i32,
+            first_block_index: first_block_index as i64,
+            next_block_index: first_block_index as i64,
+            import_block_index: import_block_index.map(|i| i as i64),
+            name,
+            fog_enabled,
+            view_only,
+            key_source: &key_source.to_string(),
+        };
+
+        diesel::insert_into(accounts::table)
+            .values(&new_account)
+            .execute(conn)?;
+
+        Account::get(&account_id, conn)
+    }
+
     fn list_all(
         conn: &Conn,
         offset: Option<u64>,
@@ -456,7 +880,9 @@ impl AccountModel for Account {
     ) -> Result<Vec<Account>, WalletDbError> {
         use crate::db::schema::accounts;
 
-        let mut query = accounts::table.into_boxed();
+        let mut query = accounts::table
+            .filter(accounts::removed_at.is_null())
+            .into_boxed();
 
         if let (Some(offset), Some(limit)) = (offset, limit) {
             query = query.limit(limit as i64).offset(offset as i64);
@@ -465,7 +891,33 @@ impl AccountModel for Account {
         Ok(query.load(conn)?)
     }
 
+    fn list_by_view_private_key(
+        view_private_key: &RistrettoPrivate,
+        conn: &Conn,
+    ) -> Result<Vec<Account>, WalletDbError> {
+        let view_public_key = RistrettoPublic::from(view_private_key);
+        let mut accounts = Vec::new();
+        for account in Account::list_all(conn, None, None)? {
+            if RistrettoPublic::from(&account.view_private_key()?) == view_public_key {
+                accounts.push(account);
+            }
+        }
+        Ok(accounts)
+    }
+
     fn get(account_id: &AccountID, conn: &Conn) -> Result<Account, WalletDbError> {
+        match Account::get_including_removed(account_id, conn) {
+            Ok(a) if a.removed_at.is_some() => {
+                Err(WalletDbError::AccountNotFound(account_id.to_string()))
+            }
+            res => res,
+        }
+    }
+
+    fn get_including_removed(
+        account_id: &AccountID,
+        conn: &Conn,
+    ) -> Result<Account, WalletDbError> {
         use crate::db::schema::accounts;
 
         match accounts::table
@@ -481,6 +933,36 @@ impl AccountModel for Account {
         }
     }
 
+    fn list_removed_before(
+        removed_at_cutoff: i64,
+        conn: &Conn,
+    ) -> Result<Vec<Account>, WalletDbError> {
+        use crate::db::schema::accounts;
+
+        Ok(accounts::table
+            .filter(accounts::removed_at.le(removed_at_cutoff))
+            .load::<Account>(conn)?)
+    }
+
+    fn count_by_sync_state(
+        block_height: u64,
+        behind_threshold: u64,
+        conn: &Conn,
+    ) -> Result<AccountSyncCounts, WalletDbError> {
+        Ok(diesel::sql_query(
+            "SELECT
+                COUNT(CASE WHEN next_block_index >= ?1 THEN 1 END) AS synced,
+                COUNT(CASE WHEN next_block_index < ?1 AND ?1 - next_block_index <= ?2 THEN 1 END)
+                    AS syncing,
+                COUNT(CASE WHEN ?1 - next_block_index > ?2 THEN 1 END) AS behind
+            FROM accounts
+            WHERE removed_at IS NULL",
+        )
+        .bind::<BigInt, _>(block_height as i64)
+        .bind::<BigInt, _>(behind_threshold as i64)
+        .get_result(conn)?)
+    }
+
     fn get_by_txo_id(txo_id_hex: &str, conn: &Conn) -> Result<Vec<Account>, WalletDbError> {
         let txo = Txo::get(txo_id_hex, conn)?;
 
@@ -503,6 +985,73 @@ impl AccountModel for Account {
         Ok(())
     }
 
+    fn update_migrated_to_account_id(
+        &self,
+        migrated_to_account_id: &AccountID,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::migrated_to_account_id.eq(Some(migrated_to_account_id.to_string())))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_subaddress_gap_limit(
+        &self,
+        subaddress_gap_limit: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::subaddress_gap_limit.eq(subaddress_gap_limit as i64))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_split_change_into(
+        &self,
+        split_change_into: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::split_change_into.eq(split_change_into as i64))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_min_confirmations(
+        &self,
+        min_confirmations: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::min_confirmations.eq(min_confirmations.map(|m| m as i64)))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_transaction_defaults(
+        &self,
+        default_fee_value: Option<u64>,
+        default_fee_token_id: Option<u64>,
+        default_tombstone_offset: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set((
+                accounts::default_fee_value.eq(default_fee_value.map(|f| f as i64)),
+                accounts::default_fee_token_id.eq(default_fee_token_id.map(|t| t as i64)),
+                accounts::default_tombstone_offset.eq(default_tombstone_offset.map(|o| o as i64)),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
     fn update_next_block_index(
         &self,
         next_block_index: u64,
@@ -515,9 +1064,55 @@ impl AccountModel for Account {
         Ok(())
     }
 
+    fn update_first_block_index(
+        &self,
+        first_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::first_block_index.eq(first_block_index as i64))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_first_activity_block_index(
+        &self,
+        first_activity_block_index: Option<u64>,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(
+                accounts::first_activity_block_index
+                    .eq(first_activity_block_index.map(|i| i as i64)),
+            )
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn mark_removed(&self, removed_at: i64, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::removed_at.eq(Some(removed_at)))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn unmark_removed(&self, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::accounts;
+        diesel::update(accounts::table.filter(accounts::id.eq(&self.id)))
+            .set(accounts::removed_at.eq(None::<i64>))
+            .execute(conn)?;
+        Ok(())
+    }
+
     fn delete(self, conn: &Conn) -> Result<(), WalletDbError> {
         use crate::db::schema::accounts;
 
+        // Delete idempotency keys associated with this account
+        IdempotencyKey::delete_all_for_account(&self.id, conn)?;
+
         // Delete transaction logs associated with this account
         TransactionLog::delete_all_for_account(&self.id, conn)?;
 
@@ -547,6 +1142,36 @@ impl AccountModel for Account {
         )
     }
 
+    fn view_private_key(&self) -> Result<RistrettoPrivate, WalletDbError> {
+        if self.view_only {
+            let view_account_key: ViewAccountKey = mc_util_serial::decode(&self.account_key)?;
+            Ok(*view_account_key.view_private_key())
+        } else {
+            let account_key: AccountKey = mc_util_serial::decode(&self.account_key)?;
+            Ok(*account_key.view_private_key())
+        }
+    }
+
+    fn public_address(&self, subaddress_index: u64) -> Result<PublicAddress, WalletDbError> {
+        if self.view_only {
+            let view_account_key: ViewAccountKey = mc_util_serial::decode(&self.account_key)?;
+            Ok(view_account_key.subaddress(subaddress_index))
+        } else {
+            let account_key: AccountKey = mc_util_serial::decode(&self.account_key)?;
+            Ok(account_key.subaddress(subaddress_index))
+        }
+    }
+
+    fn reserved_subaddresses(&self) -> Vec<ReservedSubaddress> {
+        let mut reserved = vec![ReservedSubaddress::Main];
+        if !self.fog_enabled {
+            reserved.push(ReservedSubaddress::LegacyChange);
+        }
+        reserved.push(ReservedSubaddress::Change);
+        reserved.push(ReservedSubaddress::GiftCode);
+        reserved
+    }
+
     fn get_token_ids(self, conn: &Conn) -> Result<Vec<TokenId>, WalletDbError> {
         use crate::db::schema::txos;
 
@@ -630,6 +1255,16 @@ mod tests {
             name: "Alice's Main Account".to_string(),
             fog_enabled: false,
             view_only: false,
+            subaddress_gap_limit: 0,
+            split_change_into: 1,
+            removed_at: None,
+            min_confirmations: None,
+            default_fee_value: None,
+            default_fee_token_id: None,
+            default_tombstone_offset: None,
+            key_source: "imported_keys".to_string(),
+            migrated_to_account_id: None,
+            first_activity_block_index: None,
         };
         assert_eq!(expected_account, acc);
 
@@ -692,6 +1327,16 @@ mod tests {
             name: "".to_string(),
             fog_enabled: false,
             view_only: false,
+            subaddress_gap_limit: 0,
+            split_change_into: 1,
+            removed_at: None,
+            min_confirmations: None,
+            default_fee_value: None,
+            default_fee_token_id: None,
+            default_tombstone_offset: None,
+            key_source: "imported_keys".to_string(),
+            migrated_to_account_id: None,
+            first_activity_block_index: None,
         };
         assert_eq!(expected_account_secondary, acc_secondary);
 
@@ -813,8 +1458,28 @@ mod tests {
             name: "Alice's FOG Account".to_string(),
             fog_enabled: true,
             view_only: false,
+            subaddress_gap_limit: 0,
+            split_change_into: 1,
+            removed_at: None,
+            min_confirmations: None,
+            default_fee_value: None,
+            default_fee_token_id: None,
+            default_tombstone_offset: None,
+            key_source: "imported_keys".to_string(),
+            migrated_to_account_id: None,
+            first_activity_block_index: None,
         };
         assert_eq!(expected_account, acc);
+
+        // Accounts with fog do not reserve the legacy change subaddress.
+        assert_eq!(
+            acc.reserved_subaddresses(),
+            vec![
+                ReservedSubaddress::Main,
+                ReservedSubaddress::Change,
+                ReservedSubaddress::GiftCode,
+            ]
+        );
     }
 
     #[test_with_logger]
@@ -865,6 +1530,16 @@ mod tests {
             name: "View Only Account".to_string(),
             fog_enabled: false,
             view_only: true,
+            subaddress_gap_limit: 0,
+            split_change_into: 1,
+            removed_at: None,
+            min_confirmations: None,
+            default_fee_value: None,
+            default_fee_token_id: None,
+            default_tombstone_offset: None,
+            key_source: "view_only_import".to_string(),
+            migrated_to_account_id: None,
+            first_activity_block_index: None,
         };
         assert_eq!(expected_account, account);
     }
@@ -7,9 +7,9 @@ use crate::{
     db::{
         account::{AccountID, AccountModel},
         models::{Account, AssignedSubaddress, NewAssignedSubaddress, Txo},
-        txo::TxoModel,
+        txo::{next_modified_seq, TxoModel},
     },
-    util::b58::b58_decode_public_address,
+    util::{b58::b58_decode_public_address, constants::DEFAULT_NEXT_SUBADDRESS_INDEX},
 };
 
 use crate::util::b58::b58_encode_public_address;
@@ -65,6 +65,20 @@ pub trait AssignedSubaddressModel {
         conn: &Conn,
     ) -> Result<(String, i64), WalletDbError>;
 
+    /// Create the subaddress at a given index for a given account, such as an
+    /// index assigned outside the wallet. Reserved and already assigned
+    /// indices are rejected.
+    ///
+    /// Returns:
+    /// * public_address_b58
+    fn create_at_index_for_account(
+        account_id_hex: &str,
+        subaddress_index: u64,
+        comment: &str,
+        ledger_db: &LedgerDB,
+        conn: &Conn,
+    ) -> Result<String, WalletDbError>;
+
     /// Get the AssignedSubaddress for a given public_address_b58
     fn get(public_address_b58: &str, conn: &Conn) -> Result<AssignedSubaddress, WalletDbError>;
 
@@ -93,6 +107,9 @@ pub trait AssignedSubaddressModel {
         conn: &Conn,
     ) -> Result<Vec<AssignedSubaddress>, WalletDbError>;
 
+    /// Update the comment on an AssignedSubaddress.
+    fn update_comment(&self, comment: &str, conn: &Conn) -> Result<(), WalletDbError>;
+
     /// Delete all AssignedSubaddresses for a given account.
     fn delete_all(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
 
@@ -169,107 +186,47 @@ impl AssignedSubaddressModel for AssignedSubaddress {
             return Err(WalletDbError::SubaddressesNotSupportedForFOGEnabledAccounts);
         }
 
-        let (subaddress_b58, next_subaddress_index) = if account.view_only {
-            let view_account_key: ViewAccountKey = mc_util_serial::decode(&account.account_key)?;
-            let next_subaddress_index = account.next_subaddress_index(conn)?;
-            let subaddress_b58 = AssignedSubaddress::create_for_view_only_account(
-                &view_account_key,
-                next_subaddress_index,
-                comment,
-                conn,
-            )?;
-
-            let subaddress = view_account_key.subaddress(next_subaddress_index);
-
-            // Find and repair orphaned txos at this subaddress.
-            let orphaned_txos =
-                Txo::list_orphaned(Some(account_id_hex), None, None, None, None, None, conn)?;
-
-            for orphaned_txo in orphaned_txos.iter() {
-                let tx_out_target_key: RistrettoPublic =
-                    mc_util_serial::decode(&orphaned_txo.target_key)?;
-                let tx_public_key: RistrettoPublic =
-                    mc_util_serial::decode(&orphaned_txo.public_key)?;
-
-                let txo_subaddress_spk: RistrettoPublic = recover_public_subaddress_spend_key(
-                    view_account_key.view_private_key(),
-                    &tx_out_target_key,
-                    &tx_public_key,
-                );
-
-                if txo_subaddress_spk == *subaddress.spend_public_key() {
-                    // Update the account status mapping.
-                    diesel::update(orphaned_txo)
-                        .set((crate::db::schema::txos::subaddress_index
-                            .eq(next_subaddress_index as i64),))
-                        .execute(conn)?;
-                }
-            }
-
-            (subaddress_b58, next_subaddress_index)
-        } else {
-            let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
-            let next_subaddress_index = account.next_subaddress_index(conn)?;
-            let subaddress_b58 =
-                AssignedSubaddress::create(&account_key, next_subaddress_index, comment, conn)?;
+        let next_subaddress_index = account.clone().next_subaddress_index(conn)?;
+        let subaddress_b58 =
+            assign_subaddress(&account, next_subaddress_index, comment, ledger_db, conn)?;
 
-            let subaddress = account_key.subaddress(next_subaddress_index);
+        Ok((subaddress_b58, next_subaddress_index as i64))
+    }
 
-            // Find and repair orphaned txos at this subaddress.
-            let orphaned_txos =
-                Txo::list_orphaned(Some(account_id_hex), None, None, None, None, None, conn)?;
+    fn create_at_index_for_account(
+        account_id_hex: &str,
+        subaddress_index: u64,
+        comment: &str,
+        ledger_db: &LedgerDB,
+        conn: &Conn,
+    ) -> Result<String, WalletDbError> {
+        let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
 
-            for orphaned_txo in orphaned_txos.iter() {
-                let tx_out_target_key: RistrettoPublic =
-                    mc_util_serial::decode(&orphaned_txo.target_key)?;
-                let tx_public_key: RistrettoPublic =
-                    mc_util_serial::decode(&orphaned_txo.public_key)?;
-                let txo_public_key = CompressedRistrettoPublic::from(tx_public_key);
+        if account.fog_enabled {
+            return Err(WalletDbError::SubaddressesNotSupportedForFOGEnabledAccounts);
+        }
 
-                let txo_subaddress_spk: RistrettoPublic = recover_public_subaddress_spend_key(
-                    account_key.view_private_key(),
-                    &tx_out_target_key,
-                    &tx_public_key,
-                );
+        if is_reserved_subaddress_index(subaddress_index) {
+            return Err(WalletDbError::ReservedSubaddressIndex(subaddress_index));
+        }
 
-                if txo_subaddress_spk == *subaddress.spend_public_key() {
-                    let onetime_private_key = recover_onetime_private_key(
-                        &tx_public_key,
-                        account_key.view_private_key(),
-                        &account_key.subaddress_spend_private(next_subaddress_index),
-                    );
-
-                    let key_image = KeyImage::from(&onetime_private_key);
-
-                    if ledger_db.contains_key_image(&key_image)? {
-                        let txo_index =
-                            ledger_db.get_tx_out_index_by_public_key(&txo_public_key)?;
-                        let block_index = ledger_db.get_block_index_by_tx_out_index(txo_index)?;
-                        diesel::update(orphaned_txo)
-                            .set(
-                                crate::db::schema::txos::spent_block_index
-                                    .eq(Some(block_index as i64)),
-                            )
-                            .execute(conn)?;
-                    }
-
-                    let key_image_bytes = mc_util_serial::encode(&key_image);
-
-                    // Update the account status mapping.
-                    diesel::update(orphaned_txo)
-                        .set((
-                            crate::db::schema::txos::subaddress_index
-                                .eq(next_subaddress_index as i64),
-                            crate::db::schema::txos::key_image.eq(key_image_bytes),
-                        ))
-                        .execute(conn)?;
-                }
+        match AssignedSubaddress::get_for_account_by_index(
+            account_id_hex,
+            subaddress_index as i64,
+            conn,
+        ) {
+            Ok(_) => {
+                return Err(WalletDbError::SubaddressIndexAlreadyAssigned(
+                    subaddress_index,
+                ))
             }
+            Err(WalletDbError::Diesel(diesel::result::Error::NotFound)) => {}
+            Err(e) => return Err(e),
+        }
 
-            (subaddress_b58, next_subaddress_index)
-        };
-
-        Ok((subaddress_b58, next_subaddress_index as i64))
+        // The next sequential index is one past the highest assigned index, so
+        // it moves past this index if it is beyond it.
+        assign_subaddress(&account, subaddress_index, comment, ledger_db, conn)
     }
 
     fn get(public_address_b58: &str, conn: &Conn) -> Result<AssignedSubaddress, WalletDbError> {
@@ -360,6 +317,15 @@ impl AssignedSubaddressModel for AssignedSubaddress {
         Ok(addresses_query.load(conn)?)
     }
 
+    fn update_comment(&self, comment: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::assigned_subaddresses;
+
+        diesel::update(assigned_subaddresses::table.find(&self.public_address_b58))
+            .set(assigned_subaddresses::comment.eq(comment))
+            .execute(conn)?;
+        Ok(())
+    }
+
     fn delete_all(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError> {
         use crate::db::schema::assigned_subaddresses;
 
@@ -376,3 +342,134 @@ impl AssignedSubaddressModel for AssignedSubaddress {
         Ok(public_address)
     }
 }
+
+/// Whether a subaddress index is reserved, and cannot be assigned to a
+/// contact. The main and legacy change subaddresses are below the first
+/// assignable index, and the change, gift code and invalid subaddresses are at
+/// the top of the range, beyond the indices which are stored in order.
+pub fn is_reserved_subaddress_index(subaddress_index: u64) -> bool {
+    subaddress_index < DEFAULT_NEXT_SUBADDRESS_INDEX || subaddress_index > i64::MAX as u64
+}
+
+/// Assign the subaddress at the given index of an account, and repair the
+/// orphaned txos which were received at it.
+fn assign_subaddress(
+    account: &Account,
+    subaddress_index: u64,
+    comment: &str,
+    ledger_db: &LedgerDB,
+    conn: &Conn,
+) -> Result<String, WalletDbError> {
+    let subaddress_b58 = if account.view_only {
+        let view_account_key: ViewAccountKey = mc_util_serial::decode(&account.account_key)?;
+        let subaddress_b58 = AssignedSubaddress::create_for_view_only_account(
+            &view_account_key,
+            subaddress_index,
+            comment,
+            conn,
+        )?;
+
+        let subaddress = view_account_key.subaddress(subaddress_index);
+
+        // Find and repair orphaned txos at this subaddress.
+        let orphaned_txos = Txo::list_orphaned(
+            Some(account.id.as_str()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            conn,
+        )?;
+
+        for orphaned_txo in orphaned_txos.iter() {
+            let tx_out_target_key: RistrettoPublic =
+                mc_util_serial::decode(&orphaned_txo.target_key)?;
+            let tx_public_key: RistrettoPublic = mc_util_serial::decode(&orphaned_txo.public_key)?;
+
+            let txo_subaddress_spk: RistrettoPublic = recover_public_subaddress_spend_key(
+                view_account_key.view_private_key(),
+                &tx_out_target_key,
+                &tx_public_key,
+            );
+
+            if txo_subaddress_spk == *subaddress.spend_public_key() {
+                // Update the account status mapping.
+                diesel::update(orphaned_txo)
+                    .set((
+                        crate::db::schema::txos::subaddress_index.eq(subaddress_index as i64),
+                        crate::db::schema::txos::modified_seq.eq(next_modified_seq()),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+
+        subaddress_b58
+    } else {
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
+        let subaddress_b58 =
+            AssignedSubaddress::create(&account_key, subaddress_index, comment, conn)?;
+
+        let subaddress = account_key.subaddress(subaddress_index);
+
+        // Find and repair orphaned txos at this subaddress.
+        let orphaned_txos = Txo::list_orphaned(
+            Some(account.id.as_str()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            conn,
+        )?;
+
+        for orphaned_txo in orphaned_txos.iter() {
+            let tx_out_target_key: RistrettoPublic =
+                mc_util_serial::decode(&orphaned_txo.target_key)?;
+            let tx_public_key: RistrettoPublic = mc_util_serial::decode(&orphaned_txo.public_key)?;
+            let txo_public_key = CompressedRistrettoPublic::from(tx_public_key);
+
+            let txo_subaddress_spk: RistrettoPublic = recover_public_subaddress_spend_key(
+                account_key.view_private_key(),
+                &tx_out_target_key,
+                &tx_public_key,
+            );
+
+            if txo_subaddress_spk == *subaddress.spend_public_key() {
+                let onetime_private_key = recover_onetime_private_key(
+                    &tx_public_key,
+                    account_key.view_private_key(),
+                    &account_key.subaddress_spend_private(subaddress_index),
+                );
+
+                let key_image = KeyImage::from(&onetime_private_key);
+
+                if ledger_db.contains_key_image(&key_image)? {
+                    let txo_index = ledger_db.get_tx_out_index_by_public_key(&txo_public_key)?;
+                    let block_index = ledger_db.get_block_index_by_tx_out_index(txo_index)?;
+                    diesel::update(orphaned_txo)
+                        .set((
+                            crate::db::schema::txos::spent_block_index.eq(Some(block_index as i64)),
+                            crate::db::schema::txos::modified_seq.eq(next_modified_seq()),
+                        ))
+                        .execute(conn)?;
+                }
+
+                let key_image_bytes = mc_util_serial::encode(&key_image);
+
+                // Update the account status mapping.
+                diesel::update(orphaned_txo)
+                    .set((
+                        crate::db::schema::txos::subaddress_index.eq(subaddress_index as i64),
+                        crate::db::schema::txos::key_image.eq(key_image_bytes),
+                        crate::db::schema::txos::modified_seq.eq(next_modified_seq()),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+
+        subaddress_b58
+    };
+
+    Ok(subaddress_b58)
+}
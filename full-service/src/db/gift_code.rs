@@ -4,6 +4,7 @@
 
 use crate::{
     db::{
+        account::AccountID,
         models::{GiftCode, NewGiftCode},
         Conn, WalletDbError,
     },
@@ -31,12 +32,17 @@ pub trait GiftCodeModel {
     /// that account, and the transaction_log has been stored for that
     /// deposit, all of which are handled by the GiftCodeService.
     ///
+    /// The gift code is linked to the account which funded it, if any, and to
+    /// the public key of the funding txo.
+    ///
     /// Returns:
     /// * Gift code encoded as b58 string.
     #[allow(clippy::too_many_arguments)]
     fn create(
         gift_code_b58: &EncodedGiftCode,
         value: i64,
+        account_id: Option<&AccountID>,
+        txo_public_key: &[u8],
         conn: &Conn,
     ) -> Result<GiftCode, WalletDbError>;
 
@@ -50,6 +56,12 @@ pub trait GiftCodeModel {
         limit: Option<u64>,
     ) -> Result<Vec<GiftCode>, WalletDbError>;
 
+    /// Get the Gift Codes funded by an account.
+    fn list_for_account(
+        account_id: &AccountID,
+        conn: &Conn,
+    ) -> Result<Vec<GiftCode>, WalletDbError>;
+
     /// Delete a gift code.
     fn delete(self, conn: &Conn) -> Result<(), WalletDbError>;
 }
@@ -58,6 +70,8 @@ impl GiftCodeModel for GiftCode {
     fn create(
         gift_code_b58: &EncodedGiftCode,
         value: i64,
+        account_id: Option<&AccountID>,
+        txo_public_key: &[u8],
         conn: &Conn,
     ) -> Result<GiftCode, WalletDbError> {
         use crate::db::schema::gift_codes;
@@ -66,6 +80,8 @@ impl GiftCodeModel for GiftCode {
         let new_gift_code = NewGiftCode {
             gift_code_b58: &gift_code_b58.to_string(),
             value,
+            account_id: account_id.map(|a| a.0.as_str()),
+            txo_public_key: Some(txo_public_key),
         };
         diesel::insert_into(gift_codes::table)
             .values(&new_gift_code)
@@ -107,6 +123,18 @@ impl GiftCodeModel for GiftCode {
         Ok(query.load(conn)?)
     }
 
+    fn list_for_account(
+        account_id: &AccountID,
+        conn: &Conn,
+    ) -> Result<Vec<GiftCode>, WalletDbError> {
+        use crate::db::schema::gift_codes;
+
+        Ok(gift_codes::table
+            .filter(gift_codes::account_id.eq(&account_id.0))
+            .order(gift_codes::id.asc())
+            .load(conn)?)
+    }
+
     fn delete(self, conn: &Conn) -> Result<(), WalletDbError> {
         use crate::db::schema::gift_codes::dsl::{gift_code_b58, gift_codes};
 
@@ -141,7 +169,7 @@ mod tests {
         // sufficient for this test to merely log a value.
         let value = rng.next_u64();
 
-        let (_tx_out, _key_image) = create_test_txo_for_recipient(
+        let (tx_out, _key_image) = create_test_txo_for_recipient(
             &gift_code_account_key,
             0,
             Amount::new(value, Mob::ID),
@@ -154,6 +182,8 @@ mod tests {
         let gift_code = GiftCode::create(
             &EncodedGiftCode("gk7CcXuK5RKNW13LvrWY156ZLjaoHaXxLedqACZsw3w6FfF6TR4TVzaAQkH5EHxaw54DnGWRJPA31PpcmvGLoArZbDRj1kBhcTusE8AVW4Mj7QT5".to_string()),
             value as i64,
+            Some(&AccountID("funding_account".to_string())),
+            &mc_util_serial::encode(&tx_out.public_key),
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -168,6 +198,8 @@ mod tests {
             id: 1,
             gift_code_b58: gotten.gift_code_b58.clone(),
             value: value as i64,
+            account_id: Some("funding_account".to_string()),
+            txo_public_key: Some(mc_util_serial::encode(&tx_out.public_key)),
         };
         assert_eq!(gotten, expected_gift_code);
 
@@ -175,5 +207,18 @@ mod tests {
             GiftCode::list_all(&wallet_db.get_conn().unwrap(), None, None).unwrap();
         assert_eq!(all_gift_codes.len(), 1);
         assert_eq!(all_gift_codes[0], expected_gift_code);
+
+        let funded_gift_codes = GiftCode::list_for_account(
+            &AccountID("funding_account".to_string()),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(funded_gift_codes, vec![expected_gift_code]);
+        let other_gift_codes = GiftCode::list_for_account(
+            &AccountID("other_account".to_string()),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+        assert!(other_gift_codes.is_empty());
     }
 }
@@ -0,0 +1,257 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! DB impl for the IdempotencyKey model.
+
+use crate::db::{
+    models::{IdempotencyKey, NewIdempotencyKey},
+    Conn, WalletDbError,
+};
+use diesel::prelude::*;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds since the unix epoch, as stored in the created_at column.
+pub fn unix_timestamp_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// The created_at value at or before which keys have outlived the given TTL.
+pub fn idempotency_key_cutoff(idempotency_key_ttl: Duration) -> i64 {
+    unix_timestamp_now() - idempotency_key_ttl.as_secs() as i64
+}
+
+pub trait IdempotencyKeyModel {
+    /// Record the first use of an idempotency key for an account.
+    fn create(
+        account_id_hex: &str,
+        idempotency_key: &str,
+        request_fingerprint: &str,
+        created_at: i64,
+        conn: &Conn,
+    ) -> Result<IdempotencyKey, WalletDbError>;
+
+    /// Get an idempotency key for an account.
+    fn get(
+        account_id_hex: &str,
+        idempotency_key: &str,
+        conn: &Conn,
+    ) -> Result<IdempotencyKey, WalletDbError>;
+
+    /// Record the transaction log produced by the request made with this key.
+    fn update_transaction_log_id(
+        &self,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Record that the request made with this key failed after its
+    /// transaction may have reached the network. The key is kept, so that a
+    /// retry reports the failure instead of submitting again.
+    fn update_submission_error(
+        &self,
+        submission_error: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Whether the key was created at or before the given cutoff, in seconds
+    /// since the unix epoch.
+    fn is_expired(&self, created_at_cutoff: i64) -> bool;
+
+    /// Delete an idempotency key.
+    fn delete(self, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// Delete all keys created at or before the given cutoff, in seconds since
+    /// the unix epoch. Returns the number of keys deleted.
+    fn delete_expired(created_at_cutoff: i64, conn: &Conn) -> Result<usize, WalletDbError>;
+
+    /// Delete all idempotency keys for an account.
+    fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
+}
+
+impl IdempotencyKeyModel for IdempotencyKey {
+    fn create(
+        account_id_hex: &str,
+        idempotency_key: &str,
+        request_fingerprint: &str,
+        created_at: i64,
+        conn: &Conn,
+    ) -> Result<IdempotencyKey, WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        let new_idempotency_key = NewIdempotencyKey {
+            account_id: account_id_hex,
+            idempotency_key,
+            request_fingerprint,
+            transaction_log_id: None,
+            created_at,
+        };
+
+        diesel::insert_into(idempotency_keys::table)
+            .values(&new_idempotency_key)
+            .execute(conn)?;
+
+        IdempotencyKey::get(account_id_hex, idempotency_key, conn)
+    }
+
+    fn get(
+        account_id_hex: &str,
+        idempotency_key: &str,
+        conn: &Conn,
+    ) -> Result<IdempotencyKey, WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        match idempotency_keys::table
+            .filter(idempotency_keys::account_id.eq(account_id_hex))
+            .filter(idempotency_keys::idempotency_key.eq(idempotency_key))
+            .get_result::<IdempotencyKey>(conn)
+        {
+            Ok(k) => Ok(k),
+            // Match on NotFound to get a more informative NotFound Error
+            Err(diesel::result::Error::NotFound) => Err(WalletDbError::IdempotencyKeyNotFound(
+                idempotency_key.to_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update_transaction_log_id(
+        &self,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        diesel::update(
+            idempotency_keys::table
+                .filter(idempotency_keys::account_id.eq(&self.account_id))
+                .filter(idempotency_keys::idempotency_key.eq(&self.idempotency_key)),
+        )
+        .set(idempotency_keys::transaction_log_id.eq(Some(transaction_log_id)))
+        .execute(conn)?;
+        Ok(())
+    }
+
+    fn update_submission_error(
+        &self,
+        submission_error: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        diesel::update(
+            idempotency_keys::table
+                .filter(idempotency_keys::account_id.eq(&self.account_id))
+                .filter(idempotency_keys::idempotency_key.eq(&self.idempotency_key)),
+        )
+        .set(idempotency_keys::submission_error.eq(Some(submission_error)))
+        .execute(conn)?;
+        Ok(())
+    }
+
+    fn is_expired(&self, created_at_cutoff: i64) -> bool {
+        self.created_at <= created_at_cutoff
+    }
+
+    fn delete(self, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        diesel::delete(
+            idempotency_keys::table
+                .filter(idempotency_keys::account_id.eq(&self.account_id))
+                .filter(idempotency_keys::idempotency_key.eq(&self.idempotency_key)),
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+
+    fn delete_expired(created_at_cutoff: i64, conn: &Conn) -> Result<usize, WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        Ok(diesel::delete(
+            idempotency_keys::table.filter(idempotency_keys::created_at.le(created_at_cutoff)),
+        )
+        .execute(conn)?)
+    }
+
+    fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::idempotency_keys;
+
+        diesel::delete(
+            idempotency_keys::table.filter(idempotency_keys::account_id.eq(account_id_hex)),
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{account::AccountModel, models::Account},
+        test_utils::WalletDbTestContext,
+    };
+    use mc_account_keys::RootIdentity;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_idempotency_key_crud(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger);
+        let conn = wallet_db.get_conn().unwrap();
+
+        let root_id = RootIdentity::from_random(&mut rng);
+        let (account_id, _public_address_b58) = Account::create_from_root_entropy(
+            &root_id.root_entropy,
+            Some(0),
+            None,
+            None,
+            "",
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            &conn,
+        )
+        .unwrap();
+        let account_id_hex = account_id.to_string();
+
+        let created = IdempotencyKey::create(&account_id_hex, "payout-1", "abc", 100, &conn)
+            .expect("Could not create idempotency key");
+        assert_eq!(created.transaction_log_id, None);
+        assert_eq!(created.submission_error, None);
+
+        // Keys are unique per account.
+        assert!(IdempotencyKey::create(&account_id_hex, "payout-1", "abc", 100, &conn).is_err());
+
+        created.update_transaction_log_id("log-1", &conn).unwrap();
+        let updated = IdempotencyKey::get(&account_id_hex, "payout-1", &conn).unwrap();
+        assert_eq!(updated.transaction_log_id, Some("log-1".to_string()));
+        assert!(!updated.is_expired(99));
+        assert!(updated.is_expired(100));
+
+        let failed =
+            IdempotencyKey::create(&account_id_hex, "payout-2", "def", 200, &conn).unwrap();
+        failed.update_submission_error("timed out", &conn).unwrap();
+        let failed = IdempotencyKey::get(&account_id_hex, "payout-2", &conn).unwrap();
+        assert_eq!(failed.transaction_log_id, None);
+        assert_eq!(failed.submission_error, Some("timed out".to_string()));
+
+        // Only the older key is purged.
+        assert_eq!(IdempotencyKey::delete_expired(150, &conn).unwrap(), 1);
+        match IdempotencyKey::get(&account_id_hex, "payout-1", &conn) {
+            Err(WalletDbError::IdempotencyKeyNotFound(_)) => {}
+            Ok(_) => panic!("Expired key should have been deleted"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+        assert!(IdempotencyKey::get(&account_id_hex, "payout-2", &conn).is_ok());
+
+        IdempotencyKey::delete_all_for_account(&account_id_hex, &conn).unwrap();
+        assert!(IdempotencyKey::get(&account_id_hex, "payout-2", &conn).is_err());
+    }
+}
@@ -6,12 +6,19 @@
 pub mod account;
 pub mod assigned_subaddress;
 pub mod gift_code;
+pub mod idempotency_key;
 pub mod models;
+pub mod monitored_txo;
 pub mod schema;
 pub mod transaction_log;
+pub mod transaction_report;
 pub mod txo;
 mod wallet_db;
 mod wallet_db_error;
 
-pub use wallet_db::{transaction, Conn, WalletDb};
-pub use wallet_db_error::WalletDbError;
+pub use wallet_db::{
+    set_write_attempts, transaction, Conn, ConnectionOptions, DatabaseBackup, DatabasePragmas,
+    JournalMode, MigrationOptions, Synchronous, WalletDb, DEFAULT_WRITE_ATTEMPTS,
+    MAX_WRITE_ATTEMPTS,
+};
+pub use wallet_db_error::{InsufficientFundsAmounts, InsufficientInputFundsAmounts, WalletDbError};
@@ -3,8 +3,8 @@
 //! DB Models
 
 use super::schema::{
-    accounts, assigned_subaddresses, gift_codes, transaction_input_txos, transaction_logs,
-    transaction_output_txos, txos,
+    accounts, assigned_subaddresses, gift_codes, idempotency_keys, monitored_txos,
+    transaction_input_txos, transaction_logs, transaction_output_txos, transaction_reports, txos,
 };
 
 use mc_crypto_keys::CompressedRistrettoPublic;
@@ -34,6 +34,36 @@ pub struct Account {
     pub name: String, /* empty string for nullable */
     pub fog_enabled: bool,
     pub view_only: bool,
+    /// Number of unassigned subaddresses past the highest assigned index which
+    /// are checked for incoming txos during sync.
+    pub subaddress_gap_limit: i64,
+    /// Number of outputs the change of each transaction built by this account
+    /// is divided between.
+    pub split_change_into: i64,
+    /// Seconds since the unix epoch at which this account was removed. Removed
+    /// accounts are hidden from the wallet until they are restored or purged.
+    pub removed_at: Option<i64>,
+    /// Number of blocks a received txo must be in the ledger for before it
+    /// can be spent. Uses the wallet default when not set.
+    pub min_confirmations: Option<i64>,
+    /// Fee paid by transactions built by this account which do not specify
+    /// one, when paying the fee in default_fee_token_id.
+    pub default_fee_value: Option<i64>,
+    /// Token in which transactions built by this account pay their fee, when
+    /// they do not specify one.
+    pub default_fee_token_id: Option<i64>,
+    /// Number of blocks past the tip at which transactions built by this
+    /// account which do not specify a tombstone block expire.
+    pub default_tombstone_offset: Option<i64>,
+    /// How the keys of this account entered the wallet, such as "created" or
+    /// "view_only_import".
+    pub key_source: String,
+    /// The account this legacy account's funds are being, or were, moved to
+    /// by migrate_account_to_mnemonic.
+    pub migrated_to_account_id: Option<String>,
+    /// Index of the first block in which sync found a txo received by this
+    /// account.
+    pub first_activity_block_index: Option<i64>,
 }
 
 /// A structure that can be inserted to create a new entity in the `accounts`
@@ -51,6 +81,7 @@ pub struct NewAccount<'a> {
     pub name: &'a str,
     pub fog_enabled: bool,
     pub view_only: bool,
+    pub key_source: &'a str,
 }
 
 /// A transaction output entity that either was received to an Account in this
@@ -84,6 +115,12 @@ pub struct Txo {
     pub received_block_index: Option<i64>,
     pub spent_block_index: Option<i64>,
     pub shared_secret: Option<Vec<u8>>,
+    /// Index of this Txo among all tx outs in the ledger, once it is in the
+    /// ledger.
+    pub global_index: Option<i64>,
+    /// Position of the last change to this Txo in the wallet's sequence of
+    /// txo changes, which only grows.
+    pub modified_seq: i64,
 }
 
 impl Txo {
@@ -110,6 +147,7 @@ pub struct NewTxo<'a> {
     pub received_block_index: Option<i64>,
     pub spent_block_index: Option<i64>,
     pub shared_secret: Option<&'a [u8]>,
+    pub global_index: Option<i64>,
 }
 
 /// A subaddress given to a particular contact, for the purpose of tracking
@@ -138,7 +176,9 @@ pub struct NewAssignedSubaddress<'a> {
 }
 
 /// The status of a sent transaction OR a received transaction output.
-#[derive(Clone, Serialize, Associations, Identifiable, Queryable, PartialEq, Debug)]
+#[derive(
+    Clone, Serialize, Associations, Identifiable, Queryable, QueryableByName, PartialEq, Debug,
+)]
 #[belongs_to(Account, foreign_key = "account_id")]
 #[primary_key(id)]
 #[table_name = "transaction_logs"]
@@ -153,6 +193,34 @@ pub struct TransactionLog {
     pub comment: String,
     pub tx: Vec<u8>,
     pub failed: bool,
+    /// The block version the transaction was built for, when known.
+    pub block_version: Option<i64>,
+    /// Whether the log was reconstructed from the ledger rather than recorded
+    /// when the transaction was built. Reconstructed logs have no tx.
+    pub reconstructed: bool,
+    /// For reconstructed logs, the value sent in the fee token. Includes the
+    /// fee when the fee is not known.
+    pub reconstructed_value: Option<i64>,
+    /// For reconstructed logs, the short hash of the recipient's address, when
+    /// the change recorded it.
+    pub recipient_address_hash: Option<Vec<u8>>,
+    /// The id of the transaction log which replaced this one, when it expired
+    /// and was rebuilt.
+    pub superseded_by: Option<String>,
+    /// How many more times the transaction will be rebuilt and resubmitted if
+    /// it expires. Null when automatic rebuilds were not requested.
+    pub auto_rebuild_attempts_left: Option<i32>,
+    /// The percentage by which the fee is raised on each rebuild.
+    pub auto_rebuild_fee_bump_percent: Option<i32>,
+    /// When the log was created, in seconds since the Unix epoch. Null for
+    /// logs created before this was recorded.
+    pub created_at: Option<i64>,
+    /// The payment request id put in the memos of the transaction, when it
+    /// was built by this wallet with one.
+    pub payment_request_id: Option<i64>,
+    /// The redemption memo data put in the memos of a burn transaction built
+    /// by this wallet.
+    pub burn_redemption_memo: Option<Vec<u8>>,
 }
 
 /// A structure that can be inserted to create a new TransactionLog entity.
@@ -169,6 +237,13 @@ pub struct NewTransactionLog<'a> {
     pub comment: &'a str,
     pub tx: &'a [u8],
     pub failed: bool,
+    pub block_version: Option<i64>,
+    pub reconstructed: bool,
+    pub reconstructed_value: Option<i64>,
+    pub recipient_address_hash: Option<&'a [u8]>,
+    pub created_at: Option<i64>,
+    pub payment_request_id: Option<i64>,
+    pub burn_redemption_memo: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Serialize, Associations, Identifiable, Queryable, PartialEq, Debug)]
@@ -179,6 +254,9 @@ pub struct NewTransactionLog<'a> {
 pub struct TransactionInputTxo {
     pub transaction_log_id: String,
     pub txo_id: String,
+    /// The ring the input was signed with, encoded as an InputRing. Not known
+    /// for reconstructed transactions or those logged before it was recorded.
+    pub ring: Option<Vec<u8>>,
 }
 
 #[derive(Insertable)]
@@ -186,6 +264,7 @@ pub struct TransactionInputTxo {
 pub struct NewTransactionInputTxo<'a> {
     pub transaction_log_id: &'a str,
     pub txo_id: &'a str,
+    pub ring: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Serialize, Associations, Identifiable, Queryable, PartialEq, Debug)]
@@ -198,6 +277,9 @@ pub struct TransactionOutputTxo {
     pub txo_id: String,
     pub recipient_public_address_b58: String,
     pub is_change: bool,
+    /// The encoded public key of the output, for looking up the transaction
+    /// log which produced it.
+    pub public_key: Option<Vec<u8>>,
 }
 
 #[derive(Insertable)]
@@ -207,6 +289,7 @@ pub struct NewTransactionOutputTxo<'a> {
     pub txo_id: &'a str,
     pub recipient_public_address_b58: &'a str,
     pub is_change: bool,
+    pub public_key: Option<&'a [u8]>,
 }
 
 #[derive(Clone, Serialize, Associations, Identifiable, Queryable, PartialEq, Debug)]
@@ -218,6 +301,11 @@ pub struct GiftCode {
     pub id: i32,
     pub gift_code_b58: String,
     pub value: i64,
+    /// The account which funded the gift code, if it was funded from this
+    /// wallet.
+    pub account_id: Option<String>,
+    /// The public key of the txo funding the gift code.
+    pub txo_public_key: Option<Vec<u8>>,
 }
 
 #[derive(Insertable)]
@@ -225,4 +313,98 @@ pub struct GiftCode {
 pub struct NewGiftCode<'a> {
     pub gift_code_b58: &'a str,
     pub value: i64,
+    pub account_id: Option<&'a str>,
+    pub txo_public_key: Option<&'a [u8]>,
+}
+
+/// An idempotency key, which ties a client supplied key to the transaction
+/// log produced by the first request made with it.
+#[derive(Clone, Serialize, Associations, Identifiable, Queryable, PartialEq, Debug)]
+#[belongs_to(Account, foreign_key = "account_id")]
+#[table_name = "idempotency_keys"]
+#[primary_key(account_id, idempotency_key)]
+pub struct IdempotencyKey {
+    /// The account the key is scoped to.
+    pub account_id: String,
+    /// The key supplied by the client.
+    pub idempotency_key: String,
+    /// A digest of the request parameters the key was first used with.
+    pub request_fingerprint: String,
+    /// The transaction log produced by the request, or None while the request
+    /// is still in progress.
+    pub transaction_log_id: Option<String>,
+    /// Seconds since the unix epoch when the key was first used.
+    pub created_at: i64,
+    /// Why the request failed, if it failed after its transaction may have
+    /// reached the network, so that a retry can not submit it twice.
+    pub submission_error: Option<String>,
+}
+
+#[derive(Insertable)]
+#[table_name = "idempotency_keys"]
+pub struct NewIdempotencyKey<'a> {
+    pub account_id: &'a str,
+    pub idempotency_key: &'a str,
+    pub request_fingerprint: &'a str,
+    pub transaction_log_id: Option<&'a str>,
+    pub created_at: i64,
+}
+
+/// A report of a submitted transaction, kept until it has been delivered to
+/// the reporting sink.
+#[derive(Clone, Serialize, Identifiable, Queryable, PartialEq, Debug)]
+#[table_name = "transaction_reports"]
+#[primary_key(transaction_log_id)]
+pub struct TransactionReport {
+    /// The transaction log of the reported transaction.
+    pub transaction_log_id: String,
+    /// The account which submitted the transaction.
+    pub account_id: String,
+    /// The JSON record delivered to the sink.
+    pub record: String,
+    /// Number of failed delivery attempts.
+    pub attempts: i32,
+    /// The error from the last failed delivery attempt.
+    pub last_error: Option<String>,
+    /// Seconds since the unix epoch when the report was delivered, or None
+    /// while it is outstanding.
+    pub delivered_at: Option<i64>,
+    /// Seconds since the unix epoch when the report was created.
+    pub created_at: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "transaction_reports"]
+pub struct NewTransactionReport<'a> {
+    pub transaction_log_id: &'a str,
+    pub account_id: &'a str,
+    pub record: &'a str,
+    pub created_at: i64,
+}
+
+/// A payload output of a submitted transaction, watched for in the ledger by
+/// its public key so that the payment can be confirmed without the keys of
+/// the recipient.
+#[derive(Clone, Serialize, Queryable, PartialEq, Debug)]
+pub struct MonitoredTxo {
+    /// The serialized public_key of the TxOut.
+    pub public_key: Vec<u8>,
+    /// The transaction log of the transaction which created the TxOut.
+    pub transaction_log_id: String,
+    /// The b58 address the TxOut was sent to.
+    pub recipient_public_address_b58: String,
+    pub value: i64,
+    pub token_id: i64,
+    /// The block containing the TxOut, once it has landed.
+    pub landed_block_index: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name = "monitored_txos"]
+pub struct NewMonitoredTxo<'a> {
+    pub public_key: &'a [u8],
+    pub transaction_log_id: &'a str,
+    pub recipient_public_address_b58: &'a str,
+    pub value: i64,
+    pub token_id: i64,
 }
@@ -0,0 +1,124 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! DB impl for the MonitoredTxo model.
+
+use crate::{
+    db::{
+        models::{MonitoredTxo, NewMonitoredTxo},
+        Conn, WalletDbError,
+    },
+    service::models::tx_proposal::OutputTxo,
+    util::b58::b58_encode_public_address,
+};
+use diesel::prelude::*;
+use mc_crypto_keys::CompressedRistrettoPublic;
+
+pub trait MonitoredTxoModel {
+    /// Watch for a payload output of a submitted transaction. Watching an
+    /// output which is already watched has no effect, so that resubmitting a
+    /// transaction is harmless.
+    fn create(
+        output_txo: &OutputTxo,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// List the watched outputs of a transaction.
+    fn list_for_transaction_log(
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<Vec<MonitoredTxo>, WalletDbError>;
+
+    /// List the watched outputs which have not yet landed.
+    fn list_pending(conn: &Conn) -> Result<Vec<MonitoredTxo>, WalletDbError>;
+
+    /// Record the block in which the output landed.
+    fn update_landed_block_index(
+        &self,
+        landed_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Delete the watched outputs of the transaction logs of an account.
+    fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// The public key of the watched TxOut.
+    fn public_key(&self) -> Result<CompressedRistrettoPublic, mc_util_serial::DecodeError>;
+}
+
+impl MonitoredTxoModel for MonitoredTxo {
+    fn create(
+        output_txo: &OutputTxo,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::monitored_txos;
+
+        let public_key = mc_util_serial::encode(&output_txo.tx_out.public_key);
+        let recipient_public_address_b58 =
+            b58_encode_public_address(&output_txo.recipient_public_address)?;
+        let new_monitored_txo = NewMonitoredTxo {
+            public_key: &public_key,
+            transaction_log_id,
+            recipient_public_address_b58: &recipient_public_address_b58,
+            value: output_txo.amount.value as i64,
+            token_id: *output_txo.amount.token_id as i64,
+        };
+
+        diesel::insert_or_ignore_into(monitored_txos::table)
+            .values(&new_monitored_txo)
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn list_for_transaction_log(
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<Vec<MonitoredTxo>, WalletDbError> {
+        use crate::db::schema::monitored_txos;
+
+        Ok(monitored_txos::table
+            .filter(monitored_txos::transaction_log_id.eq(transaction_log_id))
+            .order(monitored_txos::public_key.asc())
+            .load(conn)?)
+    }
+
+    fn list_pending(conn: &Conn) -> Result<Vec<MonitoredTxo>, WalletDbError> {
+        use crate::db::schema::monitored_txos;
+
+        Ok(monitored_txos::table
+            .filter(monitored_txos::landed_block_index.is_null())
+            .load(conn)?)
+    }
+
+    fn update_landed_block_index(
+        &self,
+        landed_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::monitored_txos;
+
+        diesel::update(monitored_txos::table.filter(monitored_txos::public_key.eq(&self.public_key)))
+            .set(monitored_txos::landed_block_index.eq(Some(landed_block_index as i64)))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::{monitored_txos, transaction_logs};
+
+        let transaction_log_ids = transaction_logs::table
+            .filter(transaction_logs::account_id.eq(account_id_hex))
+            .select(transaction_logs::id);
+        diesel::delete(
+            monitored_txos::table
+                .filter(monitored_txos::transaction_log_id.eq_any(transaction_log_ids)),
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+
+    fn public_key(&self) -> Result<CompressedRistrettoPublic, mc_util_serial::DecodeError> {
+        mc_util_serial::decode(&self.public_key)
+    }
+}
@@ -10,6 +10,16 @@ table! {
         name -> Text,
         fog_enabled -> Bool,
         view_only -> Bool,
+        subaddress_gap_limit -> BigInt,
+        split_change_into -> BigInt,
+        removed_at -> Nullable<BigInt>,
+        min_confirmations -> Nullable<BigInt>,
+        default_fee_value -> Nullable<BigInt>,
+        default_fee_token_id -> Nullable<BigInt>,
+        default_tombstone_offset -> Nullable<BigInt>,
+        key_source -> Text,
+        migrated_to_account_id -> Nullable<Text>,
+        first_activity_block_index -> Nullable<BigInt>,
     }
 }
 
@@ -28,6 +38,30 @@ table! {
         id -> Integer,
         gift_code_b58 -> Text,
         value -> BigInt,
+        account_id -> Nullable<Text>,
+        txo_public_key -> Nullable<Binary>,
+    }
+}
+
+table! {
+    idempotency_keys (account_id, idempotency_key) {
+        account_id -> Text,
+        idempotency_key -> Text,
+        request_fingerprint -> Text,
+        transaction_log_id -> Nullable<Text>,
+        created_at -> BigInt,
+        submission_error -> Nullable<Text>,
+    }
+}
+
+table! {
+    monitored_txos (public_key) {
+        public_key -> Binary,
+        transaction_log_id -> Text,
+        recipient_public_address_b58 -> Text,
+        value -> BigInt,
+        token_id -> BigInt,
+        landed_block_index -> Nullable<BigInt>,
     }
 }
 
@@ -35,6 +69,19 @@ table! {
     transaction_input_txos (transaction_log_id, txo_id) {
         transaction_log_id -> Text,
         txo_id -> Text,
+        ring -> Nullable<Binary>,
+    }
+}
+
+table! {
+    transaction_reports (transaction_log_id) {
+        transaction_log_id -> Text,
+        account_id -> Text,
+        record -> Text,
+        attempts -> Integer,
+        last_error -> Nullable<Text>,
+        delivered_at -> Nullable<BigInt>,
+        created_at -> BigInt,
     }
 }
 
@@ -50,6 +97,16 @@ table! {
         comment -> Text,
         tx -> Binary,
         failed -> Bool,
+        block_version -> Nullable<BigInt>,
+        reconstructed -> Bool,
+        reconstructed_value -> Nullable<BigInt>,
+        recipient_address_hash -> Nullable<Binary>,
+        superseded_by -> Nullable<Text>,
+        auto_rebuild_attempts_left -> Nullable<Integer>,
+        auto_rebuild_fee_bump_percent -> Nullable<Integer>,
+        created_at -> Nullable<BigInt>,
+        payment_request_id -> Nullable<BigInt>,
+        burn_redemption_memo -> Nullable<Binary>,
     }
 }
 
@@ -59,6 +116,7 @@ table! {
         txo_id -> Text,
         recipient_public_address_b58 -> Text,
         is_change -> Bool,
+        public_key -> Nullable<Binary>,
     }
 }
 
@@ -77,10 +135,13 @@ table! {
         received_block_index -> Nullable<BigInt>,
         spent_block_index -> Nullable<BigInt>,
         shared_secret -> Nullable<Binary>,
+        global_index -> Nullable<BigInt>,
+        modified_seq -> BigInt,
     }
 }
 
 joinable!(assigned_subaddresses -> accounts (account_id));
+joinable!(idempotency_keys -> accounts (account_id));
 joinable!(transaction_input_txos -> transaction_logs (transaction_log_id));
 joinable!(transaction_input_txos -> txos (txo_id));
 joinable!(transaction_logs -> accounts (account_id));
@@ -92,8 +153,11 @@ allow_tables_to_appear_in_same_query!(
     accounts,
     assigned_subaddresses,
     gift_codes,
+    idempotency_keys,
+    monitored_txos,
     transaction_input_txos,
     transaction_logs,
     transaction_output_txos,
+    transaction_reports,
     txos,
 );
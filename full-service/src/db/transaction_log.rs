@@ -2,23 +2,33 @@
 
 //! DB impl for the Transaction model.
 
-use diesel::prelude::*;
+use diesel::{
+    prelude::*,
+    sql_types::{BigInt, Bool, Nullable, Text},
+};
 use mc_common::HashMap;
 use mc_crypto_digestible::{Digestible, MerlinTranscript};
-use mc_transaction_core::{tx::Tx, Amount, TokenId};
-use std::fmt;
+use mc_crypto_keys::CompressedRistrettoPublic;
+use mc_transaction_core::{
+    tx::{Tx, TxOut},
+    Amount, TokenId,
+};
+use std::{collections::HashSet, fmt, str::FromStr};
 
 use crate::{
     db::{
         account::{AccountID, AccountModel},
+        idempotency_key::unix_timestamp_now,
         models::{
-            Account, NewTransactionInputTxo, NewTransactionLog, TransactionInputTxo,
-            TransactionLog, TransactionOutputTxo, Txo,
+            Account, MonitoredTxo, NewTransactionInputTxo, NewTransactionLog,
+            NewTransactionOutputTxo, TransactionInputTxo, TransactionLog, TransactionOutputTxo,
+            Txo,
         },
+        monitored_txo::MonitoredTxoModel,
         txo::{TxoID, TxoModel},
         Conn, WalletDbError,
     },
-    service::models::tx_proposal::TxProposal,
+    service::{models::tx_proposal::TxProposal, transaction::TransactionMemo},
 };
 
 #[derive(Debug)]
@@ -105,6 +115,270 @@ pub struct AssociatedTxos {
     pub change: Vec<(Txo, String)>,
 }
 
+/// A transaction of an account reconstructed from the ledger, from the txos
+/// it spent in a block and the change it received in the same block.
+#[derive(Debug)]
+pub struct ReconstructedTransaction {
+    pub block_index: u64,
+    pub block_version: u64,
+    /// The fee, which is zero when it is not known.
+    pub fee: Amount,
+    /// The value sent, in the fee token. Includes the fee when the fee is not
+    /// known.
+    pub value: u64,
+    /// The short hash of the recipient's address, when the change recorded it.
+    pub recipient_address_hash: Option<Vec<u8>>,
+    pub inputs: Vec<Txo>,
+    pub change: Vec<Txo>,
+}
+
+/// The ring an input was signed with, as the global indices of its members in
+/// the ledger. It is recorded when the transaction is logged, so that which
+/// outputs it was hidden among can be examined later without the ledger.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputRing {
+    /// The position of the real input in the ring.
+    pub real_position: usize,
+    /// The global indices of the ring members, in the order of the ring.
+    pub member_indices: Vec<u64>,
+}
+
+impl InputRing {
+    /// Find the ring containing the given input in a signed transaction, using
+    /// the membership proofs of its members for their indices.
+    pub fn from_tx(tx: &Tx, input: &TxOut) -> Option<Self> {
+        tx.prefix.inputs.iter().find_map(|tx_in| {
+            let real_position = tx_in
+                .ring
+                .iter()
+                .position(|member| member.public_key == input.public_key)?;
+            if tx_in.proofs.len() != tx_in.ring.len() {
+                return None;
+            }
+            Some(Self {
+                real_position,
+                member_indices: tx_in.proofs.iter().map(|proof| proof.index).collect(),
+            })
+        })
+    }
+
+    /// The global index of the real input.
+    pub fn real_index(&self) -> u64 {
+        self.member_indices[self.real_position]
+    }
+
+    /// Encode as the position of the real input, two bytes big endian,
+    /// followed by the index of each member, eight bytes big endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + 8 * self.member_indices.len());
+        bytes.extend_from_slice(&(self.real_position as u16).to_be_bytes());
+        for index in self.member_indices.iter() {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WalletDbError> {
+        if bytes.len() < 2 || (bytes.len() - 2) % 8 != 0 {
+            return Err(WalletDbError::InvalidInputRing(format!(
+                "unexpected length {}",
+                bytes.len()
+            )));
+        }
+        let real_position = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        let member_indices: Vec<u64> = bytes[2..]
+            .chunks(8)
+            .map(|chunk| {
+                let mut index = [0u8; 8];
+                index.copy_from_slice(chunk);
+                u64::from_be_bytes(index)
+            })
+            .collect();
+        if real_position >= member_indices.len() {
+            return Err(WalletDbError::InvalidInputRing(format!(
+                "real input at {} of a ring of {}",
+                real_position,
+                member_indices.len()
+            )));
+        }
+
+        Ok(Self {
+            real_position,
+            member_indices,
+        })
+    }
+}
+
+/// The fees paid by an account in a token, in a single block when grouped by
+/// block.
+#[derive(Debug, PartialEq)]
+pub struct FeeTotal {
+    pub token_id: TokenId,
+    pub block_index: Option<u64>,
+    pub value: u128,
+}
+
+/// A status to filter transaction logs by. Reconstructed logs also have the
+/// status of their transaction, which is succeeded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionLogStatus {
+    Built,
+    Pending,
+    Succeeded,
+    Failed,
+    Reconstructed,
+}
+
+impl FromStr for TransactionLogStatus {
+    type Err = WalletDbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "built" => Ok(TransactionLogStatus::Built),
+            "pending" => Ok(TransactionLogStatus::Pending),
+            "succeeded" => Ok(TransactionLogStatus::Succeeded),
+            "failed" => Ok(TransactionLogStatus::Failed),
+            "reconstructed" => Ok(TransactionLogStatus::Reconstructed),
+            _ => Err(WalletDbError::InvalidTransactionLogStatus(s.to_string())),
+        }
+    }
+}
+
+impl TransactionLogStatus {
+    /// The SQL condition for a transaction log to have the status, matching
+    /// TransactionLogModel::status.
+    fn sql_condition(&self) -> &'static str {
+        match self {
+            TransactionLogStatus::Built => {
+                "NOT failed AND finalized_block_index IS NULL AND submitted_block_index IS NULL"
+            }
+            TransactionLogStatus::Pending => {
+                "NOT failed AND finalized_block_index IS NULL AND submitted_block_index IS NOT NULL"
+            }
+            TransactionLogStatus::Succeeded => "NOT failed AND finalized_block_index IS NOT NULL",
+            TransactionLogStatus::Failed => "failed",
+            TransactionLogStatus::Reconstructed => "reconstructed",
+        }
+    }
+}
+
+/// What to order transaction logs by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransactionLogOrderBy {
+    /// The transaction log id, which is the default.
+    Id,
+    /// The block the transaction landed in, or was submitted at while it is
+    /// pending.
+    BlockIndex,
+    /// When the transaction log was created.
+    CreatedAt,
+}
+
+impl Default for TransactionLogOrderBy {
+    fn default() -> Self {
+        TransactionLogOrderBy::Id
+    }
+}
+
+impl FromStr for TransactionLogOrderBy {
+    type Err = WalletDbError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(TransactionLogOrderBy::Id),
+            "block_index" => Ok(TransactionLogOrderBy::BlockIndex),
+            "created_at" => Ok(TransactionLogOrderBy::CreatedAt),
+            _ => Err(WalletDbError::InvalidTransactionLogOrder(s.to_string())),
+        }
+    }
+}
+
+/// Filters for listing transaction logs, all of which must match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionLogFilter {
+    /// Only logs which sent an output to this address. Reconstructed logs do
+    /// not record the recipient's address, and never match.
+    pub recipient_public_address_b58: Option<String>,
+
+    /// Only logs which paid their fee in this token.
+    pub token_id: Option<u64>,
+
+    /// The least value the log sent in the token of its fee, excluding change.
+    pub min_value: Option<u64>,
+
+    /// The most value the log sent in the token of its fee, excluding change.
+    pub max_value: Option<u64>,
+
+    pub status: Option<TransactionLogStatus>,
+
+    /// The first block the logs were finalized in.
+    pub min_block_index: Option<u64>,
+
+    /// The last block the logs were finalized in.
+    pub max_block_index: Option<u64>,
+
+    pub order_by: TransactionLogOrderBy,
+
+    pub descending: bool,
+}
+
+impl TransactionLogFilter {
+    /// Whether any filter is set, other than the ordering.
+    pub fn is_filtered(&self) -> bool {
+        self.recipient_public_address_b58.is_some()
+            || self.token_id.is_some()
+            || self.min_value.is_some()
+            || self.max_value.is_some()
+            || self.status.is_some()
+            || self.min_block_index.is_some()
+            || self.max_block_index.is_some()
+    }
+}
+
+// The value a transaction log sent in the token of its fee, excluding change.
+// Reconstructed logs have no outputs, and record the value instead.
+const TRANSACTION_LOG_VALUE: &str = "
+    CASE WHEN transaction_logs.reconstructed
+        THEN COALESCE(transaction_logs.reconstructed_value, 0)
+        ELSE COALESCE((
+            SELECT SUM(txos.value)
+            FROM transaction_output_txos
+            JOIN txos ON txos.id = transaction_output_txos.txo_id
+            WHERE transaction_output_txos.transaction_log_id = transaction_logs.id
+                AND NOT transaction_output_txos.is_change
+                AND txos.token_id = transaction_logs.fee_token_id
+        ), 0)
+    END
+";
+
+#[derive(QueryableByName)]
+struct FeeSum {
+    #[sql_type = "BigInt"]
+    fee_token_id: i64,
+    #[sql_type = "Nullable<BigInt>"]
+    block_index: Option<i64>,
+    #[sql_type = "BigInt"]
+    fee_total: i64,
+}
+
+// Sums the fees of the finalized transactions of an account. Failed
+// transactions never landed, and built or pending ones have not paid a fee yet.
+const FEE_TOTALS_QUERY: &str = "
+    SELECT
+        fee_token_id,
+        CASE WHEN ? THEN finalized_block_index END AS block_index,
+        SUM(fee_value) AS fee_total
+    FROM transaction_logs
+    WHERE account_id = ?
+        AND NOT failed
+        AND finalized_block_index IS NOT NULL
+        AND finalized_block_index >= ?
+        AND finalized_block_index <= ?
+        AND (? IS NULL OR fee_token_id = ?)
+    GROUP BY fee_token_id, block_index
+    ORDER BY block_index, fee_token_id
+";
+
 impl TransactionLog {
     pub fn fee_amount(&self) -> Amount {
         Amount::new(
@@ -134,13 +408,32 @@ pub trait TransactionLogModel {
     /// * AssoiatedTxos(inputs, outputs, change)
     fn get_associated_txos(&self, conn: &Conn) -> Result<AssociatedTxos, WalletDbError>;
 
+    /// Get the input Txos of the transaction log, each with the ring it was
+    /// signed with, if that was recorded.
+    fn get_input_rings(&self, conn: &Conn) -> Result<Vec<(Txo, Option<InputRing>)>, WalletDbError>;
+
+    /// Get the transaction log which produced the output with the given
+    /// public key.
+    ///
+    /// Returns:
+    /// * (TransactionLog, output Txo, TransactionOutputTxo)
+    fn get_by_output_public_key(
+        public_key: &CompressedRistrettoPublic,
+        conn: &Conn,
+    ) -> Result<(TransactionLog, Txo, TransactionOutputTxo), WalletDbError>;
+
     fn update_submitted_block_index(
         &self,
         submitted_block_index: u64,
         conn: &Conn,
     ) -> Result<(), WalletDbError>;
 
-    /// List all TransactionLogs and their associated Txos for a given account.
+    /// Update the comment, which is only stored locally and never put in the
+    /// memos of the transaction.
+    fn update_comment(&self, comment: &str, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// List all TransactionLogs and their associated Txos for a given account,
+    /// which match the filter.
     ///
     /// Returns:
     /// * Vec(TransactionLog, AssociatedTxos(inputs, outputs, change))
@@ -148,14 +441,26 @@ pub trait TransactionLogModel {
         account_id: Option<String>,
         offset: Option<u64>,
         limit: Option<u64>,
-        min_block_index: Option<u64>,
-        max_block_index: Option<u64>,
+        filter: &TransactionLogFilter,
         conn: &Conn,
     ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletDbError>;
 
+    /// Get a page of the transaction logs for an account, ordered by id and
+    /// starting after the given transaction log id.
+    fn list_for_account_after(
+        account_id_hex: &str,
+        after_transaction_log_id: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletDbError>;
+
+    /// Log a transaction built by this wallet, along with the parameters of
+    /// its memos, so that they are known even when the memos can not be
+    /// recovered from the ledger.
     fn log_built(
         tx_proposal: TxProposal,
         comment: String,
+        memo: &TransactionMemo,
         account_id_hex: &str,
         conn: &Conn,
     ) -> Result<TransactionLog, WalletDbError>;
@@ -178,20 +483,97 @@ pub trait TransactionLogModel {
         conn: &Conn,
     ) -> Result<TransactionLog, WalletDbError>;
 
+    /// Log a transaction reconstructed from the ledger, linking it to the
+    /// account's inputs and change. The log is identified by the account,
+    /// block and token, and is returned unchanged if it was already logged.
+    fn log_reconstructed(
+        account_id_hex: &str,
+        reconstructed: &ReconstructedTransaction,
+        conn: &Conn,
+    ) -> Result<TransactionLog, WalletDbError>;
+
+    /// The ids of the txos which are inputs or outputs of the account's
+    /// transaction logs.
+    fn list_associated_txo_ids(
+        account_id_hex: &str,
+        conn: &Conn,
+    ) -> Result<HashSet<String>, WalletDbError>;
+
     /// Remove all logs for an account
     fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
 
+    /// Count the pending transactions of each account which has not been
+    /// removed, keyed by account id. Accounts without any are left out.
+    fn count_pending_by_account(conn: &Conn) -> Result<HashMap<String, u64>, WalletDbError>;
+
+    /// Sum the fees paid by an account's finalized transactions in each
+    /// token, optionally only for one token and within a range of finalized
+    /// block indices, and split by the block they were finalized in.
+    fn get_fee_totals(
+        account_id_hex: &str,
+        token_id: Option<u64>,
+        min_block_index: Option<u64>,
+        max_block_index: Option<u64>,
+        group_by_block: bool,
+        conn: &Conn,
+    ) -> Result<Vec<FeeTotal>, WalletDbError>;
+
     fn update_pending_associated_with_txo_to_succeeded(
         txo_id_hex: &str,
         finalized_block_index: u64,
         conn: &Conn,
     ) -> Result<(), WalletDbError>;
 
+    /// Mark a pending transaction log as succeeded at the given block, once
+    /// one of its outputs has been seen in the ledger.
+    fn update_pending_to_succeeded(
+        transaction_log_id: &str,
+        finalized_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Mark the pending transaction logs of an account whose tombstone block
+    /// is below the given block as failed. Only the account's own logs are
+    /// marked, as the other accounts may not have synced that far yet.
     fn update_pending_exceeding_tombstone_block_index_to_failed(
+        account_id_hex: &str,
         block_index: u64,
         conn: &Conn,
     ) -> Result<(), WalletDbError>;
 
+    /// Mark a transaction log which was marked failed as succeeded at the
+    /// given block, once it turns out to have landed after all.
+    fn update_failed_to_succeeded(
+        &self,
+        finalized_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Set how many more times the transaction is rebuilt and resubmitted if
+    /// it expires, and the percentage by which the fee is raised each time.
+    fn update_auto_rebuild(
+        &self,
+        attempts_left: u32,
+        fee_bump_percent: u32,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// Record that an expired transaction was rebuilt as another transaction
+    /// log.
+    fn update_superseded_by(
+        &self,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError>;
+
+    /// List the expired transaction logs which are due to be rebuilt and
+    /// resubmitted, oldest first.
+    fn list_awaiting_rebuild(conn: &Conn) -> Result<Vec<TransactionLog>, WalletDbError>;
+
+    /// Get the ids of the transaction logs which were rebuilt from one
+    /// another along with this one, from the original to the latest.
+    fn get_rebuild_chain(&self, conn: &Conn) -> Result<Vec<String>, WalletDbError>;
+
     fn status(&self) -> TxStatus;
 
     fn value_for_token_id(&self, token_id: TokenId, conn: &Conn) -> Result<u64, WalletDbError>;
@@ -293,6 +675,57 @@ impl TransactionLogModel for TransactionLog {
         })
     }
 
+    fn get_input_rings(&self, conn: &Conn) -> Result<Vec<(Txo, Option<InputRing>)>, WalletDbError> {
+        use crate::db::schema::{transaction_input_txos, txos};
+
+        let inputs: Vec<(Txo, Option<Vec<u8>>)> = txos::table
+            .inner_join(transaction_input_txos::table)
+            .filter(transaction_input_txos::transaction_log_id.eq(&self.id))
+            .select((txos::all_columns, transaction_input_txos::ring))
+            .load(conn)?;
+
+        inputs
+            .into_iter()
+            .map(|(txo, ring)| {
+                let ring = ring
+                    .map(|bytes| InputRing::from_bytes(&bytes))
+                    .transpose()?;
+                Ok((txo, ring))
+            })
+            .collect()
+    }
+
+    fn get_by_output_public_key(
+        public_key: &CompressedRistrettoPublic,
+        conn: &Conn,
+    ) -> Result<(TransactionLog, Txo, TransactionOutputTxo), WalletDbError> {
+        use crate::db::schema::{transaction_output_txos, txos};
+
+        let encoded_public_key = mc_util_serial::encode(public_key);
+        let (transaction_output_txo, txo): (TransactionOutputTxo, Txo) =
+            match transaction_output_txos::table
+                .inner_join(txos::table)
+                .filter(transaction_output_txos::public_key.eq(&encoded_public_key))
+                .select((transaction_output_txos::all_columns, txos::all_columns))
+                .first(conn)
+            {
+                Ok(found) => found,
+                Err(diesel::result::Error::NotFound) => {
+                    return Err(WalletDbError::TransactionOutputNotFound(hex::encode(
+                        &encoded_public_key,
+                    )))
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+        let transaction_log = TransactionLog::get(
+            &TransactionID(transaction_output_txo.transaction_log_id.clone()),
+            conn,
+        )?;
+
+        Ok((transaction_log, txo, transaction_output_txo))
+    }
+
     fn update_submitted_block_index(
         &self,
         submitted_block_index: u64,
@@ -307,15 +740,24 @@ impl TransactionLogModel for TransactionLog {
         Ok(())
     }
 
+    fn update_comment(&self, comment: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        diesel::update(self)
+            .set(transaction_logs::comment.eq(comment))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
     fn list_all(
         account_id: Option<String>,
         offset: Option<u64>,
         limit: Option<u64>,
-        min_block_index: Option<u64>,
-        max_block_index: Option<u64>,
+        filter: &TransactionLogFilter,
         conn: &Conn,
     ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletDbError> {
-        use crate::db::schema::transaction_logs;
+        use crate::db::schema::{transaction_logs, transaction_output_txos};
 
         let mut query = transaction_logs::table.into_boxed();
 
@@ -323,21 +765,77 @@ impl TransactionLogModel for TransactionLog {
             query = query.filter(transaction_logs::account_id.eq(account_id));
         }
 
-        if let (Some(o), Some(l)) = (offset, limit) {
-            query = query.offset(o as i64).limit(l as i64);
+        // SQLite only accepts an offset along with a limit.
+        if let Some(l) = limit {
+            query = query.limit(l as i64).offset(offset.unwrap_or(0) as i64);
         }
 
-        if let Some(min_block_index) = min_block_index {
+        if let Some(min_block_index) = filter.min_block_index {
             query =
                 query.filter(transaction_logs::finalized_block_index.ge(min_block_index as i64));
         }
 
-        if let Some(max_block_index) = max_block_index {
+        if let Some(max_block_index) = filter.max_block_index {
             query =
                 query.filter(transaction_logs::finalized_block_index.le(max_block_index as i64));
         }
 
-        let transaction_logs: Vec<TransactionLog> = query.order(transaction_logs::id).load(conn)?;
+        if let Some(token_id) = filter.token_id {
+            query = query.filter(transaction_logs::fee_token_id.eq(token_id as i64));
+        }
+
+        if let Some(recipient_public_address_b58) = &filter.recipient_public_address_b58 {
+            query = query.filter(
+                transaction_logs::id.eq_any(
+                    transaction_output_txos::table
+                        .filter(
+                            transaction_output_txos::recipient_public_address_b58
+                                .eq(recipient_public_address_b58.clone()),
+                        )
+                        .filter(transaction_output_txos::is_change.eq(false))
+                        .select(transaction_output_txos::transaction_log_id),
+                ),
+            );
+        }
+
+        if let Some(status) = filter.status {
+            query = query.filter(diesel::dsl::sql::<Bool>(status.sql_condition()));
+        }
+
+        // The values are integers, so they are safe to write into the query.
+        if let Some(min_value) = filter.min_value {
+            query = query.filter(diesel::dsl::sql::<Bool>(&format!(
+                "{} >= {}",
+                TRANSACTION_LOG_VALUE, min_value as i64
+            )));
+        }
+
+        if let Some(max_value) = filter.max_value {
+            query = query.filter(diesel::dsl::sql::<Bool>(&format!(
+                "{} <= {}",
+                TRANSACTION_LOG_VALUE, max_value as i64
+            )));
+        }
+
+        let direction = if filter.descending { "DESC" } else { "ASC" };
+        query = match filter.order_by {
+            TransactionLogOrderBy::Id if filter.descending => {
+                query.order(transaction_logs::id.desc())
+            }
+            TransactionLogOrderBy::Id => query.order(transaction_logs::id.asc()),
+            TransactionLogOrderBy::BlockIndex => query.order(diesel::dsl::sql::<BigInt>(&format!(
+                "COALESCE(finalized_block_index, submitted_block_index) {0}, id {0}",
+                direction
+            ))),
+            // Logs created in the same second are in the order they were
+            // inserted.
+            TransactionLogOrderBy::CreatedAt => query.order(diesel::dsl::sql::<BigInt>(&format!(
+                "created_at {0}, rowid {0}",
+                direction
+            ))),
+        };
+
+        let transaction_logs: Vec<TransactionLog> = query.load(conn)?;
 
         let results = transaction_logs
             .into_iter()
@@ -351,9 +849,41 @@ impl TransactionLogModel for TransactionLog {
         Ok(results)
     }
 
+    fn list_for_account_after(
+        account_id_hex: &str,
+        after_transaction_log_id: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        let mut query = transaction_logs::table
+            .into_boxed()
+            .filter(transaction_logs::account_id.eq(account_id_hex));
+
+        if let Some(after_transaction_log_id) = after_transaction_log_id {
+            query = query.filter(transaction_logs::id.gt(after_transaction_log_id));
+        }
+
+        let transaction_logs: Vec<TransactionLog> = query
+            .order(transaction_logs::id)
+            .limit(limit as i64)
+            .load(conn)?;
+
+        transaction_logs
+            .into_iter()
+            .map(|log| {
+                let associated_txos = log.get_associated_txos(conn)?;
+                let value_map = log.value_map(conn)?;
+                Ok((log, associated_txos, value_map))
+            })
+            .collect()
+    }
+
     fn log_built(
         tx_proposal: TxProposal,
         comment: String,
+        memo: &TransactionMemo,
         account_id_hex: &str,
         conn: &Conn,
     ) -> Result<TransactionLog, WalletDbError> {
@@ -374,6 +904,13 @@ impl TransactionLogModel for TransactionLog {
             comment: &comment,
             tx: &tx,
             failed: false,
+            block_version: tx_proposal.block_version.map(|v| *v as i64),
+            reconstructed: false,
+            reconstructed_value: None,
+            recipient_address_hash: None,
+            created_at: Some(unix_timestamp_now()),
+            payment_request_id: memo.payment_request_id().map(|id| id as i64),
+            burn_redemption_memo: memo.burn_redemption_memo(),
         };
 
         diesel::insert_into(crate::db::schema::transaction_logs::table)
@@ -383,9 +920,11 @@ impl TransactionLogModel for TransactionLog {
         for txo in tx_proposal.input_txos.iter() {
             let txo_id = TxoID::from(&txo.tx_out);
             Txo::update_key_image(&txo_id.to_string(), &txo.key_image, None, conn)?;
+            let ring = InputRing::from_tx(&tx_proposal.tx, &txo.tx_out).map(|ring| ring.to_bytes());
             let transaction_input_txo = NewTransactionInputTxo {
                 transaction_log_id: &transaction_log_id.to_string(),
                 txo_id: &txo_id.to_string(),
+                ring: ring.as_deref(),
             };
 
             diesel::insert_into(crate::db::schema::transaction_input_txos::table)
@@ -416,10 +955,16 @@ impl TransactionLogModel for TransactionLog {
 
         let transaction_log_id = TransactionID::from(&tx_proposal.tx);
         let tx = mc_util_serial::encode(&tx_proposal.tx);
+        // A burn signed elsewhere was not logged when it was built, so its
+        // memo is read from the burn output.
+        let burn_redemption_memo = tx_proposal.burn_redemption_memo();
 
         match TransactionLog::get(&transaction_log_id, conn) {
             Ok(transaction_log) => {
                 transaction_log.update_submitted_block_index(block_index, conn)?;
+                if !comment.is_empty() {
+                    transaction_log.update_comment(&comment, conn)?;
+                }
             }
 
             Err(WalletDbError::TransactionLogNotFound(_)) => {
@@ -434,6 +979,13 @@ impl TransactionLogModel for TransactionLog {
                     comment: &comment,
                     tx: &tx,
                     failed: false,
+                    block_version: tx_proposal.block_version.map(|v| *v as i64),
+                    reconstructed: false,
+                    reconstructed_value: None,
+                    recipient_address_hash: None,
+                    created_at: Some(unix_timestamp_now()),
+                    payment_request_id: None,
+                    burn_redemption_memo: burn_redemption_memo.as_ref().map(|memo| &memo[..]),
                 };
 
                 diesel::insert_into(crate::db::schema::transaction_logs::table)
@@ -443,9 +995,12 @@ impl TransactionLogModel for TransactionLog {
                 for input_txo in tx_proposal.input_txos.iter() {
                     let txo_id = TxoID::from(&input_txo.tx_out);
                     Txo::update_key_image(&txo_id.to_string(), &input_txo.key_image, None, conn)?;
+                    let ring = InputRing::from_tx(&tx_proposal.tx, &input_txo.tx_out)
+                        .map(|ring| ring.to_bytes());
                     let transaction_input_txo = NewTransactionInputTxo {
                         transaction_log_id: &transaction_log_id.to_string(),
                         txo_id: &txo_id.to_string(),
+                        ring: ring.as_deref(),
                     };
 
                     diesel::insert_into(crate::db::schema::transaction_input_txos::table)
@@ -470,11 +1025,158 @@ impl TransactionLogModel for TransactionLog {
         TransactionLog::get(&transaction_log_id, conn)
     }
 
+    fn log_reconstructed(
+        account_id_hex: &str,
+        reconstructed: &ReconstructedTransaction,
+        conn: &Conn,
+    ) -> Result<TransactionLog, WalletDbError> {
+        let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
+        let change_address_b58 = account.change_subaddress(conn)?.public_address_b58;
+
+        let id_seed = format!(
+            "{}/{}/{}",
+            account_id_hex, reconstructed.block_index, *reconstructed.fee.token_id
+        );
+        let transaction_log_id = TransactionID(hex::encode(
+            id_seed.digest32::<MerlinTranscript>(b"reconstructed_transaction_log"),
+        ));
+
+        match TransactionLog::get(&transaction_log_id, conn) {
+            Ok(transaction_log) => return Ok(transaction_log),
+            Err(WalletDbError::TransactionLogNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let new_transaction_log = NewTransactionLog {
+            id: &transaction_log_id.to_string(),
+            account_id: account_id_hex,
+            fee_value: reconstructed.fee.value as i64,
+            fee_token_id: *reconstructed.fee.token_id as i64,
+            submitted_block_index: Some(reconstructed.block_index as i64),
+            tombstone_block_index: None,
+            finalized_block_index: Some(reconstructed.block_index as i64),
+            comment: "",
+            tx: &[],
+            failed: false,
+            block_version: Some(reconstructed.block_version as i64),
+            reconstructed: true,
+            reconstructed_value: Some(reconstructed.value as i64),
+            recipient_address_hash: reconstructed.recipient_address_hash.as_deref(),
+            created_at: Some(unix_timestamp_now()),
+            payment_request_id: None,
+            burn_redemption_memo: None,
+        };
+
+        diesel::insert_into(crate::db::schema::transaction_logs::table)
+            .values(&new_transaction_log)
+            .execute(conn)?;
+
+        for input_txo in reconstructed.inputs.iter() {
+            let transaction_input_txo = NewTransactionInputTxo {
+                transaction_log_id: &transaction_log_id.to_string(),
+                txo_id: &input_txo.id,
+                ring: None,
+            };
+
+            diesel::insert_into(crate::db::schema::transaction_input_txos::table)
+                .values(&transaction_input_txo)
+                .execute(conn)?;
+        }
+
+        for change_txo in reconstructed.change.iter() {
+            let transaction_output_txo = NewTransactionOutputTxo {
+                transaction_log_id: &transaction_log_id.to_string(),
+                txo_id: &change_txo.id,
+                recipient_public_address_b58: &change_address_b58,
+                is_change: true,
+                public_key: Some(&change_txo.public_key),
+            };
+
+            diesel::insert_into(crate::db::schema::transaction_output_txos::table)
+                .values(&transaction_output_txo)
+                .execute(conn)?;
+        }
+
+        TransactionLog::get(&transaction_log_id, conn)
+    }
+
+    fn list_associated_txo_ids(
+        account_id_hex: &str,
+        conn: &Conn,
+    ) -> Result<HashSet<String>, WalletDbError> {
+        use crate::db::schema::{
+            transaction_input_txos, transaction_logs, transaction_output_txos,
+        };
+
+        let input_txo_ids: Vec<String> = transaction_input_txos::table
+            .inner_join(transaction_logs::table)
+            .filter(transaction_logs::account_id.eq(account_id_hex))
+            .select(transaction_input_txos::txo_id)
+            .load(conn)?;
+
+        let output_txo_ids: Vec<String> = transaction_output_txos::table
+            .inner_join(transaction_logs::table)
+            .filter(transaction_logs::account_id.eq(account_id_hex))
+            .select(transaction_output_txos::txo_id)
+            .load(conn)?;
+
+        Ok(input_txo_ids.into_iter().chain(output_txo_ids).collect())
+    }
+
+    fn count_pending_by_account(conn: &Conn) -> Result<HashMap<String, u64>, WalletDbError> {
+        use crate::db::schema::{accounts, transaction_logs};
+
+        let counts: Vec<(String, i64)> = transaction_logs::table
+            .inner_join(accounts::table)
+            .filter(accounts::removed_at.is_null())
+            .filter(transaction_logs::failed.eq(false))
+            .filter(transaction_logs::finalized_block_index.is_null())
+            .filter(transaction_logs::submitted_block_index.is_not_null())
+            .group_by(transaction_logs::account_id)
+            .select((transaction_logs::account_id, diesel::dsl::count_star()))
+            .load(conn)?;
+
+        Ok(counts
+            .into_iter()
+            .map(|(account_id, count)| (account_id, count as u64))
+            .collect())
+    }
+
+    fn get_fee_totals(
+        account_id_hex: &str,
+        token_id: Option<u64>,
+        min_block_index: Option<u64>,
+        max_block_index: Option<u64>,
+        group_by_block: bool,
+        conn: &Conn,
+    ) -> Result<Vec<FeeTotal>, WalletDbError> {
+        let token_id = token_id.map(|token_id| token_id as i64);
+        let sums: Vec<FeeSum> = diesel::sql_query(FEE_TOTALS_QUERY)
+            .bind::<Bool, _>(group_by_block)
+            .bind::<Text, _>(account_id_hex)
+            .bind::<BigInt, _>(min_block_index.unwrap_or(0) as i64)
+            .bind::<BigInt, _>(max_block_index.map_or(i64::MAX, |index| index as i64))
+            .bind::<Nullable<BigInt>, _>(token_id)
+            .bind::<Nullable<BigInt>, _>(token_id)
+            .load(conn)?;
+
+        Ok(sums
+            .into_iter()
+            .map(|sum| FeeTotal {
+                token_id: TokenId::from(sum.fee_token_id as u64),
+                block_index: sum.block_index.map(|index| index as u64),
+                value: sum.fee_total as u64 as u128,
+            })
+            .collect())
+    }
+
     fn delete_all_for_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError> {
         use crate::db::schema::{
             transaction_input_txos, transaction_logs, transaction_output_txos,
         };
 
+        MonitoredTxo::delete_all_for_account(account_id_hex, conn)?;
+
         let transaction_input_txos: Vec<TransactionInputTxo> = transaction_input_txos::table
             .inner_join(transaction_logs::table)
             .filter(transaction_logs::account_id.eq(account_id_hex))
@@ -529,7 +1231,27 @@ impl TransactionLogModel for TransactionLog {
         Ok(())
     }
 
+    fn update_pending_to_succeeded(
+        transaction_log_id: &str,
+        finalized_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        diesel::update(
+            transaction_logs::table
+                .filter(transaction_logs::id.eq(transaction_log_id))
+                .filter(transaction_logs::failed.eq(false))
+                .filter(transaction_logs::finalized_block_index.is_null()),
+        )
+        .set((transaction_logs::finalized_block_index.eq(finalized_block_index as i64),))
+        .execute(conn)?;
+
+        Ok(())
+    }
+
     fn update_pending_exceeding_tombstone_block_index_to_failed(
+        account_id_hex: &str,
         block_index: u64,
         conn: &Conn,
     ) -> Result<(), WalletDbError> {
@@ -537,6 +1259,7 @@ impl TransactionLogModel for TransactionLog {
 
         diesel::update(
             transaction_logs::table
+                .filter(transaction_logs::account_id.eq(account_id_hex))
                 .filter(transaction_logs::tombstone_block_index.lt(block_index as i64))
                 .filter(transaction_logs::failed.eq(false))
                 .filter(transaction_logs::finalized_block_index.is_null()),
@@ -547,7 +1270,111 @@ impl TransactionLogModel for TransactionLog {
         Ok(())
     }
 
+    fn update_failed_to_succeeded(
+        &self,
+        finalized_block_index: u64,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        diesel::update(
+            transaction_logs::table
+                .filter(transaction_logs::id.eq(&self.id))
+                .filter(transaction_logs::failed.eq(true)),
+        )
+        .set((
+            transaction_logs::failed.eq(false),
+            transaction_logs::finalized_block_index.eq(finalized_block_index as i64),
+        ))
+        .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn update_auto_rebuild(
+        &self,
+        attempts_left: u32,
+        fee_bump_percent: u32,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        diesel::update(transaction_logs::table.filter(transaction_logs::id.eq(&self.id)))
+            .set((
+                transaction_logs::auto_rebuild_attempts_left.eq(attempts_left as i32),
+                transaction_logs::auto_rebuild_fee_bump_percent.eq(fee_bump_percent as i32),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn update_superseded_by(
+        &self,
+        transaction_log_id: &str,
+        conn: &Conn,
+    ) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        diesel::update(transaction_logs::table.filter(transaction_logs::id.eq(&self.id)))
+            .set(transaction_logs::superseded_by.eq(transaction_log_id))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    fn list_awaiting_rebuild(conn: &Conn) -> Result<Vec<TransactionLog>, WalletDbError> {
+        use crate::db::schema::{accounts, transaction_logs};
+
+        Ok(transaction_logs::table
+            .inner_join(accounts::table)
+            .filter(accounts::removed_at.is_null())
+            .filter(transaction_logs::failed.eq(true))
+            .filter(transaction_logs::reconstructed.eq(false))
+            .filter(transaction_logs::superseded_by.is_null())
+            .filter(transaction_logs::auto_rebuild_attempts_left.gt(0))
+            .order(transaction_logs::tombstone_block_index.asc())
+            .select(transaction_logs::all_columns)
+            .load(conn)?)
+    }
+
+    fn get_rebuild_chain(&self, conn: &Conn) -> Result<Vec<String>, WalletDbError> {
+        use crate::db::schema::transaction_logs;
+
+        let mut chain = vec![self.id.clone()];
+        loop {
+            let previous: Option<String> = transaction_logs::table
+                .filter(transaction_logs::superseded_by.eq(&chain[0]))
+                .select(transaction_logs::id)
+                .first(conn)
+                .optional()?;
+            match previous {
+                Some(previous) if !chain.contains(&previous) => chain.insert(0, previous),
+                _ => break,
+            }
+        }
+
+        let mut superseded_by = self.superseded_by.clone();
+        while let Some(next) = superseded_by {
+            if chain.contains(&next) {
+                break;
+            }
+            superseded_by = TransactionLog::get(&TransactionID(next.clone()), conn)?.superseded_by;
+            chain.push(next);
+        }
+
+        Ok(chain)
+    }
+
     fn value_for_token_id(&self, token_id: TokenId, conn: &Conn) -> Result<u64, WalletDbError> {
+        // Reconstructed logs do not know their outputs, only the value sent.
+        if self.reconstructed {
+            return Ok(match self.reconstructed_value {
+                Some(value) if self.fee_token_id as u64 == *token_id => value as u64,
+                _ => 0,
+            });
+        }
+
         let associated_txos = self.get_associated_txos(conn)?;
 
         let output_total = associated_txos
@@ -561,9 +1388,16 @@ impl TransactionLogModel for TransactionLog {
     }
 
     fn value_map(&self, conn: &Conn) -> Result<ValueMap, WalletDbError> {
+        let mut value_map: HashMap<TokenId, u64> = HashMap::default();
+        if self.reconstructed {
+            if let Some(value) = self.reconstructed_value {
+                value_map.insert(TokenId::from(self.fee_token_id as u64), value as u64);
+            }
+            return Ok(ValueMap(value_map));
+        }
+
         let associated_txos = self.get_associated_txos(conn)?;
 
-        let mut value_map: HashMap<TokenId, u64> = HashMap::default();
         for (txo, _) in associated_txos.outputs.iter() {
             let token_id = TokenId::from(txo.token_id as u64);
             let value = value_map.entry(token_id).or_insert(0);
@@ -575,7 +1409,7 @@ impl TransactionLogModel for TransactionLog {
 
 #[cfg(test)]
 mod tests {
-    use mc_account_keys::{PublicAddress, CHANGE_SUBADDRESS_INDEX};
+    use mc_account_keys::{AccountKey, PublicAddress, CHANGE_SUBADDRESS_INDEX};
     use mc_common::logger::{test_with_logger, Logger};
     use mc_ledger_db::Ledger;
     use mc_transaction_core::{tokens::Mob, Token};
@@ -939,6 +1773,433 @@ mod tests {
         );
     }
 
+    #[test_with_logger]
+    fn test_get_fee_totals(logger: Logger) {
+        use crate::db::schema::transaction_logs;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB, 70 * MOB, 70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id_hex = AccountID::from(&account_key).to_string();
+
+        // Submit three transactions, each spending one of the seeded txos.
+        let conn = wallet_db.get_conn().unwrap();
+        let mut tx_logs = Vec::new();
+        for _ in 0..3 {
+            let (recipient, mut builder) =
+                builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+            builder.add_recipient(recipient, 50 * MOB, Mob::ID).unwrap();
+            builder.set_tombstone(0).unwrap();
+            builder.select_txos(&conn, None).unwrap();
+            let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+            let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+            let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+
+            tx_logs.push(
+                TransactionLog::log_submitted(
+                    &tx_proposal,
+                    ledger_db.num_blocks().unwrap(),
+                    "".to_string(),
+                    &account_id_hex,
+                    &conn,
+                )
+                .unwrap(),
+            );
+        }
+
+        // Nothing has been paid while the transactions are pending.
+        assert!(
+            TransactionLog::get_fee_totals(&account_id_hex, None, None, None, false, &conn)
+                .unwrap()
+                .is_empty()
+        );
+
+        // The first two land in different blocks, and the third fails.
+        TransactionLog::update_pending_to_succeeded(&tx_logs[0].id, 20, &conn).unwrap();
+        TransactionLog::update_pending_to_succeeded(&tx_logs[1].id, 30, &conn).unwrap();
+        diesel::update(transaction_logs::table.filter(transaction_logs::id.eq(&tx_logs[2].id)))
+            .set(transaction_logs::failed.eq(true))
+            .execute(&conn)
+            .unwrap();
+
+        let totals =
+            TransactionLog::get_fee_totals(&account_id_hex, None, None, None, false, &conn)
+                .unwrap();
+        assert_eq!(
+            totals,
+            vec![FeeTotal {
+                token_id: Mob::ID,
+                block_index: None,
+                value: 2 * Mob::MINIMUM_FEE as u128,
+            }]
+        );
+
+        let totals =
+            TransactionLog::get_fee_totals(&account_id_hex, None, None, None, true, &conn).unwrap();
+        assert_eq!(
+            totals,
+            vec![
+                FeeTotal {
+                    token_id: Mob::ID,
+                    block_index: Some(20),
+                    value: Mob::MINIMUM_FEE as u128,
+                },
+                FeeTotal {
+                    token_id: Mob::ID,
+                    block_index: Some(30),
+                    value: Mob::MINIMUM_FEE as u128,
+                },
+            ]
+        );
+
+        // The block range is inclusive.
+        let totals =
+            TransactionLog::get_fee_totals(&account_id_hex, None, Some(21), Some(30), false, &conn)
+                .unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].value, Mob::MINIMUM_FEE as u128);
+
+        // No fees were paid in other tokens.
+        assert!(
+            TransactionLog::get_fee_totals(&account_id_hex, Some(1), None, None, false, &conn)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test_with_logger]
+    fn test_list_all_filtered(logger: Logger) {
+        use crate::db::schema::transaction_logs;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB; 5],
+            &mut rng,
+            &logger,
+        );
+        let account_id_hex = AccountID::from(&account_key).to_string();
+        let conn = wallet_db.get_conn().unwrap();
+
+        let recipient_a = AccountKey::random(&mut rng).default_subaddress();
+        let recipient_b = AccountKey::random(&mut rng).default_subaddress();
+        let recipient_a_b58 = b58_encode_public_address(&recipient_a).unwrap();
+        let recipient_b_b58 = b58_encode_public_address(&recipient_b).unwrap();
+
+        // Send 10, 20, 30, 40 and 50 MOB, alternating between the recipients.
+        // The last is only built, and the rest are submitted.
+        let mut ids = Vec::new();
+        for (i, recipient) in [
+            &recipient_a,
+            &recipient_b,
+            &recipient_a,
+            &recipient_b,
+            &recipient_a,
+        ]
+        .iter()
+        .enumerate()
+        {
+            let (_, mut builder) = builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+            builder
+                .add_recipient((*recipient).clone(), (i as u64 + 1) * 10 * MOB, Mob::ID)
+                .unwrap();
+            builder.set_tombstone(0).unwrap();
+            builder.select_txos(&conn, None).unwrap();
+            let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+            let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+            let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+
+            let transaction_log = if i < 4 {
+                TransactionLog::log_submitted(
+                    &tx_proposal,
+                    ledger_db.num_blocks().unwrap(),
+                    "".to_string(),
+                    &account_id_hex,
+                    &conn,
+                )
+                .unwrap()
+            } else {
+                TransactionLog::log_built(
+                    tx_proposal,
+                    "".to_string(),
+                    &TransactionMemo::RTH,
+                    &account_id_hex,
+                    &conn,
+                )
+                .unwrap()
+            };
+            ids.push(transaction_log.id);
+        }
+
+        // The first two land, the third fails, and the fourth is pending.
+        TransactionLog::update_pending_to_succeeded(&ids[0], 20, &conn).unwrap();
+        TransactionLog::update_pending_to_succeeded(&ids[1], 30, &conn).unwrap();
+        diesel::update(transaction_logs::table.filter(transaction_logs::id.eq(&ids[2])))
+            .set(transaction_logs::failed.eq(true))
+            .execute(&conn)
+            .unwrap();
+
+        // A transaction of 60 MOB reconstructed from the ledger.
+        let reconstructed = TransactionLog::log_reconstructed(
+            &account_id_hex,
+            &ReconstructedTransaction {
+                block_index: 25,
+                block_version: 3,
+                fee: Amount::new(Mob::MINIMUM_FEE, Mob::ID),
+                value: 60 * MOB,
+                recipient_address_hash: None,
+                inputs: vec![],
+                change: vec![],
+            },
+            &conn,
+        )
+        .unwrap();
+        ids.push(reconstructed.id);
+
+        let list = |offset: Option<u64>, limit: Option<u64>, filter: TransactionLogFilter| {
+            TransactionLog::list_all(Some(account_id_hex.clone()), offset, limit, &filter, &conn)
+                .unwrap()
+                .into_iter()
+                .map(|(transaction_log, _, _)| transaction_log.id)
+                .collect::<Vec<_>>()
+        };
+        // The ids of the given logs, in the default order.
+        let expected = |indices: &[usize]| {
+            let mut expected: Vec<String> = indices.iter().map(|i| ids[*i].clone()).collect();
+            expected.sort();
+            expected
+        };
+
+        assert_eq!(
+            list(None, None, TransactionLogFilter::default()),
+            expected(&[0, 1, 2, 3, 4, 5])
+        );
+
+        // Each filter on its own.
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    recipient_public_address_b58: Some(recipient_a_b58.clone()),
+                    ..Default::default()
+                }
+            ),
+            expected(&[0, 2, 4])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    min_value: Some(20 * MOB),
+                    ..Default::default()
+                }
+            ),
+            expected(&[1, 2, 3, 4, 5])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    max_value: Some(30 * MOB),
+                    ..Default::default()
+                }
+            ),
+            expected(&[0, 1, 2])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    token_id: Some(*Mob::ID),
+                    ..Default::default()
+                }
+            ),
+            expected(&[0, 1, 2, 3, 4, 5])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    token_id: Some(1),
+                    ..Default::default()
+                }
+            ),
+            expected(&[])
+        );
+        for (status, indices) in [
+            (TransactionLogStatus::Built, vec![4]),
+            (TransactionLogStatus::Pending, vec![3]),
+            (TransactionLogStatus::Succeeded, vec![0, 1, 5]),
+            (TransactionLogStatus::Failed, vec![2]),
+            (TransactionLogStatus::Reconstructed, vec![5]),
+        ] {
+            assert_eq!(
+                list(
+                    None,
+                    None,
+                    TransactionLogFilter {
+                        status: Some(status),
+                        ..Default::default()
+                    }
+                ),
+                expected(&indices),
+                "{:?}",
+                status
+            );
+        }
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    min_block_index: Some(21),
+                    ..Default::default()
+                }
+            ),
+            expected(&[1, 5])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    max_block_index: Some(25),
+                    ..Default::default()
+                }
+            ),
+            expected(&[0, 5])
+        );
+
+        // Filters combine with AND.
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    recipient_public_address_b58: Some(recipient_a_b58.clone()),
+                    status: Some(TransactionLogStatus::Succeeded),
+                    ..Default::default()
+                }
+            ),
+            expected(&[0])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    recipient_public_address_b58: Some(recipient_b_b58),
+                    min_value: Some(30 * MOB),
+                    ..Default::default()
+                }
+            ),
+            expected(&[3])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    min_value: Some(20 * MOB),
+                    max_value: Some(40 * MOB),
+                    status: Some(TransactionLogStatus::Failed),
+                    ..Default::default()
+                }
+            ),
+            expected(&[2])
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    status: Some(TransactionLogStatus::Succeeded),
+                    max_block_index: Some(25),
+                    min_value: Some(50 * MOB),
+                    ..Default::default()
+                }
+            ),
+            expected(&[5])
+        );
+
+        // Ordering, and pagination over the order.
+        let succeeded = TransactionLogFilter {
+            status: Some(TransactionLogStatus::Succeeded),
+            order_by: TransactionLogOrderBy::BlockIndex,
+            ..Default::default()
+        };
+        assert_eq!(
+            list(None, None, succeeded.clone()),
+            vec![ids[0].clone(), ids[5].clone(), ids[1].clone()]
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    descending: true,
+                    ..succeeded
+                }
+            ),
+            vec![ids[1].clone(), ids[5].clone(), ids[0].clone()]
+        );
+        let to_a = TransactionLogFilter {
+            recipient_public_address_b58: Some(recipient_a_b58),
+            order_by: TransactionLogOrderBy::CreatedAt,
+            ..Default::default()
+        };
+        assert_eq!(
+            list(None, None, to_a.clone()),
+            vec![ids[0].clone(), ids[2].clone(), ids[4].clone()]
+        );
+        assert_eq!(
+            list(
+                None,
+                None,
+                TransactionLogFilter {
+                    descending: true,
+                    ..to_a.clone()
+                }
+            ),
+            vec![ids[4].clone(), ids[2].clone(), ids[0].clone()]
+        );
+        assert_eq!(list(Some(1), Some(1), to_a), vec![ids[2].clone()]);
+
+        // Other accounts' logs never match.
+        assert!(TransactionLog::list_all(
+            Some(AccountID::from(&AccountKey::random(&mut rng)).to_string()),
+            None,
+            None,
+            &TransactionLogFilter::default(),
+            &conn,
+        )
+        .unwrap()
+        .is_empty());
+    }
+
     // Test that transaction logging can handle submitting a value greater than
     // i64::Max Note: i64::Max is 9_223_372_036_854_775_807, or about 9.2M MOB.
     // The biggest MOB amount that can be represented on chain is u64::MAX,
@@ -0,0 +1,176 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! DB impl for the TransactionReport model.
+
+use crate::db::{
+    models::{NewTransactionReport, TransactionReport},
+    Conn, WalletDbError,
+};
+use diesel::prelude::*;
+
+pub trait TransactionReportModel {
+    /// Queue a report of a submitted transaction for delivery.
+    fn create(
+        transaction_log_id: &str,
+        account_id_hex: &str,
+        record: &str,
+        created_at: i64,
+        conn: &Conn,
+    ) -> Result<TransactionReport, WalletDbError>;
+
+    /// Get the report for a transaction log.
+    fn get(transaction_log_id: &str, conn: &Conn) -> Result<TransactionReport, WalletDbError>;
+
+    /// List reports which have not yet been delivered, oldest first.
+    fn list_undelivered(
+        account_id_hex: Option<&str>,
+        conn: &Conn,
+    ) -> Result<Vec<TransactionReport>, WalletDbError>;
+
+    /// Record that the report was delivered, in seconds since the unix epoch.
+    fn mark_delivered(&self, delivered_at: i64, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// Record a failed delivery attempt.
+    fn mark_failed(&self, error: &str, conn: &Conn) -> Result<(), WalletDbError>;
+}
+
+impl TransactionReportModel for TransactionReport {
+    fn create(
+        transaction_log_id: &str,
+        account_id_hex: &str,
+        record: &str,
+        created_at: i64,
+        conn: &Conn,
+    ) -> Result<TransactionReport, WalletDbError> {
+        use crate::db::schema::transaction_reports;
+
+        let new_transaction_report = NewTransactionReport {
+            transaction_log_id,
+            account_id: account_id_hex,
+            record,
+            created_at,
+        };
+
+        diesel::insert_into(transaction_reports::table)
+            .values(&new_transaction_report)
+            .execute(conn)?;
+
+        TransactionReport::get(transaction_log_id, conn)
+    }
+
+    fn get(transaction_log_id: &str, conn: &Conn) -> Result<TransactionReport, WalletDbError> {
+        use crate::db::schema::transaction_reports;
+
+        match transaction_reports::table
+            .find(transaction_log_id)
+            .get_result::<TransactionReport>(conn)
+        {
+            Ok(r) => Ok(r),
+            // Match on NotFound to get a more informative NotFound Error
+            Err(diesel::result::Error::NotFound) => Err(WalletDbError::TransactionReportNotFound(
+                transaction_log_id.to_string(),
+            )),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn list_undelivered(
+        account_id_hex: Option<&str>,
+        conn: &Conn,
+    ) -> Result<Vec<TransactionReport>, WalletDbError> {
+        use crate::db::schema::transaction_reports;
+
+        let mut query = transaction_reports::table
+            .filter(transaction_reports::delivered_at.is_null())
+            .order((
+                transaction_reports::created_at.asc(),
+                transaction_reports::transaction_log_id.asc(),
+            ))
+            .into_boxed();
+        if let Some(account_id_hex) = account_id_hex {
+            query = query.filter(transaction_reports::account_id.eq(account_id_hex));
+        }
+
+        Ok(query.load(conn)?)
+    }
+
+    fn mark_delivered(&self, delivered_at: i64, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_reports;
+
+        diesel::update(transaction_reports::table.find(&self.transaction_log_id))
+            .set(transaction_reports::delivered_at.eq(Some(delivered_at)))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn mark_failed(&self, error: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::transaction_reports;
+
+        diesel::update(transaction_reports::table.find(&self.transaction_log_id))
+            .set((
+                transaction_reports::attempts.eq(transaction_reports::attempts + 1),
+                transaction_reports::last_error.eq(Some(error)),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::WalletDbTestContext;
+    use mc_common::logger::{test_with_logger, Logger};
+
+    #[test_with_logger]
+    fn test_transaction_report_outbox(logger: Logger) {
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger);
+        let conn = wallet_db.get_conn().unwrap();
+
+        TransactionReport::create("log-2", "account-a", "{}", 200, &conn).unwrap();
+        TransactionReport::create("log-1", "account-a", "{}", 100, &conn).unwrap();
+        TransactionReport::create("log-3", "account-b", "{}", 300, &conn).unwrap();
+
+        // A transaction log is only reported once.
+        assert!(TransactionReport::create("log-1", "account-a", "{}", 100, &conn).is_err());
+
+        let undelivered = TransactionReport::list_undelivered(None, &conn).unwrap();
+        let ids: Vec<&str> = undelivered
+            .iter()
+            .map(|r| r.transaction_log_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["log-1", "log-2", "log-3"]);
+
+        let for_account = TransactionReport::list_undelivered(Some("account-b"), &conn).unwrap();
+        assert_eq!(for_account.len(), 1);
+        assert_eq!(for_account[0].transaction_log_id, "log-3");
+
+        undelivered[0]
+            .mark_failed("connection refused", &conn)
+            .unwrap();
+        undelivered[0].mark_failed("timed out", &conn).unwrap();
+        let failed = TransactionReport::get("log-1", &conn).unwrap();
+        assert_eq!(failed.attempts, 2);
+        assert_eq!(failed.last_error, Some("timed out".to_string()));
+        assert_eq!(failed.delivered_at, None);
+
+        failed.mark_delivered(400, &conn).unwrap();
+        assert_eq!(
+            TransactionReport::get("log-1", &conn).unwrap().delivered_at,
+            Some(400)
+        );
+        assert_eq!(
+            TransactionReport::list_undelivered(None, &conn)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        match TransactionReport::get("log-4", &conn) {
+            Err(WalletDbError::TransactionReportNotFound(_)) => {}
+            Ok(_) => panic!("Should not find a report which was never created"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+}
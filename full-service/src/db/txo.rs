@@ -4,7 +4,9 @@
 
 use diesel::{
     dsl::{count, exists, not},
+    expression::SqlLiteral,
     prelude::*,
+    sql_types::{BigInt, Text},
 };
 use mc_account_keys::AccountKey;
 use mc_common::HashMap;
@@ -13,11 +15,12 @@ use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
 use mc_ledger_db::{Ledger, LedgerDB};
 use mc_transaction_core::{
     constants::MAX_INPUTS,
+    onetime_keys::recover_public_subaddress_spend_key,
     ring_signature::KeyImage,
     tx::{TxOut, TxOutConfirmationNumber, TxOutMembershipProof},
     Amount, TokenId,
 };
-use std::{fmt, str::FromStr};
+use std::{collections::BTreeMap, convert::TryFrom, fmt, str::FromStr};
 
 use crate::{
     db::{
@@ -25,7 +28,7 @@ use crate::{
         assigned_subaddress::AssignedSubaddressModel,
         models::{Account, AssignedSubaddress, NewTransactionOutputTxo, NewTxo, Txo},
         transaction_log::TransactionID,
-        Conn, WalletDbError,
+        Conn, InsufficientFundsAmounts, WalletDbError,
     },
     service::models::tx_proposal::OutputTxo,
     util::b58::b58_encode_public_address,
@@ -104,12 +107,162 @@ pub struct SpendableTxosResult {
     pub max_spendable_in_wallet: u128,
 }
 
+/// The block index from which received txos have fewer than
+/// min_confirmations in a ledger of num_blocks blocks, counting the block a
+/// txo was received in as its first confirmation. None when every txo is
+/// confirmed.
+pub fn unconfirmed_block_index(num_blocks: u64, min_confirmations: u64) -> Option<u64> {
+    if min_confirmations == 0 {
+        return None;
+    }
+    Some((num_blocks + 1).saturating_sub(min_confirmations))
+}
+
+/// The summed value of an account's txos of a single token, by status.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct AccountTokenValues {
+    pub unverified: u128,
+    pub unspent: u128,
+    /// The part of unspent which does not have the account's minimum number
+    /// of confirmations yet.
+    pub unconfirmed: u128,
+    pub pending: u128,
+    pub spent: u128,
+    pub orphaned: u128,
+    /// The value of the largest spendable txos which fit in one transaction,
+    /// before the fee is taken out.
+    pub max_spendable_inputs: u128,
+}
+
+/// A row of the grouped query behind sum_values_by_account_and_token. Values
+/// are summed in two 32 bit halves, so that the sums cannot overflow the
+/// 64 bit integers of SQLite.
+#[derive(QueryableByName)]
+struct TxoValueSum {
+    #[sql_type = "Text"]
+    account_id: String,
+    #[sql_type = "BigInt"]
+    token_id: i64,
+    #[sql_type = "Text"]
+    status: String,
+    #[sql_type = "BigInt"]
+    value_high: i64,
+    #[sql_type = "BigInt"]
+    value_low: i64,
+}
+
+impl TxoValueSum {
+    fn value(&self) -> u128 {
+        ((self.value_high as u128) << 32) + self.value_low as u128
+    }
+}
+
+/// Classifies each txo of the accounts which have not been removed the same
+/// way as the list_* methods for each status, and sums their values per
+/// account, token and status. A txo is in as many statuses as those methods
+/// would return it for. The "any" status only marks which tokens each account
+/// holds.
+const SUM_VALUES_BY_ACCOUNT_AND_TOKEN_QUERY: &str = r#"
+WITH states AS (
+    SELECT
+        txos.account_id AS account_id,
+        txos.token_id AS token_id,
+        txos.value AS value,
+        txos.received_block_index IS NOT NULL AS received,
+        txos.spent_block_index IS NOT NULL AS spent,
+        txos.subaddress_index IS NOT NULL AS has_subaddress,
+        txos.key_image IS NOT NULL AS has_key_image,
+        txos.received_block_index + COALESCE(accounts.min_confirmations, ?) > ? AS unconfirmed,
+        (
+            NOT EXISTS (
+                SELECT 1 FROM transaction_input_txos
+                WHERE transaction_input_txos.txo_id = txos.id
+            ) OR EXISTS (
+                SELECT 1 FROM transaction_input_txos
+                JOIN transaction_logs
+                    ON transaction_logs.id = transaction_input_txos.transaction_log_id
+                WHERE transaction_input_txos.txo_id = txos.id
+                AND (transaction_logs.failed OR transaction_logs.submitted_block_index IS NULL)
+            )
+        ) AS unlocked,
+        EXISTS (
+            SELECT 1 FROM transaction_input_txos
+            JOIN transaction_logs
+                ON transaction_logs.id = transaction_input_txos.transaction_log_id
+            WHERE transaction_input_txos.txo_id = txos.id
+            AND NOT transaction_logs.failed
+            AND transaction_logs.submitted_block_index IS NOT NULL
+        ) AS submitted,
+        EXISTS (
+            SELECT 1 FROM transaction_input_txos
+            JOIN transaction_logs
+                ON transaction_logs.id = transaction_input_txos.transaction_log_id
+            WHERE transaction_input_txos.txo_id = txos.id
+            AND NOT transaction_logs.failed
+            AND transaction_logs.submitted_block_index IS NOT NULL
+            AND transaction_logs.finalized_block_index IS NULL
+        ) AS in_flight
+    FROM txos
+    JOIN accounts ON accounts.id = txos.account_id
+    WHERE accounts.removed_at IS NULL
+),
+statuses AS (
+    SELECT account_id, token_id, 0 AS value, 'any' AS status FROM states
+    UNION ALL
+    SELECT account_id, token_id, value, 'unverified' FROM states
+        WHERE received AND has_subaddress AND NOT has_key_image AND unlocked
+    UNION ALL
+    SELECT account_id, token_id, value, 'unspent' FROM states
+        WHERE received AND has_key_image AND NOT spent AND unlocked
+    UNION ALL
+    SELECT account_id, token_id, value, 'unconfirmed' FROM states
+        WHERE received AND has_key_image AND NOT spent AND unlocked AND unconfirmed
+    UNION ALL
+    SELECT account_id, token_id, value, 'pending' FROM states
+        WHERE has_subaddress AND NOT spent AND in_flight
+    UNION ALL
+    SELECT account_id, token_id, value, 'spent' FROM states
+        WHERE spent
+    UNION ALL
+    SELECT account_id, token_id, value, 'orphaned' FROM states
+        WHERE NOT has_subaddress AND NOT has_key_image
+    UNION ALL
+    SELECT account_id, token_id, value, 'max_spendable_inputs' FROM (
+        SELECT account_id, token_id, value, ROW_NUMBER() OVER (
+            PARTITION BY account_id, token_id ORDER BY value DESC
+        ) AS value_rank
+        FROM states
+        WHERE received AND has_subaddress AND NOT spent AND NOT submitted AND NOT unconfirmed
+    ) WHERE value_rank <= ?
+)
+SELECT
+    account_id,
+    token_id,
+    status,
+    SUM((value >> 32) & 4294967295) AS value_high,
+    SUM(value & 4294967295) AS value_low
+FROM statuses
+GROUP BY account_id, token_id, status
+"#;
+
 impl Txo {
     pub fn amount(&self) -> Amount {
         Amount::new(self.value as u64, TokenId::from(self.token_id as u64))
     }
 }
 
+/// The modification sequence number for the txos changed by a statement, one
+/// more than that of any txo. Every statement which changes a txo sets it, so
+/// that the change is listed by list_modified_since.
+///
+/// It is computed by the statement itself, under SQLite's write lock, so that
+/// a change committed later never has a lower number than one already read.
+/// The maximum is read from the index on modified_seq alone, as the index
+/// shared with account_id can not answer it without a scan.
+pub fn next_modified_seq() -> SqlLiteral<BigInt> {
+    diesel::dsl::sql("(SELECT COALESCE(MAX(modified_seq), 0) + 1 FROM txos)")
+}
+
 pub trait TxoModel {
     /// Upserts a received Txo.
     ///
@@ -119,6 +272,8 @@ pub trait TxoModel {
     /// * `key_image` -
     /// * `value` - The value of the output, in picoMob.
     /// * `received_block_index` - the block at which the Txo was received.
+    /// * `global_index` - the index of the Txo among all tx outs in the
+    ///   ledger, if known.
     /// * `account_id_hex` - the account ID for the account which received this
     ///   Txo.
     /// * `conn` - Sqlite database connection.
@@ -134,6 +289,7 @@ pub trait TxoModel {
         key_image: Option<KeyImage>,
         amount: Amount,
         received_block_index: u64,
+        global_index: Option<u64>,
         account_id_hex: &str,
         conn: &Conn,
     ) -> Result<String, WalletDbError>;
@@ -153,9 +309,17 @@ pub trait TxoModel {
         subaddress_index: Option<u64>,
         key_image: Option<KeyImage>,
         block_index: u64,
+        global_index: Option<u64>,
         conn: &Conn,
     ) -> Result<(), WalletDbError>;
 
+    /// Record the index of a Txo among all tx outs in the ledger.
+    fn update_global_index(&self, global_index: u64, conn: &Conn) -> Result<(), WalletDbError>;
+
+    /// Mark a Txo as changed, so that it is listed again by
+    /// list_modified_since.
+    fn touch(txo_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
+
     /// Update a Txo's status to spent
     fn update_spent_block_index(
         txo_id_hex: &str,
@@ -193,6 +357,39 @@ pub trait TxoModel {
         conn: &Conn,
     ) -> Result<Vec<Txo>, WalletDbError>;
 
+    /// Get a page of the Txos associated with an account, ordered by id and
+    /// starting after the given Txo id.
+    fn list_for_account_after(
+        account_id_hex: &str,
+        after_txo_id_hex: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError>;
+
+    /// Get a page of the Txos whose index in the ledger is not known, ordered
+    /// by id and starting after the given Txo id.
+    fn list_missing_global_index(
+        after_txo_id_hex: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError>;
+
+    /// Get a page of the Txos of an account which were created or changed
+    /// after the given position, ordered by modified_seq and then by id.
+    ///
+    /// # Arguments
+    /// * `account_id_hex` - The account whose Txos to list.
+    /// * `after` - The (modified_seq, id) of the last Txo of the previous page,
+    ///   or None to start with the first.
+    /// * `limit` - The largest number of Txos to return.
+    /// * `conn` - Sqlite database connection.
+    fn list_modified_since(
+        account_id_hex: &str,
+        after: Option<(i64, &str)>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError>;
+
     #[allow(clippy::too_many_arguments)]
     fn list_for_address(
         assigned_subaddress_b58: &str,
@@ -212,6 +409,15 @@ pub trait TxoModel {
         conn: &Conn,
     ) -> Result<HashMap<KeyImage, String>, WalletDbError>;
 
+    /// Get the txos an account had received at a known subaddress and not
+    /// spent as of a block, ordered by the block they were received in.
+    /// Txos spent after the block are included.
+    fn list_unspent_at_block(
+        account_id_hex: &str,
+        block_index: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError>;
+
     #[allow(clippy::too_many_arguments)]
     fn list_unspent(
         account_id_hex: Option<&str>,
@@ -246,6 +452,15 @@ pub trait TxoModel {
         conn: &Conn,
     ) -> Result<Vec<Txo>, WalletDbError>;
 
+    /// Get the orphaned txos whose spend key matches an assigned subaddress.
+    /// These belong to the subaddress, but were received before it was
+    /// assigned, so their subaddress index was never recorded.
+    fn list_orphaned_for_address(
+        assigned_subaddress_b58: &str,
+        token_id: Option<u64>,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError>;
+
     #[allow(clippy::too_many_arguments)]
     fn list_pending(
         account_id_hex: Option<&str>,
@@ -276,15 +491,44 @@ pub trait TxoModel {
         assigned_subaddress_b58: Option<&str>,
         token_id: u64,
         default_token_fee: u64,
+        unconfirmed_block_index: Option<u64>,
         conn: &Conn,
     ) -> Result<SpendableTxosResult, WalletDbError>;
 
+    /// Sum the value of the txos of every account which has not been removed,
+    /// by token and status, in a single grouped query. The sums for an
+    /// account match those of the list_* methods for each status. Txos are
+    /// unconfirmed in a ledger of num_blocks blocks as for
+    /// unconfirmed_block_index, using default_min_confirmations for accounts
+    /// which do not set their own.
+    ///
+    /// Returns:
+    /// * Map of (account_id_hex, token_id) to the values of its txos
+    fn sum_values_by_account_and_token(
+        num_blocks: u64,
+        default_min_confirmations: u64,
+        conn: &Conn,
+    ) -> Result<BTreeMap<(String, TokenId), AccountTokenValues>, WalletDbError>;
+
+    /// Get the ids of the txos in this account which are inputs to a
+    /// submitted transaction that has neither landed nor failed.
+    ///
+    /// Returns:
+    /// * Vec<String>
+    fn pending_txo_ids(account_id_hex: &str, conn: &Conn) -> Result<Vec<String>, WalletDbError>;
+
     /// Get the details for a specific Txo.
     ///
     /// Returns:
     /// * Txo
     fn get(txo_id_hex: &str, conn: &Conn) -> Result<Txo, WalletDbError>;
 
+    /// Get the Txo at an index among all tx outs in the ledger.
+    ///
+    /// Returns:
+    /// * Txo
+    fn get_by_global_index(global_index: u64, conn: &Conn) -> Result<Txo, WalletDbError>;
+
     /// Get several Txos by Txo public_keys
     ///
     /// Returns:
@@ -300,7 +544,15 @@ pub trait TxoModel {
     /// * Vec<(Txo)>
     fn select_by_id(txo_ids: &[String], conn: &Conn) -> Result<Vec<Txo>, WalletDbError>;
 
-    /// Select a set of unspent Txos to reach a given value.
+    /// Count the Txos received by any of the given accounts.
+    fn count_received_by_accounts(
+        account_ids: &[String],
+        conn: &Conn,
+    ) -> Result<u64, WalletDbError>;
+
+    /// Select a set of unspent Txos to reach a given value. Txos received at
+    /// or after unconfirmed_block_index, if given, are not selected, and only
+    /// txos received at assigned_subaddress_b58 are selected if it is given.
     ///
     /// Returns:
     /// * Vec<Txo>
@@ -308,8 +560,10 @@ pub trait TxoModel {
         account_id_hex: &str,
         target_value: u64,
         max_spendable_value: Option<u64>,
+        assigned_subaddress_b58: Option<&str>,
         token_id: u64,
         default_token_fee: u64,
+        unconfirmed_block_index: Option<u64>,
         conn: &Conn,
     ) -> Result<Vec<Txo>, WalletDbError>;
 
@@ -326,6 +580,18 @@ pub trait TxoModel {
 
     fn scrub_account(account_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError>;
 
+    /// Forget what syncing an account from the given block onwards found, so
+    /// that those blocks can be scanned again. Txos the account received in
+    /// those blocks are detached from it, and its other txos spent in those
+    /// blocks are marked unspent. Txos of other accounts are not changed.
+    ///
+    /// Returns the number of txos changed.
+    fn reset_for_rescan(
+        account_id_hex: &str,
+        from_block_index: u64,
+        conn: &Conn,
+    ) -> Result<usize, WalletDbError>;
+
     /// Delete txos which are not referenced by any account or transaction.
     fn delete_unreferenced(conn: &Conn) -> Result<(), WalletDbError>;
 
@@ -342,6 +608,7 @@ impl TxoModel for Txo {
         key_image: Option<KeyImage>,
         amount: Amount,
         received_block_index: u64,
+        global_index: Option<u64>,
         account_id_hex: &str,
         conn: &Conn,
     ) -> Result<String, WalletDbError> {
@@ -350,6 +617,24 @@ impl TxoModel for Txo {
 
         let txo_id = TxoID::from(&txo);
         match Txo::get(&txo_id.to_string(), conn) {
+            // Only accounts sharing a view private key can both receive a TXO. It
+            // stays with the account which received it first, and the other only
+            // fills in its key image if the first could not derive it.
+            Ok(txo)
+                if txo.account_id.as_deref().map_or(false, |id| {
+                    id != account_id_hex && Account::get(&AccountID(id.to_string()), conn).is_ok()
+                }) =>
+            {
+                if let (None, Some(key_image)) = (&txo.key_image, key_image) {
+                    Txo::update_key_image(
+                        &txo.id,
+                        &key_image,
+                        txo.spent_block_index.map(|i| i as u64),
+                        conn,
+                    )?;
+                }
+            }
+
             // If we already have this TXO for this account (e.g. from minting in a previous
             // transaction), we need to update it
             Ok(txo) => {
@@ -358,6 +643,7 @@ impl TxoModel for Txo {
                     subaddress_index,
                     key_image,
                     received_block_index,
+                    global_index,
                     conn,
                 )?;
             }
@@ -379,11 +665,13 @@ impl TxoModel for Txo {
                     spent_block_index: None,
                     shared_secret: None,
                     account_id: Some(account_id_hex.to_string()),
+                    global_index: global_index.map(|i| i as i64),
                 };
 
                 diesel::insert_into(crate::db::schema::txos::table)
                     .values(&new_txo)
                     .execute(conn)?;
+                Txo::touch(&txo_id.to_string(), conn)?;
             }
             Err(e) => {
                 return Err(e);
@@ -402,6 +690,7 @@ impl TxoModel for Txo {
 
         let txo_id = TxoID::from(&output_txo.tx_out);
         let encoded_confirmation = mc_util_serial::encode(&output_txo.confirmation_number);
+        let public_key = mc_util_serial::encode(&output_txo.tx_out.public_key);
 
         let new_txo = NewTxo {
             id: &txo_id.to_string(),
@@ -409,7 +698,7 @@ impl TxoModel for Txo {
             value: output_txo.amount.value as i64,
             token_id: *output_txo.amount.token_id as i64,
             target_key: &mc_util_serial::encode(&output_txo.tx_out.target_key),
-            public_key: &mc_util_serial::encode(&output_txo.tx_out.public_key),
+            public_key: &public_key,
             e_fog_hint: &mc_util_serial::encode(&output_txo.tx_out.e_fog_hint),
             txo: &mc_util_serial::encode(&output_txo.tx_out),
             subaddress_index: None,
@@ -417,11 +706,13 @@ impl TxoModel for Txo {
             received_block_index: None,
             spent_block_index: None,
             shared_secret: Some(&encoded_confirmation),
+            global_index: None,
         };
 
         diesel::insert_into(txos::table)
             .values(&new_txo)
             .execute(conn)?;
+        Txo::touch(&txo_id.to_string(), conn)?;
 
         let recipient_public_address_b58 =
             &b58_encode_public_address(&output_txo.recipient_public_address)?;
@@ -431,6 +722,7 @@ impl TxoModel for Txo {
             txo_id: &txo_id.to_string(),
             recipient_public_address_b58,
             is_change,
+            public_key: Some(&public_key),
         };
 
         diesel::insert_into(crate::db::schema::transaction_output_txos::table)
@@ -446,6 +738,7 @@ impl TxoModel for Txo {
         received_subaddress_index: Option<u64>,
         received_key_image: Option<KeyImage>,
         block_index: u64,
+        global_index: Option<u64>,
         conn: &Conn,
     ) -> Result<(), WalletDbError> {
         use crate::db::schema::txos;
@@ -458,8 +751,33 @@ impl TxoModel for Txo {
                 txos::received_block_index.eq(Some(block_index as i64)),
                 txos::subaddress_index.eq(received_subaddress_index.map(|i| i as i64)),
                 txos::key_image.eq(encoded_key_image),
+                txos::modified_seq.eq(next_modified_seq()),
             ))
             .execute(conn)?;
+        if let Some(global_index) = global_index {
+            self.update_global_index(global_index, conn)?;
+        }
+        Ok(())
+    }
+
+    fn update_global_index(&self, global_index: u64, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::txos;
+
+        diesel::update(self)
+            .set((
+                txos::global_index.eq(Some(global_index as i64)),
+                txos::modified_seq.eq(next_modified_seq()),
+            ))
+            .execute(conn)?;
+        Ok(())
+    }
+
+    fn touch(txo_id_hex: &str, conn: &Conn) -> Result<(), WalletDbError> {
+        use crate::db::schema::txos;
+
+        diesel::update(txos::table.filter(txos::id.eq(txo_id_hex)))
+            .set(txos::modified_seq.eq(next_modified_seq()))
+            .execute(conn)?;
         Ok(())
     }
 
@@ -471,7 +789,10 @@ impl TxoModel for Txo {
         use crate::db::schema::txos;
 
         diesel::update(txos::table.filter(txos::id.eq(txo_id_hex)))
-            .set((txos::spent_block_index.eq(Some(spent_block_index as i64)),))
+            .set((
+                txos::spent_block_index.eq(Some(spent_block_index as i64)),
+                txos::modified_seq.eq(next_modified_seq()),
+            ))
             .execute(conn)?;
         Ok(())
     }
@@ -490,6 +811,7 @@ impl TxoModel for Txo {
             .set((
                 txos::key_image.eq(Some(encoded_key_image)),
                 txos::spent_block_index.eq(spent_block_index.map(|i| i as i64)),
+                txos::modified_seq.eq(next_modified_seq()),
             ))
             .execute(conn)?;
 
@@ -691,6 +1013,69 @@ impl TxoModel for Txo {
         Ok(query.load(conn)?)
     }
 
+    fn list_for_account_after(
+        account_id_hex: &str,
+        after_txo_id_hex: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError> {
+        use crate::db::schema::txos;
+
+        let mut query = txos::table
+            .into_boxed()
+            .filter(txos::account_id.eq(account_id_hex));
+
+        if let Some(after_txo_id_hex) = after_txo_id_hex {
+            query = query.filter(txos::id.gt(after_txo_id_hex));
+        }
+
+        Ok(query.order(txos::id).limit(limit as i64).load(conn)?)
+    }
+
+    fn list_missing_global_index(
+        after_txo_id_hex: Option<&str>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError> {
+        use crate::db::schema::txos;
+
+        let mut query = txos::table
+            .into_boxed()
+            .filter(txos::global_index.is_null());
+
+        if let Some(after_txo_id_hex) = after_txo_id_hex {
+            query = query.filter(txos::id.gt(after_txo_id_hex));
+        }
+
+        Ok(query.order(txos::id).limit(limit as i64).load(conn)?)
+    }
+
+    fn list_modified_since(
+        account_id_hex: &str,
+        after: Option<(i64, &str)>,
+        limit: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError> {
+        use crate::db::schema::txos;
+
+        let mut query = txos::table
+            .into_boxed()
+            .filter(txos::account_id.eq(account_id_hex));
+
+        if let Some((modified_seq, txo_id_hex)) = after {
+            query = query.filter(
+                txos::modified_seq.gt(modified_seq).or(txos::modified_seq
+                    .eq(modified_seq)
+                    .and(txos::id.gt(txo_id_hex))),
+            );
+        }
+
+        Ok(query
+            .order((txos::modified_seq, txos::id))
+            .limit(limit as i64)
+            .load(conn)?)
+    }
+
     fn list_for_address(
         assigned_subaddress_b58: &str,
         status: Option<TxoStatus>,
@@ -754,7 +1139,7 @@ impl TxoModel for Txo {
                     )
                 }
                 TxoStatus::Orphaned => {
-                    return Ok(vec![]);
+                    return Txo::list_orphaned_for_address(assigned_subaddress_b58, token_id, conn)
                 }
             }
         }
@@ -840,7 +1225,9 @@ impl TxoModel for Txo {
 
         if let Some(subaddress_b58) = assigned_subaddress_b58 {
             let subaddress = AssignedSubaddress::get(subaddress_b58, conn)?;
-            query = query.filter(txos::subaddress_index.eq(subaddress.subaddress_index));
+            query = query
+                .filter(txos::account_id.eq(subaddress.account_id))
+                .filter(txos::subaddress_index.eq(subaddress.subaddress_index));
         }
 
         if let Some(token_id) = token_id {
@@ -902,7 +1289,9 @@ impl TxoModel for Txo {
 
         if let Some(subaddress_b58) = assigned_subaddress_b58 {
             let subaddress = AssignedSubaddress::get(subaddress_b58, conn)?;
-            query = query.filter(txos::subaddress_index.eq(subaddress.subaddress_index));
+            query = query
+                .filter(txos::account_id.eq(subaddress.account_id))
+                .filter(txos::subaddress_index.eq(subaddress.subaddress_index));
         }
 
         if let Some(token_id) = token_id {
@@ -954,6 +1343,26 @@ impl TxoModel for Txo {
             .collect())
     }
 
+    fn list_unspent_at_block(
+        account_id_hex: &str,
+        block_index: u64,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError> {
+        use crate::db::schema::txos;
+
+        Ok(txos::table
+            .filter(txos::account_id.eq(account_id_hex))
+            .filter(txos::subaddress_index.is_not_null())
+            .filter(txos::received_block_index.le(block_index as i64))
+            .filter(
+                txos::spent_block_index
+                    .is_null()
+                    .or(txos::spent_block_index.gt(block_index as i64)),
+            )
+            .order((txos::received_block_index, txos::id))
+            .load(conn)?)
+    }
+
     fn list_spent(
         account_id_hex: Option<&str>,
         assigned_subaddress_b58: Option<&str>,
@@ -976,7 +1385,9 @@ impl TxoModel for Txo {
 
         if let Some(subaddress_b58) = assigned_subaddress_b58 {
             let subaddress = AssignedSubaddress::get(subaddress_b58, conn)?;
-            query = query.filter(txos::subaddress_index.eq(subaddress.subaddress_index));
+            query = query
+                .filter(txos::account_id.eq(subaddress.account_id))
+                .filter(txos::subaddress_index.eq(subaddress.subaddress_index));
         }
 
         if let Some(token_id) = token_id {
@@ -1040,6 +1451,46 @@ impl TxoModel for Txo {
         Ok(txos)
     }
 
+    fn list_orphaned_for_address(
+        assigned_subaddress_b58: &str,
+        token_id: Option<u64>,
+        conn: &Conn,
+    ) -> Result<Vec<Txo>, WalletDbError> {
+        let subaddress = AssignedSubaddress::get(assigned_subaddress_b58, conn)?;
+        let subaddress_spend_public_key =
+            RistrettoPublic::try_from(subaddress.spend_public_key.as_slice())?;
+        let account = Account::get(&AccountID(subaddress.account_id.clone()), conn)?;
+        let view_private_key = account.view_private_key()?;
+
+        let orphaned_txos = Txo::list_orphaned(
+            Some(&subaddress.account_id),
+            token_id,
+            None,
+            None,
+            None,
+            None,
+            conn,
+        )?;
+
+        let mut txos = Vec::new();
+        for orphaned_txo in orphaned_txos {
+            let tx_out_target_key: RistrettoPublic =
+                mc_util_serial::decode(&orphaned_txo.target_key)?;
+            let tx_public_key: RistrettoPublic = mc_util_serial::decode(&orphaned_txo.public_key)?;
+
+            let txo_subaddress_spk = recover_public_subaddress_spend_key(
+                &view_private_key,
+                &tx_out_target_key,
+                &tx_public_key,
+            );
+            if txo_subaddress_spk == subaddress_spend_public_key {
+                txos.push(orphaned_txo);
+            }
+        }
+
+        Ok(txos)
+    }
+
     fn list_pending(
         account_id_hex: Option<&str>,
         assigned_subaddress_b58: Option<&str>,
@@ -1075,7 +1526,9 @@ impl TxoModel for Txo {
 
         if let Some(subaddress_b58) = assigned_subaddress_b58 {
             let subaddress = AssignedSubaddress::get(subaddress_b58, conn)?;
-            query = query.filter(txos::subaddress_index.eq(subaddress.subaddress_index));
+            query = query
+                .filter(txos::account_id.eq(subaddress.account_id))
+                .filter(txos::subaddress_index.eq(subaddress.subaddress_index));
         }
 
         if let Some(token_id) = token_id {
@@ -1118,6 +1571,22 @@ impl TxoModel for Txo {
         Ok(txo)
     }
 
+    fn get_by_global_index(global_index: u64, conn: &Conn) -> Result<Txo, WalletDbError> {
+        use crate::db::schema::txos;
+
+        match txos::table
+            .filter(txos::global_index.eq(global_index as i64))
+            .get_result::<Txo>(conn)
+        {
+            Ok(txo) => Ok(txo),
+            Err(diesel::result::Error::NotFound) => Err(WalletDbError::TxoNotFound(format!(
+                "global index {}",
+                global_index
+            ))),
+            Err(e) => Err(e.into()),
+        }
+    }
+
     fn select_by_public_key(
         public_keys: &[&CompressedRistrettoPublic],
         conn: &Conn,
@@ -1142,32 +1611,45 @@ impl TxoModel for Txo {
         Ok(txos)
     }
 
+    fn count_received_by_accounts(
+        account_ids: &[String],
+        conn: &Conn,
+    ) -> Result<u64, WalletDbError> {
+        use crate::db::schema::txos;
+
+        let count: i64 = txos::table
+            .filter(txos::account_id.eq_any(account_ids))
+            .count()
+            .get_result(conn)?;
+
+        Ok(count as u64)
+    }
+
     fn list_spendable(
         account_id_hex: Option<&str>,
         max_spendable_value: Option<u64>,
         assigned_subaddress_b58: Option<&str>,
         token_id: u64,
         default_token_fee: u64,
+        unconfirmed_block_index: Option<u64>,
         conn: &Conn,
     ) -> Result<SpendableTxosResult, WalletDbError> {
         use crate::db::schema::{transaction_input_txos, transaction_logs, txos};
 
-        let mut query = txos::table
-            .into_boxed()
-            .left_join(transaction_input_txos::table)
-            .left_join(
-                transaction_logs::table
-                    .on(transaction_logs::id.eq(transaction_input_txos::transaction_log_id)),
-            );
-
-        query = query
-            .filter(transaction_logs::id.is_null())
-            .or_filter(transaction_logs::failed.eq(true))
-            .or_filter(
-                transaction_logs::id
-                    .is_not_null()
-                    .and(transaction_logs::submitted_block_index.is_null()),
-            );
+        // A txo may be an input to several transaction logs, for example one
+        // which failed and a later one which is still in flight. It is only
+        // spendable if none of them has been submitted without failing, so
+        // exclude it if any such log exists rather than joining on each log.
+        let mut query = txos::table.into_boxed().filter(not(exists(
+            transaction_input_txos::table
+                .inner_join(
+                    transaction_logs::table
+                        .on(transaction_logs::id.eq(transaction_input_txos::transaction_log_id)),
+                )
+                .filter(transaction_input_txos::txo_id.eq(txos::id))
+                .filter(transaction_logs::submitted_block_index.is_not_null())
+                .filter(transaction_logs::failed.eq(false)),
+        )));
 
         query = query
             .filter(txos::received_block_index.is_not_null())
@@ -1177,13 +1659,19 @@ impl TxoModel for Txo {
 
         if let Some(subaddress_b58) = assigned_subaddress_b58 {
             let subaddress = AssignedSubaddress::get(subaddress_b58, conn)?;
-            query = query.filter(txos::subaddress_index.eq(subaddress.subaddress_index));
+            query = query
+                .filter(txos::account_id.eq(subaddress.account_id))
+                .filter(txos::subaddress_index.eq(subaddress.subaddress_index));
         }
 
         if let Some(max_spendable_value) = max_spendable_value {
             query = query.filter(txos::value.le(max_spendable_value as i64));
         }
 
+        if let Some(unconfirmed_block_index) = unconfirmed_block_index {
+            query = query.filter(txos::received_block_index.lt(unconfirmed_block_index as i64));
+        }
+
         if let Some(account_id_hex) = account_id_hex {
             query = query.filter(txos::account_id.eq(account_id_hex));
         }
@@ -1218,12 +1706,66 @@ impl TxoModel for Txo {
         })
     }
 
+    fn sum_values_by_account_and_token(
+        num_blocks: u64,
+        default_min_confirmations: u64,
+        conn: &Conn,
+    ) -> Result<BTreeMap<(String, TokenId), AccountTokenValues>, WalletDbError> {
+        let sums: Vec<TxoValueSum> = diesel::sql_query(SUM_VALUES_BY_ACCOUNT_AND_TOKEN_QUERY)
+            .bind::<BigInt, _>(default_min_confirmations as i64)
+            .bind::<BigInt, _>(num_blocks as i64)
+            .bind::<BigInt, _>(MAX_INPUTS as i64)
+            .load(conn)?;
+
+        let mut values: BTreeMap<(String, TokenId), AccountTokenValues> = BTreeMap::new();
+        for sum in sums {
+            let value = sum.value();
+            let entry = values
+                .entry((sum.account_id, TokenId::from(sum.token_id as u64)))
+                .or_default();
+            match sum.status.as_str() {
+                "unverified" => entry.unverified = value,
+                "unspent" => entry.unspent = value,
+                "unconfirmed" => entry.unconfirmed = value,
+                "pending" => entry.pending = value,
+                "spent" => entry.spent = value,
+                "orphaned" => entry.orphaned = value,
+                "max_spendable_inputs" => entry.max_spendable_inputs = value,
+                _ => {}
+            }
+        }
+
+        Ok(values)
+    }
+
+    fn pending_txo_ids(account_id_hex: &str, conn: &Conn) -> Result<Vec<String>, WalletDbError> {
+        use crate::db::schema::{transaction_input_txos, transaction_logs, txos};
+
+        let txo_ids = txos::table
+            .inner_join(transaction_input_txos::table)
+            .inner_join(
+                transaction_logs::table
+                    .on(transaction_logs::id.eq(transaction_input_txos::transaction_log_id)),
+            )
+            .filter(txos::account_id.eq(account_id_hex))
+            .filter(transaction_logs::submitted_block_index.is_not_null())
+            .filter(transaction_logs::finalized_block_index.is_null())
+            .filter(transaction_logs::failed.eq(false))
+            .select(txos::id)
+            .distinct()
+            .load(conn)?;
+
+        Ok(txo_ids)
+    }
+
     fn select_spendable_txos_for_value(
         account_id_hex: &str,
         target_value: u64,
         max_spendable_value: Option<u64>,
+        assigned_subaddress_b58: Option<&str>,
         token_id: u64,
         default_token_fee: u64,
+        unconfirmed_block_index: Option<u64>,
         conn: &Conn,
     ) -> Result<Vec<Txo>, WalletDbError> {
         let SpendableTxosResult {
@@ -1232,9 +1774,10 @@ impl TxoModel for Txo {
         } = Txo::list_spendable(
             Some(account_id_hex),
             max_spendable_value,
-            None,
+            assigned_subaddress_b58,
             token_id,
             default_token_fee,
+            unconfirmed_block_index,
             conn,
         )?;
 
@@ -1242,22 +1785,28 @@ impl TxoModel for Txo {
             return Err(WalletDbError::NoSpendableTxos);
         }
 
+        let total_unspent_value_in_wallet: u128 = spendable_txos
+            .iter()
+            .map(|utxo| (utxo.value as u64) as u128)
+            .sum();
+        let insufficient_funds_amounts = || InsufficientFundsAmounts {
+            token_id,
+            requested: target_value as u128,
+            available: total_unspent_value_in_wallet,
+            max_spendable: max_spendable_in_wallet,
+            shortfall: (target_value as u128).saturating_sub(total_unspent_value_in_wallet),
+        };
+
         // If we're trying to spend more than we have in the wallet, we may need to
         // defrag
         if target_value as u128 > max_spendable_in_wallet + default_token_fee as u128 {
             // See if we merged the UTXOs we would be able to spend this amount.
-            let total_unspent_value_in_wallet: u128 = spendable_txos
-                .iter()
-                .map(|utxo| (utxo.value as u64) as u128)
-                .sum();
-
             if total_unspent_value_in_wallet >= (target_value + default_token_fee) as u128 {
                 return Err(WalletDbError::InsufficientFundsFragmentedTxos);
             } else {
-                return Err(WalletDbError::InsufficientFundsUnderMaxSpendable(format!(
-                    "Max spendable value in wallet: {:?}, but target value: {:?}",
-                    max_spendable_in_wallet, target_value
-                )));
+                return Err(WalletDbError::InsufficientFundsUnderMaxSpendable(
+                    insufficient_funds_amounts(),
+                ));
             }
         }
 
@@ -1275,12 +1824,9 @@ impl TxoModel for Txo {
             }
 
             // Grab the next (smallest) utxo, in order to opportunistically sweep up dust
-            let next_utxo = spendable_txos.pop().ok_or_else(|| {
-                WalletDbError::InsufficientFunds(format!(
-                    "Not enough Txos to sum to target value: {:?}",
-                    target_value
-                ))
-            })?;
+            let next_utxo = spendable_txos
+                .pop()
+                .ok_or_else(|| WalletDbError::InsufficientFunds(insufficient_funds_amounts()))?;
             selected_utxos.push(next_utxo.clone());
             total += next_utxo.value as u64;
 
@@ -1293,9 +1839,7 @@ impl TxoModel for Txo {
         }
 
         if selected_utxos.is_empty() || selected_utxos.len() > MAX_INPUTS as usize {
-            return Err(WalletDbError::InsufficientFunds(
-                "Logic error. Could not select Txos despite having sufficient funds".to_string(),
-            ));
+            return Err(WalletDbError::InsufficientFunds(insufficient_funds_amounts()));
         }
 
         Ok(selected_utxos)
@@ -1320,12 +1864,48 @@ impl TxoModel for Txo {
         let txos_received_by_account = txos::table.filter(txos::account_id.eq(account_id_hex));
 
         diesel::update(txos_received_by_account)
-            .set(txos::account_id.eq::<Option<String>>(None))
+            .set((
+                txos::account_id.eq::<Option<String>>(None),
+                txos::modified_seq.eq(next_modified_seq()),
+            ))
             .execute(conn)?;
 
         Ok(())
     }
 
+    fn reset_for_rescan(
+        account_id_hex: &str,
+        from_block_index: u64,
+        conn: &Conn,
+    ) -> Result<usize, WalletDbError> {
+        use crate::db::schema::txos;
+
+        let received_since = txos::table
+            .filter(txos::account_id.eq(account_id_hex))
+            .filter(txos::received_block_index.ge(from_block_index as i64));
+        let num_detached = diesel::update(received_since)
+            .set((
+                txos::account_id.eq::<Option<String>>(None),
+                txos::received_block_index.eq::<Option<i64>>(None),
+                txos::subaddress_index.eq::<Option<i64>>(None),
+                txos::spent_block_index.eq::<Option<i64>>(None),
+                txos::modified_seq.eq(next_modified_seq()),
+            ))
+            .execute(conn)?;
+
+        let spent_since = txos::table
+            .filter(txos::account_id.eq(account_id_hex))
+            .filter(txos::spent_block_index.ge(from_block_index as i64));
+        let num_unspent = diesel::update(spent_since)
+            .set((
+                txos::spent_block_index.eq::<Option<i64>>(None),
+                txos::modified_seq.eq(next_modified_seq()),
+            ))
+            .execute(conn)?;
+
+        Ok(num_detached + num_unspent)
+    }
+
     fn delete_unreferenced(conn: &Conn) -> Result<(), WalletDbError> {
         use crate::db::schema::{transaction_input_txos, transaction_output_txos, txos};
 
@@ -1516,6 +2096,12 @@ mod tests {
             spent_block_index: None,
             shared_secret: None,
             account_id: Some(alice_account_id.to_string()),
+            global_index: Some(
+                ledger_db
+                    .get_tx_out_index_by_public_key(&for_alice_txo.public_key)
+                    .unwrap() as i64,
+            ),
+            modified_seq: txos[0].modified_seq,
         };
 
         assert_eq!(expected_txo, txos[0]);
@@ -1556,6 +2142,10 @@ mod tests {
         assert_eq!(minted_txo.value as u64, 33 * MOB);
         assert_eq!(change_txo.value as u64, 967 * MOB - Mob::MINIMUM_FEE);
 
+        // The outputs are not in the ledger yet, so they have no global index.
+        assert_eq!(minted_txo.global_index, None);
+        assert_eq!(change_txo.global_index, None);
+
         add_block_with_db_txos(
             &mut ledger_db,
             &wallet_db,
@@ -1583,6 +2173,12 @@ mod tests {
         )
         .unwrap();
         assert_eq!(txos.len(), 3);
+        for txo in &txos {
+            let global_index = ledger_db
+                .get_tx_out_index_by_public_key(&txo.public_key().unwrap())
+                .unwrap();
+            assert_eq!(txo.global_index, Some(global_index as i64));
+        }
 
         // test spent
         let spent_txos = Txo::list_for_account(
@@ -1846,8 +2442,10 @@ mod tests {
             &account_id_hex.to_string(),
             300 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -1859,8 +2457,10 @@ mod tests {
             &account_id_hex.to_string(),
             300 * MOB + Mob::MINIMUM_FEE,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -1875,8 +2475,10 @@ mod tests {
             &account_id_hex.to_string(),
             300 * MOB + Mob::MINIMUM_FEE,
             Some(200 * MOB),
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         );
 
@@ -1892,8 +2494,10 @@ mod tests {
             &account_id_hex.to_string(),
             16800 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -1962,8 +2566,10 @@ mod tests {
             &account_id_hex.to_string(),
             16800 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -1972,8 +2578,10 @@ mod tests {
             &account_id_hex.to_string(),
             16800 * MOB,
             Some(100 * MOB),
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         );
 
@@ -2026,8 +2634,10 @@ mod tests {
             &account_id_hex.to_string(), // FIXME: WS-11 - take AccountID
             1800 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         );
         match res {
@@ -2456,6 +3066,7 @@ mod tests {
             None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &conn,
         )
         .unwrap();
@@ -2512,6 +3123,7 @@ mod tests {
             None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &conn,
         )
         .unwrap();
@@ -2597,6 +3209,7 @@ mod tests {
             None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &conn,
         )
         .unwrap();
@@ -2644,6 +3257,7 @@ mod tests {
             None,
             amount.clone(),
             15,
+            None,
             &account_id.to_string(),
             &wallet_db.get_conn().unwrap(),
         )
@@ -2669,6 +3283,7 @@ mod tests {
             None,
             amount.clone(),
             15,
+            None,
             &account_id.to_string(),
             &wallet_db.get_conn().unwrap(),
         )
@@ -2694,6 +3309,7 @@ mod tests {
             Some(key_image),
             amount.clone(),
             15,
+            None,
             &account_id.to_string(),
             &wallet_db.get_conn().unwrap(),
         )
@@ -2812,8 +3428,10 @@ mod tests {
             &account_id.to_string(),
             target_value,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -2830,8 +3448,10 @@ mod tests {
             &account_id.to_string(),
             201 as u64 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         );
 
@@ -2848,8 +3468,10 @@ mod tests {
             &account_id.to_string(),
             3 as u64,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -2864,8 +3486,10 @@ mod tests {
             &account_id.to_string(),
             500 as u64 * MOB,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         );
         assert!(result.is_err());
@@ -2881,8 +3505,10 @@ mod tests {
             &account_id.to_string(),
             12400000000 as u64,
             None,
+            None,
             0,
             Mob::MINIMUM_FEE,
+            None,
             &wallet_db.get_conn().unwrap(),
         )
         .unwrap();
@@ -2891,6 +3517,114 @@ mod tests {
         assert_eq!(12400000000 as i64, sum);
     }
 
+    // Each change to a txo is picked up exactly once by the next incremental
+    // pull of the account's modified txos.
+    #[test_with_logger]
+    fn test_list_modified_since(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger);
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let conn = wallet_db.get_conn().unwrap();
+
+        let root_id = RootIdentity::from_random(&mut rng);
+        let account_key = AccountKey::from(&root_id);
+        let (account_id, _public_address_b58) = Account::create_from_root_entropy(
+            &root_id.root_entropy,
+            Some(1),
+            None,
+            None,
+            "Alice's Main Account",
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            &conn,
+        )
+        .unwrap();
+        let account_id = account_id.to_string();
+
+        // Pull everything which changed since the last pull, a small page at a
+        // time, as an incremental export would.
+        let mut cursor: Option<(i64, String)> = None;
+        let mut pull = || {
+            let mut txo_ids = vec![];
+            loop {
+                let page = Txo::list_modified_since(
+                    &account_id,
+                    cursor.as_ref().map(|(seq, id)| (*seq, id.as_str())),
+                    2,
+                    &conn,
+                )
+                .unwrap();
+                match page.last() {
+                    Some(txo) => cursor = Some((txo.modified_seq, txo.id.clone())),
+                    None => return txo_ids,
+                }
+                txo_ids.extend(page.into_iter().map(|txo| txo.id));
+            }
+        };
+
+        let mut received = vec![];
+        for _ in 0..3 {
+            received.push(create_test_received_txo(
+                &account_key,
+                0,
+                Amount::new(100 * MOB, Mob::ID),
+                12,
+                &mut rng,
+                &wallet_db,
+            ));
+        }
+        let (txo_a, _, _) = &received[0];
+        let (txo_b, _, _) = &received[1];
+        let (txo_c, _, key_image_c) = &received[2];
+        assert_eq!(pull(), vec![txo_a.clone(), txo_b.clone(), txo_c.clone()]);
+        assert!(pull().is_empty());
+
+        // A spent state flip.
+        Txo::update_spent_block_index(txo_b, 13, &conn).unwrap();
+        assert_eq!(pull(), vec![txo_b.clone()]);
+
+        // A key image update.
+        Txo::update_key_image(txo_c, key_image_c, Some(14), &conn).unwrap();
+        assert_eq!(pull(), vec![txo_c.clone()]);
+
+        // Several changes to the same txo between pulls are picked up once.
+        Txo::update_spent_block_index(txo_a, 15, &conn).unwrap();
+        Txo::get(txo_a, &conn)
+            .unwrap()
+            .update_global_index(100, &conn)
+            .unwrap();
+        assert_eq!(pull(), vec![txo_a.clone()]);
+
+        // A txo received at a subaddress which is not assigned yet is orphaned,
+        // and is repaired when the subaddress is assigned.
+        let (orphaned_tx_out, _) =
+            create_test_txo_for_recipient(&account_key, 5, Amount::new(MOB, Mob::ID), &mut rng);
+        let orphaned_txo = Txo::create_received(
+            orphaned_tx_out,
+            None,
+            None,
+            Amount::new(MOB, Mob::ID),
+            16,
+            None,
+            &account_id,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(pull(), vec![orphaned_txo.clone()]);
+
+        AssignedSubaddress::create_at_index_for_account(&account_id, 5, "", &ledger_db, &conn)
+            .unwrap();
+        assert_eq!(
+            Txo::get(&orphaned_txo, &conn).unwrap().subaddress_index,
+            Some(5)
+        );
+        assert_eq!(pull(), vec![orphaned_txo]);
+        assert!(pull().is_empty());
+    }
+
     // FIXME: once we have create_minted, then select_txos test with no
     // FIXME: test update txo after tombstone block is exceeded
     // FIXME: test update txo after it has landed via key_image update
@@ -1,23 +1,164 @@
 use crate::db::WalletDbError;
 use diesel::{
-    connection::SimpleConnection,
+    connection::{SimpleConnection, TransactionManager},
+    migration::MigrationConnection,
     prelude::*,
     r2d2::{ConnectionManager, Pool, PooledConnection},
     sql_types, SqliteConnection,
 };
 use diesel_migrations::embed_migrations;
-use mc_common::logger::global_log;
-use std::{env, thread::sleep, time::Duration};
+use mc_common::logger::{log, Logger};
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::atomic::{AtomicU32, Ordering},
+    thread::sleep,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 embed_migrations!("migrations/");
 
-pub type Conn = PooledConnection<ConnectionManager<SqliteConnection>>;
+/// The migrations embedded in the binary, by directory name, oldest first.
+const MIGRATIONS: &str = env!("WALLET_DB_MIGRATIONS");
+
+/// Inserted between the database file name and the time of a backup made
+/// before running migrations.
+const BACKUP_INFIX: &str = ".backup-";
 
+/// How the database is brought up to date at startup.
 #[derive(Debug)]
+pub struct MigrationOptions {
+    /// Refuse to start with pending migrations, instead of running them.
+    pub skip_migrations: bool,
+
+    /// Number of backups made before running migrations to keep. 0 disables
+    /// backups.
+    pub backup_retention: usize,
+}
+
+/// A copy of the database made before running migrations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseBackup {
+    pub path: PathBuf,
+
+    /// When the backup was made, in milliseconds since the Unix epoch.
+    pub created_at_millis: u64,
+}
+
+pub type Conn = PooledConnection<ConnectionManager<SqliteConnection>>;
+
+/// How SQLite journals writes to the database.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalMode {
+    /// Write-ahead log, so that readers do not block writers and writers do
+    /// not block readers.
+    Wal,
+
+    /// Rollback journal, deleted at the end of each transaction.
+    Delete,
+}
+
+impl fmt::Display for JournalMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalMode::Wal => write!(f, "wal"),
+            JournalMode::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+impl FromStr for JournalMode {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        match src.to_lowercase().as_str() {
+            "wal" => Ok(JournalMode::Wal),
+            "delete" => Ok(JournalMode::Delete),
+            _ => Err(format!("Unknown journal mode {}", src)),
+        }
+    }
+}
+
+/// How often SQLite waits for writes to reach the disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl fmt::Display for Synchronous {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Synchronous::Off => write!(f, "off"),
+            Synchronous::Normal => write!(f, "normal"),
+            Synchronous::Full => write!(f, "full"),
+            Synchronous::Extra => write!(f, "extra"),
+        }
+    }
+}
+
+impl FromStr for Synchronous {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        match src.to_lowercase().as_str() {
+            "off" | "0" => Ok(Synchronous::Off),
+            "normal" | "1" => Ok(Synchronous::Normal),
+            "full" | "2" => Ok(Synchronous::Full),
+            "extra" | "3" => Ok(Synchronous::Extra),
+            _ => Err(format!("Unknown synchronous level {}", src)),
+        }
+    }
+}
+
+/// The SQLite settings of the connections to the wallet db.
+#[derive(Clone, Debug)]
 pub struct ConnectionOptions {
-    pub enable_wal: bool,
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
     pub enable_foreign_keys: bool,
+
+    /// How long a statement waits for a lock held by another connection
+    /// before failing as busy.
     pub busy_timeout: Option<Duration>,
+
+    /// Size of the page cache of each connection, in KiB.
+    pub cache_size_kib: Option<u64>,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            journal_mode: JournalMode::Wal,
+            synchronous: Synchronous::Normal,
+            enable_foreign_keys: true,
+            busy_timeout: Some(Duration::from_secs(30)),
+            cache_size_kib: Some(8192),
+        }
+    }
+}
+
+impl ConnectionOptions {
+    /// Apply the settings which belong to a connection rather than to the
+    /// database file. These are applied whenever a connection is checked out
+    /// of the pool, so that they hold even if something changed them while
+    /// the connection was in use.
+    fn apply(&self, conn: &SqliteConnection) -> Result<(), diesel::result::Error> {
+        conn.batch_execute(&format!(
+            "PRAGMA busy_timeout = {}; PRAGMA synchronous = {}; PRAGMA foreign_keys = {};",
+            self.busy_timeout.map_or(0, |d| d.as_millis()),
+            self.synchronous,
+            self.enable_foreign_keys as u8,
+        ))?;
+        if let Some(cache_size_kib) = self.cache_size_kib {
+            // A negative cache size is in KiB rather than pages.
+            conn.batch_execute(&format!("PRAGMA cache_size = -{};", cache_size_kib))?;
+        }
+        Ok(())
+    }
 }
 
 impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
@@ -29,52 +170,80 @@ impl diesel::r2d2::CustomizeConnection<SqliteConnection, diesel::r2d2::Error>
                 conn.batch_execute(&format!("PRAGMA busy_timeout = {};", d.as_millis()))?;
             }
             WalletDb::set_db_encryption_key_from_env(conn);
-            if self.enable_wal {
-                conn.batch_execute("
-                    PRAGMA journal_mode = WAL;          -- better write-concurrency
-                    PRAGMA synchronous = NORMAL;        -- fsync only in critical moments
-                    PRAGMA wal_autocheckpoint = 1000;   -- write WAL changes back every 1000 pages, for an in average 1MB WAL file. May affect readers if number is increased
-                    PRAGMA wal_checkpoint(TRUNCATE);    -- free some space by truncating possibly massive WAL files from the last run.
-                ")?;
-            }
-            if self.enable_foreign_keys {
-                conn.batch_execute("PRAGMA foreign_keys = ON;")?;
-            } else {
-                conn.batch_execute("PRAGMA foreign_keys = OFF;")?;
+            match self.journal_mode {
+                JournalMode::Wal => {
+                    conn.batch_execute("
+                        PRAGMA journal_mode = WAL;          -- better write-concurrency
+                        PRAGMA wal_autocheckpoint = 1000;   -- write WAL changes back every 1000 pages, for an in average 1MB WAL file. May affect readers if number is increased
+                        PRAGMA wal_checkpoint(TRUNCATE);    -- free some space by truncating possibly massive WAL files from the last run.
+                    ")?;
+                }
+                // The journal mode is stored in the database file, so a database
+                // which used a write-ahead log has to be switched back.
+                JournalMode::Delete => conn.batch_execute("PRAGMA journal_mode = DELETE;")?,
             }
-
-            Ok(())
+            self.apply(conn)
         })()
         .map_err(diesel::r2d2::Error::QueryError)
     }
 }
 
+/// The effective SQLite settings of a connection to the wallet db.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabasePragmas {
+    pub journal_mode: String,
+    pub synchronous: String,
+    pub foreign_keys: bool,
+    pub busy_timeout_ms: u64,
+
+    /// Size of the page cache, in KiB.
+    pub cache_size_kib: u64,
+}
+
 #[derive(Clone)]
 pub struct WalletDb {
     pool: Pool<ConnectionManager<SqliteConnection>>,
+    options: ConnectionOptions,
 }
 
 impl WalletDb {
-    pub fn new(pool: Pool<ConnectionManager<SqliteConnection>>) -> Self {
-        Self { pool }
+    pub fn new(
+        pool: Pool<ConnectionManager<SqliteConnection>>,
+        options: ConnectionOptions,
+    ) -> Self {
+        Self { pool, options }
     }
 
     pub fn new_from_url(database_url: &str, db_connections: u32) -> Result<Self, WalletDbError> {
+        Self::new_from_url_with_options(database_url, db_connections, ConnectionOptions::default())
+    }
+
+    pub fn new_from_url_with_options(
+        database_url: &str,
+        db_connections: u32,
+        options: ConnectionOptions,
+    ) -> Result<Self, WalletDbError> {
         let manager = ConnectionManager::<SqliteConnection>::new(database_url);
         let pool = Pool::builder()
             .max_size(db_connections)
-            .connection_customizer(Box::new(ConnectionOptions {
-                enable_wal: true,
-                enable_foreign_keys: true,
-                busy_timeout: Some(Duration::from_secs(30)),
-            }))
+            .connection_customizer(Box::new(options.clone()))
             .test_on_check_out(true)
             .build(manager)?;
-        Ok(Self::new(pool))
+        Ok(Self::new(pool, options))
     }
 
     pub fn get_conn(&self) -> Result<Conn, WalletDbError> {
-        Ok(self.pool.get()?)
+        let conn = self.pool.get()?;
+        self.options.apply(&conn)?;
+        Ok(conn)
+    }
+
+    /// Get a connection, failing if none is free within the timeout rather
+    /// than waiting for the pool's connection timeout.
+    pub fn get_conn_timeout(&self, timeout: Duration) -> Result<Conn, WalletDbError> {
+        let conn = self.pool.get_timeout(timeout)?;
+        self.options.apply(&conn)?;
+        Ok(conn)
     }
 
     pub fn set_db_encryption_key_from_env(conn: &SqliteConnection) {
@@ -91,7 +260,7 @@ impl WalletDb {
         }
     }
 
-    pub fn try_change_db_encryption_key_from_env(conn: &SqliteConnection) {
+    pub fn try_change_db_encryption_key_from_env(conn: &SqliteConnection, logger: &Logger) {
         // Change the encryption key if specified by the environment variable.
         let encryption_key = env::var("MC_PASSWORD").unwrap_or_else(|_| "".to_string());
         let changed_encryption_key =
@@ -110,7 +279,7 @@ impl WalletDb {
             // Set the new password in the environment, so other threads can decrypt
             // correctly.
             env::set_var("MC_PASSWORD", changed_encryption_key);
-            global_log::info!("Re-encrypted database with new password.");
+            log::info!(logger, "Re-encrypted database with new password.");
         }
     }
 
@@ -155,26 +324,258 @@ impl WalletDb {
         conn.batch_execute("PRAGMA foreign_keys = ON;")
             .expect("failed enabling foreign keys");
     }
+
+    /// Run the pending migrations, first backing up the database file unless
+    /// backups are disabled. Returns the path of the backup, if one was made.
+    pub fn migrate(
+        conn: &SqliteConnection,
+        db_path: &Path,
+        options: &MigrationOptions,
+        logger: &Logger,
+    ) -> Result<Option<PathBuf>, WalletDbError> {
+        let pending_migrations = WalletDb::pending_migrations(conn)?;
+        if pending_migrations.is_empty() {
+            return Ok(None);
+        }
+        if options.skip_migrations {
+            return Err(WalletDbError::MigrationsPending(pending_migrations));
+        }
+
+        let backup = if options.backup_retention > 0 {
+            Some(WalletDb::backup(
+                conn,
+                db_path,
+                options.backup_retention,
+                logger,
+            )?)
+        } else {
+            None
+        };
+        WalletDb::run_migrations(conn);
+        Ok(backup)
+    }
+
+    /// The version of the latest migration the database has run.
+    pub fn schema_version(conn: &SqliteConnection) -> Result<Option<String>, WalletDbError> {
+        conn.setup()?;
+        Ok(conn.latest_run_migration_version()?)
+    }
+
+    /// The migrations embedded in the binary which the database has not run,
+    /// by directory name, oldest first.
+    pub fn pending_migrations(conn: &SqliteConnection) -> Result<Vec<String>, WalletDbError> {
+        conn.setup()?;
+        let run_versions = conn.previously_run_migration_versions()?;
+        Ok(MIGRATIONS
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .filter(|name| !run_versions.contains(&migration_version(name)))
+            .map(|name| name.to_string())
+            .collect())
+    }
+
+    /// Copy the database file to a sibling named for the time of the backup,
+    /// then remove the oldest backups beyond the retention.
+    pub fn backup(
+        conn: &SqliteConnection,
+        db_path: &Path,
+        retention: usize,
+        logger: &Logger,
+    ) -> Result<PathBuf, WalletDbError> {
+        // Write any changes in the write-ahead log back to the database file,
+        // so that the copy is complete.
+        conn.batch_execute("PRAGMA wal_checkpoint(TRUNCATE);")?;
+
+        let created_at_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let mut backup_path = db_path.as_os_str().to_owned();
+        backup_path.push(format!("{}{}", BACKUP_INFIX, created_at_millis));
+        let backup_path = PathBuf::from(backup_path);
+        if backup_path.exists() {
+            return Err(WalletDbError::Backup(format!(
+                "{:?} already exists",
+                backup_path
+            )));
+        }
+        fs::copy(db_path, &backup_path)
+            .map_err(|e| WalletDbError::Backup(format!("copying to {:?}: {}", backup_path, e)))?;
+        log::info!(logger, "Backed up database to {:?}", backup_path);
+
+        let backups = WalletDb::list_backups(db_path)?;
+        for backup in backups.iter().take(backups.len().saturating_sub(retention)) {
+            fs::remove_file(&backup.path)
+                .map_err(|e| WalletDbError::Backup(format!("removing {:?}: {}", backup.path, e)))?;
+        }
+
+        Ok(backup_path)
+    }
+
+    /// The backups of the database file made before running migrations,
+    /// oldest first.
+    pub fn list_backups(db_path: &Path) -> Result<Vec<DatabaseBackup>, WalletDbError> {
+        let file_name = match db_path.file_name() {
+            Some(file_name) => format!("{}{}", file_name.to_string_lossy(), BACKUP_INFIX),
+            None => return Ok(vec![]),
+        };
+        let dir = match db_path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+
+        let mut backups = Vec::new();
+        let entries = fs::read_dir(dir)
+            .map_err(|e| WalletDbError::Backup(format!("listing {:?}: {}", dir, e)))?;
+        for entry in entries {
+            let entry = entry.map_err(|e| WalletDbError::Backup(e.to_string()))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(Ok(created_at_millis)) =
+                name.strip_prefix(&file_name).map(str::parse::<u64>)
+            {
+                backups.push(DatabaseBackup {
+                    path: entry.path(),
+                    created_at_millis,
+                });
+            }
+        }
+        backups.sort_by_key(|backup| backup.created_at_millis);
+        Ok(backups)
+    }
+
+    /// The size of the database, in bytes.
+    pub fn size_bytes(conn: &SqliteConnection) -> Result<u64, WalletDbError> {
+        let page_count =
+            diesel::dsl::sql::<sql_types::BigInt>("PRAGMA page_count;").get_result::<i64>(conn)?;
+        let page_size =
+            diesel::dsl::sql::<sql_types::BigInt>("PRAGMA page_size;").get_result::<i64>(conn)?;
+        Ok((page_count * page_size) as u64)
+    }
+
+    /// The SQLite settings in effect on a connection.
+    pub fn pragmas(conn: &SqliteConnection) -> Result<DatabasePragmas, WalletDbError> {
+        let journal_mode = diesel::dsl::sql::<sql_types::Text>("PRAGMA journal_mode;")
+            .get_result::<String>(conn)?;
+        let synchronous =
+            diesel::dsl::sql::<sql_types::BigInt>("PRAGMA synchronous;").get_result::<i64>(conn)?;
+        let foreign_keys = diesel::dsl::sql::<sql_types::BigInt>("PRAGMA foreign_keys;")
+            .get_result::<i64>(conn)?;
+        let busy_timeout_ms = diesel::dsl::sql::<sql_types::BigInt>("PRAGMA busy_timeout;")
+            .get_result::<i64>(conn)?;
+        let cache_size =
+            diesel::dsl::sql::<sql_types::BigInt>("PRAGMA cache_size;").get_result::<i64>(conn)?;
+        let page_size =
+            diesel::dsl::sql::<sql_types::BigInt>("PRAGMA page_size;").get_result::<i64>(conn)?;
+
+        Ok(DatabasePragmas {
+            journal_mode,
+            synchronous: Synchronous::from_str(&synchronous.to_string())
+                .map_or_else(|_| synchronous.to_string(), |s| s.to_string()),
+            foreign_keys: foreign_keys != 0,
+            busy_timeout_ms: busy_timeout_ms as u64,
+            // A negative cache size is in KiB, and a positive one in pages.
+            cache_size_kib: if cache_size < 0 {
+                cache_size.unsigned_abs()
+            } else {
+                (cache_size * page_size / 1024) as u64
+            },
+        })
+    }
+
+    /// The path of the database file the connection is open on, if any.
+    pub fn file_path(conn: &SqliteConnection) -> Result<Option<PathBuf>, WalletDbError> {
+        let databases = diesel::dsl::sql::<(sql_types::BigInt, sql_types::Text, sql_types::Text)>(
+            "PRAGMA database_list;",
+        )
+        .get_results::<(i64, String, String)>(conn)?;
+        Ok(databases
+            .into_iter()
+            .find(|(_, name, file)| name == "main" && !file.is_empty())
+            .map(|(_, _, file)| PathBuf::from(file)))
+    }
+}
+
+/// The version diesel records for a migration: the date and time at the
+/// start of its directory name, without dashes.
+fn migration_version(name: &str) -> String {
+    name.split('_').next().unwrap_or_default().replace('-', "")
 }
 
 /// Create an immediate SQLite transaction with retry.
+///
+/// Starting the transaction is retried with exponential backoff, capped at
+/// MAX_DELAY_MS, while the database is locked by another connection, up to the
+/// configured number of attempts. Once the transaction has started, nothing else can write to the
+/// database until it ends, so errors from f are returned without retrying.
 /// Note: This function does not support nested transactions.
 pub fn transaction<T, E, F>(conn: &Conn, f: F) -> Result<T, E>
 where
-    F: Clone + FnOnce() -> Result<T, E>,
+    F: FnOnce() -> Result<T, E>,
     E: From<diesel::result::Error>,
 {
-    for i in 0..NUM_RETRIES {
-        let r = conn.exclusive_transaction::<T, E, F>(f.clone());
-        if r.is_ok() || i == (NUM_RETRIES - 1) {
-            return r;
+    let conn: &SqliteConnection = conn;
+    let transaction_manager = conn.transaction_manager();
+    let attempts = WRITE_ATTEMPTS.load(Ordering::Relaxed).max(1);
+    for i in 0.. {
+        match transaction_manager.begin_transaction_sql(conn, "BEGIN EXCLUSIVE") {
+            Ok(()) => break,
+            Err(e) if is_database_locked(&e) && i + 1 < attempts => {
+                sleep(write_retry_delay(i));
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    match f() {
+        Ok(value) => {
+            transaction_manager.commit_transaction(conn)?;
+            Ok(value)
+        }
+        Err(e) => {
+            transaction_manager.rollback_transaction(conn)?;
+            Err(e)
         }
-        sleep(Duration::from_millis((BASE_DELAY_MS * 2_u32.pow(i)) as u64));
     }
-    panic!("Should never reach this point.");
 }
 const BASE_DELAY_MS: u32 = 10;
-const NUM_RETRIES: u32 = 5;
+const MAX_DELAY_MS: u32 = 1000;
+
+/// The delay before the retry following the given failed attempt, counting
+/// from 0.
+fn write_retry_delay(attempt: u32) -> Duration {
+    let delay_ms = BASE_DELAY_MS
+        .saturating_mul(2_u32.saturating_pow(attempt))
+        .min(MAX_DELAY_MS);
+    Duration::from_millis(delay_ms as u64)
+}
+
+/// The number of times starting a write transaction is attempted while the
+/// database is locked.
+pub const DEFAULT_WRITE_ATTEMPTS: u32 = 5;
+
+/// The most times starting a write transaction may be configured to be
+/// attempted, so that a write waits at most a couple of minutes.
+pub const MAX_WRITE_ATTEMPTS: u32 = 100;
+
+static WRITE_ATTEMPTS: AtomicU32 = AtomicU32::new(DEFAULT_WRITE_ATTEMPTS);
+
+/// Set the number of times starting a write transaction is attempted while
+/// the database is locked, for the whole process.
+pub fn set_write_attempts(attempts: u32) {
+    WRITE_ATTEMPTS.store(attempts, Ordering::Relaxed);
+}
+
+/// Whether an error is SQLite reporting that another connection holds a lock
+/// on the database (SQLITE_BUSY or SQLITE_LOCKED), which diesel does not
+/// distinguish by kind.
+fn is_database_locked(error: &diesel::result::Error) -> bool {
+    match error {
+        diesel::result::Error::DatabaseError(_, info) => {
+            info.message().starts_with("database is locked")
+                || info.message().starts_with("database table is locked")
+        }
+        _ => false,
+    }
+}
 
 /// Escape a string for consumption by SQLite.
 /// This function doubles all single quote characters within the string, then
@@ -182,3 +583,329 @@ const NUM_RETRIES: u32 = 5;
 fn sql_escape_string(s: &str) -> String {
     format!("'{}'", s.replace('\'', "''"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{
+            account::{AccountID, AccountModel},
+            models::{Account, Txo},
+            txo::TxoModel,
+        },
+        test_utils::{create_test_txo_for_recipient, WalletDbTestContext, MOB},
+    };
+    use mc_account_keys::{AccountKey, RootIdentity};
+    use mc_common::logger::test_with_logger;
+    use mc_transaction_core::{tokens::Mob, Amount, Token};
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        thread,
+    };
+    use tempdir::TempDir;
+
+    /// Number of migrations the fixture database runs, leaving it at an older
+    /// schema than this build.
+    const FIXTURE_MIGRATIONS: usize = 2;
+
+    /// Create a database file at an older schema, with a gift code in it.
+    fn older_schema_db(dir: &Path) -> (SqliteConnection, PathBuf) {
+        let migrations_dir = dir.join("migrations");
+        for name in MIGRATIONS.split(',').take(FIXTURE_MIGRATIONS) {
+            let src = Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("migrations")
+                .join(name);
+            let dst = migrations_dir.join(name);
+            fs::create_dir_all(&dst).unwrap();
+            for file in ["up.sql", "down.sql"] {
+                fs::copy(src.join(file), dst.join(file)).unwrap();
+            }
+        }
+
+        let db_path = dir.join("wallet.db");
+        let conn = SqliteConnection::establish(db_path.to_str().unwrap()).unwrap();
+        diesel_migrations::run_pending_migrations_in_directory(
+            &conn,
+            &migrations_dir,
+            &mut std::io::sink(),
+        )
+        .unwrap();
+        conn.batch_execute(
+            "INSERT INTO gift_codes (id, gift_code_b58, value) VALUES (1, 'gift', 100);",
+        )
+        .unwrap();
+        (conn, db_path)
+    }
+
+    fn gift_code_count(conn: &SqliteConnection) -> i64 {
+        diesel::dsl::sql::<sql_types::BigInt>("SELECT count(*) FROM gift_codes;")
+            .get_result::<i64>(conn)
+            .unwrap()
+    }
+
+    #[test_with_logger]
+    fn test_migrate_backs_up_older_schema(logger: Logger) {
+        let dir = TempDir::new("wallet_db").unwrap();
+        let (conn, db_path) = older_schema_db(dir.path());
+
+        let migrations: Vec<&str> = MIGRATIONS.split(',').collect();
+        assert_eq!(
+            WalletDb::schema_version(&conn).unwrap(),
+            Some(migration_version(migrations[FIXTURE_MIGRATIONS - 1]))
+        );
+        assert_eq!(
+            WalletDb::pending_migrations(&conn).unwrap(),
+            migrations[FIXTURE_MIGRATIONS..].to_vec()
+        );
+
+        let options = MigrationOptions {
+            skip_migrations: false,
+            backup_retention: 3,
+        };
+        let backup_path = WalletDb::migrate(&conn, &db_path, &options, &logger)
+            .unwrap()
+            .expect("no backup made");
+
+        // The data survives the migrations, and the database is up to date.
+        assert_eq!(gift_code_count(&conn), 1);
+        assert_eq!(WalletDb::pending_migrations(&conn).unwrap().len(), 0);
+        assert_eq!(
+            WalletDb::schema_version(&conn).unwrap(),
+            Some(migration_version(migrations[migrations.len() - 1]))
+        );
+
+        // The backup has the data at the older schema.
+        let backup_conn = SqliteConnection::establish(backup_path.to_str().unwrap()).unwrap();
+        assert_eq!(gift_code_count(&backup_conn), 1);
+        assert_eq!(
+            WalletDb::schema_version(&backup_conn).unwrap(),
+            Some(migration_version(migrations[FIXTURE_MIGRATIONS - 1]))
+        );
+        assert_eq!(
+            WalletDb::list_backups(&db_path)
+                .unwrap()
+                .into_iter()
+                .map(|backup| backup.path)
+                .collect::<Vec<_>>(),
+            vec![backup_path]
+        );
+
+        // There is nothing to back up once the database is up to date.
+        assert_eq!(
+            WalletDb::migrate(&conn, &db_path, &options, &logger).unwrap(),
+            None
+        );
+    }
+
+    #[test_with_logger]
+    fn test_skip_migrations_refuses_pending(logger: Logger) {
+        let dir = TempDir::new("wallet_db").unwrap();
+        let (conn, db_path) = older_schema_db(dir.path());
+        let pending = WalletDb::pending_migrations(&conn).unwrap();
+
+        let options = MigrationOptions {
+            skip_migrations: true,
+            backup_retention: 3,
+        };
+        match WalletDb::migrate(&conn, &db_path, &options, &logger) {
+            Err(WalletDbError::MigrationsPending(migrations)) => assert_eq!(migrations, pending),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Nothing was migrated or backed up.
+        assert_eq!(WalletDb::pending_migrations(&conn).unwrap(), pending);
+        assert!(WalletDb::list_backups(&db_path).unwrap().is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_backup_retention(logger: Logger) {
+        let dir = TempDir::new("wallet_db").unwrap();
+        let (conn, db_path) = older_schema_db(dir.path());
+
+        let mut backup_paths = Vec::new();
+        for _ in 0..4 {
+            backup_paths.push(WalletDb::backup(&conn, &db_path, 2, &logger).unwrap());
+            // Backups are named by the millisecond they are made.
+            sleep(Duration::from_millis(2));
+        }
+
+        // Only the two most recent backups are kept.
+        let backups = WalletDb::list_backups(&db_path).unwrap();
+        assert_eq!(
+            backups
+                .into_iter()
+                .map(|backup| backup.path)
+                .collect::<Vec<_>>(),
+            backup_paths[2..].to_vec()
+        );
+        assert!(!backup_paths[0].exists());
+        assert!(!backup_paths[1].exists());
+    }
+
+    const NUM_WRITERS: u64 = 4;
+    const NUM_READERS: u64 = 4;
+    const TXOS_PER_WRITER: u64 = 50;
+
+    fn count_if_locked<T>(result: Result<T, WalletDbError>) -> usize {
+        match result {
+            Ok(_) => 0,
+            Err(WalletDbError::Diesel(e)) if is_database_locked(&e) => 1,
+            Err(e) => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    /// Receive txos from several threads while others read the balance, and
+    /// count the operations which failed because the database was locked.
+    /// Returns the number of lock errors and the number of txos received.
+    fn count_lock_errors(options: ConnectionOptions) -> (usize, usize) {
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance_with_options(options);
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let root_id = RootIdentity::from_random(&mut rng);
+        let account_key = AccountKey::from(&root_id);
+        let (account_id, _) = Account::create_from_root_entropy(
+            &root_id.root_entropy,
+            Some(0),
+            None,
+            None,
+            "",
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+
+        let writing = Arc::new(AtomicBool::new(true));
+        let readers: Vec<_> = (0..NUM_READERS)
+            .map(|_| {
+                let wallet_db = wallet_db.clone();
+                let writing = writing.clone();
+                let AccountID(account_id) = account_id.clone();
+                thread::spawn(move || {
+                    let conn = wallet_db.get_conn().unwrap();
+                    let mut lock_errors = 0;
+                    while writing.load(Ordering::SeqCst) {
+                        lock_errors += count_if_locked(
+                            Txo::list_unspent(
+                                Some(&account_id),
+                                None,
+                                Some(*Mob::ID),
+                                None,
+                                None,
+                                None,
+                                None,
+                                &conn,
+                            )
+                            .map(|txos| txos.iter().map(|txo| txo.value).sum::<i64>()),
+                        );
+                    }
+                    lock_errors
+                })
+            })
+            .collect();
+
+        let writers: Vec<_> = (0..NUM_WRITERS)
+            .map(|i| {
+                let wallet_db = wallet_db.clone();
+                let account_key = account_key.clone();
+                let AccountID(account_id) = account_id.clone();
+                thread::spawn(move || {
+                    let mut rng: StdRng = SeedableRng::seed_from_u64(i);
+                    let conn = wallet_db.get_conn().unwrap();
+                    let mut lock_errors = 0;
+                    for block_index in 0..TXOS_PER_WRITER {
+                        let amount = Amount::new(MOB, Mob::ID);
+                        let (tx_out, key_image) =
+                            create_test_txo_for_recipient(&account_key, 0, amount, &mut rng);
+                        lock_errors += count_if_locked(transaction(&conn, || {
+                            Txo::create_received(
+                                tx_out,
+                                Some(0),
+                                Some(key_image),
+                                amount,
+                                block_index,
+                                None,
+                                &account_id,
+                                &conn,
+                            )
+                        }));
+                    }
+                    lock_errors
+                })
+            })
+            .collect();
+
+        let mut lock_errors = 0;
+        for writer in writers {
+            lock_errors += writer.join().unwrap();
+        }
+        writing.store(false, Ordering::SeqCst);
+        for reader in readers {
+            lock_errors += reader.join().unwrap();
+        }
+
+        let num_txos = Txo::list_unspent(
+            Some(&account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap()
+        .len();
+        (lock_errors, num_txos)
+    }
+
+    #[test]
+    fn test_concurrent_access_without_lock_errors() {
+        let (lock_errors, num_txos) = count_lock_errors(ConnectionOptions::default());
+        assert_eq!(lock_errors, 0);
+        assert_eq!(num_txos as u64, NUM_WRITERS * TXOS_PER_WRITER);
+    }
+
+    #[test]
+    fn test_concurrent_access_with_rollback_journal_and_no_busy_timeout() {
+        // Without the write-ahead log, readers and writers lock each other out,
+        // and without a busy timeout they fail as soon as they do.
+        let (lock_errors, _) = count_lock_errors(ConnectionOptions {
+            journal_mode: JournalMode::Delete,
+            synchronous: Synchronous::Full,
+            enable_foreign_keys: true,
+            busy_timeout: None,
+            cache_size_kib: None,
+        });
+        assert!(lock_errors > 0);
+    }
+
+    #[test]
+    fn test_write_retry_delay_is_capped() {
+        assert_eq!(write_retry_delay(0), Duration::from_millis(10));
+        assert_eq!(write_retry_delay(3), Duration::from_millis(80));
+        assert_eq!(write_retry_delay(7), Duration::from_millis(1000));
+        // Past the point where the exponent overflows.
+        assert_eq!(write_retry_delay(99), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_parse_connection_options() {
+        assert_eq!("WAL".parse::<JournalMode>().unwrap(), JournalMode::Wal);
+        assert_eq!(
+            "delete".parse::<JournalMode>().unwrap(),
+            JournalMode::Delete
+        );
+        assert!("memory".parse::<JournalMode>().is_err());
+        assert_eq!(
+            "normal".parse::<Synchronous>().unwrap(),
+            Synchronous::Normal
+        );
+        assert_eq!("2".parse::<Synchronous>().unwrap(), Synchronous::Full);
+        assert!("sometimes".parse::<Synchronous>().is_err());
+    }
+}
@@ -3,6 +3,63 @@
 use crate::{db::gift_code::GiftCodeDbError, util::b58::B58Error};
 
 use displaydoc::Display;
+use std::fmt;
+
+/// The amounts involved when a transaction cannot be funded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsufficientFundsAmounts {
+    /// The token that could not be funded.
+    pub token_id: u64,
+
+    /// The value required, including the fee where it is paid in this token.
+    pub requested: u128,
+
+    /// The total unspent value available to the account.
+    pub available: u128,
+
+    /// The most that can be spent in a single transaction, after the fee.
+    pub max_spendable: u128,
+
+    /// How much more value is needed to cover the requested value.
+    pub shortfall: u128,
+}
+
+impl fmt::Display for InsufficientFundsAmounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Max spendable value in wallet: {}, but target value: {}",
+            self.max_spendable, self.requested
+        )
+    }
+}
+
+/// The amounts involved when the inputs chosen for a transaction cannot fund
+/// it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InsufficientInputFundsAmounts {
+    /// The token that could not be funded.
+    pub token_id: u64,
+
+    /// The value required, including the fee where it is paid in this token.
+    pub requested: u128,
+
+    /// The total value of the chosen inputs of this token.
+    pub input_value: u128,
+
+    /// How much more value is needed to cover the requested value.
+    pub shortfall: u128,
+}
+
+impl fmt::Display for InsufficientInputFundsAmounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Value of selected inputs: {}, but target value: {}",
+            self.input_value, self.requested
+        )
+    }
+}
 
 #[derive(Display, Debug)]
 pub enum WalletDbError {
@@ -59,10 +116,10 @@ pub enum WalletDbError {
     InsufficientFundsFragmentedTxos,
 
     /// Insufficient Funds: {0}
-    InsufficientFunds(String),
+    InsufficientFunds(InsufficientFundsAmounts),
 
     /// Insufficient funds from Txos under max_spendable_value: {0}
-    InsufficientFundsUnderMaxSpendable(String),
+    InsufficientFundsUnderMaxSpendable(InsufficientFundsAmounts),
 
     /// Invalid argument for query
     InvalidArgument(String),
@@ -91,6 +148,9 @@ pub enum WalletDbError {
     /// TransactionLog Not Found: {0}
     TransactionLogNotFound(String),
 
+    /// No transaction log has an output with public key: {0}
+    TransactionOutputNotFound(String),
+
     /// AccountTxoStatus not found: {0}
     AccountTxoStatusNotFound(String),
 
@@ -135,17 +195,70 @@ pub enum WalletDbError {
     /// Subaddresses are not supported for FOG enabled accounts
     SubaddressesNotSupportedForFOGEnabledAccounts,
 
+    /// Invalid fog info: {0}
+    InvalidFogInfo(String),
+
+    /// Subaddress index {0} is reserved, and cannot be assigned
+    ReservedSubaddressIndex(u64),
+
+    /// Subaddress index {0} is already assigned
+    SubaddressIndexAlreadyAssigned(u64),
+
     /// error converting keys
     KeyError(mc_crypto_keys::KeyError),
 
     /// invalid txo status
     InvalidTxoStatus(String),
 
+    /// Invalid account key source: {0}
+    InvalidAccountKeySource(String),
+
+    /// invalid transaction log status: {0}
+    InvalidTransactionLogStatus(String),
+
+    /// invalid transaction log order: {0}
+    InvalidTransactionLogOrder(String),
+
     /// Expected to find TxOut as an outlay
     ExpectedTxOutAsOutlay,
 
     /// Expected to find a membership proof for txo with id: {0}
     MissingTxoMembershipProof(String),
+
+    /// Idempotency key not found: {0}
+    IdempotencyKeyNotFound(String),
+
+    /// Idempotency key was already used with different parameters: {0}
+    IdempotencyKeyMismatch(String),
+
+    /// A request with this idempotency key is still in progress: {0}
+    IdempotencyKeyInProgress(String),
+
+    /// The request made with idempotency key {0} may have been submitted, check the account's transaction logs before retrying with a new key: {1}
+    IdempotencyKeySubmissionUnknown(String, String),
+
+    /// Transaction report not found: {0}
+    TransactionReportNotFound(String),
+
+    /// The database has pending migrations, and migrations are skipped: {0:?}
+    MigrationsPending(Vec<String>),
+
+    /// Error backing up the database: {0}
+    Backup(String),
+
+    /// Invalid ring recorded for an input txo: {0}
+    InvalidInputRing(String),
+}
+
+impl WalletDbError {
+    /// The amounts involved, if this error was caused by insufficient funds.
+    pub fn insufficient_funds(&self) -> Option<&InsufficientFundsAmounts> {
+        match self {
+            Self::InsufficientFunds(amounts) => Some(amounts),
+            Self::InsufficientFundsUnderMaxSpendable(amounts) => Some(amounts),
+            _ => None,
+        }
+    }
 }
 
 impl From<diesel::result::Error> for WalletDbError {
@@ -3,7 +3,7 @@
 //! Errors for the wallet service.
 
 use crate::{
-    db::WalletDbError,
+    db::{InsufficientFundsAmounts, InsufficientInputFundsAmounts, WalletDbError},
     service::{
         account::AccountServiceError, balance::BalanceServiceError,
         confirmation_number::ConfirmationServiceError, gift_code::GiftCodeServiceError,
@@ -236,14 +236,32 @@ impl From<B58Error> for SyncError {
 #[allow(clippy::large_enum_variant)]
 pub enum WalletTransactionBuilderError {
     /// Insufficient Funds: {0}
-    InsufficientFunds(String),
+    InsufficientFunds(InsufficientFundsAmounts),
 
     /// Insufficient Funds in inputs to construct transaction: {0}
-    InsufficientInputFunds(String),
+    InsufficientInputFunds(InsufficientInputFundsAmounts),
 
     /// Insufficient TxOuts to construct transaction
     InsufficientTxOuts,
 
+    /// Too many inputs: the transaction would have {current}, and consensus accepts at most {max}
+    TooManyInputs { current: u64, max: u64 },
+
+    /// Too many outputs: the transaction would have {current} including change, and consensus accepts at most {max}
+    TooManyOutputs { current: u64, max: u64 },
+
+    /// Membership proof for tx out {index} does not match the ledger root
+    InvalidMembershipProof { index: u64 },
+
+    /// Subaddress {0} does not belong to the account
+    SourceSubaddressNotInAccount(String),
+
+    /// Txos {0:?} are not at the source subaddress, and cannot be spent with it
+    InputsOutsideSourceSubaddress(Vec<String>),
+
+    /// Returning change to the source subaddress requires a source subaddress
+    ChangeToSourceWithoutSourceSubaddress,
+
     /// Ring size does not match number of inputs
     RingSizeMismatch,
 
@@ -274,6 +292,12 @@ pub enum WalletTransactionBuilderError {
     /// Attempting to build a transaction from a TXO without a subaddress: {0}
     NullSubaddress(String),
 
+    /// An input is not at subaddress index {0} of the signing account
+    InputSubaddressIndexMismatch(u64),
+
+    /// The unsigned transaction has schema version {version}, and this signer supports up to {supported}
+    UnsupportedUnsignedTxSchemaVersion { version: u64, supported: u64 },
+
     /// Error executing diesel transaction: {0}
     Diesel(diesel::result::Error),
 
@@ -310,6 +334,17 @@ pub enum WalletTransactionBuilderError {
     /// Transaction is missing inputs for outputs with token id {0}
     MissingInputsForTokenId(String),
 
+    /// Transaction has no inputs to pay the fee with token id {0}
+    MissingInputsForFeeTokenId(String),
+
+    /// Output of {value} with token id {token_id} is below the dust threshold
+    /// of {minimum}
+    OutputBelowDustThreshold {
+        token_id: u64,
+        value: u64,
+        minimum: u64,
+    },
+
     /// Error decoding the hex string: {0}
     FromHexError(hex::FromHexError),
 
@@ -317,6 +352,26 @@ pub enum WalletTransactionBuilderError {
     InvalidBurnRedemptionMemo(String),
 }
 
+impl WalletTransactionBuilderError {
+    /// The amounts involved, if this error was caused by insufficient funds.
+    pub fn insufficient_funds(&self) -> Option<&InsufficientFundsAmounts> {
+        match self {
+            Self::InsufficientFunds(amounts) => Some(amounts),
+            Self::WalletDb(e) => e.insufficient_funds(),
+            _ => None,
+        }
+    }
+
+    /// The amounts involved, if this error was caused by the chosen inputs
+    /// not covering the transaction.
+    pub fn insufficient_input_funds(&self) -> Option<&InsufficientInputFundsAmounts> {
+        match self {
+            Self::InsufficientInputFunds(amounts) => Some(amounts),
+            _ => None,
+        }
+    }
+}
+
 impl From<mc_transaction_core::AmountError> for WalletTransactionBuilderError {
     fn from(src: mc_transaction_core::AmountError) -> Self {
         Self::AmountError(src)
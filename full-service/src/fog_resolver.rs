@@ -6,7 +6,14 @@ use serde::{Deserialize, Serialize};
 
 use crate::util::b58::b58_encode_public_address;
 
-use std::convert::TryFrom;
+use std::{
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FullServiceFogResolver(pub HashMap<String, FullServiceFullyValidatedFogPubkey>);
@@ -54,3 +61,118 @@ impl TryFrom<FullServiceFullyValidatedFogPubkey> for FullyValidatedFogPubkey {
         })
     }
 }
+
+/// Fog pubkeys which have been fetched and validated, shared across builds so
+/// that paying many recipients of the same fog service does not fetch and
+/// validate its report for every transaction.
+///
+/// Pubkeys are keyed by the fog report url and report id of the recipient.
+/// An entry is reused until the max TTL passes, and only for transactions
+/// whose tombstone block is before the pubkey expires.
+pub struct FogPubkeyCache {
+    /// Longest an entry is reused for. Zero disables the cache.
+    max_ttl: Duration,
+
+    /// Cached pubkeys by fog report url and report id.
+    entries: Mutex<HashMap<(String, String), CachedFogPubkey>>,
+
+    /// Number of lookups answered from the cache.
+    hits: AtomicU64,
+
+    /// Number of lookups which needed the fog report to be fetched.
+    misses: AtomicU64,
+}
+
+struct CachedFogPubkey {
+    pubkey: FullServiceFullyValidatedFogPubkey,
+    expires: Instant,
+}
+
+/// Counters of a fog pubkey cache.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FogPubkeyCacheStats {
+    pub num_entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl FogPubkeyCache {
+    pub fn new(max_ttl: Duration) -> Self {
+        Self {
+            max_ttl,
+            entries: Mutex::new(HashMap::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the cached pubkey for a fog recipient, if it can be used for a
+    /// transaction with the given tombstone block. An entry which has
+    /// expired, or which expires before the tombstone block, is dropped so
+    /// that the report is fetched again.
+    pub fn get(
+        &self,
+        address: &PublicAddress,
+        tombstone_block: u64,
+    ) -> Option<FullServiceFullyValidatedFogPubkey> {
+        let key = Self::key(address)?;
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        let pubkey = match entries.get(&key) {
+            Some(entry)
+                if entry.expires > Instant::now()
+                    && entry.pubkey.pubkey_expiry >= tombstone_block =>
+            {
+                Some(entry.pubkey.clone())
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        match pubkey {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        pubkey
+    }
+
+    /// Cache a pubkey which was validated for a fog recipient.
+    pub fn insert(&self, address: &PublicAddress, pubkey: FullServiceFullyValidatedFogPubkey) {
+        if self.max_ttl.is_zero() {
+            return;
+        }
+        if let Some(key) = Self::key(address) {
+            self.entries.lock().expect("lock poisoned").insert(
+                key,
+                CachedFogPubkey {
+                    pubkey,
+                    expires: Instant::now() + self.max_ttl,
+                },
+            );
+        }
+    }
+
+    /// Drop every cached pubkey, returning how many there were.
+    pub fn flush(&self) -> usize {
+        let mut entries = self.entries.lock().expect("lock poisoned");
+        let num_entries = entries.len();
+        entries.clear();
+        num_entries
+    }
+
+    pub fn stats(&self) -> FogPubkeyCacheStats {
+        FogPubkeyCacheStats {
+            num_entries: self.entries.lock().expect("lock poisoned").len() as u64,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn key(address: &PublicAddress) -> Option<(String, String)> {
+        let fog_report_url = address.fog_report_url()?;
+        let fog_report_id = address.fog_report_id().unwrap_or_default();
+        Some((fog_report_url.to_string(), fog_report_id.to_string()))
+    }
+}
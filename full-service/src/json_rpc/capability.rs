@@ -0,0 +1,153 @@
+// Copyright (c) 2020-2021 MobileCoin Inc.
+
+//! Capabilities which a listener of the JSON RPC server grants to its clients,
+//! so that payments or read-only clients can be served on a different address
+//! from the wallet's administrators.
+
+use std::{collections::BTreeSet, fmt, iter::FromIterator, str::FromStr};
+
+/// A group of methods which a listener may serve.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Capability {
+    /// Reading the state of the wallet, its accounts and the ledger.
+    Read,
+
+    /// Building transactions, receipts and addresses, without submitting
+    /// anything to the network.
+    Build,
+
+    /// Submitting transactions to the network.
+    Submit,
+
+    /// Managing accounts and the wallet, including the methods which expose
+    /// secrets.
+    Admin,
+}
+
+impl Capability {
+    pub const ALL: [Capability; 4] = [
+        Capability::Read,
+        Capability::Build,
+        Capability::Submit,
+        Capability::Admin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Capability::Read => "read",
+            Capability::Build => "build",
+            Capability::Submit => "submit",
+            Capability::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Capability {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        Capability::ALL
+            .iter()
+            .find(|capability| capability.name() == src)
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "Unknown capability {}, expected one of read, build, submit, admin",
+                    src
+                )
+            })
+    }
+}
+
+/// The capabilities granted by a listener, given on the command line as a
+/// comma separated list such as "read,build", or "all".
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapabilitySet(BTreeSet<Capability>);
+
+impl CapabilitySet {
+    pub fn all() -> Self {
+        Capability::ALL.iter().copied().collect()
+    }
+
+    pub fn contains(&self, capability: Capability) -> bool {
+        self.0.contains(&capability)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.iter().map(Capability::name).collect()
+    }
+
+    pub fn intersection(&self, other: &CapabilitySet) -> CapabilitySet {
+        self.0.intersection(&other.0).copied().collect()
+    }
+}
+
+impl Default for CapabilitySet {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl FromIterator<Capability> for CapabilitySet {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl fmt::Display for CapabilitySet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.names().join(","))
+    }
+}
+
+impl FromStr for CapabilitySet {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        if src == "all" {
+            return Ok(Self::all());
+        }
+        let capabilities = src
+            .split(',')
+            .map(|capability| capability.trim().parse())
+            .collect::<Result<CapabilitySet, String>>()?;
+        if capabilities.0.is_empty() {
+            return Err("Expected at least one capability".to_string());
+        }
+        Ok(capabilities)
+    }
+}
+
+/// The capabilities of the listener a request arrived on, managed by rocket.
+/// A listener which does not manage one grants every capability.
+pub struct ListenerCapabilities(pub CapabilitySet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_capability_set() {
+        let capabilities: CapabilitySet = "read,build".parse().unwrap();
+        assert!(capabilities.contains(Capability::Read));
+        assert!(capabilities.contains(Capability::Build));
+        assert!(!capabilities.contains(Capability::Submit));
+        assert!(!capabilities.contains(Capability::Admin));
+        assert_eq!(capabilities.to_string(), "read,build");
+
+        assert_eq!("all".parse::<CapabilitySet>().unwrap(), CapabilitySet::all());
+        assert_eq!(
+            "admin, submit, read, build".parse::<CapabilitySet>().unwrap(),
+            CapabilitySet::all()
+        );
+
+        assert!("".parse::<CapabilitySet>().is_err());
+        assert!("read,secrets".parse::<CapabilitySet>().is_err());
+    }
+}
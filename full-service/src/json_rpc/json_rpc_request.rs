@@ -24,3 +24,15 @@ pub struct JsonRPCRequest {
     /// not optional.
     pub id: serde_json::Value,
 }
+
+/// A JSON-RPC 2.0 call, which is either a single request or a batch of
+/// requests.
+///
+/// The requests of a batch are kept as JSON values, so that a malformed
+/// request gets an error response without failing the rest of the batch.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum JsonRPCCall {
+    Single(JsonRPCRequest),
+    Batch(Vec<serde_json::Value>),
+}
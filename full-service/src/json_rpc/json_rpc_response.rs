@@ -3,7 +3,13 @@
 //! JSON-RPC Responses from the Wallet API.
 //!
 //! API v2
+use crate::{
+    json_rpc::capability::{Capability, CapabilitySet},
+    service::{confirmation::ConfirmationError, transaction::TransactionServiceError},
+    util::shutdown::ShuttingDown,
+};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use strum::Display;
 
 pub trait JsonCommandResponse {}
@@ -38,6 +44,23 @@ where
 
     /// The id of the Request object to which this response corresponds.
     pub id: serde_json::Value,
+
+    /// The request ID used to tag the server logs for this request, either
+    /// taken from the X-Request-Id header or generated by the server.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub request_id: Option<String>,
+}
+
+/// The response to a JSON RPC 2.0 call: either a single response, or the
+/// responses to the requests of a batch, in the order of the requests.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(untagged)]
+pub enum JsonRPCCallResponse<Response>
+where
+    Response: JsonCommandResponse,
+{
+    Single(JsonRPCResponse<Response>),
+    Batch(Vec<JsonRPCResponse<Response>>),
 }
 
 /// A JSON RPC Error.
@@ -74,6 +97,18 @@ pub enum JsonRPCErrorCodes {
 
     /// Internal Error.
     InternalError = -32603,
+
+    /// Server is busy, retry later.
+    ServerBusy = -32000,
+
+    /// The request is missing a required API key.
+    Unauthorized = -32001,
+
+    /// The request is irreversible, and requires a confirmation challenge.
+    ConfirmationRequired = -32002,
+
+    /// The method is not permitted on this endpoint.
+    NotPermitted = -32003,
     /* Server error.
      * ServerError(i32), // FIXME: WalletServiceError -> i32 between 32000 and 32099 */
 }
@@ -89,6 +124,164 @@ pub fn format_error<T: std::fmt::Display + std::fmt::Debug>(e: T) -> JsonRPCErro
     }
 }
 
+/// Helper method to format transaction errors in JSON RPC 2.0 format.
+///
+/// When the error was caused by insufficient funds, the amounts involved are
+/// included in the error data alongside the human readable details. When the
+/// inputs chosen for the transaction could not cover it, the value of those
+/// inputs is given instead of the wallet's balances.
+///
+/// When the service is too busy to build the transaction, the error uses the
+/// ServerBusy code and includes the suggested delay before retrying.
+///
+/// When the network did not accept a submitted transaction, the category of
+/// the rejection is included, so that clients can decide whether to retry,
+/// rebuild or alert.
+pub fn format_transaction_error(e: TransactionServiceError) -> JsonRPCError {
+    let mut data = json!({"server_error": format!("{:?}", e), "details": e.to_string()});
+    if let Some(retry_after) = e.retry_after() {
+        data["retry_after_seconds"] = json!(retry_after.as_secs().to_string());
+        return JsonRPCError::error {
+            code: JsonRPCErrorCodes::ServerBusy as i32,
+            message: JsonRPCErrorCodes::ServerBusy.to_string(),
+            data,
+        };
+    }
+    if let Some(amounts) = e.insufficient_funds() {
+        data["token_id"] = json!(amounts.token_id.to_string());
+        data["requested"] = json!(amounts.requested.to_string());
+        data["available"] = json!(amounts.available.to_string());
+        data["max_spendable"] = json!(amounts.max_spendable.to_string());
+        data["shortfall"] = json!(amounts.shortfall.to_string());
+    }
+    if let Some(amounts) = e.insufficient_input_funds() {
+        data["token_id"] = json!(amounts.token_id.to_string());
+        data["requested"] = json!(amounts.requested.to_string());
+        data["input_value"] = json!(amounts.input_value.to_string());
+        data["shortfall"] = json!(amounts.shortfall.to_string());
+    }
+    if let Some(category) = e.submission_error_category() {
+        data["submission_error"] = json!({
+            "code": category.code(),
+            "detail": e.to_string(),
+            "suggested_action": category.suggested_action(),
+        });
+    }
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::InternalError as i32,
+        message: JsonRPCErrorCodes::InternalError.to_string(),
+        data,
+    }
+}
+
+/// Helper method to format the error for a request which arrived while the
+/// wallet is shutting down, in JSON RPC 2.0 format.
+///
+/// The error uses the ServerBusy code, so that clients retry, and includes
+/// the suggested delay before retrying.
+pub fn format_shutting_down_error(e: ShuttingDown) -> JsonRPCError {
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::ServerBusy as i32,
+        message: JsonRPCErrorCodes::ServerBusy.to_string(),
+        data: json!({
+            "details": "The wallet is shutting down",
+            "retry_after_seconds": e.retry_after.as_secs().to_string(),
+        }),
+    }
+}
+
+/// Helper method to format the error for a request whose body is larger than
+/// the limit of its route, in JSON RPC 2.0 format. The size received is only
+/// known when the client sent a Content-Length.
+pub fn format_request_too_large_error(limit: u64, received: Option<u64>) -> JsonRPCError {
+    let details = match received {
+        Some(received) => format!(
+            "The request body of {} bytes is larger than the {} byte limit",
+            received, limit
+        ),
+        None => format!("The request body is larger than the {} byte limit", limit),
+    };
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::InvalidRequest as i32,
+        message: JsonRPCErrorCodes::InvalidRequest.to_string(),
+        data: json!({
+            "details": details,
+            "limit": limit.to_string(),
+            "received": received.map(|received| received.to_string()),
+        }),
+    }
+}
+
+/// Helper method to format authorization errors in JSON RPC 2.0 format.
+pub fn format_unauthorized_error(details: String) -> JsonRPCError {
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::Unauthorized as i32,
+        message: JsonRPCErrorCodes::Unauthorized.to_string(),
+        data: json!({ "details": details }),
+    }
+}
+
+/// Helper method to format errors for methods which need a capability the
+/// listener does not grant in JSON RPC 2.0 format.
+pub fn format_not_permitted_error(
+    method: &str,
+    required_capability: Capability,
+    listener_capabilities: &CapabilitySet,
+) -> JsonRPCError {
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::NotPermitted as i32,
+        message: JsonRPCErrorCodes::NotPermitted.to_string(),
+        data: json!({
+            "details": format!(
+                "The {} method requires the {} capability, which is not permitted on this endpoint",
+                method, required_capability
+            ),
+            "required_capability": required_capability.name(),
+            "listener_capabilities": listener_capabilities.names(),
+        }),
+    }
+}
+
+/// Helper method to format errors for requests which reach an account the API
+/// key is not scoped to in JSON RPC 2.0 format. Without an account, the
+/// method operates on the whole wallet.
+pub fn format_account_not_permitted_error(method: &str, account_id: Option<&str>) -> JsonRPCError {
+    let details = match account_id {
+        Some(account_id) => format!(
+            "The API key does not permit the {} method on account {}",
+            method, account_id
+        ),
+        None => format!(
+            "The {} method operates on the whole wallet, which requires an API key with the admin capability",
+            method
+        ),
+    };
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::NotPermitted as i32,
+        message: JsonRPCErrorCodes::NotPermitted.to_string(),
+        data: json!({
+            "details": details,
+            "account_id": account_id,
+        }),
+    }
+}
+
+/// Helper method to format confirmation errors in JSON RPC 2.0 format.
+///
+/// The reason lets clients tell a missing challenge from one which expired,
+/// was already used, or was prepared for a different request.
+pub fn format_confirmation_error(e: ConfirmationError) -> JsonRPCError {
+    JsonRPCError::error {
+        code: JsonRPCErrorCodes::ConfirmationRequired as i32,
+        message: JsonRPCErrorCodes::ConfirmationRequired.to_string(),
+        data: json!({
+            "server_error": format!("{:?}", e),
+            "details": e.to_string(),
+            "reason": e.reason(),
+        }),
+    }
+}
+
 /// Helper method to format displaydoc invalid request errors in JSON RPC 2.0
 /// format.
 pub fn format_invalid_request_error<T: std::fmt::Display + std::fmt::Debug>(e: T) -> JsonRPCError {
@@ -100,3 +293,37 @@ pub fn format_invalid_request_error<T: std::fmt::Display + std::fmt::Debug>(e: T
         data,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{InsufficientFundsAmounts, WalletDbError};
+
+    #[test]
+    fn test_format_insufficient_funds_error() {
+        let amounts = InsufficientFundsAmounts {
+            token_id: 1,
+            requested: 1_000,
+            available: 600,
+            max_spendable: 500,
+            shortfall: 400,
+        };
+        let e = TransactionServiceError::Database(WalletDbError::InsufficientFunds(amounts));
+        let details = e.to_string();
+
+        let JsonRPCError::error {
+            code,
+            message,
+            data,
+        } = format_transaction_error(e);
+        assert_eq!(code, JsonRPCErrorCodes::InternalError as i32);
+        assert_eq!(message, "InternalError");
+        assert_eq!(data["details"], details);
+        assert_eq!(data["token_id"], "1");
+        assert_eq!(data["requested"], "1000");
+        assert_eq!(data["available"], "600");
+        assert_eq!(data["max_spendable"], "500");
+        assert_eq!(data["shortfall"], "400");
+        assert!(data.get("input_value").is_none());
+    }
+}
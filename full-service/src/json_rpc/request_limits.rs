@@ -0,0 +1,150 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Limits on the size of request bodies, which can be set for each route, so
+//! that large transaction proposals can be submitted to the wallet endpoints
+//! while other routes keep a small limit.
+
+use crate::util::constants::{
+    DEFAULT_REQUEST_SIZE_LIMIT_BYTES, DEFAULT_WALLET_REQUEST_SIZE_LIMIT_BYTES,
+};
+use rocket::{
+    data::{self, Data, FromDataSimple},
+    http::Status,
+    outcome::Outcome,
+    Request, State,
+};
+use serde::de::DeserializeOwned;
+use std::{collections::BTreeMap, io::Read};
+
+/// The routes which receive transaction proposals, and so have the wallet
+/// limit by default.
+pub const WALLET_ROUTES: &[&str] = &["/wallet", "/wallet/v2"];
+
+/// The largest request body accepted by each route, managed by rocket.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequestSizeLimits {
+    /// The limit of routes without their own.
+    pub default: u64,
+
+    /// The limits of routes, by path.
+    pub routes: BTreeMap<String, u64>,
+}
+
+impl RequestSizeLimits {
+    /// The given default limit, with the wallet limit on the wallet routes and
+    /// the given limits on their routes.
+    pub fn new(default: u64, wallet: u64, routes: &[(String, u64)]) -> Self {
+        let mut limits = Self {
+            default,
+            routes: WALLET_ROUTES
+                .iter()
+                .map(|route| (route.to_string(), wallet))
+                .collect(),
+        };
+        limits.routes.extend(routes.iter().cloned());
+        limits
+    }
+
+    /// The largest request body accepted by the route at the given path.
+    pub fn limit_for(&self, path: &str) -> u64 {
+        *self.routes.get(path).unwrap_or(&self.default)
+    }
+}
+
+impl Default for RequestSizeLimits {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_REQUEST_SIZE_LIMIT_BYTES,
+            DEFAULT_WALLET_REQUEST_SIZE_LIMIT_BYTES,
+            &[],
+        )
+    }
+}
+
+/// A request body which was larger than the limit of its route.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OversizedBody {
+    /// The limit of the route, in bytes.
+    pub limit: u64,
+
+    /// The size of the body, from its Content-Length, if the client sent one.
+    pub received: Option<u64>,
+}
+
+/// The oversized body of a request, cached on the request for the catcher.
+struct RejectedBody(OversizedBody);
+
+/// The limit of the route of the request, as managed by rocket, or the
+/// default limits if none are managed.
+fn route_limit(req: &Request) -> u64 {
+    let path = req.uri().path();
+    match req.guard::<State<RequestSizeLimits>>() {
+        Outcome::Success(limits) => limits.limit_for(path),
+        _ => RequestSizeLimits::default().limit_for(path),
+    }
+}
+
+/// The body of a request which was rejected for being larger than the limit
+/// of its route. The first call caches the limit and size on the request.
+pub fn oversized_body(req: &Request) -> OversizedBody {
+    req.local_cache(|| {
+        RejectedBody(OversizedBody {
+            limit: route_limit(req),
+            received: content_length(req),
+        })
+    })
+    .0
+}
+
+fn content_length(req: &Request) -> Option<u64> {
+    req.headers()
+        .get_one("Content-Length")
+        .and_then(|length| length.parse().ok())
+}
+
+/// A JSON request body, read up to the limit of the route it was sent to.
+///
+/// Like rocket's Json, malformed JSON is rejected with 400 and JSON of the
+/// wrong shape with 422. A body over the limit is rejected with 413.
+pub struct LimitedJson<T>(pub T);
+
+impl<T: DeserializeOwned> FromDataSimple for LimitedJson<T> {
+    type Error = String;
+
+    fn from_data(req: &Request, data: Data) -> data::Outcome<Self, String> {
+        let limit = route_limit(req);
+        let mut body = Vec::new();
+        if let Err(err) = data.open().take(limit + 1).read_to_end(&mut body) {
+            return Outcome::Failure((Status::BadRequest, err.to_string()));
+        }
+        if body.len() as u64 > limit {
+            // Cache the rejection for the catcher.
+            oversized_body(req);
+            return Outcome::Failure((
+                Status::PayloadTooLarge,
+                format!("Request body is larger than the {} byte limit", limit),
+            ));
+        }
+
+        match serde_json::from_slice(&body) {
+            Ok(value) => Outcome::Success(LimitedJson(value)),
+            Err(err) if err.is_data() => {
+                Outcome::Failure((Status::UnprocessableEntity, err.to_string()))
+            }
+            Err(err) => Outcome::Failure((Status::BadRequest, err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_for() {
+        let limits = RequestSizeLimits::new(100, 1000, &[("/wallet".to_string(), 500)]);
+        assert_eq!(limits.limit_for("/wallet"), 500);
+        assert_eq!(limits.limit_for("/wallet/v2"), 1000);
+        assert_eq!(limits.limit_for("/wallet/v2/stream"), 100);
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright (c) 2020-2021 MobileCoin Inc.
+
+//! API keys which are bound to a set of accounts and capabilities, so that
+//! teams sharing a wallet can each only reach their own accounts.
+
+use crate::json_rpc::capability::{Capability, CapabilitySet};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// An API key, and the accounts and capabilities it grants.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ScopedApiKey {
+    pub key: String,
+
+    /// The capabilities granted by the key, on top of those of the listener
+    /// the request arrives on.
+    pub capabilities: CapabilitySet,
+
+    /// The accounts the key may reach. Keys with the admin capability reach
+    /// every account.
+    pub account_ids: BTreeSet<String>,
+}
+
+impl ScopedApiKey {
+    /// Whether the key reaches every account, and may call the methods which
+    /// operate on the whole wallet.
+    pub fn is_admin(&self) -> bool {
+        self.capabilities.contains(Capability::Admin)
+    }
+}
+
+/// A scoped API key as written in the API keys file, such as
+/// `{"key": "...", "capabilities": "read,build", "account_ids": ["..."]}`.
+#[derive(Deserialize)]
+struct ScopedApiKeyJSON {
+    key: String,
+    capabilities: String,
+    #[serde(default)]
+    account_ids: Vec<String>,
+}
+
+/// Parses the contents of an API keys file, a JSON list of scoped keys.
+pub fn parse_scoped_api_keys(src: &str) -> Result<Vec<ScopedApiKey>, String> {
+    let keys: Vec<ScopedApiKeyJSON> =
+        serde_json::from_str(src).map_err(|err| format!("Error parsing API keys: {}", err))?;
+    keys.into_iter()
+        .enumerate()
+        .map(|(i, key)| {
+            if key.key.is_empty() {
+                return Err(format!("API key {} is empty", i));
+            }
+            Ok(ScopedApiKey {
+                key: key.key,
+                capabilities: key
+                    .capabilities
+                    .parse()
+                    .map_err(|err| format!("API key {}: {}", i, err))?,
+                account_ids: key.account_ids.into_iter().collect(),
+            })
+        })
+        .collect()
+}
+
+/// The scoped API keys, managed by rocket. A listener which does not manage
+/// them only accepts the API key.
+pub struct ScopedApiKeysState(pub Vec<ScopedApiKey>);
+
+/// The accounts whose data a request reaches.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AccountScope<'a> {
+    /// No account's data, such as the state of the ledger.
+    None,
+
+    /// The data of the given accounts only.
+    Accounts(Vec<&'a str>),
+
+    /// The data of every account, which is filtered down to the accounts a
+    /// scoped key may reach.
+    Filtered,
+
+    /// The data of the whole wallet, or of accounts which the request does
+    /// not name, such as a txo or transaction log by id.
+    Wallet,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scoped_api_keys() {
+        let keys = parse_scoped_api_keys(
+            r#"[
+                {"key": "reader", "capabilities": "read", "account_ids": ["a", "b"]},
+                {"key": "admin", "capabilities": "all"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].key, "reader");
+        assert!(!keys[0].is_admin());
+        assert_eq!(
+            keys[0].account_ids,
+            vec!["a".to_string(), "b".to_string()].into_iter().collect()
+        );
+        assert!(keys[1].is_admin());
+        assert!(keys[1].account_ids.is_empty());
+
+        assert!(parse_scoped_api_keys(r#"[{"key": "", "capabilities": "read"}]"#).is_err());
+        assert!(parse_scoped_api_keys(r#"[{"key": "k", "capabilities": "write"}]"#).is_err());
+    }
+}
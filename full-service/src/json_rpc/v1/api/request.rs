@@ -3,6 +3,7 @@
 //! The JSON RPC 2.0 Requests to the Wallet API for Full Service.
 
 use crate::json_rpc::{
+    capability::Capability,
     json_rpc_request::JsonRPCRequest,
     v1::models::{receiver_receipt::ReceiverReceipt, tx_proposal::TxProposal},
 };
@@ -35,6 +36,83 @@ impl TryFrom<&JsonRPCRequest> for JsonCommandRequest {
     }
 }
 
+impl JsonCommandRequest {
+    /// Whether the method exposes or destroys private key material, and so
+    /// requires the secrets API key when one is configured.
+    pub fn exposes_secrets(&self) -> bool {
+        matches!(
+            self,
+            JsonCommandRequest::export_account_secrets { .. }
+                | JsonCommandRequest::remove_account { .. }
+        )
+    }
+
+    /// The capability a listener must grant to serve the method. Every method
+    /// is listed, so that a new method can not be added without choosing one.
+    pub fn capability(&self) -> Capability {
+        match self {
+            JsonCommandRequest::check_b58_type { .. }
+            | JsonCommandRequest::check_gift_code_status { .. }
+            | JsonCommandRequest::check_receiver_receipt_status { .. }
+            | JsonCommandRequest::get_account { .. }
+            | JsonCommandRequest::get_account_status { .. }
+            | JsonCommandRequest::get_address_for_account { .. }
+            | JsonCommandRequest::get_addresses_for_account { .. }
+            | JsonCommandRequest::get_all_accounts
+            | JsonCommandRequest::get_all_gift_codes
+            | JsonCommandRequest::get_all_transaction_logs_for_block { .. }
+            | JsonCommandRequest::get_all_transaction_logs_ordered_by_block
+            | JsonCommandRequest::get_all_txos_for_address { .. }
+            | JsonCommandRequest::get_balance_for_account { .. }
+            | JsonCommandRequest::get_balance_for_address { .. }
+            | JsonCommandRequest::get_block { .. }
+            | JsonCommandRequest::get_confirmations { .. }
+            | JsonCommandRequest::get_gift_code { .. }
+            | JsonCommandRequest::get_mc_protocol_transaction { .. }
+            | JsonCommandRequest::get_mc_protocol_txo { .. }
+            | JsonCommandRequest::get_network_status
+            | JsonCommandRequest::get_transaction_log { .. }
+            | JsonCommandRequest::get_transaction_logs_for_account { .. }
+            | JsonCommandRequest::get_txo { .. }
+            | JsonCommandRequest::get_txos_for_account { .. }
+            | JsonCommandRequest::get_wallet_status
+            | JsonCommandRequest::list_gift_codes { .. }
+            | JsonCommandRequest::validate_confirmation { .. }
+            | JsonCommandRequest::verify_address { .. }
+            | JsonCommandRequest::version => Capability::Read,
+            JsonCommandRequest::assign_address_for_account { .. }
+            | JsonCommandRequest::build_gift_code { .. }
+            | JsonCommandRequest::build_split_txo_transaction { .. }
+            | JsonCommandRequest::build_transaction { .. }
+            | JsonCommandRequest::create_payment_request { .. }
+            | JsonCommandRequest::create_receiver_receipts { .. } => Capability::Build,
+            JsonCommandRequest::build_and_submit_transaction { .. }
+            | JsonCommandRequest::claim_gift_code { .. }
+            | JsonCommandRequest::reclaim_gift_code { .. }
+            | JsonCommandRequest::submit_gift_code { .. }
+            | JsonCommandRequest::submit_transaction { .. } => Capability::Submit,
+            JsonCommandRequest::create_account { .. }
+            | JsonCommandRequest::export_account_secrets { .. }
+            | JsonCommandRequest::import_account { .. }
+            | JsonCommandRequest::import_account_from_legacy_root_entropy { .. }
+            | JsonCommandRequest::remove_account { .. }
+            | JsonCommandRequest::remove_gift_code { .. }
+            | JsonCommandRequest::update_account_name { .. } => Capability::Admin,
+        }
+    }
+
+    /// Whether the method reports the state of the wallet, and so is still
+    /// answered while the wallet is shutting down.
+    pub fn allowed_during_shutdown(&self) -> bool {
+        matches!(
+            self,
+            JsonCommandRequest::get_network_status
+                | JsonCommandRequest::get_wallet_status
+                | JsonCommandRequest::version
+        )
+    }
+}
+
 /// Requests to the Full Service Wallet Service.
 #[derive(Deserialize, Serialize, EnumIter, Debug)]
 #[serde(tag = "method", content = "params")]
@@ -200,6 +278,12 @@ pub enum JsonCommandRequest {
         fog_report_id: Option<String>,
         fog_authority_spki: Option<String>,
     },
+    list_gift_codes {
+        account_id: Option<String>,
+    },
+    reclaim_gift_code {
+        gift_code_b58: String,
+    },
     remove_account {
         account_id: String,
     },
@@ -164,6 +164,12 @@ pub enum JsonCommandResponse {
     import_account_from_legacy_root_entropy {
         account: Account,
     },
+    list_gift_codes {
+        gift_codes: Vec<GiftCode>,
+    },
+    reclaim_gift_code {
+        txo_id: String,
+    },
     remove_account {
         removed: bool,
     },
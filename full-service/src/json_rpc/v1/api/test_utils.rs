@@ -8,12 +8,12 @@ use crate::{
             request::JsonCommandRequest, response::JsonCommandResponse, wallet::wallet_api_inner,
         },
     },
-    service::WalletService,
+    service::{WalletService, WalletServiceConfig},
     test_utils::{
         get_resolver_factory, get_test_ledger, setup_peer_manager_and_network_state,
         WalletDbTestContext,
     },
-    wallet::{APIKeyState, ApiKeyGuard},
+    wallet::{api_catchers, APIKeyState, ApiKeyGuard, RequestId},
 };
 use mc_account_keys::PublicAddress;
 use mc_common::logger::{log, Logger};
@@ -50,7 +50,8 @@ pub struct TestWalletState {
 // TestWalletState, which handles Mock objects.
 #[post("/wallet", format = "json", data = "<command>")]
 fn test_wallet_api(
-    _guard: ApiKeyGuard,
+    guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<TestWalletState>,
     command: Json<JsonRPCRequest>,
 ) -> Result<Json<JsonRPCResponse<JsonCommandResponse>>, String> {
@@ -62,12 +63,19 @@ fn test_wallet_api(
         error: None,
         jsonrpc: "2.0".to_string(),
         id: command.0.id,
+        request_id: Some(request_id.0.clone()),
     };
 
-    match wallet_api_inner(
-        &state.service,
-        JsonCommandRequest::try_from(&req).map_err(|e| e)?,
-    ) {
+    let request = JsonCommandRequest::try_from(&req).map_err(|e| e)?;
+    if let Err(rpc_error) =
+        guard.check_method(&req.method, request.capability(), request.exposes_secrets())
+    {
+        response.error = Some(rpc_error);
+        return Ok(Json(response));
+    }
+    match state.service.with_request_id(&request_id.0, || {
+        wallet_api_inner(&state.service, request)
+    }) {
         Ok(command_response) => {
             response.result = Some(command_response);
         }
@@ -82,6 +90,7 @@ fn test_wallet_api(
 pub fn test_rocket(rocket_config: rocket::Config, state: TestWalletState) -> rocket::Rocket {
     rocket::custom(rocket_config)
         .mount("/", routes![test_wallet_api])
+        .register(api_catchers())
         .manage(state)
 }
 
@@ -109,7 +118,7 @@ pub fn create_test_setup(
         peer_manager,
         network_state.clone(),
         get_resolver_factory(&mut rng).unwrap(),
-        false,
+        WalletServiceConfig::default(),
         logger,
     );
 
@@ -246,7 +255,12 @@ pub fn dispatch_expect_error(
         request_body,
         response_body
     );
-    let response_json: serde_json::Value = serde_json::from_str(&response_body).unwrap();
+    let mut response_json: serde_json::Value = serde_json::from_str(&response_body).unwrap();
+    // The request ID is generated per request, so it is not part of the
+    // expected error.
+    if let Some(response_object) = response_json.as_object_mut() {
+        response_object.remove("request_id");
+    }
     let expected_json: serde_json::Value = serde_json::from_str(&expected_err).unwrap();
     assert_eq!(response_json, expected_json);
 }
@@ -1,15 +1,17 @@
 use crate::{
     db::{
         account::AccountID,
-        transaction_log::TransactionID,
+        transaction_log::{TransactionID, TransactionLogFilter},
         txo::{TxoID, TxoStatus},
     },
     json_rpc::{
         self,
         json_rpc_request::JsonRPCRequest,
         json_rpc_response::{
-            format_error, format_invalid_request_error, JsonRPCError, JsonRPCResponse,
+            format_error, format_invalid_request_error, format_shutting_down_error, JsonRPCError,
+            JsonRPCResponse,
         },
+        scoped_api_key::AccountScope,
         v1::{
             api::{request::JsonCommandRequest, response::JsonCommandResponse},
             models::{
@@ -29,7 +31,7 @@ use crate::{
             },
         },
         v2::models::amount::Amount,
-        wallet::{ApiKeyGuard, WalletState},
+        wallet::{ApiKeyGuard, RequestId, WalletState},
     },
     service::{
         self,
@@ -51,7 +53,7 @@ use crate::{
         PrintableWrapperType,
     },
 };
-use mc_common::logger::global_log;
+use mc_common::logger::log;
 use mc_connection::{BlockchainConnection, UserTxConnection};
 use mc_fog_report_validation::FogPubkeyResolver;
 use mc_mobilecoind_json::data_types::{JsonTx, JsonTxOut};
@@ -62,7 +64,8 @@ use serde_json::Map;
 use std::{collections::HashMap, convert::TryFrom, iter::FromIterator};
 
 pub fn generic_wallet_api<T, FPR>(
-    _api_key_guard: ApiKeyGuard,
+    api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<T, FPR>>,
     command: Json<JsonRPCRequest>,
 ) -> Result<Json<JsonRPCResponse<JsonCommandResponse>>, String>
@@ -78,6 +81,7 @@ where
         error: None,
         jsonrpc: "2.0".to_string(),
         id: command.0.id,
+        request_id: Some(request_id.0.clone()),
     };
 
     let request = match JsonCommandRequest::try_from(&req) {
@@ -88,7 +92,36 @@ where
         }
     };
 
-    match wallet_api_inner(&state.service, request) {
+    if let Err(error) =
+        api_key_guard.check_method(&req.method, request.capability(), request.exposes_secrets())
+    {
+        response.error = Some(error);
+        return Ok(Json(response));
+    }
+    // The v1 API is only served to keys which reach every account.
+    if let Err(error) = api_key_guard.check_accounts(&req.method, &AccountScope::Wallet) {
+        response.error = Some(error);
+        return Ok(Json(response));
+    }
+    // The v1 API has no confirmation challenges, so it serves no irreversible
+    // method: its remove_account only hides the account, and purging it is
+    // left to the confirmed v2 purge_account.
+
+    let _request_permit = if request.allowed_during_shutdown() {
+        None
+    } else {
+        match state.service.shutdown_coordinator.begin_request() {
+            Ok(permit) => Some(permit),
+            Err(e) => {
+                response.error = Some(format_shutting_down_error(e));
+                return Ok(Json(response));
+            }
+        }
+    };
+
+    match state.service.with_request_id(&request_id.0, || {
+        wallet_api_inner(&state.service, request)
+    }) {
         Ok(command_response) => {
             response.result = Some(command_response);
         }
@@ -100,6 +133,29 @@ where
     Ok(Json(response))
 }
 
+/// Convert a v1 TxProposal, identifying its change outputs with the keys of
+/// the sending account when it is known.
+fn service_tx_proposal<T, FPR>(
+    service: &WalletService<T, FPR>,
+    tx_proposal: &TxProposal,
+    account_id: Option<&str>,
+) -> Result<service::models::tx_proposal::TxProposal, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let change_keys = match account_id {
+        Some(account_id) => Some(
+            service
+                .get_change_keys(&AccountID(account_id.to_string()))
+                .map_err(format_error)?,
+        ),
+        None => None,
+    };
+    service::models::tx_proposal::TxProposal::try_from_v1(tx_proposal, change_keys.as_ref())
+        .map_err(format_error)
+}
+
 /// The Wallet API inner method, which handles switching on the method enum.
 ///
 /// Note that this is structured this way so that the routes can be defined to
@@ -114,7 +170,7 @@ where
     T: BlockchainConnection + UserTxConnection + 'static,
     FPR: FogPubkeyResolver + Send + Sync + 'static,
 {
-    global_log::trace!("Running command {:?}", command);
+    log::trace!(service.request_logger(), "Running command {:?}", command);
 
     let response = match command {
         JsonCommandRequest::assign_address_for_account {
@@ -167,8 +223,11 @@ where
                     Some(Mob::ID.to_string()),
                     tombstone_block,
                     max_spendable_value,
+                    None,
+                    false,
                     comment,
                     TransactionMemo::RTH,
+                    None,
                 )
                 .map_err(format_error)?;
 
@@ -278,6 +337,9 @@ where
                     Some(Mob::ID.to_string()),
                     tombstone_block,
                     max_spendable_value,
+                    None,
+                    false,
+                    None,
                     TransactionMemo::RTH,
                 )
                 .map_err(format_error)?;
@@ -391,7 +453,7 @@ where
         JsonCommandRequest::create_receiver_receipts { tx_proposal } => {
             let receipts = service
                 .create_receiver_receipts(
-                    &service::models::tx_proposal::TxProposal::try_from(&tx_proposal)
+                    &service::models::tx_proposal::TxProposal::try_from_v1(&tx_proposal, None)
                         .map_err(format_error)?,
                 )
                 .map_err(format_error)?;
@@ -435,13 +497,20 @@ where
                 .map_err(format_error)?;
             let balance_mob = balance_map.get(&Mob::ID).unwrap_or_default();
 
+            let gift_code_balance = service
+                .get_gift_code_balance_for_account(&account_id)
+                .map_err(format_error)?;
+
             let network_status = service.get_network_status().map_err(format_error)?;
 
-            let balance = Balance::new(
-                balance_mob,
-                account.next_block_index as u64,
-                &network_status,
-            );
+            let balance = Balance {
+                gift_code_pmob: gift_code_balance.to_string(),
+                ..Balance::new(
+                    balance_mob,
+                    account.next_block_index as u64,
+                    &network_status,
+                )
+            };
 
             let account = Account::new(account, next_subaddress_index).map_err(format_error)?;
             JsonCommandResponse::get_account_status { account, balance }
@@ -616,9 +685,16 @@ where
                 .map_err(format_error)?;
             let balance_mob = balance_map.get(&Mob::ID).unwrap_or_default();
 
+            let gift_code_balance = service
+                .get_gift_code_balance_for_account(&account_id)
+                .map_err(format_error)?;
+
             let network_status = service.get_network_status().map_err(format_error)?;
             JsonCommandResponse::get_balance_for_account {
-                balance: Balance::new(balance_mob, next_subaddress_index, &network_status),
+                balance: Balance {
+                    gift_code_pmob: gift_code_balance.to_string(),
+                    ..Balance::new(balance_mob, next_subaddress_index, &network_status)
+                },
             }
         }
         JsonCommandRequest::get_balance_for_address { address } => {
@@ -731,8 +807,11 @@ where
                     Some(account_id.clone()),
                     None,
                     None,
-                    min_block_index,
-                    max_block_index,
+                    &TransactionLogFilter {
+                        min_block_index,
+                        max_block_index,
+                        ..Default::default()
+                    },
                 )
                 .map_err(format_error)?;
 
@@ -838,7 +917,7 @@ where
             }
         }
         JsonCommandRequest::get_wallet_status => {
-            let wallet_status = service.get_wallet_status().map_err(format_error)?;
+            let wallet_status = service.get_wallet_status(None).map_err(format_error)?;
 
             let account_mapped: Vec<(String, serde_json::Value)> = wallet_status
                 .account_map
@@ -893,6 +972,7 @@ where
                     fog_report_url.unwrap_or_default(),
                     fog_report_id.unwrap_or_default(),
                     fog_authority_spki.unwrap_or_default(),
+                    false,
                 )
                 .map_err(format_error)?;
 
@@ -934,6 +1014,7 @@ where
                     fog_report_url.unwrap_or_default(),
                     fog_report_id.unwrap_or_default(),
                     fog_authority_spki.unwrap_or_default(),
+                    false,
                 )
                 .map_err(format_error)?;
 
@@ -948,6 +1029,27 @@ where
                 account: account_json,
             }
         }
+        JsonCommandRequest::list_gift_codes { account_id } => {
+            JsonCommandResponse::list_gift_codes {
+                gift_codes: service
+                    .list_gift_codes_with_status(account_id.map(AccountID).as_ref())
+                    .map_err(format_error)?
+                    .iter()
+                    .map(|(gift_code, status)| GiftCode {
+                        status: Some(*status),
+                        ..GiftCode::from(gift_code)
+                    })
+                    .collect(),
+            }
+        }
+        JsonCommandRequest::reclaim_gift_code { gift_code_b58 } => {
+            let tx = service
+                .reclaim_gift_code(&EncodedGiftCode(gift_code_b58))
+                .map_err(format_error)?;
+            JsonCommandResponse::reclaim_gift_code {
+                txo_id: TxoID::from(&tx.prefix.outputs[0]).to_string(),
+            }
+        }
         JsonCommandRequest::remove_account { account_id } => JsonCommandResponse::remove_account {
             removed: service
                 .remove_account(&AccountID(account_id))
@@ -965,12 +1067,13 @@ where
             gift_code_b58,
             tx_proposal,
         } => {
+            let tx_proposal =
+                service_tx_proposal(service, &tx_proposal, Some(from_account_id.as_str()))?;
             let gift_code = service
                 .submit_gift_code(
                     &AccountID(from_account_id),
                     &EncodedGiftCode(gift_code_b58),
-                    &service::models::tx_proposal::TxProposal::try_from(&tx_proposal)
-                        .map_err(format_error)?,
+                    &tx_proposal,
                 )
                 .map_err(format_error)?;
             JsonCommandResponse::submit_gift_code {
@@ -982,15 +1085,11 @@ where
             comment,
             account_id,
         } => {
+            let tx_proposal = service_tx_proposal(service, &tx_proposal, account_id.as_deref())?;
             let result = service
-                .submit_transaction(
-                    &service::models::tx_proposal::TxProposal::try_from(&tx_proposal)
-                        .map_err(format_error)?,
-                    comment,
-                    account_id,
-                )
+                .submit_transaction(&tx_proposal, comment, account_id, None, false)
                 .map_err(format_error)?
-                .map(|(tx_log, associated_txos, _value_map)| {
+                .map(|(tx_log, associated_txos, _value_map, _outcome)| {
                     TransactionLog::new(&tx_log, &associated_txos)
                 });
             JsonCommandResponse::submit_transaction {
@@ -4,7 +4,13 @@
 
 #[cfg(test)]
 mod e2e_account {
-    use crate::json_rpc::v1::api::test_utils::{dispatch, setup};
+    use crate::{
+        db::{
+            account::{AccountID, AccountModel},
+            models::Account,
+        },
+        json_rpc::v1::api::test_utils::{dispatch, setup},
+    };
 
     use mc_common::logger::{test_with_logger, Logger};
 
@@ -13,7 +19,7 @@ mod e2e_account {
     #[test_with_logger]
     fn test_e2e_account_crud(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
-        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+        let (client, _ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
 
         // Create Account
         let body = json!({
@@ -125,6 +131,15 @@ mod e2e_account {
         let result = res.get("result").unwrap();
         let accounts = result.get("account_ids").unwrap().as_array().unwrap();
         assert_eq!(accounts.len(), 0);
+
+        // The removed account is only hidden, so that it can still be restored.
+        let wallet_db = db_ctx.get_db_instance(logger.clone());
+        let removed = Account::get_including_removed(
+            &AccountID(account_id.as_str().unwrap().to_string()),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+        assert!(removed.removed_at.is_some());
     }
 
     #[test_with_logger]
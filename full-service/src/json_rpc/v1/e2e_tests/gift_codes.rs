@@ -131,6 +131,35 @@ mod e2e_transaction {
         let memo = res["result"]["gift_code_memo"].as_str().unwrap();
         assert_eq!(memo, "Happy Birthday!");
 
+        // The gift code is listed for Alice, and its value is broken out of her
+        // balance
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "list_gift_codes",
+            "params": {
+                "account_id": alice_account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let gift_codes = res["result"]["gift_codes"].as_array().unwrap();
+        assert_eq!(gift_codes.len(), 1);
+        assert_eq!(gift_codes[0]["gift_code_b58"], gift_code_b58);
+        assert_eq!(gift_codes[0]["account_id"], alice_account_id);
+        assert_eq!(gift_codes[0]["status"], "GiftCodeAvailable");
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_balance_for_account",
+            "params": {
+                "account_id": alice_account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let balance = res["result"]["balance"].clone();
+        assert_eq!(balance["gift_code_pmob"], "42000000000000");
+
         // Add Bob's account to our wallet
         let body = json!({
             "jsonrpc": "2.0",
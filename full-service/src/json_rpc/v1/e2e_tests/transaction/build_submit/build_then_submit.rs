@@ -68,7 +68,7 @@ mod e2e_transaction {
             "params": {
                 "account_id": account_id,
                 "recipient_public_address": b58_public_address,
-                "value_pmob": "42",
+                "value_pmob": Mob::MINIMUM_FEE.to_string(),
             }
         });
         // We will fail because we cannot afford the fee
@@ -82,8 +82,8 @@ mod e2e_transaction {
                     "code": -32603,
                     "message": "InternalError",
                     "data": json!({
-                        "server_error": format!("TransactionBuilder(WalletDb(InsufficientFundsUnderMaxSpendable(\"Max spendable value in wallet: 0, but target value: {}\")))", 42 + Mob::MINIMUM_FEE),
-                        "details": format!("Error building transaction: Wallet DB Error: Insufficient funds from Txos under max_spendable_value: Max spendable value in wallet: 0, but target value: {}", 42 + Mob::MINIMUM_FEE),
+                        "server_error": format!("TransactionBuilder(WalletDb(InsufficientFundsUnderMaxSpendable(InsufficientFundsAmounts {{ token_id: 0, requested: {}, available: 100, max_spendable: 0, shortfall: {} }})))", 2 * Mob::MINIMUM_FEE, 2 * Mob::MINIMUM_FEE - 100),
+                        "details": format!("Error building transaction: Wallet DB Error: Insufficient funds from Txos under max_spendable_value: Max spendable value in wallet: 0, but target value: {}", 2 * Mob::MINIMUM_FEE),
                     })
                 }),
                 "jsonrpc": "2.0",
@@ -153,7 +153,7 @@ mod e2e_transaction {
             3
         );
 
-        // Remove account 1.
+        // Remove account 1. Its txos are kept, so that it can be restored.
         let body = json!({
             "jsonrpc": "2.0",
             "id": 2,
@@ -170,7 +170,7 @@ mod e2e_transaction {
                 .select(count(txos::id))
                 .first::<i64>(&wallet_db.get_conn().unwrap())
                 .unwrap(),
-            1
+            3
         );
 
         // Send coins from account 2 to account 3.
@@ -219,7 +219,7 @@ mod e2e_transaction {
                 .select(count(txos::id))
                 .first::<i64>(&wallet_db.get_conn().unwrap())
                 .unwrap(),
-            3
+            5
         );
 
         // Check that account 3 received its coins.
@@ -57,6 +57,12 @@ pub struct Balance {
     /// view-key matched, but which can not be spent until their subaddress
     /// index is recovered.
     pub orphaned_pmob: String,
+
+    /// Pico MOB in gift codes funded by this account which are in the ledger
+    /// but have not been claimed. This value is not part of unspent_pmob, and
+    /// can be reclaimed with reclaim_gift_code. Only reported for the balance
+    /// of an account.
+    pub gift_code_pmob: String,
 }
 
 impl Balance {
@@ -77,6 +83,7 @@ impl Balance {
             spent_pmob: balance.spent.to_string(),
             secreted_pmob: balance.secreted.to_string(),
             orphaned_pmob: balance.orphaned.to_string(),
+            gift_code_pmob: "0".to_string(),
         }
     }
 }
@@ -2,7 +2,7 @@
 
 //! API definition for the GiftCode object.
 
-use crate::service::gift_code::DecodedGiftCode;
+use crate::service::gift_code::{DecodedGiftCode, GiftCodeStatus};
 
 use serde::{Deserialize, Serialize};
 
@@ -29,6 +29,15 @@ pub struct GiftCode {
 
     /// A memo associated with this gift code.
     pub memo: String,
+
+    /// The account in this wallet which funded the gift code, if any.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub account_id: Option<String>,
+
+    /// Whether the gift code is unfunded, funded but unclaimed, or claimed.
+    /// Only included when listing gift codes.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<GiftCodeStatus>,
 }
 
 impl From<&DecodedGiftCode> for GiftCode {
@@ -48,6 +57,8 @@ impl From<&DecodedGiftCode> for GiftCode {
                 .unwrap_or_default(),
             value_pmob: src.value.to_string(),
             memo: src.memo.clone(),
+            account_id: src.account_id.clone(),
+            status: None,
         }
     }
 }
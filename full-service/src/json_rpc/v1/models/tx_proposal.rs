@@ -9,9 +9,9 @@ use crate::{
 use mc_common::HashMap;
 use mc_mobilecoind_json::data_types::{JsonOutlay, JsonTx, JsonUnspentTxOut};
 
-use mc_transaction_core::tx::TxOutConfirmationNumber;
+use mc_transaction_core::{tokens::Mob, tx::TxOutConfirmationNumber, Token, TokenId};
 use serde_derive::{Deserialize, Serialize};
-use std::convert::TryFrom;
+use std::{convert::TryFrom, iter};
 
 #[derive(Deserialize, Serialize, Default, Debug)]
 pub struct TxProposal {
@@ -27,6 +27,21 @@ impl TryFrom<&TxProposalServiceModel> for TxProposal {
     type Error = String;
 
     fn try_from(src: &TxProposalServiceModel) -> Result<Self, String> {
+        // The v1 model has no token ids, so it can only express MOB.
+        let non_mob_token_id = src
+            .input_txos
+            .iter()
+            .map(|txo| txo.amount.token_id)
+            .chain(src.payload_txos.iter().map(|txo| txo.amount.token_id))
+            .chain(iter::once(TokenId::from(src.tx.prefix.fee_token_id)))
+            .find(|token_id| *token_id != Mob::ID);
+        if let Some(token_id) = non_mob_token_id {
+            return Err(format!(
+                "Cannot express token {} in a v1 tx proposal, which only supports MOB",
+                token_id
+            ));
+        }
+
         let mcd_tx_proposal = mc_mobilecoind::payments::TxProposal::try_from(src)?;
 
         let tx_proposal = TxProposal::try_from(&mcd_tx_proposal)?;
@@ -180,3 +195,112 @@ impl TryFrom<&TxProposal> for mc_mobilecoind_json::data_types::JsonTxProposal {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::account::AccountID,
+        json_rpc::v2::models::amount::Amount as AmountJSON,
+        service::{
+            account::AccountService,
+            transaction::{TransactionMemo, TransactionService},
+        },
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+        util::b58::b58_encode_public_address,
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_v1_tx_proposal_recovers_change(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_account_key: AccountKey = mc_util_serial::decode(&bob.account_key).unwrap();
+        let bob_public_address = bob_account_key.default_subaddress();
+
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &vec![(
+                    b58_encode_public_address(&bob_public_address).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.change_txos.len(), 1);
+
+        // The v1 model only records the outlays, as produced by older wallets.
+        let v1_tx_proposal = TxProposal::try_from(&tx_proposal).unwrap();
+
+        let change_keys = service.get_change_keys(&alice_account_id).unwrap();
+        let converted =
+            TxProposalServiceModel::try_from_v1(&v1_tx_proposal, Some(&change_keys)).unwrap();
+        assert_eq!(converted.payload_txos.len(), 1);
+        assert_eq!(converted.payload_txos[0].recipient_public_address, bob_public_address);
+        assert_eq!(converted.payload_txos[0].amount.value, 42 * MOB);
+
+        assert_eq!(converted.change_txos.len(), 1);
+        let change_txo = &converted.change_txos[0];
+        assert_eq!(change_txo.recipient_public_address, alice_account_key.change_subaddress());
+        assert_eq!(change_txo.amount.value, 100 * MOB - 42 * MOB - Mob::MINIMUM_FEE);
+        assert_eq!(change_txo.amount.token_id, Mob::ID);
+        assert_eq!(change_txo, &tx_proposal.change_txos[0]);
+
+        // Without the sender's keys, change cannot be identified.
+        let converted = TxProposalServiceModel::try_from_v1(&v1_tx_proposal, None).unwrap();
+        assert!(converted.change_txos.is_empty());
+
+        // Proposals in other tokens cannot be expressed in the v1 model.
+        let mut other_token_tx_proposal = tx_proposal.clone();
+        other_token_tx_proposal.payload_txos[0].amount.token_id = TokenId::from(1);
+        assert!(TxProposal::try_from(&other_token_tx_proposal).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 pub mod request;
 pub mod response;
+pub mod stream;
 pub mod wallet;
 
 #[cfg(any(test, feature = "test_utils"))]
@@ -2,11 +2,20 @@
 
 //! The JSON RPC 2.0 Requests to the Wallet API for Full Service.
 
-use crate::json_rpc::{
-    json_rpc_request::JsonRPCRequest,
-    v2::models::{
-        account_key::FogInfo, amount::Amount, receiver_receipt::ReceiverReceipt,
-        tx_proposal::TxProposal,
+use crate::{
+    json_rpc::{
+        capability::Capability,
+        json_rpc_request::JsonRPCRequest,
+        scoped_api_key::AccountScope,
+        v2::models::{
+            account_key::FogInfo, address::AddressLabel, amount::Amount, auto_rebuild::AutoRebuild,
+            proof_of_reserves::ProofOfReserves, public_address_keys::PublicAddressKeys,
+            receiver_receipt::ReceiverReceipt, tx_proposal::TxProposal,
+        },
+    },
+    service::{
+        account_backup::AccountBackup,
+        view_only_sync::{ViewOnlyAccountSyncRequest, ViewOnlyAccountSyncResponse},
     },
 };
 
@@ -33,11 +42,271 @@ impl TryFrom<&JsonRPCRequest> for JsonCommandRequest {
     }
 }
 
+impl JsonCommandRequest {
+    /// Whether the method exposes or destroys private key material, and so
+    /// requires the secrets API key when one is configured.
+    pub fn exposes_secrets(&self) -> bool {
+        matches!(
+            self,
+            JsonCommandRequest::export_account {
+                include_secrets: Some(true),
+                ..
+            } | JsonCommandRequest::export_account_secrets { .. }
+                | JsonCommandRequest::get_transaction_ring_details { .. }
+                | JsonCommandRequest::purge_account { .. }
+                | JsonCommandRequest::remove_account { .. }
+        )
+    }
+
+    /// Whether the method is irreversible, and so only runs with a
+    /// confirmation challenge issued by the matching prepare_ method.
+    pub fn requires_confirmation(&self) -> bool {
+        matches!(
+            self,
+            JsonCommandRequest::purge_account { .. } | JsonCommandRequest::remove_account { .. }
+        )
+    }
+
+    /// The capability a listener must grant to serve the method. Every method
+    /// is listed, so that a new method can not be added without choosing one.
+    pub fn capability(&self) -> Capability {
+        match self {
+            JsonCommandRequest::check_b58_type { .. }
+            | JsonCommandRequest::check_key_images { .. }
+            | JsonCommandRequest::check_receiver_receipt_status { .. }
+            | JsonCommandRequest::compute_address_hash { .. }
+            | JsonCommandRequest::decode_sender_memo { .. }
+            | JsonCommandRequest::decode_txo_amount { .. }
+            | JsonCommandRequest::export_address_labels { .. }
+            | JsonCommandRequest::export_proof_of_reserves { .. }
+            | JsonCommandRequest::get_account_status { .. }
+            | JsonCommandRequest::get_accounts { .. }
+            | JsonCommandRequest::get_address { .. }
+            | JsonCommandRequest::get_address_for_account { .. }
+            | JsonCommandRequest::get_address_status { .. }
+            | JsonCommandRequest::get_addresses { .. }
+            | JsonCommandRequest::get_block { .. }
+            | JsonCommandRequest::get_confirmations { .. }
+            | JsonCommandRequest::get_database_status
+            | JsonCommandRequest::get_fee_totals { .. }
+            | JsonCommandRequest::get_job_status { .. }
+            | JsonCommandRequest::get_ledger_db_status
+            | JsonCommandRequest::get_ledger_scan { .. }
+            | JsonCommandRequest::get_mc_protocol_transaction { .. }
+            | JsonCommandRequest::get_mc_protocol_txo { .. }
+            | JsonCommandRequest::get_network_status
+            | JsonCommandRequest::get_payment_status { .. }
+            | JsonCommandRequest::get_peer_status
+            | JsonCommandRequest::get_receiver_receipts { .. }
+            | JsonCommandRequest::get_reserved_addresses { .. }
+            | JsonCommandRequest::get_transaction_log { .. }
+            | JsonCommandRequest::get_transaction_log_by_tx_out_public_key { .. }
+            | JsonCommandRequest::get_transaction_logs { .. }
+            | JsonCommandRequest::get_transaction_reports { .. }
+            | JsonCommandRequest::get_txo { .. }
+            | JsonCommandRequest::get_txo_from_ledger { .. }
+            | JsonCommandRequest::get_txo_membership_proofs { .. }
+            | JsonCommandRequest::get_txos { .. }
+            | JsonCommandRequest::get_txos_modified_since { .. }
+            | JsonCommandRequest::get_wallet_status
+            | JsonCommandRequest::get_wallet_status_v2 { .. }
+            | JsonCommandRequest::identify_tx_out_owner { .. }
+            | JsonCommandRequest::list_jobs
+            | JsonCommandRequest::sample_mixins { .. }
+            | JsonCommandRequest::validate_confirmation { .. }
+            | JsonCommandRequest::verify_address { .. }
+            | JsonCommandRequest::verify_address_attestation { .. }
+            | JsonCommandRequest::verify_proof_of_reserves { .. }
+            | JsonCommandRequest::version => Capability::Read,
+            JsonCommandRequest::assign_address_at_index { .. }
+            | JsonCommandRequest::assign_address_for_account { .. }
+            | JsonCommandRequest::build_burn_transaction { .. }
+            | JsonCommandRequest::build_transaction { .. }
+            | JsonCommandRequest::build_unsigned_burn_transaction { .. }
+            | JsonCommandRequest::build_unsigned_transaction { .. }
+            | JsonCommandRequest::create_address_attestation { .. }
+            | JsonCommandRequest::create_payment_request { .. }
+            | JsonCommandRequest::create_receiver_receipts { .. }
+            | JsonCommandRequest::debug_build_report { .. }
+            | JsonCommandRequest::import_address_labels { .. }
+            | JsonCommandRequest::preview_transaction { .. } => Capability::Build,
+            JsonCommandRequest::build_and_submit_transaction { .. }
+            | JsonCommandRequest::submit_transaction { .. } => Capability::Submit,
+            JsonCommandRequest::cancel_job { .. }
+            | JsonCommandRequest::cancel_ledger_scan { .. }
+            | JsonCommandRequest::compact_ledger_db { .. }
+            | JsonCommandRequest::complete_view_only_account_sync_request { .. }
+            | JsonCommandRequest::create_account { .. }
+            | JsonCommandRequest::create_view_only_account_import_request { .. }
+            | JsonCommandRequest::create_view_only_account_sync_request { .. }
+            | JsonCommandRequest::exclude_peer { .. }
+            | JsonCommandRequest::export_account { .. }
+            | JsonCommandRequest::export_account_secrets { .. }
+            | JsonCommandRequest::flush_fog_pubkey_cache
+            | JsonCommandRequest::get_transaction_ring_details { .. }
+            | JsonCommandRequest::import_account { .. }
+            | JsonCommandRequest::import_account_backup { .. }
+            | JsonCommandRequest::import_account_from_legacy_root_entropy { .. }
+            | JsonCommandRequest::import_txo { .. }
+            | JsonCommandRequest::import_view_only_account { .. }
+            | JsonCommandRequest::import_view_only_account_sync_response { .. }
+            | JsonCommandRequest::migrate_account_to_mnemonic { .. }
+            | JsonCommandRequest::pin_peer { .. }
+            | JsonCommandRequest::prepare_purge_account { .. }
+            | JsonCommandRequest::prepare_remove_account { .. }
+            | JsonCommandRequest::purge_account { .. }
+            | JsonCommandRequest::reconstruct_transaction_history { .. }
+            | JsonCommandRequest::remove_account { .. }
+            | JsonCommandRequest::rescan_account { .. }
+            | JsonCommandRequest::restore_removed_account { .. }
+            | JsonCommandRequest::resync_account { .. }
+            | JsonCommandRequest::retry_transaction_reports
+            | JsonCommandRequest::scan_ledger_with_view_key { .. }
+            | JsonCommandRequest::sync_view_only_account { .. }
+            | JsonCommandRequest::test_webhook
+            | JsonCommandRequest::update_account_min_confirmations { .. }
+            | JsonCommandRequest::update_account_name { .. }
+            | JsonCommandRequest::update_account_split_change_into { .. }
+            | JsonCommandRequest::update_account_subaddress_gap_limit { .. }
+            | JsonCommandRequest::update_account_transaction_defaults { .. }
+            | JsonCommandRequest::verify_ledger_integrity { .. } => Capability::Admin,
+        }
+    }
+
+    /// The accounts whose data the method reaches, which an API key scoped to
+    /// some accounts must permit. Every method is listed, so that a new method
+    /// can not be added without choosing a scope.
+    pub fn account_scope(&self) -> AccountScope {
+        match self {
+            JsonCommandRequest::check_b58_type { .. }
+            | JsonCommandRequest::check_key_images { .. }
+            | JsonCommandRequest::compute_address_hash { .. }
+            | JsonCommandRequest::decode_sender_memo { .. }
+            | JsonCommandRequest::decode_txo_amount { .. }
+            | JsonCommandRequest::get_block { .. }
+            | JsonCommandRequest::get_network_status
+            | JsonCommandRequest::get_peer_status
+            | JsonCommandRequest::get_txo_from_ledger { .. }
+            | JsonCommandRequest::get_txo_membership_proofs { .. }
+            | JsonCommandRequest::sample_mixins { .. }
+            | JsonCommandRequest::verify_address_attestation { .. }
+            | JsonCommandRequest::verify_proof_of_reserves { .. }
+            | JsonCommandRequest::version => AccountScope::None,
+            JsonCommandRequest::assign_address_at_index { account_id, .. }
+            | JsonCommandRequest::assign_address_for_account { account_id, .. }
+            | JsonCommandRequest::build_and_submit_transaction { account_id, .. }
+            | JsonCommandRequest::build_burn_transaction { account_id, .. }
+            | JsonCommandRequest::build_transaction { account_id, .. }
+            | JsonCommandRequest::build_unsigned_burn_transaction { account_id, .. }
+            | JsonCommandRequest::build_unsigned_transaction { account_id, .. }
+            | JsonCommandRequest::create_payment_request { account_id, .. }
+            | JsonCommandRequest::create_view_only_account_import_request { account_id }
+            | JsonCommandRequest::create_view_only_account_sync_request { account_id }
+            | JsonCommandRequest::debug_build_report { account_id, .. }
+            | JsonCommandRequest::export_account { account_id, .. }
+            | JsonCommandRequest::export_account_secrets { account_id }
+            | JsonCommandRequest::export_address_labels { account_id }
+            | JsonCommandRequest::export_proof_of_reserves { account_id, .. }
+            | JsonCommandRequest::get_account_status { account_id }
+            | JsonCommandRequest::get_address_for_account { account_id, .. }
+            | JsonCommandRequest::get_fee_totals { account_id, .. }
+            | JsonCommandRequest::get_reserved_addresses { account_id }
+            | JsonCommandRequest::get_txos_modified_since { account_id, .. }
+            | JsonCommandRequest::import_address_labels { account_id, .. }
+            | JsonCommandRequest::import_txo { account_id, .. }
+            | JsonCommandRequest::migrate_account_to_mnemonic { account_id }
+            | JsonCommandRequest::prepare_purge_account { account_id, .. }
+            | JsonCommandRequest::prepare_remove_account { account_id }
+            | JsonCommandRequest::preview_transaction { account_id, .. }
+            | JsonCommandRequest::purge_account { account_id, .. }
+            | JsonCommandRequest::reconstruct_transaction_history { account_id }
+            | JsonCommandRequest::remove_account { account_id }
+            | JsonCommandRequest::rescan_account { account_id, .. }
+            | JsonCommandRequest::restore_removed_account { account_id }
+            | JsonCommandRequest::resync_account { account_id }
+            | JsonCommandRequest::sync_view_only_account { account_id, .. }
+            | JsonCommandRequest::update_account_min_confirmations { account_id, .. }
+            | JsonCommandRequest::update_account_name { account_id, .. }
+            | JsonCommandRequest::update_account_split_change_into { account_id, .. }
+            | JsonCommandRequest::update_account_subaddress_gap_limit { account_id, .. }
+            | JsonCommandRequest::update_account_transaction_defaults { account_id, .. }
+            | JsonCommandRequest::validate_confirmation { account_id, .. } => {
+                AccountScope::Accounts(vec![account_id.as_str()])
+            }
+            JsonCommandRequest::get_addresses { account_id, .. }
+            | JsonCommandRequest::get_transaction_logs { account_id, .. }
+            | JsonCommandRequest::get_transaction_reports { account_id }
+            | JsonCommandRequest::get_txos { account_id, .. }
+            | JsonCommandRequest::submit_transaction { account_id, .. } => match account_id {
+                Some(account_id) => AccountScope::Accounts(vec![account_id.as_str()]),
+                None => AccountScope::Wallet,
+            },
+            JsonCommandRequest::get_accounts { .. }
+            | JsonCommandRequest::get_wallet_status
+            | JsonCommandRequest::get_wallet_status_v2 { .. } => AccountScope::Filtered,
+            JsonCommandRequest::cancel_job { .. }
+            | JsonCommandRequest::cancel_ledger_scan { .. }
+            | JsonCommandRequest::check_receiver_receipt_status { .. }
+            | JsonCommandRequest::compact_ledger_db { .. }
+            | JsonCommandRequest::complete_view_only_account_sync_request { .. }
+            | JsonCommandRequest::create_account { .. }
+            | JsonCommandRequest::create_address_attestation { .. }
+            | JsonCommandRequest::create_receiver_receipts { .. }
+            | JsonCommandRequest::exclude_peer { .. }
+            | JsonCommandRequest::flush_fog_pubkey_cache
+            | JsonCommandRequest::get_address { .. }
+            | JsonCommandRequest::get_address_status { .. }
+            | JsonCommandRequest::get_confirmations { .. }
+            | JsonCommandRequest::get_database_status
+            | JsonCommandRequest::get_job_status { .. }
+            | JsonCommandRequest::get_ledger_db_status
+            | JsonCommandRequest::get_ledger_scan { .. }
+            | JsonCommandRequest::get_mc_protocol_transaction { .. }
+            | JsonCommandRequest::get_mc_protocol_txo { .. }
+            | JsonCommandRequest::get_payment_status { .. }
+            | JsonCommandRequest::get_receiver_receipts { .. }
+            | JsonCommandRequest::get_transaction_log { .. }
+            | JsonCommandRequest::get_transaction_log_by_tx_out_public_key { .. }
+            | JsonCommandRequest::get_transaction_ring_details { .. }
+            | JsonCommandRequest::get_txo { .. }
+            | JsonCommandRequest::identify_tx_out_owner { .. }
+            | JsonCommandRequest::import_account { .. }
+            | JsonCommandRequest::import_account_backup { .. }
+            | JsonCommandRequest::import_account_from_legacy_root_entropy { .. }
+            | JsonCommandRequest::import_view_only_account { .. }
+            | JsonCommandRequest::import_view_only_account_sync_response { .. }
+            | JsonCommandRequest::list_jobs
+            | JsonCommandRequest::pin_peer { .. }
+            | JsonCommandRequest::retry_transaction_reports
+            | JsonCommandRequest::scan_ledger_with_view_key { .. }
+            | JsonCommandRequest::test_webhook
+            | JsonCommandRequest::verify_address { .. }
+            | JsonCommandRequest::verify_ledger_integrity { .. } => AccountScope::Wallet,
+        }
+    }
+
+    /// Whether the method reports the state of the wallet, and so is still
+    /// answered while the wallet is shutting down.
+    pub fn allowed_during_shutdown(&self) -> bool {
+        matches!(
+            self,
+            JsonCommandRequest::get_network_status
+                | JsonCommandRequest::get_wallet_status
+                | JsonCommandRequest::version
+        )
+    }
+}
+
 /// Requests to the Full Service Wallet Service.
 #[derive(Deserialize, Serialize, EnumIter, Debug)]
 #[serde(tag = "method", content = "params")]
 #[allow(non_camel_case_types)]
 pub enum JsonCommandRequest {
+    assign_address_at_index {
+        account_id: String,
+        subaddress_index: String,
+        metadata: Option<String>,
+    },
     assign_address_for_account {
         account_id: String,
         metadata: Option<String>,
@@ -46,13 +315,22 @@ pub enum JsonCommandRequest {
         account_id: String,
         addresses_and_amounts: Option<Vec<(String, Amount)>>,
         recipient_public_address: Option<String>,
+        recipient_public_keys: Option<PublicAddressKeys>,
         amount: Option<Amount>,
         input_txo_ids: Option<Vec<String>>,
         fee_value: Option<String>,
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
         comment: Option<String>,
+        payment_request_id: Option<String>,
+        idempotency_key: Option<String>,
+        include_receiver_receipts: Option<bool>,
+        allow_cross_network: Option<bool>,
+        auto_rebuild: Option<AutoRebuild>,
+        selection_token: Option<String>,
     },
     build_burn_transaction {
         account_id: String,
@@ -63,17 +341,28 @@ pub enum JsonCommandRequest {
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
+        comment: Option<String>,
     },
     build_transaction {
         account_id: String,
         addresses_and_amounts: Option<Vec<(String, Amount)>>,
         recipient_public_address: Option<String>,
+        recipient_public_keys: Option<PublicAddressKeys>,
         amount: Option<Amount>,
         input_txo_ids: Option<Vec<String>>,
         fee_value: Option<String>,
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
+        comment: Option<String>,
+        payment_request_id: Option<String>,
+        include_receiver_receipts: Option<bool>,
+        allow_cross_network: Option<bool>,
+        selection_token: Option<String>,
     },
     build_unsigned_burn_transaction {
         account_id: String,
@@ -84,29 +373,58 @@ pub enum JsonCommandRequest {
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
     },
     build_unsigned_transaction {
         account_id: String,
         addresses_and_amounts: Option<Vec<(String, Amount)>>,
         recipient_public_address: Option<String>,
+        recipient_public_keys: Option<PublicAddressKeys>,
         amount: Option<Amount>,
         input_txo_ids: Option<Vec<String>>,
         fee_value: Option<String>,
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
+        allow_cross_network: Option<bool>,
+        selection_token: Option<String>,
+    },
+    cancel_job {
+        job_id: String,
+    },
+    cancel_ledger_scan {
+        ledger_scan_id: String,
     },
     check_b58_type {
         b58_code: String,
     },
+    check_key_images {
+        key_image_hexes: Vec<String>,
+    },
     check_receiver_receipt_status {
         address: String,
         receiver_receipt: ReceiverReceipt,
     },
+    compact_ledger_db {
+        target_path: String,
+    },
+    complete_view_only_account_sync_request {
+        sync_request: ViewOnlyAccountSyncRequest,
+    },
+    compute_address_hash {
+        public_address_b58: String,
+    },
     create_account {
         name: Option<String>,
         fog_info: Option<FogInfo>,
     },
+    create_address_attestation {
+        public_address_b58: String,
+        nonce: String,
+    },
     create_payment_request {
         account_id: String,
         subaddress_index: Option<i64>,
@@ -122,9 +440,47 @@ pub enum JsonCommandRequest {
     create_view_only_account_sync_request {
         account_id: String,
     },
+    debug_build_report {
+        account_id: String,
+        addresses_and_amounts: Option<Vec<(String, Amount)>>,
+        recipient_public_address: Option<String>,
+        amount: Option<Amount>,
+        input_txo_ids: Option<Vec<String>>,
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+    },
+    decode_sender_memo {
+        memo_payload: String,
+        tx_out_public_key: String,
+        view_private_key: String,
+        sender_public_address_b58: String,
+    },
+    decode_txo_amount {
+        tx_out_public_key: String,
+        view_private_key: String,
+    },
+    exclude_peer {
+        responder_id: String,
+        excluded: bool,
+    },
+    export_account {
+        account_id: String,
+        include_secrets: Option<bool>,
+        passphrase: Option<String>,
+    },
     export_account_secrets {
         account_id: String,
     },
+    export_address_labels {
+        account_id: String,
+    },
+    export_proof_of_reserves {
+        account_id: String,
+        block_index: Option<String>,
+    },
+    flush_fog_pubkey_cache,
     get_account_status {
         account_id: String,
     },
@@ -153,6 +509,21 @@ pub enum JsonCommandRequest {
     get_confirmations {
         transaction_log_id: String,
     },
+    get_database_status,
+    get_fee_totals {
+        account_id: String,
+        token_id: Option<String>,
+        min_block_index: Option<String>,
+        max_block_index: Option<String>,
+        group_by_month: Option<bool>,
+    },
+    get_job_status {
+        job_id: String,
+    },
+    get_ledger_db_status,
+    get_ledger_scan {
+        ledger_scan_id: String,
+    },
     get_mc_protocol_transaction {
         transaction_log_id: String,
     },
@@ -160,19 +531,49 @@ pub enum JsonCommandRequest {
         txo_id: String,
     },
     get_network_status,
+    get_payment_status {
+        transaction_log_id: String,
+    },
+    get_peer_status,
+    get_receiver_receipts {
+        transaction_log_id: String,
+    },
+    get_reserved_addresses {
+        account_id: String,
+    },
     get_transaction_log {
         transaction_log_id: String,
     },
+    get_transaction_log_by_tx_out_public_key {
+        public_key: String,
+    },
     get_transaction_logs {
         account_id: Option<String>,
         min_block_index: Option<String>,
         max_block_index: Option<String>,
+        recipient_public_address_b58: Option<String>,
+        token_id: Option<String>,
+        min_value: Option<String>,
+        max_value: Option<String>,
+        status: Option<String>,
+        order_by: Option<String>,
+        order: Option<String>,
         offset: Option<u64>,
         limit: Option<u64>,
+        include_timestamps: Option<bool>,
+    },
+    get_transaction_reports {
+        account_id: Option<String>,
+    },
+    get_transaction_ring_details {
+        transaction_log_id: String,
     },
     get_txo {
         txo_id: String,
     },
+    get_txo_from_ledger {
+        tx_out_public_key: String,
+    },
     get_txos {
         account_id: Option<String>,
         address: Option<String>,
@@ -182,11 +583,26 @@ pub enum JsonCommandRequest {
         max_received_block_index: Option<u64>,
         offset: Option<u64>,
         limit: Option<u64>,
+        include_timestamps: Option<bool>,
+        global_index: Option<String>,
+    },
+    get_txos_modified_since {
+        account_id: String,
+        cursor: Option<String>,
+        limit: Option<String>,
     },
     get_txo_membership_proofs {
         outputs: Vec<JsonTxOut>,
     },
     get_wallet_status,
+    get_wallet_status_v2 {
+        include_accounts: Option<bool>,
+        behind_threshold: Option<String>,
+    },
+    identify_tx_out_owner {
+        tx_out_proto_hex: Option<String>,
+        tx_out_public_key_hex: Option<String>,
+    },
     import_account {
         mnemonic: String,
         key_derivation_version: String,
@@ -194,6 +610,11 @@ pub enum JsonCommandRequest {
         first_block_index: Option<String>,
         next_subaddress_index: Option<String>,
         fog_info: Option<FogInfo>,
+        force: Option<bool>,
+    },
+    import_account_backup {
+        backup: AccountBackup,
+        passphrase: Option<String>,
     },
     import_account_from_legacy_root_entropy {
         entropy: String,
@@ -201,6 +622,16 @@ pub enum JsonCommandRequest {
         first_block_index: Option<String>,
         next_subaddress_index: Option<String>,
         fog_info: Option<FogInfo>,
+        force: Option<bool>,
+    },
+    import_address_labels {
+        account_id: String,
+        address_labels: Vec<AddressLabel>,
+        create_missing: Option<bool>,
+    },
+    import_txo {
+        account_id: String,
+        tx_out_public_key_hex: String,
     },
     import_view_only_account {
         view_private_key: String,
@@ -208,28 +639,107 @@ pub enum JsonCommandRequest {
         name: Option<String>,
         first_block_index: Option<String>,
         next_subaddress_index: Option<String>,
+        force: Option<bool>,
+    },
+    import_view_only_account_sync_response {
+        sync_response: ViewOnlyAccountSyncResponse,
+    },
+    list_jobs,
+    migrate_account_to_mnemonic {
+        account_id: String,
+    },
+    pin_peer {
+        responder_id: Option<String>,
+    },
+    prepare_purge_account {
+        account_id: String,
+        confirm_account_name: String,
+    },
+    prepare_remove_account {
+        account_id: String,
+    },
+    preview_transaction {
+        account_id: String,
+        addresses_and_amounts: Option<Vec<(String, Amount)>>,
+        recipient_public_address: Option<String>,
+        recipient_public_keys: Option<PublicAddressKeys>,
+        amount: Option<Amount>,
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<String>,
+        change_to_source: Option<bool>,
+    },
+    purge_account {
+        account_id: String,
+        confirm_account_name: String,
+    },
+    reconstruct_transaction_history {
+        account_id: String,
     },
     remove_account {
         account_id: String,
     },
+    rescan_account {
+        account_id: String,
+        from_block: Option<String>,
+    },
+    restore_removed_account {
+        account_id: String,
+    },
+    resync_account {
+        account_id: String,
+    },
+    retry_transaction_reports,
     sample_mixins {
         num_mixins: u64,
         excluded_outputs: Vec<JsonTxOut>,
     },
+    scan_ledger_with_view_key {
+        view_private_key: String,
+        spend_public_key: String,
+        first_block_index: String,
+        last_block_index: String,
+        first_subaddress_index: Option<String>,
+        num_subaddresses: Option<String>,
+    },
     submit_transaction {
         tx_proposal: TxProposal,
         comment: Option<String>,
         account_id: Option<String>,
+        idempotency_key: Option<String>,
+        dry_run: Option<bool>,
+        force_resubmit: Option<bool>,
     },
     sync_view_only_account {
         account_id: String,
         completed_txos: Vec<(String, String)>,
         next_subaddress_index: String,
     },
+    test_webhook,
+    update_account_min_confirmations {
+        account_id: String,
+        min_confirmations: Option<String>,
+    },
     update_account_name {
         account_id: String,
         name: String,
     },
+    update_account_split_change_into {
+        account_id: String,
+        split_change_into: String,
+    },
+    update_account_subaddress_gap_limit {
+        account_id: String,
+        subaddress_gap_limit: String,
+    },
+    update_account_transaction_defaults {
+        account_id: String,
+        default_fee_value: Option<String>,
+        default_fee_token_id: Option<String>,
+        default_tombstone_offset: Option<String>,
+    },
     validate_confirmation {
         account_id: String,
         txo_id: String,
@@ -238,5 +748,15 @@ pub enum JsonCommandRequest {
     verify_address {
         address: String,
     },
+    verify_address_attestation {
+        attestation: String,
+    },
+    verify_ledger_integrity {
+        sample_size: String,
+    },
+    verify_proof_of_reserves {
+        proof_of_reserves: ProofOfReserves,
+        view_private_key: Option<String>,
+    },
     version,
 }
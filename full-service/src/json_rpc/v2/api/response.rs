@@ -11,24 +11,48 @@ use crate::{
         v2::models::{
             account::{Account, AccountMap},
             account_secrets::AccountSecrets,
-            address::{Address, AddressMap},
+            address::{
+                Address, AddressAttestation, AddressLabel, AddressMap, AddressNetworkCheck,
+                ReservedAddress,
+            },
+            amount::Amount,
             balance::BalanceMap,
             block::{Block, BlockContents},
+            build_report::BuildReport,
+            confirmation_challenge::ConfirmationChallenge,
             confirmation_number::Confirmation,
+            database_status::DatabaseStatus,
+            fee_total::FeeTotal,
+            job::Job,
+            key_image_status::KeyImageStatus,
+            ledger_db_status::LedgerDbStatus,
+            ledger_scan::LedgerScan,
             network_status::NetworkStatus,
+            payment_status::PaymentStatus,
+            peer_status::PeerStatus,
+            proof_of_reserves::{ProofOfReserves, ReservesVerification},
             receiver_receipt::ReceiverReceipt,
-            transaction_log::{TransactionLog, TransactionLogMap},
+            sender_memo::SenderMemo,
+            sync_stats::AccountSyncStats,
+            transaction_log::{OutputTxo, TransactionLog, TransactionLogMap},
+            transaction_preview::TransactionPreview,
+            transaction_report::TransactionReport,
+            transaction_ring_details::TransactionRingDetails,
             tx_proposal::TxProposal,
             txo::{Txo, TxoMap},
-            wallet_status::WalletStatus,
+            wallet_status::{WalletRollup, WalletStatus},
         },
     },
-    service::receipt::ReceiptTransactionStatus,
+    service::{
+        account_backup::AccountBackup,
+        receipt::ReceiptTransactionStatus,
+        view_only_sync::{ViewOnlyAccountSyncRequest, ViewOnlyAccountSyncResponse},
+    },
     util::b58::PrintableWrapperType,
 };
 use mc_mobilecoind_json::data_types::{JsonTx, JsonTxOut, JsonTxOutMembershipProof};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{fog_resolver::FullServiceFogResolver, unsigned_tx::UnsignedTx};
 /// Responses from the Full Service Wallet.
@@ -37,12 +61,16 @@ use crate::{fog_resolver::FullServiceFogResolver, unsigned_tx::UnsignedTx};
 #[allow(non_camel_case_types)]
 #[allow(clippy::large_enum_variant)]
 pub enum JsonCommandResponse {
+    assign_address_at_index {
+        address: Address,
+    },
     assign_address_for_account {
         address: Address,
     },
     build_and_submit_transaction {
         transaction_log: TransactionLog,
         tx_proposal: TxProposal,
+        receiver_receipts: Option<Vec<ReceiverReceipt>>,
     },
     build_burn_transaction {
         tx_proposal: TxProposal,
@@ -51,6 +79,7 @@ pub enum JsonCommandResponse {
     build_transaction {
         tx_proposal: TxProposal,
         transaction_log_id: String,
+        receiver_receipts: Option<Vec<ReceiverReceipt>>,
     },
     build_unsigned_burn_transaction {
         account_id: String,
@@ -62,17 +91,38 @@ pub enum JsonCommandResponse {
         unsigned_tx: UnsignedTx,
         fog_resolver: FullServiceFogResolver,
     },
+    cancel_job {
+        job: Job,
+    },
+    cancel_ledger_scan {
+        ledger_scan: LedgerScan,
+    },
     check_b58_type {
         b58_type: PrintableWrapperType,
         data: HashMap<String, String>,
     },
+    check_key_images {
+        key_image_statuses: Vec<KeyImageStatus>,
+    },
     check_receiver_receipt_status {
         receipt_transaction_status: ReceiptTransactionStatus,
         txo: Option<Txo>,
     },
+    compact_ledger_db {
+        job: Job,
+    },
+    complete_view_only_account_sync_request {
+        sync_response: ViewOnlyAccountSyncResponse,
+    },
+    compute_address_hash {
+        address_hash: String,
+    },
     create_account {
         account: Account,
     },
+    create_address_attestation {
+        attestation: String,
+    },
     create_payment_request {
         payment_request_b58: String,
     },
@@ -85,15 +135,45 @@ pub enum JsonCommandResponse {
     create_view_only_account_sync_request {
         account_id: String,
         incomplete_txos_encoded: Vec<String>,
+        sync_request: ViewOnlyAccountSyncRequest,
+    },
+    debug_build_report {
+        report: BuildReport,
+    },
+    decode_sender_memo {
+        sender_memo: SenderMemo,
+    },
+    decode_txo_amount {
+        amount: Amount,
+        block_index: String,
+        global_index: String,
+    },
+    exclude_peer {
+        peer_status: PeerStatus,
+    },
+    export_account {
+        backup: AccountBackup,
     },
     export_account_secrets {
         account_secrets: AccountSecrets,
     },
+    export_address_labels {
+        address_labels: Vec<AddressLabel>,
+    },
+    export_proof_of_reserves {
+        proof_of_reserves: ProofOfReserves,
+    },
+    flush_fog_pubkey_cache {
+        num_entries_flushed: String,
+        hits: String,
+        misses: String,
+    },
     get_account_status {
         account: Account,
         network_block_height: String,
         local_block_height: String,
         balance_per_token: BalanceMap,
+        sync_stats: Option<AccountSyncStats>,
     },
     get_accounts {
         account_ids: Vec<String>,
@@ -123,6 +203,21 @@ pub enum JsonCommandResponse {
     get_confirmations {
         confirmations: Vec<Confirmation>,
     },
+    get_database_status {
+        database_status: DatabaseStatus,
+    },
+    get_fee_totals {
+        fee_totals: Vec<FeeTotal>,
+    },
+    get_job_status {
+        job: Job,
+    },
+    get_ledger_db_status {
+        ledger_db_status: LedgerDbStatus,
+    },
+    get_ledger_scan {
+        ledger_scan: LedgerScan,
+    },
     get_mc_protocol_transaction {
         transaction: JsonTx,
     },
@@ -132,20 +227,55 @@ pub enum JsonCommandResponse {
     get_network_status {
         network_status: NetworkStatus,
     },
+    get_payment_status {
+        payment_status: PaymentStatus,
+    },
+    get_peer_status {
+        peer_status: PeerStatus,
+    },
+    get_receiver_receipts {
+        receiver_receipts: Vec<ReceiverReceipt>,
+    },
+    get_reserved_addresses {
+        reserved_addresses: Vec<ReservedAddress>,
+    },
     get_transaction_log {
         transaction_log: TransactionLog,
+        rebuild_chain: Vec<String>,
+    },
+    get_transaction_log_by_tx_out_public_key {
+        transaction_log: TransactionLog,
+        output_txo: OutputTxo,
+        is_change: bool,
     },
     get_transaction_logs {
         transaction_log_ids: Vec<String>,
         transaction_log_map: TransactionLogMap,
     },
+    get_transaction_reports {
+        transaction_reports: Vec<TransactionReport>,
+    },
+    get_transaction_ring_details {
+        transaction_ring_details: TransactionRingDetails,
+    },
     get_txo {
         txo: Txo,
     },
+    get_txo_from_ledger {
+        txo: JsonTxOut,
+        txo_proto: String,
+        block_index: String,
+        global_index: String,
+    },
     get_txos {
         txo_ids: Vec<String>,
         txo_map: TxoMap,
     },
+    get_txos_modified_since {
+        txo_ids: Vec<String>,
+        txo_map: TxoMap,
+        next_cursor: Option<String>,
+    },
     get_txo_membership_proofs {
         outputs: Vec<JsonTxOut>,
         membership_proofs: Vec<JsonTxOutMembershipProof>,
@@ -153,34 +283,131 @@ pub enum JsonCommandResponse {
     get_wallet_status {
         wallet_status: WalletStatus,
     },
+    get_wallet_status_v2 {
+        wallet_status: WalletRollup,
+    },
+    identify_tx_out_owner {
+        owned: bool,
+        account_id: Option<String>,
+        subaddress_index: Option<String>,
+        amount: Option<Amount>,
+        tracked: bool,
+        txo_id: Option<String>,
+    },
     import_account {
         account: Account,
     },
+    import_account_backup {
+        account: Account,
+    },
     import_account_from_legacy_root_entropy {
         account: Account,
     },
+    import_address_labels {
+        num_updated: String,
+        num_created: String,
+        unassigned_indices: Vec<String>,
+    },
+    import_txo {
+        txo: Txo,
+    },
     import_view_only_account {
         account: Account,
     },
+    import_view_only_account_sync_response {
+        num_txos_updated: String,
+        spent_txo_ids: Vec<String>,
+    },
+    list_jobs {
+        jobs: Vec<Job>,
+    },
+    migrate_account_to_mnemonic {
+        new_account: Account,
+        transaction_log_ids: Vec<String>,
+        fees: BTreeMap<String, String>,
+        job: Job,
+    },
+    pin_peer {
+        peer_status: PeerStatus,
+    },
+    prepare_purge_account {
+        confirmation: ConfirmationChallenge,
+    },
+    prepare_remove_account {
+        confirmation: ConfirmationChallenge,
+    },
+    preview_transaction {
+        transaction_preview: TransactionPreview,
+    },
+    purge_account {
+        purged: bool,
+    },
+    reconstruct_transaction_history {
+        job: Job,
+    },
     remove_account {
         removed: bool,
     },
+    rescan_account {
+        job: Job,
+    },
+    restore_removed_account {
+        account: Account,
+    },
+    resync_account {
+        job: Job,
+    },
+    retry_transaction_reports {
+        num_delivered: String,
+        num_failed: String,
+    },
     sample_mixins {
         mixins: Vec<JsonTxOut>,
         membership_proofs: Vec<JsonTxOutMembershipProof>,
     },
+    scan_ledger_with_view_key {
+        ledger_scan: LedgerScan,
+    },
     submit_transaction {
         transaction_log: Option<TransactionLog>,
+        dry_run_block_count: Option<String>,
+        submit_outcome: Option<String>,
     },
     sync_view_only_account,
+    test_webhook {
+        event_id: String,
+    },
+    update_account_min_confirmations {
+        account: Account,
+    },
     update_account_name {
         account: Account,
     },
+    update_account_split_change_into {
+        account: Account,
+    },
+    update_account_subaddress_gap_limit {
+        account: Account,
+    },
+    update_account_transaction_defaults {
+        account: Account,
+    },
     validate_confirmation {
         validated: bool,
     },
     verify_address {
         verified: bool,
+        network_check: Option<AddressNetworkCheck>,
+    },
+    verify_address_attestation {
+        address_attestation: AddressAttestation,
+    },
+    verify_ledger_integrity {
+        num_checked: String,
+        invalid_indices: Vec<String>,
+    },
+    verify_proof_of_reserves {
+        verification: ReservesVerification,
     },
     version {
         string: String,
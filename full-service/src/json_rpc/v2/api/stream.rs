@@ -0,0 +1,292 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Streaming listings for the Full Service Wallet API.
+//!
+//! Large listings are written as newline-delimited JSON, one object per row,
+//! reading a page at a time from the database so that memory stays bounded
+//! regardless of the size of the wallet. Each row carries an opaque cursor
+//! which can be passed back to resume an interrupted stream after that row.
+
+use crate::{
+    json_rpc::{
+        capability::Capability,
+        json_rpc_request::JsonRPCRequest,
+        json_rpc_response::JsonRPCError,
+        scoped_api_key::AccountScope,
+        v2::models::{transaction_log::TransactionLog, txo::Txo},
+        wallet::{ApiKeyGuard, RequestId},
+    },
+    service::{transaction_log::TransactionLogService, txo::TxoService, WalletService},
+    util::constants::DEFAULT_STREAM_PAGE_SIZE,
+};
+use mc_common::logger::log;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+use rocket::{
+    http::ContentType,
+    response::{content::Content, Stream},
+};
+use rocket_contrib::json::Json;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io::{self, Read},
+};
+
+/// Listings which can be streamed.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "method", content = "params")]
+#[allow(non_camel_case_types)]
+pub enum JsonStreamRequest {
+    get_txos {
+        account_id: String,
+        cursor: Option<String>,
+        page_size: Option<String>,
+    },
+    get_transaction_logs {
+        account_id: String,
+        cursor: Option<String>,
+        page_size: Option<String>,
+    },
+}
+
+impl TryFrom<&JsonRPCRequest> for JsonStreamRequest {
+    type Error = String;
+
+    fn try_from(src: &JsonRPCRequest) -> Result<JsonStreamRequest, String> {
+        let src_json: serde_json::Value = serde_json::json!(src);
+        serde_json::from_value(src_json).map_err(|e| format!("Could not get value {:?}", e))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StreamListing {
+    Txos,
+    TransactionLogs,
+}
+
+impl StreamListing {
+    fn cursor_prefix(&self) -> &'static str {
+        match self {
+            StreamListing::Txos => "txo",
+            StreamListing::TransactionLogs => "transaction_log",
+        }
+    }
+}
+
+/// Encode the cursor which resumes a listing after the row with the given id.
+pub fn encode_cursor(listing: StreamListing, last_id: &str) -> String {
+    hex::encode(format!("{}:{}", listing.cursor_prefix(), last_id))
+}
+
+/// Decode a cursor to the id of the last row which was streamed.
+pub fn decode_cursor(listing: StreamListing, cursor: &str) -> Result<String, String> {
+    let decoded = hex::decode(cursor).map_err(|_| "Invalid cursor".to_string())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| "Invalid cursor".to_string())?;
+    match decoded.split_once(':') {
+        Some((prefix, last_id)) if prefix == listing.cursor_prefix() => Ok(last_id.to_string()),
+        _ => Err(format!(
+            "Cursor is not for a {} listing",
+            listing.cursor_prefix()
+        )),
+    }
+}
+
+/// Reads a listing page by page, producing one JSON line per row.
+///
+/// The final line is either `{"done": true}`, or `{"error": ...}` if a page
+/// could not be read, in which case the stream can be resumed from the cursor
+/// of the last row.
+pub struct NdjsonStream<
+    'a,
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+> {
+    service: &'a WalletService<T, FPR>,
+    listing: StreamListing,
+    account_id: String,
+    last_id: Option<String>,
+    page_size: u64,
+    lines: VecDeque<u8>,
+    finished: bool,
+}
+
+impl<'a, T, FPR> NdjsonStream<'a, T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    pub fn new(
+        service: &'a WalletService<T, FPR>,
+        listing: StreamListing,
+        account_id: String,
+        last_id: Option<String>,
+        page_size: u64,
+    ) -> Self {
+        Self {
+            service,
+            listing,
+            account_id,
+            last_id,
+            page_size: page_size.max(1),
+            lines: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Read the next page of rows into the line buffer, returning the number
+    /// of rows read.
+    fn read_page(&mut self) -> Result<usize, String> {
+        let rows: Vec<(String, serde_json::Value)> = match self.listing {
+            StreamListing::Txos => self
+                .service
+                .list_txos_after(&self.account_id, self.last_id.as_deref(), self.page_size)
+                .map_err(|e| e.to_string())?
+                .iter()
+                .map(|(txo, status)| (txo.id.clone(), json!({ "txo": Txo::new(txo, status) })))
+                .collect(),
+            StreamListing::TransactionLogs => self
+                .service
+                .list_transaction_logs_after(
+                    &self.account_id,
+                    self.last_id.as_deref(),
+                    self.page_size,
+                )
+                .map_err(|e| e.to_string())?
+                .iter()
+                .map(|(transaction_log, associated_txos, value_map)| {
+                    (
+                        transaction_log.id.clone(),
+                        json!({
+                            "transaction_log":
+                                TransactionLog::new(transaction_log, associated_txos, value_map)
+                        }),
+                    )
+                })
+                .collect(),
+        };
+
+        let num_rows = rows.len();
+        for (id, mut row) in rows {
+            row["cursor"] = json!(encode_cursor(self.listing, &id));
+            self.push_line(&row);
+            self.last_id = Some(id);
+        }
+        Ok(num_rows)
+    }
+
+    fn push_line(&mut self, line: &serde_json::Value) {
+        self.lines.extend(line.to_string().as_bytes());
+        self.lines.push_back(b'\n');
+    }
+}
+
+impl<'a, T, FPR> Read for NdjsonStream<'a, T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.lines.is_empty() && !self.finished {
+            match self.read_page() {
+                Ok(num_rows) if (num_rows as u64) < self.page_size => {
+                    self.push_line(&json!({ "done": true }));
+                    self.finished = true;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    log::error!(self.service.request_logger(), "Stream failed: {}", e);
+                    self.push_line(&json!({ "error": e }));
+                    self.finished = true;
+                }
+            }
+        }
+
+        let num_bytes = buf.len().min(self.lines.len());
+        for (dst, src) in buf.iter_mut().zip(self.lines.drain(..num_bytes)) {
+            *dst = src;
+        }
+        Ok(num_bytes)
+    }
+}
+
+pub type NdjsonResponse<'a, T, FPR> = Content<Stream<NdjsonStream<'a, T, FPR>>>;
+
+/// The streaming route handler, shared between the rocket instances.
+pub fn generic_wallet_stream_api<'a, T, FPR>(
+    api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
+    service: &'a WalletService<T, FPR>,
+    command: Json<JsonRPCRequest>,
+) -> Result<NdjsonResponse<'a, T, FPR>, String>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    // Every streamed listing only reads the wallet.
+    if !api_key_guard.capabilities.contains(Capability::Read) {
+        return Err(format!(
+            "The {} method requires the {} capability, which is not permitted on this endpoint",
+            command.0.method,
+            Capability::Read
+        ));
+    }
+
+    let request = JsonStreamRequest::try_from(&command.0)?;
+    let account_id = match &request {
+        JsonStreamRequest::get_txos { account_id, .. }
+        | JsonStreamRequest::get_transaction_logs { account_id, .. } => account_id,
+    };
+    if let Err(JsonRPCError::error { data, .. }) = api_key_guard.check_accounts(
+        &command.0.method,
+        &AccountScope::Accounts(vec![account_id.as_str()]),
+    ) {
+        return Err(data["details"].as_str().unwrap_or_default().to_string());
+    }
+
+    service.with_request_id(&request_id.0, || {
+        let stream = ndjson_stream(service, request)?;
+        Ok(Content(
+            ContentType::new("application", "x-ndjson"),
+            Stream::from(stream),
+        ))
+    })
+}
+
+/// Start streaming the requested listing.
+pub fn ndjson_stream<T, FPR>(
+    service: &WalletService<T, FPR>,
+    request: JsonStreamRequest,
+) -> Result<NdjsonStream<T, FPR>, String>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let (listing, account_id, cursor, page_size) = match request {
+        JsonStreamRequest::get_txos {
+            account_id,
+            cursor,
+            page_size,
+        } => (StreamListing::Txos, account_id, cursor, page_size),
+        JsonStreamRequest::get_transaction_logs {
+            account_id,
+            cursor,
+            page_size,
+        } => (StreamListing::TransactionLogs, account_id, cursor, page_size),
+    };
+
+    let last_id = match cursor {
+        Some(cursor) => Some(decode_cursor(listing, &cursor)?),
+        None => None,
+    };
+    let page_size = match page_size {
+        Some(page_size) => page_size.parse::<u64>().map_err(|e| e.to_string())?,
+        None => DEFAULT_STREAM_PAGE_SIZE,
+    };
+
+    Ok(NdjsonStream::new(
+        service, listing, account_id, last_id, page_size,
+    ))
+}
@@ -2,18 +2,31 @@
 
 use crate::{
     json_rpc::{
-        json_rpc_request::JsonRPCRequest,
-        json_rpc_response::JsonRPCResponse,
+        capability::{CapabilitySet, ListenerCapabilities},
+        json_rpc_request::{JsonRPCCall, JsonRPCRequest},
+        json_rpc_response::{JsonRPCCallResponse, JsonRPCResponse},
+        request_limits::{LimitedJson, RequestSizeLimits},
+        scoped_api_key::{ScopedApiKey, ScopedApiKeysState},
         v2::api::{
-            request::JsonCommandRequest, response::JsonCommandResponse, wallet::wallet_api_inner,
+            request::JsonCommandRequest,
+            response::JsonCommandResponse,
+            stream::{generic_wallet_stream_api, NdjsonResponse},
+            wallet::{check_confirmation, wallet_api_batch, wallet_api_inner},
         },
     },
-    service::WalletService,
+    service::{
+        health::{HealthConfig, HealthReport},
+        WalletService, WalletServiceConfig,
+    },
     test_utils::{
         get_resolver_factory, get_test_ledger, setup_peer_manager_and_network_state,
         WalletDbTestContext,
     },
-    wallet::{APIKeyState, ApiKeyGuard},
+    util::constants::DEFAULT_MAX_BATCH_SIZE,
+    wallet::{
+        api_catchers, generic_health_live, generic_health_ready, APIKeyState, ApiKeyGuard,
+        RequestId, SecretsAPIKeyState,
+    },
 };
 use mc_account_keys::PublicAddress;
 use mc_common::logger::{log, Logger};
@@ -23,9 +36,12 @@ use mc_ledger_db::{Ledger, LedgerDB};
 use mc_ledger_sync::PollingNetworkState;
 use rand::rngs::StdRng;
 use rocket::{
+    get,
     http::{ContentType, Header, Status},
     local::Client,
-    post, routes,
+    post,
+    response::status,
+    routes, State,
 };
 use rocket_contrib::json::{Json, JsonValue};
 use std::{
@@ -43,31 +59,59 @@ pub fn get_free_port() -> u16 {
 }
 
 pub struct TestWalletState {
-    pub service: WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>,
+    pub service: Arc<WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>>,
+    pub max_batch_size: usize,
 }
 
 // Note: the reason this is duplicated from wallet.rs is to be able to pass the
 // TestWalletState, which handles Mock objects.
 #[post("/wallet/v2", format = "json", data = "<command>")]
 fn test_wallet_api(
-    _guard: ApiKeyGuard,
+    guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<TestWalletState>,
-    command: Json<JsonRPCRequest>,
-) -> Result<Json<JsonRPCResponse<JsonCommandResponse>>, String> {
-    let req: JsonRPCRequest = command.0.clone();
+    command: LimitedJson<JsonRPCCall>,
+) -> Result<Json<JsonRPCCallResponse<JsonCommandResponse>>, String> {
+    let req: JsonRPCRequest = match command.0 {
+        JsonRPCCall::Single(req) => req,
+        JsonRPCCall::Batch(batch) => {
+            return Ok(Json(wallet_api_batch(
+                &state.service,
+                &guard,
+                &request_id.0,
+                batch,
+                state.max_batch_size,
+            )))
+        }
+    };
 
     let mut response = JsonRPCResponse {
-        method: Some(command.0.method),
+        method: Some(req.method.clone()),
         result: None,
         error: None,
         jsonrpc: "2.0".to_string(),
-        id: command.0.id,
+        id: req.id.clone(),
+        request_id: Some(request_id.0.clone()),
     };
 
-    match wallet_api_inner(
-        &state.service,
-        JsonCommandRequest::try_from(&req).map_err(|e| e)?,
-    ) {
+    let request = JsonCommandRequest::try_from(&req).map_err(|e| e)?;
+    if let Err(rpc_error) =
+        guard.check_method(&req.method, request.capability(), request.exposes_secrets())
+    {
+        response.error = Some(rpc_error);
+        return Ok(Json(JsonRPCCallResponse::Single(response)));
+    }
+    if let Err(rpc_error) = guard.check_accounts(&req.method, &request.account_scope()) {
+        response.error = Some(rpc_error);
+        return Ok(Json(JsonRPCCallResponse::Single(response)));
+    }
+    if let Err(rpc_error) = check_confirmation(&state.service, &req, &request) {
+        response.error = Some(rpc_error);
+        return Ok(Json(JsonRPCCallResponse::Single(response)));
+    }
+    match state.service.with_request_id(&request_id.0, || {
+        wallet_api_inner(&state.service, request, guard.account_ids.as_ref())
+    }) {
         Ok(command_response) => {
             response.result = Some(command_response);
         }
@@ -76,22 +120,72 @@ fn test_wallet_api(
         }
     };
 
-    Ok(Json(response))
+    Ok(Json(JsonRPCCallResponse::Single(response)))
+}
+
+#[post("/wallet/v2/stream", format = "json", data = "<command>")]
+fn test_wallet_stream_api(
+    _guard: ApiKeyGuard,
+    request_id: RequestId,
+    state: rocket::State<TestWalletState>,
+    command: LimitedJson<JsonRPCRequest>,
+) -> Result<NdjsonResponse<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>, String> {
+    generic_wallet_stream_api(_guard, request_id, &state.inner().service, Json(command.0))
+}
+
+#[get("/health/live")]
+fn test_health_live(
+    state: rocket::State<TestWalletState>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_live(&state.service, config)
+}
+
+#[get("/health/ready")]
+fn test_health_ready(
+    state: rocket::State<TestWalletState>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_ready(&state.service, config)
 }
 
 pub fn test_rocket(rocket_config: rocket::Config, state: TestWalletState) -> rocket::Rocket {
     rocket::custom(rocket_config)
-        .mount("/", routes![test_wallet_api])
+        .mount(
+            "/",
+            routes![
+                test_wallet_api,
+                test_wallet_stream_api,
+                test_health_live,
+                test_health_ready
+            ],
+        )
+        .register(api_catchers())
         .manage(state)
 }
 
 pub const BASE_TEST_BLOCK_HEIGHT: usize = 12;
 
-pub fn create_test_setup(
+fn create_test_service(
+    rng: &mut StdRng,
+    logger: Logger,
+) -> (
+    Arc<WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>>,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    create_test_service_with_peer_ledger(rng, None, logger)
+}
+
+/// Creates the service, with peers serving the given ledger, or the wallet's
+/// own ledger if None.
+fn create_test_service_with_peer_ledger(
     mut rng: &mut StdRng,
+    peer_ledger_db: Option<LedgerDB>,
     logger: Logger,
 ) -> (
-    rocket::Rocket,
+    Arc<WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>>,
     LedgerDB,
     WalletDbTestContext,
     Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
@@ -100,8 +194,11 @@ pub fn create_test_setup(
     let wallet_db = db_test_context.get_db_instance(logger.clone());
     let known_recipients: Vec<PublicAddress> = Vec::new();
     let ledger_db = get_test_ledger(5, &known_recipients, BASE_TEST_BLOCK_HEIGHT, &mut rng);
-    let (peer_manager, network_state) =
-        setup_peer_manager_and_network_state(ledger_db.clone(), logger.clone(), false);
+    let (peer_manager, network_state) = setup_peer_manager_and_network_state(
+        peer_ledger_db.unwrap_or_else(|| ledger_db.clone()),
+        logger.clone(),
+        false,
+    );
 
     let service = WalletService::new(
         wallet_db,
@@ -109,16 +206,41 @@ pub fn create_test_setup(
         peer_manager,
         network_state.clone(),
         get_resolver_factory(&mut rng).unwrap(),
-        false,
+        WalletServiceConfig::default(),
         logger,
     );
 
+    (Arc::new(service), ledger_db, db_test_context, network_state)
+}
+
+fn create_test_rocket(
+    service: Arc<WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>>,
+) -> rocket::Rocket {
     let rocket_config: rocket::Config =
         rocket::Config::build(rocket::config::Environment::Development)
             .port(get_free_port())
             .unwrap();
 
-    let rocket_instance = test_rocket(rocket_config, TestWalletState { service });
+    test_rocket(
+        rocket_config,
+        TestWalletState {
+            service,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+        },
+    )
+}
+
+pub fn create_test_setup(
+    rng: &mut StdRng,
+    logger: Logger,
+) -> (
+    rocket::Rocket,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (service, ledger_db, db_test_context, network_state) = create_test_service(rng, logger);
+    let rocket_instance = create_test_rocket(service);
 
     (rocket_instance, ledger_db, db_test_context, network_state)
 }
@@ -167,6 +289,144 @@ pub fn setup_with_api_key(
     )
 }
 
+pub fn setup_with_secrets_api_key(
+    rng: &mut StdRng,
+    logger: Logger,
+    api_key: String,
+    secrets_api_key: String,
+) -> (
+    Client,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (rocket_instance, ledger_db, db_test_context, network_state) =
+        create_test_setup(rng, logger);
+
+    let rocket = rocket_instance
+        .manage(APIKeyState(api_key))
+        .manage(SecretsAPIKeyState(secrets_api_key));
+
+    (
+        Client::new(rocket).expect("valid rocket instance"),
+        ledger_db,
+        db_test_context,
+        network_state,
+    )
+}
+
+/// Sets up a client for each of the given listener capabilities, all served by
+/// the same wallet, as the server does for multiple listeners.
+pub fn setup_with_listener_capabilities(
+    rng: &mut StdRng,
+    logger: Logger,
+    listener_capabilities: Vec<CapabilitySet>,
+) -> (
+    Vec<Client>,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (service, ledger_db, db_test_context, network_state) = create_test_service(rng, logger);
+
+    let clients = listener_capabilities
+        .into_iter()
+        .map(|capabilities| {
+            let rocket = create_test_rocket(service.clone())
+                .manage(APIKeyState("".to_string()))
+                .manage(ListenerCapabilities(capabilities));
+            Client::new(rocket).expect("valid rocket instance")
+        })
+        .collect();
+
+    (clients, ledger_db, db_test_context, network_state)
+}
+
+/// Sets up a client which accepts the API keys scoped to accounts returned by
+/// scoped_api_keys, with no main API key set. The service is passed
+/// to scoped_api_keys, so that it can create the accounts the keys name.
+pub fn setup_with_scoped_api_keys(
+    rng: &mut StdRng,
+    logger: Logger,
+    scoped_api_keys: impl FnOnce(
+        &WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>,
+    ) -> Vec<ScopedApiKey>,
+) -> (
+    Client,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (service, ledger_db, db_test_context, network_state) = create_test_service(rng, logger);
+    let scoped_api_keys = scoped_api_keys(&service);
+
+    let rocket = create_test_rocket(service)
+        .manage(APIKeyState("".to_string()))
+        .manage(ScopedApiKeysState(scoped_api_keys));
+
+    (
+        Client::new(rocket).expect("valid rocket instance"),
+        ledger_db,
+        db_test_context,
+        network_state,
+    )
+}
+
+/// Sets up a client which accepts request bodies up to the given limits.
+pub fn setup_with_request_size_limits(
+    rng: &mut StdRng,
+    logger: Logger,
+    request_size_limits: RequestSizeLimits,
+) -> (
+    Client,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (rocket_instance, ledger_db, db_test_context, network_state) =
+        create_test_setup(rng, logger);
+
+    let rocket = rocket_instance
+        .manage(APIKeyState("".to_string()))
+        .manage(request_size_limits);
+
+    (
+        Client::new(rocket).expect("valid rocket instance"),
+        ledger_db,
+        db_test_context,
+        network_state,
+    )
+}
+
+/// Sets up a client whose peers serve the given ledger rather than the
+/// wallet's, such as a longer one to put the wallet behind the network, and
+/// which checks health with the given thresholds.
+pub fn setup_with_peer_ledger(
+    rng: &mut StdRng,
+    logger: Logger,
+    peer_ledger_db: LedgerDB,
+    health_config: HealthConfig,
+) -> (
+    Client,
+    LedgerDB,
+    WalletDbTestContext,
+    Arc<RwLock<PollingNetworkState<MockBlockchainConnection<LedgerDB>>>>,
+) {
+    let (service, ledger_db, db_test_context, network_state) =
+        create_test_service_with_peer_ledger(rng, Some(peer_ledger_db), logger);
+
+    let rocket = create_test_rocket(service)
+        .manage(APIKeyState("".to_string()))
+        .manage(health_config);
+
+    (
+        Client::new(rocket).expect("valid rocket instance"),
+        ledger_db,
+        db_test_context,
+        network_state,
+    )
+}
+
 pub fn dispatch(client: &Client, request_body: JsonValue, logger: &Logger) -> JsonValue {
     log::info!(logger, "Attempting dispatch of\n{:?}\n", request_body,);
     let request_body = request_body.to_string();
@@ -185,6 +445,21 @@ pub fn dispatch(client: &Client, request_body: JsonValue, logger: &Logger) -> Js
     res
 }
 
+/// Dispatch an irreversible request the way a client confirms it, by first
+/// preparing it to get a confirmation challenge, and then sending it with the
+/// challenge.
+pub fn dispatch_confirmed(client: &Client, request_body: JsonValue, logger: &Logger) -> JsonValue {
+    let mut prepare_body = request_body.clone();
+    let method = request_body["method"].as_str().unwrap();
+    prepare_body["method"] = json!(format!("prepare_{}", method)).into();
+    let res = dispatch(client, prepare_body, logger);
+    let challenge = res["result"]["confirmation"]["challenge"].as_str().unwrap();
+
+    let mut confirmed_body = request_body.clone();
+    confirmed_body["params"]["confirmation"] = json!({ "challenge": challenge }).into();
+    dispatch(client, confirmed_body, logger)
+}
+
 pub fn dispatch_with_header(
     client: &Client,
     request_body: JsonValue,
@@ -245,7 +520,12 @@ pub fn dispatch_expect_error(
         request_body,
         response_body
     );
-    let response_json: serde_json::Value = serde_json::from_str(&response_body).unwrap();
+    let mut response_json: serde_json::Value = serde_json::from_str(&response_body).unwrap();
+    // The request ID is generated per request, so it is not part of the
+    // expected error.
+    if let Some(response_object) = response_json.as_object_mut() {
+        response_object.remove("request_id");
+    }
     let expected_json: serde_json::Value = serde_json::from_str(&expected_err).unwrap();
     assert_eq!(response_json, expected_json);
 }
@@ -1,97 +1,213 @@
 use crate::{
     db::{
         account::AccountID,
-        transaction_log::TransactionID,
+        transaction_log::{TransactionID, TransactionLogFilter},
         txo::{TxoID, TxoStatus},
+        WalletDbError,
     },
     json_rpc::{
-        json_rpc_request::JsonRPCRequest,
+        json_rpc_request::{JsonRPCCall, JsonRPCRequest},
         json_rpc_response::{
-            format_error, format_invalid_request_error, JsonRPCError, JsonRPCResponse,
+            format_confirmation_error, format_error, format_invalid_request_error,
+            format_shutting_down_error, format_transaction_error, JsonRPCCallResponse,
+            JsonRPCError, JsonRPCResponse,
         },
         v2::{
             api::{request::JsonCommandRequest, response::JsonCommandResponse},
             models::{
                 account::{Account, AccountMap},
                 account_secrets::AccountSecrets,
-                address::{Address, AddressMap},
+                address::{
+                    Address, AddressAttestation, AddressLabel, AddressMap, AddressNetworkCheck,
+                    ReservedAddress,
+                },
+                amount::{with_display_amounts, Amount as AmountJSON},
                 balance::{Balance, BalanceMap},
                 block::{Block, BlockContents},
+                build_report::BuildReport,
+                confirmation_challenge::ConfirmationChallenge,
                 confirmation_number::Confirmation,
+                database_status::DatabaseStatus,
+                fee_total::FeeTotal,
+                job::Job,
+                key_image_status::KeyImageStatus,
+                ledger_db_status::LedgerDbStatus,
+                ledger_scan::LedgerScan,
                 network_status::NetworkStatus,
+                payment_status::PaymentStatus,
+                peer_status::PeerStatus,
+                proof_of_reserves::{ProofOfReserves, ReservesVerification},
+                public_address_keys::PublicAddressKeys,
                 receiver_receipt::ReceiverReceipt,
-                transaction_log::{TransactionLog, TransactionLogMap},
+                sender_memo::SenderMemo,
+                sync_stats::AccountSyncStats,
+                transaction_log::{OutputTxo, TransactionLog, TransactionLogMap},
+                transaction_preview::TransactionPreview,
+                transaction_report::TransactionReport,
+                transaction_ring_details::TransactionRingDetails,
                 tx_proposal::TxProposal as TxProposalJSON,
                 txo::{Txo, TxoMap},
-                wallet_status::WalletStatus,
+                wallet_status::{WalletRollup, WalletStatus},
             },
         },
-        wallet::{ApiKeyGuard, WalletState},
+        wallet::{ApiKeyGuard, RequestId, WalletState},
     },
     service::{
         self,
         account::AccountService,
+        account_backup::AccountBackupService,
+        account_migration::AccountMigrationService,
         address::AddressService,
         balance::BalanceService,
+        build_report::BuildReportService,
         confirmation_number::ConfirmationService,
+        database::DatabaseService,
+        job::JobService,
         ledger::LedgerService,
+        ledger_db::LedgerDbService,
+        ledger_scan::LedgerScanService,
         models::tx_proposal::TxProposal,
         payment_request::PaymentRequestService,
+        peer::PeerService,
+        proof_of_reserves::ProofOfReservesService,
         receipt::ReceiptService,
         transaction::{TransactionMemo, TransactionService},
         transaction_log::TransactionLogService,
-        txo::TxoService,
+        transaction_report::TransactionReportService,
+        txo::{TxoService, TxoServiceError},
+        view_only_sync::ViewOnlySyncService,
         WalletService,
     },
-    util::b58::{
-        b58_decode_payment_request, b58_encode_public_address, b58_printable_wrapper_type,
-        PrintableWrapperType,
+    util::{
+        b58::{
+            b58_decode_payment_request, b58_encode_public_address, b58_printable_wrapper_type,
+            PrintableWrapperType,
+        },
+        constants::{
+            DEFAULT_ACCOUNT_BEHIND_THRESHOLD_BLOCKS, DEFAULT_NEXT_SUBADDRESS_INDEX,
+            DEFAULT_TXOS_MODIFIED_SINCE_LIMIT, TX_PROPOSAL_SIZE_WARNING_BYTES,
+        },
+        encoding_helpers::hex_to_ristretto,
+        sender_memo::{compute_address_hash, decode_sender_memo},
     },
 };
 use mc_account_keys::burn_address;
-use mc_common::logger::global_log;
+use mc_common::logger::log;
 use mc_connection::{BlockchainConnection, UserTxConnection};
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
 use mc_mobilecoind_json::data_types::{JsonTx, JsonTxOut, JsonTxOutMembershipProof};
 use mc_transaction_core::Amount;
 use mc_transaction_std::BurnRedemptionMemo;
 use rocket::{self};
 use rocket_contrib::json::Json;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     convert::{TryFrom, TryInto},
     str::FromStr,
+    sync::Arc,
+    thread,
 };
 
 pub fn generic_wallet_api<T, FPR>(
-    _api_key_guard: ApiKeyGuard,
+    api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<T, FPR>>,
-    command: Json<JsonRPCRequest>,
-) -> Result<Json<JsonRPCResponse<JsonCommandResponse>>, String>
+    command: Json<JsonRPCCall>,
+) -> Result<Json<JsonRPCCallResponse<JsonCommandResponse>>, String>
 where
     T: BlockchainConnection + UserTxConnection + 'static,
     FPR: FogPubkeyResolver + Send + Sync + 'static,
 {
-    let req: JsonRPCRequest = command.0.clone();
+    let response = match command.0 {
+        JsonRPCCall::Single(req) => JsonRPCCallResponse::Single(wallet_api_request(
+            &state.service,
+            &api_key_guard,
+            &request_id.0,
+            req,
+        )),
+        JsonRPCCall::Batch(batch) => wallet_api_batch(
+            &state.service,
+            &api_key_guard,
+            &request_id.0,
+            batch,
+            state.max_batch_size,
+        ),
+    };
+
+    Ok(Json(response))
+}
 
+/// Handles a single request, reporting any error in the response.
+pub fn wallet_api_request<T, FPR>(
+    service: &WalletService<T, FPR>,
+    api_key_guard: &ApiKeyGuard,
+    request_id: &str,
+    req: JsonRPCRequest,
+) -> JsonRPCResponse<JsonCommandResponse>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
     let mut response = JsonRPCResponse {
-        method: Some(command.0.method),
+        method: Some(req.method.clone()),
         result: None,
         error: None,
         jsonrpc: "2.0".to_string(),
-        id: command.0.id,
+        id: req.id.clone(),
+        request_id: Some(request_id.to_string()),
     };
 
     let request = match JsonCommandRequest::try_from(&req) {
         Ok(request) => request,
         Err(error) => {
             response.error = Some(format_invalid_request_error(error));
-            return Ok(Json(response));
+            return response;
+        }
+    };
+
+    if let Err(error) =
+        api_key_guard.check_method(&req.method, request.capability(), request.exposes_secrets())
+    {
+        response.error = Some(error);
+        return response;
+    }
+    if let Err(error) = api_key_guard.check_accounts(&req.method, &request.account_scope()) {
+        response.error = Some(error);
+        return response;
+    }
+
+    let _request_permit = if request.allowed_during_shutdown() {
+        None
+    } else {
+        match service.shutdown_coordinator.begin_request() {
+            Ok(permit) => Some(permit),
+            Err(e) => {
+                response.error = Some(format_shutting_down_error(e));
+                return response;
+            }
         }
     };
 
-    match wallet_api_inner(&state.service, request) {
+    if let Err(error) = check_confirmation(service, &req, &request) {
+        response.error = Some(error);
+        return response;
+    }
+
+    // Amounts in the response carry human readable fields when the request
+    // sets `display`, which is not part of the request it modifies.
+    let display = req
+        .params
+        .as_ref()
+        .and_then(|params| params["display"].as_bool())
+        .unwrap_or(false);
+
+    match service.with_request_id(request_id, || {
+        with_display_amounts(display, || {
+            wallet_api_inner(service, request, api_key_guard.account_ids.as_ref())
+        })
+    }) {
         Ok(command_response) => {
             response.result = Some(command_response);
         }
@@ -100,7 +216,143 @@ where
         }
     };
 
-    Ok(Json(response))
+    response
+}
+
+/// Checks that an irreversible request echoes back a confirmation challenge
+/// which was prepared for exactly that request, and uses the challenge up.
+///
+/// The challenge is sent as `confirmation.challenge` in the params, and is
+/// not part of the request it confirms.
+pub fn check_confirmation<T, FPR>(
+    service: &WalletService<T, FPR>,
+    req: &JsonRPCRequest,
+    request: &JsonCommandRequest,
+) -> Result<(), JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    if !request.requires_confirmation() {
+        return Ok(());
+    }
+
+    let challenge = req
+        .params
+        .as_ref()
+        .and_then(|params| params["confirmation"]["challenge"].as_str());
+    service
+        .confirmation_challenges
+        .redeem(&serde_json::json!(request), challenge)
+        .map_err(format_confirmation_error)
+}
+
+/// Handles a batch of requests, returning their responses in the order of the
+/// requests.
+///
+/// The requests are handled concurrently, each on its own thread, and
+/// transaction builds for the same account are still serialized by the build
+/// guard. They do not run on rayon workers, as a worker waiting on nested
+/// rayon work may pick up another request of the batch, and would then share
+/// its per-thread build permit with it. A request which fails only fails its
+/// own response. A batch which is empty or larger than max_batch_size is
+/// rejected with a single error response, as the JSON RPC 2.0 spec requires.
+pub fn wallet_api_batch<T, FPR>(
+    service: &Arc<WalletService<T, FPR>>,
+    api_key_guard: &ApiKeyGuard,
+    request_id: &str,
+    batch: Vec<serde_json::Value>,
+    max_batch_size: usize,
+) -> JsonRPCCallResponse<JsonCommandResponse>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    if batch.is_empty() || batch.len() > max_batch_size {
+        return JsonRPCCallResponse::Single(batch_error_response(
+            request_id,
+            serde_json::Value::Null,
+            format_invalid_request_error(format!(
+                "A batch must contain between 1 and {} requests, got {}",
+                max_batch_size,
+                batch.len()
+            )),
+        ));
+    }
+
+    let handles: Vec<_> = batch
+        .into_iter()
+        .map(|item| {
+            let id = item.get("id").cloned().unwrap_or(serde_json::Value::Null);
+            let service = service.clone();
+            let api_key_guard = api_key_guard.clone();
+            let request_id = request_id.to_string();
+            let handle = thread::Builder::new()
+                .name("batch_request".to_string())
+                .spawn(move || wallet_api_batch_item(&service, &api_key_guard, &request_id, item));
+            (id, handle)
+        })
+        .collect();
+
+    JsonRPCCallResponse::Batch(
+        handles
+            .into_iter()
+            .map(|(id, handle)| match handle {
+                Ok(handle) => handle.join().unwrap_or_else(|_| {
+                    batch_error_response(
+                        request_id,
+                        id,
+                        format_error("The request failed unexpectedly"),
+                    )
+                }),
+                Err(e) => batch_error_response(request_id, id, format_error(e)),
+            })
+            .collect(),
+    )
+}
+
+/// Handles one request of a batch.
+fn wallet_api_batch_item<T, FPR>(
+    service: &WalletService<T, FPR>,
+    api_key_guard: &ApiKeyGuard,
+    request_id: &str,
+    item: serde_json::Value,
+) -> JsonRPCResponse<JsonCommandResponse>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    if item.is_array() {
+        return batch_error_response(
+            request_id,
+            serde_json::Value::Null,
+            format_invalid_request_error("Nested batches are not supported"),
+        );
+    }
+    let id = item.get("id").cloned().unwrap_or(serde_json::Value::Null);
+    match serde_json::from_value::<JsonRPCRequest>(item) {
+        Ok(req) => wallet_api_request(service, api_key_guard, request_id, req),
+        Err(e) => batch_error_response(
+            request_id,
+            id,
+            format_invalid_request_error(format!("Could not parse request {:?}", e)),
+        ),
+    }
+}
+
+fn batch_error_response(
+    request_id: &str,
+    id: serde_json::Value,
+    error: JsonRPCError,
+) -> JsonRPCResponse<JsonCommandResponse> {
+    JsonRPCResponse {
+        method: None,
+        result: None,
+        error: Some(error),
+        jsonrpc: "2.0".to_string(),
+        id,
+        request_id: Some(request_id.to_string()),
+    }
 }
 
 /// The Wallet API inner method, which handles switching on the method enum.
@@ -109,17 +361,39 @@ where
 /// take explicit Rocket state, and then pass the service to the inner method.
 /// This allows us to properly construct state with Mock Connection Objects in
 /// tests. This also allows us to version the overall API easily.
+///
+/// The methods which list every account only list those in account_filter,
+/// when the request is limited to some accounts.
 pub fn wallet_api_inner<T, FPR>(
     service: &WalletService<T, FPR>,
     command: JsonCommandRequest,
+    account_filter: Option<&BTreeSet<String>>,
 ) -> Result<JsonCommandResponse, JsonRPCError>
 where
     T: BlockchainConnection + UserTxConnection + 'static,
     FPR: FogPubkeyResolver + Send + Sync + 'static,
 {
-    global_log::trace!("Running command {:?}", command);
+    log::trace!(service.request_logger(), "Running command {:?}", command);
 
     let response = match command {
+        JsonCommandRequest::assign_address_at_index {
+            account_id,
+            subaddress_index,
+            metadata,
+        } => {
+            let subaddress_index = subaddress_index.parse::<u64>().map_err(format_error)?;
+            JsonCommandResponse::assign_address_at_index {
+                address: Address::from(
+                    &service
+                        .assign_address_at_index(
+                            &AccountID(account_id),
+                            subaddress_index,
+                            metadata.as_deref(),
+                        )
+                        .map_err(format_error)?,
+                ),
+            }
+        }
         JsonCommandRequest::assign_address_for_account {
             account_id,
             metadata,
@@ -134,22 +408,53 @@ where
             account_id,
             addresses_and_amounts,
             recipient_public_address,
+            recipient_public_keys,
             amount,
             input_txo_ids,
             fee_value,
             fee_token_id,
             tombstone_block,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
             comment,
+            payment_request_id,
+            idempotency_key,
+            include_receiver_receipts,
+            allow_cross_network,
+            auto_rebuild,
+            selection_token,
         } => {
+            let memo = transaction_memo(payment_request_id)?;
+            let auto_rebuild = auto_rebuild
+                .map(|auto_rebuild| auto_rebuild.parse())
+                .transpose()
+                .map_err(format_invalid_request_error)?;
+            // Rebuilds select inputs from the whole account.
+            if auto_rebuild.is_some() && source_subaddress_b58.is_some() {
+                return Err(format_invalid_request_error(
+                    "auto_rebuild cannot be combined with source_subaddress_b58",
+                ));
+            }
+            let input_txo_ids =
+                input_txo_ids_for_build(service, &account_id, input_txo_ids, selection_token)?;
+
             // The user can specify a list of addresses and values,
             // or a single address and a single value.
             let mut addresses_and_amounts = addresses_and_amounts.unwrap_or_default();
+            let recipient_public_address =
+                recipient_b58(service, recipient_public_address, recipient_public_keys)?;
             if let (Some(address), Some(amount)) = (recipient_public_address, amount) {
                 addresses_and_amounts.push((address, amount));
             }
+            service
+                .check_recipient_networks(
+                    &addresses_and_amounts,
+                    allow_cross_network.unwrap_or(false),
+                )
+                .map_err(format_transaction_error)?;
 
-            let (transaction_log, associated_txos, value_map, tx_proposal) = service
+            let (mut transaction_log, associated_txos, value_map, tx_proposal) = service
                 .build_sign_and_submit_transaction(
                     &account_id,
                     &addresses_and_amounts,
@@ -158,10 +463,25 @@ where
                     fee_token_id,
                     tombstone_block,
                     max_spendable_value,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
                     comment,
-                    TransactionMemo::RTH,
+                    memo,
+                    idempotency_key,
                 )
-                .map_err(format_error)?;
+                .map_err(format_transaction_error)?;
+
+            if let Some((max_attempts, fee_bump_percent)) = auto_rebuild {
+                transaction_log = service
+                    .set_auto_rebuild(&transaction_log.id, max_attempts, fee_bump_percent)
+                    .map_err(format_transaction_error)?;
+            }
+
+            let receiver_receipts = if include_receiver_receipts.unwrap_or(false) {
+                Some(receiver_receipts_json(service, &tx_proposal)?)
+            } else {
+                None
+            };
 
             JsonCommandResponse::build_and_submit_transaction {
                 transaction_log: TransactionLog::new(
@@ -169,7 +489,8 @@ where
                     &associated_txos,
                     &value_map,
                 ),
-                tx_proposal: TxProposalJSON::try_from(&tx_proposal).map_err(format_error)?,
+                tx_proposal: tx_proposal_json(service, &tx_proposal)?,
+                receiver_receipts,
             }
         }
         JsonCommandRequest::build_burn_transaction {
@@ -181,6 +502,9 @@ where
             fee_token_id,
             tombstone_block,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
+            comment,
         } => {
             let mut memo_data = [0; BurnRedemptionMemo::MEMO_DATA_LEN];
             if let Some(redemption_memo_hex) = redemption_memo_hex {
@@ -206,12 +530,15 @@ where
                     fee_token_id,
                     tombstone_block,
                     max_spendable_value,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
+                    comment,
                     TransactionMemo::BurnRedemption(memo_data),
                 )
-                .map_err(format_error)?;
+                .map_err(format_transaction_error)?;
 
             JsonCommandResponse::build_burn_transaction {
-                tx_proposal: TxProposalJSON::try_from(&tx_proposal).map_err(format_error)?,
+                tx_proposal: tx_proposal_json(service, &tx_proposal)?,
                 transaction_log_id: TransactionID::from(&tx_proposal.tx).to_string(),
             }
         }
@@ -219,19 +546,38 @@ where
             account_id,
             addresses_and_amounts,
             recipient_public_address,
+            recipient_public_keys,
             amount,
             input_txo_ids,
             fee_value,
             fee_token_id,
             tombstone_block,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
+            comment,
+            payment_request_id,
+            include_receiver_receipts,
+            allow_cross_network,
+            selection_token,
         } => {
+            let memo = transaction_memo(payment_request_id)?;
+            let input_txo_ids =
+                input_txo_ids_for_build(service, &account_id, input_txo_ids, selection_token)?;
             // The user can specify a list of addresses and values,
             // or a single address and a single value.
             let mut addresses_and_amounts = addresses_and_amounts.unwrap_or_default();
+            let recipient_public_address =
+                recipient_b58(service, recipient_public_address, recipient_public_keys)?;
             if let (Some(address), Some(amount)) = (recipient_public_address, amount) {
                 addresses_and_amounts.push((address, amount));
             }
+            service
+                .check_recipient_networks(
+                    &addresses_and_amounts,
+                    allow_cross_network.unwrap_or(false),
+                )
+                .map_err(format_transaction_error)?;
 
             let tx_proposal = service
                 .build_and_sign_transaction(
@@ -242,13 +588,23 @@ where
                     fee_token_id,
                     tombstone_block,
                     max_spendable_value,
-                    TransactionMemo::RTH,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
+                    comment,
+                    memo,
                 )
-                .map_err(format_error)?;
+                .map_err(format_transaction_error)?;
+
+            let receiver_receipts = if include_receiver_receipts.unwrap_or(false) {
+                Some(receiver_receipts_json(service, &tx_proposal)?)
+            } else {
+                None
+            };
 
             JsonCommandResponse::build_transaction {
-                tx_proposal: TxProposalJSON::try_from(&tx_proposal).map_err(format_error)?,
+                tx_proposal: tx_proposal_json(service, &tx_proposal)?,
                 transaction_log_id: TransactionID::from(&tx_proposal.tx).to_string(),
+                receiver_receipts,
             }
         }
         JsonCommandRequest::build_unsigned_burn_transaction {
@@ -260,6 +616,8 @@ where
             fee_token_id,
             tombstone_block,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
         } => {
             let mut memo_data = [0; BurnRedemptionMemo::MEMO_DATA_LEN];
             if let Some(redemption_memo_hex) = redemption_memo_hex {
@@ -285,9 +643,11 @@ where
                     fee_token_id,
                     tombstone_block,
                     max_spendable_value,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
                     TransactionMemo::BurnRedemption(memo_data),
                 )
-                .map_err(format_error)?;
+                .map_err(format_transaction_error)?;
 
             JsonCommandResponse::build_unsigned_burn_transaction {
                 account_id,
@@ -298,6 +658,7 @@ where
         JsonCommandRequest::build_unsigned_transaction {
             account_id,
             recipient_public_address,
+            recipient_public_keys,
             amount,
             fee_value,
             fee_token_id,
@@ -305,11 +666,25 @@ where
             addresses_and_amounts,
             input_txo_ids,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
+            allow_cross_network,
+            selection_token,
         } => {
+            let input_txo_ids =
+                input_txo_ids_for_build(service, &account_id, input_txo_ids, selection_token)?;
             let mut addresses_and_amounts = addresses_and_amounts.unwrap_or_default();
+            let recipient_public_address =
+                recipient_b58(service, recipient_public_address, recipient_public_keys)?;
             if let (Some(address), Some(amount)) = (recipient_public_address, amount) {
                 addresses_and_amounts.push((address, amount));
             }
+            service
+                .check_recipient_networks(
+                    &addresses_and_amounts,
+                    allow_cross_network.unwrap_or(false),
+                )
+                .map_err(format_transaction_error)?;
             let (unsigned_tx, fog_resolver) = service
                 .build_transaction(
                     &account_id,
@@ -319,15 +694,31 @@ where
                     fee_token_id,
                     tombstone_block,
                     max_spendable_value,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
                     TransactionMemo::RTH,
                 )
-                .map_err(format_error)?;
+                .map_err(format_transaction_error)?;
             JsonCommandResponse::build_unsigned_transaction {
                 account_id,
                 unsigned_tx,
                 fog_resolver,
             }
         }
+        JsonCommandRequest::cancel_job { job_id } => {
+            let job = service.cancel_job(&job_id).map_err(format_error)?;
+            JsonCommandResponse::cancel_job {
+                job: Job::from(&job),
+            }
+        }
+        JsonCommandRequest::cancel_ledger_scan { ledger_scan_id } => {
+            let ledger_scan = service
+                .cancel_ledger_scan(&ledger_scan_id)
+                .map_err(format_error)?;
+            JsonCommandResponse::cancel_ledger_scan {
+                ledger_scan: LedgerScan::from(&ledger_scan),
+            }
+        }
         JsonCommandRequest::check_b58_type { b58_code } => {
             let b58_type = b58_printable_wrapper_type(b58_code.clone()).map_err(format_error)?;
             let mut b58_data = HashMap::new();
@@ -352,6 +743,18 @@ where
                 data: b58_data,
             }
         }
+        JsonCommandRequest::check_key_images { key_image_hexes } => {
+            let results = service
+                .check_key_images(&key_image_hexes)
+                .map_err(format_error)?;
+            JsonCommandResponse::check_key_images {
+                key_image_statuses: key_image_hexes
+                    .iter()
+                    .zip(results.iter())
+                    .map(|(key_image_hex, result)| KeyImageStatus::new(key_image_hex, result))
+                    .collect(),
+            }
+        }
         JsonCommandRequest::check_receiver_receipt_status {
             address,
             receiver_receipt,
@@ -368,6 +771,26 @@ where
                     .map(|(txo, status)| Txo::new(txo, status)),
             }
         }
+        JsonCommandRequest::compact_ledger_db { target_path } => {
+            let job = service
+                .compact_ledger_db(&target_path)
+                .map_err(format_error)?;
+            JsonCommandResponse::compact_ledger_db {
+                job: Job::from(&job),
+            }
+        }
+        JsonCommandRequest::complete_view_only_account_sync_request { sync_request } => {
+            JsonCommandResponse::complete_view_only_account_sync_request {
+                sync_response: service
+                    .complete_view_only_account_sync_request(&sync_request)
+                    .map_err(format_error)?,
+            }
+        }
+        JsonCommandRequest::compute_address_hash { public_address_b58 } => {
+            JsonCommandResponse::compute_address_hash {
+                address_hash: compute_address_hash(&public_address_b58).map_err(format_error)?,
+            }
+        }
         JsonCommandRequest::create_account { name, fog_info } => {
             let fog_info = fog_info.unwrap_or_default();
 
@@ -388,6 +811,15 @@ where
 
             JsonCommandResponse::create_account { account }
         }
+        JsonCommandRequest::create_address_attestation {
+            public_address_b58,
+            nonce,
+        } => {
+            let attestation = service
+                .create_address_attestation(&public_address_b58, &nonce)
+                .map_err(format_error)?;
+            JsonCommandResponse::create_address_attestation { attestation }
+        }
         JsonCommandRequest::create_payment_request {
             account_id,
             subaddress_index,
@@ -404,18 +836,9 @@ where
                 .map_err(format_error)?,
         },
         JsonCommandRequest::create_receiver_receipts { tx_proposal } => {
-            let receipts = service
-                .create_receiver_receipts(
-                    &TxProposal::try_from(&tx_proposal).map_err(format_error)?,
-                )
-                .map_err(format_error)?;
-            let json_receipts: Vec<ReceiverReceipt> = receipts
-                .iter()
-                .map(ReceiverReceipt::try_from)
-                .collect::<Result<Vec<ReceiverReceipt>, String>>()
-                .map_err(format_error)?;
+            let tx_proposal = TxProposal::try_from(&tx_proposal).map_err(format_error)?;
             JsonCommandResponse::create_receiver_receipts {
-                receiver_receipts: json_receipts,
+                receiver_receipts: receiver_receipts_json(service, &tx_proposal)?,
             }
         }
         JsonCommandRequest::create_view_only_account_import_request { account_id } => {
@@ -444,11 +867,102 @@ where
                 .map(|(txo, _)| hex::encode(&txo.txo))
                 .collect();
 
+            let sync_request = service
+                .create_view_only_account_sync_request(&AccountID(account_id.clone()))
+                .map_err(format_error)?;
+
             JsonCommandResponse::create_view_only_account_sync_request {
                 account_id,
                 incomplete_txos_encoded: unverified_txos_encoded,
+                sync_request,
+            }
+        }
+        JsonCommandRequest::debug_build_report {
+            account_id,
+            addresses_and_amounts,
+            recipient_public_address,
+            amount,
+            input_txo_ids,
+            fee_value,
+            fee_token_id,
+            tombstone_block,
+            max_spendable_value,
+        } => {
+            let mut addresses_and_amounts = addresses_and_amounts.unwrap_or_default();
+            if let (Some(address), Some(amount)) = (recipient_public_address, amount) {
+                addresses_and_amounts.push((address, amount));
+            }
+
+            let report = service
+                .debug_build_report(
+                    &account_id,
+                    &addresses_and_amounts,
+                    input_txo_ids.as_ref(),
+                    fee_value,
+                    fee_token_id,
+                    tombstone_block,
+                    max_spendable_value,
+                )
+                .map_err(format_error)?;
+
+            JsonCommandResponse::debug_build_report {
+                report: BuildReport::from(&report),
+            }
+        }
+        JsonCommandRequest::decode_sender_memo {
+            memo_payload,
+            tx_out_public_key,
+            view_private_key,
+            sender_public_address_b58,
+        } => {
+            let sender_memo = decode_sender_memo(
+                &memo_payload,
+                &tx_out_public_key,
+                &view_private_key,
+                &sender_public_address_b58,
+            )
+            .map_err(format_error)?;
+            JsonCommandResponse::decode_sender_memo {
+                sender_memo: SenderMemo::from(&sender_memo),
+            }
+        }
+        JsonCommandRequest::decode_txo_amount {
+            tx_out_public_key,
+            view_private_key,
+        } => {
+            let (ledger_tx_out, amount) = service
+                .decode_txo_amount(&tx_out_public_key, &view_private_key)
+                .map_err(format_error)?;
+            JsonCommandResponse::decode_txo_amount {
+                amount: AmountJSON::from(&amount),
+                block_index: ledger_tx_out.block_index.to_string(),
+                global_index: ledger_tx_out.global_index.to_string(),
             }
         }
+        JsonCommandRequest::exclude_peer {
+            responder_id,
+            excluded,
+        } => JsonCommandResponse::exclude_peer {
+            peer_status: PeerStatus::from(
+                &service
+                    .exclude_peer(&responder_id, excluded)
+                    .map_err(format_error)?,
+            ),
+        },
+        JsonCommandRequest::export_account {
+            account_id,
+            include_secrets,
+            passphrase,
+        } => {
+            let backup = service
+                .export_account(
+                    &AccountID(account_id),
+                    include_secrets.unwrap_or(false),
+                    passphrase,
+                )
+                .map_err(format_error)?;
+            JsonCommandResponse::export_account { backup }
+        }
         JsonCommandRequest::export_account_secrets { account_id } => {
             let account = service
                 .get_account(&AccountID(account_id))
@@ -457,6 +971,40 @@ where
                 account_secrets: AccountSecrets::try_from(&account).map_err(format_error)?,
             }
         }
+        JsonCommandRequest::export_address_labels { account_id } => {
+            JsonCommandResponse::export_address_labels {
+                address_labels: service
+                    .export_address_labels(&AccountID(account_id))
+                    .map_err(format_error)?
+                    .iter()
+                    .map(AddressLabel::from)
+                    .collect(),
+            }
+        }
+        JsonCommandRequest::export_proof_of_reserves {
+            account_id,
+            block_index,
+        } => {
+            let block_index = block_index
+                .map(|b| b.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+            let proof_of_reserves = service
+                .export_proof_of_reserves(&AccountID(account_id), block_index)
+                .map_err(format_error)?;
+            JsonCommandResponse::export_proof_of_reserves {
+                proof_of_reserves: ProofOfReserves::from(&proof_of_reserves),
+            }
+        }
+        JsonCommandRequest::flush_fog_pubkey_cache => {
+            let num_entries_flushed = service.fog_pubkey_cache.flush();
+            let stats = service.fog_pubkey_cache.stats();
+            JsonCommandResponse::flush_fog_pubkey_cache {
+                num_entries_flushed: num_entries_flushed.to_string(),
+                hits: stats.hits.to_string(),
+                misses: stats.misses.to_string(),
+            }
+        }
         JsonCommandRequest::get_account_status { account_id } => {
             let account = service
                 .get_account(&AccountID(account_id.clone()))
@@ -471,7 +1019,7 @@ where
             let network_status = service.get_network_status().map_err(format_error)?;
 
             let balance = service
-                .get_balance_for_account(&AccountID(account_id))
+                .get_balance_for_account(&AccountID(account_id.clone()))
                 .map_err(format_error)?;
 
             let balance_formatted = BalanceMap(
@@ -486,10 +1034,25 @@ where
                 network_block_height: network_status.network_block_height.to_string(),
                 local_block_height: network_status.local_block_height.to_string(),
                 balance_per_token: balance_formatted,
+                sync_stats: service
+                    .sync_stats
+                    .get(&account_id)
+                    .as_ref()
+                    .map(AccountSyncStats::from),
             }
         }
         JsonCommandRequest::get_accounts { offset, limit } => {
-            let accounts = service.list_accounts(offset, limit).map_err(format_error)?;
+            let accounts = match account_filter {
+                Some(account_filter) => service
+                    .list_accounts(None, None)
+                    .map_err(format_error)?
+                    .into_iter()
+                    .filter(|account| account_filter.contains(&account.id))
+                    .skip(offset.unwrap_or(0) as usize)
+                    .take(limit.map_or(usize::MAX, |limit| limit as usize))
+                    .collect(),
+                None => service.list_accounts(offset, limit).map_err(format_error)?,
+            };
             let account_map = AccountMap(
                 accounts
                     .iter()
@@ -594,6 +1157,66 @@ where
                     .collect(),
             }
         }
+        JsonCommandRequest::get_database_status => JsonCommandResponse::get_database_status {
+            database_status: DatabaseStatus::from(
+                &service.get_database_status().map_err(format_error)?,
+            ),
+        },
+        JsonCommandRequest::get_fee_totals {
+            account_id,
+            token_id,
+            min_block_index,
+            max_block_index,
+            group_by_month,
+        } => {
+            let token_id = token_id
+                .map(|t| t.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+
+            let min_block_index = min_block_index
+                .map(|i| i.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+
+            let max_block_index = max_block_index
+                .map(|i| i.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+
+            let fee_totals = service
+                .get_fee_totals(
+                    &AccountID(account_id),
+                    token_id,
+                    min_block_index,
+                    max_block_index,
+                    group_by_month.unwrap_or(false),
+                )
+                .map_err(format_error)?;
+
+            JsonCommandResponse::get_fee_totals {
+                fee_totals: fee_totals.iter().map(FeeTotal::from).collect(),
+            }
+        }
+        JsonCommandRequest::get_job_status { job_id } => {
+            let job = service.get_job_status(&job_id).map_err(format_error)?;
+            JsonCommandResponse::get_job_status {
+                job: Job::from(&job),
+            }
+        }
+        JsonCommandRequest::get_ledger_db_status => JsonCommandResponse::get_ledger_db_status {
+            ledger_db_status: LedgerDbStatus::from(
+                &service.get_ledger_db_status().map_err(format_error)?,
+            ),
+        },
+        JsonCommandRequest::get_ledger_scan { ledger_scan_id } => {
+            let ledger_scan = service
+                .get_ledger_scan(&ledger_scan_id)
+                .map_err(format_error)?;
+            JsonCommandResponse::get_ledger_scan {
+                ledger_scan: LedgerScan::from(&ledger_scan),
+            }
+        }
         JsonCommandRequest::get_mc_protocol_transaction { transaction_log_id } => {
             let tx = service
                 .get_transaction_object(&transaction_log_id)
@@ -616,44 +1239,147 @@ where
             )
             .map_err(format_error)?,
         },
+        JsonCommandRequest::get_payment_status { transaction_log_id } => {
+            let (transaction_log, monitored_txos) = service
+                .get_payment_status(&transaction_log_id)
+                .map_err(format_error)?;
+            JsonCommandResponse::get_payment_status {
+                payment_status: PaymentStatus::new(&transaction_log, &monitored_txos),
+            }
+        }
+        JsonCommandRequest::get_peer_status => JsonCommandResponse::get_peer_status {
+            peer_status: PeerStatus::from(&service.get_peer_status()),
+        },
+        JsonCommandRequest::get_receiver_receipts { transaction_log_id } => {
+            JsonCommandResponse::get_receiver_receipts {
+                receiver_receipts: service
+                    .get_receiver_receipts(&transaction_log_id)
+                    .map_err(format_error)?
+                    .iter()
+                    .map(ReceiverReceipt::try_from)
+                    .collect::<Result<Vec<ReceiverReceipt>, String>>()
+                    .map_err(format_error)?,
+            }
+        }
+        JsonCommandRequest::get_reserved_addresses { account_id } => {
+            JsonCommandResponse::get_reserved_addresses {
+                reserved_addresses: service
+                    .get_reserved_addresses(&AccountID(account_id))
+                    .map_err(format_error)?
+                    .iter()
+                    .map(ReservedAddress::try_from)
+                    .collect::<Result<Vec<ReservedAddress>, String>>()
+                    .map_err(format_error)?,
+            }
+        }
         JsonCommandRequest::get_transaction_log { transaction_log_id } => {
             let (transaction_log, associated_txos, value_map) = service
                 .get_transaction_log(&transaction_log_id)
                 .map_err(format_error)?;
+            let rebuild_chain = service
+                .get_transaction_log_rebuild_chain(&transaction_log_id)
+                .map_err(format_error)?;
             JsonCommandResponse::get_transaction_log {
                 transaction_log: TransactionLog::new(
                     &transaction_log,
                     &associated_txos,
                     &value_map,
                 ),
+                rebuild_chain,
+            }
+        }
+        JsonCommandRequest::get_transaction_log_by_tx_out_public_key { public_key } => {
+            let (transaction_log, associated_txos, value_map, txo, transaction_output_txo) =
+                service
+                    .get_transaction_log_by_tx_out_public_key(&public_key)
+                    .map_err(format_error)?;
+            JsonCommandResponse::get_transaction_log_by_tx_out_public_key {
+                transaction_log: TransactionLog::new(
+                    &transaction_log,
+                    &associated_txos,
+                    &value_map,
+                ),
+                output_txo: OutputTxo::new(
+                    &txo,
+                    transaction_output_txo.recipient_public_address_b58,
+                ),
+                is_change: transaction_output_txo.is_change,
             }
         }
         JsonCommandRequest::get_transaction_logs {
             account_id,
             min_block_index,
             max_block_index,
+            recipient_public_address_b58,
+            token_id,
+            min_value,
+            max_value,
+            status,
+            order_by,
+            order,
             offset,
             limit,
+            include_timestamps,
         } => {
-            let min_block_index = min_block_index
-                .map(|i| i.parse::<u64>())
-                .transpose()
-                .map_err(format_error)?;
+            let parse_u64 = |value: Option<String>| {
+                value
+                    .map(|v| v.parse::<u64>())
+                    .transpose()
+                    .map_err(format_error)
+            };
+            let descending = match order.as_deref() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(other) => {
+                    return Err(format_invalid_request_error(format!(
+                        "order must be asc or desc, not {}",
+                        other
+                    )))
+                }
+            };
 
-            let max_block_index = max_block_index
-                .map(|i| i.parse::<u64>())
-                .transpose()
-                .map_err(format_error)?;
+            let filter = TransactionLogFilter {
+                recipient_public_address_b58,
+                token_id: parse_u64(token_id)?,
+                min_value: parse_u64(min_value)?,
+                max_value: parse_u64(max_value)?,
+                status: status
+                    .map(|s| s.parse())
+                    .transpose()
+                    .map_err(format_invalid_request_error)?,
+                min_block_index: parse_u64(min_block_index)?,
+                max_block_index: parse_u64(max_block_index)?,
+                order_by: order_by
+                    .map(|o| o.parse())
+                    .transpose()
+                    .map_err(format_invalid_request_error)?
+                    .unwrap_or_default(),
+                descending,
+            };
+
+            // Listing every log in the wallet at once can be very large.
+            if account_id.is_none() && limit.is_none() && !filter.is_filtered() {
+                return Err(format_invalid_request_error(
+                    "get_transaction_logs requires a limit, an account_id or a filter",
+                ));
+            }
 
             let transaction_logs_and_txos = service
-                .list_transaction_logs(account_id, offset, limit, min_block_index, max_block_index)
+                .list_transaction_logs(account_id, offset, limit, &filter)
                 .map_err(format_error)?;
 
             let transaction_log_map = TransactionLogMap(
                 transaction_logs_and_txos
                     .iter()
-                    .map(|(t, a, v)| (t.id.clone(), TransactionLog::new(t, a, v)))
-                    .collect(),
+                    .map(|(t, a, v)| {
+                        let mut transaction_log = TransactionLog::new(t, a, v);
+                        if include_timestamps.unwrap_or(false) {
+                            transaction_log.finalized_block_timestamp =
+                                block_timestamp(service, t.finalized_block_index)?;
+                        }
+                        Ok((t.id.clone(), transaction_log))
+                    })
+                    .collect::<Result<_, JsonRPCError>>()?,
             );
 
             JsonCommandResponse::get_transaction_logs {
@@ -664,10 +1390,45 @@ where
                 transaction_log_map,
             }
         }
-        JsonCommandRequest::get_txo { txo_id } => {
+        JsonCommandRequest::get_transaction_reports { account_id } => {
+            let transaction_reports = service
+                .list_undelivered_transaction_reports(account_id)
+                .map_err(format_error)?;
+            JsonCommandResponse::get_transaction_reports {
+                transaction_reports: transaction_reports
+                    .iter()
+                    .map(TransactionReport::from)
+                    .collect(),
+            }
+        }
+        JsonCommandRequest::get_transaction_ring_details { transaction_log_id } => {
+            let (transaction_log, input_rings) = service
+                .get_transaction_ring_details(&transaction_log_id)
+                .map_err(format_error)?;
+            JsonCommandResponse::get_transaction_ring_details {
+                transaction_ring_details: TransactionRingDetails::new(
+                    &transaction_log,
+                    &input_rings,
+                ),
+            }
+        }
+        JsonCommandRequest::get_txo { txo_id } => {
             let (txo, status) = service.get_txo(&TxoID(txo_id)).map_err(format_error)?;
-            JsonCommandResponse::get_txo {
-                txo: Txo::new(&txo, &status),
+            let num_blocks = service.ledger_db.num_blocks().map_err(format_error)?;
+            let mut json_txo = Txo::new(&txo, &status);
+            json_txo.num_confirmations = num_confirmations(num_blocks, txo.received_block_index);
+            JsonCommandResponse::get_txo { txo: json_txo }
+        }
+        JsonCommandRequest::get_txo_from_ledger { tx_out_public_key } => {
+            let ledger_tx_out = service
+                .get_txo_from_ledger(&tx_out_public_key)
+                .map_err(format_error)?;
+            let proto_txo = mc_api::external::TxOut::from(&ledger_tx_out.tx_out);
+            JsonCommandResponse::get_txo_from_ledger {
+                txo: JsonTxOut::from(&proto_txo),
+                txo_proto: hex::encode(mc_util_serial::encode(&ledger_tx_out.tx_out)),
+                block_index: ledger_tx_out.block_index.to_string(),
+                global_index: ledger_tx_out.global_index.to_string(),
             }
         }
         JsonCommandRequest::get_txos {
@@ -679,6 +1440,8 @@ where
             max_received_block_index,
             offset,
             limit,
+            include_timestamps,
+            global_index,
         } => {
             let status = match status {
                 Some(s) => Some(TxoStatus::from_str(&s).map_err(format_error)?),
@@ -690,24 +1453,68 @@ where
                 None => None,
             };
 
-            let txos_and_statuses = service
-                .list_txos(
-                    account_id,
-                    address,
-                    status,
-                    token_id,
-                    min_received_block_index,
-                    max_received_block_index,
-                    offset,
-                    limit,
-                )
-                .map_err(format_error)?;
+            let txos_and_statuses = match global_index {
+                Some(global_index) => {
+                    if address.is_some()
+                        || min_received_block_index.is_some()
+                        || max_received_block_index.is_some()
+                        || offset.is_some()
+                        || limit.is_some()
+                    {
+                        return Err(format_error(TxoServiceError::InvalidQuery(
+                            "global_index can only be combined with account_id, status and token_id"
+                                .to_string(),
+                        )));
+                    }
+                    let global_index = global_index.parse::<u64>().map_err(format_error)?;
+                    match service.get_txo_by_global_index(global_index) {
+                        Ok((txo, txo_status)) => {
+                            let matches = account_id
+                                .as_ref()
+                                .map_or(true, |a| txo.account_id.as_ref() == Some(a))
+                                && status.as_ref().map_or(true, |s| s == &txo_status)
+                                && token_id.map_or(true, |t| txo.token_id as u64 == t);
+                            if matches {
+                                vec![(txo, txo_status)]
+                            } else {
+                                vec![]
+                            }
+                        }
+                        Err(TxoServiceError::Database(WalletDbError::TxoNotFound(_))) => vec![],
+                        Err(e) => return Err(format_error(e)),
+                    }
+                }
+                None => service
+                    .list_txos(
+                        account_id,
+                        address,
+                        status,
+                        token_id,
+                        min_received_block_index,
+                        max_received_block_index,
+                        offset,
+                        limit,
+                    )
+                    .map_err(format_error)?,
+            };
+            let num_blocks = service.ledger_db.num_blocks().map_err(format_error)?;
 
             let txo_map = TxoMap(
                 txos_and_statuses
                     .iter()
-                    .map(|(t, s)| (t.id.clone(), Txo::new(t, s)))
-                    .collect(),
+                    .map(|(t, s)| {
+                        let mut txo = Txo::new(t, s);
+                        txo.num_confirmations =
+                            num_confirmations(num_blocks, t.received_block_index);
+                        if include_timestamps.unwrap_or(false) {
+                            txo.received_block_timestamp =
+                                block_timestamp(service, t.received_block_index)?;
+                            txo.spent_block_timestamp =
+                                block_timestamp(service, t.spent_block_index)?;
+                        }
+                        Ok((t.id.clone(), txo))
+                    })
+                    .collect::<Result<_, JsonRPCError>>()?,
             );
 
             JsonCommandResponse::get_txos {
@@ -718,6 +1525,39 @@ where
                 txo_map,
             }
         }
+        JsonCommandRequest::get_txos_modified_since {
+            account_id,
+            cursor,
+            limit,
+        } => {
+            let limit = match limit {
+                Some(l) => l.parse::<u64>().map_err(format_error)?,
+                None => DEFAULT_TXOS_MODIFIED_SINCE_LIMIT,
+            };
+            let (txos_and_statuses, next_cursor) = service
+                .list_txos_modified_since(&account_id, cursor.as_deref(), limit)
+                .map_err(format_error)?;
+            let num_blocks = service.ledger_db.num_blocks().map_err(format_error)?;
+
+            JsonCommandResponse::get_txos_modified_since {
+                txo_ids: txos_and_statuses
+                    .iter()
+                    .map(|(t, _)| t.id.clone())
+                    .collect(),
+                txo_map: TxoMap(
+                    txos_and_statuses
+                        .iter()
+                        .map(|(t, s)| {
+                            let mut txo = Txo::new(t, s);
+                            txo.num_confirmations =
+                                num_confirmations(num_blocks, t.received_block_index);
+                            (t.id.clone(), txo)
+                        })
+                        .collect(),
+                ),
+                next_cursor,
+            }
+        }
         JsonCommandRequest::get_txo_membership_proofs { outputs } => {
             let public_keys = outputs
                 .clone()
@@ -754,10 +1594,54 @@ where
         }
         JsonCommandRequest::get_wallet_status => JsonCommandResponse::get_wallet_status {
             wallet_status: WalletStatus::try_from(
-                &service.get_wallet_status().map_err(format_error)?,
+                &service
+                    .get_wallet_status(account_filter)
+                    .map_err(format_error)?,
             )
             .map_err(format_error)?,
         },
+        JsonCommandRequest::get_wallet_status_v2 {
+            include_accounts,
+            behind_threshold,
+        } => {
+            let behind_threshold = behind_threshold
+                .map(|b| b.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?
+                .unwrap_or(DEFAULT_ACCOUNT_BEHIND_THRESHOLD_BLOCKS);
+            let wallet_rollup = service
+                .get_wallet_rollup(
+                    include_accounts.unwrap_or(false),
+                    behind_threshold,
+                    account_filter,
+                )
+                .map_err(format_error)?;
+            JsonCommandResponse::get_wallet_status_v2 {
+                wallet_status: WalletRollup::from(&wallet_rollup),
+            }
+        }
+        JsonCommandRequest::identify_tx_out_owner {
+            tx_out_proto_hex,
+            tx_out_public_key_hex,
+        } => {
+            let owner = service
+                .identify_tx_out_owner(
+                    tx_out_proto_hex.as_deref(),
+                    tx_out_public_key_hex.as_deref(),
+                )
+                .map_err(format_error)?;
+            JsonCommandResponse::identify_tx_out_owner {
+                owned: owner.is_some(),
+                account_id: owner.as_ref().map(|owner| owner.account_id.to_string()),
+                subaddress_index: owner
+                    .as_ref()
+                    .and_then(|owner| owner.subaddress_index)
+                    .map(|index| index.to_string()),
+                amount: owner.as_ref().map(|owner| AmountJSON::from(&owner.amount)),
+                tracked: owner.as_ref().map_or(false, |owner| owner.txo_id.is_some()),
+                txo_id: owner.and_then(|owner| owner.txo_id),
+            }
+        }
         JsonCommandRequest::import_account {
             mnemonic,
             key_derivation_version,
@@ -765,6 +1649,7 @@ where
             first_block_index,
             next_subaddress_index,
             fog_info,
+            force,
         } => {
             let fb = first_block_index
                 .map(|fb| fb.parse::<u64>())
@@ -788,6 +1673,7 @@ where
                     fog_info.report_url,
                     fog_info.report_id,
                     fog_info.authority_spki,
+                    force.unwrap_or(false),
                 )
                 .map_err(format_error)?;
 
@@ -799,12 +1685,26 @@ where
 
             JsonCommandResponse::import_account { account }
         }
+        JsonCommandRequest::import_account_backup { backup, passphrase } => {
+            let account = service
+                .import_account_backup(&backup, passphrase)
+                .map_err(format_error)?;
+
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&AccountID(account.id.clone()))
+                .map_err(format_error)?;
+
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+
+            JsonCommandResponse::import_account_backup { account }
+        }
         JsonCommandRequest::import_account_from_legacy_root_entropy {
             entropy,
             name,
             first_block_index,
             next_subaddress_index,
             fog_info,
+            force,
         } => {
             let fb = first_block_index
                 .map(|fb| fb.parse::<u64>())
@@ -826,6 +1726,7 @@ where
                     fog_info.report_url,
                     fog_info.report_id,
                     fog_info.authority_spki,
+                    force.unwrap_or(false),
                 )
                 .map_err(format_error)?;
 
@@ -837,12 +1738,51 @@ where
 
             JsonCommandResponse::import_account { account }
         }
+        JsonCommandRequest::import_address_labels {
+            account_id,
+            address_labels,
+            create_missing,
+        } => {
+            let address_labels = address_labels
+                .iter()
+                .map(service::address::AddressLabel::try_from)
+                .collect::<Result<Vec<_>, String>>()
+                .map_err(format_error)?;
+            let import = service
+                .import_address_labels(
+                    &AccountID(account_id),
+                    &address_labels,
+                    create_missing.unwrap_or(false),
+                )
+                .map_err(format_error)?;
+            JsonCommandResponse::import_address_labels {
+                num_updated: import.num_updated.to_string(),
+                num_created: import.num_created.to_string(),
+                unassigned_indices: import
+                    .unassigned_indices
+                    .iter()
+                    .map(|index| index.to_string())
+                    .collect(),
+            }
+        }
+        JsonCommandRequest::import_txo {
+            account_id,
+            tx_out_public_key_hex,
+        } => {
+            let (txo, status) = service
+                .import_txo(&AccountID(account_id), &tx_out_public_key_hex)
+                .map_err(format_error)?;
+            JsonCommandResponse::import_txo {
+                txo: Txo::new(&txo, &status),
+            }
+        }
         JsonCommandRequest::import_view_only_account {
             view_private_key,
             spend_public_key,
             name,
             first_block_index,
             next_subaddress_index,
+            force,
         } => {
             let fb = first_block_index
                 .map(|fb| fb.parse::<u64>())
@@ -854,7 +1794,14 @@ where
                 .map_err(format_error)?;
 
             let account = service
-                .import_view_only_account(view_private_key, spend_public_key, name, fb, ns)
+                .import_view_only_account(
+                    view_private_key,
+                    spend_public_key,
+                    name,
+                    fb,
+                    ns,
+                    force.unwrap_or(false),
+                )
                 .map_err(format_error)?;
             let next_subaddress_index = service
                 .get_next_subaddress_index_for_account(&AccountID(account.id.clone()))
@@ -863,11 +1810,176 @@ where
 
             JsonCommandResponse::import_view_only_account { account }
         }
+        JsonCommandRequest::import_view_only_account_sync_response { sync_response } => {
+            let result = service
+                .import_view_only_account_sync_response(&sync_response)
+                .map_err(format_error)?;
+
+            JsonCommandResponse::import_view_only_account_sync_response {
+                num_txos_updated: result.num_txos_updated.to_string(),
+                spent_txo_ids: result.spent_txo_ids,
+            }
+        }
+        JsonCommandRequest::list_jobs => {
+            let jobs = service.list_jobs().map_err(format_error)?;
+            JsonCommandResponse::list_jobs {
+                jobs: jobs.iter().map(Job::from).collect(),
+            }
+        }
+        JsonCommandRequest::migrate_account_to_mnemonic { account_id } => {
+            let migration = service
+                .migrate_account_to_mnemonic(&AccountID(account_id))
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&AccountID(migration.new_account.id.clone()))
+                .map_err(format_error)?;
+            JsonCommandResponse::migrate_account_to_mnemonic {
+                new_account: Account::new(&migration.new_account, next_subaddress_index)
+                    .map_err(format_error)?,
+                transaction_log_ids: migration.transaction_log_ids,
+                fees: migration
+                    .fees
+                    .iter()
+                    .map(|(token_id, fee)| (token_id.to_string(), fee.to_string()))
+                    .collect(),
+                job: Job::from(&migration.job),
+            }
+        }
+        JsonCommandRequest::pin_peer { responder_id } => JsonCommandResponse::pin_peer {
+            peer_status: PeerStatus::from(
+                &service
+                    .pin_peer(responder_id.as_deref())
+                    .map_err(format_error)?,
+            ),
+        },
+        JsonCommandRequest::prepare_purge_account {
+            account_id,
+            confirm_account_name,
+        } => {
+            let summary = service
+                .describe_purge_account(&AccountID(account_id.clone()))
+                .map_err(format_error)?;
+            let challenge = service.confirmation_challenges.issue(
+                serde_json::json!(JsonCommandRequest::purge_account {
+                    account_id,
+                    confirm_account_name,
+                }),
+                summary,
+            );
+            JsonCommandResponse::prepare_purge_account {
+                confirmation: ConfirmationChallenge::from(&challenge),
+            }
+        }
+        JsonCommandRequest::prepare_remove_account { account_id } => {
+            let summary = service
+                .describe_remove_account(&AccountID(account_id.clone()))
+                .map_err(format_error)?;
+            let challenge = service.confirmation_challenges.issue(
+                serde_json::json!(JsonCommandRequest::remove_account { account_id }),
+                summary,
+            );
+            JsonCommandResponse::prepare_remove_account {
+                confirmation: ConfirmationChallenge::from(&challenge),
+            }
+        }
+        JsonCommandRequest::preview_transaction {
+            account_id,
+            addresses_and_amounts,
+            recipient_public_address,
+            recipient_public_keys,
+            amount,
+            fee_value,
+            fee_token_id,
+            tombstone_block,
+            max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
+        } => {
+            let mut addresses_and_amounts = addresses_and_amounts.unwrap_or_default();
+            let recipient_public_address =
+                recipient_b58(service, recipient_public_address, recipient_public_keys)?;
+            if let (Some(address), Some(amount)) = (recipient_public_address, amount) {
+                addresses_and_amounts.push((address, amount));
+            }
+            let transaction_preview = service
+                .preview_transaction(
+                    &account_id,
+                    &addresses_and_amounts,
+                    fee_value,
+                    fee_token_id,
+                    tombstone_block,
+                    max_spendable_value,
+                    source_subaddress_b58.as_deref(),
+                    change_to_source.unwrap_or(false),
+                )
+                .map_err(format_transaction_error)?;
+            JsonCommandResponse::preview_transaction {
+                transaction_preview: TransactionPreview::from(&transaction_preview),
+            }
+        }
+        JsonCommandRequest::purge_account {
+            account_id,
+            confirm_account_name,
+        } => JsonCommandResponse::purge_account {
+            purged: service
+                .purge_account(&AccountID(account_id), &confirm_account_name)
+                .map_err(format_error)?,
+        },
+        JsonCommandRequest::reconstruct_transaction_history { account_id } => {
+            let job = service
+                .reconstruct_transaction_history(&AccountID(account_id))
+                .map_err(format_error)?;
+            JsonCommandResponse::reconstruct_transaction_history {
+                job: Job::from(&job),
+            }
+        }
         JsonCommandRequest::remove_account { account_id } => JsonCommandResponse::remove_account {
             removed: service
                 .remove_account(&AccountID(account_id))
                 .map_err(format_error)?,
         },
+        JsonCommandRequest::rescan_account {
+            account_id,
+            from_block,
+        } => {
+            let from_block = from_block
+                .map(|fb| fb.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+            let job = service
+                .rescan_account(&AccountID(account_id), from_block)
+                .map_err(format_error)?;
+            JsonCommandResponse::rescan_account {
+                job: Job::from(&job),
+            }
+        }
+        JsonCommandRequest::restore_removed_account { account_id } => {
+            let account_id = AccountID(account_id);
+            let account = service
+                .restore_removed_account(&account_id)
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&account_id)
+                .map_err(format_error)?;
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+            JsonCommandResponse::restore_removed_account { account }
+        }
+        JsonCommandRequest::resync_account { account_id } => {
+            let job = service
+                .resync_account(&AccountID(account_id))
+                .map_err(format_error)?;
+            JsonCommandResponse::resync_account {
+                job: Job::from(&job),
+            }
+        }
+        JsonCommandRequest::retry_transaction_reports => {
+            let (num_delivered, num_failed) =
+                service.retry_transaction_reports().map_err(format_error)?;
+            JsonCommandResponse::retry_transaction_reports {
+                num_delivered: num_delivered.to_string(),
+                num_failed: num_failed.to_string(),
+            }
+        }
         JsonCommandRequest::sample_mixins {
             num_mixins,
             excluded_outputs,
@@ -915,20 +2027,82 @@ where
                 membership_proofs,
             }
         }
+        JsonCommandRequest::scan_ledger_with_view_key {
+            view_private_key,
+            spend_public_key,
+            first_block_index,
+            last_block_index,
+            first_subaddress_index,
+            num_subaddresses,
+        } => {
+            let first_block_index = first_block_index.parse::<u64>().map_err(format_error)?;
+            let last_block_index = last_block_index.parse::<u64>().map_err(format_error)?;
+            let first_subaddress_index = first_subaddress_index
+                .map(|i| i.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?
+                .unwrap_or(0);
+            let num_subaddresses = num_subaddresses
+                .map(|n| n.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?
+                .unwrap_or(DEFAULT_NEXT_SUBADDRESS_INDEX);
+
+            let ledger_scan = service
+                .scan_ledger_with_view_key(
+                    &view_private_key,
+                    &spend_public_key,
+                    first_block_index,
+                    last_block_index,
+                    first_subaddress_index..first_subaddress_index.saturating_add(num_subaddresses),
+                )
+                .map_err(format_error)?;
+            JsonCommandResponse::scan_ledger_with_view_key {
+                ledger_scan: LedgerScan::from(&ledger_scan),
+            }
+        }
         JsonCommandRequest::submit_transaction {
             tx_proposal,
             comment,
             account_id,
+            idempotency_key,
+            dry_run,
+            force_resubmit,
         } => {
             let tx_proposal = TxProposal::try_from(&tx_proposal).map_err(format_error)?;
-            let result: Option<TransactionLog> = service
-                .submit_transaction(&tx_proposal, comment, account_id)
-                .map_err(format_error)?
-                .map(|(transaction_log, associated_txos, value_map)| {
-                    TransactionLog::new(&transaction_log, &associated_txos, &value_map)
-                });
-            JsonCommandResponse::submit_transaction {
-                transaction_log: result,
+            if dry_run.unwrap_or(false) {
+                if idempotency_key.is_some() {
+                    return Err(format_invalid_request_error(
+                        "An idempotency key cannot be used with a dry run",
+                    ));
+                }
+                let block_count = service
+                    .propose_tx_dry_run(&tx_proposal)
+                    .map_err(format_transaction_error)?;
+                JsonCommandResponse::submit_transaction {
+                    transaction_log: None,
+                    dry_run_block_count: Some(block_count.to_string()),
+                    submit_outcome: None,
+                }
+            } else {
+                let result = service
+                    .submit_transaction(
+                        &tx_proposal,
+                        comment,
+                        account_id,
+                        idempotency_key,
+                        force_resubmit.unwrap_or(false),
+                    )
+                    .map_err(format_transaction_error)?;
+                JsonCommandResponse::submit_transaction {
+                    transaction_log: result.as_ref().map(
+                        |(transaction_log, associated_txos, value_map, _outcome)| {
+                            TransactionLog::new(transaction_log, associated_txos, value_map)
+                        },
+                    ),
+                    dry_run_block_count: None,
+                    submit_outcome: result.map(|(_, _, _, outcome)| outcome.to_string()),
+                }
             }
         }
         JsonCommandRequest::sync_view_only_account {
@@ -946,6 +2120,14 @@ where
 
             JsonCommandResponse::sync_view_only_account
         }
+        JsonCommandRequest::test_webhook => {
+            let ping = service
+                .ping_transaction_report_sink()
+                .map_err(format_error)?;
+            JsonCommandResponse::test_webhook {
+                event_id: ping.event_id,
+            }
+        }
         JsonCommandRequest::update_account_name { account_id, name } => {
             let account_id = AccountID(account_id);
             let account = service
@@ -957,6 +2139,81 @@ where
             let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
             JsonCommandResponse::update_account_name { account }
         }
+        JsonCommandRequest::update_account_min_confirmations {
+            account_id,
+            min_confirmations,
+        } => {
+            let account_id = AccountID(account_id);
+            let min_confirmations = min_confirmations
+                .map(|m| m.parse::<u64>())
+                .transpose()
+                .map_err(format_error)?;
+            let account = service
+                .update_account_min_confirmations(&account_id, min_confirmations)
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&account_id)
+                .map_err(format_error)?;
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+            JsonCommandResponse::update_account_min_confirmations { account }
+        }
+        JsonCommandRequest::update_account_split_change_into {
+            account_id,
+            split_change_into,
+        } => {
+            let account_id = AccountID(account_id);
+            let split_change_into = split_change_into.parse::<u64>().map_err(format_error)?;
+            let account = service
+                .update_account_split_change_into(&account_id, split_change_into)
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&account_id)
+                .map_err(format_error)?;
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+            JsonCommandResponse::update_account_split_change_into { account }
+        }
+        JsonCommandRequest::update_account_subaddress_gap_limit {
+            account_id,
+            subaddress_gap_limit,
+        } => {
+            let account_id = AccountID(account_id);
+            let subaddress_gap_limit = subaddress_gap_limit.parse::<u64>().map_err(format_error)?;
+            let account = service
+                .update_account_subaddress_gap_limit(&account_id, subaddress_gap_limit)
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&account_id)
+                .map_err(format_error)?;
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+            JsonCommandResponse::update_account_subaddress_gap_limit { account }
+        }
+        JsonCommandRequest::update_account_transaction_defaults {
+            account_id,
+            default_fee_value,
+            default_fee_token_id,
+            default_tombstone_offset,
+        } => {
+            let account_id = AccountID(account_id);
+            let parse = |value: Option<String>| {
+                value
+                    .map(|v| v.parse::<u64>())
+                    .transpose()
+                    .map_err(format_error)
+            };
+            let account = service
+                .update_account_transaction_defaults(
+                    &account_id,
+                    parse(default_fee_value)?,
+                    parse(default_fee_token_id)?,
+                    parse(default_tombstone_offset)?,
+                )
+                .map_err(format_error)?;
+            let next_subaddress_index = service
+                .get_next_subaddress_index_for_account(&account_id)
+                .map_err(format_error)?;
+            let account = Account::new(&account, next_subaddress_index).map_err(format_error)?;
+            JsonCommandResponse::update_account_transaction_defaults { account }
+        }
         JsonCommandRequest::validate_confirmation {
             account_id,
             txo_id,
@@ -967,9 +2224,58 @@ where
                 .map_err(format_error)?;
             JsonCommandResponse::validate_confirmation { validated: result }
         }
-        JsonCommandRequest::verify_address { address } => JsonCommandResponse::verify_address {
-            verified: service.verify_address(&address).map_err(format_error)?,
-        },
+        JsonCommandRequest::verify_address { address } => {
+            let verified = service.verify_address(&address).map_err(format_error)?;
+            let network_check = if verified {
+                Some(AddressNetworkCheck::from(
+                    &service
+                        .check_address_network(&address)
+                        .map_err(format_error)?,
+                ))
+            } else {
+                None
+            };
+            JsonCommandResponse::verify_address {
+                verified,
+                network_check,
+            }
+        }
+        JsonCommandRequest::verify_address_attestation { attestation } => {
+            let (address_attestation, stale) = service
+                .verify_address_attestation(&attestation)
+                .map_err(format_error)?;
+            JsonCommandResponse::verify_address_attestation {
+                address_attestation: AddressAttestation::new(&address_attestation, stale),
+            }
+        }
+        JsonCommandRequest::verify_ledger_integrity { sample_size } => {
+            let sample_size = sample_size.parse::<usize>().map_err(format_error)?;
+            let (num_checked, invalid_indices) = service
+                .verify_ledger_integrity(sample_size)
+                .map_err(format_error)?;
+            JsonCommandResponse::verify_ledger_integrity {
+                num_checked: num_checked.to_string(),
+                invalid_indices: invalid_indices.iter().map(|i| i.to_string()).collect(),
+            }
+        }
+        JsonCommandRequest::verify_proof_of_reserves {
+            proof_of_reserves,
+            view_private_key,
+        } => {
+            let proof_of_reserves =
+                service::proof_of_reserves::ProofOfReserves::try_from(&proof_of_reserves)
+                    .map_err(format_error)?;
+            let view_private_key = view_private_key
+                .map(|key| hex_to_ristretto(&key))
+                .transpose()
+                .map_err(format_error)?;
+            let verification = service
+                .verify_proof_of_reserves(&proof_of_reserves, view_private_key.as_ref())
+                .map_err(format_error)?;
+            JsonCommandResponse::verify_proof_of_reserves {
+                verification: ReservesVerification::from(&verification),
+            }
+        }
         JsonCommandRequest::version => JsonCommandResponse::version {
             string: env!("CARGO_PKG_VERSION").to_string(),
             number: (
@@ -984,3 +2290,141 @@ where
 
     Ok(response)
 }
+
+/// Looks up the timestamp of an optional block, formatted for the API.
+fn block_timestamp<T, FPR>(
+    service: &WalletService<T, FPR>,
+    block_index: Option<i64>,
+) -> Result<Option<String>, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    match block_index {
+        Some(block_index) => Ok(service
+            .get_block_timestamp(block_index as u64)
+            .map_err(format_error)?
+            .map(|timestamp| timestamp.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// The number of blocks in a ledger of num_blocks blocks from the block in
+/// which a txo was received, counting that block.
+fn num_confirmations(num_blocks: u64, received_block_index: Option<i64>) -> Option<String> {
+    received_block_index.map(|index| num_blocks.saturating_sub(index as u64).to_string())
+}
+
+/// Formats a proposal for the API, logging a warning when it is large enough
+/// that clients may not be able to submit it back.
+fn tx_proposal_json<T, FPR>(
+    service: &WalletService<T, FPR>,
+    tx_proposal: &TxProposal,
+) -> Result<TxProposalJSON, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let tx_proposal_json = TxProposalJSON::try_from(tx_proposal).map_err(format_error)?;
+    let estimated_size = tx_proposal_json.estimated_size();
+    if estimated_size > TX_PROPOSAL_SIZE_WARNING_BYTES {
+        log::warn!(
+            service.request_logger(),
+            "TxProposal with {} inputs is about {} bytes, more than the {} byte warning threshold",
+            tx_proposal.input_txos.len(),
+            estimated_size,
+            TX_PROPOSAL_SIZE_WARNING_BYTES
+        );
+    }
+    Ok(tx_proposal_json)
+}
+
+/// Creates the receiver receipts for the payload outputs of a proposal,
+/// formatted for the API.
+fn receiver_receipts_json<T, FPR>(
+    service: &WalletService<T, FPR>,
+    tx_proposal: &TxProposal,
+) -> Result<Vec<ReceiverReceipt>, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    service
+        .create_receiver_receipts(tx_proposal)
+        .map_err(format_error)?
+        .iter()
+        .map(ReceiverReceipt::try_from)
+        .collect::<Result<Vec<ReceiverReceipt>, String>>()
+        .map_err(format_error)
+}
+
+/// Resolves the single recipient of a build request to a b58 address, whether
+/// it was given as a b58 public address or as hex encoded public keys.
+fn recipient_b58<T, FPR>(
+    service: &WalletService<T, FPR>,
+    recipient_public_address: Option<String>,
+    recipient_public_keys: Option<PublicAddressKeys>,
+) -> Result<Option<String>, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    match (recipient_public_address, recipient_public_keys) {
+        (Some(_), Some(_)) => Err(format_error(
+            "Cannot specify both recipient_public_address and recipient_public_keys",
+        )),
+        (Some(address), None) => Ok(Some(address)),
+        (None, Some(keys)) => {
+            let public_address = service
+                .public_address_from_keys(
+                    &keys.view_public_hex,
+                    &keys.spend_public_hex,
+                    keys.fog_report_url.as_deref(),
+                    keys.fog_authority_sig_hex.as_deref(),
+                )
+                .map_err(format_error)?;
+            Ok(Some(
+                b58_encode_public_address(&public_address).map_err(format_error)?,
+            ))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// The memo for a build request, with the payment request id when one was
+/// given.
+/// The inputs to build from: the txo ids given, or those of a selection token
+/// from preview_transaction.
+fn input_txo_ids_for_build<T, FPR>(
+    service: &WalletService<T, FPR>,
+    account_id: &str,
+    input_txo_ids: Option<Vec<String>>,
+    selection_token: Option<String>,
+) -> Result<Option<Vec<String>>, JsonRPCError>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    match (input_txo_ids, selection_token) {
+        (Some(_), Some(_)) => Err(format_invalid_request_error(
+            "input_txo_ids cannot be combined with selection_token",
+        )),
+        (input_txo_ids, None) => Ok(input_txo_ids),
+        (None, Some(selection_token)) => Ok(Some(
+            service
+                .resolve_selection_token(account_id, &selection_token)
+                .map_err(format_transaction_error)?,
+        )),
+    }
+}
+
+fn transaction_memo(payment_request_id: Option<String>) -> Result<TransactionMemo, JsonRPCError> {
+    match payment_request_id {
+        Some(payment_request_id) => Ok(TransactionMemo::RTHWithPaymentRequestId(
+            payment_request_id
+                .parse::<u64>()
+                .map_err(format_invalid_request_error)?,
+        )),
+        None => Ok(TransactionMemo::RTH),
+    }
+}
@@ -6,7 +6,7 @@
 mod e2e_account {
     use crate::{
         db::{account::AccountID, txo::TxoStatus},
-        json_rpc::v2::api::test_utils::{dispatch, setup},
+        json_rpc::v2::api::test_utils::{dispatch, dispatch_confirmed, setup},
         test_utils::{add_block_to_ledger_db, manually_sync_account},
         util::b58::b58_decode_public_address,
     };
@@ -94,7 +94,7 @@ mod e2e_account {
                 "account_id": account_id,
             }
         });
-        dispatch(&client, body, &logger);
+        dispatch_confirmed(&client, body, &logger);
 
         let body = json!({
             "jsonrpc": "2.0",
@@ -171,7 +171,7 @@ mod e2e_account {
                 "account_id": account_id,
             }
         });
-        dispatch(&client, body, &logger);
+        dispatch_confirmed(&client, body, &logger);
 
         let body = json!({
             "jsonrpc": "2.0",
@@ -487,6 +487,7 @@ mod e2e_account {
         let res = dispatch(&client, body, &logger);
         let result = res["result"]["verified"].as_bool().unwrap();
         assert!(!result);
+        assert!(res["result"]["network_check"].is_null());
 
         // Add an account
         let body = json!({
@@ -511,5 +512,11 @@ mod e2e_account {
         let res = dispatch(&client, body, &logger);
         let result = res["result"]["verified"].as_bool().unwrap();
         assert!(result);
+
+        // Addresses without fog cannot be checked against the network, so they
+        // pass with a warning.
+        let network_check = &res["result"]["network_check"];
+        assert!(network_check["passed"].as_bool().unwrap());
+        assert!(network_check["warning"].as_bool().unwrap());
     }
 }
@@ -536,6 +536,75 @@ mod e2e_account {
         assert!(result);
     }
 
+    #[test_with_logger]
+    fn test_address_attestation(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let b58_public_address = res["result"]["account"]["main_address"].as_str().unwrap();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_address_attestation",
+            "params": {
+                "public_address_b58": b58_public_address,
+                "nonce": "ticket-4812",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let attestation = res["result"]["attestation"].as_str().unwrap();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "verify_address_attestation",
+            "params": {
+                "attestation": attestation,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let address_attestation = &res["result"]["address_attestation"];
+        assert_eq!(
+            address_attestation["public_address_b58"],
+            b58_public_address
+        );
+        assert_eq!(address_attestation["nonce"], "ticket-4812");
+        assert_eq!(address_attestation["stale"], false);
+
+        // An attestation whose signature was tampered with does not verify.
+        let mut tampered = attestation.to_string();
+        let index = tampered.len() - 10;
+        let replacement = if &tampered[index..index + 1] == "A" {
+            "B"
+        } else {
+            "A"
+        };
+        tampered.replace_range(index..index + 1, replacement);
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "verify_address_attestation",
+            "params": {
+                "attestation": tampered,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert!(res["error"]["data"]["details"]
+            .as_str()
+            .unwrap()
+            .contains("signature"));
+    }
+
     #[test_with_logger]
     fn test_balance_for_address(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -4,7 +4,7 @@
 
 #[cfg(test)]
 mod e2e_account {
-    use crate::json_rpc::v2::api::test_utils::{dispatch, setup};
+    use crate::json_rpc::v2::api::test_utils::{dispatch, dispatch_confirmed, setup};
 
     use mc_common::logger::{test_with_logger, Logger};
 
@@ -113,7 +113,7 @@ mod e2e_account {
                 "account_id": *account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true,);
 
@@ -129,6 +129,99 @@ mod e2e_account {
         assert_eq!(accounts.len(), 0);
     }
 
+    #[test_with_logger]
+    fn test_e2e_remove_account_requires_confirmation(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let mut account_ids = Vec::new();
+        for name in ["Alice", "Bob"] {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "create_account",
+                "params": {
+                    "name": name,
+                },
+            });
+            let res = dispatch(&client, body, &logger);
+            account_ids.push(res["result"]["account"]["id"].as_str().unwrap().to_string());
+        }
+
+        // Without a challenge, the account is not removed.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "remove_account",
+            "params": {
+                "account_id": account_ids[0],
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(res["error"]["code"], -32002);
+        assert_eq!(res["error"]["data"]["reason"], "missing");
+
+        // Preparing the request returns a challenge and what it will do.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "prepare_remove_account",
+            "params": {
+                "account_id": account_ids[0],
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let confirmation = &res["result"]["confirmation"];
+        assert_eq!(confirmation["method"], "remove_account");
+        assert!(confirmation["summary"]
+            .as_str()
+            .unwrap()
+            .contains(&account_ids[0]));
+        let challenge = confirmation["challenge"].as_str().unwrap().to_string();
+
+        // The challenge does not confirm removing a different account.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "remove_account",
+            "params": {
+                "account_id": account_ids[1],
+                "confirmation": { "challenge": challenge },
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(res["error"]["code"], -32002);
+        assert_eq!(res["error"]["data"]["reason"], "mismatch");
+
+        // It confirms the request it was prepared for, once.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 5,
+            "method": "remove_account",
+            "params": {
+                "account_id": account_ids[0],
+                "confirmation": { "challenge": challenge },
+            }
+        });
+        let res = dispatch(&client, body.clone(), &logger);
+        assert_eq!(res["result"]["removed"], true);
+
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(res["error"]["code"], -32002);
+        assert_eq!(res["error"]["data"]["reason"], "not_found");
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 6,
+            "method": "get_accounts",
+            "params": {}
+        });
+        let res = dispatch(&client, body, &logger);
+        let accounts = res["result"]["account_ids"].as_array().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0], account_ids[1]);
+    }
+
     #[test_with_logger]
     fn test_e2e_create_account_with_fog(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -159,5 +252,31 @@ mod e2e_account {
         assert!(account_obj.get("main_address").is_some());
         assert_eq!(account_obj.get("next_subaddress_index").unwrap(), "1");
         assert_eq!(account_obj.get("fog_enabled").unwrap(), true);
+
+        let fog_info = account_obj.get("fog_info").unwrap();
+        assert_eq!(
+            fog_info.get("report_url").unwrap(),
+            "fog://fog-report.example.com"
+        );
+        assert_eq!(fog_info.get("report_id").unwrap(), "");
+        assert_eq!(fog_info.get("authority_spki").unwrap(), "MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEAvnB9wTbTOT5uoizRYaYbw7XIEkInl8E7MGOAQj+xnC+F1rIXiCnc/t1+5IIWjbRGhWzo7RAwI5sRajn2sT4rRn9NXbOzZMvIqE4hmhmEzy1YQNDnfALAWNQ+WBbYGW+Vqm3IlQvAFFjVN1YYIdYhbLjAPdkgeVsWfcLDforHn6rR3QBZYZIlSBQSKRMY/tywTxeTCvK2zWcS0kbbFPtBcVth7VFFVPAZXhPi9yy1AvnldO6n7KLiupVmojlEMtv4FQkk604nal+j/dOplTATV8a9AJBbPRBZ/yQg57EG2Y2MRiHOQifJx0S5VbNyMm9bkS8TD7Goi59aCW6OT1gyeotWwLg60JRZTfyJ7lYWBSOzh0OnaCytRpSWtNZ6barPUeOnftbnJtE8rFhF7M4F66et0LI/cuvXYecwVwykovEVBKRF4HOK9GgSm17mQMtzrD7c558TbaucOWabYR04uhdAc3s10MkuONWG0wIQhgIChYVAGnFLvSpp2/aQEq3xrRSETxsixUIjsZyWWROkuA0IFnc8d7AmcnUBvRW7FT/5thWyk5agdYUGZ+7C1o69ihR1YxmoGh69fLMPIEOhYh572+3ckgl2SaV4uo9Gvkz8MMGRBcMIMlRirSwhCfozV2RyT5Wn1NgPpyc8zJL7QdOhL7Qxb+5WjnCVrQYHI2cCAwEAAQ==");
+
+        // An authority spki which does not parse is refused.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Bob Main Account",
+                "fog_info": {
+                    "report_url": "fog://fog-report.example.com",
+                    "report_id": "",
+                    "authority_spki": base64::encode(b"not a subject public key info"),
+                }
+            },
+        });
+        let res = dispatch(&client, body, &logger);
+        let details = res["error"]["data"]["details"].as_str().unwrap();
+        assert!(details.contains("Invalid fog info"));
     }
 }
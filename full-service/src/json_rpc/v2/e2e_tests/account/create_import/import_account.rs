@@ -6,7 +6,9 @@
 mod e2e_account {
     use crate::{
         db::account::AccountID,
-        json_rpc::v2::api::test_utils::{dispatch, dispatch_expect_error, setup},
+        json_rpc::v2::api::test_utils::{
+            dispatch, dispatch_confirmed, dispatch_expect_error, setup,
+        },
         test_utils::{add_block_to_ledger_db, manually_sync_account},
         util::b58::b58_decode_public_address,
     };
@@ -233,7 +235,7 @@ mod e2e_account {
                 "account_id": *account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true);
 
@@ -332,7 +334,7 @@ mod e2e_account {
                 "account_id": account_id,
             }
         });
-        dispatch(&client, body, &logger);
+        dispatch_confirmed(&client, body, &logger);
 
         let body = json!({
             "jsonrpc": "2.0",
@@ -409,7 +411,7 @@ mod e2e_account {
                 "account_id": account_id,
             }
         });
-        dispatch(&client, body, &logger);
+        dispatch_confirmed(&client, body, &logger);
 
         let body = json!({
             "jsonrpc": "2.0",
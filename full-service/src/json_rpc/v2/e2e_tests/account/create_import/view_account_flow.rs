@@ -6,7 +6,7 @@
 mod e2e_account {
     use crate::{
         db::account::AccountID,
-        json_rpc::v2::api::test_utils::{dispatch, setup},
+        json_rpc::v2::api::test_utils::{dispatch, dispatch_confirmed, setup},
         test_utils::{add_block_to_ledger_db, manually_sync_account, MOB},
         util::b58::b58_decode_public_address,
     };
@@ -97,7 +97,7 @@ mod e2e_account {
                 "account_id": account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true);
 
@@ -198,7 +198,7 @@ mod e2e_account {
                 "account_id": vo_account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         let removed = result.get("removed").unwrap().as_bool().unwrap();
         assert!(removed);
@@ -4,17 +4,67 @@
 
 #[cfg(test)]
 mod e2e_misc {
-    use crate::json_rpc::v2::api::test_utils::{
-        dispatch, dispatch_with_header, dispatch_with_header_expect_error, setup,
-        setup_with_api_key,
+    use crate::{
+        db::account::AccountID,
+        json_rpc::{
+            capability::CapabilitySet,
+            scoped_api_key::ScopedApiKey,
+            v2::api::test_utils::{
+                dispatch, dispatch_with_header, dispatch_with_header_expect_error, setup,
+                setup_with_api_key, setup_with_listener_capabilities, setup_with_peer_ledger,
+                setup_with_scoped_api_keys, setup_with_secrets_api_key, BASE_TEST_BLOCK_HEIGHT,
+            },
+        },
+        service::{account::AccountService, health::HealthConfig},
+        test_utils::{add_block_to_ledger_db, get_test_ledger, manually_sync_account, MOB},
+        util::{
+            b58::{b58_decode_public_address, b58_encode_public_address},
+            constants::DEFAULT_MAX_BATCH_SIZE,
+            sender_memo::{compute_address_hash, MEMO_PAYLOAD_LEN},
+        },
     };
 
+    use mc_account_keys::AccountKey;
     use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
 
-    use mc_transaction_core::{tokens::Mob, BlockVersion, Token};
+    use mc_transaction_core::{
+        constants::RING_SIZE, ring_signature::KeyImage, tokens::Mob, BlockVersion, Token,
+    };
 
     use rand::{rngs::StdRng, SeedableRng};
-    use rocket::http::{Header, Status};
+    use rocket::http::{ContentType, Header, Status};
+    use slog::{Drain, Key, OwnedKVList, Record, Serializer, KV};
+    use std::{
+        fmt::Arguments,
+        sync::{Arc, Mutex},
+    };
+
+    /// Drain which records every log line, along with its key-value pairs, so
+    /// that tests can assert on the logging context.
+    struct CaptureDrain(Arc<Mutex<Vec<String>>>);
+
+    struct CaptureSerializer(String);
+
+    impl Serializer for CaptureSerializer {
+        fn emit_arguments(&mut self, key: Key, val: &Arguments) -> slog::Result {
+            self.0.push_str(&format!(" {}={}", key, val));
+            Ok(())
+        }
+    }
+
+    impl Drain for CaptureDrain {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(&self, record: &Record, values: &OwnedKVList) -> Result<(), slog::Never> {
+            let mut serializer = CaptureSerializer(format!("{}", record.msg()));
+            record.kv().serialize(record, &mut serializer).unwrap();
+            values.serialize(record, &mut serializer).unwrap();
+            self.0.lock().unwrap().push(serializer.0);
+            Ok(())
+        }
+    }
 
     #[test_with_logger]
     fn test_wallet_status(logger: Logger) {
@@ -47,6 +97,7 @@ mod e2e_misc {
         let balance_per_token = status.get("balance_per_token").unwrap();
         let balance_mob = balance_per_token.get(Mob::ID.to_string());
         assert!(balance_mob.is_none());
+        assert_eq!(status.get("unsigned_tx_schema_version").unwrap(), "4");
     }
 
     #[test_with_logger]
@@ -93,6 +144,430 @@ mod e2e_misc {
         dispatch_with_header_expect_error(&client, body, header, &logger, Status::Unauthorized);
     }
 
+    #[test_with_logger]
+    fn test_bad_api_key_is_reported_as_json_rpc_error(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) =
+            setup_with_api_key(&mut rng, logger.clone(), "mobilecats".to_string());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_accounts",
+        });
+        let mut res = client
+            .post("/wallet/v2")
+            .header(ContentType::JSON)
+            .header(Header::new("X-API-KEY", "wrong-header"))
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let response: serde_json::Value =
+            serde_json::from_str(&res.body_string().unwrap()).unwrap();
+        assert_eq!(response["jsonrpc"], "2.0");
+        assert_eq!(response["error"]["code"], -32001);
+        assert_eq!(response["error"]["message"], "Unauthorized");
+        assert!(response.get("result").is_none());
+    }
+
+    fn create_account_and_export_secrets(
+        client: &rocket::local::Client,
+        api_key: &str,
+        secrets_api_key: Option<&str>,
+        logger: &Logger,
+    ) -> serde_json::Value {
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            },
+        });
+        let res = dispatch_with_header(
+            client,
+            body,
+            Header::new("X-API-KEY", api_key.to_string()),
+            logger,
+        );
+        let account_id = res["result"]["account"]["id"].as_str().unwrap().to_string();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "export_account_secrets",
+            "params": {
+                "account_id": account_id,
+            },
+        });
+        let mut request = client
+            .post("/wallet/v2")
+            .header(ContentType::JSON)
+            .header(Header::new("X-API-KEY", api_key.to_string()));
+        if let Some(secrets_api_key) = secrets_api_key {
+            request = request.header(Header::new(
+                "X-SECRETS-API-KEY",
+                secrets_api_key.to_string(),
+            ));
+        }
+        let mut res = request.body(body.to_string()).dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        serde_json::from_str(&res.body_string().unwrap()).unwrap()
+    }
+
+    #[test_with_logger]
+    fn test_secrets_methods_without_secrets_api_key_configured(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let res = create_account_and_export_secrets(&client, "", None, &logger);
+        assert!(res.get("error").is_none());
+        assert!(res["result"]["account_secrets"]["mnemonic"].is_string());
+    }
+
+    #[test_with_logger]
+    fn test_secrets_methods_require_secrets_api_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup_with_secrets_api_key(
+            &mut rng,
+            logger.clone(),
+            "mobilecats".to_string(),
+            "mobilesecrets".to_string(),
+        );
+
+        // The general API key is not enough for a method which exposes secrets.
+        let res = create_account_and_export_secrets(&client, "mobilecats", None, &logger);
+        assert!(res.get("result").is_none());
+        assert_eq!(res["error"]["code"], -32001);
+        assert_eq!(res["id"], 2);
+
+        let res =
+            create_account_and_export_secrets(&client, "mobilecats", Some("wrong-secret"), &logger);
+        assert_eq!(res["error"]["code"], -32001);
+
+        let res = create_account_and_export_secrets(
+            &client,
+            "mobilecats",
+            Some("mobilesecrets"),
+            &logger,
+        );
+        assert!(res.get("error").is_none());
+        assert!(res["result"]["account_secrets"]["mnemonic"].is_string());
+    }
+
+    #[test_with_logger]
+    fn test_transaction_ring_details_require_secrets_api_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup_with_secrets_api_key(
+            &mut rng,
+            logger.clone(),
+            "mobilecats".to_string(),
+            "mobilesecrets".to_string(),
+        );
+        let api_key = Header::new("X-API-KEY", "mobilecats");
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            },
+        });
+        let res = dispatch_with_header(&client, body, api_key.clone(), &logger);
+        let account_id = res["result"]["account"]["id"].as_str().unwrap().to_string();
+        let b58_public_address = res["result"]["account"]["main_address"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![b58_decode_public_address(&b58_public_address).unwrap()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.clone()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "build_and_submit_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": (42 * MOB).to_string(), "token_id": "0" },
+            },
+        });
+        let res = dispatch_with_header(&client, body, api_key.clone(), &logger);
+        let transaction_log_id = res["result"]["transaction_log"]["id"]
+            .as_str()
+            .unwrap()
+            .to_string();
+
+        // Which ring member was real is private to the wallet.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "get_transaction_ring_details",
+            "params": {
+                "transaction_log_id": transaction_log_id,
+            },
+        });
+        let res = dispatch_with_header(&client, body.clone(), api_key.clone(), &logger);
+        assert!(res.get("result").is_none());
+        assert_eq!(res["error"]["code"], -32001);
+
+        let mut res = client
+            .post("/wallet/v2")
+            .header(ContentType::JSON)
+            .header(api_key)
+            .header(Header::new("X-SECRETS-API-KEY", "mobilesecrets"))
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(res.status(), Status::Ok);
+        let res: serde_json::Value = serde_json::from_str(&res.body_string().unwrap()).unwrap();
+        let details = &res["result"]["transaction_ring_details"];
+        assert_eq!(details["transaction_log_id"], transaction_log_id.as_str());
+        let inputs = details["inputs"].as_array().unwrap();
+        assert_eq!(inputs.len(), 1);
+        let ring = inputs[0]["ring"].as_array().unwrap();
+        assert_eq!(ring.len(), RING_SIZE);
+        let real: Vec<&serde_json::Value> = ring
+            .iter()
+            .filter(|member| member["is_real"].as_bool().unwrap())
+            .collect();
+        assert_eq!(real.len(), 1);
+        assert_eq!(real[0]["index"], inputs[0]["real_index"]);
+    }
+
+    #[test_with_logger]
+    fn test_listener_capabilities(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let payments_capabilities: CapabilitySet = "read,build,submit".parse().unwrap();
+        let (clients, _ledger_db, _db_ctx, _network_state) = setup_with_listener_capabilities(
+            &mut rng,
+            logger.clone(),
+            vec![CapabilitySet::all(), payments_capabilities],
+        );
+        let admin_client = &clients[0];
+        let payments_client = &clients[1];
+
+        let create_account = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            },
+        });
+
+        // Account management is not permitted on the payments listener.
+        let res = dispatch(payments_client, create_account.clone(), &logger);
+        assert!(res.get("result").is_none());
+        assert_eq!(res["error"]["code"], -32003);
+        assert_eq!(res["error"]["data"]["required_capability"], "admin");
+        assert_eq!(
+            res["error"]["data"]["listener_capabilities"],
+            json!(["read", "build", "submit"])
+        );
+
+        let res = dispatch(admin_client, create_account, &logger);
+        let account_id = res["result"]["account"]["id"].as_str().unwrap().to_string();
+
+        // Both listeners serve the same wallet.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "get_account_status",
+            "params": {
+                "account_id": account_id,
+            },
+        });
+        let res = dispatch(payments_client, body, &logger);
+        assert!(res.get("error").is_none());
+        assert_eq!(res["result"]["account"]["id"], account_id.as_str());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 3,
+            "method": "assign_address_for_account",
+            "params": {
+                "account_id": account_id,
+            },
+        });
+        let res = dispatch(payments_client, body, &logger);
+        assert!(res.get("error").is_none());
+
+        // Methods which expose secrets are admin methods, even without a secrets
+        // API key configured.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 4,
+            "method": "export_account_secrets",
+            "params": {
+                "account_id": account_id,
+            },
+        });
+        let res = dispatch(payments_client, body.clone(), &logger);
+        assert_eq!(res["error"]["code"], -32003);
+        let res = dispatch(admin_client, body, &logger);
+        assert!(res["result"]["account_secrets"]["mnemonic"].is_string());
+    }
+
+    #[test_with_logger]
+    fn test_scoped_api_keys(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let mut accounts = Vec::new();
+        let (client, mut ledger_db, db_ctx, _network_state) =
+            setup_with_scoped_api_keys(&mut rng, logger.clone(), |service| {
+                for name in &["Alice", "Bob"] {
+                    let account = service
+                        .create_account(
+                            Some(name.to_string()),
+                            "".to_string(),
+                            "".to_string(),
+                            "".to_string(),
+                        )
+                        .unwrap();
+                    let account_key: AccountKey =
+                        mc_util_serial::decode(&account.account_key).unwrap();
+                    accounts.push((account.id, account_key.default_subaddress()));
+                }
+                let scoped_key =
+                    |key: &str, capabilities: &str, account_ids: Vec<String>| ScopedApiKey {
+                        key: key.to_string(),
+                        capabilities: capabilities.parse().unwrap(),
+                        account_ids: account_ids.into_iter().collect(),
+                    };
+                vec![
+                    scoped_key("alice-reader", "read", vec![accounts[0].0.clone()]),
+                    scoped_key(
+                        "alice-payer",
+                        "read,build,submit",
+                        vec![accounts[0].0.clone()],
+                    ),
+                    scoped_key("admin", "all", Vec::new()),
+                ]
+            });
+        for (account_id, public_address) in &accounts {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![public_address.clone()],
+                100000000000000, // 100.0 MOB
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+            manually_sync_account(
+                &ledger_db,
+                &db_ctx.get_db_instance(logger.clone()),
+                &AccountID(account_id.clone()),
+                &logger,
+            );
+        }
+        let alice_id = accounts[0].0.clone();
+        let bob_id = accounts[1].0.clone();
+        let bob_b58 = b58_encode_public_address(&accounts[1].1).unwrap();
+        let build_from = |account_id: &str| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "build_transaction",
+                "params": {
+                    "account_id": account_id,
+                    "recipient_public_address": bob_b58,
+                    "amount": { "value": "42000000000000", "token_id": "0" }, // 42 MOB
+                },
+            })
+        };
+        let get_accounts = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_accounts",
+        });
+
+        // Without MC_API_KEY set, a request without a key is rejected.
+        let res = client
+            .post("/wallet/v2")
+            .header(ContentType::JSON)
+            .body(get_accounts.to_string())
+            .dispatch();
+        assert_eq!(res.status(), Status::Unauthorized);
+
+        let payer = Header::new("X-API-KEY", "alice-payer");
+
+        // Listings of every account only list Alice's.
+        let res = dispatch_with_header(&client, get_accounts.clone(), payer.clone(), &logger);
+        assert_eq!(res["result"]["account_ids"], json!([alice_id]));
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_wallet_status_v2",
+            "params": {
+                "include_accounts": true,
+            },
+        });
+        let res = dispatch_with_header(&client, body, payer.clone(), &logger);
+        let wallet_status = &res["result"]["wallet_status"];
+        assert_eq!(
+            wallet_status["balance_per_token"]["0"]["unspent"],
+            "100000000000000"
+        );
+        assert!(wallet_status["accounts"][&alice_id].is_object());
+        assert!(wallet_status["accounts"][&bob_id].is_null());
+
+        // A read-only key reads its account, but can not build from it.
+        let reader = Header::new("X-API-KEY", "alice-reader");
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_account_status",
+            "params": {
+                "account_id": alice_id,
+            },
+        });
+        let res = dispatch_with_header(&client, body, reader.clone(), &logger);
+        assert_eq!(res["result"]["account"]["id"], alice_id.as_str());
+        let res = dispatch_with_header(&client, build_from(&alice_id), reader.clone(), &logger);
+        assert_eq!(res["error"]["code"], -32003);
+        assert_eq!(res["error"]["data"]["required_capability"], "build");
+
+        // A key scoped to Alice builds from Alice, but not from Bob.
+        let res = dispatch_with_header(&client, build_from(&alice_id), payer.clone(), &logger);
+        assert!(res["result"]["tx_proposal"].is_object());
+        let res = dispatch_with_header(&client, build_from(&bob_id), payer.clone(), &logger);
+        assert!(res.get("result").is_none());
+        assert_eq!(res["error"]["code"], -32003);
+        assert_eq!(res["error"]["data"]["account_id"], bob_id.as_str());
+
+        // Methods on the whole wallet need an admin key.
+        let create_account = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Carol",
+            },
+        });
+        let res = dispatch_with_header(&client, create_account.clone(), payer, &logger);
+        assert_eq!(res["error"]["code"], -32003);
+
+        let admin = Header::new("X-API-KEY", "admin");
+        let res = dispatch_with_header(&client, create_account, admin.clone(), &logger);
+        assert!(res["result"]["account"]["id"].is_string());
+        let res = dispatch_with_header(&client, build_from(&bob_id), admin.clone(), &logger);
+        assert!(res["result"]["tx_proposal"].is_object());
+        let res = dispatch_with_header(&client, get_accounts, admin, &logger);
+        assert_eq!(res["result"]["account_ids"].as_array().unwrap().len(), 3);
+    }
+
     #[test_with_logger]
     fn test_get_network_status(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -118,5 +593,460 @@ mod e2e_misc {
             fees.get(&Mob::ID.to_string()).unwrap().as_str().unwrap(),
             &Mob::MINIMUM_FEE.to_string()
         );
+
+        // Without overrides, the dust thresholds are the network fees.
+        let dust_thresholds = status.get("dust_thresholds").unwrap().as_object().unwrap();
+        assert_eq!(dust_thresholds, fees);
+    }
+
+    #[test_with_logger]
+    fn test_sender_memo_helpers(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let account_key = AccountKey::random(&mut rng);
+        let public_address_b58 =
+            b58_encode_public_address(&account_key.default_subaddress()).unwrap();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "compute_address_hash",
+            "params": {
+                "public_address_b58": public_address_b58,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        assert_eq!(
+            result.get("address_hash").unwrap().as_str().unwrap(),
+            compute_address_hash(&public_address_b58).unwrap()
+        );
+
+        // An unused memo names no sender, which is not an error.
+        let tx_out_public_key = hex::encode([1u8; 32]);
+        let view_private_key = hex::encode(account_key.subaddress_view_private(0).to_bytes());
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "decode_sender_memo",
+            "params": {
+                "memo_payload": "00".repeat(MEMO_PAYLOAD_LEN),
+                "tx_out_public_key": tx_out_public_key,
+                "view_private_key": view_private_key,
+                "sender_public_address_b58": public_address_b58,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let sender_memo = res.get("result").unwrap().get("sender_memo").unwrap();
+        assert_eq!(sender_memo.get("memo_kind").unwrap(), "unused");
+        assert_eq!(sender_memo.get("valid").unwrap(), false);
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "decode_sender_memo",
+            "params": {
+                "memo_payload": "00".repeat(MEMO_PAYLOAD_LEN - 1),
+                "tx_out_public_key": tx_out_public_key,
+                "view_private_key": view_private_key,
+                "sender_public_address_b58": public_address_b58,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let error_data = res.get("error").unwrap().get("data").unwrap();
+        assert_eq!(
+            error_data.get("details").unwrap(),
+            "The memo payload is 65 bytes, expected 66"
+        );
+    }
+
+    fn get_health(client: &rocket::local::Client, path: &str) -> (Status, serde_json::Value) {
+        let mut res = client.get(path).dispatch();
+        let body = res.body_string().unwrap();
+        (res.status(), serde_json::from_str(&body).unwrap())
+    }
+
+    #[test_with_logger]
+    fn test_health_probes(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) =
+            setup_with_api_key(&mut rng, logger, "mobilecats".to_string());
+
+        // The probes do not require the API key.
+        let (status, body) = get_health(&client, "/health/live");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body, json!({ "failing_checks": [] }));
+
+        let (status, body) = get_health(&client, "/health/ready");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(body, json!({ "failing_checks": [] }));
+    }
+
+    #[test_with_logger]
+    fn test_health_ready_fails_when_ledger_lags(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let config = HealthConfig::default();
+        let lag = config.max_ledger_lag as usize + 1;
+        let peer_ledger_db = get_test_ledger(5, &[], BASE_TEST_BLOCK_HEIGHT + lag, &mut rng);
+        let (client, _ledger_db, _db_ctx, _network_state) =
+            setup_with_peer_ledger(&mut rng, logger.clone(), peer_ledger_db.clone(), config);
+
+        // The wallet is alive, but not ready while it is behind the network.
+        let (status, _body) = get_health(&client, "/health/live");
+        assert_eq!(status, Status::Ok);
+
+        let (status, body) = get_health(&client, "/health/ready");
+        assert_eq!(status, Status::ServiceUnavailable);
+        let failing_checks = body["failing_checks"].as_array().unwrap();
+        assert_eq!(failing_checks.len(), 1);
+        assert_eq!(failing_checks[0]["check"], "ledger_lag");
+        assert!(failing_checks[0]["error"]
+            .as_str()
+            .unwrap()
+            .contains(&format!("{} behind", lag)));
+
+        // A wider threshold tolerates the lag.
+        let config = HealthConfig {
+            max_ledger_lag: lag as u64,
+            ..Default::default()
+        };
+        let (client, _ledger_db, _db_ctx, _network_state) =
+            setup_with_peer_ledger(&mut rng, logger, peer_ledger_db, config);
+        let (status, _body) = get_health(&client, "/health/ready");
+        assert_eq!(status, Status::Ok);
+    }
+
+    #[test_with_logger]
+    fn test_flush_fog_pubkey_cache(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "flush_fog_pubkey_cache"
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        assert_eq!(result["num_entries_flushed"], "0");
+        assert_eq!(result["hits"], "0");
+        assert_eq!(result["misses"], "0");
+    }
+
+    #[test_with_logger]
+    fn test_peer_status(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        // Getting the network fees calls each peer.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_network_status"
+        });
+        dispatch(&client, body, &logger);
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_peer_status"
+        });
+        let res = dispatch(&client, body, &logger);
+        let peer_status = res["result"]["peer_status"].clone();
+        assert_eq!(peer_status["strategy"], "healthiest");
+        assert_eq!(peer_status["pinned_responder_id"], serde_json::Value::Null);
+        let peers = peer_status["peers"].as_array().unwrap();
+        assert_eq!(peers.len(), 2);
+        for peer in peers {
+            assert_eq!(peer["healthy"], true);
+            assert_eq!(peer["excluded"], false);
+            assert_eq!(peer["num_failures"], "0");
+            assert_ne!(peer["num_successes"], "0");
+            assert_eq!(peer["success_rate"], 1.0);
+        }
+        let responder_id = peers[1]["responder_id"].as_str().unwrap();
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "pin_peer",
+            "params": {
+                "responder_id": responder_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(
+            res["result"]["peer_status"]["pinned_responder_id"],
+            responder_id
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "exclude_peer",
+            "params": {
+                "responder_id": responder_id,
+                "excluded": true,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let peer = res["result"]["peer_status"]["peers"][1].clone();
+        assert_eq!(peer["excluded"], true);
+        assert_eq!(peer["healthy"], false);
+
+        // Only configured peers can be pinned or excluded.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "pin_peer",
+            "params": {
+                "responder_id": "unknown.example.com:443",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let details = res["error"]["data"]["details"].as_str().unwrap();
+        assert!(details.contains("No peer is configured"));
+    }
+
+    #[test_with_logger]
+    fn test_request_id_is_echoed(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_network_status"
+        });
+        let header = Header::new("X-Request-Id", "my-request-id");
+        let res = dispatch_with_header(&client, body.clone(), header, &logger);
+        assert_eq!(res.get("request_id").unwrap(), "my-request-id");
+
+        // Without the header, a request ID is generated.
+        let res = dispatch(&client, body, &logger);
+        let request_id = res.get("request_id").unwrap().as_str().unwrap();
+        assert!(!request_id.is_empty());
+        assert_ne!(request_id, "my-request-id");
+    }
+
+    #[test_with_logger]
+    fn test_request_id_in_build_logs(logger: Logger) {
+        let captured = Arc::new(Mutex::new(Vec::new()));
+        let capture_logger = Logger::root(CaptureDrain(captured.clone()).fuse(), slog::o!());
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) =
+            setup(&mut rng, capture_logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let account_obj = res.get("result").unwrap().get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100000000000000, // 100.0 MOB
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0"},
+            }
+        });
+        let header = Header::new("X-Request-Id", "build-request-id");
+        let res = dispatch_with_header(&client, body, header, &logger);
+        assert!(res.get("result").is_some());
+        assert_eq!(res.get("request_id").unwrap(), "build-request-id");
+
+        let captured = captured.lock().unwrap();
+        for stage in &["Selected inputs", "ring sampling"] {
+            assert!(captured
+                .iter()
+                .any(|line| line.contains(stage) && line.contains("request_id=build-request-id")));
+        }
+    }
+
+    #[test_with_logger]
+    fn test_batch_request(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!([
+            {
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "create_account",
+                "params": {
+                    "name": "Alice",
+                }
+            },
+            {
+                "jsonrpc": "2.0",
+                "id": "two",
+                "method": "not_a_method",
+            },
+            {
+                "jsonrpc": "2.0",
+                "id": 3,
+                "method": "get_network_status",
+            },
+            [
+                {
+                    "jsonrpc": "2.0",
+                    "id": 4,
+                    "method": "get_network_status",
+                }
+            ],
+            {
+                "jsonrpc": "2.0",
+                "id": 5,
+                "method": "get_wallet_status",
+            },
+        ]);
+        let res = dispatch(&client, body, &logger);
+        let responses = res.as_array().unwrap();
+
+        // Responses are in the order of the requests, and match their ids.
+        let ids: Vec<serde_json::Value> = responses.iter().map(|r| r["id"].clone()).collect();
+        assert_eq!(
+            ids,
+            vec![
+                json!(1),
+                json!("two"),
+                json!(3),
+                serde_json::Value::Null,
+                json!(5)
+            ]
+        );
+
+        assert_eq!(responses[0]["result"]["account"]["name"], "Alice");
+        assert!(responses[0].get("error").is_none());
+
+        // The invalid method and the nested batch fail on their own.
+        assert_eq!(responses[1]["error"]["code"], -32600);
+        assert!(responses[1].get("result").is_none());
+        assert_eq!(responses[3]["error"]["code"], -32600);
+
+        assert_eq!(
+            responses[2]["result"]["network_status"]["network_block_height"],
+            "12"
+        );
+        assert!(responses[4]["result"]["wallet_status"].is_object());
+    }
+
+    #[test_with_logger]
+    fn test_batch_builds_for_one_account_are_serialized(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let account_obj = &res["result"]["account"];
+        let account_id = account_obj["id"].as_str().unwrap().to_string();
+        let b58_public_address = account_obj["main_address"].as_str().unwrap().to_string();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![b58_decode_public_address(&b58_public_address).unwrap()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.clone()),
+            &logger,
+        );
+
+        // Two payments which each need the account's only txo. Were the builds
+        // not serialized, both could select it.
+        let payment = |id: u64| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "build_and_submit_transaction",
+                "params": {
+                    "account_id": account_id,
+                    "recipient_public_address": b58_public_address,
+                    "amount": { "value": (60 * MOB).to_string(), "token_id": "0" },
+                }
+            })
+        };
+        let res = dispatch(&client, json!([payment(1), payment(2)]), &logger);
+        let responses = res.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+
+        // The second build sees the txo pending from the first, and fails.
+        let succeeded: Vec<_> = responses
+            .iter()
+            .filter(|response| response["result"]["tx_proposal"].is_object())
+            .collect();
+        assert_eq!(succeeded.len(), 1);
+        let failed: Vec<_> = responses
+            .iter()
+            .filter(|response| response.get("error").is_some())
+            .collect();
+        assert_eq!(failed.len(), 1);
+        assert!(failed[0]["error"]["data"]["shortfall"].is_string());
+    }
+
+    #[test_with_logger]
+    fn test_batch_request_size_limit(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let request = |id: usize| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "get_network_status",
+            })
+        };
+
+        let batch: Vec<_> = (0..DEFAULT_MAX_BATCH_SIZE).map(request).collect();
+        let res = dispatch(&client, json!(batch), &logger);
+        assert_eq!(res.as_array().unwrap().len(), DEFAULT_MAX_BATCH_SIZE);
+
+        // A batch over the limit is rejected as a whole, with a single error.
+        let batch: Vec<_> = (0..DEFAULT_MAX_BATCH_SIZE + 1).map(request).collect();
+        let res = dispatch(&client, json!(batch), &logger);
+        assert_eq!(res["error"]["code"], -32600);
+        assert_eq!(res["id"], serde_json::Value::Null);
+
+        // So is an empty batch.
+        let res = dispatch(&client, json!([]), &logger);
+        assert_eq!(res["error"]["code"], -32600);
     }
 }
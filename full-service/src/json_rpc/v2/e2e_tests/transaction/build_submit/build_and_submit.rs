@@ -378,4 +378,79 @@ mod e2e_transaction {
         assert_eq!(secreted, "0");
         assert_eq!(orphaned, "0");
     }
+
+    #[test_with_logger]
+    fn test_build_and_submit_transaction_idempotency_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100_000_000_000_000, // 100.0 MOB
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_and_submit_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0" }, // 42.0 MOB
+                "idempotency_key": "payout-1",
+            }
+        });
+        let res = dispatch(&client, body.clone(), &logger);
+        let result = res.get("result").unwrap();
+        let transaction_log = result.get("transaction_log").unwrap();
+        let tx_proposal = result.get("tx_proposal").unwrap();
+
+        // Retrying with the same key returns the original transaction.
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        assert_eq!(result["transaction_log"]["id"], transaction_log["id"]);
+        assert_eq!(result["transaction_log"]["status"], "pending");
+        assert_eq!(result["tx_proposal"]["tx_proto"], tx_proposal["tx_proto"]);
+
+        // Reusing the key with different parameters is an error.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_and_submit_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "43000000000000", "token_id": "0" }, // 43.0 MOB
+                "idempotency_key": "payout-1",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let error = res.get("error").unwrap();
+        let server_error = error["data"]["server_error"].as_str().unwrap();
+        assert!(server_error.contains("IdempotencyKeyMismatch"));
+    }
 }
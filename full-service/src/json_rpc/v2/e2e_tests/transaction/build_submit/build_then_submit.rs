@@ -16,9 +16,12 @@ mod e2e_transaction {
         service::models::tx_proposal::TxProposal,
         test_utils::{
             add_block_to_ledger_db, add_block_with_tx, add_block_with_tx_outs,
-            manually_sync_account,
+            manually_sync_account, MOB,
+        },
+        util::{
+            amount_display::format_fixed_point, b58::b58_decode_public_address,
+            encoding_helpers::ristretto_public_to_hex,
         },
-        util::b58::b58_decode_public_address,
     };
 
     use mc_blockchain_types::BlockVersion;
@@ -34,6 +37,444 @@ mod e2e_transaction {
 
     use std::convert::TryFrom;
 
+    #[test_with_logger]
+    fn test_build_transaction_insufficient_input_funds(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        // Add an account
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        // Add a block with a single dust txo for this address
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_txos",
+            "params": {
+                "account_id": account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let txo_ids = result.get("txo_ids").unwrap().as_array().unwrap();
+        assert_eq!(txo_ids.len(), 1);
+        let txo_id = txo_ids[0].as_str().unwrap();
+
+        // Spending the dust txo explicitly cannot cover the value and the fee
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": Mob::MINIMUM_FEE.to_string(), "token_id": "0"},
+                "input_txo_ids": [txo_id],
+            }
+        });
+        dispatch_expect_error(
+            &client,
+            body,
+            &logger,
+            json!({
+                "method": "build_transaction",
+                "error": json!({
+                    "code": -32603,
+                    "message": "InternalError",
+                    "data": json!({
+                        "server_error": format!("TransactionBuilder(InsufficientInputFunds(InsufficientInputFundsAmounts {{ token_id: 0, requested: {}, input_value: 100, shortfall: {} }}))", 2 * Mob::MINIMUM_FEE, 2 * Mob::MINIMUM_FEE - 100),
+                        "details": format!("Error building transaction: Insufficient Funds in inputs to construct transaction: Value of selected inputs: 100, but target value: {}", 2 * Mob::MINIMUM_FEE),
+                        "token_id": "0",
+                        "requested": (2 * Mob::MINIMUM_FEE).to_string(),
+                        "input_value": "100",
+                        "shortfall": (2 * Mob::MINIMUM_FEE - 100).to_string(),
+                    })
+                }),
+                "jsonrpc": "2.0",
+                "id": 1,
+            }).to_string(),
+        );
+    }
+
+    #[test_with_logger]
+    fn test_build_transaction_to_public_keys(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        // Add an account
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100000000000000, // 100.0 MOB
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        // Send to ourselves, identifying the recipient by its public keys
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_keys": {
+                    "view_public_hex": ristretto_public_to_hex(public_address.view_public_key()),
+                    "spend_public_hex": ristretto_public_to_hex(public_address.spend_public_key()),
+                },
+                "amount": { "value": "42000000000000", "token_id": "0"},
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let tx_proposal = result.get("tx_proposal").unwrap();
+        let payload_txos = tx_proposal.get("payload_txos").unwrap().as_array().unwrap();
+        assert_eq!(payload_txos.len(), 1);
+        assert_eq!(
+            payload_txos[0]
+                .get("recipient_public_address_b58")
+                .unwrap()
+                .as_str()
+                .unwrap(),
+            b58_public_address
+        );
+    }
+
+    #[test_with_logger]
+    fn test_preview_then_build_transaction(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let account_obj = res["result"]["account"].clone();
+        let account_id = account_obj["id"].as_str().unwrap();
+        let b58_public_address = account_obj["main_address"].as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        for value in [100 * MOB, 60 * MOB] {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![public_address.clone()],
+                value,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "preview_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": (50 * MOB).to_string(), "token_id": "0"},
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let preview = res["result"]["transaction_preview"].clone();
+        let input_txos = preview["input_txos"].as_array().unwrap();
+        assert_eq!(input_txos.len(), 1);
+        let input_value: u64 = input_txos[0]["amount"]["value"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(input_txos[0]["subaddress_index"], "0");
+        assert_eq!(preview["fee"]["value"], Mob::MINIMUM_FEE.to_string());
+        assert_eq!(
+            preview["change_per_token"]["0"]["value"],
+            (input_value - 50 * MOB - Mob::MINIMUM_FEE).to_string()
+        );
+        let selection_token = preview["selection_token"].as_str().unwrap();
+
+        // The selection token cannot be combined with input txo ids.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": (50 * MOB).to_string(), "token_id": "0"},
+                "input_txo_ids": [input_txos[0]["txo_id"]],
+                "selection_token": selection_token,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(
+            res["error"]["data"]["details"],
+            "input_txo_ids cannot be combined with selection_token"
+        );
+
+        // Building with the selection token spends the previewed txo.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": (50 * MOB).to_string(), "token_id": "0"},
+                "selection_token": selection_token,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let tx_proposal = res["result"]["tx_proposal"].clone();
+        let built_inputs = tx_proposal["input_txos"].as_array().unwrap();
+        assert_eq!(built_inputs.len(), 1);
+        assert_eq!(built_inputs[0]["amount"], input_txos[0]["amount"]);
+        assert_eq!(tx_proposal["fee_amount"], preview["fee"]);
+        assert_eq!(
+            tx_proposal["tombstone_block_index"],
+            preview["tombstone_block_index"]
+        );
+    }
+
+    #[test_with_logger]
+    fn test_build_transaction_display_amounts(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        // Add an account
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100000000000000, // 100.0 MOB
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        // Amounts only carry display fields when asked for
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0"},
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let fee_amount = result["tx_proposal"]["fee_amount"].as_object().unwrap();
+        assert!(fee_amount.get("value_decimal").is_none());
+        assert!(fee_amount.get("symbol").is_none());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0"},
+                "display": true,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let tx_proposal = result.get("tx_proposal").unwrap();
+        let payload_amount = &tx_proposal["payload_txos"][0]["amount"];
+        assert_eq!(payload_amount["value"], "42000000000000");
+        assert_eq!(payload_amount["value_decimal"], "42.000000000000");
+        assert_eq!(payload_amount["symbol"], "MOB");
+        let fee_amount = &tx_proposal["fee_amount"];
+        assert_eq!(
+            fee_amount["value_decimal"],
+            format_fixed_point(Mob::MINIMUM_FEE, 12)
+        );
+        assert_eq!(fee_amount["symbol"], "MOB");
+
+        // Clients which parse the response are unaffected by the display fields
+        let tx_proposal: TxProposalJSON = serde_json::from_value(tx_proposal.clone()).unwrap();
+        assert_eq!(
+            tx_proposal.fee_amount,
+            AmountJSON::new(Mob::MINIMUM_FEE, Mob::ID)
+        );
+        TxProposal::try_from(&tx_proposal).unwrap();
+    }
+
+    #[test_with_logger]
+    fn test_build_transaction_with_comment_and_payment_request_id(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        // Add an account
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address.clone()],
+            100000000000000, // 100.0 MOB
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0"},
+                "comment": "Invoice 1234",
+                "payment_request_id": "1234",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let transaction_log_id = result.get("transaction_log_id").unwrap().as_str().unwrap();
+
+        // The comment and the memo parameters are recorded when the
+        // transaction is built.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_transaction_log",
+            "params": {
+                "transaction_log_id": transaction_log_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let transaction_log = &res["result"]["transaction_log"];
+        assert_eq!(transaction_log["status"], "built");
+        assert_eq!(transaction_log["comment"], "Invoice 1234");
+        assert_eq!(transaction_log["payment_request_id"], "1234");
+        assert!(transaction_log["burn_redemption_memo_hex"].is_null());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_transaction_logs",
+            "params": {
+                "account_id": account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let transaction_log = &res["result"]["transaction_log_map"][transaction_log_id];
+        assert_eq!(transaction_log["comment"], "Invoice 1234");
+        assert_eq!(transaction_log["payment_request_id"], "1234");
+
+        // A payment request id must be a u64.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": { "value": "42000000000000", "token_id": "0"},
+                "payment_request_id": "-1",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert_eq!(res["error"]["code"], -32600);
+    }
+
     #[test_with_logger]
     fn test_build_then_submit_transaction(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -82,7 +523,7 @@ mod e2e_transaction {
             "params": {
                 "account_id": account_id,
                 "recipient_public_address": b58_public_address,
-                "amount": { "value": "42", "token_id": "0"},
+                "amount": { "value": Mob::MINIMUM_FEE.to_string(), "token_id": "0"},
             }
         });
         // We will fail because we cannot afford the fee
@@ -96,8 +537,13 @@ mod e2e_transaction {
                     "code": -32603,
                     "message": "InternalError",
                     "data": json!({
-                        "server_error": format!("TransactionBuilder(WalletDb(InsufficientFundsUnderMaxSpendable(\"Max spendable value in wallet: 0, but target value: {}\")))", 42 + Mob::MINIMUM_FEE),
-                        "details": format!("Error building transaction: Wallet DB Error: Insufficient funds from Txos under max_spendable_value: Max spendable value in wallet: 0, but target value: {}", 42 + Mob::MINIMUM_FEE),
+                        "server_error": format!("TransactionBuilder(WalletDb(InsufficientFundsUnderMaxSpendable(InsufficientFundsAmounts {{ token_id: 0, requested: {}, available: 100, max_spendable: 0, shortfall: {} }})))", 2 * Mob::MINIMUM_FEE, 2 * Mob::MINIMUM_FEE - 100),
+                        "details": format!("Error building transaction: Wallet DB Error: Insufficient funds from Txos under max_spendable_value: Max spendable value in wallet: 0, but target value: {}", 2 * Mob::MINIMUM_FEE),
+                        "token_id": "0",
+                        "requested": (2 * Mob::MINIMUM_FEE).to_string(),
+                        "available": "100",
+                        "max_spendable": "0",
+                        "shortfall": (2 * Mob::MINIMUM_FEE - 100).to_string(),
                     })
                 }),
                 "jsonrpc": "2.0",
@@ -6,16 +6,22 @@
 mod e2e_transaction {
     use crate::{
         db::account::AccountID,
-        json_rpc::v2::api::test_utils::{dispatch, setup},
+        fog_resolver::FullServiceFogResolver,
+        json_rpc::v2::{
+            api::test_utils::{dispatch, dispatch_confirmed, setup},
+            models::tx_proposal::TxProposal as TxProposalJSON,
+        },
         test_utils::{add_block_to_ledger_db, manually_sync_account, MOB},
         unsigned_tx::UnsignedTx,
         util::b58::b58_decode_public_address,
     };
 
+    use mc_account_keys::{burn_address, AccountKey, RootEntropy, RootIdentity};
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_rand::rand_core::RngCore;
     use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
     use rand::{rngs::StdRng, SeedableRng};
+    use std::convert::TryFrom;
 
     #[test_with_logger]
     fn test_build_unsigned_transaction(logger: Logger) {
@@ -96,7 +102,7 @@ mod e2e_transaction {
                 "account_id": account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true);
 
@@ -169,4 +175,89 @@ mod e2e_transaction {
         let _tx: UnsignedTx =
             serde_json::from_value(result.get("unsigned_tx").unwrap().clone()).unwrap();
     }
+
+    #[test_with_logger]
+    fn test_build_unsigned_burn_transaction(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let entropy = "c593274dc6f6eb94242e34ae5f0ab16bc3085d45d49d9e18b8a8c6f057e6b56b";
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "import_account_from_legacy_root_entropy",
+            "params": {
+                "entropy": entropy,
+                "name": "Offline Signer Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let account_id = res["result"]["account"]["id"].as_str().unwrap().to_string();
+
+        let mut entropy_bytes = [0u8; 32];
+        hex::decode_to_slice(entropy, &mut entropy_bytes).unwrap();
+        let account_key = AccountKey::from(&RootIdentity::from(&RootEntropy::from(&entropy_bytes)));
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![account_key.subaddress(0)],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.clone()),
+            &logger,
+        );
+
+        let redemption_memo_hex = "a5".repeat(64);
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_unsigned_burn_transaction",
+            "params": {
+                "account_id": account_id,
+                "amount": { "value": (10 * MOB).to_string(), "token_id": "0"},
+                "redemption_memo_hex": redemption_memo_hex,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let unsigned_tx: UnsignedTx =
+            serde_json::from_value(result["unsigned_tx"].clone()).unwrap();
+        let fog_resolver: FullServiceFogResolver =
+            serde_json::from_value(result["fog_resolver"].clone()).unwrap();
+
+        // The offline signer puts the burn redemption memo on the output to
+        // the burn address.
+        let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+        assert_eq!(tx_proposal.payload_txos.len(), 1);
+        assert_eq!(
+            tx_proposal.payload_txos[0].recipient_public_address,
+            burn_address()
+        );
+        assert_eq!(
+            tx_proposal.burn_redemption_memo().map(hex::encode),
+            Some(redemption_memo_hex.clone())
+        );
+
+        // Submitting the signed burn records its memo on the transaction log.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "submit_transaction",
+            "params": {
+                "tx_proposal": TxProposalJSON::try_from(&tx_proposal).unwrap(),
+                "account_id": account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let transaction_log = &res["result"]["transaction_log"];
+        assert_eq!(
+            transaction_log["burn_redemption_memo_hex"],
+            redemption_memo_hex
+        );
+    }
 }
@@ -6,23 +6,29 @@
 mod e2e_transaction {
     use crate::{
         db::account::AccountID,
-        json_rpc::v2::{
-            api::test_utils::{dispatch, setup},
-            models::{
-                amount::Amount, transaction_log::TransactionLog,
-                tx_proposal::TxProposal as TxProposalJSON,
+        json_rpc::{
+            request_limits::RequestSizeLimits,
+            v2::{
+                api::test_utils::{dispatch, setup, setup_with_request_size_limits},
+                models::{
+                    amount::Amount, transaction_log::TransactionLog,
+                    tx_proposal::TxProposal as TxProposalJSON,
+                },
             },
         },
         service::models::tx_proposal::TxProposal,
-        test_utils::{add_block_to_ledger_db, add_block_with_tx, manually_sync_account},
+        test_utils::{add_block_to_ledger_db, add_block_with_tx, manually_sync_account, MOB},
         util::b58::b58_decode_public_address,
     };
 
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_rand::rand_core::RngCore;
     use mc_ledger_db::Ledger;
-    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
+    use mc_transaction_core::{
+        constants::MAX_INPUTS, ring_signature::KeyImage, tokens::Mob, Token,
+    };
     use rand::{rngs::StdRng, SeedableRng};
+    use rocket::http::{ContentType, Header, Status};
 
     use std::convert::TryFrom;
 
@@ -140,4 +146,125 @@ mod e2e_transaction {
         assert_eq!(secreted, "0");
         assert_eq!(orphaned, "0");
     }
+
+    #[test_with_logger]
+    fn test_max_inputs_proposal_round_trips(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) =
+            setup_with_request_size_limits(&mut rng, logger.clone(), RequestSizeLimits::default());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let account_obj = res["result"]["account"].clone();
+        let account_id = account_obj["id"].as_str().unwrap();
+        let b58_public_address = account_obj["main_address"].as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address; MAX_INPUTS as usize],
+            MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        // Sending everything but the fee needs every txo as an input.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "build_transaction",
+            "params": {
+                "account_id": account_id,
+                "recipient_public_address": b58_public_address,
+                "amount": {
+                    "value": (MAX_INPUTS * MOB - Mob::MINIMUM_FEE).to_string(),
+                    "token_id": "0",
+                },
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let tx_proposal = res["result"]["tx_proposal"].clone();
+        assert_eq!(
+            tx_proposal["input_txos"].as_array().unwrap().len(),
+            MAX_INPUTS as usize
+        );
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "submit_transaction",
+            "params": {
+                "tx_proposal": tx_proposal,
+                "account_id": account_id,
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let transaction_log = res["result"]["transaction_log"].clone();
+        assert_eq!(
+            transaction_log["input_txos"].as_array().unwrap().len(),
+            MAX_INPUTS as usize
+        );
+    }
+
+    #[test_with_logger]
+    fn test_oversized_request(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, _db_ctx, _network_state) = setup_with_request_size_limits(
+            &mut rng,
+            logger.clone(),
+            RequestSizeLimits::new(1024, 4096, &[]),
+        );
+
+        // Requests within the limit are handled as usual.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_network_status",
+        });
+        let res = dispatch(&client, body, &logger);
+        assert!(res.get("result").is_some());
+
+        // Larger requests are rejected with the limit of their route, and the
+        // size received.
+        for (route, limit) in &[("/wallet/v2", 4096), ("/wallet/v2/stream", 1024)] {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "get_txos",
+                "params": {
+                    "account_id": "a".repeat(*limit),
+                }
+            })
+            .to_string();
+            let mut res = client
+                .post(*route)
+                .header(ContentType::JSON)
+                .header(Header::new("Content-Length", body.len().to_string()))
+                .body(body.clone())
+                .dispatch();
+            assert_eq!(res.status(), Status::PayloadTooLarge);
+
+            let response: serde_json::Value =
+                serde_json::from_str(&res.body_string().unwrap()).unwrap();
+            assert_eq!(response["error"]["code"], -32600);
+            assert_eq!(response["error"]["data"]["limit"], limit.to_string());
+            assert_eq!(
+                response["error"]["data"]["received"],
+                body.len().to_string()
+            );
+        }
+    }
 }
@@ -7,7 +7,7 @@ mod e2e_transaction {
     use crate::{
         db::{account::AccountID, txo::TxoStatus},
         json_rpc::v2::{
-            api::test_utils::{dispatch, setup},
+            api::test_utils::{dispatch, dispatch_confirmed, setup},
             models::tx_proposal::TxProposal as TxProposalJSON,
         },
         service::models::tx_proposal::TxProposal,
@@ -154,7 +154,7 @@ mod e2e_transaction {
             3
         );
 
-        // Remove account 1.
+        // Remove account 1. Its txos are kept until it is purged.
         let body = json!({
             "jsonrpc": "2.0",
             "id": 2,
@@ -163,9 +163,30 @@ mod e2e_transaction {
                 "account_id": account_id_1,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true,);
+        assert_eq!(
+            txos::table
+                .select(count(txos::id))
+                .first::<i64>(&wallet_db.get_conn().unwrap())
+                .unwrap(),
+            3
+        );
+
+        // Purge account 1.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "purge_account",
+            "params": {
+                "account_id": account_id_1,
+                "confirm_account_name": "account 1",
+            }
+        });
+        let res = dispatch_confirmed(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        assert_eq!(result["purged"].as_bool().unwrap(), true,);
         assert_eq!(
             txos::table
                 .select(count(txos::id))
@@ -311,7 +332,7 @@ mod e2e_transaction {
                 "account_id": *account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true,);
 
@@ -406,7 +427,7 @@ mod e2e_transaction {
                 "account_id": *account_id_2,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true,);
 
@@ -475,7 +496,7 @@ mod e2e_transaction {
                 "account_id": *account_id,
             }
         });
-        let res = dispatch(&client, body, &logger);
+        let res = dispatch_confirmed(&client, body, &logger);
         let result = res.get("result").unwrap();
         assert_eq!(result["removed"].as_bool().unwrap(), true,);
 
@@ -592,4 +613,289 @@ mod e2e_transaction {
         let unspent = balance_mob["unspent"].as_str().unwrap();
         assert_eq!(unspent, "100");
     }
+
+    #[test_with_logger]
+    fn test_get_txos_include_timestamps(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, mut ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "create_account",
+            "params": {
+                "name": "Alice Main Account",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        let result = res.get("result").unwrap();
+        let account_obj = result.get("account").unwrap();
+        let account_id = account_obj.get("id").unwrap().as_str().unwrap();
+        let b58_public_address = account_obj.get("main_address").unwrap().as_str().unwrap();
+        let public_address = b58_decode_public_address(b58_public_address).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address],
+            100,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+
+        manually_sync_account(
+            &ledger_db,
+            &db_ctx.get_db_instance(logger.clone()),
+            &AccountID(account_id.to_string()),
+            &logger,
+        );
+
+        // Test blocks are not signed, so the timestamps are omitted rather than
+        // an error, both on the first lookup and on the cached one.
+        for _ in 0..2 {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "get_txos",
+                "params": {
+                    "account_id": account_id,
+                    "include_timestamps": true,
+                }
+            });
+            let res = dispatch(&client, body, &logger);
+            let result = res.get("result").unwrap();
+            let txos = result.get("txo_ids").unwrap().as_array().unwrap();
+            assert_eq!(txos.len(), 1);
+            let txo_map = result.get("txo_map").unwrap().as_object().unwrap();
+            let txo = txo_map.get(txos[0].as_str().unwrap()).unwrap();
+            assert!(txo.get("received_block_timestamp").is_none());
+            assert!(txo.get("spent_block_timestamp").is_none());
+        }
+    }
+
+    #[test_with_logger]
+    fn test_stream_txos_resumes_from_cursor(logger: Logger) {
+        use crate::{
+            db::{account::AccountModel, models::Account},
+            test_utils::create_test_received_txo,
+        };
+        use mc_account_keys::{AccountKey, RootIdentity};
+        use mc_transaction_core::Amount;
+        use mc_util_from_random::FromRandom;
+        use rocket::http::{ContentType, Status};
+        use std::{
+            collections::HashSet,
+            io::{BufRead, BufReader},
+        };
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+        let wallet_db = db_ctx.get_db_instance(logger.clone());
+
+        let root_id = RootIdentity::from_random(&mut rng);
+        let account_key = AccountKey::from(&root_id);
+        let (account_id, _public_address_b58) = Account::create_from_root_entropy(
+            &root_id.root_entropy,
+            Some(0),
+            None,
+            None,
+            "Alice",
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+
+        let num_txos = 10_000;
+        let mut expected_txo_ids = HashSet::new();
+        for i in 0..num_txos {
+            let (txo_id, _, _) = create_test_received_txo(
+                &account_key,
+                0,
+                Amount::new(1000 + i, Mob::ID),
+                i,
+                &mut rng,
+                &wallet_db,
+            );
+            expected_txo_ids.insert(txo_id);
+        }
+
+        let stream = |cursor: Option<String>| {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "get_txos",
+                "params": {
+                    "account_id": account_id.to_string(),
+                    "cursor": cursor,
+                    "page_size": "500",
+                }
+            });
+            client
+                .post("/wallet/v2/stream")
+                .header(ContentType::JSON)
+                .body(body.to_string())
+                .dispatch()
+        };
+
+        // Read part of the stream, then drop the connection mid-page.
+        let mut streamed_txo_ids = HashSet::new();
+        let mut last_cursor = None;
+        {
+            let mut res = stream(None);
+            assert_eq!(res.status(), Status::Ok);
+            assert_eq!(
+                res.content_type(),
+                Some(ContentType::new("application", "x-ndjson"))
+            );
+            let reader = BufReader::new(res.body().unwrap().into_inner());
+            for line in reader.lines().take(1234) {
+                let row: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+                let txo_id = row["txo"]["id"].as_str().unwrap().to_string();
+                assert!(streamed_txo_ids.insert(txo_id));
+                last_cursor = Some(row["cursor"].as_str().unwrap().to_string());
+            }
+        }
+        assert_eq!(streamed_txo_ids.len(), 1234);
+
+        // Resume after the last row which was read.
+        let mut res = stream(last_cursor);
+        assert_eq!(res.status(), Status::Ok);
+        let reader = BufReader::new(res.body().unwrap().into_inner());
+        let mut done = false;
+        for line in reader.lines() {
+            let row: serde_json::Value = serde_json::from_str(&line.unwrap()).unwrap();
+            assert!(!done, "No rows should follow the end of the stream");
+            if row.get("done").is_some() {
+                done = true;
+                continue;
+            }
+            let txo_id = row["txo"]["id"].as_str().unwrap().to_string();
+            assert!(streamed_txo_ids.insert(txo_id), "Duplicate txo streamed");
+        }
+        assert!(done);
+        assert_eq!(streamed_txo_ids, expected_txo_ids);
+
+        // A cursor for another listing is rejected.
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_transaction_logs",
+            "params": {
+                "account_id": account_id.to_string(),
+                "cursor": hex::encode("txo:abc"),
+            }
+        });
+        let res = client
+            .post("/wallet/v2/stream")
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(res.status(), Status::InternalServerError);
+    }
+
+    #[test_with_logger]
+    fn test_get_txos_modified_since(logger: Logger) {
+        use crate::{
+            db::{
+                account::AccountModel,
+                models::{Account, Txo},
+                txo::TxoModel,
+            },
+            test_utils::create_test_received_txo,
+        };
+        use mc_account_keys::{AccountKey, RootIdentity};
+        use mc_transaction_core::Amount;
+        use mc_util_from_random::FromRandom;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (client, _ledger_db, db_ctx, _network_state) = setup(&mut rng, logger.clone());
+        let wallet_db = db_ctx.get_db_instance(logger.clone());
+
+        let root_id = RootIdentity::from_random(&mut rng);
+        let account_key = AccountKey::from(&root_id);
+        let (account_id, _public_address_b58) = Account::create_from_root_entropy(
+            &root_id.root_entropy,
+            Some(0),
+            None,
+            None,
+            "Alice",
+            "".to_string(),
+            "".to_string(),
+            "".to_string(),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+
+        let mut txo_ids = vec![];
+        for i in 0..3 {
+            let (txo_id, _, _) = create_test_received_txo(
+                &account_key,
+                0,
+                Amount::new(1000 + i, Mob::ID),
+                i,
+                &mut rng,
+                &wallet_db,
+            );
+            txo_ids.push(txo_id);
+        }
+
+        let get_txos_modified_since = |cursor: Option<String>| {
+            let body = json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "get_txos_modified_since",
+                "params": {
+                    "account_id": account_id.to_string(),
+                    "cursor": cursor,
+                    "limit": "2",
+                }
+            });
+            let res = dispatch(&client, body, &logger);
+            let result = res["result"].clone();
+            let txo_ids: Vec<String> = result["txo_ids"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|txo_id| txo_id.as_str().unwrap().to_string())
+                .collect();
+            for txo_id in txo_ids.iter() {
+                assert!(result["txo_map"].get(txo_id).is_some());
+            }
+            (txo_ids, result["next_cursor"].as_str().map(String::from))
+        };
+
+        // Nothing has been exported yet, so everything is listed, a page at a
+        // time.
+        let (page, cursor) = get_txos_modified_since(None);
+        assert_eq!(page, txo_ids[..2].to_vec());
+        let (page, cursor) = get_txos_modified_since(cursor);
+        assert_eq!(page, txo_ids[2..].to_vec());
+        let (page, next_cursor) = get_txos_modified_since(cursor.clone());
+        assert!(page.is_empty());
+        assert_eq!(next_cursor, cursor);
+
+        // Only the txo which changed is listed again.
+        Txo::update_spent_block_index(&txo_ids[0], 5, &wallet_db.get_conn().unwrap()).unwrap();
+        let (page, cursor) = get_txos_modified_since(cursor);
+        assert_eq!(page, vec![txo_ids[0].clone()]);
+        let (page, _) = get_txos_modified_since(cursor);
+        assert!(page.is_empty());
+
+        let body = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "get_txos_modified_since",
+            "params": {
+                "account_id": account_id.to_string(),
+                "cursor": "not a cursor",
+            }
+        });
+        let res = dispatch(&client, body, &logger);
+        assert!(res.get("result").is_none());
+        assert!(res["error"]["data"]["server_error"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid cursor"));
+    }
 }
@@ -2,7 +2,15 @@
 
 //! API definition for the Account object.
 
-use crate::{db, util::b58::b58_encode_public_address};
+use crate::{
+    db::{
+        self,
+        account::{AccountModel, ReservedSubaddress},
+    },
+    json_rpc::v2::models::account_key::FogInfo,
+    util::b58::b58_encode_public_address,
+};
+use mc_account_keys::AccountKey;
 use serde_derive::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -30,6 +38,19 @@ pub struct Account {
     /// recipient, and should be consider a free-for-all address.
     pub main_address: String,
 
+    /// B58 Address Code for the subaddress the account sends change to. It is
+    /// derived from the account key, whether or not the subaddress has been
+    /// assigned.
+    pub change_subaddress_b58: String,
+
+    /// B58 Address Code for the subaddress gift codes are funded from.
+    pub gift_code_subaddress_b58: String,
+
+    /// B58 Address Code for the subaddress which accounts without fog sent
+    /// change to before the change subaddress was introduced. Not set for fog
+    /// enabled accounts, which do not reserve it.
+    pub legacy_change_subaddress_b58: Option<String>,
+
     /// This index represents the next subaddress to be assigned as an address.
     /// This is useful information in case the account is imported elsewhere.
     pub next_subaddress_index: String,
@@ -41,6 +62,10 @@ pub struct Account {
     /// Index of the next block this account needs to sync.
     pub next_block_index: String,
 
+    /// Index of the first block in which sync found a txo received by this
+    /// account. Not set until the account has received a txo.
+    pub first_activity_block_index: Option<String>,
+
     /// A flag that indicates this imported account is attempting to un-orphan
     /// found TXOs. It is recommended to move all MOB to another account after
     /// recovery if the user is unsure of the assigned addresses.
@@ -52,37 +77,114 @@ pub struct Account {
     /// PublicAddressB58's with fog credentials.
     pub fog_enabled: bool,
 
+    /// The fog report url, report id and authority spki the account's
+    /// addresses are built with. Not set for accounts without fog.
+    pub fog_info: Option<FogInfo>,
+
     /// A flag that indicates if this account is a watch only account.
     pub view_only: bool,
+
+    /// How the keys of this account entered the wallet: "created",
+    /// "imported_mnemonic", "imported_keys", "view_only_import" or "hardware".
+    /// Only accounts whose spend key the wallet holds can sign locally.
+    pub key_source: String,
+
+    /// The account this legacy account's funds are being, or were, moved to
+    /// by migrate_account_to_mnemonic. Not set for accounts which have not
+    /// been migrated.
+    pub migrated_to_account_id: Option<String>,
+
+    /// Number of unassigned subaddresses past the highest assigned subaddress
+    /// which are checked for incoming txos during sync. Subaddresses which
+    /// receive txos within this gap are assigned automatically.
+    pub subaddress_gap_limit: String,
+
+    /// Number of outputs the change of each transaction built by this account
+    /// is divided between, so that the balance is not held in a single txo.
+    pub split_change_into: String,
+
+    /// Number of blocks a received txo must be in the ledger for before this
+    /// account can spend it. Not set when the wallet default is used.
+    pub min_confirmations: Option<String>,
+
+    /// Fee paid by transactions built by this account which do not specify
+    /// one, when paying the fee in default_fee_token_id. Not set when the
+    /// network fee is used.
+    pub default_fee_value: Option<String>,
+
+    /// Token in which transactions built by this account which do not specify
+    /// a fee token pay their fee. Not set when the fee is paid in the token
+    /// being sent.
+    pub default_fee_token_id: Option<String>,
+
+    /// Number of blocks past the tip at which transactions built by this
+    /// account which do not specify a tombstone block expire. Not set when the
+    /// wallet default is used.
+    pub default_tombstone_offset: Option<String>,
 }
 
 impl Account {
     pub fn new(src: &db::models::Account, next_subaddress_index: u64) -> Result<Self, String> {
-        let main_public_address = if src.view_only {
-            let account_key: mc_account_keys::ViewAccountKey =
-                mc_util_serial::decode(&src.account_key)
-                    .map_err(|e| format!("Failed to decode view account key: {}", e))?;
-            account_key.default_subaddress()
+        let legacy_change_subaddress_b58 = if src
+            .reserved_subaddresses()
+            .contains(&ReservedSubaddress::LegacyChange)
+        {
+            Some(reserved_address_b58(src, ReservedSubaddress::LegacyChange)?)
         } else {
-            let account_key: mc_account_keys::AccountKey = mc_util_serial::decode(&src.account_key)
-                .map_err(|e| format!("Failed to decode account key: {}", e))?;
-            account_key.default_subaddress()
+            None
         };
 
-        let main_public_address_b58 = b58_encode_public_address(&main_public_address)
-            .map_err(|e| format!("Could not b58 encode public address {:?}", e))?;
+        let fog_info = if src.fog_enabled && !src.view_only {
+            let account_key: AccountKey = mc_util_serial::decode(&src.account_key)
+                .map_err(|e| format!("Failed to decode account key: {}", e))?;
+            Some(FogInfo {
+                report_url: account_key.fog_report_url().unwrap_or_default().to_string(),
+                report_id: account_key.fog_report_id().unwrap_or_default().to_string(),
+                authority_spki: base64::encode(
+                    account_key.fog_authority_spki().unwrap_or_default(),
+                ),
+            })
+        } else {
+            None
+        };
 
         Ok(Account {
             id: src.id.clone(),
             key_derivation_version: src.key_derivation_version.to_string(),
             name: src.name.clone(),
-            main_address: main_public_address_b58,
+            main_address: reserved_address_b58(src, ReservedSubaddress::Main)?,
+            change_subaddress_b58: reserved_address_b58(src, ReservedSubaddress::Change)?,
+            gift_code_subaddress_b58: reserved_address_b58(src, ReservedSubaddress::GiftCode)?,
+            legacy_change_subaddress_b58,
             next_subaddress_index: next_subaddress_index.to_string(),
             first_block_index: (src.first_block_index as u64).to_string(),
             next_block_index: (src.next_block_index as u64).to_string(),
+            first_activity_block_index: src
+                .first_activity_block_index
+                .map(|i| (i as u64).to_string()),
             recovery_mode: false,
             fog_enabled: src.fog_enabled,
+            fog_info,
             view_only: src.view_only,
+            key_source: src.key_source.clone(),
+            migrated_to_account_id: src.migrated_to_account_id.clone(),
+            subaddress_gap_limit: (src.subaddress_gap_limit as u64).to_string(),
+            split_change_into: (src.split_change_into as u64).to_string(),
+            min_confirmations: src.min_confirmations.map(|m| (m as u64).to_string()),
+            default_fee_value: src.default_fee_value.map(|f| (f as u64).to_string()),
+            default_fee_token_id: src.default_fee_token_id.map(|t| (t as u64).to_string()),
+            default_tombstone_offset: src.default_tombstone_offset.map(|o| (o as u64).to_string()),
         })
     }
 }
+
+fn reserved_address_b58(
+    src: &db::models::Account,
+    purpose: ReservedSubaddress,
+) -> Result<String, String> {
+    let public_address = src
+        .public_address(purpose.index())
+        .map_err(|e| format!("Failed to decode account key: {}", e))?;
+    b58_encode_public_address(&public_address)
+        .map_err(|e| format!("Could not b58 encode public address {:?}", e))
+}
@@ -41,12 +41,30 @@ pub struct AccountSecrets {
     ///  Private keys for receiving and spending MobileCoin.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub view_account_key: Option<ViewAccountKey>,
+
+    /// How the keys of this account entered the wallet.
+    #[serde(default)]
+    pub key_source: String,
+
+    /// The secrets the wallet holds for accounts from this key source, such
+    /// as only the view account key for view only and hardware accounts.
+    #[serde(default)]
+    pub exportable_secrets: Vec<String>,
 }
 
 impl TryFrom<&Account> for AccountSecrets {
     type Error = String;
 
     fn try_from(src: &Account) -> Result<AccountSecrets, String> {
+        let key_source = src
+            .key_source()
+            .map_err(|err| format!("Could not read account key source: {:?}", err))?;
+        let exportable_secrets = key_source
+            .exportable_secrets()
+            .iter()
+            .map(|secret| secret.to_string())
+            .collect();
+
         if src.view_only {
             let view_account_key: mc_account_keys::ViewAccountKey =
                 mc_util_serial::decode(&src.account_key).map_err(|err| {
@@ -61,6 +79,8 @@ impl TryFrom<&Account> for AccountSecrets {
                 key_derivation_version: src.key_derivation_version.to_string(),
                 account_key: None,
                 view_account_key: Some(ViewAccountKey::from(&view_account_key)),
+                key_source: key_source.to_string(),
+                exportable_secrets,
             })
         } else {
             let account_key: mc_account_keys::AccountKey = mc_util_serial::decode(&src.account_key)
@@ -97,6 +117,8 @@ impl TryFrom<&Account> for AccountSecrets {
                     )
                 })?),
                 view_account_key: None,
+                key_source: key_source.to_string(),
+                exportable_secrets,
             })
         }
     }
@@ -2,9 +2,13 @@
 
 //! API definition for the Address object.
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, convert::TryFrom};
 
-use crate::db::models::AssignedSubaddress;
+use crate::{
+    db::models::AssignedSubaddress,
+    service::address,
+    util::{address_attestation, b58::b58_encode_public_address},
+};
 use serde_derive::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Default, Debug, Clone)]
@@ -44,3 +48,124 @@ impl From<&AssignedSubaddress> for Address {
         }
     }
 }
+
+/// Whether an address belongs to the network the wallet is connected to.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AddressNetworkCheck {
+    /// Whether the wallet will build transactions to the address without
+    /// allow_cross_network.
+    pub passed: bool,
+
+    /// Set when the address passed only because its network could not be
+    /// checked, such as when it has no fog info.
+    pub warning: bool,
+
+    /// Why the address passed or failed.
+    pub reasons: Vec<String>,
+}
+
+impl From<&address::AddressNetworkCheck> for AddressNetworkCheck {
+    fn from(src: &address::AddressNetworkCheck) -> AddressNetworkCheck {
+        AddressNetworkCheck {
+            passed: src.passed,
+            warning: src.warning,
+            reasons: src.reasons.clone(),
+        }
+    }
+}
+
+/// A subaddress which the wallet reserves for an account, rather than
+/// assigning it as an address.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ReservedAddress {
+    /// The index of this address in the subaddress space for the account.
+    pub subaddress_index: String,
+
+    /// What the wallet reserves the subaddress for. One of "main",
+    /// "legacy_change", "change" or "gift_code".
+    pub purpose: String,
+
+    /// A b58 encoding of the public address materials.
+    pub public_address_b58: String,
+}
+
+impl TryFrom<&address::ReservedAddress> for ReservedAddress {
+    type Error = String;
+
+    fn try_from(src: &address::ReservedAddress) -> Result<ReservedAddress, String> {
+        Ok(ReservedAddress {
+            subaddress_index: src.purpose.index().to_string(),
+            purpose: src.purpose.to_string(),
+            public_address_b58: b58_encode_public_address(&src.public_address)
+                .map_err(|e| format!("Could not b58 encode public address {:?}", e))?,
+        })
+    }
+}
+
+/// The label of an assigned address, for copying labels between wallets which
+/// hold the same account.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AddressLabel {
+    /// The index of this address in the subaddress space for the account.
+    pub subaddress_index: String,
+
+    /// A b58 encoding of the public address materials.
+    pub public_address_b58: String,
+
+    /// Additional data associated with this address.
+    pub metadata: String,
+}
+
+impl From<&address::AddressLabel> for AddressLabel {
+    fn from(src: &address::AddressLabel) -> AddressLabel {
+        AddressLabel {
+            subaddress_index: src.subaddress_index.to_string(),
+            public_address_b58: src.public_address_b58.clone(),
+            metadata: src.comment.clone(),
+        }
+    }
+}
+
+impl TryFrom<&AddressLabel> for address::AddressLabel {
+    type Error = String;
+
+    fn try_from(src: &AddressLabel) -> Result<address::AddressLabel, String> {
+        Ok(address::AddressLabel {
+            subaddress_index: src
+                .subaddress_index
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid subaddress index {}: {}", src.subaddress_index, e))?,
+            public_address_b58: src.public_address_b58.clone(),
+            comment: src.metadata.clone(),
+        })
+    }
+}
+
+/// A verified attestation that an address belongs to the wallet which signed
+/// it.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AddressAttestation {
+    /// The b58 public address the attestation is for.
+    pub public_address_b58: String,
+
+    /// The nonce the attestation was made with.
+    pub nonce: String,
+
+    /// When the attestation was made, in seconds since the Unix epoch.
+    pub timestamp: String,
+
+    /// Whether the attestation is too old, or too far in the future, to be
+    /// trusted, as it may have been replayed.
+    pub stale: bool,
+}
+
+impl AddressAttestation {
+    pub fn new(src: &address_attestation::AddressAttestation, stale: bool) -> Self {
+        AddressAttestation {
+            public_address_b58: src.public_address_b58.clone(),
+            nonce: src.nonce.clone(),
+            timestamp: src.timestamp.to_string(),
+            stale,
+        }
+    }
+}
@@ -2,25 +2,90 @@
 
 //! API definition for the Account object.
 
+use crate::util::amount_display::display_amount;
 use mc_transaction_core::TokenId;
-use serde::{Deserialize, Serialize};
-use std::convert::TryFrom;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{cell::Cell, convert::TryFrom};
+
+thread_local! {
+    /// Whether amounts built on this thread include their display fields.
+    static DISPLAY_AMOUNTS: Cell<bool> = Cell::new(false);
+}
+
+/// Restores the previous display setting when a display scope ends, including
+/// when it ends by unwinding.
+struct DisplayAmountsGuard(bool);
+
+impl Drop for DisplayAmountsGuard {
+    fn drop(&mut self) {
+        DISPLAY_AMOUNTS.with(|display| display.set(self.0));
+    }
+}
+
+/// Runs `f` with every Amount built on this thread including its
+/// human readable value and symbol, for requests which set `display`.
+pub fn with_display_amounts<R>(display: bool, f: impl FnOnce() -> R) -> R {
+    let previous = DISPLAY_AMOUNTS.with(|current| current.replace(display));
+    let _guard = DisplayAmountsGuard(previous);
+    f()
+}
 
 /// The value and token_id of a txo.
+///
+/// Both are serialized as strings. Some clients send them as JSON numbers,
+/// which are accepted as well.
 #[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq, Eq)]
 pub struct Amount {
     /// The value of a Txo
+    #[serde(deserialize_with = "string_or_number")]
     pub value: String,
 
     /// The token_id of a Txo
+    #[serde(deserialize_with = "string_or_number")]
     pub token_id: String,
+
+    /// The value in display units of the token, with the full precision of
+    /// the token's decimals. Only set when the request asks for display
+    /// fields and the token is known, and ignored when parsing.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub value_decimal: Option<String>,
+
+    /// The symbol of the token. Set along with value_decimal.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
 }
 
 impl Amount {
     pub fn new(value: u64, token_id: TokenId) -> Self {
+        let display = if DISPLAY_AMOUNTS.with(|display| display.get()) {
+            display_amount(value, token_id)
+        } else {
+            None
+        };
+        let (value_decimal, symbol) = match display {
+            Some((value_decimal, symbol)) => (Some(value_decimal), Some(symbol.to_string())),
+            None => (None, None),
+        };
+
         Self {
             value: value.to_string(),
             token_id: token_id.to_string(),
+            value_decimal,
+            symbol,
         }
     }
 }
@@ -48,3 +113,109 @@ impl TryFrom<&Amount> for mc_transaction_core::Amount {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json_rpc::v2::api::request::JsonCommandRequest;
+    use mc_transaction_core::{tokens::Mob, Token};
+
+    #[test]
+    fn test_amount_parses_client_payloads() {
+        let expected = Amount::new(42_000_000_000, TokenId::from(1));
+
+        // As returned by the v2 API.
+        let amount: Amount =
+            serde_json::from_str(r#"{"value":"42000000000","token_id":"1"}"#).unwrap();
+        assert_eq!(amount, expected);
+
+        // With a numeric token id.
+        let amount: Amount =
+            serde_json::from_str(r#"{"value":"42000000000","token_id":1}"#).unwrap();
+        assert_eq!(amount, expected);
+
+        // With a numeric value and token id.
+        let amount: Amount = serde_json::from_str(r#"{"value":42000000000,"token_id":1}"#).unwrap();
+        assert_eq!(amount, expected);
+
+        // Amounts are always serialized as strings.
+        assert_eq!(
+            serde_json::to_string(&amount).unwrap(),
+            r#"{"value":"42000000000","token_id":"1"}"#
+        );
+
+        assert!(serde_json::from_str::<Amount>(r#"{"value":-1,"token_id":"0"}"#).is_err());
+    }
+
+    #[test]
+    fn test_amount_display_fields() {
+        // Display fields are only set within a display scope.
+        let amount = Amount::new(1_500_000_000_000, Mob::ID);
+        assert_eq!(amount.value_decimal, None);
+        assert_eq!(
+            serde_json::to_string(&amount).unwrap(),
+            r#"{"value":"1500000000000","token_id":"0"}"#
+        );
+
+        let amount = with_display_amounts(true, || Amount::new(1_500_000_000_000, Mob::ID));
+        assert_eq!(
+            serde_json::to_string(&amount).unwrap(),
+            r#"{"value":"1500000000000","token_id":"0","value_decimal":"1.500000000000","symbol":"MOB"}"#
+        );
+
+        let amount = with_display_amounts(true, || Amount::new(2_500_000, TokenId::from(1)));
+        assert_eq!(amount.value_decimal, Some("2.500000".to_string()));
+        assert_eq!(amount.symbol, Some("eUSD".to_string()));
+
+        // Unknown tokens have no display fields.
+        let amount = with_display_amounts(true, || Amount::new(5, TokenId::from(8192)));
+        assert_eq!(amount.value_decimal, None);
+        assert_eq!(amount.symbol, None);
+
+        // The scope ends with the closure.
+        assert_eq!(Amount::new(1, Mob::ID).value_decimal, None);
+
+        // Display fields sent back by a client are ignored.
+        let amount: Amount = serde_json::from_str(
+            r#"{"value":"1500000000000","token_id":"0","value_decimal":"9.0","symbol":"MOB"}"#,
+        )
+        .unwrap();
+        assert_eq!(amount, Amount::new(1_500_000_000_000, Mob::ID));
+        assert_eq!(
+            mc_transaction_core::Amount::try_from(&amount).unwrap(),
+            mc_transaction_core::Amount::new(1_500_000_000_000, Mob::ID)
+        );
+    }
+
+    #[test]
+    fn test_build_transaction_parses_numeric_token_ids() {
+        let request: JsonCommandRequest = serde_json::from_str(
+            r#"{
+                "method": "build_transaction",
+                "params": {
+                    "account_id": "b0be5377a2f45b1573586ed530b2901a559d9952ea8a02f8c2dbb033a935ac17",
+                    "addresses_and_amounts": [
+                        [
+                            "3CnfnRFfGgqBbMRyk4VP5DkibxdG6cWLtTUXBuWd4LrGzPLHRAAh7YqWBxV7pqLxLZ4UmtsMa3XcMSXFiAnZTNNN3WWFPBpWCe2N6jfZPn",
+                            {"value": "42000000000", "token_id": 0}
+                        ]
+                    ]
+                }
+            }"#,
+        )
+        .unwrap();
+
+        match request {
+            JsonCommandRequest::build_transaction {
+                addresses_and_amounts: Some(addresses_and_amounts),
+                ..
+            } => {
+                assert_eq!(
+                    addresses_and_amounts[0].1,
+                    Amount::new(42_000_000_000, Mob::ID)
+                );
+            }
+            _ => panic!("expected build_transaction"),
+        }
+    }
+}
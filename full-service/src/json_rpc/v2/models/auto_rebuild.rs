@@ -0,0 +1,50 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for automatically rebuilding expired transactions.
+
+use crate::util::constants::{MAX_AUTO_REBUILD_ATTEMPTS, MAX_AUTO_REBUILD_FEE_BUMP_PERCENT};
+use serde_derive::{Deserialize, Serialize};
+
+/// Asks for a transaction to be rebuilt and resubmitted if it expires before
+/// landing in the ledger, paying the same recipients from fresh inputs.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AutoRebuild {
+    /// The most times the transaction is rebuilt.
+    pub max_attempts: String,
+
+    /// The percentage by which the fee is raised on each rebuild. Defaults to
+    /// 0, keeping the fee unchanged.
+    pub fee_bump_percent: Option<String>,
+}
+
+impl AutoRebuild {
+    /// Parse and check the settings, returning the most attempts and the fee
+    /// bump percentage.
+    pub fn parse(&self) -> Result<(u32, u32), String> {
+        let max_attempts = self
+            .max_attempts
+            .parse::<u64>()
+            .map_err(|e| format!("Invalid max_attempts: {}", e))?;
+        if max_attempts > MAX_AUTO_REBUILD_ATTEMPTS {
+            return Err(format!(
+                "max_attempts can be at most {}",
+                MAX_AUTO_REBUILD_ATTEMPTS
+            ));
+        }
+
+        let fee_bump_percent = match &self.fee_bump_percent {
+            Some(f) => f
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid fee_bump_percent: {}", e))?,
+            None => 0,
+        };
+        if fee_bump_percent > MAX_AUTO_REBUILD_FEE_BUMP_PERCENT {
+            return Err(format!(
+                "fee_bump_percent can be at most {}",
+                MAX_AUTO_REBUILD_FEE_BUMP_PERCENT
+            ));
+        }
+
+        Ok((max_attempts as u32, fee_bump_percent as u32))
+    }
+}
@@ -26,6 +26,14 @@ pub struct Balance {
     /// If the account is syncing, this value may change.
     pub unspent: String,
 
+    /// The part of unspent which has the account's minimum number of
+    /// confirmations, and can be spent.
+    pub confirmed: String,
+
+    /// The part of unspent received too recently to have the account's
+    /// minimum number of confirmations, which can not be spent yet.
+    pub unconfirmed: String,
+
     /// Pending, out-going pico MOB. The pending value will clear once the
     /// ledger processes the outgoing txos. The available_pmob will reflect the
     /// change.
@@ -51,6 +59,8 @@ impl From<&service::balance::Balance> for Balance {
             max_spendable: src.max_spendable.to_string(),
             unverified: src.unverified.to_string(),
             unspent: src.unspent.to_string(),
+            confirmed: (src.unspent - src.unconfirmed).to_string(),
+            unconfirmed: src.unconfirmed.to_string(),
             pending: src.pending.to_string(),
             spent: src.spent.to_string(),
             secreted: src.secreted.to_string(),
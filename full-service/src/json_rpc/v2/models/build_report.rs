@@ -0,0 +1,144 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the BuildReport object.
+
+use crate::service;
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A txo which was considered as an input.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct BuildReportTxo {
+    /// Salted hash of the txo id. Hashes are only comparable within a report.
+    pub id_hash: String,
+
+    pub value: String,
+
+    pub token_id: String,
+
+    pub status: String,
+}
+
+/// A recipient of the transaction.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct BuildReportOutlay {
+    /// The first characters of the recipient's b58 address.
+    pub recipient: String,
+
+    pub value: String,
+
+    pub token_id: String,
+}
+
+/// The outcome of selecting inputs for one token.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct BuildReportSelection {
+    pub token_id: String,
+
+    /// Value of the outlays in this token, including the fee if it is paid in
+    /// this token.
+    pub target_value: String,
+
+    /// The largest value which can be spent in one transaction, if inputs were
+    /// selected from the wallet.
+    pub max_spendable_in_wallet: Option<String>,
+
+    /// Salted hashes of the ids of the selected txos.
+    pub selected_txos: Vec<String>,
+
+    pub selected_value: String,
+
+    /// Why inputs could not be selected, if they could not.
+    pub failure: Option<String>,
+}
+
+/// A report of an attempted transaction build, with key material and
+/// addresses redacted.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct BuildReport {
+    /// The first characters of the account id.
+    pub account_id: String,
+
+    pub ledger_block_height: String,
+
+    pub network_block_height: String,
+
+    /// Map of token id to the fee for that token on the network.
+    pub network_fees: BTreeMap<String, String>,
+
+    pub outlays: Vec<BuildReportOutlay>,
+
+    pub fee_value: Option<String>,
+
+    pub fee_token_id: Option<String>,
+
+    pub tombstone_block: Option<String>,
+
+    /// Number of txos considered as inputs. Only the first 100 are listed.
+    pub num_candidate_txos: String,
+
+    pub candidate_txos: Vec<BuildReportTxo>,
+
+    pub selections: Vec<BuildReportSelection>,
+
+    /// The step at which the build failed, if it failed.
+    pub failed_step: Option<String>,
+
+    /// Why the build failed, if it failed.
+    pub failure: Option<String>,
+}
+
+impl From<&service::build_report::BuildReport> for BuildReport {
+    fn from(src: &service::build_report::BuildReport) -> BuildReport {
+        BuildReport {
+            account_id: src.account_id.clone(),
+            ledger_block_height: src.ledger_block_height.to_string(),
+            network_block_height: src.network_block_height.to_string(),
+            network_fees: src
+                .network_fees
+                .iter()
+                .map(|(token_id, fee)| (token_id.to_string(), fee.to_string()))
+                .collect(),
+            outlays: src
+                .outlays
+                .iter()
+                .map(|outlay| BuildReportOutlay {
+                    recipient: outlay.recipient.clone(),
+                    value: outlay.value.to_string(),
+                    token_id: outlay.token_id.to_string(),
+                })
+                .collect(),
+            fee_value: src.fee_value.map(|v| v.to_string()),
+            fee_token_id: src.fee_token_id.map(|t| t.to_string()),
+            tombstone_block: src.tombstone_block.map(|t| t.to_string()),
+            num_candidate_txos: src.num_candidate_txos.to_string(),
+            candidate_txos: src
+                .candidate_txos
+                .iter()
+                .map(|txo| BuildReportTxo {
+                    id_hash: txo.id_hash.clone(),
+                    value: txo.value.to_string(),
+                    token_id: txo.token_id.to_string(),
+                    status: txo.status.clone(),
+                })
+                .collect(),
+            selections: src
+                .selections
+                .iter()
+                .map(|selection| BuildReportSelection {
+                    token_id: selection.token_id.to_string(),
+                    target_value: selection.target_value.to_string(),
+                    max_spendable_in_wallet: selection
+                        .max_spendable_in_wallet
+                        .map(|m| m.to_string()),
+                    selected_txos: selection.selected_txos.clone(),
+                    selected_value: selection.selected_value.to_string(),
+                    failure: selection.failure.clone(),
+                })
+                .collect(),
+            failed_step: src.failed_step.map(|step| step.to_string()),
+            failure: src.failure.clone(),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Confirmation Challenge object.
+
+use crate::service;
+use serde::{Deserialize, Serialize};
+
+/// A challenge confirming one irreversible request.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ConfirmationChallenge {
+    /// The value to send back as `confirmation.challenge` with the request.
+    pub challenge: String,
+
+    /// The method the challenge confirms.
+    pub method: String,
+
+    /// What the request will do.
+    pub summary: String,
+
+    /// When the challenge expires, in seconds since the Unix epoch.
+    pub expires_at: String,
+}
+
+impl From<&service::confirmation::ConfirmationChallenge> for ConfirmationChallenge {
+    fn from(src: &service::confirmation::ConfirmationChallenge) -> ConfirmationChallenge {
+        ConfirmationChallenge {
+            challenge: src.challenge.clone(),
+            method: src.method.clone(),
+            summary: src.summary.clone(),
+            expires_at: src.expires_at.to_string(),
+        }
+    }
+}
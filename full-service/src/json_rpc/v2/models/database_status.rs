@@ -0,0 +1,64 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Database Status object.
+
+use crate::service;
+
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct DatabaseStatus {
+    /// The version of the latest migration the database has run.
+    pub schema_version: Option<String>,
+
+    /// The migrations in this build which the database has not run.
+    pub pending_migrations: Vec<String>,
+
+    /// The path of the most recent backup made before running migrations.
+    pub last_backup_path: Option<String>,
+
+    /// When the most recent backup was made, in seconds since the Unix epoch.
+    pub last_backup_created_at: Option<String>,
+
+    /// The size of the database, in bytes.
+    pub size_bytes: String,
+
+    /// The SQLite journal mode, such as wal.
+    pub journal_mode: String,
+
+    /// How often SQLite waits for writes to reach the disk.
+    pub synchronous: String,
+
+    /// Whether foreign key constraints are enforced.
+    pub foreign_keys: bool,
+
+    /// How long a statement waits for a lock held by another connection, in
+    /// milliseconds.
+    pub busy_timeout_ms: String,
+
+    /// The size of the page cache of a connection, in KiB.
+    pub cache_size_kib: String,
+}
+
+impl From<&service::database::DatabaseStatus> for DatabaseStatus {
+    fn from(src: &service::database::DatabaseStatus) -> DatabaseStatus {
+        DatabaseStatus {
+            schema_version: src.schema_version.clone(),
+            pending_migrations: src.pending_migrations.clone(),
+            last_backup_path: src
+                .last_backup
+                .as_ref()
+                .map(|backup| backup.path.to_string_lossy().to_string()),
+            last_backup_created_at: src
+                .last_backup
+                .as_ref()
+                .map(|backup| (backup.created_at_millis / 1000).to_string()),
+            size_bytes: src.size_bytes.to_string(),
+            journal_mode: src.pragmas.journal_mode.clone(),
+            synchronous: src.pragmas.synchronous.clone(),
+            foreign_keys: src.pragmas.foreign_keys,
+            busy_timeout_ms: src.pragmas.busy_timeout_ms.to_string(),
+            cache_size_kib: src.pragmas.cache_size_kib.to_string(),
+        }
+    }
+}
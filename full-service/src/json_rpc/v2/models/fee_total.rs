@@ -0,0 +1,31 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the FeeTotal object.
+
+use crate::service;
+use serde::{Deserialize, Serialize};
+
+/// The fees an account has paid in a token, for transactions which landed in
+/// the ledger.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct FeeTotal {
+    /// The token the fees were paid in.
+    pub token_id: String,
+
+    /// The month the fees were paid in, as YYYY-MM (UTC), when grouped by
+    /// month. Blocks without a signed timestamp have no month.
+    pub month: Option<String>,
+
+    /// The total value of the fees.
+    pub value: String,
+}
+
+impl From<&service::transaction_log::FeeTotal> for FeeTotal {
+    fn from(src: &service::transaction_log::FeeTotal) -> FeeTotal {
+        FeeTotal {
+            token_id: src.token_id.to_string(),
+            month: src.month.clone(),
+            value: src.value.to_string(),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Job object.
+
+use crate::service;
+use serde::{Deserialize, Serialize};
+
+/// A long-running operation, run in the background and polled by its id.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct Job {
+    /// Unique identifier for the job, used to poll and cancel it.
+    pub id: String,
+
+    /// The operation the job runs, such as "resync_account".
+    pub kind: String,
+
+    /// One of "queued", "running", "completed", "cancelled" or "failed".
+    pub status: String,
+
+    /// How much of the job's work has been done. For account resyncs, this is
+    /// the number of blocks synced.
+    pub progress: String,
+
+    /// How much work the job has to do in total, if it is known.
+    pub total: Option<String>,
+
+    /// What the job returned, once it has completed.
+    pub result: Option<serde_json::Value>,
+
+    /// Why the job failed, if it did.
+    pub error: Option<String>,
+
+    /// When the job was submitted, in seconds since the Unix epoch.
+    pub created_at: String,
+
+    /// When the job finished, in seconds since the Unix epoch.
+    pub finished_at: Option<String>,
+}
+
+impl From<&service::job::Job> for Job {
+    fn from(src: &service::job::Job) -> Job {
+        Job {
+            id: src.id.clone(),
+            kind: src.kind.clone(),
+            status: src.status.to_string(),
+            progress: src.progress.to_string(),
+            total: src.total.map(|t| t.to_string()),
+            result: src.result.clone(),
+            error: src.error.clone(),
+            created_at: src.created_at.to_string(),
+            finished_at: src.finished_at.map(|t| t.to_string()),
+        }
+    }
+}
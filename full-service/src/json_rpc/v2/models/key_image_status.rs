@@ -0,0 +1,45 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the KeyImageStatus object.
+
+use crate::service::ledger::LedgerServiceError;
+use serde::{Deserialize, Serialize};
+
+/// Whether a key image is in the ledger, meaning the txo it belongs to has
+/// been spent.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct KeyImageStatus {
+    /// The key image, as given in the request.
+    pub key_image: String,
+
+    /// Whether the key image is in the ledger. False when it could not be
+    /// checked.
+    pub spent: bool,
+
+    /// The index of the block the key image appeared in, if it is in the
+    /// ledger.
+    pub block_index: Option<String>,
+
+    /// Why the key image could not be checked, such as when it is not valid
+    /// hex.
+    pub error: Option<String>,
+}
+
+impl KeyImageStatus {
+    pub fn new(key_image: &str, result: &Result<Option<u64>, LedgerServiceError>) -> Self {
+        match result {
+            Ok(block_index) => KeyImageStatus {
+                key_image: key_image.to_string(),
+                spent: block_index.is_some(),
+                block_index: block_index.map(|index| index.to_string()),
+                error: None,
+            },
+            Err(err) => KeyImageStatus {
+                key_image: key_image.to_string(),
+                spent: false,
+                block_index: None,
+                error: Some(err.to_string()),
+            },
+        }
+    }
+}
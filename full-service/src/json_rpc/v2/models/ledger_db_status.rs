@@ -0,0 +1,53 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Ledger DB Status object.
+
+use crate::service;
+
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct LedgerDbStatus {
+    /// The size of the ledger's data file, in bytes.
+    pub file_size_bytes: String,
+
+    /// The size of LMDB's memory map, which bounds the size of the file.
+    pub map_size_bytes: String,
+
+    pub page_size: String,
+
+    /// The pages LMDB has allocated, in use or free.
+    pub total_pages: String,
+
+    /// The allocated pages which hold no data.
+    pub free_pages: String,
+
+    /// The share of the allocated pages in use, from 0 to 1.
+    pub page_utilization: String,
+
+    pub num_blocks: String,
+    pub num_txos: String,
+
+    /// When the last block was signed, in seconds since the Unix epoch.
+    pub last_block_timestamp: Option<String>,
+}
+
+impl From<&service::ledger_db::LedgerDbStatus> for LedgerDbStatus {
+    fn from(src: &service::ledger_db::LedgerDbStatus) -> LedgerDbStatus {
+        let page_utilization = match src.total_pages {
+            0 => 0.0,
+            total_pages => src.used_pages() as f64 / total_pages as f64,
+        };
+        LedgerDbStatus {
+            file_size_bytes: src.file_size_bytes.to_string(),
+            map_size_bytes: src.map_size_bytes.to_string(),
+            page_size: src.page_size.to_string(),
+            total_pages: src.total_pages.to_string(),
+            free_pages: src.free_pages.to_string(),
+            page_utilization: format!("{:.4}", page_utilization),
+            num_blocks: src.num_blocks.to_string(),
+            num_txos: src.num_txos.to_string(),
+            last_block_timestamp: src.last_block_timestamp.map(|t| t.to_string()),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the LedgerScan object.
+
+use crate::{db::txo::TxoID, service};
+use serde::{Deserialize, Serialize};
+
+/// The progress of a scan of the ledger for a view key, with the txos it has
+/// found so far.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct LedgerScan {
+    /// Unique identifier for the scan, used to poll and cancel it.
+    pub id: String,
+
+    /// One of "running", "completed", "cancelled" or "failed".
+    pub status: String,
+
+    /// Why the scan failed, if it did.
+    pub error: Option<String>,
+
+    pub first_block_index: String,
+
+    pub last_block_index: String,
+
+    /// The next block to be scanned.
+    pub next_block_index: String,
+
+    /// The txos received by the view key in the blocks scanned so far.
+    pub txos: Vec<ScannedTxo>,
+}
+
+/// A txo received by a scanned view key.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ScannedTxo {
+    /// The id the txo would have in the wallet.
+    pub txo_id: String,
+
+    /// The public key of the txo, as returned for txos in the wallet.
+    pub public_key: String,
+
+    pub value: String,
+
+    pub token_id: String,
+
+    /// The block in which the txo was received.
+    pub received_block_index: String,
+
+    /// The subaddress which received the txo, if it is within the scanned
+    /// subaddress range.
+    pub subaddress_index: Option<String>,
+}
+
+impl From<&service::ledger_scan::LedgerScan> for LedgerScan {
+    fn from(src: &service::ledger_scan::LedgerScan) -> LedgerScan {
+        LedgerScan {
+            id: src.id.clone(),
+            status: src.status.to_string(),
+            error: src.error.clone(),
+            first_block_index: src.first_block_index.to_string(),
+            last_block_index: src.last_block_index.to_string(),
+            next_block_index: src.next_block_index.to_string(),
+            txos: src.txos.iter().map(ScannedTxo::from).collect(),
+        }
+    }
+}
+
+impl From<&service::ledger_scan::ScannedTxo> for ScannedTxo {
+    fn from(src: &service::ledger_scan::ScannedTxo) -> ScannedTxo {
+        ScannedTxo {
+            txo_id: TxoID::from(&src.tx_out).to_string(),
+            public_key: hex::encode(mc_util_serial::encode(&src.tx_out.public_key)),
+            value: src.amount.value.to_string(),
+            token_id: src.amount.token_id.to_string(),
+            received_block_index: src.block_index.to_string(),
+            subaddress_index: src.subaddress_index.map(|i| i.to_string()),
+        }
+    }
+}
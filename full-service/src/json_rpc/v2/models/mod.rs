@@ -3,13 +3,31 @@ pub mod account_key;
 pub mod account_secrets;
 pub mod address;
 pub mod amount;
+pub mod auto_rebuild;
 pub mod balance;
 pub mod block;
+pub mod build_report;
+pub mod confirmation_challenge;
 pub mod confirmation_number;
+pub mod database_status;
+pub mod fee_total;
+pub mod job;
+pub mod key_image_status;
+pub mod ledger_db_status;
+pub mod ledger_scan;
 pub mod masked_amount;
 pub mod network_status;
+pub mod payment_status;
+pub mod peer_status;
+pub mod proof_of_reserves;
+pub mod public_address_keys;
 pub mod receiver_receipt;
+pub mod sender_memo;
+pub mod sync_stats;
 pub mod transaction_log;
+pub mod transaction_preview;
+pub mod transaction_report;
+pub mod transaction_ring_details;
 pub mod tx_proposal;
 pub mod txo;
 pub mod wallet_status;
@@ -21,6 +21,16 @@ pub struct NetworkStatus {
 
     /// The current block version
     pub block_version: String,
+
+    /// The smallest value of an output per token_id which the wallet will
+    /// build.
+    pub dust_thresholds: BTreeMap<String, String>,
+
+    /// The most inputs a transaction can have.
+    pub max_inputs: String,
+
+    /// The most outputs a transaction can have, including change.
+    pub max_outputs: String,
 }
 
 impl TryFrom<&service::balance::NetworkStatus> for NetworkStatus {
@@ -36,6 +46,13 @@ impl TryFrom<&service::balance::NetworkStatus> for NetworkStatus {
                 .map(|(token_id, fee)| (token_id.to_string(), fee.to_string()))
                 .collect(),
             block_version: src.block_version.to_string(),
+            dust_thresholds: src
+                .dust_thresholds
+                .iter()
+                .map(|(token_id, minimum)| (token_id.to_string(), minimum.to_string()))
+                .collect(),
+            max_inputs: src.max_inputs.to_string(),
+            max_outputs: src.max_outputs.to_string(),
         })
     }
 }
@@ -0,0 +1,74 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the PaymentStatus object.
+
+use crate::db::{
+    models::{MonitoredTxo, TransactionLog},
+    transaction_log::TransactionLogModel,
+};
+use serde::{Deserialize, Serialize};
+
+/// The confirmation status of a submitted transaction, for each of its
+/// payload outputs.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct PaymentStatus {
+    /// The transaction log of the payment.
+    pub transaction_log_id: String,
+
+    /// The status of the transaction log.
+    pub status: String,
+
+    /// The block in which the transaction was finalized, if it has been.
+    pub finalized_block_index: Option<String>,
+
+    /// The payload outputs of the transaction.
+    pub outputs: Vec<PaymentOutputStatus>,
+}
+
+/// The confirmation status of a single payload output.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct PaymentOutputStatus {
+    /// The public key of the output, as hex.
+    pub public_key: String,
+
+    /// The b58 address the output was sent to.
+    pub recipient_address_id: String,
+
+    /// The value of the output.
+    pub value: String,
+
+    /// The token id of the output.
+    pub token_id: String,
+
+    /// The block in which the output landed, if it has been seen in the
+    /// ledger.
+    pub landed_block_index: Option<String>,
+}
+
+impl PaymentStatus {
+    pub fn new(transaction_log: &TransactionLog, monitored_txos: &[MonitoredTxo]) -> Self {
+        Self {
+            transaction_log_id: transaction_log.id.clone(),
+            status: transaction_log.status().to_string(),
+            finalized_block_index: transaction_log
+                .finalized_block_index
+                .map(|b| (b as u64).to_string()),
+            outputs: monitored_txos
+                .iter()
+                .map(PaymentOutputStatus::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<&MonitoredTxo> for PaymentOutputStatus {
+    fn from(src: &MonitoredTxo) -> PaymentOutputStatus {
+        PaymentOutputStatus {
+            public_key: hex::encode(&src.public_key),
+            recipient_address_id: src.recipient_public_address_b58.clone(),
+            value: (src.value as u64).to_string(),
+            token_id: (src.token_id as u64).to_string(),
+            landed_block_index: src.landed_block_index.map(|b| (b as u64).to_string()),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Peer Status object.
+
+use crate::service;
+
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct PeerStatus {
+    /// How the peer a transaction is submitted to is chosen, "healthiest" or
+    /// "round-robin".
+    pub strategy: String,
+
+    /// The peer submissions are tried on first, if one is pinned.
+    pub pinned_responder_id: Option<String>,
+
+    /// The stats and health of each peer.
+    pub peers: Vec<PeerStats>,
+}
+
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct PeerStats {
+    /// The responder id of the peer, its host and port.
+    pub responder_id: String,
+
+    /// Whether the peer is used. A peer is unhealthy after several failed
+    /// calls in a row, until it is tried again.
+    pub healthy: bool,
+
+    /// Whether the peer was excluded by an admin.
+    pub excluded: bool,
+
+    /// Number of calls to the peer which succeeded.
+    pub num_successes: String,
+
+    /// Number of calls to the peer which failed.
+    pub num_failures: String,
+
+    /// Number of calls which failed since the last one which succeeded.
+    pub consecutive_failures: String,
+
+    /// The fraction of calls which succeeded, if any were made.
+    pub success_rate: Option<f64>,
+
+    /// Moving average of the latency of the calls, in milliseconds.
+    pub average_latency_ms: Option<String>,
+
+    /// When a call last succeeded, in seconds since the Unix epoch.
+    pub last_success_at: Option<String>,
+
+    /// When a call last failed, in seconds since the Unix epoch.
+    pub last_failure_at: Option<String>,
+
+    /// The error of the last call which failed.
+    pub last_error: Option<String>,
+}
+
+impl From<&service::peer::PeerStatus> for PeerStatus {
+    fn from(src: &service::peer::PeerStatus) -> PeerStatus {
+        PeerStatus {
+            strategy: src.strategy.to_string(),
+            pinned_responder_id: src.pinned_responder_id.clone(),
+            peers: src
+                .peers
+                .iter()
+                .map(|(responder_id, stats, healthy)| PeerStats {
+                    responder_id: responder_id.clone(),
+                    healthy: *healthy,
+                    excluded: stats.excluded,
+                    num_successes: stats.num_successes.to_string(),
+                    num_failures: stats.num_failures.to_string(),
+                    consecutive_failures: stats.consecutive_failures.to_string(),
+                    success_rate: stats.success_rate(),
+                    average_latency_ms: stats
+                        .average_latency
+                        .map(|latency| latency.as_millis().to_string()),
+                    last_success_at: stats.last_success_at.map(|at| at.to_string()),
+                    last_failure_at: stats.last_failure_at.map(|at| at.to_string()),
+                    last_error: stats.last_error.clone(),
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the ProofOfReserves object.
+
+use crate::{
+    json_rpc::v2::models::amount::Amount,
+    service::proof_of_reserves,
+    util::encoding_helpers::{hex_to_ristretto_public, ristretto_public_to_hex},
+};
+use serde_derive::{Deserialize, Serialize};
+use std::{collections::BTreeMap, convert::TryFrom};
+
+/// An unspent txo in a proof of reserves.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ReserveTxo {
+    /// Hex of the protobuf encoding of the TxOut.
+    pub tx_out_proto: String,
+
+    /// The index of the TxOut among all TxOuts in the ledger.
+    pub global_index: String,
+
+    /// Hex of the protobuf encoding of a TxOutMembershipProof, anchored at
+    /// the ledger after the block of the proof.
+    pub membership_proof: String,
+
+    /// The unmasked amount of the txo.
+    pub amount: Amount,
+
+    /// The subaddress of the account which received the txo.
+    pub subaddress_index: String,
+
+    /// Hex of the key image of the txo, which is not in the ledger up to the
+    /// block of the proof.
+    pub key_image: String,
+}
+
+impl From<&proof_of_reserves::ReserveTxo> for ReserveTxo {
+    fn from(src: &proof_of_reserves::ReserveTxo) -> Self {
+        Self {
+            tx_out_proto: hex::encode(mc_util_serial::encode(&src.tx_out)),
+            global_index: src.global_index.to_string(),
+            membership_proof: hex::encode(mc_util_serial::encode(&src.membership_proof)),
+            amount: Amount::from(&src.amount),
+            subaddress_index: src.subaddress_index.to_string(),
+            key_image: hex::encode(mc_util_serial::encode(&src.key_image)),
+        }
+    }
+}
+
+impl TryFrom<&ReserveTxo> for proof_of_reserves::ReserveTxo {
+    type Error = String;
+
+    fn try_from(src: &ReserveTxo) -> Result<Self, String> {
+        Ok(Self {
+            tx_out: mc_util_serial::decode(
+                &hex::decode(&src.tx_out_proto)
+                    .map_err(|err| format!("Could not decode hex for tx_out_proto: {:?}", err))?,
+            )
+            .map_err(|err| format!("Could not decode tx out: {:?}", err))?,
+            global_index: src
+                .global_index
+                .parse::<u64>()
+                .map_err(|err| format!("Could not parse global_index u64: {:?}", err))?,
+            membership_proof: mc_util_serial::decode(
+                &hex::decode(&src.membership_proof).map_err(|err| {
+                    format!("Could not decode hex for membership_proof: {:?}", err)
+                })?,
+            )
+            .map_err(|err| format!("Could not decode membership proof: {:?}", err))?,
+            amount: mc_transaction_core::Amount::try_from(&src.amount)?,
+            subaddress_index: src
+                .subaddress_index
+                .parse::<u64>()
+                .map_err(|err| format!("Could not parse subaddress_index u64: {:?}", err))?,
+            key_image: mc_util_serial::decode(
+                &hex::decode(&src.key_image)
+                    .map_err(|err| format!("Could not decode hex for key_image: {:?}", err))?,
+            )
+            .map_err(|err| format!("Could not decode key image: {:?}", err))?,
+        })
+    }
+}
+
+/// The txos an account had not spent as of a block, with the evidence an
+/// auditor needs to check them against the ledger.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ProofOfReserves {
+    /// The account whose reserves are proven.
+    pub account_id: String,
+
+    /// The block at which the txos were unspent.
+    pub block_index: String,
+
+    /// Hex of the root hash of the TxOut membership tree after the block.
+    pub root_hash: String,
+
+    /// Hex of the view public key of the account.
+    pub view_public_key: String,
+
+    /// Hex of the spend public keys of the subaddresses which received the
+    /// txos, keyed by subaddress index.
+    pub subaddress_spend_public_keys: BTreeMap<String, String>,
+
+    /// The unspent txos.
+    pub txos: Vec<ReserveTxo>,
+}
+
+impl From<&proof_of_reserves::ProofOfReserves> for ProofOfReserves {
+    fn from(src: &proof_of_reserves::ProofOfReserves) -> Self {
+        Self {
+            account_id: src.account_id.clone(),
+            block_index: src.block_index.to_string(),
+            root_hash: hex::encode(src.root_hash),
+            view_public_key: ristretto_public_to_hex(&src.view_public_key),
+            subaddress_spend_public_keys: src
+                .subaddress_spend_public_keys
+                .iter()
+                .map(|(index, key)| (index.to_string(), ristretto_public_to_hex(key)))
+                .collect(),
+            txos: src.txos.iter().map(ReserveTxo::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<&ProofOfReserves> for proof_of_reserves::ProofOfReserves {
+    type Error = String;
+
+    fn try_from(src: &ProofOfReserves) -> Result<Self, String> {
+        let mut root_hash = [0u8; 32];
+        hex::decode_to_slice(&src.root_hash, &mut root_hash)
+            .map_err(|err| format!("Could not decode hex for root_hash: {:?}", err))?;
+
+        Ok(Self {
+            account_id: src.account_id.clone(),
+            block_index: src
+                .block_index
+                .parse::<u64>()
+                .map_err(|err| format!("Could not parse block_index u64: {:?}", err))?,
+            root_hash,
+            view_public_key: hex_to_ristretto_public(&src.view_public_key)?,
+            subaddress_spend_public_keys: src
+                .subaddress_spend_public_keys
+                .iter()
+                .map(|(index, key)| {
+                    Ok((
+                        index.parse::<u64>().map_err(|err| {
+                            format!("Could not parse subaddress index u64: {:?}", err)
+                        })?,
+                        hex_to_ristretto_public(key)?,
+                    ))
+                })
+                .collect::<Result<BTreeMap<_, _>, String>>()?,
+            txos: src
+                .txos
+                .iter()
+                .map(proof_of_reserves::ReserveTxo::try_from)
+                .collect::<Result<Vec<_>, String>>()?,
+        })
+    }
+}
+
+/// The result of checking a proof of reserves against the ledger.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct ReservesVerification {
+    /// The total value of the txos in each token, keyed by token id.
+    pub balance_per_token: BTreeMap<String, String>,
+
+    /// Whether the amounts and ownership of the txos were checked, which
+    /// requires the account's view private key.
+    pub ownership_verified: bool,
+
+    /// Global indices of txos which were unspent at the block of the proof,
+    /// but have been spent since.
+    pub spent_since: Vec<String>,
+}
+
+impl From<&proof_of_reserves::ReservesVerification> for ReservesVerification {
+    fn from(src: &proof_of_reserves::ReservesVerification) -> Self {
+        Self {
+            balance_per_token: src
+                .balance_per_token
+                .iter()
+                .map(|(token_id, value)| (token_id.to_string(), value.to_string()))
+                .collect(),
+            ownership_verified: src.ownership_verified,
+            spent_since: src.spent_since.iter().map(|i| i.to_string()).collect(),
+        }
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for a recipient given by its public keys.
+
+use serde_derive::{Deserialize, Serialize};
+
+/// A recipient specified by its hex encoded public keys, rather than by a b58
+/// encoded public address.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct PublicAddressKeys {
+    /// The hex encoded view public key of the recipient.
+    pub view_public_hex: String,
+
+    /// The hex encoded spend public key of the recipient.
+    pub spend_public_hex: String,
+
+    /// The fog report url of the recipient, if they use fog.
+    pub fog_report_url: Option<String>,
+
+    /// The hex encoded fog authority signature. Required when a fog report url
+    /// is provided.
+    pub fog_authority_sig_hex: Option<String>,
+}
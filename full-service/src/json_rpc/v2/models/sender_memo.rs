@@ -0,0 +1,50 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the SenderMemo object.
+
+use crate::util::sender_memo::DecodedSenderMemo;
+use serde::{Deserialize, Serialize};
+
+/// A memo, decoded to find who sent the payment it is attached to.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct SenderMemo {
+    /// "unused" for an unused memo, "authenticated_sender" for a memo naming
+    /// its sender, and "other" for memos of any other type.
+    pub memo_kind: String,
+
+    /// The memo type, as hex, for memos of kind "other".
+    pub memo_type: Option<String>,
+
+    /// Whether the memo's HMAC shows that it was written by the claimed
+    /// sender, for this TXO. False unless the memo names its sender.
+    pub valid: bool,
+
+    /// The short hash of the sender's address carried by the memo, as hex.
+    pub address_hash: Option<String>,
+
+    /// The id of the payment request being paid, if the memo carries one.
+    pub payment_request_id: Option<String>,
+}
+
+impl From<&DecodedSenderMemo> for SenderMemo {
+    fn from(src: &DecodedSenderMemo) -> Self {
+        match src {
+            DecodedSenderMemo::Unused => SenderMemo {
+                memo_kind: "unused".to_string(),
+                ..Default::default()
+            },
+            DecodedSenderMemo::NotSenderMemo(memo_type) => SenderMemo {
+                memo_kind: "other".to_string(),
+                memo_type: Some(memo_type.clone()),
+                ..Default::default()
+            },
+            DecodedSenderMemo::Sender(memo) => SenderMemo {
+                memo_kind: "authenticated_sender".to_string(),
+                memo_type: None,
+                valid: memo.valid,
+                address_hash: Some(memo.address_hash_hex.clone()),
+                payment_request_id: memo.payment_request_id.map(|id| id.to_string()),
+            },
+        }
+    }
+}
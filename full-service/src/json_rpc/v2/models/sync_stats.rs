@@ -0,0 +1,38 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the Account Sync Stats object.
+
+use crate::service;
+use serde::{Deserialize, Serialize};
+
+/// Sync throughput of an account since the wallet started.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AccountSyncStats {
+    /// Number of blocks synced for the account.
+    pub blocks_synced: String,
+
+    /// Number of txos the account received in the blocks synced.
+    pub txos_received: String,
+
+    /// Number of blocks in the most recent batch.
+    pub last_batch_blocks: String,
+
+    /// How long the most recent batch took to scan and write, in
+    /// milliseconds.
+    pub last_batch_duration_ms: String,
+
+    /// Blocks synced per second over the most recent batch.
+    pub blocks_per_second: String,
+}
+
+impl From<&service::sync::AccountSyncStats> for AccountSyncStats {
+    fn from(src: &service::sync::AccountSyncStats) -> AccountSyncStats {
+        AccountSyncStats {
+            blocks_synced: src.blocks_synced.to_string(),
+            txos_received: src.txos_received.to_string(),
+            last_batch_blocks: src.last_batch_blocks.to_string(),
+            last_batch_duration_ms: src.last_batch_duration.as_millis().to_string(),
+            blocks_per_second: format!("{:.1}", src.blocks_per_second()),
+        }
+    }
+}
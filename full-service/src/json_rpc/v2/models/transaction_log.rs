@@ -52,18 +52,57 @@ pub struct TransactionLog {
     ///  The scanned block block index in which this transaction occurred.
     pub finalized_block_index: Option<String>,
 
+    /// Time at which the finalized block was signed, in seconds since the Unix
+    /// epoch. Only present when timestamps are requested, and omitted for
+    /// blocks without a timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finalized_block_timestamp: Option<String>,
+
     /// String representing the transaction log status. On "sent", valid
     /// statuses are "built", "pending", "succeeded", "failed".  On "received",
     /// the status is "succeeded".
     pub status: String,
 
-    /// Time at which sent transaction log was created. Only available if
-    /// direction is "sent". This value is null if "received" or if the sent
-    /// transactions were recovered from the ledger (is_sent_recovered = true).
+    /// Time at which sent transaction log was created, in seconds since the
+    /// Unix epoch. Only available if direction is "sent". This value is null
+    /// if "received", if the sent transactions were recovered from the ledger
+    /// (is_sent_recovered = true), or if the log was created before the time
+    /// was recorded.
     pub sent_time: Option<String>,
 
-    /// An arbitrary string attached to the object.
+    /// An arbitrary string attached to the object. Only stored by this
+    /// wallet, and never put in the memos of the transaction.
     pub comment: String,
+
+    /// The payment request id put in the memos of the transaction, when it
+    /// was built by this wallet with one.
+    pub payment_request_id: Option<String>,
+
+    /// The redemption memo of a burn transaction built by this wallet, as
+    /// hex.
+    pub burn_redemption_memo_hex: Option<String>,
+
+    /// The block version the transaction was built for. Null for transactions
+    /// logged before it was recorded.
+    pub block_version: Option<String>,
+
+    /// Whether the transaction log was reconstructed from the ledger rather
+    /// than recorded when the transaction was built, such as for an account
+    /// restored from its keys.
+    pub reconstructed: bool,
+
+    /// The short hash of the recipient's public address, as hex, when a
+    /// reconstructed transaction had a single recipient recorded in the memo
+    /// of its change.
+    pub recipient_address_hash: Option<String>,
+
+    /// The id of the transaction log which replaced this one, when the
+    /// transaction expired and was automatically rebuilt and resubmitted.
+    pub superseded_by: Option<String>,
+
+    /// How many more times the transaction will be rebuilt and resubmitted if
+    /// it expires. Null when automatic rebuilds were not requested.
+    pub auto_rebuild_attempts_left: Option<String>,
 }
 
 impl TransactionLog {
@@ -90,6 +129,7 @@ impl TransactionLog {
             finalized_block_index: transaction_log
                 .finalized_block_index
                 .map(|b| (b as u64).to_string()),
+            finalized_block_timestamp: None,
             status: transaction_log.status().to_string(),
             input_txos: associated_txos.inputs.iter().map(InputTxo::new).collect(),
             output_txos: associated_txos
@@ -104,8 +144,28 @@ impl TransactionLog {
                 .collect(),
             value_map: values,
             fee_amount: Amount::from(&transaction_log.fee_amount()),
-            sent_time: None,
+            sent_time: transaction_log
+                .created_at
+                .filter(|_| !transaction_log.reconstructed)
+                .map(|created_at| created_at.to_string()),
             comment: transaction_log.comment.clone(),
+            payment_request_id: transaction_log
+                .payment_request_id
+                .map(|id| (id as u64).to_string()),
+            burn_redemption_memo_hex: transaction_log
+                .burn_redemption_memo
+                .as_ref()
+                .map(hex::encode),
+            block_version: transaction_log.block_version.map(|v| v.to_string()),
+            reconstructed: transaction_log.reconstructed,
+            recipient_address_hash: transaction_log
+                .recipient_address_hash
+                .as_ref()
+                .map(hex::encode),
+            superseded_by: transaction_log.superseded_by.clone(),
+            auto_rebuild_attempts_left: transaction_log
+                .auto_rebuild_attempts_left
+                .map(|a| a.to_string()),
         }
     }
 }
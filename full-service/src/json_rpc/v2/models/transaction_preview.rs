@@ -0,0 +1,65 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the TransactionPreview object.
+
+use crate::{json_rpc::v2::models::amount::Amount, service};
+
+use mc_transaction_core::TokenId;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A txo which the previewed transaction would spend.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct TransactionPreviewInput {
+    pub txo_id: String,
+
+    pub amount: Amount,
+
+    /// The subaddress index the txo was received at.
+    pub subaddress_index: Option<String>,
+}
+
+/// The inputs, fee, change and tombstone of a transaction, worked out without
+/// building it.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct TransactionPreview {
+    pub input_txos: Vec<TransactionPreviewInput>,
+
+    /// The fee, including any change of the fee token below its dust
+    /// threshold.
+    pub fee: Amount,
+
+    /// The change of each token among the inputs, keyed by token id.
+    pub change_per_token: BTreeMap<String, Amount>,
+
+    /// The estimated block after which the transaction would be invalid.
+    pub tombstone_block_index: String,
+
+    /// Pass to the build methods to build from exactly these input txos.
+    pub selection_token: String,
+}
+
+impl From<&service::transaction::TransactionPreview> for TransactionPreview {
+    fn from(src: &service::transaction::TransactionPreview) -> TransactionPreview {
+        let build_preview = &src.build_preview;
+        TransactionPreview {
+            input_txos: build_preview
+                .inputs
+                .iter()
+                .map(|txo| TransactionPreviewInput {
+                    txo_id: txo.id.clone(),
+                    amount: Amount::new(txo.value as u64, TokenId::from(txo.token_id as u64)),
+                    subaddress_index: txo.subaddress_index.map(|i| (i as u64).to_string()),
+                })
+                .collect(),
+            fee: Amount::new(build_preview.fee, build_preview.fee_token_id),
+            change_per_token: build_preview
+                .change_per_token
+                .iter()
+                .map(|(token_id, change)| (token_id.to_string(), Amount::new(*change, *token_id)))
+                .collect(),
+            tombstone_block_index: build_preview.tombstone_block_index.to_string(),
+            selection_token: src.selection_token.clone(),
+        }
+    }
+}
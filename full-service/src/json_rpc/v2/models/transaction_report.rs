@@ -0,0 +1,43 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the TransactionReport object.
+
+use crate::db;
+use serde::{Deserialize, Serialize};
+
+/// A report of a submitted transaction which has not yet been delivered to
+/// the configured report sink.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct TransactionReport {
+    /// The transaction log of the reported transaction.
+    pub transaction_log_id: String,
+
+    /// The account which submitted the transaction.
+    pub account_id: String,
+
+    /// The record which will be delivered to the sink.
+    pub record: serde_json::Value,
+
+    /// The number of failed delivery attempts.
+    pub attempts: String,
+
+    /// The error from the last failed delivery attempt, if any.
+    pub last_error: Option<String>,
+
+    /// Seconds since the unix epoch when the report was queued.
+    pub created_at: String,
+}
+
+impl From<&db::models::TransactionReport> for TransactionReport {
+    fn from(src: &db::models::TransactionReport) -> TransactionReport {
+        TransactionReport {
+            transaction_log_id: src.transaction_log_id.clone(),
+            account_id: src.account_id.clone(),
+            record: serde_json::from_str(&src.record)
+                .unwrap_or_else(|_| serde_json::Value::String(src.record.clone())),
+            attempts: src.attempts.to_string(),
+            last_error: src.last_error.clone(),
+            created_at: src.created_at.to_string(),
+        }
+    }
+}
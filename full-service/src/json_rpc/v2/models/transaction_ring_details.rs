@@ -0,0 +1,79 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! API definition for the TransactionRingDetails object.
+
+use crate::db::{
+    models::{TransactionLog, Txo},
+    transaction_log::InputRing,
+};
+use serde::{Deserialize, Serialize};
+
+/// The rings the inputs of a transaction were signed with, as recorded when
+/// it was logged.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct TransactionRingDetails {
+    /// The transaction log of the transaction.
+    pub transaction_log_id: String,
+
+    /// The inputs of the transaction.
+    pub inputs: Vec<InputRingDetails>,
+}
+
+/// The ring of a single input.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct InputRingDetails {
+    /// The id of the input txo.
+    pub txo_id: String,
+
+    /// The global index of the input in the ledger. Missing when the ring was
+    /// not recorded, as for reconstructed transactions and those logged
+    /// before rings were recorded.
+    pub real_index: Option<String>,
+
+    /// The members of the ring, in the order of the ring. Empty when the ring
+    /// was not recorded.
+    pub ring: Vec<RingMember>,
+}
+
+/// A member of a ring.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct RingMember {
+    /// The global index of the member in the ledger.
+    pub index: String,
+
+    /// Whether the member is the input being spent, rather than a mixin.
+    pub is_real: bool,
+}
+
+impl TransactionRingDetails {
+    pub fn new(transaction_log: &TransactionLog, input_rings: &[(Txo, Option<InputRing>)]) -> Self {
+        Self {
+            transaction_log_id: transaction_log.id.clone(),
+            inputs: input_rings
+                .iter()
+                .map(|(txo, ring)| InputRingDetails::new(txo, ring.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+impl InputRingDetails {
+    pub fn new(txo: &Txo, ring: Option<&InputRing>) -> Self {
+        Self {
+            txo_id: txo.id.clone(),
+            real_index: ring.map(|ring| ring.real_index().to_string()),
+            ring: ring
+                .map(|ring| {
+                    ring.member_indices
+                        .iter()
+                        .enumerate()
+                        .map(|(position, index)| RingMember {
+                            index: index.to_string(),
+                            is_real: position == ring.real_position,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -3,7 +3,7 @@
 //! API definition for the TxProposal object.
 
 use super::amount::Amount as AmountJSON;
-use crate::util::b58::{b58_encode_public_address, B58Error};
+use crate::util::{b58::b58_encode_public_address, field_context::FieldContext};
 
 use serde_derive::{Deserialize, Serialize};
 use std::convert::TryFrom;
@@ -22,6 +22,11 @@ pub struct OutputTxo {
     pub amount: AmountJSON,
     pub recipient_public_address_b58: String,
     pub confirmation_number: String,
+
+    /// Whether this is a change output with a value of zero, which UIs may
+    /// hide.
+    #[serde(default)]
+    pub is_zero_change: bool,
 }
 
 #[derive(Deserialize, Serialize, Default, Debug)]
@@ -32,6 +37,34 @@ pub struct TxProposal {
     pub fee_amount: AmountJSON,
     pub tombstone_block_index: String,
     pub tx_proto: String,
+
+    /// The block version the transaction was built for. Submitting fails once
+    /// the network has moved to another block version.
+    #[serde(default)]
+    pub block_version: Option<String>,
+}
+
+impl TxProposal {
+    /// An estimate of the size of the proposal once serialized, from its hex
+    /// encoded protobufs, which make up nearly all of it.
+    pub fn estimated_size(&self) -> usize {
+        let input_txos_size: usize = self
+            .input_txos
+            .iter()
+            .map(|input_txo| input_txo.tx_out_proto.len() + input_txo.key_image.len())
+            .sum();
+        let output_txos_size: usize = self
+            .payload_txos
+            .iter()
+            .chain(self.change_txos.iter())
+            .map(|output_txo| {
+                output_txo.tx_out_proto.len()
+                    + output_txo.recipient_public_address_b58.len()
+                    + output_txo.confirmation_number.len()
+            })
+            .sum();
+        self.tx_proto.len() + input_txos_size + output_txos_size
+    }
 }
 
 impl TryFrom<&crate::service::models::tx_proposal::TxProposal> for TxProposal {
@@ -52,34 +85,38 @@ impl TryFrom<&crate::service::models::tx_proposal::TxProposal> for TxProposal {
         let payload_txos = src
             .payload_txos
             .iter()
-            .map(|output_txo| {
+            .enumerate()
+            .map(|(i, output_txo)| {
                 Ok(OutputTxo {
                     tx_out_proto: hex::encode(mc_util_serial::encode(&output_txo.tx_out)),
                     amount: AmountJSON::from(&output_txo.amount),
                     recipient_public_address_b58: b58_encode_public_address(
                         &output_txo.recipient_public_address,
-                    )?,
+                    )
+                    .field_context(|| format!("payload_txos[{}].recipient_public_address", i))?,
                     confirmation_number: hex::encode(output_txo.confirmation_number.as_ref()),
+                    is_zero_change: false,
                 })
             })
-            .collect::<Result<Vec<OutputTxo>, B58Error>>()
-            .map_err(|_| "Error".to_string())?;
+            .collect::<Result<Vec<OutputTxo>, String>>()?;
 
         let change_txos = src
             .change_txos
             .iter()
-            .map(|output_txo| {
+            .enumerate()
+            .map(|(i, output_txo)| {
                 Ok(OutputTxo {
                     tx_out_proto: hex::encode(mc_util_serial::encode(&output_txo.tx_out)),
                     amount: AmountJSON::from(&output_txo.amount),
                     recipient_public_address_b58: b58_encode_public_address(
                         &output_txo.recipient_public_address,
-                    )?,
+                    )
+                    .field_context(|| format!("change_txos[{}].recipient_public_address", i))?,
                     confirmation_number: hex::encode(output_txo.confirmation_number.as_ref()),
+                    is_zero_change: output_txo.amount.value == 0,
                 })
             })
-            .collect::<Result<Vec<OutputTxo>, B58Error>>()
-            .map_err(|_| "Error".to_string())?;
+            .collect::<Result<Vec<OutputTxo>, String>>()?;
 
         Ok(Self {
             input_txos,
@@ -88,6 +125,7 @@ impl TryFrom<&crate::service::models::tx_proposal::TxProposal> for TxProposal {
             tx_proto: hex::encode(mc_util_serial::encode(&src.tx)),
             fee_amount: AmountJSON::new(src.tx.prefix.fee, src.tx.prefix.fee_token_id.into()),
             tombstone_block_index: src.tx.prefix.tombstone_block.to_string(),
+            block_version: src.block_version.map(|v| (*v).to_string()),
         })
     }
 }
@@ -60,6 +60,27 @@ pub struct Txo {
     /// A confirmation number that the sender of the Txo can provide to verify
     /// that they participated in the construction of this Txo.
     pub confirmation: Option<String>,
+
+    /// Time at which the block in which this Txo was received was signed, in
+    /// seconds since the Unix epoch. Only present when timestamps are
+    /// requested, and omitted for blocks without a timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub received_block_timestamp: Option<String>,
+
+    /// Time at which the block in which this Txo was spent was signed, in
+    /// seconds since the Unix epoch. Only present when timestamps are
+    /// requested, and omitted for blocks without a timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spent_block_timestamp: Option<String>,
+
+    /// Number of blocks in the local ledger from the block in which this Txo
+    /// was received, counting that block, up to the tip. Compared against the
+    /// account's min_confirmations to decide whether the Txo can be spent.
+    pub num_confirmations: Option<String>,
+
+    /// Index of this Txo among all txos in the ledger, for cross-referencing
+    /// with other ledger tools. Null until the Txo is in the ledger.
+    pub global_index: Option<String>,
 }
 
 impl Txo {
@@ -78,6 +99,10 @@ impl Txo {
             subaddress_index: txo.subaddress_index.map(|s| (s as u64).to_string()),
             key_image: txo.key_image.as_ref().map(|k| hex::encode(&k)),
             confirmation: txo.shared_secret.as_ref().map(hex::encode),
+            received_block_timestamp: None,
+            spent_block_timestamp: None,
+            num_confirmations: None,
+            global_index: txo.global_index.map(|i| (i as u64).to_string()),
         }
     }
 }
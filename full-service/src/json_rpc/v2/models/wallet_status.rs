@@ -2,8 +2,11 @@
 
 //! API definition for the Wallet Status object.
 
-use crate::{json_rpc::v2::models::balance::Balance, service};
+use crate::{
+    json_rpc::v2::models::balance::Balance, service, util::constants::UNSIGNED_TX_SCHEMA_VERSION,
+};
 
+use mc_transaction_core::TokenId;
 use serde_derive::{Deserialize, Serialize};
 use std::{collections::BTreeMap, convert::TryFrom};
 
@@ -27,6 +30,27 @@ pub struct WalletStatus {
     pub min_synced_block_index: String,
 
     pub balance_per_token: BTreeMap<String, Balance>,
+
+    /// Version of the unsigned transactions built by this wallet, for signers
+    /// to check that they can parse them.
+    pub unsigned_tx_schema_version: String,
+
+    /// The number of txos received by accounts which share their view private
+    /// key with another account in the wallet, for example the same keys
+    /// imported with and without fog. Each of these txos appears under more
+    /// than one account, but is only counted once in the balances.
+    pub duplicate_txo_count: String,
+
+    /// Whether the wallet is shutting down. Other requests are turned away
+    /// with a retriable error, so load balancers should stop routing to it.
+    pub shutting_down: bool,
+
+    /// Whether the ledger is read-only, and kept in sync by another process
+    /// rather than by this wallet.
+    pub ledger_read_only: bool,
+
+    /// The number of blocks the local ledger is behind the network.
+    pub ledger_height_lag: String,
 }
 
 impl TryFrom<&service::balance::WalletStatus> for WalletStatus {
@@ -43,6 +67,99 @@ impl TryFrom<&service::balance::WalletStatus> for WalletStatus {
                 .iter()
                 .map(|(k, v)| (k.to_string(), Balance::from(v)))
                 .collect(),
+            unsigned_tx_schema_version: UNSIGNED_TX_SCHEMA_VERSION.to_string(),
+            duplicate_txo_count: src.duplicate_txo_count.to_string(),
+            shutting_down: src.shutting_down,
+            ledger_read_only: src.ledger_read_only,
+            ledger_height_lag: src.ledger_height_lag.to_string(),
         })
     }
 }
+
+/// A rollup of the balances and sync state of the whole wallet.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct WalletRollup {
+    /// The block count of MobileCoin's distributed ledger.
+    pub network_block_height: String,
+
+    /// The local block count downloaded from the ledger.
+    pub local_block_height: String,
+
+    /// The sum of the balances of all accounts.
+    pub balance_per_token: BTreeMap<String, Balance>,
+
+    /// The number of accounts which have processed every block up to the
+    /// network_block_height.
+    pub num_accounts_synced: String,
+
+    /// The number of accounts which are no more than the behind threshold
+    /// from the network_block_height.
+    pub num_accounts_syncing: String,
+
+    /// The number of accounts which are further than the behind threshold
+    /// from the network_block_height.
+    pub num_accounts_behind: String,
+
+    /// The number of submitted transactions which have neither landed nor
+    /// failed.
+    pub num_pending_transactions: String,
+
+    /// The rollup of each account, keyed by account id, when requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<BTreeMap<String, AccountRollup>>,
+}
+
+/// The balances and sync state of a single account.
+#[derive(Deserialize, Serialize, Default, Debug, Clone)]
+pub struct AccountRollup {
+    /// The next block the account will process.
+    pub account_block_height: String,
+
+    pub balance_per_token: BTreeMap<String, Balance>,
+
+    /// The number of submitted transactions of the account which have
+    /// neither landed nor failed.
+    pub num_pending_transactions: String,
+}
+
+fn balance_per_token_json(
+    balance_per_token: &BTreeMap<TokenId, service::balance::Balance>,
+) -> BTreeMap<String, Balance> {
+    balance_per_token
+        .iter()
+        .map(|(k, v)| (k.to_string(), Balance::from(v)))
+        .collect()
+}
+
+impl From<&service::balance::WalletRollup> for WalletRollup {
+    fn from(src: &service::balance::WalletRollup) -> WalletRollup {
+        WalletRollup {
+            network_block_height: src.network_block_height.to_string(),
+            local_block_height: src.local_block_height.to_string(),
+            balance_per_token: balance_per_token_json(&src.balance_per_token),
+            num_accounts_synced: src.num_accounts_synced.to_string(),
+            num_accounts_syncing: src.num_accounts_syncing.to_string(),
+            num_accounts_behind: src.num_accounts_behind.to_string(),
+            num_pending_transactions: src.num_pending_transactions.to_string(),
+            accounts: src.accounts.as_ref().map(|accounts| {
+                accounts
+                    .iter()
+                    .map(|account| {
+                        (
+                            account.account_id.to_string(),
+                            AccountRollup {
+                                account_block_height: account.next_block_index.to_string(),
+                                balance_per_token: balance_per_token_json(
+                                    &account.balance_per_token,
+                                ),
+                                num_pending_transactions: account
+                                    .num_pending_transactions
+                                    .to_string(),
+                            },
+                        )
+                    })
+                    .collect()
+            }),
+        }
+    }
+}
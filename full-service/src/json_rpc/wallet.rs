@@ -4,8 +4,15 @@
 
 use crate::{
     json_rpc::{
-        json_rpc_request::JsonRPCRequest,
-        json_rpc_response::JsonRPCResponse,
+        capability::{Capability, CapabilitySet, ListenerCapabilities},
+        json_rpc_request::{JsonRPCCall, JsonRPCRequest},
+        json_rpc_response::{
+            format_account_not_permitted_error, format_not_permitted_error,
+            format_request_too_large_error, format_unauthorized_error, JsonRPCCallResponse,
+            JsonRPCError, JsonRPCResponse,
+        },
+        request_limits::{oversized_body, LimitedJson},
+        scoped_api_key::{AccountScope, ScopedApiKeysState},
         v1::api::{
             request::help_str as help_str_v1,
             response::JsonCommandResponse as JsonCommandResponse_v1,
@@ -14,10 +21,14 @@ use crate::{
         v2::api::{
             request::help_str as help_str_v2,
             response::JsonCommandResponse as JsonCommandResponse_v2,
+            stream::{generic_wallet_stream_api, NdjsonResponse},
             wallet::generic_wallet_api as generic_wallet_api_v2,
         },
     },
-    service::WalletService,
+    service::{
+        health::{HealthConfig, HealthReport, HealthService},
+        WalletService,
+    },
 };
 use mc_connection::{
     BlockchainConnection, HardcodedCredentialsProvider, ThickClient, UserTxConnection,
@@ -25,9 +36,13 @@ use mc_connection::{
 use mc_fog_report_validation::{FogPubkeyResolver, FogResolver};
 use mc_validator_connection::ValidatorConnection;
 use rocket::{
-    self, get, http::Status, outcome::Outcome, post, request::FromRequest, routes, Request, State,
+    self, catch, catchers, get, http::Status, outcome::Outcome, post, request::FromRequest,
+    response::status, routes, Catcher, Request, State,
 };
 use rocket_contrib::json::Json;
+use std::{collections::BTreeSet, sync::Arc};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
 
 /// State managed by rocket.
 pub struct WalletState<
@@ -35,16 +50,96 @@ pub struct WalletState<
     FPR: FogPubkeyResolver + Send + Sync + 'static,
 > {
     /// The Wallet Service implementation.
-    pub service: WalletService<T, FPR>,
+    pub service: Arc<WalletService<T, FPR>>,
+
+    /// The largest number of requests accepted in a single batch.
+    pub max_batch_size: usize,
 }
 
 pub const API_KEY_HEADER: &str = "X-API-KEY";
 
+pub const SECRETS_API_KEY_HEADER: &str = "X-SECRETS-API-KEY";
+
 pub struct APIKeyState(pub String);
 
+/// The key required, on top of the API key, by methods which expose private
+/// key material. When it is empty or not managed, those methods only require
+/// the API key.
+pub struct SecretsAPIKeyState(pub String);
+
 /// Ensures check for a pre-shared symmetric API key for the JsonRPC loop on the
-/// Mobilecoin wallet.
-pub struct ApiKeyGuard {}
+/// Mobilecoin wallet, whether the request may call methods which expose
+/// secrets, and which methods and accounts the listener it arrived on and its
+/// key permit.
+#[derive(Clone)]
+pub struct ApiKeyGuard {
+    /// Whether the request presented the secrets API key, or none is
+    /// configured.
+    pub secrets_authorized: bool,
+
+    /// The capabilities granted by the listener the request arrived on, and
+    /// by its key if the key is scoped.
+    pub capabilities: CapabilitySet,
+
+    /// The accounts a scoped key may reach, or None if the request may reach
+    /// every account.
+    pub account_ids: Option<BTreeSet<String>>,
+}
+
+impl ApiKeyGuard {
+    /// Checks that the request may call the given method.
+    pub fn check_method(
+        &self,
+        method: &str,
+        capability: Capability,
+        exposes_secrets: bool,
+    ) -> Result<(), JsonRPCError> {
+        if !self.capabilities.contains(capability) {
+            return Err(format_not_permitted_error(
+                method,
+                capability,
+                &self.capabilities,
+            ));
+        }
+        if exposes_secrets && !self.secrets_authorized {
+            return Err(format_unauthorized_error(format!(
+                "The {} method requires the {} header",
+                method, SECRETS_API_KEY_HEADER
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the request may reach the accounts of the given scope.
+    pub fn check_accounts(&self, method: &str, scope: &AccountScope) -> Result<(), JsonRPCError> {
+        let account_ids = match &self.account_ids {
+            Some(account_ids) => account_ids,
+            None => return Ok(()),
+        };
+        match scope {
+            AccountScope::None | AccountScope::Filtered => Ok(()),
+            AccountScope::Accounts(scope_account_ids) => {
+                match scope_account_ids
+                    .iter()
+                    .find(|account_id| !account_ids.contains(**account_id))
+                {
+                    Some(account_id) => Err(format_account_not_permitted_error(
+                        method,
+                        Some(*account_id),
+                    )),
+                    None => Ok(()),
+                }
+            }
+            AccountScope::Wallet => Err(format_account_not_permitted_error(method, None)),
+        }
+    }
+}
+
+/// Compares keys in constant time, so that the time taken does not reveal how
+/// much of the configured key was guessed.
+fn keys_match(local_key: &str, client_key: &str) -> bool {
+    local_key.as_bytes().ct_eq(client_key.as_bytes()).into()
+}
 
 #[derive(Debug)]
 pub enum ApiKeyError {
@@ -58,23 +153,205 @@ impl<'a, 'r> FromRequest<'a, 'r> for ApiKeyGuard {
         req: &'a Request<'r>,
     ) -> Outcome<Self, (rocket::http::Status, Self::Error), ()> {
         let client_key = req.headers().get_one(API_KEY_HEADER).unwrap_or_default();
+        let (scoped_key, scoped_keys_configured) = match req.guard::<State<ScopedApiKeysState>>() {
+            Outcome::Success(scoped_keys) => (
+                scoped_keys
+                    .0
+                    .iter()
+                    .find(|scoped_key| keys_match(&scoped_key.key, client_key))
+                    .cloned(),
+                !scoped_keys.0.is_empty(),
+            ),
+            _ => (None, false),
+        };
         let local_key = &req
             .guard::<State<APIKeyState>>()
             .expect("api key state config is bad. see main.rs")
             .0;
-        if local_key == client_key {
-            Outcome::Success(ApiKeyGuard {})
-        } else {
-            Outcome::Failure((Status::Unauthorized, ApiKeyError::Invalid))
+        // Once scoped keys are configured, an unset main key no longer lets
+        // requests without a key reach every account.
+        let unscoped_allowed = !(local_key.is_empty() && scoped_keys_configured);
+        if scoped_key.is_none() && !(unscoped_allowed && keys_match(local_key, client_key)) {
+            return Outcome::Failure((Status::Unauthorized, ApiKeyError::Invalid));
         }
+
+        let secrets_authorized = match req.guard::<State<SecretsAPIKeyState>>() {
+            Outcome::Success(secrets_key) if !secrets_key.0.is_empty() => keys_match(
+                &secrets_key.0,
+                req.headers()
+                    .get_one(SECRETS_API_KEY_HEADER)
+                    .unwrap_or_default(),
+            ),
+            _ => true,
+        };
+        let listener_capabilities = match req.guard::<State<ListenerCapabilities>>() {
+            Outcome::Success(capabilities) => capabilities.0.clone(),
+            _ => CapabilitySet::all(),
+        };
+        let (capabilities, account_ids) = match scoped_key {
+            Some(scoped_key) if scoped_key.is_admin() => (
+                listener_capabilities.intersection(&scoped_key.capabilities),
+                None,
+            ),
+            Some(scoped_key) => (
+                listener_capabilities.intersection(&scoped_key.capabilities),
+                Some(scoped_key.account_ids),
+            ),
+            None => (listener_capabilities, None),
+        };
+        Outcome::Success(ApiKeyGuard {
+            secrets_authorized,
+            capabilities,
+            account_ids,
+        })
     }
 }
 
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Identifier used to correlate the log lines and the response of a single
+/// JSON RPC request. Taken from the X-Request-Id header if provided, otherwise
+/// generated.
+pub struct RequestId(pub String);
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestId {
+    type Error = ();
+
+    fn from_request(req: &'a Request<'r>) -> Outcome<Self, (rocket::http::Status, ()), ()> {
+        let request_id = match req.headers().get_one(REQUEST_ID_HEADER) {
+            Some(request_id) if !request_id.is_empty() => request_id.to_string(),
+            _ => Uuid::new_v4().to_string(),
+        };
+        Outcome::Success(RequestId(request_id))
+    }
+}
+
+/// Reports a request which failed the API key check as a JSON RPC error,
+/// instead of with the default HTML body.
+#[catch(401)]
+fn unauthorized(req: &Request) -> Json<JsonRPCResponse<JsonCommandResponse_v2>> {
+    let request_id = match req.guard::<RequestId>() {
+        Outcome::Success(request_id) => Some(request_id.0),
+        _ => None,
+    };
+    Json(JsonRPCResponse {
+        method: None,
+        result: None,
+        error: Some(format_unauthorized_error(format!(
+            "Missing or invalid {} header",
+            API_KEY_HEADER
+        ))),
+        jsonrpc: "2.0".to_string(),
+        id: serde_json::Value::Null,
+        request_id,
+    })
+}
+
+/// Reports a request whose body is larger than the limit of its route as a
+/// JSON RPC error, stating the limit and the size received.
+#[catch(413)]
+fn payload_too_large(req: &Request) -> Json<JsonRPCResponse<JsonCommandResponse_v2>> {
+    let request_id = match req.guard::<RequestId>() {
+        Outcome::Success(request_id) => Some(request_id.0),
+        _ => None,
+    };
+    let oversized = oversized_body(req);
+    Json(JsonRPCResponse {
+        method: None,
+        result: None,
+        error: Some(format_request_too_large_error(
+            oversized.limit,
+            oversized.received,
+        )),
+        jsonrpc: "2.0".to_string(),
+        id: serde_json::Value::Null,
+        request_id,
+    })
+}
+
+/// The catchers shared by every instance of the Rocket server.
+pub fn api_catchers() -> Vec<Catcher> {
+    catchers![unauthorized, payload_too_large]
+}
+
 #[get("/health")]
 fn health() -> Result<(), ()> {
     Ok(())
 }
 
+/// Responds to a health check with 200 if every check passed, and otherwise
+/// with 503 and the checks which failed.
+fn health_response(report: HealthReport) -> status::Custom<Json<HealthReport>> {
+    let status = if report.is_healthy() {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    status::Custom(status, Json(report))
+}
+
+/// Checks that the wallet is alive, with the configured thresholds or the
+/// defaults if none are managed.
+pub fn generic_health_live<T, FPR>(
+    service: &WalletService<T, FPR>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let config = config.map(|config| config.inner().clone());
+    health_response(service.check_liveness(&config.unwrap_or_default()))
+}
+
+/// Checks that the wallet is ready to serve requests, with the configured
+/// thresholds or the defaults if none are managed.
+pub fn generic_health_ready<T, FPR>(
+    service: &WalletService<T, FPR>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    let config = config.map(|config| config.inner().clone());
+    health_response(service.check_readiness(&config.unwrap_or_default()))
+}
+
+/// The liveness probe, which does not require the API key.
+#[get("/health/live")]
+fn consensus_backed_health_live(
+    state: rocket::State<WalletState<ThickClient<HardcodedCredentialsProvider>, FogResolver>>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_live(&state.service, config)
+}
+
+#[get("/health/live")]
+fn validator_backed_health_live(
+    state: rocket::State<WalletState<ValidatorConnection, FogResolver>>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_live(&state.service, config)
+}
+
+/// The readiness probe, which does not require the API key.
+#[get("/health/ready")]
+fn consensus_backed_health_ready(
+    state: rocket::State<WalletState<ThickClient<HardcodedCredentialsProvider>, FogResolver>>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_ready(&state.service, config)
+}
+
+#[get("/health/ready")]
+fn validator_backed_health_ready(
+    state: rocket::State<WalletState<ValidatorConnection, FogResolver>>,
+    config: Option<State<HealthConfig>>,
+) -> status::Custom<Json<HealthReport>> {
+    generic_health_ready(&state.service, config)
+}
+
 #[get("/wallet")]
 fn wallet_help_v1() -> Result<String, String> {
     Ok(help_str_v1())
@@ -84,19 +361,21 @@ fn wallet_help_v1() -> Result<String, String> {
 #[post("/wallet", format = "json", data = "<command>")]
 fn consensus_backed_wallet_api_v1(
     _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<ThickClient<HardcodedCredentialsProvider>, FogResolver>>,
-    command: Json<JsonRPCRequest>,
+    command: LimitedJson<JsonRPCRequest>,
 ) -> Result<Json<JsonRPCResponse<JsonCommandResponse_v1>>, String> {
-    generic_wallet_api_v1(_api_key_guard, state, command)
+    generic_wallet_api_v1(_api_key_guard, request_id, state, Json(command.0))
 }
 
 #[post("/wallet", format = "json", data = "<command>")]
 fn validator_backed_wallet_api_v1(
     _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<ValidatorConnection, FogResolver>>,
-    command: Json<JsonRPCRequest>,
+    command: LimitedJson<JsonRPCRequest>,
 ) -> Result<Json<JsonRPCResponse<JsonCommandResponse_v1>>, String> {
-    generic_wallet_api_v1(_api_key_guard, state, command)
+    generic_wallet_api_v1(_api_key_guard, request_id, state, Json(command.0))
 }
 
 #[get("/wallet/v2")]
@@ -108,19 +387,52 @@ fn wallet_help_v2() -> Result<String, String> {
 #[post("/wallet/v2", format = "json", data = "<command>")]
 fn consensus_backed_wallet_api_v2(
     _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<ThickClient<HardcodedCredentialsProvider>, FogResolver>>,
-    command: Json<JsonRPCRequest>,
-) -> Result<Json<JsonRPCResponse<JsonCommandResponse_v2>>, String> {
-    generic_wallet_api_v2(_api_key_guard, state, command)
+    command: LimitedJson<JsonRPCCall>,
+) -> Result<Json<JsonRPCCallResponse<JsonCommandResponse_v2>>, String> {
+    generic_wallet_api_v2(_api_key_guard, request_id, state, Json(command.0))
 }
 
 #[post("/wallet/v2", format = "json", data = "<command>")]
 fn validator_backed_wallet_api_v2(
     _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
+    state: rocket::State<WalletState<ValidatorConnection, FogResolver>>,
+    command: LimitedJson<JsonRPCCall>,
+) -> Result<Json<JsonRPCCallResponse<JsonCommandResponse_v2>>, String> {
+    generic_wallet_api_v2(_api_key_guard, request_id, state, Json(command.0))
+}
+
+/// The route for streaming large listings from the Full Service Wallet API.
+#[post("/wallet/v2/stream", format = "json", data = "<command>")]
+fn consensus_backed_wallet_stream_api(
+    _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
+    state: rocket::State<WalletState<ThickClient<HardcodedCredentialsProvider>, FogResolver>>,
+    command: LimitedJson<JsonRPCRequest>,
+) -> Result<NdjsonResponse<ThickClient<HardcodedCredentialsProvider>, FogResolver>, String> {
+    generic_wallet_stream_api(
+        _api_key_guard,
+        request_id,
+        &state.inner().service,
+        Json(command.0),
+    )
+}
+
+#[post("/wallet/v2/stream", format = "json", data = "<command>")]
+fn validator_backed_wallet_stream_api(
+    _api_key_guard: ApiKeyGuard,
+    request_id: RequestId,
     state: rocket::State<WalletState<ValidatorConnection, FogResolver>>,
-    command: Json<JsonRPCRequest>,
-) -> Result<Json<JsonRPCResponse<JsonCommandResponse_v2>>, String> {
-    generic_wallet_api_v2(_api_key_guard, state, command)
+    command: LimitedJson<JsonRPCRequest>,
+) -> Result<NdjsonResponse<ValidatorConnection, FogResolver>, String> {
+    generic_wallet_stream_api(
+        _api_key_guard,
+        request_id,
+        &state.inner().service,
+        Json(command.0),
+    )
 }
 
 /// Returns an instance of a Rocket server.
@@ -134,11 +446,15 @@ pub fn consensus_backed_rocket(
             routes![
                 consensus_backed_wallet_api_v1,
                 consensus_backed_wallet_api_v2,
+                consensus_backed_wallet_stream_api,
                 wallet_help_v1,
                 wallet_help_v2,
-                health
+                health,
+                consensus_backed_health_live,
+                consensus_backed_health_ready
             ],
         )
+        .register(api_catchers())
         .manage(state)
 }
 
@@ -152,10 +468,14 @@ pub fn validator_backed_rocket(
             routes![
                 validator_backed_wallet_api_v1,
                 validator_backed_wallet_api_v2,
+                validator_backed_wallet_stream_api,
                 wallet_help_v1,
                 wallet_help_v2,
-                health
+                health,
+                validator_backed_health_live,
+                validator_backed_health_ready
             ],
         )
+        .register(api_catchers())
         .manage(state)
 }
@@ -0,0 +1,380 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Bootstrapping of a new ledger from a trusted snapshot.
+//!
+//! A snapshot is the data.mdb of a ledger, compressed with gzip. It is
+//! downloaded into the ledger directory, where an interrupted download is
+//! resumed with a range request the next time the wallet starts. The download
+//! is checked against the expected SHA-256 hash before it is unpacked, and the
+//! latest block of the unpacked ledger against the trusted block ID before the
+//! ledger is used.
+
+use displaydoc::Display;
+use flate2::read::GzDecoder;
+use mc_common::logger::{log, Logger};
+use mc_ledger_db::{Ledger, LedgerDB};
+use reqwest::{blocking::Client, header::RANGE, StatusCode};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// The name of the snapshot being downloaded, in the ledger directory.
+const PARTIAL_SNAPSHOT_FILE: &str = "snapshot.mdb.gz.partial";
+
+/// The name of the snapshot being unpacked, in the ledger directory.
+const UNPACKING_SNAPSHOT_FILE: &str = "data.mdb.unpacking";
+
+/// Errors while bootstrapping the ledger from a snapshot.
+#[derive(Display, Debug)]
+pub enum LedgerBootstrapError {
+    /// Error downloading the snapshot: {0}
+    Reqwest(reqwest::Error),
+
+    /// IO error: {0}
+    Io(io::Error),
+
+    /// Error with LedgerDB: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Snapshot hash mismatch, expected {expected}, got {actual}
+    HashMismatch { expected: String, actual: String },
+
+    /// The snapshot ledger has no blocks
+    EmptyLedger,
+
+    /// Snapshot latest block mismatch, expected {expected}, got {actual}
+    BlockIdMismatch { expected: String, actual: String },
+}
+
+impl From<reqwest::Error> for LedgerBootstrapError {
+    fn from(src: reqwest::Error) -> Self {
+        Self::Reqwest(src)
+    }
+}
+
+impl From<io::Error> for LedgerBootstrapError {
+    fn from(src: io::Error) -> Self {
+        Self::Io(src)
+    }
+}
+
+impl From<mc_ledger_db::Error> for LedgerBootstrapError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+/// A trusted snapshot of the ledger.
+#[derive(Clone, Debug)]
+pub struct LedgerSnapshot {
+    /// URL of the gzip compressed data.mdb.
+    pub url: String,
+
+    /// Hex encoded SHA-256 hash of the compressed data.mdb.
+    pub sha256: String,
+
+    /// Hex encoded ID of the latest block in the snapshot.
+    pub block_id: String,
+}
+
+impl LedgerSnapshot {
+    /// Downloads the snapshot into the ledger directory, resuming a previous
+    /// download if there is one, and unpacks it into data.mdb once its hash
+    /// has been verified. A download which does not match the hash is
+    /// removed.
+    pub fn download_and_unpack(
+        &self,
+        ledger_path: &Path,
+        logger: &Logger,
+    ) -> Result<(), LedgerBootstrapError> {
+        fs::create_dir_all(ledger_path)?;
+        let partial_path = ledger_path.join(PARTIAL_SNAPSHOT_FILE);
+        self.download(&partial_path, logger)?;
+
+        let actual = sha256_hex(&partial_path)?;
+        if actual != self.sha256.to_lowercase() {
+            fs::remove_file(&partial_path)?;
+            return Err(LedgerBootstrapError::HashMismatch {
+                expected: self.sha256.clone(),
+                actual,
+            });
+        }
+
+        log::info!(logger, "Unpacking ledger snapshot into {:?}", ledger_path);
+        let unpacking_path = ledger_path.join(UNPACKING_SNAPSHOT_FILE);
+        let mut decoder = GzDecoder::new(File::open(&partial_path)?);
+        let mut unpacking = File::create(&unpacking_path)?;
+        if let Err(e) = io::copy(&mut decoder, &mut unpacking).and_then(|_| unpacking.sync_all()) {
+            let _ = fs::remove_file(&unpacking_path);
+            return Err(e.into());
+        }
+        fs::rename(&unpacking_path, ledger_path.join("data.mdb"))?;
+        fs::remove_file(&partial_path)?;
+
+        Ok(())
+    }
+
+    /// Checks that the latest block of the unpacked ledger is the trusted
+    /// block, and returns the number of blocks in the ledger. A ledger which
+    /// does not match is removed.
+    pub fn verify_latest_block(&self, ledger_path: &Path) -> Result<u64, LedgerBootstrapError> {
+        let result = latest_block_id(ledger_path).and_then(|(num_blocks, actual)| {
+            if actual == self.block_id.to_lowercase() {
+                Ok(num_blocks)
+            } else {
+                Err(LedgerBootstrapError::BlockIdMismatch {
+                    expected: self.block_id.clone(),
+                    actual,
+                })
+            }
+        });
+
+        if result.is_err() {
+            for file in ["data.mdb", "lock.mdb"] {
+                let _ = fs::remove_file(ledger_path.join(file));
+            }
+        }
+        result
+    }
+
+    /// Downloads the snapshot to partial_path, continuing from the end of
+    /// what has already been downloaded there.
+    fn download(&self, partial_path: &Path, logger: &Logger) -> Result<(), LedgerBootstrapError> {
+        let offset = fs::metadata(partial_path).map_or(0, |metadata| metadata.len());
+
+        // Content encoding would make the range refer to the encoded bytes.
+        let client = Client::builder().gzip(false).use_rustls_tls().build()?;
+        let mut request = client.get(&self.url);
+        if offset > 0 {
+            log::info!(
+                logger,
+                "Resuming ledger snapshot download at {} bytes",
+                offset
+            );
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let response = request.send()?;
+        if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            // The previous download finished before the wallet stopped.
+            return Ok(());
+        }
+        let mut response = response.error_for_status()?;
+
+        // A server which ignores the range sends the whole snapshot again.
+        let (mut file, mut downloaded) = if response.status() == StatusCode::PARTIAL_CONTENT {
+            (OpenOptions::new().append(true).open(partial_path)?, offset)
+        } else {
+            (File::create(partial_path)?, 0)
+        };
+        let total = response.content_length().map(|length| downloaded + length);
+
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut logged_percent = None;
+        loop {
+            let read = response.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            file.write_all(&buffer[..read])?;
+            downloaded += read as u64;
+
+            if let Some(total) = total {
+                let percent = downloaded * 100 / total.max(1);
+                if logged_percent != Some(percent) {
+                    log::info!(logger, "Bootstrapping ledger: {}%", percent);
+                    logged_percent = Some(percent);
+                }
+            }
+        }
+        file.sync_all()?;
+
+        Ok(())
+    }
+}
+
+/// The hex encoded SHA-256 hash of a file.
+fn sha256_hex(path: &Path) -> Result<String, LedgerBootstrapError> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// The number of blocks in the ledger, and the hex encoded ID of its latest
+/// block.
+fn latest_block_id(ledger_path: &Path) -> Result<(u64, String), LedgerBootstrapError> {
+    let ledger_db = LedgerDB::open(ledger_path)?;
+    let num_blocks = ledger_db.num_blocks()?;
+    if num_blocks == 0 {
+        return Err(LedgerBootstrapError::EmptyLedger);
+    }
+    let block = ledger_db.get_block(num_blocks - 1)?;
+    Ok((num_blocks, hex::encode(&block.id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_block_to_ledger_db, generate_ledger_db, MOB};
+    use flate2::{write::GzEncoder, Compression};
+    use mc_account_keys::AccountKey;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_transaction_core::ring_signature::KeyImage;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+    use tempdir::TempDir;
+
+    /// A compressed ledger of three blocks, and the ID of its latest block.
+    fn snapshot_fixture(rng: &mut StdRng) -> (Vec<u8>, String) {
+        let ledger_db_tmp = TempDir::new("snapshot_ledger_db").unwrap();
+        let ledger_path = ledger_db_tmp.path();
+        let mut ledger_db = generate_ledger_db(ledger_path.to_str().unwrap());
+        for _ in 0..3 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &[AccountKey::random(rng).default_subaddress()],
+                100 * MOB,
+                &[KeyImage::from(rng.next_u64())],
+                rng,
+            );
+        }
+        let block_id = hex::encode(&ledger_db.get_block(2).unwrap().id);
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(&fs::read(ledger_path.join("data.mdb")).unwrap())
+            .unwrap();
+        (encoder.finish().unwrap(), block_id)
+    }
+
+    /// Serves the body over HTTP, honoring range requests, and returns its URL
+    /// and the start of the range of each request received.
+    fn serve(body: Vec<u8>) -> (String, Arc<Mutex<Vec<Option<usize>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/ledger.mdb.gz", listener.local_addr().unwrap());
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let received_ranges = ranges.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(start) = line.to_lowercase().strip_prefix("range: bytes=") {
+                        range = Some(start.trim().trim_end_matches('-').parse().unwrap());
+                    }
+                }
+                received_ranges.lock().unwrap().push(range);
+
+                let start = range.unwrap_or(0);
+                let status = match range {
+                    Some(start) if start >= body.len() => "416 Range Not Satisfiable",
+                    Some(_) => "206 Partial Content",
+                    None => "200 OK",
+                };
+                let content = &body[start.min(body.len())..];
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    content.len()
+                )
+                .unwrap();
+                stream.write_all(content).unwrap();
+            }
+        });
+        (url, ranges)
+    }
+
+    // An interrupted download is resumed, and the unpacked ledger is verified.
+    #[test_with_logger]
+    fn test_bootstrap_resumes_download(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (snapshot, block_id) = snapshot_fixture(&mut rng);
+        let (url, ranges) = serve(snapshot.clone());
+
+        let ledger_db_tmp = TempDir::new("ledger_db").unwrap();
+        let ledger_path = ledger_db_tmp.path();
+        let half = snapshot.len() / 2;
+        fs::write(ledger_path.join(PARTIAL_SNAPSHOT_FILE), &snapshot[..half]).unwrap();
+
+        let ledger_snapshot = LedgerSnapshot {
+            url,
+            sha256: hex::encode(Sha256::digest(&snapshot)),
+            block_id,
+        };
+        ledger_snapshot
+            .download_and_unpack(ledger_path, &logger)
+            .unwrap();
+        assert_eq!(*ranges.lock().unwrap(), vec![Some(half)]);
+        assert!(!ledger_path.join(PARTIAL_SNAPSHOT_FILE).exists());
+
+        assert_eq!(ledger_snapshot.verify_latest_block(ledger_path).unwrap(), 3);
+        assert_eq!(
+            LedgerDB::open(ledger_path).unwrap().num_blocks().unwrap(),
+            3
+        );
+    }
+
+    // A download which does not match the expected hash is removed without
+    // being unpacked.
+    #[test_with_logger]
+    fn test_bootstrap_hash_mismatch(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (snapshot, block_id) = snapshot_fixture(&mut rng);
+        let mut corrupted = snapshot.clone();
+        corrupted[0] ^= 1;
+        let (url, _ranges) = serve(corrupted);
+
+        let ledger_db_tmp = TempDir::new("ledger_db").unwrap();
+        let ledger_path = ledger_db_tmp.path();
+        let ledger_snapshot = LedgerSnapshot {
+            url,
+            sha256: hex::encode(Sha256::digest(&snapshot)),
+            block_id,
+        };
+        match ledger_snapshot.download_and_unpack(ledger_path, &logger) {
+            Err(LedgerBootstrapError::HashMismatch { .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(!ledger_path.join(PARTIAL_SNAPSHOT_FILE).exists());
+        assert!(!ledger_path.join("data.mdb").exists());
+    }
+
+    // A ledger whose latest block is not the trusted block is removed.
+    #[test_with_logger]
+    fn test_bootstrap_block_id_mismatch(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let (snapshot, _block_id) = snapshot_fixture(&mut rng);
+        let (url, _ranges) = serve(snapshot.clone());
+
+        let ledger_db_tmp = TempDir::new("ledger_db").unwrap();
+        let ledger_path = ledger_db_tmp.path();
+        let ledger_snapshot = LedgerSnapshot {
+            url,
+            sha256: hex::encode(Sha256::digest(&snapshot)),
+            block_id: hex::encode([0u8; 32]),
+        };
+        ledger_snapshot
+            .download_and_unpack(ledger_path, &logger)
+            .unwrap();
+        match ledger_snapshot.verify_latest_block(ledger_path) {
+            Err(LedgerBootstrapError::BlockIdMismatch { .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        assert!(!ledger_path.join("data.mdb").exists());
+    }
+}
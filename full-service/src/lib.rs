@@ -10,6 +10,8 @@ pub mod db;
 mod error;
 pub mod fog_resolver;
 pub mod json_rpc;
+pub mod ledger_bootstrap;
+mod read_only_ledger;
 pub mod service;
 pub mod unsigned_tx;
 pub mod util;
@@ -17,6 +19,7 @@ mod validator_ledger_sync;
 
 pub use db::WalletDb;
 pub use json_rpc::wallet;
+pub use read_only_ledger::ReadOnlyLedgerThread;
 pub use service::WalletService;
 pub use validator_ledger_sync::ValidatorLedgerSyncThread;
 
@@ -32,4 +35,6 @@ extern crate rocket_contrib;
 extern crate diesel_migrations;
 
 #[cfg(any(test, feature = "test_utils"))]
-mod test_utils;
+pub mod test_harness;
+#[cfg(any(test, feature = "test_utils"))]
+pub mod test_utils;
@@ -0,0 +1,145 @@
+// Copyright (c) 2018-2022 MobileCoin, Inc.
+
+//! Following a ledger which another process, such as mobilecoind or another
+//! full-service, owns and keeps in sync.
+//!
+//! Every read of the LedgerDB begins its own LMDB read transaction, so blocks
+//! appended by the owning process are visible to the next read and no reader
+//! is held across blocks. This thread polls the network state, which the
+//! ledger sync threads would otherwise poll, and logs the ledger's progress.
+
+use mc_common::logger::{log, Logger};
+use mc_connection::BlockchainConnection;
+use mc_ledger_db::{Ledger, LedgerDB};
+use mc_ledger_sync::{NetworkState, PollingNetworkState};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long the ledger may fall behind the network without growing before a
+/// warning is logged that the owning process is not keeping it in sync.
+pub const READ_ONLY_LEDGER_STALL_WARNING: Duration = Duration::from_secs(300);
+
+pub struct ReadOnlyLedgerThread {
+    join_handle: Option<thread::JoinHandle<()>>,
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl ReadOnlyLedgerThread {
+    /// Start following the ledger. The network state is polled unless it is
+    /// None, as in offline mode.
+    pub fn new<T: BlockchainConnection + 'static>(
+        poll_interval: Duration,
+        ledger_db: LedgerDB,
+        network_state: Option<Arc<RwLock<PollingNetworkState<T>>>>,
+        logger: Logger,
+    ) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+
+        let thread_stop_requested = stop_requested.clone();
+        let join_handle = Some(
+            thread::Builder::new()
+                .name("ReadOnlyLedger".into())
+                .spawn(move || {
+                    Self::thread_entrypoint(
+                        poll_interval,
+                        ledger_db,
+                        network_state,
+                        logger,
+                        thread_stop_requested,
+                    );
+                })
+                .expect("Failed spawning ReadOnlyLedger thread"),
+        );
+
+        Self {
+            join_handle,
+            stop_requested,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.join_handle.take() {
+            thread.join().expect("thread join failed");
+        }
+    }
+
+    fn thread_entrypoint<T: BlockchainConnection + 'static>(
+        poll_interval: Duration,
+        ledger_db: LedgerDB,
+        network_state: Option<Arc<RwLock<PollingNetworkState<T>>>>,
+        logger: Logger,
+        stop_requested: Arc<AtomicBool>,
+    ) {
+        log::info!(logger, "ReadOnlyLedger thread started");
+
+        let mut last_num_blocks = 0;
+        let mut last_growth = Instant::now();
+        let mut stall_logged = false;
+
+        loop {
+            if stop_requested.load(Ordering::SeqCst) {
+                log::debug!(logger, "ReadOnlyLedgerThread stop requested.");
+                break;
+            }
+
+            let network_block_height = network_state.as_ref().map(|network_state| {
+                let mut network_state = network_state.write().expect("network_state lock poisoned");
+                network_state.poll();
+                network_state
+                    .highest_block_index_on_network()
+                    .map(|index| index + 1)
+                    .unwrap_or_default()
+            });
+
+            match ledger_db.num_blocks() {
+                Ok(num_blocks) if num_blocks > last_num_blocks => {
+                    log::debug!(
+                        logger,
+                        "Read-only ledger has {} blocks, network has {:?}",
+                        num_blocks,
+                        network_block_height
+                    );
+                    last_num_blocks = num_blocks;
+                    last_growth = Instant::now();
+                    stall_logged = false;
+                }
+                Ok(num_blocks) => {
+                    let behind = network_block_height
+                        .map(|height| height > num_blocks)
+                        .unwrap_or(false);
+                    if behind
+                        && !stall_logged
+                        && last_growth.elapsed() >= READ_ONLY_LEDGER_STALL_WARNING
+                    {
+                        log::warn!(
+                            logger,
+                            "Read-only ledger has not grown from {} blocks in {:?}, while the network has {:?}. Is the process which owns the ledger syncing it?",
+                            num_blocks,
+                            last_growth.elapsed(),
+                            network_block_height
+                        );
+                        stall_logged = true;
+                    }
+                }
+                Err(err) => {
+                    log::error!(logger, "Failed reading read-only ledger: {}", err);
+                }
+            }
+
+            thread::sleep(poll_interval);
+        }
+    }
+}
+
+impl Drop for ReadOnlyLedgerThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
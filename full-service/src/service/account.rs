@@ -6,18 +6,26 @@ use crate::{
     db::{
         account::{AccountID, AccountModel},
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, AssignedSubaddress, Txo},
+        idempotency_key::unix_timestamp_now,
+        models::{Account, AssignedSubaddress, TransactionLog, Txo},
         transaction,
+        transaction_log::{TransactionLogFilter, TransactionLogModel},
         txo::TxoModel,
-        WalletDbError,
+        Conn, WalletDbError,
     },
     json_rpc::{json_rpc_request::JsonRPCRequest, v2::api::request::JsonCommandRequest},
     service::{
+        job::{Job, JobServiceError},
         ledger::{LedgerService, LedgerServiceError},
+        models::tx_proposal::ChangeKeys,
+        sync::{rescan_account, resync_account},
         WalletService,
     },
     util::{
-        constants::MNEMONIC_KEY_DERIVATION_VERSION,
+        constants::{
+            MNEMONIC_KEY_DERIVATION_VERSION, RECENT_FIRST_BLOCK_INDEX_WARNING_BLOCKS,
+            REMOVED_ACCOUNT_RETENTION_SECONDS,
+        },
         encoding_helpers::{
             hex_to_ristretto, hex_to_ristretto_public, ristretto_public_to_hex, ristretto_to_hex,
         },
@@ -72,6 +80,33 @@ pub enum AccountServiceError {
 
     /// JSON Rpc Request was formatted incorrectly
     InvalidJsonRPCRequest,
+
+    /// Account has not been removed: {0}
+    AccountNotRemoved(AccountID),
+
+    /// Account was removed too long ago to be restored: {0}
+    RemovedAccountRetentionExpired(AccountID),
+
+    /// The confirmation does not match the name of the account to purge: {0}
+    PurgeConfirmationMismatch(AccountID),
+
+    /// Error with the Job Service: {0}
+    JobService(JobServiceError),
+
+    /// Invalid transaction default: {0}
+    InvalidTransactionDefault(String),
+
+    /// An account with the same view private key is already in the wallet: {0}
+    ViewKeyInUse(AccountID),
+
+    /// Cannot rescan from block {0}, the ledger has {1} blocks
+    RescanPastLedger(u64, u64),
+}
+
+impl From<JobServiceError> for AccountServiceError {
+    fn from(src: JobServiceError) -> Self {
+        Self::JobService(src)
+    }
 }
 
 impl From<WalletDbError> for AccountServiceError {
@@ -135,6 +170,11 @@ pub trait AccountService {
     ) -> Result<Account, AccountServiceError>;
 
     /// Import an existing account to the wallet using the mnemonic.
+    ///
+    /// Accounts sharing a view private key receive the same txos, so the
+    /// import is refused if another account in the wallet has the same view
+    /// private key, for example the same mnemonic with different fog info,
+    /// unless force is set.
     #[allow(clippy::too_many_arguments)]
     fn import_account(
         &self,
@@ -146,9 +186,11 @@ pub trait AccountService {
         fog_report_url: String,
         fog_report_id: String,
         fog_authority_spki: String,
+        force: bool,
     ) -> Result<Account, AccountServiceError>;
 
-    /// Import an existing account to the wallet using the entropy.
+    /// Import an existing account to the wallet using the entropy. The import
+    /// is refused as for import_account unless force is set.
     #[allow(clippy::too_many_arguments)]
     fn import_account_from_legacy_root_entropy(
         &self,
@@ -159,9 +201,11 @@ pub trait AccountService {
         fog_report_url: String,
         fog_report_id: String,
         fog_authority_spki: String,
+        force: bool,
     ) -> Result<Account, AccountServiceError>;
 
-    /// Import an existing account to the wallet using the mnemonic.
+    /// Import an existing account to the wallet using the mnemonic. The import
+    /// is refused as for import_account unless force is set.
     fn import_view_only_account(
         &self,
         view_private_key: String,
@@ -169,6 +213,7 @@ pub trait AccountService {
         name: Option<String>,
         first_block_index: Option<u64>,
         next_subaddress_index: Option<u64>,
+        force: bool,
     ) -> Result<Account, AccountServiceError>;
 
     fn get_view_only_account_import_request(
@@ -186,6 +231,9 @@ pub trait AccountService {
     /// Get an account in the wallet.
     fn get_account(&self, account_id: &AccountID) -> Result<Account, AccountServiceError>;
 
+    /// Get the keys which identify change outputs sent by an account.
+    fn get_change_keys(&self, account_id: &AccountID) -> Result<ChangeKeys, AccountServiceError>;
+
     fn get_next_subaddress_index_for_account(
         &self,
         account_id: &AccountID,
@@ -198,6 +246,41 @@ pub trait AccountService {
         name: String,
     ) -> Result<Account, AccountServiceError>;
 
+    /// Update the number of unassigned subaddresses past the highest assigned
+    /// subaddress which are checked for incoming txos during sync.
+    fn update_account_subaddress_gap_limit(
+        &self,
+        account_id: &AccountID,
+        subaddress_gap_limit: u64,
+    ) -> Result<Account, AccountServiceError>;
+
+    /// Update the number of outputs the change of each transaction built by
+    /// the account is divided between.
+    fn update_account_split_change_into(
+        &self,
+        account_id: &AccountID,
+        split_change_into: u64,
+    ) -> Result<Account, AccountServiceError>;
+
+    /// Update the number of blocks a received txo must be in the ledger for
+    /// before the account can spend it. None uses the wallet default.
+    fn update_account_min_confirmations(
+        &self,
+        account_id: &AccountID,
+        min_confirmations: Option<u64>,
+    ) -> Result<Account, AccountServiceError>;
+
+    /// Update the fee and tombstone offset used by transactions built by the
+    /// account which do not specify them. None uses the wallet defaults. A
+    /// default fee value only applies to fees paid in the default fee token.
+    fn update_account_transaction_defaults(
+        &self,
+        account_id: &AccountID,
+        default_fee_value: Option<u64>,
+        default_fee_token_id: Option<u64>,
+        default_tombstone_offset: Option<u64>,
+    ) -> Result<Account, AccountServiceError>;
+
     /// complete a sync request for a view only account
     fn sync_account(
         &self,
@@ -206,8 +289,52 @@ pub trait AccountService {
         next_subaddress_index: u64,
     ) -> Result<(), AccountServiceError>;
 
-    /// Remove an account from the wallet.
+    /// Start a background job which syncs the account again from its first
+    /// block, picking up txos which were missed. The job's progress counts the
+    /// blocks synced so far.
+    fn resync_account(&self, account_id: &AccountID) -> Result<Job, AccountServiceError>;
+
+    /// Start a background job which forgets the txos the account received and
+    /// spent from the given block onwards, then syncs it again from that
+    /// block, by default its first block. Use this when an account was
+    /// imported with a first block index which was too late. The job's
+    /// progress counts the blocks synced so far.
+    fn rescan_account(
+        &self,
+        account_id: &AccountID,
+        from_block_index: Option<u64>,
+    ) -> Result<Job, AccountServiceError>;
+
+    /// Remove an account from the wallet. The account is hidden from listings,
+    /// balances and sync, but its keys and history are kept so that it can be
+    /// restored. Once the retention window has passed it is purged; to delete
+    /// its keys sooner, purge it with purge_account.
     fn remove_account(&self, account_id: &AccountID) -> Result<bool, AccountServiceError>;
+
+    /// Return a removed account to the wallet, if it was removed within the
+    /// retention window.
+    fn restore_removed_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Account, AccountServiceError>;
+
+    /// Delete an account, removed or not, along with its txos, subaddresses
+    /// and transaction logs. The confirmation must match the account name.
+    fn purge_account(
+        &self,
+        account_id: &AccountID,
+        confirm_account_name: &str,
+    ) -> Result<bool, AccountServiceError>;
+
+    /// Describe what removing an account would do, without removing it.
+    fn describe_remove_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<String, AccountServiceError>;
+
+    /// Describe what purging an account would delete, without deleting it.
+    fn describe_purge_account(&self, account_id: &AccountID)
+        -> Result<String, AccountServiceError>;
 }
 
 impl<T, FPR> AccountService for WalletService<T, FPR>
@@ -222,7 +349,7 @@ where
         fog_report_id: String,
         fog_authority_spki: String,
     ) -> Result<Account, AccountServiceError> {
-        log::info!(self.logger, "Creating account {:?}", name,);
+        log::info!(self.request_logger(), "Creating account {:?}", name,);
 
         // Generate entropy for the account
         let mnemonic = Mnemonic::new(MnemonicType::Words24, Language::English);
@@ -274,13 +401,15 @@ where
         fog_report_url: String,
         fog_report_id: String,
         fog_authority_spki: String,
+        force: bool,
     ) -> Result<Account, AccountServiceError> {
         log::info!(
-            self.logger,
+            self.request_logger(),
             "Importing account {:?} with first block: {:?}",
             name,
             first_block_index,
         );
+        self.warn_if_first_block_index_recent(first_block_index)?;
 
         if key_derivation_version != MNEMONIC_KEY_DERIVATION_VERSION {
             return Err(AccountServiceError::UnknownKeyDerivation(
@@ -304,7 +433,7 @@ where
 
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
-            Ok(Account::import(
+            let account = Account::import(
                 &mnemonic,
                 name,
                 import_block,
@@ -314,7 +443,9 @@ where
                 fog_report_id,
                 fog_authority_spki,
                 &conn,
-            )?)
+            )?;
+            self.check_view_key_not_in_use(&account, force, &conn)?;
+            Ok(account)
         })
     }
 
@@ -327,13 +458,15 @@ where
         fog_report_url: String,
         fog_report_id: String,
         fog_authority_spki: String,
+        force: bool,
     ) -> Result<Account, AccountServiceError> {
         log::info!(
-            self.logger,
+            self.request_logger(),
             "Importing account {:?} with first block: {:?}",
             name,
             first_block_index,
         );
+        self.warn_if_first_block_index_recent(first_block_index)?;
         // Get account key from entropy
         let mut entropy_bytes = [0u8; 32];
         hex::decode_to_slice(entropy, &mut entropy_bytes)?;
@@ -344,7 +477,7 @@ where
 
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
-            Ok(Account::import_legacy(
+            let account = Account::import_legacy(
                 &RootEntropy::from(&entropy_bytes),
                 name,
                 import_block,
@@ -354,7 +487,9 @@ where
                 fog_report_id,
                 fog_authority_spki,
                 &conn,
-            )?)
+            )?;
+            self.check_view_key_not_in_use(&account, force, &conn)?;
+            Ok(account)
         })
     }
 
@@ -365,13 +500,15 @@ where
         name: Option<String>,
         first_block_index: Option<u64>,
         next_subaddress_index: Option<u64>,
+        force: bool,
     ) -> Result<Account, AccountServiceError> {
         log::info!(
-            self.logger,
+            self.request_logger(),
             "Importing view only account {:?} with first block: {:?}",
             name,
             first_block_index,
         );
+        self.warn_if_first_block_index_recent(first_block_index)?;
 
         let view_private_key =
             hex_to_ristretto(&view_private_key).map_err(AccountServiceError::Base64DecodeError)?;
@@ -382,7 +519,7 @@ where
 
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
-            Ok(Account::import_view_only(
+            let account = Account::import_view_only(
                 &view_private_key,
                 &spend_public_key,
                 name,
@@ -390,7 +527,9 @@ where
                 first_block_index,
                 next_subaddress_index,
                 &conn,
-            )?)
+            )?;
+            self.check_view_key_not_in_use(&account, force, &conn)?;
+            Ok(account)
         })
     }
 
@@ -415,6 +554,7 @@ where
             name: Some(account.name.clone()),
             first_block_index: Some(account.first_block_index.to_string()),
             next_subaddress_index: Some(account.next_subaddress_index(&conn)?.to_string()),
+            force: None,
         };
 
         let src_json: serde_json::Value = serde_json::json!(json_command_request);
@@ -449,6 +589,19 @@ where
         Ok(Account::get(account_id, &conn)?)
     }
 
+    fn get_change_keys(&self, account_id: &AccountID) -> Result<ChangeKeys, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+
+        let view_private_key = account.view_private_key()?;
+        let change_public_address = account.change_subaddress(&conn)?.public_address()?;
+
+        Ok(ChangeKeys {
+            view_private_key,
+            change_public_address,
+        })
+    }
+
     fn get_next_subaddress_index_for_account(
         &self,
         account_id: &AccountID,
@@ -468,6 +621,69 @@ where
         Ok(Account::get(account_id, &conn)?)
     }
 
+    fn update_account_subaddress_gap_limit(
+        &self,
+        account_id: &AccountID,
+        subaddress_gap_limit: u64,
+    ) -> Result<Account, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?.update_subaddress_gap_limit(subaddress_gap_limit, &conn)?;
+        Ok(Account::get(account_id, &conn)?)
+    }
+
+    fn update_account_split_change_into(
+        &self,
+        account_id: &AccountID,
+        split_change_into: u64,
+    ) -> Result<Account, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?.update_split_change_into(split_change_into.max(1), &conn)?;
+        Ok(Account::get(account_id, &conn)?)
+    }
+
+    fn update_account_min_confirmations(
+        &self,
+        account_id: &AccountID,
+        min_confirmations: Option<u64>,
+    ) -> Result<Account, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?.update_min_confirmations(min_confirmations, &conn)?;
+        Ok(Account::get(account_id, &conn)?)
+    }
+
+    fn update_account_transaction_defaults(
+        &self,
+        account_id: &AccountID,
+        default_fee_value: Option<u64>,
+        default_fee_token_id: Option<u64>,
+        default_tombstone_offset: Option<u64>,
+    ) -> Result<Account, AccountServiceError> {
+        if default_fee_value == Some(0) {
+            return Err(AccountServiceError::InvalidTransactionDefault(
+                "default_fee_value must be at least 1".to_string(),
+            ));
+        }
+        if default_fee_value.is_some() && default_fee_token_id.is_none() {
+            return Err(AccountServiceError::InvalidTransactionDefault(
+                "default_fee_value requires default_fee_token_id".to_string(),
+            ));
+        }
+        if default_tombstone_offset == Some(0) {
+            return Err(AccountServiceError::InvalidTransactionDefault(
+                "default_tombstone_offset must be greater than 0".to_string(),
+            ));
+        }
+
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?.update_transaction_defaults(
+            default_fee_value,
+            default_fee_token_id,
+            default_tombstone_offset,
+            &conn,
+        )?;
+        Ok(Account::get(account_id, &conn)?)
+    }
+
     fn sync_account(
         &self,
         account_id: &AccountID,
@@ -501,29 +717,274 @@ where
         Ok(())
     }
 
+    fn resync_account(&self, account_id: &AccountID) -> Result<Job, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?;
+
+        let ledger_db = self.ledger_db.clone();
+        let wallet_db = self.wallet_db.clone();
+        let logger = self.logger.clone();
+        let account_id_hex = account_id.to_string();
+        let job = self.jobs.submit("resync_account", move |context| {
+            let next_block_index =
+                resync_account(&ledger_db, &wallet_db, &account_id_hex, context, &logger)
+                    .map_err(|e| e.to_string())?;
+            Ok(Some(serde_json::json!({
+                "account_id": account_id_hex,
+                "next_block_index": next_block_index.to_string(),
+            })))
+        })?;
+        log::info!(
+            self.request_logger(),
+            "Resyncing account {} in job {}",
+            account_id,
+            job.id
+        );
+
+        Ok(job)
+    }
+
+    fn rescan_account(
+        &self,
+        account_id: &AccountID,
+        from_block_index: Option<u64>,
+    ) -> Result<Job, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        let from_block_index = from_block_index.unwrap_or(account.first_block_index as u64);
+        let num_blocks = self.ledger_db.num_blocks()?;
+        if from_block_index > num_blocks {
+            return Err(AccountServiceError::RescanPastLedger(
+                from_block_index,
+                num_blocks,
+            ));
+        }
+
+        let ledger_db = self.ledger_db.clone();
+        let wallet_db = self.wallet_db.clone();
+        let logger = self.logger.clone();
+        let account_id_hex = account_id.to_string();
+        let job = self.jobs.submit("rescan_account", move |context| {
+            let next_block_index = rescan_account(
+                &ledger_db,
+                &wallet_db,
+                &account_id_hex,
+                from_block_index,
+                context,
+                &logger,
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(Some(serde_json::json!({
+                "account_id": account_id_hex,
+                "from_block_index": from_block_index.to_string(),
+                "next_block_index": next_block_index.to_string(),
+            })))
+        })?;
+        log::info!(
+            self.request_logger(),
+            "Rescanning account {} from block {} in job {}",
+            account_id,
+            from_block_index,
+            job.id
+        );
+
+        Ok(job)
+    }
+
     fn remove_account(&self, account_id: &AccountID) -> Result<bool, AccountServiceError> {
-        log::info!(self.logger, "Deleting account {}", account_id,);
+        log::info!(self.request_logger(), "Removing account {}", account_id,);
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
             let account = Account::get(account_id, &conn)?;
+            account.mark_removed(unix_timestamp_now(), &conn)?;
+            Ok(true)
+        })
+    }
+
+    fn restore_removed_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Account, AccountServiceError> {
+        log::info!(self.request_logger(), "Restoring account {}", account_id,);
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let account = Account::get_including_removed(account_id, &conn)?;
+            let removed_at = account
+                .removed_at
+                .ok_or_else(|| AccountServiceError::AccountNotRemoved(account_id.clone()))?;
+
+            let retention_cutoff = unix_timestamp_now() - REMOVED_ACCOUNT_RETENTION_SECONDS as i64;
+            if removed_at < retention_cutoff {
+                return Err(AccountServiceError::RemovedAccountRetentionExpired(
+                    account_id.clone(),
+                ));
+            }
+
+            account.unmark_removed(&conn)?;
+            Ok(Account::get(account_id, &conn)?)
+        })
+    }
+
+    fn purge_account(
+        &self,
+        account_id: &AccountID,
+        confirm_account_name: &str,
+    ) -> Result<bool, AccountServiceError> {
+        log::info!(self.request_logger(), "Purging account {}", account_id,);
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let account = Account::get_including_removed(account_id, &conn)?;
+            if account.name != confirm_account_name {
+                return Err(AccountServiceError::PurgeConfirmationMismatch(
+                    account_id.clone(),
+                ));
+            }
             account.delete(&conn)?;
             Ok(true)
         })
     }
+
+    fn describe_remove_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<String, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        Ok(format!(
+            "Will remove account \"{}\" ({}) from listings, balances and sync. Its keys and history are kept, and it can be restored for {} days, after which it is purged.",
+            account.name,
+            account_id,
+            REMOVED_ACCOUNT_RETENTION_SECONDS / 86_400,
+        ))
+    }
+
+    fn describe_purge_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<String, AccountServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get_including_removed(account_id, &conn)?;
+        let num_subaddresses =
+            AssignedSubaddress::list_all(Some(account_id.to_string()), None, None, &conn)?.len();
+        let num_txos = Txo::list_for_account(
+            &account_id.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )?
+        .len();
+        let num_transaction_logs = TransactionLog::list_all(
+            Some(account_id.to_string()),
+            None,
+            None,
+            &TransactionLogFilter::default(),
+            &conn,
+        )?
+        .len();
+        Ok(format!(
+            "Will delete account \"{}\" ({}) with {} subaddresses, {} txos and {} transaction logs. This cannot be undone.",
+            account.name, account_id, num_subaddresses, num_txos, num_transaction_logs,
+        ))
+    }
+}
+
+impl<T, FPR> WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    /// Checks that no other account in the wallet shares the view private key
+    /// of a newly imported account. If one does, the import is refused unless
+    /// it is forced, in which case a warning is logged. Txos received by both
+    /// accounts are recorded under whichever of them received them first.
+    fn check_view_key_not_in_use(
+        &self,
+        account: &Account,
+        force: bool,
+        conn: &Conn,
+    ) -> Result<(), AccountServiceError> {
+        let twins: Vec<Account> =
+            Account::list_by_view_private_key(&account.view_private_key()?, conn)?
+                .into_iter()
+                .filter(|twin| twin.id != account.id)
+                .collect();
+
+        if let Some(twin) = twins.first() {
+            if !force {
+                return Err(AccountServiceError::ViewKeyInUse(AccountID(
+                    twin.id.clone(),
+                )));
+            }
+            log::warn!(
+                self.request_logger(),
+                "Imported account {} shares its view private key with {:?}",
+                account.id,
+                twins.iter().map(|twin| &twin.id).collect::<Vec<_>>(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Warns when an account is imported with a first block index past the
+    /// local ledger, or within the last few blocks of it. Such an index is
+    /// likely to be wrong, and txos the account received before it are not
+    /// synced until the account is rescanned.
+    fn warn_if_first_block_index_recent(
+        &self,
+        first_block_index: Option<u64>,
+    ) -> Result<(), AccountServiceError> {
+        let first_block_index = match first_block_index {
+            Some(first_block_index) => first_block_index,
+            None => return Ok(()),
+        };
+        let num_blocks = self.ledger_db.num_blocks()?;
+        if first_block_index >= num_blocks {
+            log::warn!(
+                self.request_logger(),
+                "First block index {} is past the local ledger, which has {} blocks",
+                first_block_index,
+                num_blocks,
+            );
+        } else if first_block_index.saturating_add(RECENT_FIRST_BLOCK_INDEX_WARNING_BLOCKS)
+            >= num_blocks
+        {
+            log::warn!(
+                self.request_logger(),
+                "First block index {} is within the last {} blocks of the ledger. Txos received before it will be missed; use rescan_account if the balance is too low.",
+                first_block_index,
+                RECENT_FIRST_BLOCK_INDEX_WARNING_BLOCKS,
+            );
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        db::{models::Txo, txo::TxoModel},
+        db::{models::Txo, txo::TxoModel, WalletDb},
+        service::{
+            address::{AddressService, AddressServiceError},
+            balance::BalanceService,
+            job::{JobService, JobStatus},
+            sync::{purge_expired_removed_accounts, sync_all_accounts},
+        },
         test_utils::{
             add_block_to_ledger_db, create_test_received_txo, get_empty_test_ledger,
-            get_test_ledger, manually_sync_account, setup_wallet_service,
-            setup_wallet_service_offline, MOB,
+            get_test_ledger, manually_sync_account, random_account_with_seed_values,
+            setup_wallet_service, setup_wallet_service_offline, MOB,
         },
+        util::b58::{b58_decode_public_address, b58_encode_public_address},
+    };
+    use mc_account_keys::{
+        AccountKey, PublicAddress, RootIdentity, ViewAccountKey, DEFAULT_SUBADDRESS_INDEX,
     };
-    use mc_account_keys::{AccountKey, PublicAddress, ViewAccountKey};
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_keys::RistrettoPrivate;
     use mc_crypto_rand::RngCore;
@@ -576,9 +1037,9 @@ mod tests {
         .unwrap();
         assert_eq!(txos.len(), 1);
 
-        // Delete the account. The transaction status referring to it is also cleared.
+        // Purge the account. The transaction status referring to it is also cleared.
         let account_id = AccountID(account.id.clone().to_string());
-        let result = service.remove_account(&account_id);
+        let result = service.purge_account(&account_id, "A");
         assert!(result.is_ok());
 
         let txos = Txo::list_for_account(
@@ -595,6 +1056,226 @@ mod tests {
         assert_eq!(txos.len(), 0);
     }
 
+    fn txo_count(wallet_db: &WalletDb, account_id: &AccountID) -> usize {
+        Txo::list_for_account(
+            &account_id.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(0),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap()
+        .len()
+    }
+
+    #[test_with_logger]
+    fn test_remove_and_restore_account(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let wallet_db = &service.wallet_db;
+
+        let account = service
+            .create_account(
+                Some("A".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        let public_address = account_key.default_subaddress();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[public_address.clone()],
+            100 * MOB,
+            &[],
+            &mut rng,
+        );
+        let synced = manually_sync_account(&ledger_db, wallet_db, &account_id, &logger);
+        let subaddress = service
+            .assign_address_for_account(&account_id, Some("Savings"))
+            .unwrap();
+        assert_eq!(txo_count(wallet_db, &account_id), 1);
+
+        // Removing the account hides it from the wallet.
+        assert!(service.remove_account(&account_id).unwrap());
+        assert!(service.list_accounts(None, None).unwrap().is_empty());
+        match service.get_account(&account_id) {
+            Err(AccountServiceError::Database(WalletDbError::AccountNotFound(_))) => {}
+            other => panic!("Expected AccountNotFound, got {:?}", other),
+        }
+        assert!(service
+            .get_wallet_status(None)
+            .unwrap()
+            .account_ids
+            .is_empty());
+
+        // The sync thread does not advance a removed account.
+        add_block_to_ledger_db(&mut ledger_db, &[public_address], 100 * MOB, &[], &mut rng);
+        sync_all_accounts(&ledger_db, wallet_db, &logger).unwrap();
+        let removed =
+            Account::get_including_removed(&account_id, &wallet_db.get_conn().unwrap()).unwrap();
+        assert!(removed.removed_at.is_some());
+        assert_eq!(removed.next_block_index, synced.next_block_index);
+
+        // Restoring it brings back its history, and it catches up on sync.
+        let restored = service.restore_removed_account(&account_id).unwrap();
+        assert_eq!(restored.removed_at, None);
+        assert_eq!(service.list_accounts(None, None).unwrap(), vec![restored]);
+        assert_eq!(txo_count(wallet_db, &account_id), 1);
+        assert_eq!(
+            service
+                .get_address(&subaddress.public_address_b58)
+                .unwrap()
+                .comment,
+            "Savings"
+        );
+
+        manually_sync_account(&ledger_db, wallet_db, &account_id, &logger);
+        assert_eq!(txo_count(wallet_db, &account_id), 2);
+
+        match service.restore_removed_account(&account_id) {
+            Err(AccountServiceError::AccountNotRemoved(_)) => {}
+            other => panic!("Expected AccountNotRemoved, got {:?}", other),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_restore_account_after_retention_window(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let conn = service.wallet_db.get_conn().unwrap();
+
+        let account = service
+            .create_account(
+                Some("A".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+
+        let removed_at = unix_timestamp_now() - REMOVED_ACCOUNT_RETENTION_SECONDS as i64 - 1;
+        account.mark_removed(removed_at, &conn).unwrap();
+
+        match service.restore_removed_account(&account_id) {
+            Err(AccountServiceError::RemovedAccountRetentionExpired(_)) => {}
+            other => panic!("Expected RemovedAccountRetentionExpired, got {:?}", other),
+        }
+
+        // It can still be purged.
+        assert!(service.purge_account(&account_id, "A").unwrap());
+    }
+
+    #[test_with_logger]
+    fn test_purge_expired_removed_accounts(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let conn = service.wallet_db.get_conn().unwrap();
+
+        let mut account_ids = Vec::new();
+        for name in ["Expired", "Recent", "Kept"] {
+            let account = service
+                .create_account(
+                    Some(name.to_string()),
+                    "".to_string(),
+                    "".to_string(),
+                    "".to_string(),
+                )
+                .unwrap();
+            account_ids.push(AccountID(account.id.clone()));
+        }
+
+        let expired_removed_at =
+            unix_timestamp_now() - REMOVED_ACCOUNT_RETENTION_SECONDS as i64 - 1;
+        Account::get(&account_ids[0], &conn)
+            .unwrap()
+            .mark_removed(expired_removed_at, &conn)
+            .unwrap();
+        assert!(service.remove_account(&account_ids[1]).unwrap());
+
+        assert_eq!(
+            purge_expired_removed_accounts(&service.wallet_db).unwrap(),
+            1
+        );
+
+        // The keys of the account past its retention window are gone, while
+        // the recently removed account can still be restored.
+        assert!(matches!(
+            Account::get_including_removed(&account_ids[0], &conn),
+            Err(WalletDbError::AccountNotFound(_))
+        ));
+        service.restore_removed_account(&account_ids[1]).unwrap();
+        Account::get(&account_ids[2], &conn).unwrap();
+    }
+
+    #[test_with_logger]
+    fn test_purge_account_requires_name_confirmation(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let wallet_db = &service.wallet_db;
+
+        let account = service
+            .create_account(
+                Some("Alice".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[account_key.default_subaddress()],
+            100 * MOB,
+            &[],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, wallet_db, &account_id, &logger);
+        service.remove_account(&account_id).unwrap();
+
+        match service.purge_account(&account_id, "alice") {
+            Err(AccountServiceError::PurgeConfirmationMismatch(_)) => {}
+            other => panic!("Expected PurgeConfirmationMismatch, got {:?}", other),
+        }
+        assert_eq!(txo_count(wallet_db, &account_id), 1);
+
+        let summary = service.describe_purge_account(&account_id).unwrap();
+        assert!(summary.contains("\"Alice\""));
+        assert!(summary.contains("1 txos"));
+
+        assert!(service.purge_account(&account_id, "Alice").unwrap());
+        assert_eq!(txo_count(wallet_db, &account_id), 0);
+        match service.restore_removed_account(&account_id) {
+            Err(AccountServiceError::Database(WalletDbError::AccountNotFound(_))) => {}
+            other => panic!("Expected AccountNotFound, got {:?}", other),
+        }
+    }
+
     #[test_with_logger]
     fn test_create_account_offline(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -685,6 +1366,7 @@ mod tests {
                 None,
                 None,
                 None,
+                false,
             )
             .unwrap();
 
@@ -801,4 +1483,467 @@ mod tests {
 
         assert_eq!(unspent_txos.len(), 2);
     }
+
+    #[test_with_logger]
+    fn test_resync_account(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB, 80 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id = AccountID::from(&account_key);
+        let num_blocks = ledger_db.num_blocks().unwrap();
+
+        let job = service.resync_account(&account_id).unwrap();
+        assert_eq!(job.kind, "resync_account");
+
+        let mut job = service.get_job_status(&job.id).unwrap();
+        for _ in 0..500 {
+            if job.status.is_finished() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = service.get_job_status(&job.id).unwrap();
+        }
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.progress, num_blocks);
+        assert_eq!(job.total, Some(num_blocks));
+        assert_eq!(
+            job.result.unwrap()["next_block_index"],
+            num_blocks.to_string()
+        );
+
+        // The resync finds the same txos as the first sync.
+        let unspent_txos = Txo::list_unspent(
+            Some(&account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &service.wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(unspent_txos.len(), 2);
+
+        assert!(matches!(
+            service.resync_account(&AccountID("missing".to_string())),
+            Err(AccountServiceError::Database(_))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_rescan_account(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let other_account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let other_account_id = AccountID::from(&other_account_key);
+
+        // The account receives two txos before the block it is imported from,
+        // and one after.
+        let entropy = [7u8; 32];
+        let account_key = AccountKey::from(&RootIdentity::from(&RootEntropy::from(&entropy)));
+        let first_activity_block_index = ledger_db.num_blocks().unwrap();
+        for value in &[10 * MOB, 20 * MOB] {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![account_key.subaddress(0)],
+                *value,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        let wrong_first_block_index = ledger_db.num_blocks().unwrap();
+        let num_blocks = add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![account_key.subaddress(0)],
+            30 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+
+        let account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(entropy),
+                None,
+                Some(wrong_first_block_index),
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        let unspent_value = |account_id: &AccountID| -> u64 {
+            Txo::list_unspent(
+                Some(&account_id.to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                &conn,
+            )
+            .unwrap()
+            .iter()
+            .map(|txo| txo.value as u64)
+            .sum()
+        };
+        assert_eq!(unspent_value(&account_id), 30 * MOB);
+        let account = Account::get(&account_id, &conn).unwrap();
+        assert_eq!(
+            account.first_activity_block_index,
+            Some(wrong_first_block_index as i64)
+        );
+
+        let job = service.rescan_account(&account_id, Some(0)).unwrap();
+        assert_eq!(job.kind, "rescan_account");
+
+        let mut job = service.get_job_status(&job.id).unwrap();
+        for _ in 0..500 {
+            if job.status.is_finished() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = service.get_job_status(&job.id).unwrap();
+        }
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.progress, num_blocks);
+        assert_eq!(job.total, Some(num_blocks));
+        assert_eq!(
+            job.result.unwrap()["next_block_index"],
+            num_blocks.to_string()
+        );
+
+        // The rescan finds every txo, and records where the account's activity
+        // starts.
+        assert_eq!(unspent_value(&account_id), 60 * MOB);
+        let account = Account::get(&account_id, &conn).unwrap();
+        assert_eq!(account.first_block_index, 0);
+        assert_eq!(
+            account.first_activity_block_index,
+            Some(first_activity_block_index as i64)
+        );
+
+        // The other account is not changed.
+        assert_eq!(unspent_value(&other_account_id), 70 * MOB);
+
+        assert!(matches!(
+            service.rescan_account(&account_id, Some(num_blocks + 1)),
+            Err(AccountServiceError::RescanPastLedger(_, _))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_import_account_with_shared_view_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        // The same mnemonic imported with and without fog gives two accounts with
+        // the same view private key.
+        let import = |fog_report_url: &str, fog_authority_spki: &str, force: bool| {
+            service.import_account(
+                "sheriff odor square mistake huge skate mouse shoot purity weapon proof stuff correct concert blanket neck own shift clay mistake air viable stick group".to_string(),
+                MNEMONIC_KEY_DERIVATION_VERSION,
+                None,
+                None,
+                None,
+                fog_report_url.to_string(),
+                "".to_string(),
+                fog_authority_spki.to_string(),
+                force,
+            )
+        };
+        let fog_report_url = "fog://fog-report.example.com";
+        let fog_authority_spki = "MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEAvnB9wTbTOT5uoizRYaYbw7XIEkInl8E7MGOAQj+xnC+F1rIXiCnc/t1+5IIWjbRGhWzo7RAwI5sRajn2sT4rRn9NXbOzZMvIqE4hmhmEzy1YQNDnfALAWNQ+WBbYGW+Vqm3IlQvAFFjVN1YYIdYhbLjAPdkgeVsWfcLDforHn6rR3QBZYZIlSBQSKRMY/tywTxeTCvK2zWcS0kbbFPtBcVth7VFFVPAZXhPi9yy1AvnldO6n7KLiupVmojlEMtv4FQkk604nal+j/dOplTATV8a9AJBbPRBZ/yQg57EG2Y2MRiHOQifJx0S5VbNyMm9bkS8TD7Goi59aCW6OT1gyeotWwLg60JRZTfyJ7lYWBSOzh0OnaCytRpSWtNZ6barPUeOnftbnJtE8rFhF7M4F66et0LI/cuvXYecwVwykovEVBKRF4HOK9GgSm17mQMtzrD7c558TbaucOWabYR04uhdAc3s10MkuONWG0wIQhgIChYVAGnFLvSpp2/aQEq3xrRSETxsixUIjsZyWWROkuA0IFnc8d7AmcnUBvRW7FT/5thWyk5agdYUGZ+7C1o69ihR1YxmoGh69fLMPIEOhYh572+3ckgl2SaV4uo9Gvkz8MMGRBcMIMlRirSwhCfozV2RyT5Wn1NgPpyc8zJL7QdOhL7Qxb+5WjnCVrQYHI2cCAwEAAQ==";
+
+        let account = import("", "", false).unwrap();
+        let account_id = AccountID(account.id.clone());
+
+        // Importing the same account again fails, even when forced.
+        assert!(matches!(
+            import("", "", true),
+            Err(AccountServiceError::Database(WalletDbError::AccountAlreadyExists(id))) if id == account.id
+        ));
+
+        // The fog twin is only imported when forced.
+        assert!(matches!(
+            import(fog_report_url, fog_authority_spki, false),
+            Err(AccountServiceError::ViewKeyInUse(id)) if id == account_id
+        ));
+        let twin = import(fog_report_url, fog_authority_spki, true).unwrap();
+        let twin_id = AccountID(twin.id.clone());
+        assert_ne!(twin_id, account_id);
+
+        // Both accounts receive the txo, which is recorded once.
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+        manually_sync_account(&ledger_db, &service.wallet_db, &twin_id, &logger);
+        assert_eq!(txo_count(&service.wallet_db, &account_id), 1);
+        assert_eq!(txo_count(&service.wallet_db, &twin_id), 0);
+
+        let wallet_status = service.get_wallet_status(None).unwrap();
+        assert_eq!(wallet_status.duplicate_txo_count, 1);
+        let balance = wallet_status.balance_per_token.get(&Mob::ID).unwrap();
+        assert_eq!(balance.unspent, 100 * MOB as u128);
+
+        // The txo is spent, and only the twin has synced the block spending it.
+        let conn = service.wallet_db.get_conn().unwrap();
+        let txo = Txo::list_for_account(&account.id, None, None, None, None, None, None, &conn)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let key_image: KeyImage = mc_util_serial::decode(&txo.key_image.unwrap()).unwrap();
+        let num_blocks = add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![AccountKey::random(&mut rng).default_subaddress()],
+            100 * MOB,
+            &vec![key_image],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &twin_id, &logger);
+
+        let txo = Txo::get(&txo.id, &conn).unwrap();
+        assert_eq!(txo.spent_block_index, Some(num_blocks as i64 - 1));
+
+        let wallet_status = service.get_wallet_status(None).unwrap();
+        let balance = wallet_status.balance_per_token.get(&Mob::ID).unwrap();
+        assert_eq!(balance.unspent, 0);
+        assert_eq!(balance.spent, 100 * MOB as u128);
+    }
+
+    #[test_with_logger]
+    fn test_create_account_with_fog(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+
+        let create = |fog_report_url: &str, fog_authority_spki: &str| {
+            service.create_account(
+                Some("Fog Account".to_string()),
+                fog_report_url.to_string(),
+                "".to_string(),
+                fog_authority_spki.to_string(),
+            )
+        };
+        let fog_report_url = "fog://fog-report.example.com";
+        let fog_authority_spki = "MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEAvnB9wTbTOT5uoizRYaYbw7XIEkInl8E7MGOAQj+xnC+F1rIXiCnc/t1+5IIWjbRGhWzo7RAwI5sRajn2sT4rRn9NXbOzZMvIqE4hmhmEzy1YQNDnfALAWNQ+WBbYGW+Vqm3IlQvAFFjVN1YYIdYhbLjAPdkgeVsWfcLDforHn6rR3QBZYZIlSBQSKRMY/tywTxeTCvK2zWcS0kbbFPtBcVth7VFFVPAZXhPi9yy1AvnldO6n7KLiupVmojlEMtv4FQkk604nal+j/dOplTATV8a9AJBbPRBZ/yQg57EG2Y2MRiHOQifJx0S5VbNyMm9bkS8TD7Goi59aCW6OT1gyeotWwLg60JRZTfyJ7lYWBSOzh0OnaCytRpSWtNZ6barPUeOnftbnJtE8rFhF7M4F66et0LI/cuvXYecwVwykovEVBKRF4HOK9GgSm17mQMtzrD7c558TbaucOWabYR04uhdAc3s10MkuONWG0wIQhgIChYVAGnFLvSpp2/aQEq3xrRSETxsixUIjsZyWWROkuA0IFnc8d7AmcnUBvRW7FT/5thWyk5agdYUGZ+7C1o69ihR1YxmoGh69fLMPIEOhYh572+3ckgl2SaV4uo9Gvkz8MMGRBcMIMlRirSwhCfozV2RyT5Wn1NgPpyc8zJL7QdOhL7Qxb+5WjnCVrQYHI2cCAwEAAQ==";
+
+        let account = create(fog_report_url, fog_authority_spki).unwrap();
+        assert!(account.fog_enabled);
+
+        // The account's addresses carry its fog info.
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        let main_address_b58 =
+            b58_encode_public_address(&account.public_address(DEFAULT_SUBADDRESS_INDEX).unwrap())
+                .unwrap();
+        let main_address = b58_decode_public_address(&main_address_b58).unwrap();
+        assert_eq!(main_address, account_key.default_subaddress());
+        assert_eq!(main_address.fog_report_url(), Some(fog_report_url));
+        assert!(main_address.fog_authority_sig().is_some());
+
+        // Fog enabled accounts still can not assign addresses.
+        assert!(matches!(
+            service.assign_address_for_account(&AccountID(account.id.clone()), None),
+            Err(AddressServiceError::Database(
+                WalletDbError::SubaddressesNotSupportedForFOGEnabledAccounts
+            ))
+        ));
+
+        // An authority spki which is not a subject public key info is refused.
+        let invalid_fog_info = |result: Result<Account, AccountServiceError>| {
+            matches!(
+                result,
+                Err(AccountServiceError::Database(
+                    WalletDbError::InvalidFogInfo(_)
+                ))
+            )
+        };
+        let not_spki = base64::encode(b"not a subject public key info");
+        assert!(invalid_fog_info(create(fog_report_url, &not_spki)));
+        assert!(invalid_fog_info(create(fog_report_url, "")));
+        assert!(matches!(
+            create(fog_report_url, "not base64!"),
+            Err(AccountServiceError::Database(WalletDbError::Base64Decode(
+                _
+            )))
+        ));
+
+        // As is fog info without a valid fog report url.
+        assert!(invalid_fog_info(create("", fog_authority_spki)));
+        assert!(invalid_fog_info(create(
+            "not a fog url",
+            fog_authority_spki
+        )));
+    }
+
+    #[test_with_logger]
+    fn test_account_key_source(logger: Logger) {
+        use crate::{
+            db::{account::AccountKeySource, schema::accounts},
+            json_rpc::v2::models::{account_secrets::AccountSecrets, amount::Amount as AmountJSON},
+            service::transaction::{TransactionMemo, TransactionService, TransactionServiceError},
+        };
+        use diesel::prelude::*;
+        use std::convert::TryFrom;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+
+        // Each creation and import path records how the keys entered the
+        // wallet.
+        let created = service
+            .create_account(
+                Some("Created".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        assert_eq!(created.key_source().unwrap(), AccountKeySource::Created);
+
+        let imported_mnemonic = service
+            .import_account(
+                "sheriff odor square mistake huge skate mouse shoot purity weapon proof stuff correct concert blanket neck own shift clay mistake air viable stick group".to_string(),
+                MNEMONIC_KEY_DERIVATION_VERSION,
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            imported_mnemonic.key_source().unwrap(),
+            AccountKeySource::ImportedMnemonic
+        );
+
+        let imported_keys = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode([7u8; 32]),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            imported_keys.key_source().unwrap(),
+            AccountKeySource::ImportedKeys
+        );
+
+        let view_account_key = ViewAccountKey::from(&AccountKey::random(&mut rng));
+        let view_only = service
+            .import_view_only_account(
+                ristretto_to_hex(view_account_key.view_private_key()),
+                ristretto_public_to_hex(view_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        assert_eq!(
+            view_only.key_source().unwrap(),
+            AccountKeySource::ViewOnlyImport
+        );
+
+        // The exported secrets are labelled by what each source holds.
+        let exportable_secrets = |account: &Account| {
+            AccountSecrets::try_from(account)
+                .unwrap()
+                .exportable_secrets
+        };
+        assert_eq!(
+            exportable_secrets(&created),
+            vec!["mnemonic".to_string(), "account_key".to_string()]
+        );
+        assert_eq!(
+            exportable_secrets(&imported_keys),
+            vec!["entropy".to_string(), "account_key".to_string()]
+        );
+        assert_eq!(
+            exportable_secrets(&view_only),
+            vec!["view_account_key".to_string()]
+        );
+
+        // Only accounts whose spend key the wallet holds sign locally.
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        let build_and_sign = |account_id: &str| {
+            service.build_and_sign_transaction(
+                account_id,
+                &[(recipient.clone(), AmountJSON::new(MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+        };
+        assert!(!matches!(
+            build_and_sign(&created.id),
+            Err(TransactionServiceError::AccountCannotSignLocally(..))
+        ));
+        assert!(matches!(
+            build_and_sign(&view_only.id),
+            Err(TransactionServiceError::AccountCannotSignLocally(id, source))
+                if id == view_only.id && source == "view_only_import"
+        ));
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        diesel::update(accounts::table.filter(accounts::id.eq(&created.id)))
+            .set(accounts::key_source.eq("hardware"))
+            .execute(&conn)
+            .unwrap();
+        assert!(matches!(
+            build_and_sign(&created.id),
+            Err(TransactionServiceError::AccountCannotSignLocally(id, source))
+                if id == created.id && source == "hardware"
+        ));
+    }
 }
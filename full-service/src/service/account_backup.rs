@@ -0,0 +1,917 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for backing up accounts and restoring them in another wallet.
+//!
+//! A backup records what cannot be recovered from the ledger alone: the keys
+//! of the account, its assigned subaddresses with their comments, and its sync
+//! settings. The keys are only included on request, and can be encrypted with
+//! a passphrase.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountKeySource, AccountModel},
+        assigned_subaddress::AssignedSubaddressModel,
+        models::{Account, AssignedSubaddress},
+        transaction, Conn, WalletDbError,
+    },
+    service::WalletService,
+    util::constants::{
+        ACCOUNT_BACKUP_KDF_ROUNDS, ACCOUNT_BACKUP_VERSION, MAX_ACCOUNT_BACKUP_KDF_ROUNDS,
+    },
+};
+use aes_gcm::{
+    aead::{Aead, NewAead, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use displaydoc::Display;
+use hmac::Hmac;
+use mc_account_keys::{AccountKey, ViewAccountKey};
+use mc_common::logger::log;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
+use mc_crypto_rand::rand_core::RngCore;
+use mc_fog_report_validation::FogPubkeyResolver;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// The key derivation function used to encrypt backup secrets.
+const ACCOUNT_BACKUP_KDF: &str = "pbkdf2-hmac-sha256";
+
+/// Errors for the Account Backup Service.
+#[derive(Display, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum AccountBackupServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+
+    /// Diesel Error: {0}
+    Diesel(diesel::result::Error),
+
+    /// Error decoding from hex: {0}
+    HexDecode(hex::FromHexError),
+
+    /// Error serializing the backup: {0}
+    Json(serde_json::Error),
+
+    /// mc_util_serial decode error: {0}
+    Decode(mc_util_serial::DecodeError),
+
+    /// Unsupported account backup version: {0}
+    UnsupportedVersion(String),
+
+    /// Invalid account backup: {0}
+    InvalidBackup(String),
+
+    /// The checksum of the backup does not match its contents
+    ChecksumMismatch,
+
+    /// The secrets in the backup are encrypted, and a passphrase is required
+    PassphraseRequired,
+
+    /// The passphrase does not decrypt the secrets in the backup
+    InvalidPassphrase,
+
+    /// Account {0} is not in the wallet, and the backup does not contain its
+    /// keys
+    SecretsRequired(String),
+
+    /// The keys in the backup are not the keys of account {0}
+    AccountIdMismatch(String),
+
+    /// Account {0} is already in the wallet with different keys
+    ExistingAccountKeysDiffer(String),
+
+    /// Subaddress {0} in the backup does not belong to the account
+    SubaddressMismatch(String),
+}
+
+impl From<WalletDbError> for AccountBackupServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+impl From<diesel::result::Error> for AccountBackupServiceError {
+    fn from(src: diesel::result::Error) -> Self {
+        Self::Diesel(src)
+    }
+}
+
+impl From<hex::FromHexError> for AccountBackupServiceError {
+    fn from(src: hex::FromHexError) -> Self {
+        Self::HexDecode(src)
+    }
+}
+
+impl From<serde_json::Error> for AccountBackupServiceError {
+    fn from(src: serde_json::Error) -> Self {
+        Self::Json(src)
+    }
+}
+
+impl From<mc_util_serial::DecodeError> for AccountBackupServiceError {
+    fn from(src: mc_util_serial::DecodeError) -> Self {
+        Self::Decode(src)
+    }
+}
+
+/// A backup of an account, which can be imported into another wallet.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct AccountBackup {
+    /// The version of the backup format.
+    pub version: String,
+
+    pub account_id: String,
+
+    pub name: String,
+
+    pub key_derivation_version: String,
+
+    /// Index of the first block where the account may have held funds.
+    pub first_block_index: String,
+
+    /// Index of the next block the account would have synced, when the backup
+    /// was made.
+    pub next_block_index: String,
+
+    pub import_block_index: Option<String>,
+
+    pub fog_enabled: bool,
+
+    pub view_only: bool,
+
+    pub subaddress_gap_limit: String,
+
+    pub split_change_into: String,
+
+    /// Absent in backups made before confirmations were configurable.
+    #[serde(default)]
+    pub min_confirmations: Option<String>,
+
+    /// Absent in backups made before accounts had transaction defaults.
+    #[serde(default)]
+    pub default_fee_value: Option<String>,
+
+    #[serde(default)]
+    pub default_fee_token_id: Option<String>,
+
+    #[serde(default)]
+    pub default_tombstone_offset: Option<String>,
+
+    /// Absent in backups made before accounts recorded how their keys entered
+    /// the wallet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_source: Option<String>,
+
+    /// The assigned subaddresses of the account, ordered by index.
+    pub subaddresses: Vec<AccountBackupSubaddress>,
+
+    /// The keys of the account, if they were exported.
+    pub secrets: Option<AccountBackupSecrets>,
+
+    /// Hash of the rest of the backup, as hex.
+    pub checksum: String,
+}
+
+/// An assigned subaddress recorded in a backup.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AccountBackupSubaddress {
+    pub subaddress_index: String,
+    pub public_address_b58: String,
+    pub comment: String,
+}
+
+/// The keys of an account recorded in a backup, either in the clear or
+/// encrypted with a passphrase.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountBackupSecrets {
+    Plaintext(AccountBackupKeys),
+    Encrypted(EncryptedAccountBackupKeys),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct AccountBackupKeys {
+    /// The serialized account key, or view account key for a view only
+    /// account, as hex.
+    pub account_key: String,
+
+    /// The entropy the account key was derived from, as hex.
+    pub entropy: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct EncryptedAccountBackupKeys {
+    /// The key derivation function applied to the passphrase.
+    pub kdf: String,
+
+    pub kdf_rounds: String,
+
+    /// The key derivation salt, as hex.
+    pub salt: String,
+
+    /// The AES-256-GCM nonce, as hex.
+    pub nonce: String,
+
+    /// The AES-256-GCM encryption of the JSON AccountBackupKeys, bound to the
+    /// account id, as hex.
+    pub ciphertext: String,
+}
+
+impl AccountBackup {
+    /// Hash the backup, excluding its checksum.
+    pub fn compute_checksum(&self) -> Result<String, AccountBackupServiceError> {
+        let mut unchecked = self.clone();
+        unchecked.checksum = String::new();
+        let digest: [u8; 32] = serde_json::to_string(&unchecked)?
+            .digest32::<MerlinTranscript>(b"account_backup_checksum");
+        Ok(hex::encode(digest))
+    }
+}
+
+fn backup_cipher(passphrase: &str, salt: &[u8], rounds: u32) -> Aes256Gcm {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(passphrase.as_bytes(), salt, rounds, &mut key);
+    Aes256Gcm::new(Key::from_slice(&key))
+}
+
+fn encrypt_keys(
+    keys: &AccountBackupKeys,
+    passphrase: &str,
+    account_id_hex: &str,
+) -> Result<EncryptedAccountBackupKeys, AccountBackupServiceError> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; 16];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; 12];
+    rng.fill_bytes(&mut nonce);
+
+    let plaintext = serde_json::to_vec(keys)?;
+    let ciphertext = backup_cipher(passphrase, &salt, ACCOUNT_BACKUP_KDF_ROUNDS)
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: account_id_hex.as_bytes(),
+            },
+        )
+        .map_err(|_| AccountBackupServiceError::InvalidBackup("Encryption failed".to_string()))?;
+
+    Ok(EncryptedAccountBackupKeys {
+        kdf: ACCOUNT_BACKUP_KDF.to_string(),
+        kdf_rounds: ACCOUNT_BACKUP_KDF_ROUNDS.to_string(),
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn decrypt_keys(
+    encrypted: &EncryptedAccountBackupKeys,
+    passphrase: &str,
+    account_id_hex: &str,
+) -> Result<AccountBackupKeys, AccountBackupServiceError> {
+    if encrypted.kdf != ACCOUNT_BACKUP_KDF {
+        return Err(AccountBackupServiceError::InvalidBackup(format!(
+            "Unknown kdf {}",
+            encrypted.kdf
+        )));
+    }
+    let rounds = encrypted
+        .kdf_rounds
+        .parse::<u32>()
+        .ok()
+        .filter(|rounds| {
+            (ACCOUNT_BACKUP_KDF_ROUNDS..=MAX_ACCOUNT_BACKUP_KDF_ROUNDS).contains(rounds)
+        })
+        .ok_or_else(|| {
+            AccountBackupServiceError::InvalidBackup(format!(
+                "Invalid kdf_rounds {}, must be between {} and {}",
+                encrypted.kdf_rounds, ACCOUNT_BACKUP_KDF_ROUNDS, MAX_ACCOUNT_BACKUP_KDF_ROUNDS
+            ))
+        })?;
+    let salt = hex::decode(&encrypted.salt)?;
+    let nonce = hex::decode(&encrypted.nonce)?;
+    if nonce.len() != 12 {
+        return Err(AccountBackupServiceError::InvalidBackup(
+            "Nonce must be 12 bytes".to_string(),
+        ));
+    }
+    let ciphertext = hex::decode(&encrypted.ciphertext)?;
+
+    let plaintext = backup_cipher(passphrase, &salt, rounds)
+        .decrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &ciphertext,
+                aad: account_id_hex.as_bytes(),
+            },
+        )
+        .map_err(|_| AccountBackupServiceError::InvalidPassphrase)?;
+
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+fn parse_field<N: std::str::FromStr>(
+    field: &str,
+    value: &str,
+) -> Result<N, AccountBackupServiceError> {
+    value.parse::<N>().map_err(|_| {
+        AccountBackupServiceError::InvalidBackup(format!("Invalid {} {}", field, value))
+    })
+}
+
+/// Insert the account recorded in a backup, with the given keys.
+fn restore_account(
+    backup: &AccountBackup,
+    keys: &AccountBackupKeys,
+    conn: &Conn,
+) -> Result<Account, AccountBackupServiceError> {
+    let account_key = hex::decode(&keys.account_key)?;
+    let entropy = keys.entropy.as_ref().map(hex::decode).transpose()?;
+    let import_block_index = backup
+        .import_block_index
+        .as_ref()
+        .map(|i| parse_field::<u64>("import_block_index", i))
+        .transpose()?;
+    let key_derivation_version =
+        parse_field::<u8>("key_derivation_version", &backup.key_derivation_version)?;
+    let first_block_index = parse_field::<u64>("first_block_index", &backup.first_block_index)?;
+    let key_source = match &backup.key_source {
+        Some(key_source) => parse_field::<AccountKeySource>("key_source", key_source)?,
+        None => AccountKeySource::infer(
+            backup.view_only,
+            key_derivation_version as i32,
+            first_block_index as i64,
+            import_block_index.map(|i| i as i64),
+        ),
+    };
+
+    let account = Account::restore(
+        &account_key,
+        entropy.as_deref(),
+        key_derivation_version,
+        first_block_index,
+        import_block_index,
+        &backup.name,
+        backup.fog_enabled,
+        backup.view_only,
+        key_source,
+        conn,
+    )?;
+    if account.id != backup.account_id {
+        return Err(AccountBackupServiceError::AccountIdMismatch(
+            backup.account_id.clone(),
+        ));
+    }
+
+    account.update_subaddress_gap_limit(
+        parse_field("subaddress_gap_limit", &backup.subaddress_gap_limit)?,
+        conn,
+    )?;
+    account.update_split_change_into(
+        parse_field("split_change_into", &backup.split_change_into)?,
+        conn,
+    )?;
+    account.update_min_confirmations(
+        backup
+            .min_confirmations
+            .as_ref()
+            .map(|m| parse_field("min_confirmations", m))
+            .transpose()?,
+        conn,
+    )?;
+    account.update_transaction_defaults(
+        backup
+            .default_fee_value
+            .as_ref()
+            .map(|f| parse_field("default_fee_value", f))
+            .transpose()?,
+        backup
+            .default_fee_token_id
+            .as_ref()
+            .map(|t| parse_field("default_fee_token_id", t))
+            .transpose()?,
+        backup
+            .default_tombstone_offset
+            .as_ref()
+            .map(|o| parse_field("default_tombstone_offset", o))
+            .transpose()?,
+        conn,
+    )?;
+
+    Ok(account)
+}
+
+/// Assign the subaddresses recorded in a backup which are missing from the
+/// account, and restore the comments of those which are already assigned.
+fn restore_subaddresses(
+    account: &Account,
+    subaddresses: &[AccountBackupSubaddress],
+    conn: &Conn,
+) -> Result<(), AccountBackupServiceError> {
+    for subaddress in subaddresses {
+        let subaddress_index: u64 = parse_field("subaddress_index", &subaddress.subaddress_index)?;
+
+        let public_address_b58 = match AssignedSubaddress::get_for_account_by_index(
+            &account.id,
+            subaddress_index as i64,
+            conn,
+        ) {
+            Ok(assigned_subaddress) => {
+                assigned_subaddress.update_comment(&subaddress.comment, conn)?;
+                assigned_subaddress.public_address_b58
+            }
+            Err(WalletDbError::Diesel(diesel::result::Error::NotFound)) => {
+                if account.view_only {
+                    let view_account_key: ViewAccountKey =
+                        mc_util_serial::decode(&account.account_key)?;
+                    AssignedSubaddress::create_for_view_only_account(
+                        &view_account_key,
+                        subaddress_index,
+                        &subaddress.comment,
+                        conn,
+                    )?
+                } else {
+                    let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
+                    AssignedSubaddress::create(
+                        &account_key,
+                        subaddress_index,
+                        &subaddress.comment,
+                        conn,
+                    )?
+                }
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if public_address_b58 != subaddress.public_address_b58 {
+            return Err(AccountBackupServiceError::SubaddressMismatch(
+                subaddress.public_address_b58.clone(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Trait defining the ways in which the wallet can back up and restore
+/// accounts.
+pub trait AccountBackupService {
+    /// Export a backup of an account. Its keys are only included if
+    /// include_secrets is set, and are encrypted if a passphrase is given.
+    fn export_account(
+        &self,
+        account_id: &AccountID,
+        include_secrets: bool,
+        passphrase: Option<String>,
+    ) -> Result<AccountBackup, AccountBackupServiceError>;
+
+    /// Import an account from a backup.
+    ///
+    /// If the account is not in the wallet, the backup must contain its keys,
+    /// and the account is synced again from its first block. If it is, its
+    /// subaddresses and their comments are restored from the backup.
+    fn import_account_backup(
+        &self,
+        backup: &AccountBackup,
+        passphrase: Option<String>,
+    ) -> Result<Account, AccountBackupServiceError>;
+}
+
+impl<T, FPR> AccountBackupService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn export_account(
+        &self,
+        account_id: &AccountID,
+        include_secrets: bool,
+        passphrase: Option<String>,
+    ) -> Result<AccountBackup, AccountBackupServiceError> {
+        log::info!(
+            self.request_logger(),
+            "Exporting account {} with secrets: {}",
+            account_id,
+            include_secrets
+        );
+
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        let mut subaddresses =
+            AssignedSubaddress::list_all(Some(account.id.clone()), None, None, &conn)?;
+        subaddresses.sort_by_key(|s| s.subaddress_index);
+
+        let secrets = if include_secrets {
+            let keys = AccountBackupKeys {
+                account_key: hex::encode(&account.account_key),
+                entropy: account.entropy.as_ref().map(hex::encode),
+            };
+            Some(match passphrase {
+                Some(passphrase) => {
+                    AccountBackupSecrets::Encrypted(encrypt_keys(&keys, &passphrase, &account.id)?)
+                }
+                None => AccountBackupSecrets::Plaintext(keys),
+            })
+        } else {
+            None
+        };
+
+        let mut backup = AccountBackup {
+            version: ACCOUNT_BACKUP_VERSION.to_string(),
+            account_id: account.id.clone(),
+            name: account.name.clone(),
+            key_derivation_version: account.key_derivation_version.to_string(),
+            first_block_index: (account.first_block_index as u64).to_string(),
+            next_block_index: (account.next_block_index as u64).to_string(),
+            import_block_index: account.import_block_index.map(|i| (i as u64).to_string()),
+            fog_enabled: account.fog_enabled,
+            view_only: account.view_only,
+            subaddress_gap_limit: (account.subaddress_gap_limit as u64).to_string(),
+            split_change_into: (account.split_change_into as u64).to_string(),
+            min_confirmations: account.min_confirmations.map(|m| (m as u64).to_string()),
+            default_fee_value: account.default_fee_value.map(|f| (f as u64).to_string()),
+            default_fee_token_id: account.default_fee_token_id.map(|t| (t as u64).to_string()),
+            default_tombstone_offset: account
+                .default_tombstone_offset
+                .map(|o| (o as u64).to_string()),
+            key_source: Some(account.key_source.clone()),
+            subaddresses: subaddresses
+                .iter()
+                .map(|s| AccountBackupSubaddress {
+                    subaddress_index: (s.subaddress_index as u64).to_string(),
+                    public_address_b58: s.public_address_b58.clone(),
+                    comment: s.comment.clone(),
+                })
+                .collect(),
+            secrets,
+            checksum: String::new(),
+        };
+        backup.checksum = backup.compute_checksum()?;
+
+        Ok(backup)
+    }
+
+    fn import_account_backup(
+        &self,
+        backup: &AccountBackup,
+        passphrase: Option<String>,
+    ) -> Result<Account, AccountBackupServiceError> {
+        log::info!(
+            self.request_logger(),
+            "Importing backup of account {}",
+            backup.account_id
+        );
+
+        if backup.version != ACCOUNT_BACKUP_VERSION.to_string() {
+            return Err(AccountBackupServiceError::UnsupportedVersion(
+                backup.version.clone(),
+            ));
+        }
+        if backup.checksum != backup.compute_checksum()? {
+            return Err(AccountBackupServiceError::ChecksumMismatch);
+        }
+
+        let keys = match &backup.secrets {
+            None => None,
+            Some(AccountBackupSecrets::Plaintext(keys)) => Some(keys.clone()),
+            Some(AccountBackupSecrets::Encrypted(encrypted)) => {
+                let passphrase =
+                    passphrase.ok_or(AccountBackupServiceError::PassphraseRequired)?;
+                Some(decrypt_keys(encrypted, &passphrase, &backup.account_id)?)
+            }
+        };
+
+        let account_id = AccountID(backup.account_id.clone());
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let account = match (Account::get(&account_id, &conn), &keys) {
+                (Ok(existing), Some(keys)) => {
+                    if existing.account_key != hex::decode(&keys.account_key)? {
+                        return Err(AccountBackupServiceError::ExistingAccountKeysDiffer(
+                            backup.account_id.clone(),
+                        ));
+                    }
+                    existing
+                }
+                (Ok(existing), None) => existing,
+                (Err(WalletDbError::AccountNotFound(_)), Some(keys)) => {
+                    restore_account(backup, keys, &conn)?
+                }
+                (Err(WalletDbError::AccountNotFound(_)), None) => {
+                    return Err(AccountBackupServiceError::SecretsRequired(
+                        backup.account_id.clone(),
+                    ))
+                }
+                (Err(e), _) => return Err(e.into()),
+            };
+
+            restore_subaddresses(&account, &backup.subaddresses, &conn)?;
+
+            Ok(Account::get(&account_id, &conn)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{models::Txo, txo::TxoModel},
+        service::{account::AccountService, address::AddressService},
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+        util::constants::MNEMONIC_KEY_DERIVATION_VERSION,
+    };
+    use bip39::{Language, Mnemonic};
+    use mc_account_keys::PublicAddress;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_transaction_core::ring_signature::KeyImage;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    fn subaddress_comments<T, FPR>(
+        service: &WalletService<T, FPR>,
+        account_id: &AccountID,
+    ) -> Vec<(i64, String, String)>
+    where
+        T: BlockchainConnection + UserTxConnection + 'static,
+        FPR: FogPubkeyResolver + Send + Sync + 'static,
+    {
+        let mut subaddresses: Vec<(i64, String, String)> = service
+            .get_addresses(Some(account_id.to_string()), None, None)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.subaddress_index, s.public_address_b58, s.comment))
+            .collect();
+        subaddresses.sort();
+        subaddresses
+    }
+
+    #[test_with_logger]
+    fn test_account_backup_round_trip(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+
+        for i in 0..20 {
+            service
+                .assign_address_for_account(&account_id, Some(&format!("Customer {}", i)))
+                .unwrap();
+        }
+        service
+            .update_account_split_change_into(&account_id, 3)
+            .unwrap();
+
+        // Fund one of the labeled subaddresses.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![account_key.subaddress(7)],
+            50 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let synced = manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let backup = service
+            .export_account(&account_id, true, Some(PASSPHRASE.to_string()))
+            .unwrap();
+        assert_eq!(backup.subaddresses.len(), 23);
+        assert_eq!(
+            backup.next_block_index,
+            (synced.next_block_index as u64).to_string()
+        );
+        match &backup.secrets {
+            Some(AccountBackupSecrets::Encrypted(_)) => {}
+            other => panic!("Secrets should be encrypted: {:?}", other),
+        }
+        let serialized = serde_json::to_string(&backup).unwrap();
+        assert!(!serialized.contains(&hex::encode(&account.account_key)));
+        assert!(!serialized.contains(&hex::encode(account.entropy.as_ref().unwrap())));
+
+        // Restore the backup into a new wallet on the same ledger.
+        let backup: AccountBackup = serde_json::from_str(&serialized).unwrap();
+        let restored_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        match restored_service.import_account_backup(&backup, None) {
+            Err(AccountBackupServiceError::PassphraseRequired) => {}
+            other => panic!("Expected PassphraseRequired, got {:?}", other),
+        }
+        match restored_service.import_account_backup(&backup, Some("wrong".to_string())) {
+            Err(AccountBackupServiceError::InvalidPassphrase) => {}
+            other => panic!("Expected InvalidPassphrase, got {:?}", other),
+        }
+
+        // A backup asking for too few or too many kdf rounds is rejected
+        // before any key is derived.
+        for kdf_rounds in &["1", "4294967295", "lots"] {
+            let mut tampered = backup.clone();
+            match &mut tampered.secrets {
+                Some(AccountBackupSecrets::Encrypted(keys)) => {
+                    keys.kdf_rounds = kdf_rounds.to_string()
+                }
+                other => panic!("Secrets should be encrypted: {:?}", other),
+            }
+            match restored_service.import_account_backup(&tampered, Some(PASSPHRASE.to_string())) {
+                Err(AccountBackupServiceError::InvalidBackup(_)) => {}
+                other => panic!("Expected InvalidBackup, got {:?}", other),
+            }
+        }
+        let restored = restored_service
+            .import_account_backup(&backup, Some(PASSPHRASE.to_string()))
+            .unwrap();
+
+        assert_eq!(restored.id, account.id);
+        assert_eq!(restored.account_key, account.account_key);
+        assert_eq!(restored.entropy, account.entropy);
+        assert_eq!(restored.name, account.name);
+        assert_eq!(restored.first_block_index, account.first_block_index);
+        assert_eq!(restored.import_block_index, account.import_block_index);
+        assert_eq!(restored.split_change_into, 3);
+        assert_eq!(
+            subaddress_comments(&restored_service, &account_id),
+            subaddress_comments(&service, &account_id)
+        );
+
+        // Sync starts over from the first block, and catches up to where the
+        // exporting wallet was.
+        assert_eq!(restored.next_block_index, account.first_block_index);
+        let restored = manually_sync_account(
+            &ledger_db,
+            &restored_service.wallet_db,
+            &account_id,
+            &logger,
+        );
+        assert_eq!(
+            (restored.next_block_index as u64).to_string(),
+            backup.next_block_index
+        );
+        let conn = restored_service.wallet_db.get_conn().unwrap();
+        let unspent = Txo::list_unspent(
+            Some(&account.id),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(unspent.len(), 1);
+        assert_eq!(unspent[0].value as u64, 50 * MOB);
+        assert_eq!(unspent[0].subaddress_index, Some(7));
+
+        // Importing again changes nothing.
+        restored_service
+            .import_account_backup(&backup, Some(PASSPHRASE.to_string()))
+            .unwrap();
+        assert_eq!(
+            subaddress_comments(&restored_service, &account_id),
+            subaddress_comments(&service, &account_id)
+        );
+    }
+
+    #[test_with_logger]
+    fn test_account_backup_without_secrets(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        for i in 0..3 {
+            service
+                .assign_address_for_account(&account_id, Some(&format!("Customer {}", i)))
+                .unwrap();
+        }
+        let comments = subaddress_comments(&service, &account_id);
+
+        let backup = service.export_account(&account_id, false, None).unwrap();
+        assert_eq!(backup.secrets, None);
+        let serialized = serde_json::to_string(&backup).unwrap();
+        assert!(!serialized.contains(&hex::encode(&account.account_key)));
+        assert!(!serialized.contains(&hex::encode(account.entropy.as_ref().unwrap())));
+        assert!(!serialized.contains(&hex::encode(
+            account_key.view_private_key().to_bytes()
+        )));
+
+        // Without the keys, the account cannot be recreated in another wallet.
+        let other_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        match other_service.import_account_backup(&backup, None) {
+            Err(AccountBackupServiceError::SecretsRequired(_)) => {}
+            other => panic!("Expected SecretsRequired, got {:?}", other),
+        }
+
+        // Re-importing from the mnemonic loses the comments, which the backup
+        // restores.
+        service.remove_account(&account_id).unwrap();
+        let mnemonic =
+            Mnemonic::from_entropy(account.entropy.as_ref().unwrap(), Language::English).unwrap();
+        service
+            .import_account(
+                mnemonic.phrase().to_string(),
+                MNEMONIC_KEY_DERIVATION_VERSION,
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        assert_ne!(subaddress_comments(&service, &account_id), comments);
+
+        service.import_account_backup(&backup, None).unwrap();
+        assert_eq!(subaddress_comments(&service, &account_id), comments);
+    }
+
+    #[test_with_logger]
+    fn test_account_backup_rejects_tampering(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let backup = service.export_account(&account_id, true, None).unwrap();
+
+        let restored_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        // Changing the contents without updating the checksum is detected.
+        let mut tampered = backup.clone();
+        tampered.subaddresses[0].comment = "Mallory".to_string();
+        match restored_service.import_account_backup(&tampered, None) {
+            Err(AccountBackupServiceError::ChecksumMismatch) => {}
+            other => panic!("Expected ChecksumMismatch, got {:?}", other),
+        }
+
+        let mut tampered = backup.clone();
+        tampered.checksum = hex::encode([0u8; 32]);
+        match restored_service.import_account_backup(&tampered, None) {
+            Err(AccountBackupServiceError::ChecksumMismatch) => {}
+            other => panic!("Expected ChecksumMismatch, got {:?}", other),
+        }
+
+        // Keys which do not belong to the account are rejected, even with a
+        // valid checksum.
+        let other_account = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let mut tampered = backup.clone();
+        tampered.secrets = Some(AccountBackupSecrets::Plaintext(AccountBackupKeys {
+            account_key: hex::encode(&other_account.account_key),
+            entropy: other_account.entropy.as_ref().map(hex::encode),
+        }));
+        tampered.checksum = tampered.compute_checksum().unwrap();
+        match restored_service.import_account_backup(&tampered, None) {
+            Err(AccountBackupServiceError::AccountIdMismatch(_)) => {}
+            other => panic!("Expected AccountIdMismatch, got {:?}", other),
+        }
+        match service.import_account_backup(&tampered, None) {
+            Err(AccountBackupServiceError::ExistingAccountKeysDiffer(_)) => {}
+            other => panic!("Expected ExistingAccountKeysDiffer, got {:?}", other),
+        }
+        assert!(restored_service.list_accounts(None, None).unwrap().is_empty());
+
+        restored_service
+            .import_account_backup(&backup, None)
+            .unwrap();
+    }
+}
@@ -0,0 +1,678 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for migrating accounts created from legacy root entropy to new
+//! accounts created from a mnemonic.
+//!
+//! The funds of the legacy account are swept into the new account, and the
+//! legacy account is retired once its balance reaches zero. The new account
+//! is recorded on the legacy account before anything is swept, so that a
+//! migration which is interrupted, or whose sweeps expire, is resumed by
+//! migrating the legacy account again.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountModel},
+        assigned_subaddress::AssignedSubaddressModel,
+        idempotency_key::unix_timestamp_now,
+        models::{Account, AssignedSubaddress, TransactionLog, Txo},
+        transaction,
+        transaction_log::{TransactionLogFilter, TransactionLogModel},
+        txo::{unconfirmed_block_index, TxoModel},
+        Conn, WalletDb, WalletDbError,
+    },
+    json_rpc::v2::models::amount::Amount as AmountJSON,
+    service::{
+        job::{Job, JobContext, JobServiceError},
+        ledger::{LedgerService, LedgerServiceError},
+        transaction::{TransactionMemo, TransactionService, TransactionServiceError},
+        WalletService,
+    },
+    util::{
+        b58::{b58_encode_public_address, B58Error},
+        constants::{
+            ACCOUNT_MIGRATION_POLL_INTERVAL_MILLIS, DEFAULT_NEXT_SUBADDRESS_INDEX,
+            ROOT_ENTROPY_KEY_DERIVATION_VERSION,
+        },
+    },
+};
+use bip39::{Language, Mnemonic, MnemonicType};
+use displaydoc::Display;
+use mc_account_keys::{AccountKey, DEFAULT_SUBADDRESS_INDEX};
+use mc_common::logger::{log, Logger};
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
+use mc_transaction_core::{constants::MAX_INPUTS, TokenId};
+use std::{collections::BTreeMap, convert::TryFrom, thread, time::Duration};
+
+/// Errors for the Account Migration Service.
+#[derive(Display, Debug)]
+pub enum AccountMigrationServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+
+    /// Error with LedgerDB: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Error with the Ledger Service: {0}
+    LedgerService(LedgerServiceError),
+
+    /// Error sweeping the account: {0}
+    TransactionService(TransactionServiceError),
+
+    /// Error with the Job Service: {0}
+    JobService(JobServiceError),
+
+    /// Error encoding b58: {0:?}
+    B58(B58Error),
+
+    /// Error decoding the account key: {0}
+    Decode(mc_util_serial::DecodeError),
+
+    /// Diesel error: {0}
+    Diesel(diesel::result::Error),
+
+    /// Account was not created from legacy root entropy, or cannot sign: {0}
+    NotLegacyAccount(AccountID),
+
+    /// The account this account is being migrated to has been removed, restore
+    /// it to resume the migration: {0}
+    MigratedToAccountRemoved(AccountID),
+
+    /// No network fee is known for token {0}, so it cannot be swept
+    NoFeeForToken(TokenId),
+
+    /// {0} txos of account {1} were not swept, migrate it again to resume
+    Unswept(u64, AccountID),
+}
+
+impl From<WalletDbError> for AccountMigrationServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+impl From<mc_ledger_db::Error> for AccountMigrationServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<LedgerServiceError> for AccountMigrationServiceError {
+    fn from(src: LedgerServiceError) -> Self {
+        Self::LedgerService(src)
+    }
+}
+
+impl From<TransactionServiceError> for AccountMigrationServiceError {
+    fn from(src: TransactionServiceError) -> Self {
+        Self::TransactionService(src)
+    }
+}
+
+impl From<JobServiceError> for AccountMigrationServiceError {
+    fn from(src: JobServiceError) -> Self {
+        Self::JobService(src)
+    }
+}
+
+impl From<B58Error> for AccountMigrationServiceError {
+    fn from(src: B58Error) -> Self {
+        Self::B58(src)
+    }
+}
+
+impl From<mc_util_serial::DecodeError> for AccountMigrationServiceError {
+    fn from(src: mc_util_serial::DecodeError) -> Self {
+        Self::Decode(src)
+    }
+}
+
+impl From<diesel::result::Error> for AccountMigrationServiceError {
+    fn from(src: diesel::result::Error) -> Self {
+        Self::Diesel(src)
+    }
+}
+
+/// A migration of a legacy account, as started or resumed by
+/// migrate_account_to_mnemonic.
+#[derive(Clone, Debug)]
+pub struct AccountMigration {
+    /// The account the legacy account's funds are moved to.
+    pub new_account: Account,
+
+    /// The transaction logs of the sweeps submitted by this call.
+    pub transaction_log_ids: Vec<String>,
+
+    /// The fees paid by every sweep of the migration so far, by token.
+    pub fees: BTreeMap<TokenId, u64>,
+
+    /// The job which retires the legacy account once its balance reaches
+    /// zero.
+    pub job: Job,
+}
+
+/// Trait defining the ways in which the wallet can migrate legacy accounts.
+pub trait AccountMigrationService {
+    /// Move the funds of an account created from legacy root entropy to a new
+    /// account created from a mnemonic.
+    ///
+    /// The new account is created on the first call, and the comments of the
+    /// legacy account's assigned subaddresses are given to subaddresses
+    /// assigned on it in the same order. The spendable txos of each token are
+    /// then swept into the new account's main address, at most MAX_INPUTS per
+    /// transaction, each paying the network fee in the token swept. Txos too
+    /// small to pay the fee are left behind.
+    ///
+    /// The returned job waits for the sweeps to land, counting the txos moved,
+    /// and retires the legacy account by removing it once its balance reaches
+    /// zero. It fails if funds remain once no sweep is pending, such as when
+    /// a sweep expired, and calling this again resumes the migration into the
+    /// same new account. The legacy account is never retired while the new
+    /// account is removed.
+    fn migrate_account_to_mnemonic(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<AccountMigration, AccountMigrationServiceError>;
+}
+
+impl<T, FPR> AccountMigrationService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn migrate_account_to_mnemonic(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<AccountMigration, AccountMigrationServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let legacy_account = Account::get(account_id, &conn)?;
+        if legacy_account.key_derivation_version != ROOT_ENTROPY_KEY_DERIVATION_VERSION as i32
+            || !legacy_account.key_source()?.can_sign_locally()
+        {
+            return Err(AccountMigrationServiceError::NotLegacyAccount(
+                account_id.clone(),
+            ));
+        }
+
+        let new_account = match self.get_migrated_to_account(&legacy_account, &conn)? {
+            Some(new_account) => new_account,
+            None => self.create_migrated_to_account(&legacy_account, &conn)?,
+        };
+        let new_account_id = AccountID(new_account.id.clone());
+        let new_address_b58 =
+            b58_encode_public_address(&new_account.public_address(DEFAULT_SUBADDRESS_INDEX)?)?;
+        log::info!(
+            self.request_logger(),
+            "Migrating account {} to {}",
+            account_id,
+            new_account_id
+        );
+
+        let transaction_log_ids =
+            self.sweep_to_migrated_to_account(&legacy_account, &new_address_b58, &conn)?;
+
+        let total = count_unswept_txos(&account_id.to_string(), &conn)?;
+        let wallet_db = self.wallet_db.clone();
+        let logger = self.logger.clone();
+        let legacy_account_id = account_id.clone();
+        let job_new_account_id = new_account_id.clone();
+        let job_new_address_b58 = new_address_b58.clone();
+        let job = self
+            .jobs
+            .submit("migrate_account_to_mnemonic", move |context| {
+                retire_when_swept(
+                    &wallet_db,
+                    &legacy_account_id,
+                    &job_new_account_id,
+                    &job_new_address_b58,
+                    total,
+                    context,
+                    &logger,
+                )
+                .map_err(|e| e.to_string())
+            })?;
+
+        Ok(AccountMigration {
+            new_account,
+            transaction_log_ids,
+            fees: migration_fees(&account_id.to_string(), &new_address_b58, &conn)?,
+            job,
+        })
+    }
+}
+
+impl<T, FPR> WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    /// The account a legacy account is being migrated to, if a migration was
+    /// started and the account has not been purged since.
+    fn get_migrated_to_account(
+        &self,
+        legacy_account: &Account,
+        conn: &Conn,
+    ) -> Result<Option<Account>, AccountMigrationServiceError> {
+        let new_account_id = match &legacy_account.migrated_to_account_id {
+            Some(new_account_id) => AccountID(new_account_id.clone()),
+            None => return Ok(None),
+        };
+        match Account::get_including_removed(&new_account_id, conn) {
+            Ok(new_account) if new_account.removed_at.is_some() => Err(
+                AccountMigrationServiceError::MigratedToAccountRemoved(new_account_id),
+            ),
+            Ok(new_account) => Ok(Some(new_account)),
+            Err(WalletDbError::AccountNotFound(_)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Create the account a legacy account is migrated to, with the same fog
+    /// info, and the comments of the legacy account's assigned subaddresses,
+    /// and record it on the legacy account.
+    fn create_migrated_to_account(
+        &self,
+        legacy_account: &Account,
+        conn: &Conn,
+    ) -> Result<Account, AccountMigrationServiceError> {
+        let account_key: AccountKey = mc_util_serial::decode(&legacy_account.account_key)?;
+        let mnemonic = Mnemonic::new(MnemonicType::Words24, Language::English);
+
+        // As in create_account, the new account cannot have received funds
+        // before the current network height.
+        let local_block_height = self.ledger_db.num_blocks()?;
+        let network_block_height = if self.offline {
+            local_block_height
+        } else {
+            self.get_network_block_height()?
+        };
+
+        let mut subaddresses =
+            AssignedSubaddress::list_all(Some(legacy_account.id.clone()), None, None, conn)?
+                .into_iter()
+                .filter(|subaddress| {
+                    let index = subaddress.subaddress_index as u64;
+                    index >= DEFAULT_NEXT_SUBADDRESS_INDEX
+                        && !legacy_account
+                            .reserved_subaddresses()
+                            .iter()
+                            .any(|reserved| reserved.index() == index)
+                })
+                .collect::<Vec<_>>();
+        subaddresses.sort_by_key(|subaddress| subaddress.subaddress_index);
+
+        transaction(conn, || {
+            let (new_account_id, _public_address_b58) = Account::create_from_mnemonic(
+                &mnemonic,
+                Some(network_block_height),
+                Some(local_block_height),
+                None,
+                &format!("{} (migrated)", legacy_account.name),
+                account_key.fog_report_url().unwrap_or_default().to_string(),
+                account_key.fog_report_id().unwrap_or_default().to_string(),
+                base64::encode(account_key.fog_authority_spki().unwrap_or_default()),
+                conn,
+            )?;
+            for subaddress in &subaddresses {
+                AssignedSubaddress::create_next_for_account(
+                    &new_account_id.to_string(),
+                    &subaddress.comment,
+                    &self.ledger_db,
+                    conn,
+                )?;
+            }
+            legacy_account.update_migrated_to_account_id(&new_account_id, conn)?;
+            Ok(Account::get(&new_account_id, conn)?)
+        })
+    }
+
+    /// Submit transactions sweeping the spendable txos of a legacy account to
+    /// the new account's address, returning their transaction log ids.
+    fn sweep_to_migrated_to_account(
+        &self,
+        legacy_account: &Account,
+        new_address_b58: &str,
+        conn: &Conn,
+    ) -> Result<Vec<String>, AccountMigrationServiceError> {
+        let network_fees = self.get_network_fees();
+        let dust_thresholds = self.get_dust_thresholds();
+        let unconfirmed_block_index = unconfirmed_block_index(
+            self.ledger_db.num_blocks()?,
+            self.min_confirmations_for_account(legacy_account),
+        );
+
+        let mut transaction_log_ids = Vec::new();
+        for token_id in legacy_account.clone().get_token_ids(conn)? {
+            let network_fee = network_fees.get(&token_id).copied();
+            let spendable_txos = Txo::list_spendable(
+                Some(&legacy_account.id),
+                None,
+                None,
+                *token_id,
+                network_fee.unwrap_or_default(),
+                unconfirmed_block_index,
+                conn,
+            )?
+            .spendable_txos;
+            if spendable_txos.is_empty() {
+                continue;
+            }
+            let fee = network_fee.ok_or(AccountMigrationServiceError::NoFeeForToken(token_id))?;
+            let min_sweep = fee as u128 + *dust_thresholds.get(&token_id).unwrap_or(&0) as u128;
+
+            for inputs in spendable_txos.chunks(MAX_INPUTS as usize) {
+                let input_value: u128 = inputs.iter().map(|txo| txo.value as u64 as u128).sum();
+                let sweep_value = match u64::try_from(input_value.saturating_sub(fee as u128)) {
+                    Ok(sweep_value) if input_value >= min_sweep => sweep_value,
+                    _ => {
+                        log::warn!(
+                            self.request_logger(),
+                            "Leaving {} txos of token {} worth {} in account {}, too little to sweep",
+                            inputs.len(),
+                            token_id,
+                            input_value,
+                            legacy_account.id
+                        );
+                        continue;
+                    }
+                };
+
+                let input_txo_ids = inputs.iter().map(|txo| txo.id.clone()).collect();
+                let (transaction_log, _, _, _) = self.build_sign_and_submit_transaction(
+                    &legacy_account.id,
+                    &[(
+                        new_address_b58.to_string(),
+                        AmountJSON::new(sweep_value, token_id),
+                    )],
+                    Some(&input_txo_ids),
+                    Some(fee.to_string()),
+                    Some(token_id.to_string()),
+                    None,
+                    None,
+                    None,
+                    false,
+                    Some("Account migration".to_string()),
+                    TransactionMemo::RTH,
+                    None,
+                )?;
+                transaction_log_ids.push(transaction_log.id);
+            }
+        }
+        Ok(transaction_log_ids)
+    }
+}
+
+/// The number of txos of value in an account which are unspent, or inputs to
+/// a pending transaction.
+fn count_unswept_txos(account_id_hex: &str, conn: &Conn) -> Result<u64, WalletDbError> {
+    let unspent = Txo::list_unspent(
+        Some(account_id_hex),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        conn,
+    )?;
+    let pending = Txo::list_pending(
+        Some(account_id_hex),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        conn,
+    )?;
+    Ok(unspent
+        .iter()
+        .chain(pending.iter())
+        .filter(|txo| txo.value > 0)
+        .count() as u64)
+}
+
+/// The fees paid by the sweeps from a legacy account to the new account's
+/// address which have not failed, by token.
+fn migration_fees(
+    legacy_account_id_hex: &str,
+    new_address_b58: &str,
+    conn: &Conn,
+) -> Result<BTreeMap<TokenId, u64>, WalletDbError> {
+    let transaction_logs = TransactionLog::list_all(
+        Some(legacy_account_id_hex.to_string()),
+        None,
+        None,
+        &TransactionLogFilter {
+            recipient_public_address_b58: Some(new_address_b58.to_string()),
+            ..Default::default()
+        },
+        conn,
+    )?;
+    let mut fees = BTreeMap::new();
+    for (transaction_log, _, _) in transaction_logs {
+        if transaction_log.failed {
+            continue;
+        }
+        *fees
+            .entry(TokenId::from(transaction_log.fee_token_id as u64))
+            .or_insert(0) += transaction_log.fee_value as u64;
+    }
+    Ok(fees)
+}
+
+/// Wait for the sweeps of a migration to land, and retire the legacy account
+/// once none of its txos of value are left.
+fn retire_when_swept(
+    wallet_db: &WalletDb,
+    legacy_account_id: &AccountID,
+    new_account_id: &AccountID,
+    new_address_b58: &str,
+    total: u64,
+    context: &JobContext,
+    logger: &Logger,
+) -> Result<Option<serde_json::Value>, AccountMigrationServiceError> {
+    let legacy_account_id_hex = legacy_account_id.to_string();
+    loop {
+        let conn = wallet_db.get_conn()?;
+        let pending = Txo::list_pending(
+            Some(&legacy_account_id_hex),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+        let unswept = count_unswept_txos(&legacy_account_id_hex, &conn)?;
+        context.set_progress(total.saturating_sub(unswept), Some(total));
+
+        if pending.is_empty() {
+            if unswept > 0 {
+                return Err(AccountMigrationServiceError::Unswept(
+                    unswept,
+                    legacy_account_id.clone(),
+                ));
+            }
+
+            transaction(&conn, || {
+                // Never leave both accounts hidden.
+                Account::get(new_account_id, &conn).map_err(|_| {
+                    AccountMigrationServiceError::MigratedToAccountRemoved(new_account_id.clone())
+                })?;
+                Account::get(legacy_account_id, &conn)?
+                    .mark_removed(unix_timestamp_now(), &conn)?;
+                Ok(())
+            })?;
+            log::info!(
+                logger,
+                "Retired account {} after migrating it to {}",
+                legacy_account_id,
+                new_account_id
+            );
+
+            let fees = migration_fees(&legacy_account_id_hex, new_address_b58, &conn)?;
+            return Ok(Some(serde_json::json!({
+                "account_id": legacy_account_id_hex,
+                "new_account_id": new_account_id.to_string(),
+                "fees": fees
+                    .iter()
+                    .map(|(token_id, fee)| (token_id.to_string(), fee.to_string()))
+                    .collect::<BTreeMap<_, _>>(),
+            })));
+        }
+
+        if context.is_cancelled() {
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(
+            ACCOUNT_MIGRATION_POLL_INTERVAL_MILLIS,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::transaction_log::TransactionID,
+        service::{
+            account::AccountService,
+            address::AddressService,
+            job::{JobService, JobStatus},
+        },
+        test_utils::{
+            add_block_from_transaction_log, add_block_to_ledger_db, get_test_ledger,
+            manually_sync_account, setup_wallet_service, MOB,
+        },
+    };
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
+    use mc_transaction_core::{tokens::Mob, Token};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_migrate_account_to_mnemonic(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let mut entropy = [0u8; 32];
+        rng.fill_bytes(&mut entropy);
+        let legacy_account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(entropy),
+                Some("Legacy".to_string()),
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let legacy_account_id = AccountID(legacy_account.id.clone());
+        service
+            .assign_address_for_account(&legacy_account_id, Some("Alice"))
+            .unwrap();
+        service
+            .assign_address_for_account(&legacy_account_id, Some("Bob"))
+            .unwrap();
+
+        // More txos than fit in one transaction.
+        let public_address = legacy_account
+            .public_address(DEFAULT_SUBADDRESS_INDEX)
+            .unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![public_address; 20],
+            MOB,
+            &[],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &legacy_account_id, &logger);
+
+        let migration = service
+            .migrate_account_to_mnemonic(&legacy_account_id)
+            .unwrap();
+        let new_account_id = AccountID(migration.new_account.id.clone());
+        assert_eq!(migration.transaction_log_ids.len(), 2);
+        assert_eq!(migration.fees[&Mob::ID], 2 * Mob::MINIMUM_FEE);
+        assert_eq!(migration.job.kind, "migrate_account_to_mnemonic");
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        let comments =
+            AssignedSubaddress::list_all(Some(new_account_id.to_string()), None, None, &conn)
+                .unwrap()
+                .into_iter()
+                .filter(|subaddress| {
+                    subaddress.subaddress_index as u64 >= DEFAULT_NEXT_SUBADDRESS_INDEX
+                })
+                .map(|subaddress| subaddress.comment)
+                .collect::<Vec<_>>();
+        assert_eq!(comments, vec!["Alice".to_string(), "Bob".to_string()]);
+        assert_eq!(
+            Account::get(&legacy_account_id, &conn)
+                .unwrap()
+                .migrated_to_account_id,
+            Some(new_account_id.to_string())
+        );
+
+        // Migrating again while the sweeps are pending sweeps nothing more.
+        let resumed = service
+            .migrate_account_to_mnemonic(&legacy_account_id)
+            .unwrap();
+        assert_eq!(resumed.new_account.id, new_account_id.to_string());
+        assert!(resumed.transaction_log_ids.is_empty());
+        service.cancel_job(&resumed.job.id).unwrap();
+
+        for transaction_log_id in &migration.transaction_log_ids {
+            let transaction_log =
+                TransactionLog::get(&TransactionID(transaction_log_id.clone()), &conn).unwrap();
+            add_block_from_transaction_log(&mut ledger_db, &conn, &transaction_log, &mut rng);
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &legacy_account_id, &logger);
+        manually_sync_account(&ledger_db, &service.wallet_db, &new_account_id, &logger);
+
+        let mut job = service.get_job_status(&migration.job.id).unwrap();
+        for _ in 0..500 {
+            if job.status.is_finished() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            job = service.get_job_status(&migration.job.id).unwrap();
+        }
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(job.progress, 20);
+        assert_eq!(job.total, Some(20));
+        assert_eq!(
+            job.result.unwrap()["new_account_id"],
+            new_account_id.to_string()
+        );
+
+        // The legacy account is retired, and its funds are in the new
+        // account less the fees.
+        assert!(Account::get(&legacy_account_id, &conn).is_err());
+        assert!(Account::get_including_removed(&legacy_account_id, &conn)
+            .unwrap()
+            .removed_at
+            .is_some());
+        let new_value: u64 = Txo::list_unspent(
+            Some(&new_account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap()
+        .iter()
+        .map(|txo| txo.value as u64)
+        .sum();
+        assert_eq!(new_value, 20 * MOB - 2 * Mob::MINIMUM_FEE);
+    }
+}
@@ -4,14 +4,30 @@
 
 use crate::{
     db::{
-        account::AccountID, assigned_subaddress::AssignedSubaddressModel,
-        models::AssignedSubaddress, transaction, WalletDbError,
+        account::{AccountID, AccountModel, ReservedSubaddress},
+        assigned_subaddress::AssignedSubaddressModel,
+        idempotency_key::unix_timestamp_now,
+        models::{Account, AssignedSubaddress},
+        transaction, WalletDbError,
     },
     service::WalletService,
-    util::b58::b58_decode_public_address,
+    util::{
+        address_attestation::{
+            create_address_attestation, verify_address_attestation, AddressAttestation,
+            AddressAttestationError,
+        },
+        b58::{b58_decode_public_address, b58_encode_public_address},
+        constants::{
+            ADDRESS_ATTESTATION_MAX_AGE_SECONDS, MAINNET_FOG_DOMAINS, TESTNET_FOG_DOMAINS,
+        },
+        encoding_helpers::{hex_to_ristretto_public, hex_to_vec},
+    },
 };
+use mc_account_keys::{AccountKey, PublicAddress};
 use mc_connection::{BlockchainConnection, UserTxConnection};
 use mc_fog_report_validation::FogPubkeyResolver;
+use mc_util_uri::{ConnectionUri, FogUri};
+use std::str::FromStr;
 
 use displaydoc::Display;
 
@@ -24,6 +40,35 @@ pub enum AddressServiceError {
 
     /// Diesel Error: {0}
     Diesel(diesel::result::Error),
+
+    /// Invalid public key: {0}
+    InvalidPublicKey(String),
+
+    /// Invalid fog authority signature: {0}
+    InvalidFogAuthoritySig(String),
+
+    /// A fog authority signature is required when a fog report url is provided
+    MissingFogAuthoritySig,
+
+    /// Invalid public address: {0}
+    InvalidPublicAddress(String),
+
+    /** The public addresses of the labels do not match the account at
+     * subaddress indices: {0}
+     */
+    AddressLabelMismatch(String),
+
+    /// Address attestation error: {0}
+    AddressAttestation(AddressAttestationError),
+
+    /// Account {0} cannot sign address attestations, because the wallet does not hold its spend key
+    CannotSignAddressAttestation(String),
+}
+
+impl From<AddressAttestationError> for AddressServiceError {
+    fn from(src: AddressAttestationError) -> Self {
+        Self::AddressAttestation(src)
+    }
 }
 
 impl From<WalletDbError> for AddressServiceError {
@@ -38,6 +83,199 @@ impl From<diesel::result::Error> for AddressServiceError {
     }
 }
 
+/// Which recipient addresses the wallet sends to, given the network it is
+/// connected to. b58 public addresses do not say which network they are for,
+/// so the fog report urls of fog addresses are checked against the fog
+/// domains of the network instead, along with explicit allow and deny lists.
+#[derive(Clone, Debug, Default)]
+pub struct AddressNetworkPolicy {
+    /// The network the wallet is connected to, such as "mainnet".
+    pub network: Option<String>,
+
+    /// Domains which the fog report urls of addresses on the network are in.
+    /// When empty, fog addresses cannot be checked.
+    pub allowed_fog_domains: Vec<String>,
+
+    /// Prefixes of b58 addresses which always pass. A full address only
+    /// matches itself.
+    pub allowlist: Vec<String>,
+
+    /// Prefixes of b58 addresses which always fail.
+    pub denylist: Vec<String>,
+}
+
+impl AddressNetworkPolicy {
+    /// A policy for the given network. Without explicit fog domains, the fog
+    /// domains of mainnet and testnet are known.
+    pub fn new(
+        network: Option<String>,
+        allowed_fog_domains: Vec<String>,
+        allowlist: Vec<String>,
+        denylist: Vec<String>,
+    ) -> Self {
+        let allowed_fog_domains = if allowed_fog_domains.is_empty() {
+            let known_domains: &[&str] = match network.as_deref() {
+                Some("mainnet") => MAINNET_FOG_DOMAINS,
+                Some("testnet") => TESTNET_FOG_DOMAINS,
+                _ => &[],
+            };
+            known_domains.iter().map(|d| d.to_string()).collect()
+        } else {
+            allowed_fog_domains
+        };
+        Self {
+            network,
+            allowed_fog_domains,
+            allowlist,
+            denylist,
+        }
+    }
+
+    /// Check whether the address belongs to the wallet's network.
+    pub fn check(
+        &self,
+        public_address_b58: &str,
+        public_address: &PublicAddress,
+    ) -> AddressNetworkCheck {
+        let network = self.network.as_deref().unwrap_or("this network");
+
+        if self
+            .denylist
+            .iter()
+            .any(|prefix| public_address_b58.starts_with(prefix.as_str()))
+        {
+            return AddressNetworkCheck::failed("the address is on the denylist".to_string());
+        }
+        if self
+            .allowlist
+            .iter()
+            .any(|prefix| public_address_b58.starts_with(prefix.as_str()))
+        {
+            return AddressNetworkCheck::passed("the address is on the allowlist".to_string());
+        }
+
+        let fog_report_url = match public_address.fog_report_url() {
+            Some(url) if !url.is_empty() => url,
+            _ => {
+                return AddressNetworkCheck::warning(
+                    "the address has no fog info, so its network cannot be checked".to_string(),
+                )
+            }
+        };
+        if self.allowed_fog_domains.is_empty() {
+            return AddressNetworkCheck::warning(format!(
+                "no fog domains are configured for {}, so the fog report url cannot be checked",
+                network
+            ));
+        }
+
+        let host = match FogUri::from_str(fog_report_url) {
+            Ok(uri) => uri.host().to_lowercase(),
+            Err(e) => {
+                return AddressNetworkCheck::failed(format!(
+                    "the fog report url {} is invalid: {:?}",
+                    fog_report_url, e
+                ))
+            }
+        };
+        let in_allowed_domain = self.allowed_fog_domains.iter().any(|domain| {
+            let domain = domain.to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        });
+        if in_allowed_domain {
+            AddressNetworkCheck::passed(format!(
+                "the fog report host {} is in a fog domain of {}",
+                host, network
+            ))
+        } else {
+            AddressNetworkCheck::failed(format!(
+                "the fog report host {} is not in the fog domains of {}: {}",
+                host,
+                network,
+                self.allowed_fog_domains.join(", ")
+            ))
+        }
+    }
+}
+
+/// Whether an address belongs to the wallet's network, and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddressNetworkCheck {
+    /// Whether the wallet will send to the address without an override.
+    pub passed: bool,
+
+    /// Set when the address passed only because its network could not be
+    /// checked.
+    pub warning: bool,
+
+    pub reasons: Vec<String>,
+}
+
+impl AddressNetworkCheck {
+    fn passed(reason: String) -> Self {
+        Self {
+            passed: true,
+            warning: false,
+            reasons: vec![reason],
+        }
+    }
+
+    fn warning(reason: String) -> Self {
+        Self {
+            passed: true,
+            warning: true,
+            reasons: vec![reason],
+        }
+    }
+
+    fn failed(reason: String) -> Self {
+        Self {
+            passed: false,
+            warning: false,
+            reasons: vec![reason],
+        }
+    }
+}
+
+/// A subaddress the wallet reserves for an account, and its public address.
+#[derive(Debug, Clone)]
+pub struct ReservedAddress {
+    /// What the wallet reserves the subaddress for.
+    pub purpose: ReservedSubaddress,
+
+    /// The public address at the reserved subaddress index.
+    pub public_address: PublicAddress,
+}
+
+/// The label of an assigned subaddress, which can be copied between wallets
+/// holding the same account without moving its keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressLabel {
+    /// The index of the address in the subaddress space for the account.
+    pub subaddress_index: u64,
+
+    /// A b58 encoding of the public address, which must match the address
+    /// the account derives at the index.
+    pub public_address_b58: String,
+
+    /// The comment of the address.
+    pub comment: String,
+}
+
+/// What importing address labels changed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AddressLabelImport {
+    /// Number of assigned addresses whose comment changed.
+    pub num_updated: u64,
+
+    /// Number of addresses assigned for labels at unassigned indices.
+    pub num_created: u64,
+
+    /// Indices of labels which were skipped because they are not assigned,
+    /// when missing addresses are not created.
+    pub unassigned_indices: Vec<u64>,
+}
+
 /// Trait defining the ways in which the wallet can interact with and manage
 /// addresses.
 pub trait AddressService {
@@ -49,6 +287,51 @@ pub trait AddressService {
         // FIXME: FS-32 - add "sync from block"
     ) -> Result<AssignedSubaddress, AddressServiceError>;
 
+    /// Creates an address at the given subaddress index, such as an index
+    /// assigned outside the wallet.
+    fn assign_address_at_index(
+        &self,
+        account_id: &AccountID,
+        subaddress_index: u64,
+        metadata: Option<&str>,
+    ) -> Result<AssignedSubaddress, AddressServiceError>;
+
+    /// Gets the labels of the addresses assigned for an account, by
+    /// subaddress index.
+    fn export_address_labels(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Vec<AddressLabel>, AddressServiceError>;
+
+    /// Sets the comments of the addresses assigned for an account from labels
+    /// exported from a wallet holding the same account. Labels at indices
+    /// which are not assigned are skipped, or assigned when create_missing is
+    /// set. Nothing is imported if the public address of any label does not
+    /// match the account.
+    fn import_address_labels(
+        &self,
+        account_id: &AccountID,
+        labels: &[AddressLabel],
+        create_missing: bool,
+    ) -> Result<AddressLabelImport, AddressServiceError>;
+
+    /// Signs an attestation that an address assigned in this wallet belongs to
+    /// it, with the spend key of the address, carrying the nonce and the
+    /// current time.
+    fn create_address_attestation(
+        &self,
+        public_address_b58: &str,
+        nonce: &str,
+    ) -> Result<String, AddressServiceError>;
+
+    /// Checks the signature of an attestation from
+    /// create_address_attestation, returning its contents and whether it is
+    /// stale, and so may have been replayed.
+    fn verify_address_attestation(
+        &self,
+        attestation: &str,
+    ) -> Result<(AddressAttestation, bool), AddressServiceError>;
+
     /// Get an assigned subaddress, if it exists.
     fn get_address(&self, address_b58: &str) -> Result<AssignedSubaddress, AddressServiceError>;
 
@@ -66,8 +349,32 @@ pub trait AddressService {
         limit: Option<u64>,
     ) -> Result<Vec<AssignedSubaddress>, AddressServiceError>;
 
+    /// Gets the public addresses at the subaddress indices the wallet reserves
+    /// for an account, whether or not they have been assigned.
+    fn get_reserved_addresses(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Vec<ReservedAddress>, AddressServiceError>;
+
     /// Verifies whether an address can be decoded from b58.
     fn verify_address(&self, public_address: &str) -> Result<bool, AddressServiceError>;
+
+    /// Checks whether an address belongs to the network the wallet is
+    /// connected to.
+    fn check_address_network(
+        &self,
+        public_address: &str,
+    ) -> Result<AddressNetworkCheck, AddressServiceError>;
+
+    /// Constructs a public address from hex encoded view and spend public
+    /// keys, along with optional fog info.
+    fn public_address_from_keys(
+        &self,
+        view_public_hex: &str,
+        spend_public_hex: &str,
+        fog_report_url: Option<&str>,
+        fog_authority_sig_hex: Option<&str>,
+    ) -> Result<PublicAddress, AddressServiceError>;
 }
 
 impl<T, FPR> AddressService for WalletService<T, FPR>
@@ -93,6 +400,143 @@ where
         })
     }
 
+    fn assign_address_at_index(
+        &self,
+        account_id: &AccountID,
+        subaddress_index: u64,
+        metadata: Option<&str>,
+    ) -> Result<AssignedSubaddress, AddressServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let public_address_b58 = AssignedSubaddress::create_at_index_for_account(
+                &account_id.to_string(),
+                subaddress_index,
+                metadata.unwrap_or(""),
+                &self.ledger_db,
+                &conn,
+            )?;
+            Ok(AssignedSubaddress::get(&public_address_b58, &conn)?)
+        })
+    }
+
+    fn export_address_labels(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Vec<AddressLabel>, AddressServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?;
+        let mut labels: Vec<AddressLabel> =
+            AssignedSubaddress::list_all(Some(account_id.to_string()), None, None, &conn)?
+                .into_iter()
+                .map(|address| AddressLabel {
+                    subaddress_index: address.subaddress_index as u64,
+                    public_address_b58: address.public_address_b58,
+                    comment: address.comment,
+                })
+                .collect();
+        labels.sort_by_key(|label| label.subaddress_index);
+        Ok(labels)
+    }
+
+    fn import_address_labels(
+        &self,
+        account_id: &AccountID,
+        labels: &[AddressLabel],
+        create_missing: bool,
+    ) -> Result<AddressLabelImport, AddressServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let account = Account::get(account_id, &conn)?;
+
+            // Check every label before changing any, so that labels from
+            // another account are not partly imported.
+            let mut mismatched_indices = Vec::new();
+            for label in labels {
+                let public_address_b58 =
+                    b58_encode_public_address(&account.public_address(label.subaddress_index)?)
+                        .map_err(|e| AddressServiceError::InvalidPublicAddress(e.to_string()))?;
+                if public_address_b58 != label.public_address_b58 {
+                    mismatched_indices.push(label.subaddress_index.to_string());
+                }
+            }
+            if !mismatched_indices.is_empty() {
+                return Err(AddressServiceError::AddressLabelMismatch(
+                    mismatched_indices.join(", "),
+                ));
+            }
+
+            let mut import = AddressLabelImport::default();
+            for label in labels {
+                match AssignedSubaddress::get_for_account_by_index(
+                    &account_id.to_string(),
+                    label.subaddress_index as i64,
+                    &conn,
+                ) {
+                    Ok(address) => {
+                        if address.comment != label.comment {
+                            address.update_comment(&label.comment, &conn)?;
+                            import.num_updated += 1;
+                        }
+                    }
+                    Err(WalletDbError::Diesel(diesel::result::Error::NotFound)) => {
+                        if create_missing {
+                            AssignedSubaddress::create_at_index_for_account(
+                                &account_id.to_string(),
+                                label.subaddress_index,
+                                &label.comment,
+                                &self.ledger_db,
+                                &conn,
+                            )?;
+                            import.num_created += 1;
+                        } else {
+                            import.unassigned_indices.push(label.subaddress_index);
+                        }
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Ok(import)
+        })
+    }
+
+    fn create_address_attestation(
+        &self,
+        public_address_b58: &str,
+        nonce: &str,
+    ) -> Result<String, AddressServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let address = AssignedSubaddress::get(public_address_b58, &conn)?;
+        let account = Account::get(&AccountID(address.account_id.clone()), &conn)?;
+        if account.view_only || !account.key_source()?.can_sign_locally() {
+            return Err(AddressServiceError::CannotSignAddressAttestation(
+                account.id,
+            ));
+        }
+        let account_key: AccountKey =
+            mc_util_serial::decode(&account.account_key).map_err(WalletDbError::from)?;
+
+        Ok(create_address_attestation(
+            &account_key.subaddress_spend_private(address.subaddress_index as u64),
+            &AddressAttestation {
+                public_address_b58: public_address_b58.to_string(),
+                nonce: nonce.to_string(),
+                timestamp: unix_timestamp_now() as u64,
+            },
+        )?)
+    }
+
+    fn verify_address_attestation(
+        &self,
+        attestation: &str,
+    ) -> Result<(AddressAttestation, bool), AddressServiceError> {
+        let attestation = verify_address_attestation(attestation)?;
+        let stale = attestation.is_stale(
+            unix_timestamp_now() as u64,
+            ADDRESS_ATTESTATION_MAX_AGE_SECONDS,
+        );
+        Ok((attestation, stale))
+    }
+
     fn get_address(&self, address_b58: &str) -> Result<AssignedSubaddress, AddressServiceError> {
         let conn = self.wallet_db.get_conn()?;
         Ok(AssignedSubaddress::get(address_b58, &conn)?)
@@ -123,30 +567,104 @@ where
         )?)
     }
 
+    fn get_reserved_addresses(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Vec<ReservedAddress>, AddressServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        account
+            .reserved_subaddresses()
+            .into_iter()
+            .map(|purpose| {
+                Ok(ReservedAddress {
+                    purpose,
+                    public_address: account.public_address(purpose.index())?,
+                })
+            })
+            .collect()
+    }
+
     fn verify_address(&self, public_address: &str) -> Result<bool, AddressServiceError> {
         match b58_decode_public_address(public_address) {
             Ok(_) => Ok(true),
             Err(_) => Ok(false),
         }
     }
+
+    fn check_address_network(
+        &self,
+        public_address: &str,
+    ) -> Result<AddressNetworkCheck, AddressServiceError> {
+        let decoded = b58_decode_public_address(public_address)
+            .map_err(|e| AddressServiceError::InvalidPublicAddress(e.to_string()))?;
+        Ok(self.address_network_policy.check(public_address, &decoded))
+    }
+
+    fn public_address_from_keys(
+        &self,
+        view_public_hex: &str,
+        spend_public_hex: &str,
+        fog_report_url: Option<&str>,
+        fog_authority_sig_hex: Option<&str>,
+    ) -> Result<PublicAddress, AddressServiceError> {
+        let view_public_key = hex_to_ristretto_public(view_public_hex)
+            .map_err(AddressServiceError::InvalidPublicKey)?;
+        let spend_public_key = hex_to_ristretto_public(spend_public_hex)
+            .map_err(AddressServiceError::InvalidPublicKey)?;
+
+        match fog_report_url {
+            Some(fog_report_url) if !fog_report_url.is_empty() => {
+                let fog_authority_sig_hex = fog_authority_sig_hex
+                    .filter(|sig| !sig.is_empty())
+                    .ok_or(AddressServiceError::MissingFogAuthoritySig)?;
+                let fog_authority_sig = hex_to_vec(fog_authority_sig_hex)
+                    .map_err(AddressServiceError::InvalidFogAuthoritySig)?;
+                Ok(PublicAddress::new_with_fog(
+                    &spend_public_key,
+                    &view_public_key,
+                    fog_report_url,
+                    "".to_string(),
+                    fog_authority_sig,
+                ))
+            }
+            _ => Ok(PublicAddress::new(&spend_public_key, &view_public_key)),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        db::account::AccountModel,
-        service::account::AccountService,
-        test_utils::{get_test_ledger, setup_wallet_service},
+        db::{
+            account::AccountModel,
+            models::Txo,
+            txo::{TxoModel, TxoStatus},
+        },
+        json_rpc::v2::models::amount::Amount as AmountJSON,
+        service::{
+            account::AccountService,
+            transaction::{TransactionMemo, TransactionService},
+        },
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
         util::{
             b58::b58_encode_public_address,
+            constants::LEGACY_CHANGE_SUBADDRESS_INDEX,
             encoding_helpers::{ristretto_public_to_hex, ristretto_to_hex},
         },
     };
-    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_account_keys::{
+        AccountKey, RootEntropy, RootIdentity, CHANGE_SUBADDRESS_INDEX, DEFAULT_SUBADDRESS_INDEX,
+        GIFT_CODE_SUBADDRESS_INDEX, INVALID_SUBADDRESS_INDEX,
+    };
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
     use mc_crypto_rand::rand_core::RngCore;
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
     use mc_util_from_random::FromRandom;
     use rand::{rngs::StdRng, SeedableRng};
 
@@ -194,7 +712,7 @@ mod tests {
 
         // Create an account.
         let account = service
-            .import_view_only_account(vpk_hex, spk_hex, None, None, None)
+            .import_view_only_account(vpk_hex, spk_hex, None, None, None, false)
             .unwrap();
         assert_eq!(account.clone().next_subaddress_index(&conn).unwrap(), 2);
 
@@ -208,6 +726,145 @@ mod tests {
         assert_eq!(account.next_subaddress_index(&conn).unwrap(), 3);
     }
 
+    #[test_with_logger]
+    fn test_assign_address_at_index(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+        let conn = service.wallet_db.get_conn().unwrap();
+
+        let account = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let account_id = AccountID(account.id);
+
+        let address = service
+            .assign_address_at_index(&account_id, 10, Some("Customer 10"))
+            .unwrap();
+        assert_eq!(address.subaddress_index, 10);
+        assert_eq!(address.comment, "Customer 10");
+
+        // An index below the next sequential index does not move it back.
+        service
+            .assign_address_at_index(&account_id, 5, None)
+            .unwrap();
+        let account = service.get_account(&account_id).unwrap();
+        assert_eq!(account.next_subaddress_index(&conn).unwrap(), 11);
+
+        // Assigned indices collide, both with each other and with the
+        // sequential assigner.
+        match service.assign_address_at_index(&account_id, 10, None) {
+            Err(AddressServiceError::Database(WalletDbError::SubaddressIndexAlreadyAssigned(
+                10,
+            ))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let address = service
+            .assign_address_for_account(&account_id, None)
+            .unwrap();
+        assert_eq!(address.subaddress_index, 11);
+        match service.assign_address_at_index(&account_id, 11, None) {
+            Err(AddressServiceError::Database(WalletDbError::SubaddressIndexAlreadyAssigned(
+                11,
+            ))) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Reserved indices are rejected.
+        for index in [
+            DEFAULT_SUBADDRESS_INDEX,
+            LEGACY_CHANGE_SUBADDRESS_INDEX,
+            CHANGE_SUBADDRESS_INDEX,
+            GIFT_CODE_SUBADDRESS_INDEX,
+            INVALID_SUBADDRESS_INDEX,
+        ] {
+            match service.assign_address_at_index(&account_id, index, None) {
+                Err(AddressServiceError::Database(WalletDbError::ReservedSubaddressIndex(i)))
+                    if i == index => {}
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test_with_logger]
+    fn test_assign_address_at_index_repairs_orphaned_txos(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let entropy = RootEntropy::from_random(&mut rng);
+        let account_key = AccountKey::from(&RootIdentity::from(&entropy));
+
+        // The account is paid at a high index before it is assigned.
+        let high_index = 1_000_000;
+        let ledger_db = get_test_ledger(5, &[account_key.subaddress(high_index)], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let conn = service.wallet_db.get_conn().unwrap();
+
+        let account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&entropy.bytes),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let account_id = AccountID(account.id);
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let orphaned_txos = Txo::list_orphaned(
+            Some(&account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(orphaned_txos.len(), 12);
+
+        let address = service
+            .assign_address_at_index(&account_id, high_index, None)
+            .unwrap();
+        assert_eq!(address.subaddress_index, high_index as i64);
+
+        let orphaned_txos = Txo::list_orphaned(
+            Some(&account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(orphaned_txos.len(), 0);
+
+        let unspent_txos = Txo::list_for_address(
+            &address.public_address_b58,
+            Some(TxoStatus::Unspent),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(unspent_txos.len(), 12);
+        assert!(unspent_txos.iter().all(|txo| txo.key_image.is_some()));
+
+        // The sequential assigner continues past the high index.
+        let address = service
+            .assign_address_for_account(&account_id, None)
+            .unwrap();
+        assert_eq!(address.subaddress_index, high_index as i64 + 1);
+    }
+
     // A properly encoded address should verify.
     #[test_with_logger]
     fn test_verify_address_succeeds(logger: Logger) {
@@ -253,4 +910,409 @@ mod tests {
             .verify_address(&public_address_b58)
             .expect("Could not verify address"));
     }
+
+    #[test_with_logger]
+    fn test_public_address_from_keys(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account_key = AccountKey::random(&mut rng);
+        let public_address = account_key.default_subaddress();
+        let view_public_hex = ristretto_public_to_hex(public_address.view_public_key());
+        let spend_public_hex = ristretto_public_to_hex(public_address.spend_public_key());
+
+        let constructed = service
+            .public_address_from_keys(&view_public_hex, &spend_public_hex, None, None)
+            .expect("Could not construct public address");
+        assert_eq!(constructed, public_address);
+        assert!(b58_encode_public_address(&constructed).is_ok());
+    }
+
+    #[test_with_logger]
+    fn test_public_address_from_keys_invalid_point(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let spend_public_hex = ristretto_public_to_hex(&RistrettoPublic::from_random(&mut rng));
+        // 32 bytes of 0xff is not a valid compressed Ristretto point.
+        let view_public_hex = "ff".repeat(32);
+
+        match service.public_address_from_keys(&view_public_hex, &spend_public_hex, None, None) {
+            Err(AddressServiceError::InvalidPublicKey(_)) => {}
+            Ok(_) => panic!("Should not construct an address from an invalid point"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_public_address_from_keys_fog_url_without_sig(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let view_public_hex = ristretto_public_to_hex(&RistrettoPublic::from_random(&mut rng));
+        let spend_public_hex = ristretto_public_to_hex(&RistrettoPublic::from_random(&mut rng));
+
+        match service.public_address_from_keys(
+            &view_public_hex,
+            &spend_public_hex,
+            Some("fog://fog.example.com"),
+            None,
+        ) {
+            Err(AddressServiceError::MissingFogAuthoritySig) => {}
+            Ok(_) => panic!("Should require a fog authority sig with a fog report url"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    fn fog_address_b58(fog_report_url: &str, rng: &mut StdRng) -> String {
+        let public_address = PublicAddress::new_with_fog(
+            &RistrettoPublic::from_random(rng),
+            &RistrettoPublic::from_random(rng),
+            fog_report_url,
+            "".to_string(),
+            vec![1u8; 64],
+        );
+        b58_encode_public_address(&public_address).unwrap()
+    }
+
+    #[test_with_logger]
+    fn test_check_address_network(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let mut service = setup_wallet_service(ledger_db, logger);
+
+        let mainnet_address = fog_address_b58("fog://fog.prod.mobilecoinww.com", &mut rng);
+        let testnet_address = fog_address_b58("fog://fog.test.mobilecoin.com", &mut rng);
+        let plain_address =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+
+        // Without a network, fog addresses cannot be checked.
+        let check = service.check_address_network(&mainnet_address).unwrap();
+        assert!(check.passed);
+        assert!(check.warning);
+
+        service.address_network_policy =
+            AddressNetworkPolicy::new(Some("mainnet".to_string()), vec![], vec![], vec![]);
+
+        let check = service.check_address_network(&mainnet_address).unwrap();
+        assert!(check.passed);
+        assert!(!check.warning);
+
+        let check = service.check_address_network(&testnet_address).unwrap();
+        assert!(!check.passed);
+        assert!(check.reasons[0].contains("fog.test.mobilecoin.com"));
+
+        // Addresses without fog pass, flagged with a warning.
+        let check = service.check_address_network(&plain_address).unwrap();
+        assert!(check.passed);
+        assert!(check.warning);
+
+        // The allow and deny lists take precedence over the fog domain check.
+        service.address_network_policy = AddressNetworkPolicy::new(
+            Some("mainnet".to_string()),
+            vec![],
+            vec![testnet_address.clone()],
+            vec![plain_address[..10].to_string()],
+        );
+        assert!(
+            service
+                .check_address_network(&testnet_address)
+                .unwrap()
+                .passed
+        );
+        assert!(
+            !service
+                .check_address_network(&plain_address)
+                .unwrap()
+                .passed
+        );
+
+        assert!(matches!(
+            service.check_address_network("NOTVALIDB58"),
+            Err(AddressServiceError::InvalidPublicAddress(_))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_get_reserved_addresses(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_id = AccountID(alice.id.clone());
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+
+        let reserved_addresses = service.get_reserved_addresses(&alice_account_id).unwrap();
+        assert_eq!(
+            reserved_addresses
+                .iter()
+                .map(|r| r.purpose)
+                .collect::<Vec<_>>(),
+            vec![
+                ReservedSubaddress::Main,
+                ReservedSubaddress::LegacyChange,
+                ReservedSubaddress::Change,
+                ReservedSubaddress::GiftCode,
+            ]
+        );
+        for reserved_address in reserved_addresses.iter() {
+            assert_eq!(
+                reserved_address.public_address,
+                alice_account_key.subaddress(reserved_address.purpose.index())
+            );
+        }
+        let change_address = reserved_addresses[2].public_address.clone();
+
+        // The change of a transaction is sent to the reserved change address.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[alice_account_key.default_subaddress()],
+            100 * MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.change_txos.len(), 1);
+        assert_eq!(
+            tx_proposal.change_txos[0].recipient_public_address,
+            change_address
+        );
+
+        // A view only account of the same keys, in another wallet, reserves the
+        // same addresses.
+        let view_only_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let view_only = view_only_service
+            .import_view_only_account(
+                ristretto_to_hex(alice_account_key.view_private_key()),
+                ristretto_public_to_hex(&alice_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        let view_only_reserved_addresses = view_only_service
+            .get_reserved_addresses(&AccountID(view_only.id))
+            .unwrap();
+        assert_eq!(view_only_reserved_addresses.len(), reserved_addresses.len());
+        for (view_only_reserved, reserved) in view_only_reserved_addresses
+            .iter()
+            .zip(reserved_addresses.iter())
+        {
+            assert_eq!(view_only_reserved.purpose, reserved.purpose);
+            assert_eq!(view_only_reserved.public_address, reserved.public_address);
+        }
+    }
+
+    #[test_with_logger]
+    fn test_export_and_import_address_labels(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_id = AccountID(alice.id.clone());
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        service
+            .assign_address_for_account(&alice_account_id, Some("Alice's friend"))
+            .unwrap();
+        service
+            .assign_address_at_index(&alice_account_id, 10, Some("Customer 10"))
+            .unwrap();
+
+        let labels = service.export_address_labels(&alice_account_id).unwrap();
+        assert_eq!(
+            labels
+                .iter()
+                .map(|label| label.subaddress_index)
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 10, CHANGE_SUBADDRESS_INDEX]
+        );
+        for label in labels.iter() {
+            assert_eq!(
+                label.public_address_b58,
+                b58_encode_public_address(&alice_account_key.subaddress(label.subaddress_index))
+                    .unwrap()
+            );
+        }
+        assert_eq!(labels[3].comment, "Customer 10");
+
+        // The view only twin of the account, in another wallet, has not
+        // assigned the contacts' addresses.
+        let view_only_service = setup_wallet_service(ledger_db, logger);
+        let view_only = view_only_service
+            .import_view_only_account(
+                ristretto_to_hex(alice_account_key.view_private_key()),
+                ristretto_public_to_hex(&alice_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        let view_only_account_id = AccountID(view_only.id);
+
+        let import = view_only_service
+            .import_address_labels(&view_only_account_id, &labels, false)
+            .unwrap();
+        assert_eq!(import.num_created, 0);
+        assert_eq!(import.unassigned_indices, vec![2, 10]);
+
+        let import = view_only_service
+            .import_address_labels(&view_only_account_id, &labels, true)
+            .unwrap();
+        assert_eq!(import.num_created, 2);
+        assert_eq!(import.num_updated, 0);
+        assert!(import.unassigned_indices.is_empty());
+        assert_eq!(
+            view_only_service
+                .export_address_labels(&view_only_account_id)
+                .unwrap(),
+            labels
+        );
+
+        // Importing the labels again changes nothing.
+        let import = view_only_service
+            .import_address_labels(&view_only_account_id, &labels, true)
+            .unwrap();
+        assert_eq!(import, AddressLabelImport::default());
+
+        // A label whose address is not the account's address at its index is
+        // rejected, along with the rest of the labels.
+        let mut mismatched_labels = labels.clone();
+        mismatched_labels[2].public_address_b58 =
+            b58_encode_public_address(&alice_account_key.subaddress(3)).unwrap();
+        mismatched_labels[3].comment = "Customer 11".to_string();
+        match view_only_service.import_address_labels(
+            &view_only_account_id,
+            &mismatched_labels,
+            true,
+        ) {
+            Err(AddressServiceError::AddressLabelMismatch(indices)) => assert_eq!(indices, "2"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        let address = view_only_service
+            .get_address_for_account(&view_only_account_id, 10)
+            .unwrap();
+        assert_eq!(address.comment, "Customer 10");
+
+        // Labels of another account do not match at all.
+        let bob = view_only_service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        match view_only_service.import_address_labels(&AccountID(bob.id), &labels, true) {
+            Err(AddressServiceError::AddressLabelMismatch(indices)) => {
+                assert_eq!(indices, format!("0, 1, 2, 10, {}", CHANGE_SUBADDRESS_INDEX))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_address_attestation(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_id = AccountID(alice.id.clone());
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let deposit_address = service
+            .assign_address_for_account(&alice_account_id, Some("Deposits"))
+            .unwrap();
+
+        let blob = service
+            .create_address_attestation(&deposit_address.public_address_b58, "ticket-4812")
+            .unwrap();
+        let (attestation, stale) = service.verify_address_attestation(&blob).unwrap();
+        assert_eq!(
+            attestation.public_address_b58,
+            deposit_address.public_address_b58
+        );
+        assert_eq!(attestation.nonce, "ticket-4812");
+        assert!(!stale);
+
+        // An attestation from long ago is still validly signed, but stale.
+        let old_blob = create_address_attestation(
+            &alice_account_key.subaddress_spend_private(deposit_address.subaddress_index as u64),
+            &AddressAttestation {
+                timestamp: attestation.timestamp - ADDRESS_ATTESTATION_MAX_AGE_SECONDS - 1,
+                ..attestation
+            },
+        )
+        .unwrap();
+        let (_, stale) = service.verify_address_attestation(&old_blob).unwrap();
+        assert!(stale);
+
+        // Addresses which are not assigned in the wallet cannot be attested.
+        let unknown = b58_encode_public_address(&alice_account_key.subaddress(50)).unwrap();
+        assert!(service
+            .create_address_attestation(&unknown, "ticket-4812")
+            .is_err());
+
+        // Nor can the addresses of view only accounts, whose spend keys the
+        // wallet does not hold.
+        let view_only_service = setup_wallet_service(ledger_db, logger);
+        let view_only = view_only_service
+            .import_view_only_account(
+                ristretto_to_hex(alice_account_key.view_private_key()),
+                ristretto_public_to_hex(&alice_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        let main_address =
+            b58_encode_public_address(&alice_account_key.default_subaddress()).unwrap();
+        match view_only_service.create_address_attestation(&main_address, "ticket-4812") {
+            Err(AddressServiceError::CannotSignAddressAttestation(account_id)) => {
+                assert_eq!(account_id, view_only.id)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 }
@@ -1,14 +1,16 @@
 // Copyright (c) 2020-2021 MobileCoin Inc.
 
 //! Service for managing balances.
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use crate::{
     db::{
-        account::{AccountID, AccountModel},
+        account::{AccountID, AccountModel, AccountSyncCounts},
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, AssignedSubaddress, Txo},
-        txo::TxoModel,
+        models::{Account, AssignedSubaddress, TransactionLog, Txo},
+        transaction,
+        transaction_log::TransactionLogModel,
+        txo::{unconfirmed_block_index, AccountTokenValues, TxoModel},
         Conn, WalletDbError,
     },
     service::{
@@ -20,9 +22,13 @@ use crate::{
 use displaydoc::Display;
 use mc_common::HashMap;
 use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::RistrettoPublic;
 use mc_fog_report_validation::FogPubkeyResolver;
 use mc_ledger_db::Ledger;
-use mc_transaction_core::TokenId;
+use mc_transaction_core::{
+    constants::{MAX_INPUTS, MAX_OUTPUTS},
+    TokenId,
+};
 
 /// Errors for the Address Service.
 #[derive(Display, Debug)]
@@ -86,6 +92,9 @@ pub struct Balance {
     pub max_spendable: u128,
     pub unverified: u128,
     pub unspent: u128,
+    /// The part of unspent received too recently to have the account's
+    /// minimum number of confirmations, which can not be spent yet.
+    pub unconfirmed: u128,
     pub pending: u128,
     pub spent: u128,
     pub secreted: u128,
@@ -98,6 +107,7 @@ impl Default for &Balance {
             max_spendable: 0,
             unverified: 0,
             unspent: 0,
+            unconfirmed: 0,
             pending: 0,
             spent: 0,
             secreted: 0,
@@ -113,6 +123,11 @@ pub struct NetworkStatus {
     pub local_block_height: u64,
     pub fees: BTreeMap<TokenId, u64>,
     pub block_version: u32,
+    pub dust_thresholds: BTreeMap<TokenId, u64>,
+    /// The most inputs consensus accepts in a transaction.
+    pub max_inputs: u64,
+    /// The most outputs consensus accepts in a transaction, including change.
+    pub max_outputs: u64,
 }
 
 /// The Wallet Status object returned by balance services.
@@ -129,6 +144,44 @@ pub struct WalletStatus {
     pub min_synced_block_index: u64,
     pub account_ids: Vec<AccountID>,
     pub account_map: HashMap<AccountID, Account>,
+    /// Txos received by accounts which share their view private key with
+    /// another account in the wallet, and so appear under more than one
+    /// account.
+    pub duplicate_txo_count: u64,
+    /// Whether the wallet is shutting down, and turning away new requests.
+    pub shutting_down: bool,
+    /// Whether the ledger is read-only, and kept in sync by another process.
+    pub ledger_read_only: bool,
+    /// How many blocks the local ledger is behind the network.
+    pub ledger_height_lag: u64,
+}
+
+/// A rollup of the balances and sync state of the whole wallet, gathered
+/// with grouped queries rather than a query per account.
+pub struct WalletRollup {
+    /// The sum of the balances of all accounts.
+    pub balance_per_token: BTreeMap<TokenId, Balance>,
+    pub network_block_height: u64,
+    pub local_block_height: u64,
+    /// Accounts which have processed every block up to the network height.
+    pub num_accounts_synced: u64,
+    /// Accounts within the behind threshold of the network height.
+    pub num_accounts_syncing: u64,
+    /// Accounts further than the behind threshold from the network height.
+    pub num_accounts_behind: u64,
+    /// Submitted transactions which have neither landed nor failed.
+    pub num_pending_transactions: u64,
+    /// The rollup of each account, when requested.
+    pub accounts: Option<Vec<AccountRollup>>,
+}
+
+/// The balances and sync state of a single account, as part of a
+/// WalletRollup.
+pub struct AccountRollup {
+    pub account_id: AccountID,
+    pub next_block_index: u64,
+    pub balance_per_token: BTreeMap<TokenId, Balance>,
+    pub num_pending_transactions: u64,
 }
 
 /// Trait defining the ways in which the wallet can interact with and manage
@@ -149,7 +202,25 @@ pub trait BalanceService {
 
     fn get_network_status(&self) -> Result<NetworkStatus, BalanceServiceError>;
 
-    fn get_wallet_status(&self) -> Result<WalletStatus, BalanceServiceError>;
+    /// Gets the status of the wallet, or only of the accounts in
+    /// account_filter if given.
+    fn get_wallet_status(
+        &self,
+        account_filter: Option<&BTreeSet<String>>,
+    ) -> Result<WalletStatus, BalanceServiceError>;
+
+    /// Gets the balances of the whole wallet, the number of accounts in each
+    /// stage of sync, and the number of pending transactions, in a single pass
+    /// over the database. Accounts more than behind_threshold blocks from
+    /// the network height are counted as behind. The balances of each account
+    /// are included if include_accounts is set. Only the accounts in
+    /// account_filter are rolled up, if given.
+    fn get_wallet_rollup(
+        &self,
+        include_accounts: bool,
+        behind_threshold: u64,
+        account_filter: Option<&BTreeSet<String>>,
+    ) -> Result<WalletRollup, BalanceServiceError>;
 }
 
 impl<T, FPR> BalanceService for WalletService<T, FPR>
@@ -163,6 +234,10 @@ where
     ) -> Result<BTreeMap<TokenId, Balance>, BalanceServiceError> {
         let conn = &self.wallet_db.get_conn()?;
         let account = self.get_account(account_id)?;
+        let unconfirmed_block_index = unconfirmed_block_index(
+            self.ledger_db.num_blocks()?,
+            self.min_confirmations_for_account(&account),
+        );
         let distinct_token_ids = account.get_token_ids(conn)?;
 
         let network_fees = self.get_network_fees();
@@ -176,6 +251,7 @@ where
                     None,
                     token_id,
                     default_token_fee,
+                    unconfirmed_block_index,
                     conn,
                 )?;
                 Ok((token_id, balance))
@@ -193,6 +269,10 @@ where
         let assigned_address = AssignedSubaddress::get(address, conn)?;
         let account_id = AccountID::from(assigned_address.account_id);
         let account = self.get_account(&account_id)?;
+        let unconfirmed_block_index = unconfirmed_block_index(
+            self.ledger_db.num_blocks()?,
+            self.min_confirmations_for_account(&account),
+        );
         let distinct_token_ids = account.get_token_ids(conn)?;
         let network_fees = self.get_network_fees();
 
@@ -205,6 +285,7 @@ where
                     Some(address),
                     token_id,
                     default_token_fee,
+                    unconfirmed_block_index,
                     conn,
                 )?;
                 Ok((token_id, balance))
@@ -220,26 +301,44 @@ where
             local_block_height: self.ledger_db.num_blocks()?,
             fees: self.get_network_fees(),
             block_version: *self.get_network_block_version(),
+            dust_thresholds: self.get_dust_thresholds(),
+            max_inputs: MAX_INPUTS,
+            max_outputs: MAX_OUTPUTS,
         })
     }
 
     // Wallet Status is an overview of the wallet's status
-    fn get_wallet_status(&self) -> Result<WalletStatus, BalanceServiceError> {
+    fn get_wallet_status(
+        &self,
+        account_filter: Option<&BTreeSet<String>>,
+    ) -> Result<WalletStatus, BalanceServiceError> {
         let network_block_height = self.get_network_block_height()?;
 
         let conn = self.wallet_db.get_conn()?;
-        let accounts = Account::list_all(&conn, None, None)?;
+        let accounts = Account::list_all(&conn, None, None)?
+            .into_iter()
+            .filter(|account| account_filter.map_or(true, |filter| filter.contains(&account.id)));
         let mut account_map = HashMap::default();
 
         let mut balance_per_token = BTreeMap::new();
 
+        let local_block_height = self.ledger_db.num_blocks()?;
         let mut min_synced_block_index = network_block_height.saturating_sub(1);
         let mut account_ids = Vec::new();
+        let mut account_ids_by_view_key: HashMap<[u8; 32], Vec<String>> = HashMap::default();
         let network_fees = self.get_network_fees();
 
         for account in accounts {
             let account_id = AccountID(account.id.clone());
+            account_ids_by_view_key
+                .entry(RistrettoPublic::from(&account.view_private_key()?).to_bytes())
+                .or_default()
+                .push(account.id.clone());
             let token_ids = account.clone().get_token_ids(&conn)?;
+            let unconfirmed_block_index = unconfirmed_block_index(
+                local_block_height,
+                self.min_confirmations_for_account(&account),
+            );
 
             for token_id in token_ids {
                 let default_token_fee = network_fees.get(&token_id).unwrap_or(&0);
@@ -248,6 +347,7 @@ where
                     None,
                     token_id,
                     default_token_fee,
+                    unconfirmed_block_index,
                     &conn,
                 )?;
                 balance_per_token
@@ -255,6 +355,7 @@ where
                     .and_modify(|b: &mut Balance| {
                         b.unverified += balance.unverified;
                         b.unspent += balance.unspent;
+                        b.unconfirmed += balance.unconfirmed;
                         b.pending += balance.pending;
                         b.spent += balance.spent;
                         b.secreted += balance.secreted;
@@ -273,17 +374,170 @@ where
             account_ids.push(account_id);
         }
 
+        let shared_view_key_account_ids: Vec<String> = account_ids_by_view_key
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .flat_map(|(_, ids)| ids)
+            .collect();
+        let duplicate_txo_count =
+            Txo::count_received_by_accounts(&shared_view_key_account_ids, &conn)?;
+
         Ok(WalletStatus {
             balance_per_token,
             network_block_height,
-            local_block_height: self.ledger_db.num_blocks()?,
+            local_block_height,
             min_synced_block_index,
             account_ids,
             account_map,
+            duplicate_txo_count,
+            shutting_down: self.shutdown_coordinator.is_shutting_down(),
+            ledger_read_only: self.ledger_read_only,
+            ledger_height_lag: network_block_height.saturating_sub(local_block_height),
+        })
+    }
+
+    fn get_wallet_rollup(
+        &self,
+        include_accounts: bool,
+        behind_threshold: u64,
+        account_filter: Option<&BTreeSet<String>>,
+    ) -> Result<WalletRollup, BalanceServiceError> {
+        let in_filter =
+            |account_id: &str| account_filter.map_or(true, |filter| filter.contains(account_id));
+        let network_block_height = self.get_network_block_height()?;
+        let local_block_height = self.ledger_db.num_blocks()?;
+        let network_fees = self.get_network_fees();
+
+        // Read everything within one transaction, so that the sums and counts
+        // agree with each other even while accounts are syncing.
+        let conn = self.wallet_db.get_conn()?;
+        let (values, sync_counts, pending_counts, accounts) = transaction(&conn, || {
+            let values = Txo::sum_values_by_account_and_token(
+                local_block_height,
+                self.min_confirmations,
+                &conn,
+            )?;
+            let sync_counts = match account_filter {
+                Some(_) => count_by_sync_state(
+                    Account::list_all(&conn, None, None)?
+                        .iter()
+                        .filter(|account| in_filter(account.id.as_str())),
+                    network_block_height,
+                    behind_threshold,
+                ),
+                None => {
+                    Account::count_by_sync_state(network_block_height, behind_threshold, &conn)?
+                }
+            };
+            let pending_counts = TransactionLog::count_pending_by_account(&conn)?;
+            let accounts = if include_accounts {
+                Some(
+                    Account::list_all(&conn, None, None)?
+                        .into_iter()
+                        .filter(|account| in_filter(account.id.as_str()))
+                        .collect::<Vec<_>>(),
+                )
+            } else {
+                None
+            };
+            Ok::<_, BalanceServiceError>((values, sync_counts, pending_counts, accounts))
+        })?;
+        let pending_counts: HashMap<String, u64> = pending_counts
+            .into_iter()
+            .filter(|(account_id, _)| in_filter(account_id.as_str()))
+            .collect();
+
+        let mut balance_per_token: BTreeMap<TokenId, Balance> = BTreeMap::new();
+        let mut balances_per_account: HashMap<String, BTreeMap<TokenId, Balance>> =
+            HashMap::default();
+        for ((account_id, token_id), token_values) in values {
+            if !in_filter(account_id.as_str()) {
+                continue;
+            }
+            let default_token_fee = network_fees.get(&token_id).unwrap_or(&0);
+            let balance = balance_from_values(&token_values, *default_token_fee);
+            add_balance(balance_per_token.entry(token_id).or_default(), &balance);
+            if include_accounts {
+                balances_per_account
+                    .entry(account_id)
+                    .or_default()
+                    .insert(token_id, balance);
+            }
+        }
+
+        let accounts = accounts.map(|accounts| {
+            accounts
+                .into_iter()
+                .map(|account| AccountRollup {
+                    balance_per_token: balances_per_account.remove(&account.id).unwrap_or_default(),
+                    num_pending_transactions: pending_counts.get(&account.id).copied().unwrap_or(0),
+                    next_block_index: account.next_block_index as u64,
+                    account_id: AccountID(account.id),
+                })
+                .collect()
+        });
+
+        Ok(WalletRollup {
+            balance_per_token,
+            network_block_height,
+            local_block_height,
+            num_accounts_synced: sync_counts.synced as u64,
+            num_accounts_syncing: sync_counts.syncing as u64,
+            num_accounts_behind: sync_counts.behind as u64,
+            num_pending_transactions: pending_counts.values().sum(),
+            accounts,
         })
     }
 }
 
+/// Counts the given accounts in each stage of sync, as
+/// Account::count_by_sync_state counts every account.
+fn count_by_sync_state<'a>(
+    accounts: impl Iterator<Item = &'a Account>,
+    block_height: u64,
+    behind_threshold: u64,
+) -> AccountSyncCounts {
+    let mut counts = AccountSyncCounts::default();
+    for account in accounts {
+        let next_block_index = account.next_block_index as u64;
+        if next_block_index >= block_height {
+            counts.synced += 1;
+        } else if block_height - next_block_index <= behind_threshold {
+            counts.syncing += 1;
+        } else {
+            counts.behind += 1;
+        }
+    }
+    counts
+}
+
+/// The balance of an account in a token, from the summed values of its txos.
+fn balance_from_values(values: &AccountTokenValues, default_token_fee: u64) -> Balance {
+    Balance {
+        max_spendable: values
+            .max_spendable_inputs
+            .saturating_sub(default_token_fee as u128),
+        unverified: values.unverified,
+        unspent: values.unspent,
+        unconfirmed: values.unconfirmed,
+        pending: values.pending,
+        spent: values.spent,
+        secreted: 0,
+        orphaned: values.orphaned,
+    }
+}
+
+fn add_balance(total: &mut Balance, balance: &Balance) {
+    total.max_spendable += balance.max_spendable;
+    total.unverified += balance.unverified;
+    total.unspent += balance.unspent;
+    total.unconfirmed += balance.unconfirmed;
+    total.pending += balance.pending;
+    total.spent += balance.spent;
+    total.secreted += balance.secreted;
+    total.orphaned += balance.orphaned;
+}
+
 fn sum_query_result(txos: Vec<Txo>) -> u128 {
     txos.iter().map(|t| (t.value as u64) as u128).sum::<u128>()
 }
@@ -299,6 +553,7 @@ where
         public_address_b58: Option<&str>,
         token_id: TokenId,
         default_token_fee: &u64,
+        unconfirmed_block_index: Option<u64>,
         conn: &Conn,
     ) -> Result<Balance, BalanceServiceError> {
         let unspent = sum_query_result(Txo::list_unspent(
@@ -312,6 +567,20 @@ where
             conn,
         )?);
 
+        let unconfirmed = match unconfirmed_block_index {
+            Some(unconfirmed_block_index) => sum_query_result(Txo::list_unspent(
+                account_id_hex,
+                public_address_b58,
+                Some(*token_id),
+                Some(unconfirmed_block_index),
+                None,
+                None,
+                None,
+                conn,
+            )?),
+            None => 0,
+        };
+
         let spent = sum_query_result(Txo::list_spent(
             account_id_hex,
             public_address_b58,
@@ -347,8 +616,12 @@ where
 
         let secreted = 0;
 
-        let orphaned = if public_address_b58.is_some() {
-            0
+        let orphaned = if let Some(public_address_b58) = public_address_b58 {
+            sum_query_result(Txo::list_orphaned_for_address(
+                public_address_b58,
+                Some(*token_id),
+                conn,
+            )?)
         } else {
             sum_query_result(Txo::list_orphaned(
                 account_id_hex,
@@ -367,6 +640,7 @@ where
             public_address_b58,
             *token_id,
             *default_token_fee,
+            unconfirmed_block_index,
             conn,
         )?;
 
@@ -374,6 +648,7 @@ where
             max_spendable: spendable_txos_result.max_spendable_in_wallet,
             unverified,
             unspent,
+            unconfirmed,
             pending,
             spent,
             secreted,
@@ -386,15 +661,22 @@ where
 mod tests {
     use super::*;
     use crate::{
+        db::txo::TxoStatus,
+        error::WalletTransactionBuilderError,
         service::{account::AccountService, address::AddressService},
-        test_utils::{get_test_ledger, manually_sync_account, setup_wallet_service, MOB},
+        test_utils::{
+            add_block_to_ledger_db, builder_for_random_recipient,
+            create_test_minted_and_change_txos, create_test_received_txo, get_test_ledger,
+            manually_sync_account, random_account_with_seed_values, setup_wallet_service,
+            setup_wallet_service_with_dust_thresholds, MOB,
+        },
         util::b58::b58_encode_public_address,
     };
     use mc_account_keys::{AccountKey, PublicAddress, RootEntropy, RootIdentity};
     use mc_common::logger::{test_with_logger, Logger};
-    use mc_transaction_core::{tokens::Mob, Token};
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Amount, Token};
     use mc_util_from_random::FromRandom;
-    use rand::{rngs::StdRng, SeedableRng};
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
 
     // The balance for an address should be accurate.
     #[test_with_logger]
@@ -429,6 +711,7 @@ mod tests {
                 "".to_string(),
                 "".to_string(),
                 "".to_string(),
+                false,
             )
             .expect("Could not import account entropy");
 
@@ -504,4 +787,376 @@ mod tests {
             Err(e) => panic!("Unexpected error {:?}", e),
         }
     }
+
+    // The balance for an address should only count txos of its own account,
+    // and report orphaned txos which match it.
+    #[test_with_logger]
+    fn test_address_balance_with_spent_and_orphaned_txos(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let entropy = RootEntropy::from_random(&mut rng);
+        let account_key = AccountKey::from(&RootIdentity::from(&entropy));
+
+        let public_address0 = account_key.subaddress(0);
+        let public_address3 = account_key.subaddress(3);
+        let known_recipients: Vec<PublicAddress> =
+            vec![public_address0.clone(), public_address3.clone()];
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&entropy.bytes),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .expect("Could not import account entropy");
+        let account_id = AccountID(account.id.clone());
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        // Another account receives at its own subaddress 0.
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_account_key: AccountKey = mc_util_serial::decode(&bob.account_key).unwrap();
+        let bob_account_id = AccountID::from(&bob_account_key);
+
+        // Spend one of the txos received at subaddress 0.
+        let b58_pub_address0 = b58_encode_public_address(&public_address0).unwrap();
+        let conn = service.wallet_db.get_conn().unwrap();
+        let unspent_txos = Txo::list_for_address(
+            &b58_pub_address0,
+            Some(TxoStatus::Unspent),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        let key_image: KeyImage =
+            mc_util_serial::decode(unspent_txos[0].key_image.as_ref().unwrap()).unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![bob_account_key.default_subaddress()],
+            1_000 * MOB,
+            &vec![key_image],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+        manually_sync_account(&ledger_db, &service.wallet_db, &bob_account_id, &logger);
+
+        let address_balance = service
+            .get_balance_for_address(&b58_pub_address0)
+            .expect("Could not get balance for address");
+        let address_balance_pmob = address_balance.get(&Mob::ID).unwrap();
+        assert_eq!(address_balance_pmob.unspent, 55_000 * MOB as u128);
+        assert_eq!(address_balance_pmob.spent, 5_000 * MOB as u128);
+        assert_eq!(address_balance_pmob.pending, 0);
+        assert_eq!(address_balance_pmob.orphaned, 0);
+
+        // Subaddress 3 was paid before it was assigned, so its txos are
+        // orphaned until they are recovered.
+        let b58_pub_address3 = b58_encode_public_address(&public_address3).unwrap();
+        AssignedSubaddress::create(&account_key, 3, "", &conn).unwrap();
+
+        let address_balance = service
+            .get_balance_for_address(&b58_pub_address3)
+            .expect("Could not get balance for address");
+        let address_balance_pmob = address_balance.get(&Mob::ID).unwrap();
+        assert_eq!(address_balance_pmob.unspent, 0);
+        assert_eq!(address_balance_pmob.spent, 0);
+        assert_eq!(address_balance_pmob.orphaned, 60_000 * MOB as u128);
+
+        let orphaned_txos = Txo::list_for_address(
+            &b58_pub_address3,
+            Some(TxoStatus::Orphaned),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(orphaned_txos.len(), 12);
+    }
+
+    // The wallet rollup should agree with the balances of each account, and
+    // count the accounts by how far they are from the network height.
+    #[test_with_logger]
+    fn test_wallet_rollup(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let entropy = RootEntropy::from_random(&mut rng);
+        let account_key = AccountKey::from(&RootIdentity::from(&entropy));
+        let public_address0 = account_key.subaddress(0);
+        let public_address3 = account_key.subaddress(3);
+        let known_recipients: Vec<PublicAddress> = vec![public_address0.clone(), public_address3];
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        // Alice has unspent txos at subaddress 0 and orphaned txos at
+        // subaddress 3.
+        let alice = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&entropy.bytes),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let alice_account_id = AccountID(alice.id.clone());
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Bob receives MOB, and an amount of another token.
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_account_key: AccountKey = mc_util_serial::decode(&bob.account_key).unwrap();
+        let bob_account_id = AccountID::from(&bob_account_key);
+
+        // Alice spends one of her txos, paying Bob.
+        let conn = service.wallet_db.get_conn().unwrap();
+        let b58_pub_address0 = b58_encode_public_address(&public_address0).unwrap();
+        let unspent_txos = Txo::list_for_address(
+            &b58_pub_address0,
+            Some(TxoStatus::Unspent),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap();
+        let key_image: KeyImage =
+            mc_util_serial::decode(unspent_txos[0].key_image.as_ref().unwrap()).unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![bob_account_key.default_subaddress()],
+            1_000 * MOB,
+            &vec![key_image],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+        manually_sync_account(&ledger_db, &service.wallet_db, &bob_account_id, &logger);
+        create_test_received_txo(
+            &bob_account_key,
+            0,
+            Amount::new(250 * MOB, TokenId::from(1)),
+            ledger_db.num_blocks().unwrap() - 1,
+            &mut rng,
+            &service.wallet_db,
+        );
+
+        // Alice has a transaction in flight.
+        create_test_minted_and_change_txos(
+            account_key.clone(),
+            bob_account_key.default_subaddress(),
+            10 * MOB,
+            service.wallet_db.clone(),
+            ledger_db.clone(),
+        );
+
+        // Carol starts from the origin block, Dave a couple of blocks from the
+        // tip, and neither has synced yet.
+        let network_block_height = ledger_db.num_blocks().unwrap();
+        let carol = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&RootEntropy::from_random(&mut rng).bytes),
+                None,
+                Some(0),
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let dave = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&RootEntropy::from_random(&mut rng).bytes),
+                None,
+                Some(network_block_height - 2),
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+
+        let rollup = service.get_wallet_rollup(true, 5, None).unwrap();
+        assert_eq!(rollup.network_block_height, network_block_height);
+        assert_eq!(rollup.local_block_height, network_block_height);
+        assert_eq!(rollup.num_accounts_synced, 2);
+        assert_eq!(rollup.num_accounts_syncing, 1);
+        assert_eq!(rollup.num_accounts_behind, 1);
+        assert_eq!(rollup.num_pending_transactions, 1);
+
+        let account_rollups = rollup.accounts.unwrap();
+        assert_eq!(account_rollups.len(), 4);
+
+        let mut expected_totals: BTreeMap<TokenId, Balance> = BTreeMap::new();
+        for account_rollup in account_rollups {
+            let balances = service
+                .get_balance_for_account(&account_rollup.account_id)
+                .unwrap();
+            assert_eq!(account_rollup.balance_per_token, balances);
+            for (token_id, balance) in balances {
+                add_balance(expected_totals.entry(token_id).or_default(), &balance);
+            }
+
+            let expected_pending = if account_rollup.account_id == alice_account_id {
+                1
+            } else {
+                0
+            };
+            assert_eq!(account_rollup.num_pending_transactions, expected_pending);
+
+            if account_rollup.account_id.0 == carol.id || account_rollup.account_id.0 == dave.id {
+                assert!(account_rollup.balance_per_token.is_empty());
+            }
+        }
+        assert_eq!(rollup.balance_per_token, expected_totals);
+
+        let total_mob = rollup.balance_per_token.get(&Mob::ID).unwrap();
+        assert!(total_mob.unspent > 0);
+        assert!(total_mob.pending > 0);
+        assert_eq!(total_mob.spent, 5_000 * MOB as u128);
+        assert_eq!(total_mob.orphaned, 60_000 * MOB as u128);
+        assert_eq!(
+            rollup
+                .balance_per_token
+                .get(&TokenId::from(1))
+                .unwrap()
+                .unspent,
+            250 * MOB as u128
+        );
+
+        // The per-account breakdown is left out unless requested.
+        let rollup = service.get_wallet_rollup(false, 5, None).unwrap();
+        assert!(rollup.accounts.is_none());
+        assert_eq!(rollup.balance_per_token, expected_totals);
+    }
+
+    // Configured dust thresholds override the network fees of their tokens.
+    #[test_with_logger]
+    fn test_network_status_dust_thresholds(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let mut overrides = BTreeMap::new();
+        overrides.insert(Mob::ID, MOB);
+        let service = setup_wallet_service_with_dust_thresholds(ledger_db, overrides, logger);
+
+        let network_status = service.get_network_status().unwrap();
+        let eusd_token_id = TokenId::from(1);
+        assert_eq!(network_status.dust_thresholds.get(&Mob::ID), Some(&MOB));
+        assert_eq!(
+            network_status.dust_thresholds.get(&eusd_token_id),
+            network_status.fees.get(&eusd_token_id)
+        );
+    }
+
+    // Received txos are unconfirmed, and can not be selected, until they have
+    // been in the ledger for the account's minimum number of confirmations.
+    #[test_with_logger]
+    fn test_min_confirmations(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id = AccountID::from(&account_key);
+        let account = service
+            .update_account_min_confirmations(&account_id, Some(3))
+            .unwrap();
+        assert_eq!(account.min_confirmations, Some(3));
+
+        let balance = service.get_balance_for_account(&account_id).unwrap();
+        let balance_pmob = balance.get(&Mob::ID).unwrap();
+        assert_eq!(balance_pmob.unspent, 70 * MOB as u128);
+        assert_eq!(balance_pmob.unconfirmed, 70 * MOB as u128);
+        assert_eq!(balance_pmob.max_spendable, 0);
+
+        let rollup = service.get_wallet_rollup(true, 5, None).unwrap();
+        let account_rollup = &rollup.accounts.unwrap()[0];
+        assert_eq!(account_rollup.balance_per_token, balance);
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.add_recipient(recipient, 10 * MOB, Mob::ID).unwrap();
+        builder.set_min_confirmations(3);
+        match builder.select_txos(&conn, None) {
+            Err(WalletTransactionBuilderError::WalletDb(WalletDbError::NoSpendableTxos)) => {}
+            Ok(_) => panic!("Should not select an unconfirmed txo"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+
+        // The override selects it anyway.
+        builder.set_include_unconfirmed(true);
+        builder.select_txos(&conn, None).unwrap();
+
+        for _ in 0..3 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &[AccountKey::random(&mut rng).default_subaddress()],
+                100 * MOB,
+                &[KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+
+        let balance = service.get_balance_for_account(&account_id).unwrap();
+        let balance_pmob = balance.get(&Mob::ID).unwrap();
+        assert_eq!(balance_pmob.unspent, 70 * MOB as u128);
+        assert_eq!(balance_pmob.unconfirmed, 0);
+        assert_eq!(
+            balance_pmob.max_spendable,
+            70 * MOB as u128 - Mob::MINIMUM_FEE as u128
+        );
+
+        let rollup = service.get_wallet_rollup(true, 5, None).unwrap();
+        let account_rollup = &rollup.accounts.unwrap()[0];
+        assert_eq!(account_rollup.balance_per_token, balance);
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.add_recipient(recipient, 10 * MOB, Mob::ID).unwrap();
+        builder.set_min_confirmations(3);
+        builder.select_txos(&conn, None).unwrap();
+    }
 }
@@ -0,0 +1,598 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for reporting why a transaction fails to build.
+//!
+//! The report runs the same steps as building a transaction, recording the
+//! outcome of each, and is meant to be attached to support tickets. Ids are
+//! hashed with a salt which is not included in the report, and addresses are
+//! truncated, so that the report does not identify the wallet on the ledger.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountModel},
+        models::{Account, Txo},
+        txo::{unconfirmed_block_index, SpendableTxosResult, TxoModel},
+        WalletDbError,
+    },
+    json_rpc::v2::models::amount::Amount as AmountJSON,
+    service::{
+        address::AddressService,
+        ledger::LedgerService,
+        transaction::{
+            account_default_fee, account_default_fee_token_id, account_default_tombstone_block,
+            validate_number_inputs, validate_number_outputs, TransactionMemo,
+            TransactionServiceError,
+        },
+        transaction_builder::WalletTransactionBuilder,
+    },
+    util::{b58::b58_decode_public_address, constants::DEBUG_BUILD_REPORT_MAX_TXOS},
+    WalletService,
+};
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
+use mc_crypto_rand::rand_core::RngCore;
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
+use mc_transaction_core::{tokens::Mob, Amount, Token, TokenId};
+use std::{collections::BTreeMap, convert::TryFrom};
+use strum::Display;
+
+/// The step of the build at which the build failed.
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+#[strum(serialize_all = "snake_case")]
+pub enum BuildStep {
+    ValidateInputs,
+    ValidateOutputs,
+    Recipients,
+    Tombstone,
+    Fee,
+    Selection,
+    Build,
+}
+
+/// A txo which was considered as an input.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildReportTxo {
+    /// Salted hash of the txo id.
+    pub id_hash: String,
+    pub value: u64,
+    pub token_id: u64,
+    pub status: String,
+}
+
+/// A recipient of the transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildReportOutlay {
+    /// Truncated b58 address of the recipient.
+    pub recipient: String,
+    pub value: u64,
+    pub token_id: u64,
+}
+
+/// The outcome of selecting inputs for one token.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuildReportSelection {
+    pub token_id: u64,
+
+    /// Value of the outlays for this token, including the fee if it is paid in
+    /// this token.
+    pub target_value: u64,
+
+    /// The largest value which can be spent in one transaction, if inputs
+    /// were selected from the wallet.
+    pub max_spendable_in_wallet: Option<u128>,
+
+    /// Salted hashes of the ids of the selected txos.
+    pub selected_txos: Vec<String>,
+    pub selected_value: u64,
+    pub failure: Option<String>,
+}
+
+/// A sanitized report of an attempted transaction build.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BuildReport {
+    /// Truncated id of the account.
+    pub account_id: String,
+    pub ledger_block_height: u64,
+    pub network_block_height: u64,
+    pub network_fees: BTreeMap<u64, u64>,
+    pub outlays: Vec<BuildReportOutlay>,
+    pub fee_value: Option<u64>,
+    pub fee_token_id: Option<u64>,
+    pub tombstone_block: Option<u64>,
+
+    /// Number of txos considered as inputs. At most
+    /// DEBUG_BUILD_REPORT_MAX_TXOS of them are listed in candidate_txos.
+    pub num_candidate_txos: usize,
+    pub candidate_txos: Vec<BuildReportTxo>,
+    pub selections: Vec<BuildReportSelection>,
+    pub failed_step: Option<BuildStep>,
+    pub failure: Option<String>,
+}
+
+/// Trait defining the ways in which the wallet can report on transaction
+/// builds.
+pub trait BuildReportService {
+    /// Run the steps of building a transaction without signing or logging it,
+    /// and report the outcome of each step.
+    ///
+    /// A build which fails is reported as Ok, with the failed step and the
+    /// reason in the report.
+    #[allow(clippy::too_many_arguments)]
+    fn debug_build_report(
+        &self,
+        account_id_hex: &str,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        input_txo_ids: Option<&Vec<String>>,
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+    ) -> Result<BuildReport, TransactionServiceError>;
+}
+
+impl<T, FPR> BuildReportService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn debug_build_report(
+        &self,
+        account_id_hex: &str,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        input_txo_ids: Option<&Vec<String>>,
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+    ) -> Result<BuildReport, TransactionServiceError> {
+        if !self.debug_build_report_enabled {
+            return Err(TransactionServiceError::DebugBuildReportDisabled);
+        }
+
+        let mut redactor = Redactor::new(account_id_hex);
+        let mut report = BuildReport {
+            account_id: truncate(account_id_hex),
+            ledger_block_height: self.ledger_db.num_blocks()?,
+            network_block_height: self.get_network_block_height().unwrap_or(0),
+            network_fees: self
+                .get_network_fees()
+                .iter()
+                .map(|(token_id, fee)| (**token_id, *fee))
+                .collect(),
+            ..Default::default()
+        };
+
+        let conn = self.wallet_db.get_conn()?;
+        let mut builder = WalletTransactionBuilder::new(
+            account_id_hex.to_string(),
+            self.ledger_db.clone(),
+            self.fog_resolver_factory.clone(),
+        );
+        builder.set_dust_thresholds(self.get_dust_thresholds());
+        let account = Account::get(&AccountID(account_id_hex.to_string()), &conn)?;
+        let min_confirmations = self.min_confirmations_for_account(&account);
+        builder.set_min_confirmations(min_confirmations);
+        let unconfirmed_block_index =
+            unconfirmed_block_index(report.ledger_block_height, min_confirmations);
+
+        macro_rules! fail {
+            ($step:expr, $error:expr) => {{
+                report.failed_step = Some($step);
+                report.failure = Some(redactor.redact(&$error.to_string()));
+                return Ok(report);
+            }};
+        }
+
+        if let Err(e) = validate_number_inputs(input_txo_ids.map_or(0, |ids| ids.len()) as u64) {
+            fail!(BuildStep::ValidateInputs, e);
+        }
+        if let Err(e) = validate_number_outputs(addresses_and_amounts.len() as u64) {
+            fail!(BuildStep::ValidateOutputs, e);
+        }
+
+        let mut default_fee_token_id = Mob::ID;
+        for (recipient_public_address, amount) in addresses_and_amounts {
+            redactor.add_address(recipient_public_address);
+            let amount = match Amount::try_from(amount) {
+                Ok(amount) => amount,
+                Err(e) => fail!(BuildStep::Recipients, e),
+            };
+            report.outlays.push(BuildReportOutlay {
+                recipient: truncate(recipient_public_address),
+                value: amount.value,
+                token_id: *amount.token_id,
+            });
+            if !self.verify_address(recipient_public_address)? {
+                fail!(
+                    BuildStep::Recipients,
+                    TransactionServiceError::InvalidPublicAddress(
+                        recipient_public_address.to_string()
+                    )
+                );
+            }
+            let recipient = b58_decode_public_address(recipient_public_address)?;
+            if let Err(e) = builder.add_recipient(recipient, amount.value, amount.token_id) {
+                fail!(BuildStep::Recipients, e);
+            }
+            default_fee_token_id = amount.token_id;
+        }
+
+        let tombstone = match tombstone_block.map(|t| t.parse::<u64>()).transpose() {
+            Ok(tombstone) => tombstone.unwrap_or_else(|| {
+                account_default_tombstone_block(&account, report.ledger_block_height)
+            }),
+            Err(e) => fail!(BuildStep::Tombstone, e),
+        };
+        if let Err(e) = builder.set_tombstone(tombstone) {
+            fail!(BuildStep::Tombstone, e);
+        }
+        report.tombstone_block = Some(builder.tombstone());
+
+        let fee_token_id = match fee_token_id.map(|t| t.parse::<u64>()).transpose() {
+            Ok(fee_token_id) => fee_token_id.map_or_else(
+                || account_default_fee_token_id(&account, default_fee_token_id),
+                TokenId::from,
+            ),
+            Err(e) => fail!(BuildStep::Fee, e),
+        };
+        report.fee_token_id = Some(*fee_token_id);
+        let fee_value = match fee_value {
+            Some(f) => match f.parse::<u64>() {
+                Ok(fee_value) => fee_value,
+                Err(e) => fail!(BuildStep::Fee, e),
+            },
+            None => match account_default_fee(&account, fee_token_id)
+                .or_else(|| report.network_fees.get(&*fee_token_id).copied())
+            {
+                Some(fee_value) => fee_value,
+                None => fail!(
+                    BuildStep::Fee,
+                    TransactionServiceError::DefaultFeeNotFoundForToken(fee_token_id)
+                ),
+            },
+        };
+        report.fee_value = Some(fee_value);
+        if let Err(e) = builder.set_fee(fee_value, fee_token_id) {
+            fail!(BuildStep::Fee, e);
+        }
+        builder.set_block_version(self.get_network_block_version());
+
+        let max_spendable = match max_spendable_value.map(|m| m.parse::<u64>()).transpose() {
+            Ok(max_spendable) => max_spendable,
+            Err(e) => fail!(BuildStep::Selection, e),
+        };
+
+        let mut target_values: BTreeMap<u64, u64> = BTreeMap::new();
+        for outlay in report.outlays.iter() {
+            *target_values.entry(outlay.token_id).or_default() += outlay.value;
+        }
+        *target_values.entry(*fee_token_id).or_default() += fee_value;
+
+        let mut candidates = Vec::new();
+        match input_txo_ids {
+            Some(input_txo_ids) => {
+                candidates = Txo::select_by_id(input_txo_ids, &conn)?;
+                for (token_id, target_value) in target_values.iter() {
+                    let selected: Vec<&Txo> = candidates
+                        .iter()
+                        .filter(|txo| txo.token_id as u64 == *token_id)
+                        .collect();
+                    report.selections.push(BuildReportSelection {
+                        token_id: *token_id,
+                        target_value: *target_value,
+                        max_spendable_in_wallet: None,
+                        selected_txos: selected.iter().map(|txo| redactor.txo(&txo.id)).collect(),
+                        selected_value: selected.iter().map(|txo| txo.value as u64).sum(),
+                        failure: None,
+                    });
+                }
+            }
+            None => {
+                for (token_id, target_value) in target_values.iter() {
+                    let default_token_fee = if *token_id == *fee_token_id {
+                        fee_value
+                    } else {
+                        0
+                    };
+                    let SpendableTxosResult {
+                        spendable_txos,
+                        max_spendable_in_wallet,
+                    } = Txo::list_spendable(
+                        Some(account_id_hex),
+                        max_spendable,
+                        None,
+                        *token_id,
+                        default_token_fee,
+                        unconfirmed_block_index,
+                        &conn,
+                    )?;
+                    candidates.extend(spendable_txos);
+
+                    let mut selection = BuildReportSelection {
+                        token_id: *token_id,
+                        target_value: *target_value,
+                        max_spendable_in_wallet: Some(max_spendable_in_wallet),
+                        selected_txos: vec![],
+                        selected_value: 0,
+                        failure: None,
+                    };
+                    match Txo::select_spendable_txos_for_value(
+                        account_id_hex,
+                        *target_value,
+                        max_spendable,
+                        None,
+                        *token_id,
+                        default_token_fee,
+                        unconfirmed_block_index,
+                        &conn,
+                    ) {
+                        Ok(selected) => {
+                            selection.selected_txos =
+                                selected.iter().map(|txo| redactor.txo(&txo.id)).collect();
+                            selection.selected_value =
+                                selected.iter().map(|txo| txo.value as u64).sum();
+                        }
+                        Err(e) => selection.failure = Some(redactor.redact(&e.to_string())),
+                    }
+                    report.selections.push(selection);
+                }
+            }
+        }
+
+        report.num_candidate_txos = candidates.len();
+        for txo in candidates.iter().take(DEBUG_BUILD_REPORT_MAX_TXOS) {
+            let status = match txo.status(&conn) {
+                Ok(status) => status.to_string(),
+                Err(WalletDbError::TxoNotFound(_)) => "unknown".to_string(),
+                Err(e) => return Err(e.into()),
+            };
+            report.candidate_txos.push(BuildReportTxo {
+                id_hash: redactor.txo(&txo.id),
+                value: txo.value as u64,
+                token_id: txo.token_id as u64,
+                status,
+            });
+        }
+
+        let selected = match input_txo_ids {
+            Some(input_txo_ids) => builder.set_txos(&conn, input_txo_ids),
+            None => builder.select_txos(&conn, max_spendable),
+        };
+        if let Err(e) = selected {
+            fail!(BuildStep::Selection, e);
+        }
+
+        if let Err(e) = builder.build(TransactionMemo::RTH) {
+            fail!(BuildStep::Build, e);
+        }
+
+        Ok(report)
+    }
+}
+
+/// Show only the start of an identifier.
+fn truncate(identifier: &str) -> String {
+    format!("{}...", identifier.chars().take(8).collect::<String>())
+}
+
+/// Replaces identifiers with their redacted forms.
+struct Redactor {
+    /// Salt for hashing txo ids, so that the hashes cannot be matched against
+    /// txos on the ledger.
+    salt: String,
+
+    /// Identifiers seen so far, with their redacted forms.
+    redactions: Vec<(String, String)>,
+}
+
+impl Redactor {
+    fn new(account_id_hex: &str) -> Self {
+        let mut salt = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self {
+            salt: hex::encode(salt),
+            redactions: vec![(account_id_hex.to_string(), truncate(account_id_hex))],
+        }
+    }
+
+    fn add_address(&mut self, address: &str) {
+        self.redactions
+            .push((address.to_string(), truncate(address)));
+    }
+
+    /// The salted hash of a txo id.
+    fn txo(&mut self, txo_id_hex: &str) -> String {
+        let digest: [u8; 32] = format!("{}{}", self.salt, txo_id_hex)
+            .digest32::<MerlinTranscript>(b"debug_build_report_txo");
+        let id_hash = hex::encode(&digest[..8]);
+        self.redactions
+            .push((txo_id_hex.to_string(), id_hash.clone()));
+        id_hash
+    }
+
+    /// Redact every identifier seen so far from a message.
+    fn redact(&self, message: &str) -> String {
+        self.redactions
+            .iter()
+            .fold(message.to_string(), |message, (identifier, redacted)| {
+                message.replace(identifier, redacted)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::account::AccountID,
+        service::{account::AccountService, txo::TxoService},
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+        util::b58::b58_encode_public_address,
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_transaction_core::ring_signature::KeyImage;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_debug_build_report(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let mut service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        let account_id = AccountID(account.id.clone());
+
+        // Fund the account with 20 txos of 1 MOB.
+        for _ in 0..20 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![account_key.subaddress(0)],
+                MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let recipient = b58_encode_public_address(&AccountKey::random(&mut rng).subaddress(0))
+            .unwrap();
+        let amount = |value: u64| AmountJSON::new(value, Mob::ID);
+
+        // Reports are disabled unless configured.
+        match service.debug_build_report(
+            &account.id,
+            &[(recipient.clone(), amount(MOB))],
+            None,
+            None,
+            None,
+            None,
+            None,
+        ) {
+            Err(TransactionServiceError::DebugBuildReportDisabled) => {}
+            result => panic!("Expected DebugBuildReportDisabled, got {:?}", result),
+        }
+        service.debug_build_report_enabled = true;
+
+        // Asking for more than the account holds.
+        let report = service
+            .debug_build_report(
+                &account.id,
+                &[(recipient.clone(), amount(100 * MOB))],
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.failed_step, Some(BuildStep::Selection));
+        assert_eq!(report.fee_value, Some(Mob::MINIMUM_FEE));
+        assert_eq!(report.fee_token_id, Some(*Mob::ID));
+        assert_eq!(
+            report.tombstone_block,
+            Some(ledger_db.num_blocks().unwrap() + 10)
+        );
+        assert_eq!(report.ledger_block_height, ledger_db.num_blocks().unwrap());
+        assert_eq!(report.num_candidate_txos, 20);
+        assert_eq!(report.candidate_txos.len(), 20);
+        assert!(report
+            .candidate_txos
+            .iter()
+            .all(|txo| txo.value == MOB && txo.status == "unspent"));
+        assert_eq!(report.selections.len(), 1);
+        let selection = &report.selections[0];
+        assert_eq!(selection.target_value, 100 * MOB + Mob::MINIMUM_FEE);
+        assert!(selection.selected_txos.is_empty());
+        assert!(selection
+            .failure
+            .as_ref()
+            .unwrap()
+            .starts_with("Insufficient funds from Txos under max_spendable_value"));
+
+        // Nothing identifying the wallet is reported.
+        let debug = format!("{:?}", report);
+        assert!(!debug.contains(&account.id));
+        assert!(!debug.contains(&recipient));
+        let txos = service
+            .list_txos(Some(account.id.clone()), None, None, None, None, None, None, None)
+            .unwrap();
+        assert_eq!(txos.len(), 20);
+        for (txo, _status) in txos {
+            assert!(!debug.contains(&txo.id));
+        }
+
+        // Spending 17 MOB needs more than the maximum of 16 inputs.
+        let report = service
+            .debug_build_report(
+                &account.id,
+                &[(recipient.clone(), amount(17 * MOB))],
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.failed_step, Some(BuildStep::Selection));
+        assert_eq!(
+            report.selections[0].max_spendable_in_wallet,
+            Some((16 * MOB - Mob::MINIMUM_FEE) as u128)
+        );
+        assert_eq!(
+            report.selections[0].failure.as_deref(),
+            Some(WalletDbError::InsufficientFundsFragmentedTxos.to_string().as_str())
+        );
+
+        // Too many input txos given explicitly.
+        let input_txo_ids: Vec<String> = (0..17).map(|i| format!("{:064x}", i)).collect();
+        let report = service
+            .debug_build_report(
+                &account.id,
+                &[(recipient.clone(), amount(MOB))],
+                Some(&input_txo_ids),
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.failed_step, Some(BuildStep::ValidateInputs));
+        assert!(report
+            .failure
+            .as_ref()
+            .unwrap()
+            .contains("maximum allowed number of inputs is 16"));
+
+        // A build which succeeds has no failure.
+        let report = service
+            .debug_build_report(
+                &account.id,
+                &[(recipient, amount(3 * MOB))],
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.failed_step, None);
+        assert_eq!(report.failure, None);
+        assert_eq!(report.selections[0].selected_txos.len(), 4);
+        assert_eq!(report.selections[0].selected_value, 4 * MOB);
+    }
+}
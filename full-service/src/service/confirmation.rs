@@ -0,0 +1,217 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Challenges confirming irreversible requests.
+//!
+//! Irreversible methods only run when the request echoes back a challenge
+//! which the wallet issued for exactly that request, so that a templated or
+//! replayed request cannot run one by accident. A challenge is issued along
+//! with a summary of what the request will do, expires after a few minutes,
+//! and can only be used once. Challenges are kept in memory, and are
+//! forgotten when the wallet restarts.
+
+use crate::{
+    db::idempotency_key::unix_timestamp_now,
+    util::{
+        constants::{CONFIRMATION_CHALLENGE_CACHE_SIZE, CONFIRMATION_CHALLENGE_TTL_SECONDS},
+        lru_cache::LruCache,
+    },
+};
+use displaydoc::Display;
+use mc_crypto_rand::rand_core::RngCore;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Errors confirming an irreversible request.
+#[derive(Display, Debug, PartialEq)]
+pub enum ConfirmationError {
+    /// The {0} method is irreversible, and requires a confirmation challenge from prepare_{0}
+    Missing(String),
+
+    /// Unknown confirmation challenge. It may already have been used
+    NotFound,
+
+    /// The confirmation challenge expired. Prepare the request again
+    Expired,
+
+    /// The confirmation challenge was prepared for a different request
+    Mismatch,
+}
+
+impl ConfirmationError {
+    /// A short, stable name for the error, for clients to match on.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ConfirmationError::Missing(_) => "missing",
+            ConfirmationError::NotFound => "not_found",
+            ConfirmationError::Expired => "expired",
+            ConfirmationError::Mismatch => "mismatch",
+        }
+    }
+}
+
+/// A challenge confirming one irreversible request.
+#[derive(Clone, Debug)]
+pub struct ConfirmationChallenge {
+    /// The value to echo back with the request.
+    pub challenge: String,
+
+    /// The method the challenge confirms.
+    pub method: String,
+
+    /// What the request will do, for the caller to check before confirming.
+    pub summary: String,
+
+    /// When the challenge expires, in seconds since the Unix epoch.
+    pub expires_at: i64,
+
+    /// The request the challenge confirms, as its method and params.
+    request: serde_json::Value,
+
+    /// When the challenge expires.
+    expires: Instant,
+}
+
+/// The challenges which have been issued and not yet used.
+pub struct ConfirmationChallenges {
+    /// How long a challenge can be used for after it is issued.
+    ttl: Duration,
+
+    /// Unused challenges, by challenge value. The least recently issued are
+    /// forgotten first.
+    challenges: Mutex<LruCache<String, ConfirmationChallenge>>,
+}
+
+impl Default for ConfirmationChallenges {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(CONFIRMATION_CHALLENGE_TTL_SECONDS),
+            CONFIRMATION_CHALLENGE_CACHE_SIZE,
+        )
+    }
+}
+
+impl ConfirmationChallenges {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            challenges: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Issue a challenge for a request, given as JSON with its method and
+    /// params.
+    pub fn issue(&self, request: serde_json::Value, summary: String) -> ConfirmationChallenge {
+        let mut challenge_bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut challenge_bytes);
+
+        let challenge = ConfirmationChallenge {
+            challenge: hex::encode(challenge_bytes),
+            method: request["method"].as_str().unwrap_or_default().to_string(),
+            summary,
+            expires_at: unix_timestamp_now() + self.ttl.as_secs() as i64,
+            request,
+            expires: Instant::now() + self.ttl,
+        };
+        self.challenges
+            .lock()
+            .expect("lock poisoned")
+            .insert(challenge.challenge.clone(), challenge.clone());
+        challenge
+    }
+
+    /// Check that a challenge was issued for the given request, and use it up
+    /// so that it cannot confirm the request a second time.
+    pub fn redeem(
+        &self,
+        request: &serde_json::Value,
+        challenge: Option<&str>,
+    ) -> Result<(), ConfirmationError> {
+        let challenge = challenge.ok_or_else(|| {
+            ConfirmationError::Missing(request["method"].as_str().unwrap_or_default().to_string())
+        })?;
+
+        let mut challenges = self.challenges.lock().expect("lock poisoned");
+        let issued = challenges
+            .get(&challenge.to_string())
+            .ok_or(ConfirmationError::NotFound)?;
+        if Instant::now() >= issued.expires {
+            challenges.remove(&issued.challenge);
+            return Err(ConfirmationError::Expired);
+        }
+        if &issued.request != request {
+            return Err(ConfirmationError::Mismatch);
+        }
+
+        challenges.remove(&issued.challenge);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn remove_account_request(account_id: &str) -> serde_json::Value {
+        json!({
+            "method": "remove_account",
+            "params": { "account_id": account_id },
+        })
+    }
+
+    #[test]
+    fn test_redeem_confirmation_challenge() {
+        let challenges = ConfirmationChallenges::default();
+        let request = remove_account_request("a");
+
+        assert_eq!(
+            challenges.redeem(&request, None),
+            Err(ConfirmationError::Missing("remove_account".to_string()))
+        );
+        assert_eq!(
+            challenges.redeem(&request, Some("not a challenge")),
+            Err(ConfirmationError::NotFound)
+        );
+
+        let challenge = challenges.issue(request.clone(), "Will remove a".to_string());
+        assert_eq!(challenge.method, "remove_account");
+        assert_eq!(challenge.summary, "Will remove a");
+
+        // A challenge only confirms the request it was issued for, and a
+        // mismatch does not use it up.
+        assert_eq!(
+            challenges.redeem(&remove_account_request("b"), Some(&challenge.challenge)),
+            Err(ConfirmationError::Mismatch)
+        );
+        assert_eq!(
+            challenges.redeem(&request, Some(&challenge.challenge)),
+            Ok(())
+        );
+
+        // Each challenge can only be used once.
+        assert_eq!(
+            challenges.redeem(&request, Some(&challenge.challenge)),
+            Err(ConfirmationError::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_confirmation_challenge_expires() {
+        let challenges = ConfirmationChallenges::new(Duration::from_secs(0), 8);
+        let request = remove_account_request("a");
+
+        let challenge = challenges.issue(request.clone(), "Will remove a".to_string());
+        assert_eq!(
+            challenges.redeem(&request, Some(&challenge.challenge)),
+            Err(ConfirmationError::Expired)
+        );
+
+        // An expired challenge is forgotten.
+        assert_eq!(
+            challenges.redeem(&request, Some(&challenge.challenge)),
+            Err(ConfirmationError::NotFound)
+        );
+    }
+}
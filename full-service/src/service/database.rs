@@ -0,0 +1,98 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for reporting the state of the wallet database.
+
+use crate::{
+    db::{DatabaseBackup, DatabasePragmas, WalletDb, WalletDbError},
+    WalletService,
+};
+use displaydoc::Display;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+
+/// Errors for the Database Service.
+#[derive(Display, Debug)]
+pub enum DatabaseServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+}
+
+impl From<WalletDbError> for DatabaseServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+/// The schema, size and SQLite settings of the wallet database.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DatabaseStatus {
+    /// The version of the latest migration the database has run.
+    pub schema_version: Option<String>,
+
+    /// The migrations in this build which the database has not run.
+    pub pending_migrations: Vec<String>,
+
+    /// The most recent backup made before running migrations.
+    pub last_backup: Option<DatabaseBackup>,
+
+    /// The size of the database, in bytes.
+    pub size_bytes: u64,
+
+    /// The SQLite settings in effect on the wallet's connections.
+    pub pragmas: DatabasePragmas,
+}
+
+/// Trait defining the ways in which the wallet can report on its database.
+pub trait DatabaseService {
+    /// Get the schema version, pending migrations, last backup, size and
+    /// SQLite settings of the wallet database.
+    fn get_database_status(&self) -> Result<DatabaseStatus, DatabaseServiceError>;
+}
+
+impl<T, FPR> DatabaseService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn get_database_status(&self) -> Result<DatabaseStatus, DatabaseServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let last_backup = match WalletDb::file_path(&conn)? {
+            Some(db_path) => WalletDb::list_backups(&db_path)?.pop(),
+            None => None,
+        };
+
+        Ok(DatabaseStatus {
+            schema_version: WalletDb::schema_version(&conn)?,
+            pending_migrations: WalletDb::pending_migrations(&conn)?,
+            last_backup,
+            size_bytes: WalletDb::size_bytes(&conn)?,
+            pragmas: WalletDb::pragmas(&conn)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_test_ledger, setup_wallet_service};
+    use mc_common::logger::{test_with_logger, Logger};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_get_database_status(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+
+        let status = service.get_database_status().unwrap();
+        assert!(status.schema_version.is_some());
+        assert_eq!(status.pending_migrations, Vec::<String>::new());
+        assert_eq!(status.last_backup, None);
+        assert!(status.size_bytes > 0);
+        assert_eq!(status.pragmas.journal_mode, "wal");
+        assert_eq!(status.pragmas.synchronous, "normal");
+        assert!(status.pragmas.foreign_keys);
+        assert_eq!(status.pragmas.busy_timeout_ms, 30_000);
+        assert_eq!(status.pragmas.cache_size_kib, 8192);
+    }
+}
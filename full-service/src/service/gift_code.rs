@@ -38,7 +38,7 @@ use mc_connection::{BlockchainConnection, RetryableUserTxConnection, UserTxConne
 use mc_crypto_keys::RistrettoPublic;
 use mc_crypto_ring_signature_signer::NoKeysRingSigner;
 use mc_fog_report_validation::FogPubkeyResolver;
-use mc_ledger_db::Ledger;
+use mc_ledger_db::{Ledger, LedgerDB};
 use mc_transaction_core::{
     constants::RING_SIZE,
     get_tx_out_shared_secret,
@@ -54,7 +54,7 @@ use mc_transaction_std::{
 use mc_util_uri::FogUri;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, fmt, iter::empty, str::FromStr, sync::atomic::Ordering};
+use std::{convert::TryFrom, fmt, iter::empty, str::FromStr};
 
 #[derive(Display, Debug)]
 #[allow(clippy::large_enum_variant)]
@@ -124,6 +124,9 @@ pub enum GiftCodeServiceError {
     /// Cannot claim a gift code which has not yet landed in the ledger
     GiftCodeNotYetAvailable,
 
+    /// Gift Code was not funded by an account in this wallet: {0}
+    GiftCodeNotFundedByWallet(String),
+
     /// Gift Code was removed from the DB prior to claiming
     GiftCodeRemoved,
 
@@ -280,6 +283,8 @@ pub struct DecodedGiftCode {
     pub txo_public_key: Vec<u8>,
     pub value: u64,
     pub memo: String,
+    /// The account in this wallet which funded the gift code, if any.
+    pub account_id: Option<String>,
 }
 
 impl TryFrom<GiftCode> for DecodedGiftCode {
@@ -296,13 +301,14 @@ impl TryFrom<GiftCode> for DecodedGiftCode {
             txo_public_key: mc_util_serial::encode(&transfer_payload.txo_public_key),
             value: src.value as u64,
             memo: transfer_payload.memo,
+            account_id: src.account_id,
         })
     }
 }
 
 /// Possible states for a Gift Code in relation to accounts in this wallet.
 #[allow(clippy::enum_variant_names)]
-#[derive(Debug, Serialize, Deserialize, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Eq, PartialEq)]
 pub enum GiftCodeStatus {
     /// The Gift Code has been submitted, but has not yet hit the ledger.
     GiftCodeSubmittedPending,
@@ -383,6 +389,28 @@ pub trait GiftCodeService {
         &self,
         gift_code_b58: &EncodedGiftCode,
     ) -> Result<bool, GiftCodeServiceError>;
+
+    /// List the gift codes in the wallet with their status in the ledger,
+    /// optionally only those funded by an account.
+    fn list_gift_codes_with_status(
+        &self,
+        account_id: Option<&AccountID>,
+    ) -> Result<Vec<(DecodedGiftCode, GiftCodeStatus)>, GiftCodeServiceError>;
+
+    /// The total value of the gift codes funded by an account which are in
+    /// the ledger and have not been claimed. This value is no longer part of
+    /// the account's balance, but can be reclaimed.
+    fn get_gift_code_balance_for_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<u64, GiftCodeServiceError>;
+
+    /// Claim an unclaimed gift code back to the main address of the account
+    /// which funded it.
+    fn reclaim_gift_code(
+        &self,
+        gift_code_b58: &EncodedGiftCode,
+    ) -> Result<Tx, GiftCodeServiceError>;
 }
 
 impl<T, FPR> GiftCodeService for WalletService<T, FPR>
@@ -439,6 +467,7 @@ where
                 crate::json_rpc::v2::models::amount::Amount {
                     value: value.to_string(),
                     token_id: Mob::ID.to_string(),
+                    ..Default::default()
                 },
             )],
             input_txo_ids,
@@ -446,11 +475,12 @@ where
             None,
             tombstone_block.map(|t| t.to_string()),
             max_spendable_value.map(|f| f.to_string()),
+            None,
+            false,
             TransactionMemo::RTH,
         )?;
 
-        let account_key: AccountKey = mc_util_serial::decode(&from_account.account_key)?;
-        let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver)?;
+        let tx_proposal = self.sign_transaction(unsigned_tx, fog_resolver, &from_account, &conn)?;
 
         if tx_proposal.payload_txos.len() != 1 {
             return Err(GiftCodeServiceError::UnexpectedTxProposalFormat);
@@ -479,28 +509,40 @@ where
         let value = tx_proposal.payload_txos[0].amount.value as i64;
 
         log::info!(
-            self.logger,
+            self.request_logger(),
             "submitting transaction for gift code... {:?}",
             value
         );
 
         // Save the gift code to the database before attempting to send it out.
         let conn = self.wallet_db.get_conn()?;
-        let gift_code = transaction(&conn, || GiftCode::create(gift_code_b58, value, &conn))?;
+        let txo_public_key = mc_util_serial::encode(&transfer_payload.txo_public_key);
+        let gift_code = transaction(&conn, || {
+            GiftCode::create(
+                gift_code_b58,
+                value,
+                Some(from_account_id),
+                &txo_public_key,
+                &conn,
+            )
+        })?;
 
         self.submit_transaction(
             tx_proposal,
             Some(json!({"gift_code_memo": transfer_payload.memo}).to_string()),
             Some(from_account_id.clone().0),
+            None,
+            false,
         )?;
 
         Ok(DecodedGiftCode {
             gift_code_b58: gift_code.gift_code_b58,
             root_entropy: transfer_payload.root_entropy.map(|e| e.bytes.to_vec()),
             bip39_entropy: transfer_payload.bip39_entropy,
-            txo_public_key: mc_util_serial::encode(&transfer_payload.txo_public_key),
+            txo_public_key,
             value: tx_proposal.payload_txos[0].amount.value,
             memo: transfer_payload.memo,
+            account_id: gift_code.account_id,
         })
     }
 
@@ -529,66 +571,18 @@ where
         &self,
         gift_code_b58: &EncodedGiftCode,
     ) -> Result<(GiftCodeStatus, Option<i64>, String), GiftCodeServiceError> {
-        log::info!(self.logger, "encoded_gift_code: {:?}", gift_code_b58);
-
         let transfer_payload = decode_transfer_payload(gift_code_b58)?;
-        let gift_account_key = transfer_payload.account_key;
 
+        // The gift code and its account key are secrets, so only the public
+        // key of its txo is logged.
         log::info!(
-            self.logger,
-            "transfer_payload.pubKey: {:?}, account_key: {:?}",
-            transfer_payload.txo_public_key,
-            gift_account_key
-        );
-
-        // Check if the GiftCode is in the local ledger.
-        let gift_txo = match self
-            .ledger_db
-            .get_tx_out_index_by_public_key(&transfer_payload.txo_public_key)
-        {
-            Ok(tx_out_index) => self.ledger_db.get_tx_out_by_index(tx_out_index)?,
-            Err(mc_ledger_db::Error::NotFound) => {
-                return Ok((
-                    GiftCodeStatus::GiftCodeSubmittedPending,
-                    None,
-                    transfer_payload.memo,
-                ))
-            }
-            Err(e) => return Err(e.into()),
-        };
-
-        let shared_secret = get_tx_out_shared_secret(
-            gift_account_key.view_private_key(),
-            &RistrettoPublic::try_from(&gift_txo.public_key)?,
+            self.request_logger(),
+            "Checking gift code status, txo_public_key: {}",
+            hex::encode(transfer_payload.txo_public_key.as_bytes()),
         );
 
-        let (value, _blinding) = gift_txo.masked_amount.get_value(&shared_secret)?;
-
-        // Check if the Gift Code has been spent - by convention gift codes are always
-        // to the main subaddress index and gift accounts should NEVER have MOB stored
-        // anywhere else. If they do, that's not good :,)
-        let gift_code_key_image = {
-            let onetime_private_key = recover_onetime_private_key(
-                &RistrettoPublic::try_from(&transfer_payload.txo_public_key)?,
-                gift_account_key.view_private_key(),
-                &gift_account_key.subaddress_spend_private(DEFAULT_SUBADDRESS_INDEX as u64),
-            );
-            KeyImage::from(&onetime_private_key)
-        };
-
-        if self.ledger_db.contains_key_image(&gift_code_key_image)? {
-            return Ok((
-                GiftCodeStatus::GiftCodeClaimed,
-                Some(value.value as i64),
-                transfer_payload.memo,
-            ));
-        }
-
-        Ok((
-            GiftCodeStatus::GiftCodeAvailable,
-            Some(value.value as i64),
-            transfer_payload.memo,
-        ))
+        let (status, value) = gift_code_ledger_status(&self.ledger_db, &transfer_payload)?;
+        Ok((status, value, transfer_payload.memo))
     }
 
     fn claim_gift_code(
@@ -712,22 +706,21 @@ where
             .set_tombstone_block(num_blocks_in_ledger + DEFAULT_NEW_TX_BLOCK_ATTEMPTS);
         let tx = transaction_builder.build(&NoKeysRingSigner {}, &mut rng)?;
 
-        let responder_ids = self.peer_manager.responder_ids();
-        if responder_ids.is_empty() {
-            return Err(GiftCodeServiceError::TxoNotConsumable);
-        }
-
-        let idx = self.submit_node_offset.fetch_add(1, Ordering::SeqCst);
-        let responder_id = &responder_ids[idx % responder_ids.len()];
+        let responder_ids = self.submission_peers();
+        let responder_id = responder_ids
+            .first()
+            .ok_or(GiftCodeServiceError::TxoNotConsumable)?;
 
-        let block_index = self
+        let conn = self
             .peer_manager
             .conn(responder_id)
-            .ok_or(GiftCodeServiceError::NodeNotFound)?
-            .propose_tx(&tx, empty())?;
+            .ok_or(GiftCodeServiceError::NodeNotFound)?;
+        let block_index = self
+            .peer_health
+            .call(responder_id, || conn.propose_tx(&tx, empty()))?;
 
         log::info!(
-            self.logger,
+            self.request_logger(),
             "Tx {:?} submitted at block height {}",
             tx,
             block_index
@@ -744,6 +737,61 @@ where
         transaction(&conn, || GiftCode::get(gift_code_b58, &conn)?.delete(&conn))?;
         Ok(true)
     }
+
+    fn list_gift_codes_with_status(
+        &self,
+        account_id: Option<&AccountID>,
+    ) -> Result<Vec<(DecodedGiftCode, GiftCodeStatus)>, GiftCodeServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let gift_codes = match account_id {
+            Some(account_id) => GiftCode::list_for_account(account_id, &conn)?,
+            None => GiftCode::list_all(&conn, None, None)?,
+        };
+
+        gift_codes
+            .into_iter()
+            .map(|gift_code| {
+                let transfer_payload =
+                    decode_transfer_payload(&EncodedGiftCode(gift_code.gift_code_b58.clone()))?;
+                let (status, _value) = gift_code_ledger_status(&self.ledger_db, &transfer_payload)?;
+                Ok((DecodedGiftCode::try_from(gift_code)?, status))
+            })
+            .collect()
+    }
+
+    fn get_gift_code_balance_for_account(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<u64, GiftCodeServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let mut balance = 0;
+        for gift_code in GiftCode::list_for_account(account_id, &conn)? {
+            let transfer_payload =
+                decode_transfer_payload(&EncodedGiftCode(gift_code.gift_code_b58))?;
+            if let (GiftCodeStatus::GiftCodeAvailable, Some(value)) =
+                gift_code_ledger_status(&self.ledger_db, &transfer_payload)?
+            {
+                balance += value as u64;
+            }
+        }
+        Ok(balance)
+    }
+
+    fn reclaim_gift_code(
+        &self,
+        gift_code_b58: &EncodedGiftCode,
+    ) -> Result<Tx, GiftCodeServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let gift_code = GiftCode::get(gift_code_b58, &conn)?;
+        let account_id = gift_code.account_id.map(AccountID).ok_or_else(|| {
+            GiftCodeServiceError::GiftCodeNotFundedByWallet(gift_code_b58.to_string())
+        })?;
+        let main_address = Account::get(&account_id, &conn)?
+            .main_subaddress(&conn)?
+            .public_address_b58;
+
+        self.claim_gift_code(gift_code_b58, &account_id, Some(main_address))
+    }
 }
 
 /// Decode the gift code from b58 to its component parts.
@@ -753,6 +801,50 @@ pub fn decode_transfer_payload(
     Ok(b58_decode_transfer_payload(gift_code_b58.to_string())?)
 }
 
+/// The status of a gift code in the ledger, and the value of its txo once the
+/// txo is in the ledger.
+fn gift_code_ledger_status(
+    ledger_db: &LedgerDB,
+    transfer_payload: &DecodedTransferPayload,
+) -> Result<(GiftCodeStatus, Option<i64>), GiftCodeServiceError> {
+    let gift_account_key = &transfer_payload.account_key;
+
+    // Check if the GiftCode is in the local ledger.
+    let gift_txo = match ledger_db.get_tx_out_index_by_public_key(&transfer_payload.txo_public_key)
+    {
+        Ok(tx_out_index) => ledger_db.get_tx_out_by_index(tx_out_index)?,
+        Err(mc_ledger_db::Error::NotFound) => {
+            return Ok((GiftCodeStatus::GiftCodeSubmittedPending, None))
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let shared_secret = get_tx_out_shared_secret(
+        gift_account_key.view_private_key(),
+        &RistrettoPublic::try_from(&gift_txo.public_key)?,
+    );
+
+    let (value, _blinding) = gift_txo.masked_amount.get_value(&shared_secret)?;
+
+    // Check if the Gift Code has been spent - by convention gift codes are always
+    // to the main subaddress index and gift accounts should NEVER have MOB stored
+    // anywhere else. If they do, that's not good :,)
+    let gift_code_key_image = {
+        let onetime_private_key = recover_onetime_private_key(
+            &RistrettoPublic::try_from(&transfer_payload.txo_public_key)?,
+            gift_account_key.view_private_key(),
+            &gift_account_key.subaddress_spend_private(DEFAULT_SUBADDRESS_INDEX as u64),
+        );
+        KeyImage::from(&onetime_private_key)
+    };
+
+    if ledger_db.contains_key_image(&gift_code_key_image)? {
+        return Ok((GiftCodeStatus::GiftCodeClaimed, Some(value.value as i64)));
+    }
+
+    Ok((GiftCodeStatus::GiftCodeAvailable, Some(value.value as i64)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1036,4 +1128,150 @@ mod tests {
             .expect("Could not list gift codes");
         assert_eq!(gift_codes.len(), 0);
     }
+
+    #[test_with_logger]
+    fn test_gift_code_balance_and_reclaim(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID(alice.id.to_string());
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB as u64,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Fund two gift codes. Each is unfunded until its txo lands, and then
+        // its value is committed to the gift code rather than part of the
+        // unspent balance.
+        let mut gift_codes = Vec::new();
+        for value in [2 * MOB, 3 * MOB] {
+            let (tx_proposal, gift_code_b58) = service
+                .build_gift_code(&alice_account_id, value, None, None, None, None, None)
+                .unwrap();
+            let gift_code = service
+                .submit_gift_code(&alice_account_id, &gift_code_b58, &tx_proposal)
+                .unwrap();
+            assert_eq!(gift_code.account_id, Some(alice.id.clone()));
+
+            let statuses = service
+                .list_gift_codes_with_status(Some(&alice_account_id))
+                .unwrap();
+            assert_eq!(statuses.len(), gift_codes.len() + 1);
+            assert_eq!(
+                statuses.last().unwrap().1,
+                GiftCodeStatus::GiftCodeSubmittedPending
+            );
+
+            add_block_with_tx(&mut ledger_db, tx_proposal.tx, &mut rng);
+            manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+            gift_codes.push(gift_code_b58);
+        }
+        let statuses = service
+            .list_gift_codes_with_status(Some(&alice_account_id))
+            .unwrap();
+        assert!(statuses
+            .iter()
+            .all(|(_, status)| *status == GiftCodeStatus::GiftCodeAvailable));
+        assert_eq!(
+            service
+                .get_gift_code_balance_for_account(&alice_account_id)
+                .unwrap(),
+            5 * MOB
+        );
+        let balance = service.get_balance_for_account(&alice_account_id).unwrap();
+        assert_eq!(
+            balance.get(&Mob::ID).unwrap().unspent,
+            (95 * MOB - 2 * Mob::MINIMUM_FEE) as u128
+        );
+
+        // Gift codes funded elsewhere are not listed for the account.
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        assert!(service
+            .list_gift_codes_with_status(Some(&AccountID(bob.id.clone())))
+            .unwrap()
+            .is_empty());
+
+        // The first gift code is claimed outside the wallet, by spending its txo
+        // with the gift code account key.
+        let claimed_b58 = &gift_codes[0];
+        let claimed_payload = decode_transfer_payload(claimed_b58).unwrap();
+        let claimed_key_image = KeyImage::from(&recover_onetime_private_key(
+            &RistrettoPublic::try_from(&claimed_payload.txo_public_key).unwrap(),
+            claimed_payload.account_key.view_private_key(),
+            &claimed_payload
+                .account_key
+                .subaddress_spend_private(DEFAULT_SUBADDRESS_INDEX),
+        ));
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![AccountKey::random(&mut rng).default_subaddress()],
+            2 * MOB - Mob::MINIMUM_FEE,
+            &vec![claimed_key_image],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let statuses = service
+            .list_gift_codes_with_status(Some(&alice_account_id))
+            .unwrap();
+        assert_eq!(statuses[0].1, GiftCodeStatus::GiftCodeClaimed);
+        assert_eq!(statuses[1].1, GiftCodeStatus::GiftCodeAvailable);
+        assert_eq!(
+            service
+                .get_gift_code_balance_for_account(&alice_account_id)
+                .unwrap(),
+            3 * MOB
+        );
+        assert!(matches!(
+            service.reclaim_gift_code(claimed_b58),
+            Err(GiftCodeServiceError::GiftCodeClaimed)
+        ));
+
+        // The second gift code is reclaimed to Alice's main address.
+        let reclaimed_b58 = &gift_codes[1];
+        let tx = service.reclaim_gift_code(reclaimed_b58).unwrap();
+        add_block_with_tx(&mut ledger_db, tx, &mut rng);
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let statuses = service.list_gift_codes_with_status(None).unwrap();
+        assert!(statuses
+            .iter()
+            .all(|(_, status)| *status == GiftCodeStatus::GiftCodeClaimed));
+        assert_eq!(
+            service
+                .get_gift_code_balance_for_account(&alice_account_id)
+                .unwrap(),
+            0
+        );
+        let balance = service.get_balance_for_account(&alice_account_id).unwrap();
+        assert_eq!(
+            balance.get(&Mob::ID).unwrap().unspent,
+            (98 * MOB - 3 * Mob::MINIMUM_FEE) as u128
+        );
+    }
 }
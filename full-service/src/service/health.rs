@@ -0,0 +1,250 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for checking whether the wallet is alive, and ready to serve
+//! requests, for orchestrators' liveness and readiness probes.
+
+use crate::{
+    db::WalletDb,
+    service::ledger::LedgerService,
+    util::constants::{
+        DEFAULT_HEALTH_TIMEOUT_SECONDS, DEFAULT_READY_MAX_LEDGER_LAG,
+        DEFAULT_READY_PEER_RESPONSE_WINDOW_SECONDS,
+    },
+    WalletService,
+};
+use mc_connection::{BlockchainConnection, RetryableBlockchainConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
+use serde::Serialize;
+use std::{
+    cmp,
+    iter::empty,
+    time::{Duration, Instant},
+};
+
+/// Thresholds of the health checks.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HealthConfig {
+    /// Longest the checks wait for a wallet db connection.
+    pub timeout: Duration,
+
+    /// Most blocks the ledger may be behind the network for the wallet to be
+    /// ready.
+    pub max_ledger_lag: u64,
+
+    /// Longest since a peer or the validator last responded for the wallet
+    /// to be ready.
+    pub peer_response_window: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(DEFAULT_HEALTH_TIMEOUT_SECONDS),
+            max_ledger_lag: DEFAULT_READY_MAX_LEDGER_LAG,
+            peer_response_window: Duration::from_secs(DEFAULT_READY_PEER_RESPONSE_WINDOW_SECONDS),
+        }
+    }
+}
+
+/// A health check which failed, and why.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct FailedHealthCheck {
+    /// The check, one of "database", "ledger_lag" or "peers".
+    pub check: String,
+
+    /// Why the check failed.
+    pub error: String,
+}
+
+/// The outcome of the liveness or readiness checks.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// The checks which failed. The wallet is healthy if there are none.
+    pub failing_checks: Vec<FailedHealthCheck>,
+}
+
+impl HealthReport {
+    /// Whether every check passed.
+    pub fn is_healthy(&self) -> bool {
+        self.failing_checks.is_empty()
+    }
+
+    fn check(&mut self, check: &str, result: Result<(), String>) {
+        if let Err(error) = result {
+            self.failing_checks.push(FailedHealthCheck {
+                check: check.to_string(),
+                error,
+            });
+        }
+    }
+}
+
+/// The last time a peer, or the validator, responded to the health checks.
+#[derive(Clone, Copy, Debug)]
+pub struct PeerResponse {
+    /// When the response arrived.
+    pub received_at: Instant,
+
+    /// The highest block height any peer reported.
+    pub network_block_height: u64,
+}
+
+/// Trait defining the ways in which the wallet can report on its health.
+pub trait HealthService {
+    /// Check that the wallet db responds within the timeout.
+    fn check_liveness(&self, config: &HealthConfig) -> HealthReport;
+
+    /// Check liveness, and that the ledger is within the maximum lag of the
+    /// network and that a peer, or the validator, responded within the
+    /// response window. Neither is checked in offline mode.
+    fn check_readiness(&self, config: &HealthConfig) -> HealthReport;
+}
+
+impl<T, FPR> HealthService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn check_liveness(&self, config: &HealthConfig) -> HealthReport {
+        let mut report = HealthReport::default();
+        report.check(
+            "database",
+            self.wallet_db
+                .get_conn_timeout(config.timeout)
+                .map_err(|err| err.to_string())
+                .and_then(|conn| {
+                    if WalletDb::check_database_connectivity(&conn) {
+                        Ok(())
+                    } else {
+                        Err("Could not query the wallet db".to_string())
+                    }
+                }),
+        );
+        report
+    }
+
+    fn check_readiness(&self, config: &HealthConfig) -> HealthReport {
+        let mut report = self.check_liveness(config);
+        if self.offline {
+            return report;
+        }
+
+        let peer_response = self.probe_peers(config.peer_response_window / 2);
+        report.check(
+            "peers",
+            match peer_response {
+                Some(peer_response)
+                    if peer_response.received_at.elapsed() <= config.peer_response_window =>
+                {
+                    Ok(())
+                }
+                Some(peer_response) => Err(format!(
+                    "No peer has responded in {:?}",
+                    peer_response.received_at.elapsed()
+                )),
+                None => Err("No peer has responded".to_string()),
+            },
+        );
+
+        report.check(
+            "ledger_lag",
+            self.ledger_db
+                .num_blocks()
+                .map_err(|err| err.to_string())
+                .and_then(|local_block_height| {
+                    let network_block_height = cmp::max(
+                        self.get_network_block_height()
+                            .map_err(|err| err.to_string())?,
+                        peer_response.map_or(0, |peer_response| {
+                            peer_response.network_block_height
+                        }),
+                    );
+                    let lag = network_block_height.saturating_sub(local_block_height);
+                    if lag > config.max_ledger_lag {
+                        Err(format!(
+                            "The ledger has {} blocks, {} behind the network's {}, more than the {} allowed",
+                            local_block_height, lag, network_block_height, config.max_ledger_lag
+                        ))
+                    } else {
+                        Ok(())
+                    }
+                }),
+        );
+        report
+    }
+}
+
+impl<T, FPR> WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    /// The last response of the peers, asking them again unless one
+    /// responded within max_age.
+    fn probe_peers(&self, max_age: Duration) -> Option<PeerResponse> {
+        let last_response = *self.last_peer_response.lock().expect("lock poisoned");
+        if let Some(last_response) = last_response {
+            if last_response.received_at.elapsed() <= max_age {
+                return Some(last_response);
+            }
+        }
+
+        let network_block_height = self
+            .call_aggregation_peers(|conn| conn.fetch_block_info(empty()))
+            .into_iter()
+            .map(|block_info| block_info.block_index + 1)
+            .max();
+        match network_block_height {
+            Some(network_block_height) => {
+                let response = PeerResponse {
+                    received_at: Instant::now(),
+                    network_block_height,
+                };
+                *self.last_peer_response.lock().expect("lock poisoned") = Some(response);
+                Some(response)
+            }
+            None => last_response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{get_test_ledger, setup_wallet_service};
+    use mc_common::logger::{test_with_logger, Logger};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_check_readiness(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+        let config = HealthConfig::default();
+
+        assert!(service.check_liveness(&config).is_healthy());
+        assert!(service.check_readiness(&config).is_healthy());
+        let peer_response = service.last_peer_response.lock().unwrap().unwrap();
+        assert_eq!(peer_response.network_block_height, 12);
+
+        // A peer reporting blocks the ledger does not have yet puts it behind.
+        *service.last_peer_response.lock().unwrap() = Some(PeerResponse {
+            received_at: Instant::now(),
+            network_block_height: 12 + config.max_ledger_lag + 1,
+        });
+        let report = service.check_readiness(&config);
+        assert_eq!(report.failing_checks.len(), 1);
+        assert_eq!(report.failing_checks[0].check, "ledger_lag");
+
+        // A response older than half the window is refreshed by probing the
+        // peers again.
+        *service.last_peer_response.lock().unwrap() = Some(PeerResponse {
+            received_at: Instant::now() - config.peer_response_window,
+            network_block_height: 12,
+        });
+        assert!(service.check_readiness(&config).is_healthy());
+        let peer_response = service.last_peer_response.lock().unwrap().unwrap();
+        assert!(peer_response.received_at.elapsed() < config.peer_response_window / 2);
+    }
+}
@@ -0,0 +1,469 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for running long operations as background jobs.
+//!
+//! Jobs are queued on a bounded queue and run by a small pool of worker
+//! threads, so that the request which submits one returns straight away with
+//! a job id to poll. Jobs are kept in memory, and are forgotten when the
+//! wallet restarts.
+
+use crate::{
+    db::idempotency_key::unix_timestamp_now, service::WalletService,
+    util::constants::JOB_HISTORY_SIZE,
+};
+use displaydoc::Display;
+use mc_common::logger::{log, Logger};
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_rand::rand_core::RngCore;
+use mc_fog_report_validation::FogPubkeyResolver;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+use strum::Display as StrumDisplay;
+
+/// Errors for the Job Service.
+#[derive(Display, Debug)]
+pub enum JobServiceError {
+    /// The job queue is full, at most {0} jobs can wait to run
+    QueueFull(usize),
+
+    /// Job not found: {0}
+    JobNotFound(String),
+
+    /// Error starting a job worker thread: {0}
+    Thread(std::io::Error),
+}
+
+impl From<std::io::Error> for JobServiceError {
+    fn from(src: std::io::Error) -> Self {
+        Self::Thread(src)
+    }
+}
+
+/// The state of a job.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, StrumDisplay)]
+#[strum(serialize_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+impl JobStatus {
+    /// Whether the job has stopped, and will not change again.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            JobStatus::Completed | JobStatus::Cancelled | JobStatus::Failed
+        )
+    }
+}
+
+/// The progress of a job, and its result once it has finished.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: String,
+
+    /// The operation the job runs, such as "resync_account".
+    pub kind: String,
+
+    pub status: JobStatus,
+
+    /// How much of the job's work has been done, in units of the job's
+    /// choosing.
+    pub progress: u64,
+
+    /// How much work the job has to do in total, if it is known.
+    pub total: Option<u64>,
+
+    /// What the job returned, once it has completed.
+    pub result: Option<serde_json::Value>,
+
+    /// Why the job failed, if it did.
+    pub error: Option<String>,
+
+    pub created_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+/// A job shared between the worker running it and the job manager. Jobs use
+/// it to report their progress and to check whether they have been
+/// cancelled.
+pub struct JobContext {
+    job: Mutex<Job>,
+
+    /// Cancel trigger, checked by jobs between units of work.
+    cancel_requested: AtomicBool,
+}
+
+impl JobContext {
+    fn new(id: String, kind: &str) -> Self {
+        Self {
+            job: Mutex::new(Job {
+                id,
+                kind: kind.to_string(),
+                status: JobStatus::Queued,
+                progress: 0,
+                total: None,
+                result: None,
+                error: None,
+                created_at: unix_timestamp_now(),
+                finished_at: None,
+            }),
+            cancel_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// A snapshot of the job.
+    pub fn job(&self) -> Job {
+        self.job.lock().expect("lock poisoned").clone()
+    }
+
+    /// Record how much of the job's work has been done.
+    pub fn set_progress(&self, progress: u64, total: Option<u64>) {
+        let mut job = self.job.lock().expect("lock poisoned");
+        job.progress = progress;
+        job.total = total;
+    }
+
+    /// Whether the job has been asked to stop. Jobs should check this between
+    /// units of work and return early when it is set.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel_requested.load(Ordering::SeqCst)
+    }
+
+    /// Ask the job to stop. Queued jobs are cancelled straight away, and
+    /// running jobs once they next check. Finished jobs are left as they are.
+    fn cancel(&self) {
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        let mut job = self.job.lock().expect("lock poisoned");
+        if job.status == JobStatus::Queued {
+            job.status = JobStatus::Cancelled;
+            job.finished_at = Some(unix_timestamp_now());
+        }
+    }
+
+    /// Run the job, unless it was cancelled while queued, and record its
+    /// outcome.
+    fn run(&self, job_fn: JobFn) {
+        {
+            let mut job = self.job.lock().expect("lock poisoned");
+            if job.status != JobStatus::Queued {
+                return;
+            }
+            job.status = JobStatus::Running;
+        }
+
+        let result = job_fn(self);
+
+        let mut job = self.job.lock().expect("lock poisoned");
+        match result {
+            Ok(value) => {
+                job.status = if self.is_cancelled() {
+                    JobStatus::Cancelled
+                } else {
+                    JobStatus::Completed
+                };
+                job.result = value;
+            }
+            Err(e) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(e);
+            }
+        }
+        job.finished_at = Some(unix_timestamp_now());
+    }
+}
+
+/// The work a job does. It returns its result, or why it failed.
+pub type JobFn =
+    Box<dyn FnOnce(&JobContext) -> Result<Option<serde_json::Value>, String> + Send + 'static>;
+
+/// Queues jobs and runs them on a pool of worker threads.
+pub struct JobManager {
+    /// Recent jobs, in the order they were submitted.
+    jobs: Mutex<Vec<Arc<JobContext>>>,
+
+    /// The sending end of the job queue. Dropped to stop the workers.
+    sender: Option<SyncSender<(Arc<JobContext>, JobFn)>>,
+
+    /// Maximum number of jobs which can wait for a worker.
+    queue_size: usize,
+
+    /// The worker thread handles.
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl JobManager {
+    pub fn start(
+        num_workers: usize,
+        queue_size: usize,
+        logger: Logger,
+    ) -> Result<Self, JobServiceError> {
+        let (sender, receiver) = mpsc::sync_channel(queue_size);
+        let receiver: Arc<Mutex<Receiver<(Arc<JobContext>, JobFn)>>> =
+            Arc::new(Mutex::new(receiver));
+
+        let workers = (0..num_workers)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let logger = logger.clone();
+                thread::Builder::new()
+                    .name("job_worker".to_string())
+                    .spawn(move || loop {
+                        // The lock is only held while waiting for the next job.
+                        let next = receiver.lock().expect("lock poisoned").recv();
+                        let (context, job_fn) = match next {
+                            Ok(next) => next,
+                            Err(_) => break,
+                        };
+                        context.run(job_fn);
+                        let job = context.job();
+                        log::info!(logger, "Job {} ({}) {}", job.id, job.kind, job.status);
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            jobs: Mutex::new(Vec::new()),
+            sender: Some(sender),
+            queue_size,
+            workers,
+        })
+    }
+
+    /// Queue a job to run on the next free worker.
+    pub fn submit<F>(&self, kind: &str, job_fn: F) -> Result<Job, JobServiceError>
+    where
+        F: FnOnce(&JobContext) -> Result<Option<serde_json::Value>, String> + Send + 'static,
+    {
+        let mut id_bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id_bytes);
+        let context = Arc::new(JobContext::new(hex::encode(id_bytes), kind));
+
+        let sender = self.sender.as_ref().expect("job manager is stopped");
+        match sender.try_send((context.clone(), Box::new(job_fn))) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+                return Err(JobServiceError::QueueFull(self.queue_size));
+            }
+        }
+
+        let mut jobs = self.jobs.lock().expect("lock poisoned");
+        jobs.push(context.clone());
+        // Forget the oldest finished jobs once there are too many to keep.
+        while jobs.len() > JOB_HISTORY_SIZE {
+            match jobs.iter().position(|c| c.job().status.is_finished()) {
+                Some(index) => jobs.remove(index),
+                None => break,
+            };
+        }
+
+        Ok(context.job())
+    }
+
+    pub fn get(&self, job_id: &str) -> Result<Job, JobServiceError> {
+        Ok(self.context(job_id)?.job())
+    }
+
+    pub fn cancel(&self, job_id: &str) -> Result<Job, JobServiceError> {
+        let context = self.context(job_id)?;
+        context.cancel();
+        Ok(context.job())
+    }
+
+    /// Cancel every job which has not finished, such as when the wallet is
+    /// shutting down.
+    pub fn cancel_all(&self) {
+        for context in self.jobs.lock().expect("lock poisoned").iter() {
+            context.cancel();
+        }
+    }
+
+    /// All recent jobs, in the order they were submitted.
+    pub fn list(&self) -> Vec<Job> {
+        self.jobs
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .map(|c| c.job())
+            .collect()
+    }
+
+    fn context(&self, job_id: &str) -> Result<Arc<JobContext>, JobServiceError> {
+        self.jobs
+            .lock()
+            .expect("lock poisoned")
+            .iter()
+            .find(|c| c.job().id == job_id)
+            .cloned()
+            .ok_or_else(|| JobServiceError::JobNotFound(job_id.to_string()))
+    }
+}
+
+impl Drop for JobManager {
+    fn drop(&mut self) {
+        self.cancel_all();
+        self.sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Trait defining the ways in which the wallet can interact with background
+/// jobs.
+pub trait JobService {
+    /// Get the progress of a job, with its result once it has finished.
+    fn get_job_status(&self, job_id: &str) -> Result<Job, JobServiceError>;
+
+    /// Cancel a job. Jobs which have already finished are left as they are.
+    fn cancel_job(&self, job_id: &str) -> Result<Job, JobServiceError>;
+
+    /// List recent jobs, in the order they were submitted.
+    fn list_jobs(&self) -> Result<Vec<Job>, JobServiceError>;
+}
+
+impl<T, FPR> JobService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn get_job_status(&self, job_id: &str) -> Result<Job, JobServiceError> {
+        self.jobs.get(job_id)
+    }
+
+    fn cancel_job(&self, job_id: &str) -> Result<Job, JobServiceError> {
+        self.jobs.cancel(job_id)
+    }
+
+    fn list_jobs(&self) -> Result<Vec<Job>, JobServiceError> {
+        Ok(self.jobs.list())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mc_common::logger::{test_with_logger, Logger};
+    use std::time::Duration;
+
+    fn wait_for_status(jobs: &JobManager, job_id: &str, status: JobStatus) -> Job {
+        for _ in 0..500 {
+            let job = jobs.get(job_id).unwrap();
+            if job.status == status {
+                return job;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("job {} never reached {}", job_id, status);
+    }
+
+    #[test_with_logger]
+    fn test_job_reports_progress_and_result(logger: Logger) {
+        let jobs = JobManager::start(1, 4, logger).unwrap();
+        let (step_sender, step_receiver) = mpsc::channel::<()>();
+
+        let job = jobs
+            .submit("count", move |context| {
+                for i in 1..=3 {
+                    step_receiver.recv().unwrap();
+                    context.set_progress(i, Some(3));
+                }
+                Ok(Some(serde_json::json!({ "counted": 3 })))
+            })
+            .unwrap();
+        assert_eq!(job.kind, "count");
+        assert_eq!(job.progress, 0);
+
+        step_sender.send(()).unwrap();
+        for _ in 0..500 {
+            if jobs.get(&job.id).unwrap().progress == 1 {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let running = jobs.get(&job.id).unwrap();
+        assert_eq!(running.status, JobStatus::Running);
+        assert_eq!(running.progress, 1);
+        assert_eq!(running.total, Some(3));
+
+        step_sender.send(()).unwrap();
+        step_sender.send(()).unwrap();
+        let completed = wait_for_status(&jobs, &job.id, JobStatus::Completed);
+        assert_eq!(completed.progress, 3);
+        assert_eq!(completed.result, Some(serde_json::json!({ "counted": 3 })));
+        assert!(completed.finished_at.is_some());
+        assert_eq!(jobs.list().len(), 1);
+    }
+
+    #[test_with_logger]
+    fn test_cancel_running_job(logger: Logger) {
+        let jobs = JobManager::start(1, 4, logger).unwrap();
+
+        let job = jobs
+            .submit("spin", |context| {
+                let mut progress = 0;
+                while !context.is_cancelled() {
+                    progress += 1;
+                    context.set_progress(progress, None);
+                    thread::sleep(Duration::from_millis(1));
+                }
+                Ok(None)
+            })
+            .unwrap();
+        wait_for_status(&jobs, &job.id, JobStatus::Running);
+
+        jobs.cancel(&job.id).unwrap();
+        let cancelled = wait_for_status(&jobs, &job.id, JobStatus::Cancelled);
+        assert!(cancelled.progress > 0);
+        assert!(cancelled.finished_at.is_some());
+
+        // Cancelling a finished job leaves it as it is.
+        assert_eq!(jobs.cancel(&job.id).unwrap().status, JobStatus::Cancelled);
+        assert!(matches!(
+            jobs.cancel("missing"),
+            Err(JobServiceError::JobNotFound(_))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_full_queue_rejects_jobs(logger: Logger) {
+        let jobs = JobManager::start(1, 1, logger).unwrap();
+
+        let blocking_job = |context: &JobContext| {
+            while !context.is_cancelled() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            Ok(None)
+        };
+
+        // One job occupies the worker, and the next fills the queue.
+        let running = jobs.submit("block", blocking_job).unwrap();
+        wait_for_status(&jobs, &running.id, JobStatus::Running);
+        let queued = jobs.submit("block", blocking_job).unwrap();
+        assert_eq!(queued.status, JobStatus::Queued);
+
+        assert!(matches!(
+            jobs.submit("block", blocking_job),
+            Err(JobServiceError::QueueFull(1))
+        ));
+        assert_eq!(jobs.list().len(), 2);
+
+        // A job cancelled while queued never runs.
+        assert_eq!(
+            jobs.cancel(&queued.id).unwrap().status,
+            JobStatus::Cancelled
+        );
+        jobs.cancel(&running.id).unwrap();
+        wait_for_status(&jobs, &running.id, JobStatus::Cancelled);
+        assert_eq!(jobs.get(&queued.id).unwrap().progress, 0);
+    }
+}
@@ -8,6 +8,11 @@ use crate::{
         transaction_log::{TransactionID, TransactionLogModel},
         txo::TxoModel,
     },
+    service::sync::decode_amount,
+    util::{
+        constants::{CHECK_KEY_IMAGES_BATCH_SIZE, MAX_CHECK_KEY_IMAGES},
+        encoding_helpers::hex_to_ristretto,
+    },
     WalletService,
 };
 use mc_blockchain_types::{Block, BlockContents, BlockVersion};
@@ -15,13 +20,14 @@ use mc_common::HashSet;
 use mc_connection::{BlockchainConnection, RetryableBlockchainConnection, UserTxConnection};
 use mc_crypto_keys::CompressedRistrettoPublic;
 use mc_fog_report_validation::FogPubkeyResolver;
-use mc_ledger_db::Ledger;
+use mc_ledger_db::{Ledger, LedgerDB};
 use mc_ledger_sync::NetworkState;
 use mc_transaction_core::{
+    membership_proofs::is_membership_proof_valid,
     ring_signature::KeyImage,
     tokens::Mob,
     tx::{Tx, TxOut, TxOutMembershipProof},
-    Token, TokenId,
+    Amount, Token, TokenId,
 };
 use rand::Rng;
 
@@ -59,6 +65,15 @@ pub enum LedgerServiceError {
 
     /// Insufficient Tx Outs
     InsufficientTxOuts,
+
+    /// Membership proof for tx out {index} does not match the ledger root
+    InvalidMembershipProof { index: u64 },
+
+    /// No tx out with public key {0} is in the ledger
+    TxOutNotFound(String),
+
+    /// The view key does not own this txo: {0}
+    TxOutNotOwned(String),
 }
 
 impl From<mc_ledger_db::Error> for LedgerServiceError {
@@ -91,6 +106,45 @@ impl From<mc_crypto_keys::KeyError> for LedgerServiceError {
     }
 }
 
+/// Verify proofs of membership against the root of the ledger's tx out
+/// membership tree, returning the indices of the tx outs whose proofs do not
+/// verify.
+pub fn invalid_membership_proofs<'a>(
+    ledger_db: &LedgerDB,
+    tx_outs_and_proofs: impl IntoIterator<Item = (u64, &'a TxOut, &'a TxOutMembershipProof)>,
+) -> Result<Vec<u64>, mc_ledger_db::Error> {
+    let root_element = ledger_db.get_root_tx_out_membership_element()?;
+    Ok(tx_outs_and_proofs
+        .into_iter()
+        .filter(|(_index, tx_out, proof)| {
+            !is_membership_proof_valid(tx_out, proof, &root_element.hash.0).unwrap_or(false)
+        })
+        .map(|(index, _tx_out, _proof)| index)
+        .collect())
+}
+
+/// Decode a key image from hex of either the key image or its protobuf
+/// encoding.
+fn decode_key_image(key_image_hex: &str) -> Result<KeyImage, LedgerServiceError> {
+    let key_image_bytes = hex::decode(key_image_hex)?;
+    match KeyImage::try_from(&key_image_bytes[..]) {
+        Ok(key_image) => Ok(key_image),
+        Err(_) => Ok(mc_util_serial::decode(&key_image_bytes)?),
+    }
+}
+
+/// A tx out in the ledger, with where it was found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerTxOut {
+    pub tx_out: TxOut,
+
+    /// The block which contains the tx out.
+    pub block_index: u64,
+
+    /// The index of the tx out among all tx outs in the ledger.
+    pub global_index: u64,
+}
+
 /// Trait defining the ways in which the wallet can interact with and manage
 /// ledger objects and interfaces.
 pub trait LedgerService {
@@ -106,12 +160,41 @@ pub trait LedgerService {
         block_index: u64,
     ) -> Result<(Block, BlockContents), LedgerServiceError>;
 
+    /// Get the time at which a block was signed, in seconds since the Unix
+    /// epoch. Returns None for blocks which have no signature.
+    fn get_block_timestamp(&self, block_index: u64) -> Result<Option<u64>, LedgerServiceError>;
+
     fn contains_key_image(&self, key_image: &KeyImage) -> Result<bool, LedgerServiceError>;
 
+    /// Get the index of the block a key image appeared in, or None if it is
+    /// not in the ledger.
+    fn get_key_image_block_index(
+        &self,
+        key_image: &KeyImage,
+    ) -> Result<Option<u64>, LedgerServiceError>;
+
+    /// Check which of a list of key images, given as hex of either the key
+    /// image or its protobuf encoding, are in the ledger, returning the index
+    /// of the block each appeared in. Entries which can not be decoded fail on
+    /// their own, without failing the others.
+    fn check_key_images(
+        &self,
+        key_image_hexes: &[String],
+    ) -> Result<Vec<Result<Option<u64>, LedgerServiceError>>, LedgerServiceError>;
+
     fn get_network_fees(&self) -> BTreeMap<TokenId, u64>;
 
+    /// The smallest value of an output of each token the wallet will build,
+    /// which is the network fee of the token unless overridden in the config.
+    fn get_dust_thresholds(&self) -> BTreeMap<TokenId, u64>;
+
+    /// The block version of the network, or BlockVersion::MAX if it is not
+    /// known.
     fn get_network_block_version(&self) -> BlockVersion;
 
+    /// The block version of the network, or None if no peer answered.
+    fn get_known_network_block_version(&self) -> Option<BlockVersion>;
+
     fn get_tx_out_proof_of_memberships(
         &self,
         indices: &[u64],
@@ -122,11 +205,37 @@ pub trait LedgerService {
         public_keys: &[CompressedRistrettoPublic],
     ) -> Result<Vec<u64>, LedgerServiceError>;
 
+    /// Get a tx out from the ledger by its public key, given as hex of either
+    /// the key or its protobuf encoding. Txos do not need to belong to an
+    /// account in the wallet.
+    fn get_txo_from_ledger(
+        &self,
+        tx_out_public_key_hex: &str,
+    ) -> Result<LedgerTxOut, LedgerServiceError>;
+
+    /// Unmask the amount of a tx out in the ledger with a view private key,
+    /// without importing the key as an account.
+    fn decode_txo_amount(
+        &self,
+        tx_out_public_key_hex: &str,
+        view_private_key_hex: &str,
+    ) -> Result<(LedgerTxOut, Amount), LedgerServiceError>;
+
     fn sample_mixins(
         &self,
         num_mixins: usize,
         excluded_indices: &[u64],
     ) -> Result<(Vec<TxOut>, Vec<TxOutMembershipProof>), LedgerServiceError>;
+
+    /// Verify the proofs of membership of a random sample of tx outs against
+    /// the root of the ledger.
+    ///
+    /// Returns the number of tx outs checked, and the indices of those whose
+    /// proofs did not verify.
+    fn verify_ledger_integrity(
+        &self,
+        sample_size: usize,
+    ) -> Result<(usize, Vec<u64>), LedgerServiceError>;
 }
 
 impl<T, FPR> LedgerService for WalletService<T, FPR>
@@ -146,6 +255,9 @@ where
         let conn = self.wallet_db.get_conn()?;
         let transaction_log =
             TransactionLog::get(&TransactionID(transaction_id_hex.to_string()), &conn)?;
+        if transaction_log.reconstructed {
+            return Err(LedgerServiceError::NoTxInTransaction);
+        }
         let tx: Tx = mc_util_serial::decode(&transaction_log.tx)?;
         Ok(tx)
     }
@@ -167,18 +279,74 @@ where
         Ok((block, block_contents))
     }
 
+    fn get_block_timestamp(&self, block_index: u64) -> Result<Option<u64>, LedgerServiceError> {
+        if let Some(timestamp) = self
+            .block_timestamp_cache
+            .lock()
+            .expect("lock poisoned")
+            .get(&block_index)
+        {
+            return Ok(timestamp);
+        }
+
+        let timestamp = self
+            .ledger_db
+            .get_block_data(block_index)?
+            .signature()
+            .map(|signature| signature.signed_at());
+
+        self.block_timestamp_cache
+            .lock()
+            .expect("lock poisoned")
+            .insert(block_index, timestamp);
+
+        Ok(timestamp)
+    }
+
     fn contains_key_image(&self, key_image: &KeyImage) -> Result<bool, LedgerServiceError> {
         Ok(self.ledger_db.contains_key_image(key_image)?)
     }
 
+    fn get_key_image_block_index(
+        &self,
+        key_image: &KeyImage,
+    ) -> Result<Option<u64>, LedgerServiceError> {
+        Ok(self.ledger_db.check_key_image(key_image)?)
+    }
+
+    fn check_key_images(
+        &self,
+        key_image_hexes: &[String],
+    ) -> Result<Vec<Result<Option<u64>, LedgerServiceError>>, LedgerServiceError> {
+        if key_image_hexes.len() > MAX_CHECK_KEY_IMAGES {
+            return Err(LedgerServiceError::InvalidArgument(format!(
+                "At most {} key images can be checked at once, got {}",
+                MAX_CHECK_KEY_IMAGES,
+                key_image_hexes.len()
+            )));
+        }
+
+        let batches: Vec<Vec<_>> = key_image_hexes
+            .par_chunks(CHECK_KEY_IMAGES_BATCH_SIZE)
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|key_image_hex| {
+                        let key_image = decode_key_image(key_image_hex)?;
+                        self.get_key_image_block_index(&key_image)
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(batches.into_iter().flatten().collect())
+    }
+
     fn get_network_fees(&self) -> BTreeMap<TokenId, u64> {
         let mut fees = self
-            .peer_manager
-            .conns()
-            .par_iter()
-            .filter_map(|conn| conn.fetch_block_info(empty()).ok())
+            .call_aggregation_peers(|conn| conn.fetch_block_info(empty()))
+            .into_iter()
             .map(|block_info| block_info.minimum_fees)
-            .reduce(BTreeMap::new, |mut acc, fees| {
+            .fold(BTreeMap::new(), |mut acc, fees| {
                 for (token_id, fee) in fees {
                     acc.entry(token_id)
                         .and_modify(|e| *e = cmp::max(*e, fee))
@@ -192,21 +360,28 @@ where
         fees
     }
 
+    fn get_dust_thresholds(&self) -> BTreeMap<TokenId, u64> {
+        let mut dust_thresholds = self.get_network_fees();
+        dust_thresholds.extend(self.dust_threshold_overrides.clone());
+        dust_thresholds
+    }
+
     fn get_network_block_version(&self) -> BlockVersion {
+        self.get_known_network_block_version()
+            .unwrap_or(BlockVersion::MAX)
+    }
+
+    fn get_known_network_block_version(&self) -> Option<BlockVersion> {
         if self.peer_manager.is_empty() {
-            BlockVersion::MAX
-        } else {
-            let block_version = self
-                .peer_manager
-                .conns()
-                .par_iter()
-                .filter_map(|conn| conn.fetch_block_info(empty()).ok())
-                .map(|block_info| block_info.network_block_version)
-                .max()
-                .unwrap_or(*BlockVersion::MAX);
-
-            BlockVersion::try_from(block_version).unwrap_or(BlockVersion::MAX)
+            return None;
         }
+        let block_version = self
+            .call_aggregation_peers(|conn| conn.fetch_block_info(empty()))
+            .into_iter()
+            .map(|block_info| block_info.network_block_version)
+            .max()?;
+
+        Some(BlockVersion::try_from(block_version).unwrap_or(BlockVersion::MAX))
     }
 
     fn get_tx_out_proof_of_memberships(
@@ -227,6 +402,56 @@ where
         Ok(indices)
     }
 
+    fn get_txo_from_ledger(
+        &self,
+        tx_out_public_key_hex: &str,
+    ) -> Result<LedgerTxOut, LedgerServiceError> {
+        let public_key_bytes = hex::decode(tx_out_public_key_hex)?;
+        let public_key = match CompressedRistrettoPublic::try_from(&public_key_bytes[..]) {
+            Ok(public_key) => public_key,
+            Err(_) => mc_util_serial::decode(&public_key_bytes)?,
+        };
+
+        let global_index = match self.ledger_db.get_tx_out_index_by_public_key(&public_key) {
+            Ok(global_index) => global_index,
+            Err(mc_ledger_db::Error::NotFound) => {
+                return Err(LedgerServiceError::TxOutNotFound(
+                    tx_out_public_key_hex.to_string(),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let tx_out = self.ledger_db.get_tx_out_by_index(global_index)?;
+        let block_index = self
+            .ledger_db
+            .get_block_index_by_tx_out_index(global_index)?;
+
+        Ok(LedgerTxOut {
+            tx_out,
+            block_index,
+            global_index,
+        })
+    }
+
+    fn decode_txo_amount(
+        &self,
+        tx_out_public_key_hex: &str,
+        view_private_key_hex: &str,
+    ) -> Result<(LedgerTxOut, Amount), LedgerServiceError> {
+        let view_private_key =
+            hex_to_ristretto(view_private_key_hex).map_err(LedgerServiceError::InvalidArgument)?;
+        let ledger_tx_out = self.get_txo_from_ledger(tx_out_public_key_hex)?;
+
+        // The amount's commitment only matches when it is unmasked with the
+        // shared secret of the view key which owns the txo.
+        match decode_amount(&ledger_tx_out.tx_out, &view_private_key) {
+            Some(amount) => Ok((ledger_tx_out, amount)),
+            None => Err(LedgerServiceError::TxOutNotOwned(
+                tx_out_public_key_hex.to_string(),
+            )),
+        }
+    }
+
     fn sample_mixins(
         &self,
         num_mixins: usize,
@@ -266,6 +491,314 @@ where
             .map(|index| self.ledger_db.get_tx_out_by_index(*index))
             .collect::<Result<Vec<TxOut>, _>>()?;
 
+        if let Some(index) = invalid_membership_proofs(
+            &self.ledger_db,
+            sampled_indices_vec
+                .iter()
+                .copied()
+                .zip(tx_outs.iter())
+                .zip(proofs.iter())
+                .map(|((index, tx_out), proof)| (index, tx_out, proof)),
+        )?
+        .first()
+        {
+            return Err(LedgerServiceError::InvalidMembershipProof { index: *index });
+        }
+
         Ok((tx_outs, proofs))
     }
+
+    fn verify_ledger_integrity(
+        &self,
+        sample_size: usize,
+    ) -> Result<(usize, Vec<u64>), LedgerServiceError> {
+        let num_txos = self.ledger_db.num_txos()?;
+        let sample_size = cmp::min(sample_size as u64, num_txos) as usize;
+
+        let mut rng = rand::thread_rng();
+        let mut sampled_indices: HashSet<u64> = HashSet::default();
+        while sampled_indices.len() < sample_size {
+            sampled_indices.insert(rng.gen_range(0..num_txos));
+        }
+        let mut sampled_indices_vec: Vec<u64> = sampled_indices.into_iter().collect();
+        sampled_indices_vec.sort_unstable();
+
+        let proofs = self
+            .ledger_db
+            .get_tx_out_proof_of_memberships(&sampled_indices_vec)?;
+        let tx_outs = sampled_indices_vec
+            .iter()
+            .map(|index| self.ledger_db.get_tx_out_by_index(*index))
+            .collect::<Result<Vec<TxOut>, _>>()?;
+
+        let invalid_indices = invalid_membership_proofs(
+            &self.ledger_db,
+            sampled_indices_vec
+                .iter()
+                .copied()
+                .zip(tx_outs.iter())
+                .zip(proofs.iter())
+                .map(|((index, tx_out), proof)| (index, tx_out, proof)),
+        )?;
+
+        Ok((sample_size, invalid_indices))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, random_account_with_seed_values,
+            setup_wallet_service, setup_wallet_service_offline, MOB,
+        },
+        util::encoding_helpers::ristretto_to_hex,
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_keys::RistrettoPrivate;
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+
+    #[test_with_logger]
+    fn test_get_block_timestamp_caches_blocks_without_signatures(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db, logger);
+
+        // Test blocks are appended without signatures, so they have no timestamp.
+        assert_eq!(service.get_block_timestamp(0).unwrap(), None);
+        assert_eq!(service.get_block_timestamp(5).unwrap(), None);
+        {
+            let cache = service.block_timestamp_cache.lock().unwrap();
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.hits(), 0);
+            assert_eq!(cache.misses(), 2);
+        }
+
+        // Repeated lookups are served from the cache.
+        assert_eq!(service.get_block_timestamp(0).unwrap(), None);
+        assert_eq!(service.get_block_timestamp(5).unwrap(), None);
+        {
+            let cache = service.block_timestamp_cache.lock().unwrap();
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.hits(), 2);
+            assert_eq!(cache.misses(), 2);
+        }
+
+        // Blocks which are not in the ledger are an error, and are not cached.
+        assert!(service.get_block_timestamp(100).is_err());
+        assert_eq!(service.block_timestamp_cache.lock().unwrap().len(), 2);
+    }
+
+    #[test_with_logger]
+    fn test_invalid_membership_proofs(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger);
+
+        let indices = vec![0, 3, 7];
+        let tx_outs = indices
+            .iter()
+            .map(|index| ledger_db.get_tx_out_by_index(*index).unwrap())
+            .collect::<Vec<TxOut>>();
+        let mut proofs = ledger_db.get_tx_out_proof_of_memberships(&indices).unwrap();
+
+        let tx_outs_and_proofs = |proofs: &[TxOutMembershipProof]| {
+            indices
+                .iter()
+                .copied()
+                .zip(tx_outs.iter())
+                .zip(proofs.iter())
+                .map(|((index, tx_out), proof)| (index, tx_out, proof))
+                .collect::<Vec<_>>()
+        };
+        assert!(invalid_membership_proofs(&ledger_db, tx_outs_and_proofs(&proofs))
+            .unwrap()
+            .is_empty());
+
+        // Corrupt one element of the proof for tx out 3.
+        proofs[1].elements[0].hash.0[0] ^= 1;
+        assert_eq!(
+            invalid_membership_proofs(&ledger_db, tx_outs_and_proofs(&proofs)).unwrap(),
+            vec![3]
+        );
+
+        // A proof for another tx out does not verify either.
+        proofs[1] = proofs[2].clone();
+        assert_eq!(
+            invalid_membership_proofs(&ledger_db, tx_outs_and_proofs(&proofs)).unwrap(),
+            vec![3]
+        );
+
+        // The whole ledger is intact.
+        let num_txos = ledger_db.num_txos().unwrap() as usize;
+        let (num_checked, invalid_indices) =
+            service.verify_ledger_integrity(num_txos + 10).unwrap();
+        assert_eq!(num_checked, num_txos);
+        assert!(invalid_indices.is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_get_and_decode_txo_from_ledger(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        // The seed value is the only output of the last block.
+        let block_index = ledger_db.num_blocks().unwrap() - 1;
+        let tx_out = ledger_db.get_block_contents(block_index).unwrap().outputs[0].clone();
+        let public_key_hex = hex::encode(tx_out.public_key.as_bytes());
+
+        let ledger_tx_out = service.get_txo_from_ledger(&public_key_hex).unwrap();
+        assert_eq!(ledger_tx_out.tx_out, tx_out);
+        assert_eq!(ledger_tx_out.block_index, block_index);
+        assert_eq!(
+            ledger_tx_out.global_index,
+            ledger_db.num_txos().unwrap() - 1
+        );
+
+        // The protobuf encoding of the public key is accepted too.
+        let encoded_public_key_hex = hex::encode(mc_util_serial::encode(&tx_out.public_key));
+        assert_eq!(
+            service
+                .get_txo_from_ledger(&encoded_public_key_hex)
+                .unwrap(),
+            ledger_tx_out
+        );
+
+        let (_, amount) = service
+            .decode_txo_amount(
+                &public_key_hex,
+                &ristretto_to_hex(account_key.view_private_key()),
+            )
+            .unwrap();
+        assert_eq!(amount, Amount::new(70 * MOB, Mob::ID));
+
+        // An unrelated view key cannot unmask the amount.
+        let other_view_key = AccountKey::random(&mut rng).view_private_key().clone();
+        match service.decode_txo_amount(&public_key_hex, &ristretto_to_hex(&other_view_key)) {
+            Err(LedgerServiceError::TxOutNotOwned(_)) => {}
+            other => panic!("Expected TxOutNotOwned, got {:?}", other),
+        }
+
+        let missing_public_key_hex = hex::encode(
+            CompressedRistrettoPublic::from(&RistrettoPrivate::from_random(&mut rng)).as_bytes(),
+        );
+        match service.get_txo_from_ledger(&missing_public_key_hex) {
+            Err(LedgerServiceError::TxOutNotFound(_)) => {}
+            other => panic!("Expected TxOutNotFound, got {:?}", other),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_check_key_images(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let spent_key_image = KeyImage::from(rng.next_u64());
+        let num_blocks = add_block_to_ledger_db(
+            &mut ledger_db,
+            &[AccountKey::random(&mut rng).default_subaddress()],
+            100 * MOB,
+            &[spent_key_image],
+            &mut rng,
+        );
+        let unspent_key_image = KeyImage::from(rng.next_u64());
+
+        let service = setup_wallet_service(ledger_db, logger);
+        assert_eq!(
+            service.get_key_image_block_index(&spent_key_image).unwrap(),
+            Some(num_blocks - 1)
+        );
+        assert_eq!(
+            service
+                .get_key_image_block_index(&unspent_key_image)
+                .unwrap(),
+            None
+        );
+
+        // Key images can be given as hex of the key image or of its protobuf
+        // encoding, and malformed entries fail on their own.
+        let results = service
+            .check_key_images(&[
+                hex::encode(spent_key_image.as_bytes()),
+                hex::encode(mc_util_serial::encode(&spent_key_image)),
+                hex::encode(unspent_key_image.as_bytes()),
+                "not hex".to_string(),
+                "abcd".to_string(),
+            ])
+            .unwrap();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap(), &Some(num_blocks - 1));
+        assert_eq!(results[1].as_ref().unwrap(), &Some(num_blocks - 1));
+        assert_eq!(results[2].as_ref().unwrap(), &None);
+        assert!(matches!(results[3], Err(LedgerServiceError::FromHex(_))));
+        assert!(matches!(
+            results[4],
+            Err(LedgerServiceError::ProstDecode(_))
+        ));
+
+        // Results are in the order of the key images across batches.
+        let key_image_hexes: Vec<String> = (0..CHECK_KEY_IMAGES_BATCH_SIZE + 1)
+            .map(|i| {
+                if i == CHECK_KEY_IMAGES_BATCH_SIZE {
+                    hex::encode(spent_key_image.as_bytes())
+                } else {
+                    hex::encode(KeyImage::from(rng.next_u64()).as_bytes())
+                }
+            })
+            .collect();
+        let results = service.check_key_images(&key_image_hexes).unwrap();
+        assert_eq!(results.len(), CHECK_KEY_IMAGES_BATCH_SIZE + 1);
+        assert!(results[..CHECK_KEY_IMAGES_BATCH_SIZE]
+            .iter()
+            .all(|result| result.as_ref().unwrap().is_none()));
+        assert_eq!(
+            results[CHECK_KEY_IMAGES_BATCH_SIZE].as_ref().unwrap(),
+            &Some(num_blocks - 1)
+        );
+
+        // Lists over the limit are rejected as a whole.
+        let too_many = vec![hex::encode(spent_key_image.as_bytes()); MAX_CHECK_KEY_IMAGES + 1];
+        assert!(matches!(
+            service.check_key_images(&too_many),
+            Err(LedgerServiceError::InvalidArgument(_))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_network_block_version_without_peers(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let block_version = service.get_known_network_block_version().unwrap();
+        assert_eq!(service.get_network_block_version(), block_version);
+
+        // Without peers the network's block version is not known.
+        let offline_service = setup_wallet_service_offline(ledger_db, logger);
+        assert_eq!(offline_service.get_known_network_block_version(), None);
+        assert_eq!(
+            offline_service.get_network_block_version(),
+            BlockVersion::MAX
+        );
+    }
 }
@@ -0,0 +1,586 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for reporting on and maintaining the files of the ledger database.
+//!
+//! LMDB never shrinks its file, so after years of syncing the ledger carries
+//! free pages. The ledger can be compacted by copying its records into a
+//! fresh environment, which the operator swaps in while the wallet is
+//! stopped.
+//!
+//! The ledger is opened a second time here, read-only and without LMDB's lock
+//! file, since opening the lock file again from the process which already
+//! has the ledger open would reset it. The copy is taken in a single read
+//! transaction, and checked against the wallet's ledger once it is written.
+
+use crate::{
+    service::job::{Job, JobContext, JobServiceError},
+    util::constants::{LEDGER_DB_COMPACTION_BATCH_SIZE, LEDGER_DB_MAX_DBS},
+    WalletService,
+};
+use displaydoc::Display;
+use lmdb::{Cursor, DatabaseFlags, Environment, EnvironmentFlags, Transaction, WriteFlags};
+use mc_common::logger::log;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::{Ledger, LedgerDB};
+use std::{
+    ffi::CString,
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+/// The file LMDB keeps its data in, within the ledger's directory.
+const LMDB_DATA_FILE: &str = "data.mdb";
+
+/// The file LMDB keeps its reader table in, within the ledger's directory.
+const LMDB_LOCK_FILE: &str = "lock.mdb";
+
+/// Errors for the Ledger DB Service.
+#[derive(Display, Debug)]
+pub enum LedgerDbServiceError {
+    /// Error interacting with the ledger: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Error from LMDB: {0}
+    Lmdb(lmdb::Error),
+
+    /// IO error: {0}
+    Io(std::io::Error),
+
+    /// Error with the job service: {0}
+    JobService(JobServiceError),
+
+    /// The location of the ledger database is not known
+    LedgerDbPathUnknown,
+
+    /// Invalid target path: {0}
+    InvalidTargetPath(String),
+
+    /// Not enough disk space for the copy: {required} bytes are needed, {available} are available
+    InsufficientDiskSpace { required: u64, available: u64 },
+
+    /// The compacted copy does not match the ledger: {0}
+    CopyVerificationFailed(String),
+}
+
+impl From<mc_ledger_db::Error> for LedgerDbServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<lmdb::Error> for LedgerDbServiceError {
+    fn from(src: lmdb::Error) -> Self {
+        Self::Lmdb(src)
+    }
+}
+
+impl From<std::io::Error> for LedgerDbServiceError {
+    fn from(src: std::io::Error) -> Self {
+        Self::Io(src)
+    }
+}
+
+impl From<JobServiceError> for LedgerDbServiceError {
+    fn from(src: JobServiceError) -> Self {
+        Self::JobService(src)
+    }
+}
+
+/// The size and page usage of the ledger database, along with what it holds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerDbStatus {
+    /// The size of the data file, in bytes.
+    pub file_size_bytes: u64,
+
+    /// The size of LMDB's memory map, which bounds the size of the file.
+    pub map_size_bytes: u64,
+
+    pub page_size: u64,
+
+    /// The pages LMDB has allocated, which are either in use or free.
+    pub total_pages: u64,
+
+    /// The allocated pages which hold no data, and are reused by later writes.
+    pub free_pages: u64,
+
+    pub num_blocks: u64,
+    pub num_txos: u64,
+
+    /// When the last block was signed, in seconds since the Unix epoch, if it
+    /// has a signature.
+    pub last_block_timestamp: Option<u64>,
+}
+
+impl LedgerDbStatus {
+    pub fn used_pages(&self) -> u64 {
+        self.total_pages.saturating_sub(self.free_pages)
+    }
+
+    /// The bytes of the pages in use, which bounds the size of a compacted
+    /// copy.
+    pub fn used_bytes(&self) -> u64 {
+        self.used_pages() * self.page_size
+    }
+}
+
+/// The outcome of compacting the ledger database into a copy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LedgerDbCompaction {
+    pub target_path: PathBuf,
+
+    /// The number of blocks in the copy.
+    pub num_blocks: u64,
+
+    /// The ID of the last block in the copy, as hex.
+    pub last_block_id: String,
+
+    /// The size of the ledger's data file, in bytes.
+    pub source_size_bytes: u64,
+
+    /// The size of the copy's data file, in bytes.
+    pub compacted_size_bytes: u64,
+}
+
+impl LedgerDbCompaction {
+    /// How much larger the copy is than the ledger, in bytes, which is
+    /// negative when compacting saves space.
+    pub fn size_delta_bytes(&self) -> i64 {
+        self.compacted_size_bytes as i64 - self.source_size_bytes as i64
+    }
+}
+
+/// Trait defining the ways in which the wallet can report on and maintain the
+/// ledger database.
+pub trait LedgerDbService {
+    /// Get the size and page usage of the ledger database, and the blocks and
+    /// txos it holds.
+    fn get_ledger_db_status(&self) -> Result<LedgerDbStatus, LedgerDbServiceError>;
+
+    /// Start a job copying the ledger database into a new directory without
+    /// its free pages, and checking the copy against the ledger. Replacing
+    /// the ledger with the copy is left to the operator.
+    fn compact_ledger_db(&self, target_path: &str) -> Result<Job, LedgerDbServiceError>;
+}
+
+impl<T, FPR> LedgerDbService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn get_ledger_db_status(&self) -> Result<LedgerDbStatus, LedgerDbServiceError> {
+        let ledger_db_path = self
+            .ledger_db_path
+            .as_ref()
+            .ok_or(LedgerDbServiceError::LedgerDbPathUnknown)?;
+        ledger_db_status(&self.ledger_db, ledger_db_path)
+    }
+
+    fn compact_ledger_db(&self, target_path: &str) -> Result<Job, LedgerDbServiceError> {
+        let ledger_db_path = self
+            .ledger_db_path
+            .clone()
+            .ok_or(LedgerDbServiceError::LedgerDbPathUnknown)?;
+        let target_path = PathBuf::from(target_path);
+        check_compaction_target(&ledger_db_path, &target_path)?;
+
+        // Refuse to start a copy which can not fit.
+        let status = ledger_db_status(&self.ledger_db, &ledger_db_path)?;
+        check_available_space(&target_path, status.used_bytes())?;
+
+        let ledger_db = self.ledger_db.clone();
+        let target_display = target_path.display().to_string();
+        let job = self.jobs.submit("compact_ledger_db", move |context| {
+            let compaction =
+                match compact_ledger_db(&ledger_db, &ledger_db_path, &target_path, context)
+                    .map_err(|e| e.to_string())?
+                {
+                    Some(compaction) => compaction,
+                    None => return Ok(None),
+                };
+            Ok(Some(serde_json::json!({
+                "target_path": compaction.target_path.display().to_string(),
+                "num_blocks": compaction.num_blocks.to_string(),
+                "last_block_id": compaction.last_block_id,
+                "source_size_bytes": compaction.source_size_bytes.to_string(),
+                "compacted_size_bytes": compaction.compacted_size_bytes.to_string(),
+                "size_delta_bytes": compaction.size_delta_bytes().to_string(),
+            })))
+        })?;
+        log::info!(
+            self.request_logger(),
+            "Compacting the ledger db into {} in job {}",
+            target_display,
+            job.id
+        );
+
+        Ok(job)
+    }
+}
+
+/// Open the LMDB environment of the ledger read-only, alongside the wallet's
+/// own.
+fn open_read_only(ledger_db_path: &Path) -> Result<Environment, LedgerDbServiceError> {
+    Ok(Environment::new()
+        .set_flags(EnvironmentFlags::READ_ONLY | EnvironmentFlags::NO_LOCK)
+        .set_max_dbs(LEDGER_DB_MAX_DBS)
+        .open(ledger_db_path)?)
+}
+
+/// Get the size and page usage of the ledger database in the given
+/// directory, and the blocks and txos it holds.
+pub fn ledger_db_status(
+    ledger_db: &LedgerDB,
+    ledger_db_path: &Path,
+) -> Result<LedgerDbStatus, LedgerDbServiceError> {
+    let env = open_read_only(ledger_db_path)?;
+    let stat = env.stat()?;
+    let info = env.info()?;
+    let free_pages = env.freelist()?;
+
+    let num_blocks = ledger_db.num_blocks()?;
+    let last_block_timestamp = match num_blocks {
+        0 => None,
+        _ => ledger_db
+            .get_block_data(num_blocks - 1)?
+            .signature()
+            .map(|signature| signature.signed_at()),
+    };
+
+    Ok(LedgerDbStatus {
+        file_size_bytes: fs::metadata(ledger_db_path.join(LMDB_DATA_FILE))?.len(),
+        map_size_bytes: info.map_size() as u64,
+        page_size: stat.page_size() as u64,
+        total_pages: info.last_pgno() as u64 + 1,
+        free_pages: free_pages as u64,
+        num_blocks,
+        num_txos: ledger_db.num_txos()?,
+        last_block_timestamp,
+    })
+}
+
+/// Check that a compacted copy can be written to the target directory: it
+/// must not be the ledger's own directory, and must not hold a database.
+pub fn check_compaction_target(
+    ledger_db_path: &Path,
+    target_path: &Path,
+) -> Result<(), LedgerDbServiceError> {
+    if target_path.as_os_str().is_empty() {
+        return Err(LedgerDbServiceError::InvalidTargetPath(
+            "the target path is empty".to_string(),
+        ));
+    }
+    if target_path.join(LMDB_DATA_FILE).exists() {
+        return Err(LedgerDbServiceError::InvalidTargetPath(format!(
+            "{:?} already holds a database",
+            target_path
+        )));
+    }
+    fs::create_dir_all(target_path)?;
+    if fs::canonicalize(target_path)? == fs::canonicalize(ledger_db_path)? {
+        return Err(LedgerDbServiceError::InvalidTargetPath(
+            "the target path is the ledger's own directory".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that the filesystem holding the path has the given number of bytes
+/// available.
+pub fn check_available_space(path: &Path, required: u64) -> Result<(), LedgerDbServiceError> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| LedgerDbServiceError::InvalidTargetPath(e.to_string()))?;
+    // Safety: c_path is NUL terminated, and statvfs only writes to stat.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let available = stat.f_bavail as u64 * stat.f_frsize as u64;
+    if available < required {
+        return Err(LedgerDbServiceError::InsufficientDiskSpace {
+            required,
+            available,
+        });
+    }
+    Ok(())
+}
+
+/// Copy the records of every database in the ledger into a new environment
+/// in the target directory, appending them in order so that the copy's pages
+/// are full, then check the copy against the ledger.
+///
+/// Returns None if the job was cancelled, in which case the partial copy is
+/// removed, as it is when the copy fails.
+pub fn compact_ledger_db(
+    ledger_db: &LedgerDB,
+    ledger_db_path: &Path,
+    target_path: &Path,
+    job: &JobContext,
+) -> Result<Option<LedgerDbCompaction>, LedgerDbServiceError> {
+    let result = copy_ledger_db(ledger_db_path, target_path, job).and_then(|completed| {
+        if completed {
+            verify_compacted_copy(ledger_db, ledger_db_path, target_path).map(Some)
+        } else {
+            Ok(None)
+        }
+    });
+    if !matches!(result, Ok(Some(_))) {
+        for file in &[LMDB_DATA_FILE, LMDB_LOCK_FILE] {
+            let _ = fs::remove_file(target_path.join(file));
+        }
+    }
+    result
+}
+
+/// Returns whether the copy completed, rather than being cancelled.
+fn copy_ledger_db(
+    ledger_db_path: &Path,
+    target_path: &Path,
+    job: &JobContext,
+) -> Result<bool, LedgerDbServiceError> {
+    let source = open_read_only(ledger_db_path)?;
+    let target = Environment::new()
+        .set_max_dbs(LEDGER_DB_MAX_DBS)
+        .set_map_size(source.info()?.map_size())
+        .open(target_path)?;
+
+    // The keys of the unnamed database are the names of the others.
+    let db_names: Vec<String> = {
+        let txn = source.begin_ro_txn()?;
+        let mut cursor = txn.open_ro_cursor(source.open_db(None)?)?;
+        cursor
+            .iter_start()
+            .map(|item| {
+                let (name, _) = item?;
+                Ok(String::from_utf8_lossy(name).into_owned())
+            })
+            .collect::<Result<_, LedgerDbServiceError>>()?
+    };
+    let dbs = db_names
+        .iter()
+        .map(|name| Ok((name, source.open_db(Some(name.as_str()))?)))
+        .collect::<Result<Vec<_>, LedgerDbServiceError>>()?;
+
+    // Every database is read from the same snapshot.
+    let txn = source.begin_ro_txn()?;
+    for (copied, (name, db)) in dbs.iter().enumerate() {
+        if job.is_cancelled() {
+            return Ok(false);
+        }
+        job.set_progress(copied as u64, Some(dbs.len() as u64));
+
+        let flags = txn.db_flags(*db)?;
+        let target_db = target.create_db(Some(name.as_str()), flags)?;
+        // Duplicates of a key can not be appended as new keys.
+        let write_flags = if flags.contains(DatabaseFlags::DUP_SORT) {
+            WriteFlags::empty()
+        } else {
+            WriteFlags::APPEND
+        };
+
+        let mut cursor = txn.open_ro_cursor(*db)?;
+        let mut target_txn = target.begin_rw_txn()?;
+        let mut batch_len = 0;
+        for item in cursor.iter_start() {
+            let (key, value) = item?;
+            target_txn.put(target_db, &key, &value, write_flags)?;
+            batch_len += 1;
+            if batch_len == LEDGER_DB_COMPACTION_BATCH_SIZE {
+                target_txn.commit()?;
+                if job.is_cancelled() {
+                    return Ok(false);
+                }
+                target_txn = target.begin_rw_txn()?;
+                batch_len = 0;
+            }
+        }
+        target_txn.commit()?;
+    }
+    job.set_progress(dbs.len() as u64, Some(dbs.len() as u64));
+    target.sync(true)?;
+
+    Ok(true)
+}
+
+/// Check that the copy holds the blocks the ledger held when it was taken,
+/// ending with the same block, and the txos of those blocks.
+fn verify_compacted_copy(
+    ledger_db: &LedgerDB,
+    ledger_db_path: &Path,
+    target_path: &Path,
+) -> Result<LedgerDbCompaction, LedgerDbServiceError> {
+    let copy = LedgerDB::open(target_path)?;
+    let num_blocks = copy.num_blocks()?;
+    if num_blocks == 0 || num_blocks > ledger_db.num_blocks()? {
+        return Err(LedgerDbServiceError::CopyVerificationFailed(format!(
+            "the copy has {} blocks, and the ledger {}",
+            num_blocks,
+            ledger_db.num_blocks()?
+        )));
+    }
+
+    let last_block = copy.get_block(num_blocks - 1)?;
+    let ledger_block = ledger_db.get_block(num_blocks - 1)?;
+    if last_block.id != ledger_block.id {
+        return Err(LedgerDbServiceError::CopyVerificationFailed(format!(
+            "block {} of the copy has ID {}, and of the ledger {}",
+            num_blocks - 1,
+            hex::encode(last_block.id.clone()),
+            hex::encode(ledger_block.id.clone())
+        )));
+    }
+    if copy.num_txos()? != last_block.cumulative_txo_count {
+        return Err(LedgerDbServiceError::CopyVerificationFailed(format!(
+            "the copy has {} txos, and its blocks {}",
+            copy.num_txos()?,
+            last_block.cumulative_txo_count
+        )));
+    }
+
+    Ok(LedgerDbCompaction {
+        target_path: target_path.to_path_buf(),
+        num_blocks,
+        last_block_id: hex::encode(last_block.id),
+        source_size_bytes: fs::metadata(ledger_db_path.join(LMDB_DATA_FILE))?.len(),
+        compacted_size_bytes: fs::metadata(target_path.join(LMDB_DATA_FILE))?.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::job::{JobService, JobStatus},
+        test_utils::{add_block_to_ledger_db, generate_ledger_db, setup_wallet_service},
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
+    use mc_transaction_core::ring_signature::KeyImage;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{thread, time::Duration};
+    use tempdir::TempDir;
+
+    fn wait_for_job<S: JobService>(service: &S, job_id: &str) -> Job {
+        for _ in 0..1000 {
+            let job = service.get_job_status(job_id).unwrap();
+            if job.status.is_finished() {
+                return job;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("job {} never finished", job_id);
+    }
+
+    #[test_with_logger]
+    fn test_compact_ledger_db(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let ledger_dir = TempDir::new("ledger_db").unwrap();
+        let mut ledger_db = generate_ledger_db(ledger_dir.path().to_str().unwrap());
+        let recipients: Vec<PublicAddress> = (0..4)
+            .map(|_| AccountKey::random(&mut rng).default_subaddress())
+            .collect();
+        add_block_to_ledger_db(&mut ledger_db, &recipients, 100, &[], &mut rng);
+        for _ in 0..20 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &recipients,
+                100,
+                &[KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+
+        let mut service = setup_wallet_service(ledger_db.clone(), logger);
+        service.ledger_db_path = Some(ledger_dir.path().to_path_buf());
+
+        let status = service.get_ledger_db_status().unwrap();
+        assert_eq!(status.num_blocks, 21);
+        assert_eq!(status.num_txos, 84);
+        assert!(status.free_pages < status.total_pages);
+        assert!(status.used_bytes() <= status.file_size_bytes);
+        assert!(status.file_size_bytes <= status.map_size_bytes);
+
+        let target_dir = TempDir::new("compacted_ledger_db").unwrap();
+        let target_path = target_dir.path().join("ledger");
+        let job = service
+            .compact_ledger_db(target_path.to_str().unwrap())
+            .unwrap();
+        assert_eq!(job.kind, "compact_ledger_db");
+
+        let job = wait_for_job(&service, &job.id);
+        assert_eq!(job.status, JobStatus::Completed, "{:?}", job.error);
+        let result = job.result.unwrap();
+        assert_eq!(result["num_blocks"], "21");
+        assert_eq!(
+            result["last_block_id"],
+            hex::encode(ledger_db.get_block(20).unwrap().id)
+        );
+        let source_size: i64 = result["source_size_bytes"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let compacted_size: i64 = result["compacted_size_bytes"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(source_size, status.file_size_bytes as i64);
+        assert!(compacted_size > 0);
+        assert!(compacted_size <= source_size);
+        assert_eq!(
+            result["size_delta_bytes"],
+            (compacted_size - source_size).to_string()
+        );
+
+        // The copy opens as a ledger holding the same blocks.
+        let copy = LedgerDB::open(&target_path).unwrap();
+        assert_eq!(copy.num_blocks().unwrap(), 21);
+        assert_eq!(copy.num_txos().unwrap(), 84);
+        assert_eq!(
+            copy.get_block(20).unwrap(),
+            ledger_db.get_block(20).unwrap()
+        );
+
+        // A directory which already holds a database is not overwritten.
+        assert!(matches!(
+            service.compact_ledger_db(target_path.to_str().unwrap()),
+            Err(LedgerDbServiceError::InvalidTargetPath(_))
+        ));
+        assert!(matches!(
+            service.compact_ledger_db(ledger_dir.path().to_str().unwrap()),
+            Err(LedgerDbServiceError::InvalidTargetPath(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_available_space() {
+        let dir = TempDir::new("ledger_db_space").unwrap();
+        check_available_space(dir.path(), 1).unwrap();
+        assert!(matches!(
+            check_available_space(dir.path(), u64::MAX),
+            Err(LedgerDbServiceError::InsufficientDiskSpace {
+                required: u64::MAX,
+                ..
+            })
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_ledger_db_path_unknown(logger: Logger) {
+        let ledger_dir = TempDir::new("ledger_db").unwrap();
+        let ledger_db = generate_ledger_db(ledger_dir.path().to_str().unwrap());
+        let service = setup_wallet_service(ledger_db, logger);
+        assert!(matches!(
+            service.get_ledger_db_status(),
+            Err(LedgerDbServiceError::LedgerDbPathUnknown)
+        ));
+        assert!(matches!(
+            service.compact_ledger_db(ledger_dir.path().to_str().unwrap()),
+            Err(LedgerDbServiceError::LedgerDbPathUnknown)
+        ));
+    }
+}
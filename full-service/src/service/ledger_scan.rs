@@ -0,0 +1,512 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for scanning a range of the ledger for the txos received by a view
+//! key, without importing it as an account.
+//!
+//! Scans run on a background thread and are polled by their id until they
+//! finish. Nothing is written to the wallet database, and scans are forgotten
+//! when the wallet restarts.
+
+use crate::{
+    service::{
+        sync::{decode_amount, decode_subaddress_index},
+        WalletService,
+    },
+    util::{
+        constants::{MAX_LEDGER_SCAN_BLOCKS, MAX_LEDGER_SCAN_SUBADDRESSES},
+        encoding_helpers::{hex_to_ristretto, hex_to_ristretto_public},
+    },
+};
+use displaydoc::Display;
+use mc_account_keys::ViewAccountKey;
+use mc_common::{logger::log, HashMap};
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::RistrettoPublic;
+use mc_crypto_rand::rand_core::RngCore;
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::{Ledger, LedgerDB};
+use mc_transaction_core::{tx::TxOut, Amount};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+use strum::Display as StrumDisplay;
+
+/// Errors for the Ledger Scan Service.
+#[derive(Display, Debug)]
+pub enum LedgerScanServiceError {
+    /// Error with LedgerDB: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Invalid key: {0}
+    InvalidKey(String),
+
+    /// Invalid block range: {0}
+    InvalidBlockRange(String),
+
+    /// Cannot scan {0} subaddresses, the maximum is {1}
+    TooManySubaddresses(u64, u64),
+
+    /// Error starting the scan thread: {0}
+    Thread(std::io::Error),
+
+    /// Ledger scan not found: {0}
+    ScanNotFound(String),
+}
+
+impl From<mc_ledger_db::Error> for LedgerScanServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<std::io::Error> for LedgerScanServiceError {
+    fn from(src: std::io::Error) -> Self {
+        Self::Thread(src)
+    }
+}
+
+/// A txo received by the scanned view key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScannedTxo {
+    pub tx_out: TxOut,
+    pub amount: Amount,
+    pub block_index: u64,
+
+    /// The subaddress which received the txo, if it is within the scanned
+    /// subaddress range.
+    pub subaddress_index: Option<u64>,
+}
+
+/// The state of a ledger scan.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, StrumDisplay)]
+#[strum(serialize_all = "snake_case")]
+pub enum LedgerScanStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+/// The progress of a ledger scan, with the txos found so far.
+#[derive(Clone, Debug)]
+pub struct LedgerScan {
+    pub id: String,
+    pub status: LedgerScanStatus,
+
+    /// Why the scan failed, if it did.
+    pub error: Option<String>,
+
+    pub first_block_index: u64,
+    pub last_block_index: u64,
+
+    /// The next block to be scanned. Once the scan has completed, this is one
+    /// past the last block index.
+    pub next_block_index: u64,
+
+    pub txos: Vec<ScannedTxo>,
+}
+
+/// A ledger scan shared between the thread running it and the wallet service.
+pub struct LedgerScanJob {
+    scan: Mutex<LedgerScan>,
+
+    /// Cancel trigger, checked before each block is scanned.
+    cancel_requested: AtomicBool,
+}
+
+impl LedgerScanJob {
+    pub fn new(id: String, first_block_index: u64, last_block_index: u64) -> Self {
+        Self {
+            scan: Mutex::new(LedgerScan {
+                id,
+                status: LedgerScanStatus::Running,
+                error: None,
+                first_block_index,
+                last_block_index,
+                next_block_index: first_block_index,
+                txos: Vec::new(),
+            }),
+            cancel_requested: AtomicBool::new(false),
+        }
+    }
+
+    /// A snapshot of the scan's progress.
+    pub fn scan(&self) -> LedgerScan {
+        self.scan.lock().expect("lock poisoned").clone()
+    }
+
+    /// Stop the scan before its next block. The txos found so far are kept.
+    pub fn cancel(&self) {
+        self.cancel_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Scan the job's blocks for txos received by the view key, recording
+    /// them as each block is scanned, until the range is finished or the scan
+    /// is cancelled.
+    pub fn run(
+        &self,
+        ledger_db: &LedgerDB,
+        view_account_key: &ViewAccountKey,
+        subaddress_keys: &HashMap<RistrettoPublic, u64>,
+    ) {
+        let result = self.scan_blocks(ledger_db, view_account_key, subaddress_keys);
+
+        let mut scan = self.scan.lock().expect("lock poisoned");
+        match result {
+            Ok(status) => scan.status = status,
+            Err(e) => {
+                scan.status = LedgerScanStatus::Failed;
+                scan.error = Some(e.to_string());
+            }
+        }
+    }
+
+    fn scan_blocks(
+        &self,
+        ledger_db: &LedgerDB,
+        view_account_key: &ViewAccountKey,
+        subaddress_keys: &HashMap<RistrettoPublic, u64>,
+    ) -> Result<LedgerScanStatus, mc_ledger_db::Error> {
+        let (first_block_index, last_block_index) = {
+            let scan = self.scan.lock().expect("lock poisoned");
+            (scan.first_block_index, scan.last_block_index)
+        };
+
+        for block_index in first_block_index..=last_block_index {
+            if self.cancel_requested.load(Ordering::SeqCst) {
+                return Ok(LedgerScanStatus::Cancelled);
+            }
+
+            let block_contents = ledger_db.get_block_contents(block_index)?;
+            let txos: Vec<ScannedTxo> = block_contents
+                .outputs
+                .into_iter()
+                .filter_map(|tx_out| {
+                    let view_private_key = view_account_key.view_private_key();
+                    let amount = decode_amount(&tx_out, view_private_key)?;
+                    let subaddress_index =
+                        decode_subaddress_index(&tx_out, view_private_key, subaddress_keys);
+                    Some(ScannedTxo {
+                        tx_out,
+                        amount,
+                        block_index,
+                        subaddress_index,
+                    })
+                })
+                .collect();
+
+            let mut scan = self.scan.lock().expect("lock poisoned");
+            scan.txos.extend(txos);
+            scan.next_block_index = block_index + 1;
+        }
+
+        Ok(LedgerScanStatus::Completed)
+    }
+}
+
+/// Trait defining the ways in which the wallet can scan the ledger for a view
+/// key which is not an account in the wallet.
+pub trait LedgerScanService {
+    /// Start scanning the blocks from first_block_index to last_block_index,
+    /// inclusive, for the txos received by the view key. Txos received at a
+    /// subaddress outside of the given range are still found, without their
+    /// subaddress index.
+    fn scan_ledger_with_view_key(
+        &self,
+        view_private_key: &str,
+        spend_public_key: &str,
+        first_block_index: u64,
+        last_block_index: u64,
+        subaddress_indices: Range<u64>,
+    ) -> Result<LedgerScan, LedgerScanServiceError>;
+
+    /// Get the progress of a ledger scan, with the txos found so far.
+    fn get_ledger_scan(&self, ledger_scan_id: &str) -> Result<LedgerScan, LedgerScanServiceError>;
+
+    /// Cancel a ledger scan. Scans which have already finished are left as
+    /// they are.
+    fn cancel_ledger_scan(
+        &self,
+        ledger_scan_id: &str,
+    ) -> Result<LedgerScan, LedgerScanServiceError>;
+}
+
+impl<T, FPR> LedgerScanService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn scan_ledger_with_view_key(
+        &self,
+        view_private_key: &str,
+        spend_public_key: &str,
+        first_block_index: u64,
+        last_block_index: u64,
+        subaddress_indices: Range<u64>,
+    ) -> Result<LedgerScan, LedgerScanServiceError> {
+        let view_private_key =
+            hex_to_ristretto(view_private_key).map_err(LedgerScanServiceError::InvalidKey)?;
+        let spend_public_key = hex_to_ristretto_public(spend_public_key)
+            .map_err(LedgerScanServiceError::InvalidKey)?;
+        let view_account_key = ViewAccountKey::new(view_private_key, spend_public_key);
+
+        let num_blocks = self.ledger_db.num_blocks()?;
+        if first_block_index > last_block_index || last_block_index >= num_blocks {
+            return Err(LedgerScanServiceError::InvalidBlockRange(format!(
+                "blocks {} to {} are not in the ledger, which has {} blocks",
+                first_block_index, last_block_index, num_blocks
+            )));
+        }
+        if last_block_index - first_block_index >= MAX_LEDGER_SCAN_BLOCKS {
+            return Err(LedgerScanServiceError::InvalidBlockRange(format!(
+                "at most {} blocks can be scanned at once",
+                MAX_LEDGER_SCAN_BLOCKS
+            )));
+        }
+        let num_subaddresses = subaddress_indices
+            .end
+            .saturating_sub(subaddress_indices.start);
+        if num_subaddresses > MAX_LEDGER_SCAN_SUBADDRESSES {
+            return Err(LedgerScanServiceError::TooManySubaddresses(
+                num_subaddresses,
+                MAX_LEDGER_SCAN_SUBADDRESSES,
+            ));
+        }
+
+        let subaddress_keys: HashMap<RistrettoPublic, u64> = subaddress_indices
+            .map(|index| {
+                (
+                    *view_account_key.subaddress(index).spend_public_key(),
+                    index,
+                )
+            })
+            .collect();
+
+        let mut id_bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id_bytes);
+        let id = hex::encode(id_bytes);
+
+        let job = Arc::new(LedgerScanJob::new(
+            id.clone(),
+            first_block_index,
+            last_block_index,
+        ));
+        let scan = job.scan();
+
+        let thread_job = job.clone();
+        let ledger_db = self.ledger_db.clone();
+        let logger = self.logger.clone();
+        thread::Builder::new()
+            .name("ledger_scan".to_string())
+            .spawn(move || {
+                thread_job.run(&ledger_db, &view_account_key, &subaddress_keys);
+                let scan = thread_job.scan();
+                log::info!(
+                    logger,
+                    "Ledger scan {} {}, found {} txos",
+                    scan.id,
+                    scan.status,
+                    scan.txos.len()
+                );
+            })?;
+
+        self.ledger_scans
+            .lock()
+            .expect("lock poisoned")
+            .insert(id, job);
+
+        Ok(scan)
+    }
+
+    fn get_ledger_scan(&self, ledger_scan_id: &str) -> Result<LedgerScan, LedgerScanServiceError> {
+        let job = self
+            .ledger_scans
+            .lock()
+            .expect("lock poisoned")
+            .get(&ledger_scan_id.to_string())
+            .ok_or_else(|| LedgerScanServiceError::ScanNotFound(ledger_scan_id.to_string()))?;
+
+        Ok(job.scan())
+    }
+
+    fn cancel_ledger_scan(
+        &self,
+        ledger_scan_id: &str,
+    ) -> Result<LedgerScan, LedgerScanServiceError> {
+        let job = self
+            .ledger_scans
+            .lock()
+            .expect("lock poisoned")
+            .get(&ledger_scan_id.to_string())
+            .ok_or_else(|| LedgerScanServiceError::ScanNotFound(ledger_scan_id.to_string()))?;
+
+        job.cancel();
+        Ok(job.scan())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::schema::{accounts, txos},
+        test_utils::{add_block_to_ledger_db, get_test_ledger, setup_wallet_service, MOB},
+        util::encoding_helpers::{ristretto_public_to_hex, ristretto_to_hex},
+    };
+    use diesel::{dsl::count_star, prelude::*};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_keys::RistrettoPrivate;
+    use mc_transaction_core::ring_signature::KeyImage;
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::time::Duration;
+
+    #[test_with_logger]
+    fn test_scan_ledger_with_view_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &vec![], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let view_private_key = RistrettoPrivate::from_random(&mut rng);
+        let spend_public_key = RistrettoPublic::from_random(&mut rng);
+        let view_account_key = ViewAccountKey::new(view_private_key, spend_public_key);
+
+        // Deposits to the main address, to a subaddress within the scanned
+        // range, to someone else, and to a subaddress past the scanned range.
+        let first_block_index = ledger_db.num_blocks().unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![view_account_key.default_subaddress()],
+            10 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![view_account_key.subaddress(5)],
+            20 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let other_view_account_key = ViewAccountKey::new(
+            RistrettoPrivate::from_random(&mut rng),
+            RistrettoPublic::from_random(&mut rng),
+        );
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![other_view_account_key.default_subaddress()],
+            40 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![view_account_key.subaddress(50)],
+            30 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let last_block_index = ledger_db.num_blocks().unwrap() - 1;
+
+        let scan = service
+            .scan_ledger_with_view_key(
+                &ristretto_to_hex(&view_private_key),
+                &ristretto_public_to_hex(&spend_public_key),
+                first_block_index,
+                last_block_index,
+                0..10,
+            )
+            .unwrap();
+
+        let mut scan = service.get_ledger_scan(&scan.id).unwrap();
+        for _ in 0..100 {
+            if scan.status != LedgerScanStatus::Running {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+            scan = service.get_ledger_scan(&scan.id).unwrap();
+        }
+
+        assert_eq!(scan.status, LedgerScanStatus::Completed);
+        assert_eq!(scan.next_block_index, last_block_index + 1);
+        let found: Vec<(u64, u64, Option<u64>)> = scan
+            .txos
+            .iter()
+            .map(|txo| (txo.block_index, txo.amount.value, txo.subaddress_index))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (first_block_index, 10 * MOB, Some(0)),
+                (first_block_index + 1, 20 * MOB, Some(5)),
+                (first_block_index + 3, 30 * MOB, None),
+            ]
+        );
+
+        // Nothing was written to the wallet.
+        let conn = service.wallet_db.get_conn().unwrap();
+        assert_eq!(
+            Ok(0),
+            accounts::table.select(count_star()).first::<i64>(&conn)
+        );
+        assert_eq!(Ok(0), txos::table.select(count_star()).first::<i64>(&conn));
+    }
+
+    #[test_with_logger]
+    fn test_scan_ledger_with_view_key_limits(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &vec![], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let view_private_key = RistrettoPrivate::from_random(&mut rng);
+        let spend_public_key = RistrettoPublic::from_random(&mut rng);
+        let view_private_key_hex = ristretto_to_hex(&view_private_key);
+        let spend_public_key_hex = ristretto_public_to_hex(&spend_public_key);
+
+        // Blocks past the end of the ledger cannot be scanned.
+        match service.scan_ledger_with_view_key(
+            &view_private_key_hex,
+            &spend_public_key_hex,
+            0,
+            12,
+            0..10,
+        ) {
+            Err(LedgerScanServiceError::InvalidBlockRange(_)) => {}
+            _ => panic!("expected an invalid block range"),
+        }
+
+        match service.scan_ledger_with_view_key(
+            &view_private_key_hex,
+            &spend_public_key_hex,
+            0,
+            11,
+            0..MAX_LEDGER_SCAN_SUBADDRESSES + 1,
+        ) {
+            Err(LedgerScanServiceError::TooManySubaddresses(_, _)) => {}
+            _ => panic!("expected too many subaddresses"),
+        }
+
+        match service.get_ledger_scan("missing") {
+            Err(LedgerScanServiceError::ScanNotFound(_)) => {}
+            _ => panic!("expected the scan not to be found"),
+        }
+
+        // A cancelled scan stops before its next block.
+        let view_account_key = ViewAccountKey::new(view_private_key, spend_public_key);
+        let job = LedgerScanJob::new("cancelled".to_string(), 0, 11);
+        job.cancel();
+        job.run(&ledger_db, &view_account_key, &HashMap::default());
+
+        let scan = job.scan();
+        assert_eq!(scan.status, LedgerScanStatus::Cancelled);
+        assert_eq!(scan.next_block_index, 0);
+        assert!(scan.txos.is_empty());
+    }
+}
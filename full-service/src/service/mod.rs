@@ -3,19 +3,32 @@
 //! Implementations of services.
 
 pub mod account;
+pub mod account_backup;
+pub mod account_migration;
 pub mod address;
 pub mod balance;
+pub mod build_report;
+pub mod confirmation;
 pub mod confirmation_number;
+pub mod database;
 pub mod gift_code;
+pub mod health;
+pub mod job;
 pub mod ledger;
+pub mod ledger_db;
+pub mod ledger_scan;
 pub mod models;
 pub mod payment_request;
+pub mod peer;
+pub mod proof_of_reserves;
 pub mod receipt;
 pub mod sync;
 pub mod transaction;
 pub mod transaction_builder;
 pub mod transaction_log;
+pub mod transaction_report;
 pub mod txo;
+pub mod view_only_sync;
 mod wallet_service;
 
-pub use wallet_service::WalletService;
+pub use wallet_service::{WalletService, WalletServiceConfig};
@@ -1,14 +1,24 @@
-use std::convert::{TryFrom, TryInto};
+use std::{
+    collections::HashSet,
+    convert::{TryFrom, TryInto},
+};
 
-use mc_account_keys::PublicAddress;
+use mc_account_keys::{burn_address, burn_address_view_private, PublicAddress};
+use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
 use mc_transaction_core::{
+    get_tx_out_shared_secret,
+    onetime_keys::recover_public_subaddress_spend_key,
     ring_signature::KeyImage,
     tokens::Mob,
     tx::{Tx, TxOut, TxOutConfirmationNumber},
-    Amount, Token,
+    Amount, BlockVersion, Token,
 };
+use mc_transaction_std::{BurnRedemptionMemo, MemoType};
 
-use crate::util::b58::b58_decode_public_address;
+use crate::util::{
+    b58::b58_decode_public_address,
+    field_context::{decode_hex_array, FieldContext},
+};
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct InputTxo {
@@ -32,17 +42,39 @@ pub struct TxProposal {
     pub input_txos: Vec<InputTxo>,
     pub payload_txos: Vec<OutputTxo>,
     pub change_txos: Vec<OutputTxo>,
+    /// The block version the transaction was built for. Unknown for
+    /// proposals from older clients, which do not record it.
+    pub block_version: Option<BlockVersion>,
 }
 
-impl TryFrom<&crate::json_rpc::v1::models::tx_proposal::TxProposal> for TxProposal {
-    type Error = String;
+/// The keys of the sending account which identify the change outputs of a v1
+/// TxProposal, since the v1 model does not list them.
+#[derive(Clone)]
+pub struct ChangeKeys {
+    pub view_private_key: RistrettoPrivate,
+    pub change_public_address: PublicAddress,
+}
 
-    fn try_from(
+impl TxProposal {
+    /// Convert a v1 TxProposal. Outputs which are not paid to an outlay are
+    /// recovered as change when they belong to the change subaddress of the
+    /// given keys; without keys, no change is recovered.
+    pub fn try_from_v1(
         src: &crate::json_rpc::v1::models::tx_proposal::TxProposal,
-    ) -> Result<Self, Self::Error> {
+        change_keys: Option<&ChangeKeys>,
+    ) -> Result<Self, String> {
         let mc_api_tx = mc_api::external::Tx::try_from(&src.tx)?;
         let tx = Tx::try_from(&mc_api_tx).map_err(|e| e.to_string())?;
 
+        // The v1 model has no token ids, and its amounts are taken to be MOB.
+        // The fee token is the only token recorded, and must agree.
+        if tx.prefix.fee_token_id != *Mob::ID {
+            return Err(format!(
+                "v1 tx proposal pays its fee in token {}, but only MOB is supported",
+                tx.prefix.fee_token_id
+            ));
+        }
+
         let input_txos = src
             .input_list
             .iter()
@@ -68,12 +100,19 @@ impl TryFrom<&crate::json_rpc::v1::models::tx_proposal::TxProposal> for TxPropos
             .collect::<Result<Vec<_>, String>>()?;
 
         let mut payload_txos = Vec::new();
+        let mut payload_tx_out_indices = HashSet::new();
 
         for (outlay_index, tx_out_index) in src.outlay_index_to_tx_out_index.iter() {
             let outlay_index = outlay_index.parse::<usize>().map_err(|e| e.to_string())?;
             let outlay = &src.outlay_list[outlay_index];
             let tx_out_index = tx_out_index.parse::<usize>().map_err(|e| e.to_string())?;
-            let tx_out = tx.prefix.outputs[tx_out_index].clone();
+            let tx_out = tx
+                .prefix
+                .outputs
+                .get(tx_out_index)
+                .ok_or_else(|| format!("tx_out index {} out of range", tx_out_index))?
+                .clone();
+            payload_tx_out_indices.insert(tx_out_index);
             let confirmation_number_bytes: &[u8; 32] = src.outlay_confirmation_numbers
                 [outlay_index]
                 .as_slice()
@@ -100,13 +139,76 @@ impl TryFrom<&crate::json_rpc::v1::models::tx_proposal::TxProposal> for TxPropos
             payload_txos.push(payload_txo);
         }
 
+        let mut change_txos = Vec::new();
+
+        if let Some(change_keys) = change_keys {
+            for (tx_out_index, tx_out) in tx.prefix.outputs.iter().enumerate() {
+                if payload_tx_out_indices.contains(&tx_out_index) {
+                    continue;
+                }
+                if let Some(change_txo) = decode_change_txo(tx_out, change_keys)? {
+                    change_txos.push(change_txo);
+                }
+            }
+        }
+
         Ok(Self {
             tx,
             input_txos,
             payload_txos,
-            change_txos: Vec::new(),
+            change_txos,
+            block_version: None,
         })
     }
+
+    /// The redemption data of the burn redemption memo on the proposal's
+    /// output to the burn address, if it has one. The burn address's view key
+    /// is public, so the memo can be read from a proposal signed elsewhere.
+    pub fn burn_redemption_memo(&self) -> Option<[u8; BurnRedemptionMemo::MEMO_DATA_LEN]> {
+        let burn_address = burn_address();
+        let burn_txo = self
+            .payload_txos
+            .iter()
+            .find(|txo| txo.recipient_public_address == burn_address)?;
+        let tx_public_key = RistrettoPublic::try_from(&burn_txo.tx_out.public_key).ok()?;
+        let shared_secret = get_tx_out_shared_secret(&burn_address_view_private(), &tx_public_key);
+        match MemoType::try_from(&burn_txo.tx_out.decrypt_memo(&shared_secret)) {
+            Ok(MemoType::BurnRedemption(memo)) => Some(*memo.memo_data()),
+            _ => None,
+        }
+    }
+}
+
+/// Decode an output as change, if it was sent to the change subaddress of the
+/// given keys.
+fn decode_change_txo(
+    tx_out: &TxOut,
+    change_keys: &ChangeKeys,
+) -> Result<Option<OutputTxo>, String> {
+    let tx_public_key = RistrettoPublic::try_from(&tx_out.public_key).map_err(|e| e.to_string())?;
+    let tx_target_key = RistrettoPublic::try_from(&tx_out.target_key).map_err(|e| e.to_string())?;
+
+    let subaddress_spend_public_key = recover_public_subaddress_spend_key(
+        &change_keys.view_private_key,
+        &tx_target_key,
+        &tx_public_key,
+    );
+    if &subaddress_spend_public_key != change_keys.change_public_address.spend_public_key() {
+        return Ok(None);
+    }
+
+    let shared_secret = get_tx_out_shared_secret(&change_keys.view_private_key, &tx_public_key);
+    let (amount, _blinding) = tx_out
+        .masked_amount
+        .get_value(&shared_secret)
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(OutputTxo {
+        tx_out: tx_out.clone(),
+        recipient_public_address: change_keys.change_public_address.clone(),
+        confirmation_number: TxOutConfirmationNumber::from(&shared_secret),
+        amount,
+    }))
 }
 
 impl TryFrom<&crate::json_rpc::v2::models::tx_proposal::TxProposal> for TxProposal {
@@ -115,97 +217,213 @@ impl TryFrom<&crate::json_rpc::v2::models::tx_proposal::TxProposal> for TxPropos
     fn try_from(
         src: &crate::json_rpc::v2::models::tx_proposal::TxProposal,
     ) -> Result<Self, Self::Error> {
-        let tx_bytes = hex::decode(&src.tx_proto).map_err(|e| e.to_string())?;
-        let tx = mc_util_serial::decode(tx_bytes.as_slice()).map_err(|e| e.to_string())?;
+        let tx_bytes = hex::decode(&src.tx_proto).field_context(|| "tx_proto")?;
+        let tx = mc_util_serial::decode(tx_bytes.as_slice()).field_context(|| "tx_proto")?;
+
         let input_txos = src
             .input_txos
             .iter()
-            .map(|input_txo| {
-                let key_image_bytes =
-                    hex::decode(&input_txo.key_image).map_err(|e| e.to_string())?;
+            .enumerate()
+            .map(|(i, input_txo)| {
+                let field = |name: &str| format!("input_txos[{}].{}", i, name);
+
+                let tx_out_bytes =
+                    hex::decode(&input_txo.tx_out_proto).field_context(|| field("tx_out_proto"))?;
+                let key_image_bytes = decode_hex_array::<32>(&input_txo.key_image)
+                    .field_context(|| field("key_image"))?;
+
                 Ok(InputTxo {
-                    tx_out: mc_util_serial::decode(
-                        hex::decode(&input_txo.tx_out_proto)
-                            .map_err(|e| e.to_string())?
-                            .as_slice(),
-                    )
-                    .map_err(|e| e.to_string())?,
+                    tx_out: mc_util_serial::decode(tx_out_bytes.as_slice())
+                        .field_context(|| field("tx_out_proto"))?,
                     subaddress_index: input_txo
                         .subaddress_index
                         .parse::<u64>()
-                        .map_err(|e| e.to_string())?,
-                    key_image: KeyImage::try_from(key_image_bytes.as_slice())
-                        .map_err(|e| e.to_string())?,
-                    amount: Amount::try_from(&input_txo.amount)?,
+                        .field_context(|| field("subaddress_index"))?,
+                    key_image: KeyImage::try_from(&key_image_bytes[..])
+                        .field_context(|| field("key_image"))?,
+                    amount: Amount::try_from(&input_txo.amount)
+                        .field_context(|| field("amount"))?,
                 })
             })
             .collect::<Result<Vec<_>, String>>()?;
 
-        let mut payload_txos = Vec::new();
-
-        for txo in src.payload_txos.iter() {
-            let confirmation_number_hex =
-                hex::decode(&txo.confirmation_number).map_err(|e| format!("{}", e))?;
-            let confirmation_number_bytes: [u8; 32] =
-                confirmation_number_hex.as_slice().try_into().map_err(|_| {
-                    "confirmation number is not the right number of bytes (expecting 32)"
-                })?;
-            let confirmation_number = TxOutConfirmationNumber::from(confirmation_number_bytes);
-
-            let txo_out_hex = hex::decode(&txo.tx_out_proto).map_err(|e| e.to_string())?;
-            let tx_out =
-                mc_util_serial::decode(txo_out_hex.as_slice()).map_err(|e| e.to_string())?;
-            let recipient_public_address =
-                b58_decode_public_address(&txo.recipient_public_address_b58)
-                    .map_err(|e| e.to_string())?;
-
-            let amount = Amount::try_from(&txo.amount)?;
-
-            let output_txo = OutputTxo {
-                tx_out,
-                recipient_public_address,
-                confirmation_number,
-                amount,
-            };
-
-            payload_txos.push(output_txo);
-        }
-
-        let mut change_txos = Vec::new();
-
-        for txo in src.change_txos.iter() {
-            let confirmation_number_hex =
-                hex::decode(&txo.confirmation_number).map_err(|e| format!("{}", e))?;
-            let confirmation_number_bytes: [u8; 32] =
-                confirmation_number_hex.as_slice().try_into().map_err(|_| {
-                    "confirmation number is not the right number of bytes (expecting 32)"
-                })?;
-            let confirmation_number = TxOutConfirmationNumber::from(confirmation_number_bytes);
-
-            let txo_out_hex = hex::decode(&txo.tx_out_proto).map_err(|e| e.to_string())?;
-            let tx_out =
-                mc_util_serial::decode(txo_out_hex.as_slice()).map_err(|e| e.to_string())?;
-            let recipient_public_address =
-                b58_decode_public_address(&txo.recipient_public_address_b58)
-                    .map_err(|e| e.to_string())?;
-
-            let amount = Amount::try_from(&txo.amount)?;
+        let payload_txos = src
+            .payload_txos
+            .iter()
+            .enumerate()
+            .map(|(i, txo)| output_txo_from_json(txo, &format!("payload_txos[{}]", i)))
+            .collect::<Result<Vec<_>, String>>()?;
 
-            let output_txo = OutputTxo {
-                tx_out,
-                recipient_public_address,
-                confirmation_number,
-                amount,
-            };
+        let change_txos = src
+            .change_txos
+            .iter()
+            .enumerate()
+            .map(|(i, txo)| output_txo_from_json(txo, &format!("change_txos[{}]", i)))
+            .collect::<Result<Vec<_>, String>>()?;
 
-            change_txos.push(output_txo);
-        }
+        let block_version = src
+            .block_version
+            .as_ref()
+            .map(|block_version| {
+                let block_version = block_version.parse::<u32>().map_err(|e| e.to_string())?;
+                BlockVersion::try_from(block_version).map_err(|e| e.to_string())
+            })
+            .transpose()
+            .field_context(|| "block_version")?;
 
         Ok(Self {
             tx,
             input_txos,
             payload_txos,
             change_txos,
+            block_version,
+        })
+    }
+}
+
+/// Convert an output of a v2 TxProposal, naming the failing field of the
+/// output at the given path in any error.
+fn output_txo_from_json(
+    txo: &crate::json_rpc::v2::models::tx_proposal::OutputTxo,
+    path: &str,
+) -> Result<OutputTxo, String> {
+    let field = |name: &str| format!("{}.{}", path, name);
+
+    let confirmation_number_bytes = decode_hex_array::<32>(&txo.confirmation_number)
+        .field_context(|| field("confirmation_number"))?;
+    let tx_out_bytes = hex::decode(&txo.tx_out_proto).field_context(|| field("tx_out_proto"))?;
+
+    Ok(OutputTxo {
+        tx_out: mc_util_serial::decode(tx_out_bytes.as_slice())
+            .field_context(|| field("tx_out_proto"))?,
+        recipient_public_address: b58_decode_public_address(&txo.recipient_public_address_b58)
+            .field_context(|| field("recipient_public_address_b58"))?,
+        confirmation_number: TxOutConfirmationNumber::from(confirmation_number_bytes),
+        amount: Amount::try_from(&txo.amount).field_context(|| field("amount"))?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::account::AccountID,
+        json_rpc::v2::models::{
+            amount::Amount as AmountJSON, tx_proposal::TxProposal as TxProposalJSON,
+        },
+        service::{
+            account::AccountService,
+            transaction::{TransactionMemo, TransactionService},
+        },
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+        util::b58::b58_encode_public_address,
+    };
+    use mc_account_keys::AccountKey;
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_tx_proposal_conversion_errors_name_the_field(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let addresses_and_amounts: Vec<(String, AmountJSON)> = (0..3)
+            .map(|_| {
+                (
+                    b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress())
+                        .unwrap(),
+                    AmountJSON::new(10 * MOB, Mob::ID),
+                )
+            })
+            .collect();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &addresses_and_amounts,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.payload_txos.len(), 3);
+
+        let to_json = || TxProposalJSON::try_from(&tx_proposal).unwrap();
+        assert_eq!(TxProposal::try_from(&to_json()).unwrap(), tx_proposal);
+
+        let error_for = |corrupt: &dyn Fn(&mut TxProposalJSON)| {
+            let mut json = to_json();
+            corrupt(&mut json);
+            TxProposal::try_from(&json).unwrap_err()
+        };
+
+        assert_eq!(
+            error_for(&|json| json.tx_proto.push('0')),
+            "tx_proto: Odd number of digits"
+        );
+        assert_eq!(
+            error_for(&|json| json.input_txos[0].key_image = "zz".repeat(32)),
+            "input_txos[0].key_image: Invalid character 'z' at position 0"
+        );
+        assert_eq!(
+            error_for(&|json| json.input_txos[0].subaddress_index = "-1".to_string()),
+            "input_txos[0].subaddress_index: invalid digit found in string"
+        );
+        assert_eq!(
+            error_for(&|json| {
+                json.payload_txos[2].confirmation_number.truncate(62);
+            }),
+            "payload_txos[2].confirmation_number: expected 64 hex chars, got 62"
+        );
+        assert_eq!(
+            error_for(&|json| json.payload_txos[1].tx_out_proto.truncate(10)),
+            format!(
+                "payload_txos[1].tx_out_proto: {}",
+                mc_util_serial::decode::<TxOut>(
+                    &hex::decode(&to_json().payload_txos[1].tx_out_proto[..10]).unwrap()
+                )
+                .unwrap_err()
+            )
+        );
+        assert!(error_for(&|json| {
+            json.payload_txos[0].recipient_public_address_b58 = "not b58".to_string()
         })
+        .starts_with("payload_txos[0].recipient_public_address_b58: "));
+        assert_eq!(
+            error_for(&|json| json.change_txos[0].amount.value = "ten".to_string()),
+            "change_txos[0].amount: Could not parse value u64: ParseIntError { kind: InvalidDigit }"
+        );
     }
 }
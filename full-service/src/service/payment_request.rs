@@ -5,7 +5,7 @@
 use crate::{
     db::{assigned_subaddress::AssignedSubaddressModel, models::AssignedSubaddress, WalletDbError},
     service::WalletService,
-    util::b58::{b58_decode_public_address, b58_encode_payment_request, B58Error},
+    util::b58::{create_payment_request, B58Error},
 };
 use mc_connection::{BlockchainConnection, UserTxConnection};
 use mc_fog_report_validation::FogPubkeyResolver;
@@ -108,12 +108,11 @@ where
             &conn,
         )?;
 
-        let public_address = b58_decode_public_address(&assigned_subaddress.public_address_b58)?;
-
-        let payment_request_b58 = b58_encode_payment_request(
-            &public_address,
-            &amount,
-            memo.unwrap_or_else(|| "".to_string()),
+        let payment_request_b58 = create_payment_request(
+            &assigned_subaddress.public_address_b58,
+            amount.value,
+            *amount.token_id,
+            memo.as_deref().unwrap_or_default(),
         )?;
 
         Ok(payment_request_b58)
@@ -0,0 +1,607 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for tracking the health of the consensus peers, from the latency
+//! and outcome of the wallet's calls to them, and choosing which peer
+//! transactions are submitted to.
+
+use crate::{
+    db::idempotency_key::unix_timestamp_now,
+    util::constants::{
+        PEER_LATENCY_SAMPLE_WEIGHT_PERCENT, PEER_SELECTION_JITTER_PERCENT,
+        PEER_UNHEALTHY_CONSECUTIVE_FAILURES, PEER_UNHEALTHY_RETRY_SECONDS,
+    },
+    WalletService,
+};
+use displaydoc::Display;
+use mc_common::ResponderId;
+use mc_connection::{BlockchainConnection, SyncConnection, UserTxConnection};
+use mc_fog_report_validation::FogPubkeyResolver;
+use rand::Rng;
+use rayon::prelude::*; // For par_iter
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How the peer a transaction is submitted to is chosen.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PeerSelectionStrategy {
+    /// The healthy peer with the lowest latency and fewest failures, with
+    /// some jitter so that peers which are nearly as good share the load.
+    Healthiest,
+
+    /// Each peer in turn.
+    RoundRobin,
+}
+
+impl Default for PeerSelectionStrategy {
+    fn default() -> Self {
+        Self::Healthiest
+    }
+}
+
+impl FromStr for PeerSelectionStrategy {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, String> {
+        match src {
+            "healthiest" => Ok(Self::Healthiest),
+            "round-robin" => Ok(Self::RoundRobin),
+            _ => Err(format!(
+                "Unknown peer selection strategy {:?}, expected \"healthiest\" or \"round-robin\"",
+                src
+            )),
+        }
+    }
+}
+
+impl fmt::Display for PeerSelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Healthiest => write!(f, "healthiest"),
+            Self::RoundRobin => write!(f, "round-robin"),
+        }
+    }
+}
+
+/// The outcome of the wallet's calls to a peer since the wallet started.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerStats {
+    /// Number of calls which succeeded.
+    pub num_successes: u64,
+
+    /// Number of calls which failed.
+    pub num_failures: u64,
+
+    /// Number of calls which failed since the last one which succeeded.
+    pub consecutive_failures: u64,
+
+    /// Moving average of the latency of the calls, weighted towards the most
+    /// recent.
+    pub average_latency: Option<Duration>,
+
+    /// When a call last succeeded, in seconds since the Unix epoch.
+    pub last_success_at: Option<i64>,
+
+    /// When a call last failed, in seconds since the Unix epoch.
+    pub last_failure_at: Option<i64>,
+
+    /// The error of the last call which failed.
+    pub last_error: Option<String>,
+
+    /// Whether the peer was excluded by an admin, so that it is not used.
+    pub excluded: bool,
+
+    /// When a call last failed, to retry unhealthy peers after a while.
+    last_failure: Option<Instant>,
+}
+
+impl PeerStats {
+    /// The fraction of calls which succeeded, if any were made.
+    pub fn success_rate(&self) -> Option<f64> {
+        let num_calls = self.num_successes + self.num_failures;
+        if num_calls == 0 {
+            None
+        } else {
+            Some(self.num_successes as f64 / num_calls as f64)
+        }
+    }
+
+    /// Whether the peer is used. A peer which failed too many times in a row
+    /// is unhealthy until it has been left alone for a while, after which it
+    /// is tried again.
+    pub fn is_healthy(&self) -> bool {
+        !self.excluded
+            && (self.consecutive_failures < PEER_UNHEALTHY_CONSECUTIVE_FAILURES
+                || self.last_failure.map_or(true, |last_failure| {
+                    last_failure.elapsed() >= Duration::from_secs(PEER_UNHEALTHY_RETRY_SECONDS)
+                }))
+    }
+
+    /// Lower is better. Peers which have not been called yet score best, so
+    /// that they are tried.
+    fn score(&self) -> f64 {
+        let latency = self
+            .average_latency
+            .map_or(0.0, |latency| latency.as_secs_f64());
+        latency / self.success_rate().unwrap_or(1.0).max(0.01)
+    }
+
+    fn record(&mut self, latency: Duration, result: Result<(), String>) {
+        self.average_latency = Some(match self.average_latency {
+            Some(average) => {
+                (average * (100 - PEER_LATENCY_SAMPLE_WEIGHT_PERCENT)
+                    + latency * PEER_LATENCY_SAMPLE_WEIGHT_PERCENT)
+                    / 100
+            }
+            None => latency,
+        });
+        match result {
+            Ok(()) => {
+                self.num_successes += 1;
+                self.consecutive_failures = 0;
+                self.last_success_at = Some(unix_timestamp_now());
+            }
+            Err(error) => {
+                self.num_failures += 1;
+                self.consecutive_failures += 1;
+                self.last_failure_at = Some(unix_timestamp_now());
+                self.last_failure = Some(Instant::now());
+                self.last_error = Some(error);
+            }
+        }
+    }
+}
+
+/// Health of each peer, shared by every call to the peers, and the admin's
+/// choice of peers.
+#[derive(Debug, Default)]
+pub struct PeerHealth {
+    strategy: PeerSelectionStrategy,
+    peers: Mutex<BTreeMap<String, PeerStats>>,
+    pinned: Mutex<Option<String>>,
+}
+
+impl PeerHealth {
+    pub fn new(strategy: PeerSelectionStrategy) -> Self {
+        Self {
+            strategy,
+            ..Default::default()
+        }
+    }
+
+    pub fn strategy(&self) -> PeerSelectionStrategy {
+        self.strategy
+    }
+
+    /// Record the latency and outcome of a call to a peer.
+    pub fn record(
+        &self,
+        responder_id: &ResponderId,
+        latency: Duration,
+        result: Result<(), String>,
+    ) {
+        self.peers
+            .lock()
+            .expect("PeerHealth mutex poisoned")
+            .entry(responder_id.to_string())
+            .or_default()
+            .record(latency, result);
+    }
+
+    /// The stats of a peer. Peers which have not been called have no calls
+    /// recorded.
+    pub fn get(&self, responder_id: &ResponderId) -> PeerStats {
+        self.peers
+            .lock()
+            .expect("PeerHealth mutex poisoned")
+            .get(&responder_id.to_string())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// The peer every submission is tried on first, if one is pinned.
+    pub fn pinned(&self) -> Option<String> {
+        self.pinned
+            .lock()
+            .expect("PeerHealth mutex poisoned")
+            .clone()
+    }
+
+    /// Make a call to a peer, recording the latency and outcome of the call.
+    pub fn call<R, E: fmt::Display>(
+        &self,
+        responder_id: &ResponderId,
+        call: impl FnOnce() -> Result<R, E>,
+    ) -> Result<R, E> {
+        let start = Instant::now();
+        let result = call();
+        self.record(
+            responder_id,
+            start.elapsed(),
+            result.as_ref().map(|_| ()).map_err(|err| err.to_string()),
+        );
+        result
+    }
+
+    /// Pin a peer, so that submissions are tried on it first, or unpin it.
+    pub fn set_pinned(&self, responder_id: Option<&ResponderId>) {
+        *self.pinned.lock().expect("PeerHealth mutex poisoned") =
+            responder_id.map(|responder_id| responder_id.to_string());
+    }
+
+    /// Exclude a peer, so that it is not used until it is included again.
+    pub fn set_excluded(&self, responder_id: &ResponderId, excluded: bool) {
+        self.peers
+            .lock()
+            .expect("PeerHealth mutex poisoned")
+            .entry(responder_id.to_string())
+            .or_default()
+            .excluded = excluded;
+    }
+
+    /// The peers to ask for the network's fees and block version: the
+    /// healthy ones, or every peer which is not excluded if none is healthy.
+    pub fn aggregation_peers(&self, responder_ids: &[ResponderId]) -> Vec<ResponderId> {
+        let included: Vec<(ResponderId, PeerStats)> = responder_ids
+            .iter()
+            .map(|responder_id| (responder_id.clone(), self.get(responder_id)))
+            .filter(|(_, stats)| !stats.excluded)
+            .collect();
+        let healthy: Vec<ResponderId> = included
+            .iter()
+            .filter(|(_, stats)| stats.is_healthy())
+            .map(|(responder_id, _)| responder_id.clone())
+            .collect();
+        if healthy.is_empty() {
+            included
+                .into_iter()
+                .map(|(responder_id, _)| responder_id)
+                .collect()
+        } else {
+            healthy
+        }
+    }
+
+    /// The order in which to try the peers for a submission. The pinned peer
+    /// comes first, and excluded peers are left out. With the healthiest
+    /// strategy, healthy peers follow by score, and unhealthy ones after
+    /// them. With round robin, the peers follow in turn from the offset.
+    pub fn submission_order<R: Rng>(
+        &self,
+        responder_ids: &[ResponderId],
+        offset: usize,
+        rng: &mut R,
+    ) -> Vec<ResponderId> {
+        let mut peers: Vec<(ResponderId, PeerStats)> = responder_ids
+            .iter()
+            .map(|responder_id| (responder_id.clone(), self.get(responder_id)))
+            .filter(|(_, stats)| !stats.excluded)
+            .collect();
+        if peers.is_empty() {
+            return Vec::new();
+        }
+
+        match self.strategy {
+            PeerSelectionStrategy::RoundRobin => {
+                let len = peers.len();
+                peers.rotate_left(offset % len);
+            }
+            PeerSelectionStrategy::Healthiest => {
+                let mut scored: Vec<(bool, f64, ResponderId, PeerStats)> = peers
+                    .into_iter()
+                    .map(|(responder_id, stats)| {
+                        let jitter =
+                            1.0 + rng.gen_range(0.0..PEER_SELECTION_JITTER_PERCENT as f64 / 100.0);
+                        (
+                            !stats.is_healthy(),
+                            stats.score() * jitter,
+                            responder_id,
+                            stats,
+                        )
+                    })
+                    .collect();
+                scored.sort_by(|a, b| {
+                    a.0.cmp(&b.0)
+                        .then(a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                });
+                peers = scored
+                    .into_iter()
+                    .map(|(_, _, responder_id, stats)| (responder_id, stats))
+                    .collect();
+            }
+        }
+
+        if let Some(pinned) = self.pinned() {
+            if let Some(index) = peers
+                .iter()
+                .position(|(responder_id, _)| responder_id.to_string() == pinned)
+            {
+                let pinned_peer = peers.remove(index);
+                peers.insert(0, pinned_peer);
+            }
+        }
+
+        peers
+            .into_iter()
+            .map(|(responder_id, _)| responder_id)
+            .collect()
+    }
+}
+
+/// The health of every configured peer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PeerStatus {
+    /// How submissions choose a peer.
+    pub strategy: PeerSelectionStrategy,
+
+    /// The peer submissions are tried on first, if one is pinned.
+    pub pinned_responder_id: Option<String>,
+
+    /// The stats and health of each peer, by responder id.
+    pub peers: Vec<(String, PeerStats, bool)>,
+}
+
+#[derive(Display, Debug)]
+pub enum PeerServiceError {
+    /// No peer is configured with responder id: {0}
+    UnknownPeer(String),
+}
+
+/// Trait defining the ways in which the wallet can report on and choose the
+/// consensus peers.
+pub trait PeerService {
+    /// Get the stats and health of every peer.
+    fn get_peer_status(&self) -> PeerStatus;
+
+    /// Pin a peer, so that submissions are tried on it first, or unpin the
+    /// pinned peer.
+    fn pin_peer(&self, responder_id: Option<&str>) -> Result<PeerStatus, PeerServiceError>;
+
+    /// Exclude a peer, so that it is not used until it is included again, or
+    /// include it again.
+    fn exclude_peer(
+        &self,
+        responder_id: &str,
+        excluded: bool,
+    ) -> Result<PeerStatus, PeerServiceError>;
+}
+
+impl<T, FPR> PeerService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn get_peer_status(&self) -> PeerStatus {
+        PeerStatus {
+            strategy: self.peer_health.strategy(),
+            pinned_responder_id: self.peer_health.pinned(),
+            peers: self
+                .peer_manager
+                .responder_ids()
+                .iter()
+                .map(|responder_id| {
+                    let stats = self.peer_health.get(responder_id);
+                    let healthy = stats.is_healthy();
+                    (responder_id.to_string(), stats, healthy)
+                })
+                .collect(),
+        }
+    }
+
+    fn pin_peer(&self, responder_id: Option<&str>) -> Result<PeerStatus, PeerServiceError> {
+        let responder_id = responder_id
+            .map(|responder_id| self.configured_peer(responder_id))
+            .transpose()?;
+        self.peer_health.set_pinned(responder_id.as_ref());
+        Ok(self.get_peer_status())
+    }
+
+    fn exclude_peer(
+        &self,
+        responder_id: &str,
+        excluded: bool,
+    ) -> Result<PeerStatus, PeerServiceError> {
+        let responder_id = self.configured_peer(responder_id)?;
+        self.peer_health.set_excluded(&responder_id, excluded);
+        Ok(self.get_peer_status())
+    }
+}
+
+impl<T, FPR> WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn configured_peer(&self, responder_id: &str) -> Result<ResponderId, PeerServiceError> {
+        self.peer_manager
+            .responder_ids()
+            .into_iter()
+            .find(|configured| configured.to_string() == responder_id)
+            .ok_or_else(|| PeerServiceError::UnknownPeer(responder_id.to_string()))
+    }
+
+    /// The peers to try a submission on, in order.
+    pub fn submission_peers(&self) -> Vec<ResponderId> {
+        let offset = self.submit_node_offset.fetch_add(1, Ordering::SeqCst);
+        self.peer_health.submission_order(
+            &self.peer_manager.responder_ids(),
+            offset,
+            &mut rand::thread_rng(),
+        )
+    }
+
+    /// Make a call to each of the peers which is asked for the state of the
+    /// network, in parallel, returning the results of the calls which
+    /// succeeded.
+    pub fn call_aggregation_peers<R, E>(
+        &self,
+        call: impl Fn(&SyncConnection<T>) -> Result<R, E> + Send + Sync,
+    ) -> Vec<R>
+    where
+        R: Send,
+        E: fmt::Display,
+    {
+        let peer_health = &self.peer_health;
+        let conns: Vec<(ResponderId, SyncConnection<T>)> = peer_health
+            .aggregation_peers(&self.peer_manager.responder_ids())
+            .into_iter()
+            .filter_map(|responder_id| {
+                let conn = self.peer_manager.conn(&responder_id)?;
+                Some((responder_id, conn))
+            })
+            .collect();
+        conns
+            .par_iter()
+            .filter_map(|(responder_id, conn)| peer_health.call(responder_id, || call(conn)).ok())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn peers() -> Vec<ResponderId> {
+        [
+            "a.example.com:443",
+            "b.example.com:443",
+            "c.example.com:443",
+        ]
+        .iter()
+        .map(|peer| ResponderId::from_str(peer).unwrap())
+        .collect()
+    }
+
+    /// Record calls to each peer, with the given latency in milliseconds and
+    /// whether every `n`th call fails.
+    fn record_calls(health: &PeerHealth, peer: &ResponderId, latency_ms: u64, fail_every: u64) {
+        for i in 1..=20 {
+            let result = if fail_every > 0 && i % fail_every == 0 {
+                Err("connection refused".to_string())
+            } else {
+                Ok(())
+            };
+            health.record(peer, Duration::from_millis(latency_ms), result);
+        }
+    }
+
+    #[test]
+    fn test_healthiest_prefers_fast_reliable_peer() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let peers = peers();
+        let health = PeerHealth::new(PeerSelectionStrategy::Healthiest);
+        record_calls(&health, &peers[0], 500, 0);
+        record_calls(&health, &peers[1], 50, 0);
+        record_calls(&health, &peers[2], 40, 2);
+
+        for offset in 0..10 {
+            let order = health.submission_order(&peers, offset, &mut rng);
+            assert_eq!(
+                order,
+                vec![peers[1].clone(), peers[2].clone(), peers[0].clone()]
+            );
+        }
+
+        let stats = health.get(&peers[2]);
+        assert_eq!(stats.num_successes, 10);
+        assert_eq!(stats.num_failures, 10);
+        assert_eq!(stats.success_rate(), Some(0.5));
+        assert_eq!(stats.average_latency, Some(Duration::from_millis(40)));
+        assert_eq!(stats.last_error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn test_jitter_shares_load_between_similar_peers() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let peers = peers();
+        let health = PeerHealth::new(PeerSelectionStrategy::Healthiest);
+        record_calls(&health, &peers[0], 100, 0);
+        record_calls(&health, &peers[1], 101, 0);
+        record_calls(&health, &peers[2], 1000, 0);
+
+        let firsts: Vec<ResponderId> = (0..50)
+            .map(|offset| health.submission_order(&peers, offset, &mut rng)[0].clone())
+            .collect();
+        assert!(firsts.contains(&peers[0]));
+        assert!(firsts.contains(&peers[1]));
+        assert!(!firsts.contains(&peers[2]));
+    }
+
+    #[test]
+    fn test_unhealthy_peers_are_tried_last_and_skipped_for_aggregation() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let peers = peers();
+        let health = PeerHealth::new(PeerSelectionStrategy::Healthiest);
+        record_calls(&health, &peers[1], 100, 0);
+        record_calls(&health, &peers[2], 200, 0);
+        for _ in 0..PEER_UNHEALTHY_CONSECUTIVE_FAILURES {
+            health.record(
+                &peers[0],
+                Duration::from_millis(1),
+                Err("timeout".to_string()),
+            );
+        }
+
+        assert!(!health.get(&peers[0]).is_healthy());
+        let order = health.submission_order(&peers, 0, &mut rng);
+        assert_eq!(order.last(), Some(&peers[0]));
+        assert_eq!(health.aggregation_peers(&peers), peers[1..].to_vec());
+
+        // A success makes the peer healthy again.
+        health.record(&peers[0], Duration::from_millis(1), Ok(()));
+        assert!(health.get(&peers[0]).is_healthy());
+        assert_eq!(health.aggregation_peers(&peers), peers);
+    }
+
+    #[test]
+    fn test_pinned_and_excluded_peers() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let peers = peers();
+        for strategy in &[
+            PeerSelectionStrategy::Healthiest,
+            PeerSelectionStrategy::RoundRobin,
+        ] {
+            let health = PeerHealth::new(*strategy);
+            record_calls(&health, &peers[0], 10, 0);
+            record_calls(&health, &peers[2], 1000, 0);
+
+            health.set_pinned(Some(&peers[2]));
+            health.set_excluded(&peers[1], true);
+            for offset in 0..3 {
+                let order = health.submission_order(&peers, offset, &mut rng);
+                assert_eq!(order.len(), 2);
+                assert_eq!(order[0], peers[2]);
+                assert!(!order.contains(&peers[1]));
+            }
+            assert_eq!(
+                health.aggregation_peers(&peers),
+                vec![peers[0].clone(), peers[2].clone()]
+            );
+
+            health.set_pinned(None);
+            health.set_excluded(&peers[1], false);
+            assert_eq!(health.submission_order(&peers, 0, &mut rng).len(), 3);
+        }
+    }
+
+    #[test]
+    fn test_round_robin_takes_turns() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let peers = peers();
+        let health = PeerHealth::new(PeerSelectionStrategy::RoundRobin);
+        record_calls(&health, &peers[1], 10, 0);
+        let firsts: Vec<ResponderId> = (0..3)
+            .map(|offset| health.submission_order(&peers, offset, &mut rng)[0].clone())
+            .collect();
+        assert_eq!(firsts, peers);
+        assert_eq!(
+            "round-robin".parse::<PeerSelectionStrategy>(),
+            Ok(PeerSelectionStrategy::RoundRobin)
+        );
+    }
+}
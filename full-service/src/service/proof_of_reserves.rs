@@ -0,0 +1,604 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for proving an account's reserves to an auditor.
+//!
+//! A proof of reserves lists the txos an account had not spent as of a block,
+//! with a proof that each was in the ledger at that block, and the key image
+//! which would appear in the ledger had it been spent. The proof can be
+//! checked against any copy of the ledger with [verify_proof_of_reserves],
+//! without access to the wallet.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountModel},
+        models::{Account, Txo},
+        txo::TxoModel,
+        WalletDbError,
+    },
+    service::{
+        sync::{decode_amount, recover_subaddress_spend_public_key},
+        WalletService,
+    },
+};
+use displaydoc::Display;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::{RistrettoPrivate, RistrettoPublic};
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
+use mc_transaction_core::{
+    membership_proofs::{hash_leaf, hash_nodes, is_membership_proof_valid, Range, NIL_HASH},
+    ring_signature::KeyImage,
+    tx::{TxOut, TxOutMembershipElement, TxOutMembershipProof},
+    Amount, TokenId,
+};
+use std::collections::{btree_map::Entry, BTreeMap, HashSet};
+
+/// Errors for the Proof of Reserves Service.
+#[derive(Display, Debug)]
+pub enum ProofOfReservesServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+
+    /// Error with LedgerDB: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Error decoding prost: {0}
+    ProstDecode(mc_util_serial::DecodeError),
+
+    /// Block {0} is not in the ledger
+    BlockNotInLedger(u64),
+
+    /// The account has not synced block {0}
+    AccountNotSynced(u64),
+
+    /// The key image of txo {0} is not known, so it cannot be shown unspent
+    MissingKeyImage(String),
+
+    /// Tx out {0} was not in the ledger at the block of the proof
+    TxOutNotInBlock(u64),
+
+    /// Tx out {0} appears more than once in the proof
+    DuplicateTxOut(u64),
+
+    /// Tx out {0} does not match the ledger
+    TxOutMismatch(u64),
+
+    /// Membership proof for tx out {0} does not match the ledger at the block
+    InvalidMembershipProof(u64),
+
+    /// The root hash of the proof does not match the ledger at the block
+    RootHashMismatch,
+
+    /// Tx out {index} was spent in block {spent_block_index}
+    KeyImageSpent { index: u64, spent_block_index: u64 },
+
+    /// The view private key does not match the view public key of the proof
+    ViewKeyMismatch,
+
+    /// The amount of tx out {0} does not match its masked amount
+    AmountMismatch(u64),
+
+    /// Tx out {0} was not sent to the subaddress the proof claims
+    NotOwned(u64),
+}
+
+impl From<WalletDbError> for ProofOfReservesServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+impl From<mc_ledger_db::Error> for ProofOfReservesServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<mc_util_serial::DecodeError> for ProofOfReservesServiceError {
+    fn from(src: mc_util_serial::DecodeError) -> Self {
+        Self::ProstDecode(src)
+    }
+}
+
+/// An unspent txo in a proof of reserves.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReserveTxo {
+    pub tx_out: TxOut,
+
+    /// The index of the tx out among all tx outs in the ledger.
+    pub global_index: u64,
+
+    /// Proof that the tx out was in the ledger at the block of the proof.
+    pub membership_proof: TxOutMembershipProof,
+
+    /// The unmasked amount of the tx out.
+    pub amount: Amount,
+
+    /// The subaddress of the account which received the tx out.
+    pub subaddress_index: u64,
+
+    /// The key image which spending the tx out would add to the ledger. It
+    /// is not in the ledger up to the block of the proof.
+    pub key_image: KeyImage,
+}
+
+/// The txos an account had not spent as of a block, with the evidence an
+/// auditor needs to check them against the ledger.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofOfReserves {
+    pub account_id: String,
+
+    /// The block at which the txos were unspent. Membership proofs are
+    /// anchored at the ledger as it was after this block.
+    pub block_index: u64,
+
+    /// Root hash of the tx out membership tree after the block.
+    pub root_hash: [u8; 32],
+
+    /// The view public key of the account.
+    pub view_public_key: RistrettoPublic,
+
+    /// The spend public keys of the subaddresses which received the txos,
+    /// keyed by subaddress index.
+    pub subaddress_spend_public_keys: BTreeMap<u64, RistrettoPublic>,
+
+    pub txos: Vec<ReserveTxo>,
+}
+
+/// The result of checking a proof of reserves against a ledger.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReservesVerification {
+    /// The total value of the txos in each token.
+    pub balance_per_token: BTreeMap<TokenId, u128>,
+
+    /// Whether the amounts and ownership of the txos were checked, which
+    /// requires the account's view private key.
+    pub ownership_verified: bool,
+
+    /// Global indices of txos which were unspent at the block of the proof,
+    /// but have been spent since.
+    pub spent_since: Vec<u64>,
+}
+
+/// The tx out membership tree over the first tx outs of a ledger, as it was
+/// when the ledger held only those tx outs.
+///
+/// The tree is padded to a power of two leaves, and a subtree with no tx outs
+/// has the nil hash.
+pub struct MembershipTree {
+    num_tx_outs: u64,
+
+    /// The hashes of each level of the tree, from the leaves to the root.
+    /// Subtrees past the last tx out are left out.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MembershipTree {
+    /// Build the tree over the tx outs in the ledger after a block.
+    pub fn at_block(
+        ledger: &impl Ledger,
+        block_index: u64,
+    ) -> Result<Self, ProofOfReservesServiceError> {
+        if block_index >= ledger.num_blocks()? {
+            return Err(ProofOfReservesServiceError::BlockNotInLedger(block_index));
+        }
+        let num_tx_outs = ledger.get_block(block_index)?.cumulative_txo_count;
+
+        let leaves = (0..num_tx_outs)
+            .map(|index| Ok(hash_leaf(&ledger.get_tx_out_by_index(index)?)))
+            .collect::<Result<Vec<[u8; 32]>, mc_ledger_db::Error>>()?;
+
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let level = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| hash_nodes(&pair[0], pair.get(1).unwrap_or(&*NIL_HASH)))
+                .collect();
+            levels.push(level);
+        }
+
+        Ok(Self {
+            num_tx_outs,
+            levels,
+        })
+    }
+
+    /// The root hash of the tree.
+    pub fn root_hash(&self) -> [u8; 32] {
+        self.levels[self.levels.len() - 1]
+            .first()
+            .copied()
+            .unwrap_or(*NIL_HASH)
+    }
+
+    /// Proof of membership of the tx out at an index, anchored at the root of
+    /// the tree.
+    pub fn proof(&self, index: u64) -> Result<TxOutMembershipProof, ProofOfReservesServiceError> {
+        if index >= self.num_tx_outs {
+            return Err(ProofOfReservesServiceError::TxOutNotInBlock(index));
+        }
+
+        let mut elements = vec![TxOutMembershipElement::new(
+            Range {
+                from: index,
+                to: index,
+            },
+            self.levels[0][index as usize],
+        )];
+        for (height, level) in self.levels[..self.levels.len() - 1].iter().enumerate() {
+            let sibling = (index >> height) ^ 1;
+            let from = sibling << height;
+            let hash = level.get(sibling as usize).copied().unwrap_or(*NIL_HASH);
+            elements.push(TxOutMembershipElement::new(
+                Range {
+                    from,
+                    to: from + (1 << height) - 1,
+                },
+                hash,
+            ));
+        }
+
+        Ok(TxOutMembershipProof::new(
+            index,
+            self.num_tx_outs - 1,
+            elements,
+        ))
+    }
+}
+
+/// Check a proof of reserves against a ledger which contains its block.
+///
+/// Each tx out must be in the ledger at the block of the proof, with a
+/// membership proof anchored there, and its key image must not be in the
+/// ledger up to that block. Given the account's view private key, the amounts
+/// and the subaddresses which received the txos are checked too.
+pub fn verify_proof_of_reserves(
+    proof_of_reserves: &ProofOfReserves,
+    ledger: &impl Ledger,
+    view_private_key: Option<&RistrettoPrivate>,
+) -> Result<ReservesVerification, ProofOfReservesServiceError> {
+    let tree = MembershipTree::at_block(ledger, proof_of_reserves.block_index)?;
+    let root_hash = tree.root_hash();
+    if proof_of_reserves.root_hash != root_hash {
+        return Err(ProofOfReservesServiceError::RootHashMismatch);
+    }
+
+    if let Some(view_private_key) = view_private_key {
+        if RistrettoPublic::from(view_private_key) != proof_of_reserves.view_public_key {
+            return Err(ProofOfReservesServiceError::ViewKeyMismatch);
+        }
+    }
+
+    let mut verification = ReservesVerification {
+        ownership_verified: view_private_key.is_some(),
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    for txo in proof_of_reserves.txos.iter() {
+        let index = txo.global_index;
+        if !seen.insert(index) {
+            return Err(ProofOfReservesServiceError::DuplicateTxOut(index));
+        }
+        if index >= tree.num_tx_outs {
+            return Err(ProofOfReservesServiceError::TxOutNotInBlock(index));
+        }
+        if ledger.get_tx_out_by_index(index)? != txo.tx_out {
+            return Err(ProofOfReservesServiceError::TxOutMismatch(index));
+        }
+        if txo.membership_proof.index != index
+            || !is_membership_proof_valid(&txo.tx_out, &txo.membership_proof, &root_hash)
+                .unwrap_or(false)
+        {
+            return Err(ProofOfReservesServiceError::InvalidMembershipProof(index));
+        }
+
+        match ledger.check_key_image(&txo.key_image)? {
+            Some(spent_block_index) if spent_block_index <= proof_of_reserves.block_index => {
+                return Err(ProofOfReservesServiceError::KeyImageSpent {
+                    index,
+                    spent_block_index,
+                })
+            }
+            Some(_) => verification.spent_since.push(index),
+            None => {}
+        }
+
+        if let Some(view_private_key) = view_private_key {
+            if decode_amount(&txo.tx_out, view_private_key) != Some(txo.amount) {
+                return Err(ProofOfReservesServiceError::AmountMismatch(index));
+            }
+            let subaddress_spend_public_key = proof_of_reserves
+                .subaddress_spend_public_keys
+                .get(&txo.subaddress_index);
+            if subaddress_spend_public_key.is_none()
+                || recover_subaddress_spend_public_key(&txo.tx_out, view_private_key).as_ref()
+                    != subaddress_spend_public_key
+            {
+                return Err(ProofOfReservesServiceError::NotOwned(index));
+            }
+        }
+
+        *verification
+            .balance_per_token
+            .entry(txo.amount.token_id)
+            .or_default() += txo.amount.value as u128;
+    }
+
+    Ok(verification)
+}
+
+/// Trait defining the ways in which the wallet can prove the reserves of its
+/// accounts.
+pub trait ProofOfReservesService {
+    /// Export the txos an account had not spent as of a block, by default the
+    /// last block the account has synced, with proofs which can be checked
+    /// against the ledger.
+    fn export_proof_of_reserves(
+        &self,
+        account_id: &AccountID,
+        block_index: Option<u64>,
+    ) -> Result<ProofOfReserves, ProofOfReservesServiceError>;
+
+    /// Check a proof of reserves against the wallet's ledger.
+    fn verify_proof_of_reserves(
+        &self,
+        proof_of_reserves: &ProofOfReserves,
+        view_private_key: Option<&RistrettoPrivate>,
+    ) -> Result<ReservesVerification, ProofOfReservesServiceError>;
+}
+
+impl<T, FPR> ProofOfReservesService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn export_proof_of_reserves(
+        &self,
+        account_id: &AccountID,
+        block_index: Option<u64>,
+    ) -> Result<ProofOfReserves, ProofOfReservesServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+
+        // Spends in blocks the account has not synced would go unnoticed.
+        let block_index =
+            block_index.unwrap_or((account.next_block_index as u64).saturating_sub(1));
+        if block_index >= account.next_block_index as u64 {
+            return Err(ProofOfReservesServiceError::AccountNotSynced(block_index));
+        }
+        let tree = MembershipTree::at_block(&self.ledger_db, block_index)?;
+
+        let mut subaddress_spend_public_keys = BTreeMap::new();
+        let txos = Txo::list_unspent_at_block(&account.id, block_index, &conn)?
+            .iter()
+            .map(|txo| {
+                let tx_out: TxOut = mc_util_serial::decode(&txo.txo)?;
+                let key_image: KeyImage = match &txo.key_image {
+                    Some(key_image) => mc_util_serial::decode(key_image)?,
+                    None => {
+                        return Err(ProofOfReservesServiceError::MissingKeyImage(txo.id.clone()))
+                    }
+                };
+                let subaddress_index = txo.subaddress_index.unwrap_or_default() as u64;
+                if let Entry::Vacant(entry) = subaddress_spend_public_keys.entry(subaddress_index) {
+                    let public_address = account.public_address(subaddress_index)?;
+                    entry.insert(*public_address.spend_public_key());
+                }
+
+                let global_index = self
+                    .ledger_db
+                    .get_tx_out_index_by_public_key(&tx_out.public_key)?;
+                Ok(ReserveTxo {
+                    membership_proof: tree.proof(global_index)?,
+                    tx_out,
+                    global_index,
+                    amount: Amount::new(txo.value as u64, TokenId::from(txo.token_id as u64)),
+                    subaddress_index,
+                    key_image,
+                })
+            })
+            .collect::<Result<Vec<ReserveTxo>, ProofOfReservesServiceError>>()?;
+
+        Ok(ProofOfReserves {
+            account_id: account.id.clone(),
+            block_index,
+            root_hash: tree.root_hash(),
+            view_public_key: RistrettoPublic::from(&account.view_private_key()?),
+            subaddress_spend_public_keys,
+            txos,
+        })
+    }
+
+    fn verify_proof_of_reserves(
+        &self,
+        proof_of_reserves: &ProofOfReserves,
+        view_private_key: Option<&RistrettoPrivate>,
+    ) -> Result<ReservesVerification, ProofOfReservesServiceError> {
+        verify_proof_of_reserves(proof_of_reserves, &self.ledger_db, view_private_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        service::{account::AccountService, address::AddressService},
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+    };
+    use mc_account_keys::{AccountKey, RootEntropy, RootIdentity};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_transaction_core::{tokens::Mob, Token};
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_export_and_verify_proof_of_reserves(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let entropy = RootEntropy::from_random(&mut rng);
+        let account_key = AccountKey::from(&RootIdentity::from(&entropy));
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&entropy.bytes),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let address = service
+            .assign_address_for_account(&account_id, None)
+            .unwrap();
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[account_key.default_subaddress()],
+            100 * MOB,
+            &[],
+            &mut rng,
+        );
+        let block_index = add_block_to_ledger_db(
+            &mut ledger_db,
+            &[account_key.subaddress(address.subaddress_index as u64)],
+            50 * MOB,
+            &[],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        // The tree at the tip matches the ledger's own.
+        let tree = MembershipTree::at_block(&ledger_db, block_index).unwrap();
+        let root_element = ledger_db.get_root_tx_out_membership_element().unwrap();
+        assert_eq!(tree.root_hash(), root_element.hash.0);
+
+        let proof_of_reserves = service.export_proof_of_reserves(&account_id, None).unwrap();
+        assert_eq!(proof_of_reserves.block_index, block_index);
+        assert_eq!(proof_of_reserves.txos.len(), 2);
+        assert_eq!(
+            proof_of_reserves
+                .subaddress_spend_public_keys
+                .keys()
+                .copied()
+                .collect::<Vec<u64>>(),
+            vec![0, address.subaddress_index as u64],
+        );
+        for txo in proof_of_reserves.txos.iter() {
+            assert!(is_membership_proof_valid(
+                &txo.tx_out,
+                &txo.membership_proof,
+                &root_element.hash.0
+            )
+            .unwrap());
+        }
+
+        let view_private_key = *account_key.view_private_key();
+        let verification = service
+            .verify_proof_of_reserves(&proof_of_reserves, Some(&view_private_key))
+            .unwrap();
+        assert!(verification.ownership_verified);
+        assert_eq!(
+            verification.balance_per_token.get(&Mob::ID),
+            Some(&(150 * MOB as u128)),
+        );
+        assert!(verification.spent_since.is_empty());
+
+        // Without the view key, only membership and key images are checked.
+        let verification = verify_proof_of_reserves(&proof_of_reserves, &ledger_db, None).unwrap();
+        assert!(!verification.ownership_verified);
+
+        // A txo claimed for another subaddress is rejected.
+        let mut tampered = proof_of_reserves.clone();
+        tampered.txos[0].subaddress_index = address.subaddress_index as u64;
+        match verify_proof_of_reserves(&tampered, &ledger_db, Some(&view_private_key)) {
+            Err(ProofOfReservesServiceError::NotOwned(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // An inflated amount is rejected.
+        let mut tampered = proof_of_reserves.clone();
+        tampered.txos[0].amount = Amount::new(1_000 * MOB, Mob::ID);
+        match verify_proof_of_reserves(&tampered, &ledger_db, Some(&view_private_key)) {
+            Err(ProofOfReservesServiceError::AmountMismatch(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // Blocks the account has not synced cannot be exported.
+        match service.export_proof_of_reserves(&account_id, Some(block_index + 1)) {
+            Err(ProofOfReservesServiceError::AccountNotSynced(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_proof_of_reserves_spent_after_block(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let entropy = RootEntropy::from_random(&mut rng);
+        let account_key = AccountKey::from(&RootIdentity::from(&entropy));
+
+        let mut ledger_db = get_test_ledger(5, &[account_key.default_subaddress()], 3, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account = service
+            .import_account_from_legacy_root_entropy(
+                hex::encode(&entropy.bytes),
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let account_id = AccountID(account.id);
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let proof_of_reserves = service.export_proof_of_reserves(&account_id, None).unwrap();
+        assert_eq!(proof_of_reserves.txos.len(), 3);
+        let spent = proof_of_reserves.txos[0].clone();
+
+        // Spend a txo after the block of the proof, without the wallet
+        // syncing it.
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let spent_block_index = add_block_to_ledger_db(
+            &mut ledger_db,
+            &[recipient],
+            MOB,
+            &[spent.key_image],
+            &mut rng,
+        );
+
+        // The proof still holds for its own block, but flags the spend.
+        let verification = verify_proof_of_reserves(&proof_of_reserves, &ledger_db, None).unwrap();
+        assert_eq!(verification.spent_since, vec![spent.global_index]);
+
+        // The same txos claimed as unspent at the later block are rejected.
+        let tree = MembershipTree::at_block(&ledger_db, spent_block_index).unwrap();
+        let mut forged = proof_of_reserves.clone();
+        forged.block_index = spent_block_index;
+        forged.root_hash = tree.root_hash();
+        for txo in forged.txos.iter_mut() {
+            txo.membership_proof = tree.proof(txo.global_index).unwrap();
+        }
+        match verify_proof_of_reserves(&forged, &ledger_db, None) {
+            Err(ProofOfReservesServiceError::KeyImageSpent {
+                index,
+                spent_block_index: block_index,
+            }) => {
+                assert_eq!(index, spent.global_index);
+                assert_eq!(block_index, spent_block_index);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
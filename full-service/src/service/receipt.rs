@@ -12,7 +12,8 @@ use crate::{
     db::{
         account::{AccountID, AccountModel},
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, AssignedSubaddress, Txo},
+        models::{Account, AssignedSubaddress, TransactionLog, Txo},
+        transaction_log::{TransactionID, TransactionLogModel},
         txo::{TxoModel, TxoStatus},
         WalletDbError,
     },
@@ -24,7 +25,11 @@ use mc_account_keys::AccountKey;
 use mc_connection::{BlockchainConnection, UserTxConnection};
 use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
 use mc_fog_report_validation::FogPubkeyResolver;
-use mc_transaction_core::{get_tx_out_shared_secret, tx::TxOutConfirmationNumber, MaskedAmount};
+use mc_transaction_core::{
+    get_tx_out_shared_secret,
+    tx::{Tx, TxOut, TxOutConfirmationNumber},
+    MaskedAmount,
+};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
@@ -55,6 +60,9 @@ pub enum ReceiptServiceError {
 
     /// Error decoding from hex: {0}
     HexDecode(hex::FromHexError),
+
+    /// Missing confirmation number for Txo: {0}
+    MissingConfirmation(String),
 }
 
 impl From<WalletDbError> for ReceiptServiceError {
@@ -171,6 +179,14 @@ pub trait ReceiptService {
         &self,
         tx_proposal: &TxProposal,
     ) -> Result<Vec<ReceiverReceipt>, ReceiptServiceError>;
+
+    /// Get the receipts for the payload Txos of a transaction this wallet
+    /// built, so that they can be shared with the recipients after the
+    /// proposal itself is gone.
+    fn get_receiver_receipts(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<Vec<ReceiverReceipt>, ReceiptServiceError>;
 }
 
 impl<T, FPR> ReceiptService for WalletService<T, FPR>
@@ -261,13 +277,46 @@ where
             .collect::<Vec<ReceiverReceipt>>();
         Ok(receiver_tx_receipts)
     }
+
+    fn get_receiver_receipts(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<Vec<ReceiverReceipt>, ReceiptServiceError> {
+        let conn = &self.wallet_db.get_conn()?;
+        let transaction_log =
+            TransactionLog::get(&TransactionID(transaction_log_id.to_string()), conn)?;
+        let tx: Tx = mc_util_serial::decode(&transaction_log.tx)?;
+        let associated_txos = transaction_log.get_associated_txos(conn)?;
+
+        associated_txos
+            .outputs
+            .iter()
+            .map(|(txo, _)| {
+                let tx_out: TxOut = mc_util_serial::decode(&txo.txo)?;
+                let confirmation = match &txo.shared_secret {
+                    Some(confirmation) => mc_util_serial::decode(confirmation)?,
+                    None => return Err(ReceiptServiceError::MissingConfirmation(txo.id.clone())),
+                };
+                Ok(ReceiverReceipt {
+                    public_key: tx_out.public_key,
+                    confirmation,
+                    tombstone_block: tx.prefix.tombstone_block,
+                    amount: tx_out.masked_amount,
+                })
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        db::{account::AccountID, models::TransactionLog, transaction_log::TransactionLogModel},
+        db::{
+            account::AccountID,
+            models::TransactionLog,
+            transaction_log::{TransactionLogFilter, TransactionLogModel},
+        },
         json_rpc::v2::models::amount::Amount as AmountJSON,
         service::{
             account::AccountService,
@@ -391,6 +440,9 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .expect("Could not build transaction");
@@ -437,7 +489,7 @@ mod tests {
         // Get the corresponding TransactionLog for Alice's Account - only the sender
         // has the confirmation number.
         let transaction_logs = service
-            .list_transaction_logs(Some(alice.id), None, None, None, None)
+            .list_transaction_logs(Some(alice.id), None, None, &TransactionLogFilter::default())
             .expect("Could not get transaction logs");
         // Alice should have one sent tranasction log
         assert_eq!(transaction_logs.len(), 1);
@@ -517,6 +569,9 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .expect("Could not build transaction");
@@ -544,6 +599,13 @@ mod tests {
         )
         .expect("Could not log submitted");
 
+        // Alice can get the same receipts later from the transaction log.
+        let transaction_log_id = TransactionID::from(&tx_proposal.tx).to_string();
+        let stored_receipts = service
+            .get_receiver_receipts(&transaction_log_id)
+            .expect("Could not get receiver receipts");
+        assert_eq!(stored_receipts, receipts);
+
         // Status for Bob should still be pending, even though the Txos will show up in
         // the wallet, but under Alice's account.
         let (status, _txo) = service
@@ -640,6 +702,9 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .expect("Could not build transaction");
@@ -770,6 +835,9 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .expect("Could not build transaction");
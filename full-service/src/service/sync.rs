@@ -6,13 +6,17 @@ use crate::{
     db::{
         account::{AccountID, AccountModel},
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, AssignedSubaddress, TransactionLog, Txo},
+        idempotency_key::{idempotency_key_cutoff, unix_timestamp_now, IdempotencyKeyModel},
+        models::{Account, AssignedSubaddress, IdempotencyKey, MonitoredTxo, TransactionLog, Txo},
+        monitored_txo::MonitoredTxoModel,
         transaction,
         transaction_log::TransactionLogModel,
         txo::TxoModel,
         Conn, WalletDb,
     },
     error::SyncError,
+    service::job::JobContext,
+    util::constants::{DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS, REMOVED_ACCOUNT_RETENTION_SECONDS},
 };
 use mc_account_keys::{AccountKey, ViewAccountKey};
 use mc_common::{
@@ -31,16 +35,108 @@ use mc_transaction_core::{
 use rayon::prelude::*;
 
 use std::{
+    collections::HashSet,
     convert::{TryFrom, TryInto},
+    ops::Range,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     thread,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 const BLOCKS_CHUNK_SIZE: u64 = 1_000;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+const SYNC_BATCH_PAUSE: Duration = Duration::from_millis(10);
+
+/// How the sync thread processes blocks.
+#[derive(Clone, Debug)]
+pub struct SyncConfig {
+    /// Number of blocks synced for an account in each database transaction.
+    pub batch_size: u64,
+
+    /// Number of threads matching blocks against account view keys. Zero uses
+    /// one thread per core.
+    pub num_workers: usize,
+
+    /// Pause between batches, so that the sync thread does not starve other
+    /// users of the wallet database.
+    pub batch_pause: Duration,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: BLOCKS_CHUNK_SIZE,
+            num_workers: 0,
+            batch_pause: SYNC_BATCH_PAUSE,
+        }
+    }
+}
+
+/// Sync throughput of an account since the wallet started.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountSyncStats {
+    /// Number of blocks synced.
+    pub blocks_synced: u64,
+
+    /// Number of txos received in the blocks synced.
+    pub txos_received: u64,
+
+    /// Number of blocks in the most recent batch.
+    pub last_batch_blocks: u64,
+
+    /// How long the most recent batch took to scan and write.
+    pub last_batch_duration: Duration,
+}
+
+impl AccountSyncStats {
+    /// Blocks synced per second over the most recent batch.
+    pub fn blocks_per_second(&self) -> f64 {
+        let seconds = self.last_batch_duration.as_secs_f64();
+        if seconds > 0.0 {
+            self.last_batch_blocks as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Sync throughput per account, shared between the sync thread and the
+/// service.
+#[derive(Debug, Default)]
+pub struct SyncStats {
+    accounts: Mutex<HashMap<String, AccountSyncStats>>,
+}
+
+impl SyncStats {
+    /// Record a batch of blocks synced for an account.
+    pub fn record_batch(
+        &self,
+        account_id_hex: &str,
+        num_blocks: u64,
+        num_txos_received: u64,
+        duration: Duration,
+    ) {
+        let mut accounts = self.accounts.lock().expect("SyncStats mutex poisoned");
+        let stats = accounts.entry(account_id_hex.to_string()).or_default();
+        stats.blocks_synced += num_blocks;
+        stats.txos_received += num_txos_received;
+        stats.last_batch_blocks = num_blocks;
+        stats.last_batch_duration = duration;
+    }
+
+    /// Get the sync throughput of an account, if it has synced any blocks
+    /// since the wallet started.
+    pub fn get(&self, account_id_hex: &str) -> Option<AccountSyncStats> {
+        self.accounts
+            .lock()
+            .expect("SyncStats mutex poisoned")
+            .get(account_id_hex)
+            .cloned()
+    }
+}
 
 /// Sync thread - holds objects needed to cleanly terminate the sync thread.
 pub struct SyncThread {
@@ -53,26 +149,104 @@ pub struct SyncThread {
 
 impl SyncThread {
     pub fn start(ledger_db: LedgerDB, wallet_db: WalletDb, logger: Logger) -> Self {
+        Self::start_with_config(
+            ledger_db,
+            wallet_db,
+            Duration::from_secs(DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS),
+            SyncConfig::default(),
+            Arc::new(SyncStats::default()),
+            logger,
+        )
+    }
+
+    /// Start the sync thread, syncing accounts in batches as configured and
+    /// recording their throughput in `sync_stats`. Idempotency keys older
+    /// than `idempotency_key_ttl` are purged as part of its maintenance.
+    pub fn start_with_config(
+        ledger_db: LedgerDB,
+        wallet_db: WalletDb,
+        idempotency_key_ttl: Duration,
+        sync_config: SyncConfig,
+        sync_stats: Arc<SyncStats>,
+        logger: Logger,
+    ) -> Self {
         // Start the sync thread.
 
         let stop_requested = Arc::new(AtomicBool::new(false));
         let thread_stop_requested = stop_requested.clone();
 
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(sync_config.num_workers)
+            .thread_name(|index| format!("sync-worker-{}", index))
+            .build()
+            .expect("failed starting sync worker threads");
+
         let join_handle = Some(
             thread::Builder::new()
                 .name("sync".to_string())
                 .spawn(move || {
                     log::debug!(logger, "Sync thread started.");
 
+                    let mut last_purge: Option<Instant> = None;
                     loop {
                         if thread_stop_requested.load(Ordering::SeqCst) {
                             log::debug!(logger, "SyncThread stop requested.");
                             break;
                         }
-                        match sync_all_accounts(&ledger_db, &wallet_db, &logger) {
+                        match sync_all_accounts_in_batches(
+                            &ledger_db,
+                            &wallet_db,
+                            &sync_config,
+                            &pool,
+                            &sync_stats,
+                            &thread_stop_requested,
+                            &logger,
+                        ) {
                             Ok(()) => (),
                             Err(e) => log::error!(&logger, "Error during account sync:\n{:?}", e),
                         }
+                        match sync_monitored_txos(&ledger_db, &wallet_db) {
+                            Ok(0) => (),
+                            Ok(num_landed) => log::info!(
+                                &logger,
+                                "{} monitored txos landed in the ledger",
+                                num_landed
+                            ),
+                            Err(e) => {
+                                log::error!(&logger, "Error checking monitored txos:\n{:?}", e)
+                            }
+                        }
+
+                        if last_purge.map_or(true, |purged| purged.elapsed() >= PURGE_INTERVAL) {
+                            match purge_expired_idempotency_keys(&wallet_db, idempotency_key_ttl) {
+                                Ok(0) => (),
+                                Ok(num_purged) => log::info!(
+                                    &logger,
+                                    "Purged {} expired idempotency keys",
+                                    num_purged
+                                ),
+                                Err(e) => log::error!(
+                                    &logger,
+                                    "Error purging expired idempotency keys:\n{:?}",
+                                    e
+                                ),
+                            }
+                            match purge_expired_removed_accounts(&wallet_db) {
+                                Ok(0) => (),
+                                Ok(num_purged) => log::info!(
+                                    &logger,
+                                    "Purged {} accounts removed more than {} days ago",
+                                    num_purged,
+                                    REMOVED_ACCOUNT_RETENTION_SECONDS / 86_400
+                                ),
+                                Err(e) => log::error!(
+                                    &logger,
+                                    "Error purging expired removed accounts:\n{:?}",
+                                    e
+                                ),
+                            }
+                            last_purge = Some(Instant::now());
+                        }
 
                         thread::sleep(std::time::Duration::from_secs(1));
                     }
@@ -101,39 +275,184 @@ impl Drop for SyncThread {
     }
 }
 
+/// Sync every account which is behind the ledger, in batches of the default
+/// size.
 pub fn sync_all_accounts(
     ledger_db: &LedgerDB,
     wallet_db: &WalletDb,
     logger: &Logger,
 ) -> Result<(), SyncError> {
-    // Get the current number of blocks in ledger.
-    let num_blocks = ledger_db
-        .num_blocks()
-        .expect("failed getting number of blocks");
-
-    // Go over our list of accounts and see which ones need to process more blocks.
-    let accounts: Vec<Account> = {
-        let conn = &wallet_db
-            .get_conn()
-            .expect("Could not get connection to DB");
-        Account::list_all(conn, None, None).expect("Failed getting accounts from database")
-    };
+    let pool = rayon::ThreadPoolBuilder::new()
+        .build()
+        .expect("failed starting sync worker threads");
+    sync_all_accounts_in_batches(
+        ledger_db,
+        wallet_db,
+        &SyncConfig::default(),
+        &pool,
+        &SyncStats::default(),
+        &AtomicBool::new(false),
+        logger,
+    )
+}
+
+/// Sync every account which is behind the ledger.
+///
+/// Each round loads the next batch of blocks for every account, matches the
+/// batches against the accounts' view keys in parallel on `pool`, then writes
+/// each account's txos together with its next block index in a single
+/// database transaction. Rounds are separated by the configured pause so that
+/// JSON RPC requests are not starved of the database, and syncing stops early
+/// when `stop_requested` is set.
+pub fn sync_all_accounts_in_batches(
+    ledger_db: &LedgerDB,
+    wallet_db: &WalletDb,
+    config: &SyncConfig,
+    pool: &rayon::ThreadPool,
+    stats: &SyncStats,
+    stop_requested: &AtomicBool,
+    logger: &Logger,
+) -> Result<(), SyncError> {
+    let conn = wallet_db.get_conn()?;
+
+    loop {
+        if stop_requested.load(Ordering::SeqCst) {
+            return Ok(());
+        }
 
-    for account in accounts {
-        // If there are no new blocks for this account, don't do anything.
-        if account.next_block_index as u64 > num_blocks - 1 {
-            continue;
+        // Go over our list of accounts and see which ones need to process more
+        // blocks.
+        let num_blocks = ledger_db.num_blocks()?;
+        let mut states = Account::list_all(&conn, None, None)?
+            .into_iter()
+            .filter(|account| (account.next_block_index as u64) < num_blocks)
+            .map(|account| AccountSyncState::load(account, &conn))
+            .collect::<Result<Vec<_>, SyncError>>()?;
+        if states.is_empty() {
+            return Ok(());
         }
-        sync_account(ledger_db, wallet_db, &account.id, logger)?;
+
+        // Accounts at the same height share a batch, so that its blocks are
+        // only read from the ledger once.
+        let mut batches: HashMap<u64, BlockBatch> = HashMap::default();
+        for state in &states {
+            let start_block_index = state.next_block_index();
+            if !batches.contains_key(&start_block_index) {
+                if let Some(batch) =
+                    BlockBatch::load(ledger_db, start_block_index, config.batch_size)?
+                {
+                    batches.insert(start_block_index, batch);
+                }
+            }
+        }
+
+        let scanned_batches: Vec<Option<ScannedBatch>> = pool.install(|| {
+            states
+                .par_iter_mut()
+                .map(|state| {
+                    batches
+                        .get(&state.next_block_index())
+                        .map(|batch| state.scan(batch))
+                })
+                .collect()
+        });
+
+        let mut more_blocks = false;
+        for (state, scanned) in states.iter().zip(scanned_batches) {
+            let scanned = match scanned {
+                Some(scanned) => scanned,
+                None => continue,
+            };
+            let batch = &batches[&state.next_block_index()];
+            let commit_start_time = Instant::now();
+            match state.commit(batch, &scanned, config.batch_size, &conn, logger)? {
+                SyncStatus::NoMoreBlocks => (),
+                SyncStatus::ChunkFinished => more_blocks = true,
+                SyncStatus::Stale => {
+                    more_blocks = true;
+                    continue;
+                }
+            }
+            stats.record_batch(
+                &state.account.id,
+                batch.num_blocks(),
+                scanned.received_txos.len() as u64,
+                scanned.duration + commit_start_time.elapsed(),
+            );
+        }
+
+        if !more_blocks {
+            return Ok(());
+        }
+        thread::sleep(config.batch_pause);
     }
+}
 
-    Ok(())
+/// Look for the outputs of submitted transactions in the ledger by their
+/// public keys, recording the block each has landed in and marking its
+/// transaction log as succeeded. Returns the number of outputs which landed.
+pub fn sync_monitored_txos(ledger_db: &LedgerDB, wallet_db: &WalletDb) -> Result<usize, SyncError> {
+    let conn = &wallet_db.get_conn()?;
+    let mut num_landed = 0;
+
+    for monitored_txo in MonitoredTxo::list_pending(conn)? {
+        let tx_out_index =
+            match ledger_db.get_tx_out_index_by_public_key(&monitored_txo.public_key()?) {
+                Ok(tx_out_index) => tx_out_index,
+                Err(mc_ledger_db::Error::NotFound) => continue,
+                Err(e) => return Err(e.into()),
+            };
+        let block_index = ledger_db.get_block_index_by_tx_out_index(tx_out_index)?;
+
+        transaction(conn, || {
+            monitored_txo.update_landed_block_index(block_index, conn)?;
+            TransactionLog::update_pending_to_succeeded(
+                &monitored_txo.transaction_log_id,
+                block_index,
+                conn,
+            )
+        })?;
+        num_landed += 1;
+    }
+
+    Ok(num_landed)
+}
+
+/// Delete idempotency keys which are older than the given TTL.
+pub fn purge_expired_idempotency_keys(
+    wallet_db: &WalletDb,
+    idempotency_key_ttl: Duration,
+) -> Result<usize, SyncError> {
+    let conn = &wallet_db.get_conn()?;
+    Ok(IdempotencyKey::delete_expired(
+        idempotency_key_cutoff(idempotency_key_ttl),
+        conn,
+    )?)
+}
+
+/// Delete the accounts which were removed longer ago than they can be
+/// restored, along with their keys, txos, subaddresses and transaction logs.
+pub fn purge_expired_removed_accounts(wallet_db: &WalletDb) -> Result<usize, SyncError> {
+    let conn = &wallet_db.get_conn()?;
+    let removed_at_cutoff = unix_timestamp_now() - REMOVED_ACCOUNT_RETENTION_SECONDS as i64;
+    transaction(conn, || {
+        let accounts = Account::list_removed_before(removed_at_cutoff, conn)?;
+        let num_purged = accounts.len();
+        for account in accounts {
+            account.delete(conn)?;
+        }
+        Ok(num_purged)
+    })
 }
 
 #[derive(Debug)]
 enum SyncStatus {
     ChunkFinished,
     NoMoreBlocks,
+
+    /// The account changed while the chunk was scanned, so nothing was
+    /// written and the chunk must be scanned again.
+    Stale,
 }
 
 /// Sync a single account.
@@ -145,46 +464,171 @@ pub fn sync_account(
 ) -> Result<(), SyncError> {
     let conn = wallet_db.get_conn()?;
 
-    while let SyncStatus::ChunkFinished =
-        sync_account_next_chunk(ledger_db, &conn, logger, account_id_hex)?
+    while let SyncStatus::ChunkFinished | SyncStatus::Stale =
+        sync_account_next_chunk(ledger_db, &conn, logger, account_id_hex, BLOCKS_CHUNK_SIZE)?
     {}
 
     Ok(())
 }
 
+/// Sync an account again from its first block, reporting the number of blocks
+/// synced to the job after each chunk. Stops early if the job is cancelled,
+/// leaving the rest of the blocks to the sync thread. Returns the account's
+/// next block index.
+pub fn resync_account(
+    ledger_db: &LedgerDB,
+    wallet_db: &WalletDb,
+    account_id_hex: &str,
+    job: &JobContext,
+    logger: &Logger,
+) -> Result<u64, SyncError> {
+    let conn = wallet_db.get_conn()?;
+    let account_id = AccountID(account_id_hex.to_string());
+    let account = Account::get(&account_id, &conn)?;
+    let first_block_index = account.first_block_index as u64;
+    account.update_next_block_index(first_block_index, &conn)?;
+
+    sync_account_with_progress(
+        ledger_db,
+        &conn,
+        account_id_hex,
+        first_block_index,
+        job,
+        logger,
+    )
+}
+
+/// Sync an account again from the given block, forgetting the txos it
+/// received and spent from that block onwards first, so that txos missed
+/// because its first block index was set too late are found. If the block is
+/// before the account's first block index, the first block index is moved
+/// back to it. Reports progress like resync_account, and returns the account's
+/// next block index.
+pub fn rescan_account(
+    ledger_db: &LedgerDB,
+    wallet_db: &WalletDb,
+    account_id_hex: &str,
+    from_block_index: u64,
+    job: &JobContext,
+    logger: &Logger,
+) -> Result<u64, SyncError> {
+    let conn = wallet_db.get_conn()?;
+    let account_id = AccountID(account_id_hex.to_string());
+    transaction(&conn, || -> Result<(), SyncError> {
+        let account = Account::get(&account_id, &conn)?;
+        if from_block_index < account.first_block_index as u64 {
+            account.update_first_block_index(from_block_index, &conn)?;
+        }
+        if account
+            .first_activity_block_index
+            .map_or(false, |i| i as u64 >= from_block_index)
+        {
+            account.update_first_activity_block_index(None, &conn)?;
+        }
+        let num_txos_reset = Txo::reset_for_rescan(account_id_hex, from_block_index, &conn)?;
+        account.update_next_block_index(from_block_index, &conn)?;
+        log::info!(
+            logger,
+            "Rescanning account {} from block {}, {} txos reset",
+            account_id_hex.chars().take(6).collect::<String>(),
+            from_block_index,
+            num_txos_reset,
+        );
+        Ok(())
+    })?;
+
+    sync_account_with_progress(
+        ledger_db,
+        &conn,
+        account_id_hex,
+        from_block_index,
+        job,
+        logger,
+    )
+}
+
+/// Sync an account from its next block index, which has just been set to
+/// `start_block_index`, reporting the number of blocks synced to the job.
+fn sync_account_with_progress(
+    ledger_db: &LedgerDB,
+    conn: &Conn,
+    account_id_hex: &str,
+    start_block_index: u64,
+    job: &JobContext,
+    logger: &Logger,
+) -> Result<u64, SyncError> {
+    let account_id = AccountID(account_id_hex.to_string());
+    let num_blocks = ledger_db.num_blocks()?;
+    let total = num_blocks.saturating_sub(start_block_index);
+    job.set_progress(0, Some(total));
+
+    loop {
+        if job.is_cancelled() {
+            break;
+        }
+        let status =
+            sync_account_next_chunk(ledger_db, conn, logger, account_id_hex, BLOCKS_CHUNK_SIZE)?;
+        let next_block_index = Account::get(&account_id, conn)?.next_block_index as u64;
+        job.set_progress(
+            next_block_index
+                .saturating_sub(start_block_index)
+                .min(total),
+            Some(total),
+        );
+        if let SyncStatus::NoMoreBlocks = status {
+            break;
+        }
+    }
+
+    Ok(Account::get(&account_id, conn)?.next_block_index as u64)
+}
+
 fn sync_account_next_chunk(
     ledger_db: &LedgerDB,
     conn: &Conn,
     logger: &Logger,
     account_id_hex: &str,
+    batch_size: u64,
 ) -> Result<SyncStatus, SyncError> {
-    transaction(conn, || {
-        // Get the account data. If it is no longer available, the account has been
-        // removed and we can simply return.
-        let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
+    // Get the account data. If it is no longer available, the account has been
+    // removed and we can simply return.
+    let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
+    let mut state = AccountSyncState::load(account, conn)?;
+
+    let batch = match BlockBatch::load(ledger_db, state.next_block_index(), batch_size)? {
+        Some(batch) => batch,
+        None => return Ok(SyncStatus::NoMoreBlocks),
+    };
+    let scanned = state.scan(&batch);
+    state.commit(&batch, &scanned, batch_size, conn, logger)
+}
 
-        // Load subaddresses for this account into a hash map.
-        let mut subaddress_keys: HashMap<RistrettoPublic, u64> = HashMap::default();
-        let subaddresses: Vec<_> =
-            AssignedSubaddress::list_all(Some(account_id_hex.to_string()), None, None, conn)?;
-        for s in subaddresses {
-            let subaddress_key: RistrettoPublic = s.spend_public_key.as_slice().try_into()?;
-            subaddress_keys.insert(subaddress_key, s.subaddress_index as u64);
-        }
+/// The outputs and key images of a range of blocks.
+struct BlockBatch {
+    start_block_index: u64,
+    end_block_index: u64,
+    tx_outs: Vec<(u64, TxOut)>,
+    key_images: Vec<(u64, KeyImage)>,
 
-        let start_time = Instant::now();
-        let start_block_index = account.next_block_index as u64;
-        let mut end_block_index: Option<u64> = None;
+    /// Index of the batch's first tx out among all tx outs in the ledger. The
+    /// rest follow it in order.
+    first_global_index: u64,
+}
 
-        // Load transaction outputs and key images for this chunk.
+impl BlockBatch {
+    /// Load up to `batch_size` blocks from the ledger, starting at
+    /// `start_block_index`. Returns None if there are no blocks to load.
+    fn load(
+        ledger_db: &LedgerDB,
+        start_block_index: u64,
+        batch_size: u64,
+    ) -> Result<Option<Self>, SyncError> {
+        let mut end_block_index: Option<u64> = None;
         let mut tx_outs: Vec<(u64, TxOut)> = Vec::new();
         let mut key_images: Vec<(u64, KeyImage)> = Vec::new();
 
-        let start = account.next_block_index as u64;
-        let end = start + BLOCKS_CHUNK_SIZE;
-        for block_index in start..end {
-            let block_index = block_index as u64;
-            let block_contents = match ledger_db.get_block_contents(block_index as u64) {
+        for block_index in start_block_index..start_block_index + batch_size {
+            let block_contents = match ledger_db.get_block_contents(block_index) {
                 Ok(block_contents) => block_contents,
                 Err(mc_ledger_db::Error::NotFound) => {
                     break;
@@ -204,137 +648,282 @@ fn sync_account_next_chunk(
             }
         }
 
-        // If no blocks were found, exit.
-        if end_block_index.is_none() {
-            return Ok(SyncStatus::NoMoreBlocks);
+        let first_global_index = match tx_outs.first() {
+            Some((_, tx_out)) => ledger_db.get_tx_out_index_by_hash(&tx_out.hash())?,
+            None => 0,
+        };
+
+        Ok(end_block_index.map(|end_block_index| Self {
+            start_block_index,
+            end_block_index,
+            tx_outs,
+            key_images,
+            first_global_index,
+        }))
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.end_block_index - self.start_block_index + 1
+    }
+}
+
+/// The keys an account scans blocks with.
+pub(crate) enum SyncKeys {
+    Full(AccountKey),
+    ViewOnly(ViewAccountKey),
+}
+
+impl SyncKeys {
+    /// Decode the keys stored for an account.
+    pub(crate) fn decode(account: &Account) -> Result<Self, mc_util_serial::DecodeError> {
+        Ok(if account.view_only {
+            SyncKeys::ViewOnly(mc_util_serial::decode(&account.account_key)?)
+        } else {
+            SyncKeys::Full(mc_util_serial::decode(&account.account_key)?)
+        })
+    }
+
+    pub(crate) fn view_private_key(&self) -> &RistrettoPrivate {
+        match self {
+            SyncKeys::Full(account_key) => account_key.view_private_key(),
+            SyncKeys::ViewOnly(view_account_key) => view_account_key.view_private_key(),
         }
-        let end_block_index = end_block_index.unwrap();
-
-        if account.view_only {
-            let view_account_key: ViewAccountKey = mc_util_serial::decode(&account.account_key)?;
-
-            // Attempt to decode each transaction as received by this account.
-            let received_txos: Vec<_> = tx_outs
-                .into_par_iter()
-                .filter_map(|(block_index, tx_out)| {
-                    let amount = match decode_amount(&tx_out, view_account_key.view_private_key()) {
-                        None => return None,
-                        Some(a) => a,
-                    };
-                    let subaddress_index = decode_subaddress_index(
-                        &tx_out,
-                        view_account_key.view_private_key(),
-                        &subaddress_keys,
-                    );
-                    Some((block_index, tx_out, amount, subaddress_index))
-                })
-                .collect();
-            let num_received_txos = received_txos.len();
+    }
 
-            // Write received transactions to the database.
-            for (block_index, tx_out, amount, subaddress_index) in received_txos {
-                Txo::create_received(
-                    tx_out.clone(),
-                    subaddress_index,
-                    None,
-                    amount,
-                    block_index,
-                    account_id_hex,
-                    conn,
-                )?;
+    pub(crate) fn subaddress_spend_public_key(&self, index: u64) -> RistrettoPublic {
+        match self {
+            SyncKeys::Full(account_key) => *account_key.subaddress(index).spend_public_key(),
+            SyncKeys::ViewOnly(view_account_key) => {
+                *view_account_key.subaddress(index).spend_public_key()
             }
+        }
+    }
+}
 
-            // Match key images to mark existing unspent transactions as spent.
-            let unspent_key_images: HashMap<KeyImage, String> =
-                Txo::list_unspent_or_pending_key_images(account_id_hex, None, conn)?;
-            let spent_txos: Vec<(u64, String)> = key_images
-                .into_par_iter()
-                .filter_map(|(block_index, key_image)| {
-                    unspent_key_images
-                        .get(&key_image)
-                        .map(|txo_id_hex| (block_index, txo_id_hex.clone()))
-                })
-                .collect();
-            let num_spent_txos = spent_txos.len();
-            for (block_index, txo_id_hex) in &spent_txos {
-                Txo::update_spent_block_index(txo_id_hex, *block_index as u64, conn)?;
-                TransactionLog::update_pending_associated_with_txo_to_succeeded(
-                    txo_id_hex,
-                    *block_index,
-                    conn,
-                )?;
-            }
+/// A txo received by an account: its block index, its index among all tx outs
+/// in the ledger, the txo, its amount, and its subaddress index and key image
+/// if they are known.
+type ReceivedTxo = (u64, u64, TxOut, Amount, Option<u64>, Option<KeyImage>);
 
-            TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
-                end_block_index + 1,
-                conn,
-            )?;
+/// The result of matching a batch of blocks against an account's keys.
+struct ScannedBatch {
+    /// Unassigned subaddresses within the gap limit which received txos in
+    /// the batch.
+    assigned_subaddress_indices: Range<u64>,
 
-            // Done syncing this chunk. Mark these blocks as synced for this account.
-            account.update_next_block_index(end_block_index + 1, conn)?;
+    received_txos: Vec<ReceivedTxo>,
 
-            let num_blocks_synced = end_block_index - start_block_index + 1;
+    /// How long the scan took.
+    duration: Duration,
+}
 
-            let duration = start_time.elapsed();
+/// What an account needs in order to scan blocks. This is read from the
+/// wallet database up front so that scanning, which is the expensive part of
+/// syncing, does not hold a database transaction open.
+struct AccountSyncState {
+    account: Account,
+    keys: SyncKeys,
+    subaddress_keys: HashMap<RistrettoPublic, u64>,
+    first_unassigned_index: u64,
+}
 
-            log::debug!(
-            logger,
-            "Synced {} blocks ({}-{}) for account {} in {:?}. {} txos received, {}/{} txos spent.",
-            num_blocks_synced,
-            start_block_index,
-            end_block_index,
-            account_id_hex.chars().take(6).collect::<String>(),
-            duration,
-            num_received_txos,
-            num_spent_txos,
-            unspent_key_images.len(),
-        );
+impl AccountSyncState {
+    fn load(account: Account, conn: &Conn) -> Result<Self, SyncError> {
+        let keys = SyncKeys::decode(&account)?;
 
-            if num_blocks_synced < BLOCKS_CHUNK_SIZE {
-                Ok(SyncStatus::NoMoreBlocks)
-            } else {
-                Ok(SyncStatus::ChunkFinished)
+        // Load subaddresses for this account into a hash map.
+        let mut subaddress_keys: HashMap<RistrettoPublic, u64> = HashMap::default();
+        let subaddresses: Vec<_> =
+            AssignedSubaddress::list_all(Some(account.id.clone()), None, None, conn)?;
+        for s in subaddresses {
+            let subaddress_key: RistrettoPublic = s.spend_public_key.as_slice().try_into()?;
+            subaddress_keys.insert(subaddress_key, s.subaddress_index as u64);
+        }
+
+        let first_unassigned_index = account.clone().next_subaddress_index(conn)?;
+
+        Ok(Self {
+            account,
+            keys,
+            subaddress_keys,
+            first_unassigned_index,
+        })
+    }
+
+    fn next_block_index(&self) -> u64 {
+        self.account.next_block_index as u64
+    }
+
+    /// Attempt to decode each txo in the batch as received by this account.
+    fn scan(&mut self, batch: &BlockBatch) -> ScannedBatch {
+        let start_time = Instant::now();
+
+        // Find any unassigned subaddresses within the gap limit which received
+        // txos in this batch, so that those txos are not orphaned.
+        let gap_limit = self.account.subaddress_gap_limit as u64;
+        let assigned_subaddress_indices = if gap_limit > 0 {
+            let keys = &self.keys;
+            let highest_index = find_highest_subaddress_in_gap(
+                &batch.tx_outs,
+                keys.view_private_key(),
+                &self.subaddress_keys,
+                self.first_unassigned_index,
+                gap_limit,
+                |index| keys.subaddress_spend_public_key(index),
+            );
+            let assigned_indices =
+                highest_index.map_or(0..0, |h| self.first_unassigned_index..h + 1);
+            for index in assigned_indices.clone() {
+                self.subaddress_keys
+                    .insert(self.keys.subaddress_spend_public_key(index), index);
             }
+            assigned_indices
         } else {
-            let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
-
-            // Attempt to decode each transaction as received by this account.
-            let received_txos: Vec<_> = tx_outs
-                .into_par_iter()
-                .filter_map(|(block_index, tx_out)| {
-                    let amount = match decode_amount(&tx_out, account_key.view_private_key()) {
-                        None => return None,
-                        Some(a) => a,
-                    };
-                    let (subaddress_index, key_image) =
-                        decode_subaddress_and_key_image(&tx_out, &account_key, &subaddress_keys);
-                    Some((block_index, tx_out, amount, subaddress_index, key_image))
-                })
-                .collect();
-            let num_received_txos = received_txos.len();
+            0..0
+        };
+
+        let keys = &self.keys;
+        let subaddress_keys = &self.subaddress_keys;
+        let received_txos: Vec<ReceivedTxo> = batch
+            .tx_outs
+            .par_iter()
+            .enumerate()
+            .filter_map(|(position, (block_index, tx_out))| {
+                let amount = decode_amount(tx_out, keys.view_private_key())?;
+                let (subaddress_index, key_image) = match keys {
+                    SyncKeys::Full(account_key) => {
+                        decode_subaddress_and_key_image(tx_out, account_key, subaddress_keys)
+                    }
+                    SyncKeys::ViewOnly(view_account_key) => (
+                        decode_subaddress_index(
+                            tx_out,
+                            view_account_key.view_private_key(),
+                            subaddress_keys,
+                        ),
+                        None,
+                    ),
+                };
+                Some((
+                    *block_index,
+                    batch.first_global_index + position as u64,
+                    tx_out.clone(),
+                    amount,
+                    subaddress_index,
+                    key_image,
+                ))
+            })
+            .collect();
+
+        ScannedBatch {
+            assigned_subaddress_indices,
+            received_txos,
+            duration: start_time.elapsed(),
+        }
+    }
+
+    /// Write a scanned batch to the wallet database, together with the
+    /// account's next block index, in a single transaction, so that a batch
+    /// is either synced completely or not at all.
+    ///
+    /// If the account's next block index or subaddresses changed since it
+    /// was loaded, for example because it was resynced, nothing is written
+    /// and the batch is reported as stale.
+    fn commit(
+        &self,
+        batch: &BlockBatch,
+        scanned: &ScannedBatch,
+        batch_size: u64,
+        conn: &Conn,
+        logger: &Logger,
+    ) -> Result<SyncStatus, SyncError> {
+        let start_time = Instant::now();
+        let account_id_hex = self.account.id.as_str();
+
+        transaction(conn, || {
+            // Get the account data. If it is no longer available, the account has
+            // been removed and we can simply return.
+            let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
+            if account.next_block_index as u64 != batch.start_block_index
+                || account.clone().next_subaddress_index(conn)? != self.first_unassigned_index
+            {
+                return Ok(SyncStatus::Stale);
+            }
+
+            for index in scanned.assigned_subaddress_indices.clone() {
+                match &self.keys {
+                    SyncKeys::Full(account_key) => {
+                        AssignedSubaddress::create(account_key, index, "", conn)?;
+                    }
+                    SyncKeys::ViewOnly(view_account_key) => {
+                        AssignedSubaddress::create_for_view_only_account(
+                            view_account_key,
+                            index,
+                            "",
+                            conn,
+                        )?;
+                    }
+                }
+            }
+            if !scanned.assigned_subaddress_indices.is_empty() {
+                log::info!(
+                    logger,
+                    "Assigned subaddresses {:?} within the gap limit for account {}",
+                    scanned.assigned_subaddress_indices,
+                    account_id_hex.chars().take(6).collect::<String>(),
+                );
+            }
 
             // Write received transactions to the database.
-            for (block_index, tx_out, amount, subaddress_index, key_image) in received_txos {
+            for (block_index, global_index, tx_out, amount, subaddress_index, key_image) in
+                &scanned.received_txos
+            {
                 Txo::create_received(
                     tx_out.clone(),
-                    subaddress_index,
-                    key_image,
-                    amount,
-                    block_index,
+                    *subaddress_index,
+                    *key_image,
+                    *amount,
+                    *block_index,
+                    Some(*global_index),
                     account_id_hex,
                     conn,
                 )?;
             }
 
+            // Record the first block with activity for this account, so that a
+            // first block index which was set too late can be noticed.
+            let first_received_block_index = scanned
+                .received_txos
+                .iter()
+                .map(|(block_index, ..)| *block_index)
+                .min();
+            if let Some(block_index) = first_received_block_index {
+                let first_activity_block_index = account
+                    .first_activity_block_index
+                    .map_or(block_index, |i| block_index.min(i as u64));
+                account
+                    .update_first_activity_block_index(Some(first_activity_block_index), conn)?;
+            }
+
             // Match key images to mark existing unspent transactions as spent.
-            let unspent_key_images: HashMap<KeyImage, String> =
+            // Accounts sharing this account's view key receive the same txos,
+            // which are recorded under whichever of them received them first,
+            // so their txos are matched as well.
+            let mut unspent_key_images: HashMap<KeyImage, String> =
                 Txo::list_unspent_or_pending_key_images(account_id_hex, None, conn)?;
-            let spent_txos: Vec<(u64, String)> = key_images
-                .into_par_iter()
+            for twin in Account::list_by_view_private_key(self.keys.view_private_key(), conn)? {
+                if twin.id != account_id_hex {
+                    unspent_key_images.extend(Txo::list_unspent_or_pending_key_images(
+                        &twin.id, None, conn,
+                    )?);
+                }
+            }
+            let spent_txos: Vec<(u64, String)> = batch
+                .key_images
+                .par_iter()
                 .filter_map(|(block_index, key_image)| {
                     unspent_key_images
-                        .get(&key_image)
-                        .map(|txo_id_hex| (block_index, txo_id_hex.clone()))
+                        .get(key_image)
+                        .map(|txo_id_hex| (*block_index, txo_id_hex.clone()))
                 })
                 .collect();
             let num_spent_txos = spent_txos.len();
@@ -348,37 +937,36 @@ fn sync_account_next_chunk(
             }
 
             TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
-                end_block_index + 1,
+                account_id_hex,
+                batch.end_block_index + 1,
                 conn,
             )?;
 
             // Done syncing this chunk. Mark these blocks as synced for this account.
-            account.update_next_block_index(end_block_index + 1, conn)?;
-
-            let num_blocks_synced = end_block_index - start_block_index + 1;
+            account.update_next_block_index(batch.end_block_index + 1, conn)?;
 
-            let duration = start_time.elapsed();
+            let num_blocks_synced = batch.num_blocks();
 
             log::debug!(
-            logger,
-            "Synced {} blocks ({}-{}) for account {} in {:?}. {} txos received, {}/{} txos spent.",
-            num_blocks_synced,
-            start_block_index,
-            end_block_index,
-            account_id_hex.chars().take(6).collect::<String>(),
-            duration,
-            num_received_txos,
-            num_spent_txos,
-            unspent_key_images.len(),
-        );
-
-            if num_blocks_synced < BLOCKS_CHUNK_SIZE {
+                logger,
+                "Synced {} blocks ({}-{}) for account {} in {:?}. {} txos received, {}/{} txos spent.",
+                num_blocks_synced,
+                batch.start_block_index,
+                batch.end_block_index,
+                account_id_hex.chars().take(6).collect::<String>(),
+                scanned.duration + start_time.elapsed(),
+                scanned.received_txos.len(),
+                num_spent_txos,
+                unspent_key_images.len(),
+            );
+
+            if num_blocks_synced < batch_size {
                 Ok(SyncStatus::NoMoreBlocks)
             } else {
                 Ok(SyncStatus::ChunkFinished)
             }
-        }
-    })
+        })
+    }
 }
 
 /// Attempt to decode the transaction amount. If we can't, then this transaction
@@ -395,11 +983,11 @@ pub fn decode_amount(tx_out: &TxOut, view_private_key: &RistrettoPrivate) -> Opt
     }
 }
 
-pub fn decode_subaddress_index(
+/// Recover the spend public key of the subaddress which received a TxOut.
+pub fn recover_subaddress_spend_public_key(
     tx_out: &TxOut,
     view_private_key: &RistrettoPrivate,
-    subaddress_keys: &HashMap<RistrettoPublic, u64>,
-) -> Option<u64> {
+) -> Option<RistrettoPublic> {
     let tx_public_key = match RistrettoPublic::try_from(&tx_out.public_key) {
         Ok(k) => k,
         Err(_) => return None,
@@ -409,11 +997,71 @@ pub fn decode_subaddress_index(
         Err(_) => return None,
     };
 
-    let subaddress_spk: RistrettoPublic =
-        recover_public_subaddress_spend_key(view_private_key, &tx_out_target_key, &tx_public_key);
+    Some(recover_public_subaddress_spend_key(
+        view_private_key,
+        &tx_out_target_key,
+        &tx_public_key,
+    ))
+}
+
+pub fn decode_subaddress_index(
+    tx_out: &TxOut,
+    view_private_key: &RistrettoPrivate,
+    subaddress_keys: &HashMap<RistrettoPublic, u64>,
+) -> Option<u64> {
+    let subaddress_spk = recover_subaddress_spend_public_key(tx_out, view_private_key)?;
     subaddress_keys.get(&subaddress_spk).copied()
 }
 
+/// Find the highest unassigned subaddress index which received one of the
+/// given TxOuts, looking at most `gap_limit` indices past the first unassigned
+/// index or past the previous hit.
+///
+/// Returns None if none of the TxOuts were received at an unassigned
+/// subaddress within the gap limit.
+pub fn find_highest_subaddress_in_gap(
+    tx_outs: &[(u64, TxOut)],
+    view_private_key: &RistrettoPrivate,
+    subaddress_keys: &HashMap<RistrettoPublic, u64>,
+    first_unassigned_index: u64,
+    gap_limit: u64,
+    subaddress_spend_public_key: impl Fn(u64) -> RistrettoPublic,
+) -> Option<u64> {
+    // Spend public keys of the subaddresses which received txos belonging to
+    // this account, but which are not assigned yet.
+    let unmatched_spend_public_keys: HashSet<RistrettoPublic> = tx_outs
+        .par_iter()
+        .filter_map(|(_block_index, tx_out)| {
+            decode_amount(tx_out, view_private_key)?;
+            let subaddress_spk = recover_subaddress_spend_public_key(tx_out, view_private_key)?;
+            if subaddress_keys.contains_key(&subaddress_spk) {
+                None
+            } else {
+                Some(subaddress_spk)
+            }
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect();
+
+    if unmatched_spend_public_keys.is_empty() {
+        return None;
+    }
+
+    let mut highest_index = None;
+    let mut window_end = first_unassigned_index + gap_limit;
+    let mut index = first_unassigned_index;
+    while index < window_end {
+        if unmatched_spend_public_keys.contains(&subaddress_spend_public_key(index)) {
+            highest_index = Some(index);
+            window_end = index + 1 + gap_limit;
+        }
+        index += 1;
+    }
+
+    highest_index
+}
+
 /// Attempt to match the target address with one of our subaddresses. This
 /// should only be done on tx-outs that have already had their amounts decoded.
 /// If this fails, then the transaction is "orphaned", meaning we haven't
@@ -449,14 +1097,17 @@ pub fn decode_subaddress_and_key_image(
 mod tests {
     use super::*;
     use crate::{
+        db::txo::TxoStatus,
         service::{account::AccountService, balance::BalanceService, txo::TxoService},
         test_utils::{
             add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
-            MOB,
+            WalletDbTestContext, MOB,
         },
+        util::encoding_helpers::{ristretto_public_to_hex, ristretto_to_hex},
     };
-    use mc_account_keys::{AccountKey, RootEntropy, RootIdentity};
+    use mc_account_keys::{AccountKey, PublicAddress, RootEntropy, RootIdentity};
     use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
     use mc_transaction_core::{tokens::Mob, Token};
     use mc_util_from_random::FromRandom;
     use rand::{rngs::StdRng, SeedableRng};
@@ -511,6 +1162,7 @@ mod tests {
                 "".to_string(),
                 "".to_string(),
                 "".to_string(),
+                false,
             )
             .expect("Could not import account entropy");
 
@@ -549,6 +1201,413 @@ mod tests {
         assert_eq!(balance_pmob.unspent, 250_000_000 * MOB as u128);
     }
 
+    /// Creates an account with the given gap limit, sends it a txo at the
+    /// subaddress 5 past its next unassigned subaddress, and syncs it.
+    ///
+    /// Returns the received txo's subaddress index and status, the subaddress
+    /// index it was sent to, and the account's next subaddress index after the
+    /// sync.
+    const FOG_AUTHORITY_SPKI: &str = "MIICIjANBgkqhkiG9w0BAQEFAAOCAg8AMIICCgKCAgEAvnB9wTbTOT5uoizRYaYbw7XIEkInl8E7MGOAQj+xnC+F1rIXiCnc/t1+5IIWjbRGhWzo7RAwI5sRajn2sT4rRn9NXbOzZMvIqE4hmhmEzy1YQNDnfALAWNQ+WBbYGW+Vqm3IlQvAFFjVN1YYIdYhbLjAPdkgeVsWfcLDforHn6rR3QBZYZIlSBQSKRMY/tywTxeTCvK2zWcS0kbbFPtBcVth7VFFVPAZXhPi9yy1AvnldO6n7KLiupVmojlEMtv4FQkk604nal+j/dOplTATV8a9AJBbPRBZ/yQg57EG2Y2MRiHOQifJx0S5VbNyMm9bkS8TD7Goi59aCW6OT1gyeotWwLg60JRZTfyJ7lYWBSOzh0OnaCytRpSWtNZ6barPUeOnftbnJtE8rFhF7M4F66et0LI/cuvXYecwVwykovEVBKRF4HOK9GgSm17mQMtzrD7c558TbaucOWabYR04uhdAc3s10MkuONWG0wIQhgIChYVAGnFLvSpp2/aQEq3xrRSETxsixUIjsZyWWROkuA0IFnc8d7AmcnUBvRW7FT/5thWyk5agdYUGZ+7C1o69ihR1YxmoGh69fLMPIEOhYh572+3ckgl2SaV4uo9Gvkz8MMGRBcMIMlRirSwhCfozV2RyT5Wn1NgPpyc8zJL7QdOhL7Qxb+5WjnCVrQYHI2cCAwEAAQ==";
+
+    enum GapTestAccount {
+        Full,
+        Fog,
+        ViewOnly,
+    }
+
+    fn sync_txo_received_past_next_subaddress(
+        gap_limit: u64,
+        account_kind: GapTestAccount,
+        logger: Logger,
+    ) -> (Option<i64>, TxoStatus, u64, u64) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &vec![], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let (account, subaddress): (_, Box<dyn Fn(u64) -> PublicAddress>) = match account_kind {
+            GapTestAccount::ViewOnly => {
+                let view_private_key = RistrettoPrivate::from_random(&mut rng);
+                let spend_public_key = RistrettoPublic::from_random(&mut rng);
+                let account = service
+                    .import_view_only_account(
+                        ristretto_to_hex(&view_private_key),
+                        ristretto_public_to_hex(&spend_public_key),
+                        None,
+                        None,
+                        None,
+                        false,
+                    )
+                    .unwrap();
+                let view_account_key = ViewAccountKey::new(view_private_key, spend_public_key);
+                (
+                    account,
+                    Box::new(move |index| view_account_key.subaddress(index)),
+                )
+            }
+            GapTestAccount::Full | GapTestAccount::Fog => {
+                let (fog_report_url, fog_authority_spki) = match account_kind {
+                    GapTestAccount::Fog => ("fog://fog-report.example.com", FOG_AUTHORITY_SPKI),
+                    _ => ("", ""),
+                };
+                let account = service
+                    .create_account(
+                        None,
+                        fog_report_url.to_string(),
+                        "".to_string(),
+                        fog_authority_spki.to_string(),
+                    )
+                    .unwrap();
+                let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+                (
+                    account,
+                    Box::new(move |index| account_key.subaddress(index)),
+                )
+            }
+        };
+        let account_id = AccountID(account.id.clone());
+
+        service
+            .update_account_subaddress_gap_limit(&account_id, gap_limit)
+            .unwrap();
+
+        let next_subaddress_index = service
+            .get_next_subaddress_index_for_account(&account_id)
+            .unwrap();
+        let receiving_subaddress_index = next_subaddress_index + 5;
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[subaddress(receiving_subaddress_index)],
+            100 * MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let txos = service
+            .list_txos(
+                Some(account.id.clone()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(txos.len(), 1);
+        let (txo, status) = txos.into_iter().next().unwrap();
+
+        (
+            txo.subaddress_index,
+            status,
+            receiving_subaddress_index,
+            service
+                .get_next_subaddress_index_for_account(&account_id)
+                .unwrap(),
+        )
+    }
+
+    #[test_with_logger]
+    fn test_sync_assigns_subaddress_within_gap_limit(logger: Logger) {
+        let (subaddress_index, status, receiving_subaddress_index, next_subaddress_index) =
+            sync_txo_received_past_next_subaddress(20, GapTestAccount::Full, logger);
+
+        assert_eq!(subaddress_index, Some(receiving_subaddress_index as i64));
+        assert_eq!(status, TxoStatus::Unspent);
+        assert_eq!(next_subaddress_index, receiving_subaddress_index + 1);
+    }
+
+    #[test_with_logger]
+    fn test_sync_assigns_fog_subaddress_within_gap_limit(logger: Logger) {
+        let (subaddress_index, status, receiving_subaddress_index, next_subaddress_index) =
+            sync_txo_received_past_next_subaddress(20, GapTestAccount::Fog, logger);
+
+        assert_eq!(subaddress_index, Some(receiving_subaddress_index as i64));
+        assert_eq!(status, TxoStatus::Unspent);
+        assert_eq!(next_subaddress_index, receiving_subaddress_index + 1);
+    }
+
+    #[test_with_logger]
+    fn test_sync_assigns_view_only_subaddress_within_gap_limit(logger: Logger) {
+        let (subaddress_index, status, receiving_subaddress_index, next_subaddress_index) =
+            sync_txo_received_past_next_subaddress(20, GapTestAccount::ViewOnly, logger);
+
+        assert_eq!(subaddress_index, Some(receiving_subaddress_index as i64));
+        assert_eq!(status, TxoStatus::Unverified);
+        assert_eq!(next_subaddress_index, receiving_subaddress_index + 1);
+    }
+
+    #[test_with_logger]
+    fn test_sync_does_not_assign_subaddress_past_gap_limit(logger: Logger) {
+        let (subaddress_index, status, receiving_subaddress_index, next_subaddress_index) =
+            sync_txo_received_past_next_subaddress(3, GapTestAccount::Full, logger);
+
+        assert_eq!(subaddress_index, None);
+        assert_eq!(status, TxoStatus::Orphaned);
+        assert_eq!(next_subaddress_index, receiving_subaddress_index - 5);
+    }
+
+    /// Creates a wallet database with an account for each of the given root
+    /// entropies. The wallet has no sync thread, so accounts only sync when
+    /// the test syncs them.
+    fn wallet_with_accounts(entropies: &[RootEntropy], logger: &Logger) -> WalletDb {
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let conn = wallet_db.get_conn().unwrap();
+        for entropy in entropies {
+            Account::create_from_root_entropy(
+                entropy,
+                Some(0),
+                None,
+                None,
+                "",
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                &conn,
+            )
+            .unwrap();
+        }
+        wallet_db
+    }
+
+    /// Builds a ledger in which the given accounts take turns receiving txos
+    /// at their main and change subaddresses, and the first txo received by
+    /// the first account is then spent.
+    fn ledger_with_txos_for(account_keys: &[AccountKey], rng: &mut StdRng) -> LedgerDB {
+        let mut ledger_db = get_test_ledger(5, &[], 12, rng);
+        let first_block_index = ledger_db.num_blocks().unwrap();
+        for i in 0..9 {
+            let account_key = &account_keys[i as usize % account_keys.len()];
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &[account_key.subaddress(0), account_key.change_subaddress()],
+                (i + 1) * MOB,
+                &[KeyImage::from(rng.next_u64())],
+                rng,
+            );
+        }
+
+        let mut subaddress_keys = HashMap::default();
+        subaddress_keys.insert(*account_keys[0].subaddress(0).spend_public_key(), 0);
+        let key_image = ledger_db
+            .get_block_contents(first_block_index)
+            .unwrap()
+            .outputs
+            .iter()
+            .find_map(|tx_out| {
+                decode_subaddress_and_key_image(tx_out, &account_keys[0], &subaddress_keys).1
+            })
+            .unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[account_keys[1].subaddress(0)],
+            50 * MOB,
+            &[key_image],
+            rng,
+        );
+
+        ledger_db
+    }
+
+    fn txos_for(wallet_db: &WalletDb, account_ids: &[AccountID]) -> Vec<Txo> {
+        let conn = wallet_db.get_conn().unwrap();
+        let mut txos: Vec<Txo> = account_ids
+            .iter()
+            .flat_map(|account_id| {
+                Txo::list_for_account(
+                    &account_id.to_string(),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    &conn,
+                )
+                .unwrap()
+            })
+            .collect();
+        txos.sort_by(|a, b| a.id.cmp(&b.id));
+        txos
+    }
+
+    fn random_accounts(
+        num_accounts: usize,
+        rng: &mut StdRng,
+    ) -> (Vec<RootEntropy>, Vec<AccountKey>) {
+        let entropies: Vec<RootEntropy> = (0..num_accounts)
+            .map(|_| RootEntropy::from_random(rng))
+            .collect();
+        let account_keys = entropies
+            .iter()
+            .map(|entropy| AccountKey::from(&RootIdentity::from(entropy)))
+            .collect();
+        (entropies, account_keys)
+    }
+
+    #[test_with_logger]
+    fn test_sync_all_accounts_in_batches_matches_sync_account(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let (entropies, account_keys) = random_accounts(3, &mut rng);
+        let account_ids: Vec<AccountID> = account_keys.iter().map(AccountID::from).collect();
+        let ledger_db = ledger_with_txos_for(&account_keys, &mut rng);
+        let num_blocks = ledger_db.num_blocks().unwrap();
+
+        // Sync each account on its own, in chunks of the default size.
+        let expected_wallet_db = wallet_with_accounts(&entropies, &logger);
+        for account_id in &account_ids {
+            sync_account(
+                &ledger_db,
+                &expected_wallet_db,
+                &account_id.to_string(),
+                &logger,
+            )
+            .unwrap();
+        }
+
+        // Sync all accounts together, in batches smaller than the ledger.
+        let wallet_db = wallet_with_accounts(&entropies, &logger);
+        let config = SyncConfig {
+            batch_size: 4,
+            num_workers: 2,
+            batch_pause: Duration::from_millis(0),
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.num_workers)
+            .build()
+            .unwrap();
+        let stats = SyncStats::default();
+        sync_all_accounts_in_batches(
+            &ledger_db,
+            &wallet_db,
+            &config,
+            &pool,
+            &stats,
+            &AtomicBool::new(false),
+            &logger,
+        )
+        .unwrap();
+
+        let txos = txos_for(&wallet_db, &account_ids);
+        assert_eq!(txos, txos_for(&expected_wallet_db, &account_ids));
+        assert_eq!(txos.len(), 19);
+        assert_eq!(
+            txos.iter()
+                .filter(|txo| txo.spent_block_index.is_some())
+                .count(),
+            1
+        );
+
+        let conn = wallet_db.get_conn().unwrap();
+        for account_id in &account_ids {
+            let account = Account::get(account_id, &conn).unwrap();
+            assert_eq!(account.next_block_index as u64, num_blocks);
+
+            let account_stats = stats.get(&account_id.to_string()).unwrap();
+            assert_eq!(account_stats.blocks_synced, num_blocks);
+            assert_eq!(
+                account_stats.last_batch_blocks,
+                num_blocks % config.batch_size
+            );
+        }
+        assert_eq!(
+            stats
+                .get(&account_ids[0].to_string())
+                .unwrap()
+                .txos_received,
+            6
+        );
+    }
+
+    #[test_with_logger]
+    fn test_sync_resumes_after_interrupted_batch(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let (entropies, account_keys) = random_accounts(2, &mut rng);
+        let account_ids: Vec<AccountID> = account_keys.iter().map(AccountID::from).collect();
+        let ledger_db = ledger_with_txos_for(&account_keys, &mut rng);
+
+        let expected_wallet_db = wallet_with_accounts(&entropies, &logger);
+        sync_all_accounts(&ledger_db, &expected_wallet_db, &logger).unwrap();
+
+        // Simulate a crash partway through writing a batch: one of the batch's
+        // txos is in the database, but the account's next block index was
+        // never advanced past it.
+        let wallet_db = wallet_with_accounts(&entropies, &logger);
+        let conn = wallet_db.get_conn().unwrap();
+        let account = Account::get(&account_ids[0], &conn).unwrap();
+        let mut state = AccountSyncState::load(account, &conn).unwrap();
+        let batch = BlockBatch::load(&ledger_db, 0, BLOCKS_CHUNK_SIZE)
+            .unwrap()
+            .unwrap();
+        let scanned = state.scan(&batch);
+        assert!(scanned.received_txos.len() > 1);
+        let (block_index, global_index, tx_out, amount, subaddress_index, key_image) =
+            scanned.received_txos[0].clone();
+        Txo::create_received(
+            tx_out,
+            subaddress_index,
+            key_image,
+            amount,
+            block_index,
+            Some(global_index),
+            &account_ids[0].to_string(),
+            &conn,
+        )
+        .unwrap();
+        assert_eq!(
+            Account::get(&account_ids[0], &conn)
+                .unwrap()
+                .next_block_index,
+            0
+        );
+
+        // Syncing again picks up where the account's next block index says,
+        // without duplicating the txo written before the crash.
+        sync_all_accounts(&ledger_db, &wallet_db, &logger).unwrap();
+        assert_eq!(
+            txos_for(&wallet_db, &account_ids),
+            txos_for(&expected_wallet_db, &account_ids)
+        );
+    }
+
+    #[test_with_logger]
+    fn test_stale_batch_is_not_written(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let (entropies, account_keys) = random_accounts(1, &mut rng);
+        let account_id = AccountID::from(&account_keys[0]);
+        let ledger_db = ledger_with_txos_for(&account_keys, &mut rng);
+        let wallet_db = wallet_with_accounts(&entropies, &logger);
+        let conn = wallet_db.get_conn().unwrap();
+
+        let account = Account::get(&account_id, &conn).unwrap();
+        let mut state = AccountSyncState::load(account.clone(), &conn).unwrap();
+        let batch = BlockBatch::load(&ledger_db, 0, BLOCKS_CHUNK_SIZE)
+            .unwrap()
+            .unwrap();
+        let scanned = state.scan(&batch);
+        assert!(!scanned.received_txos.is_empty());
+
+        // The account moves while the batch is being scanned.
+        account.update_next_block_index(5, &conn).unwrap();
+
+        let status = state
+            .commit(&batch, &scanned, BLOCKS_CHUNK_SIZE, &conn, &logger)
+            .unwrap();
+        assert!(matches!(status, SyncStatus::Stale));
+        assert!(txos_for(&wallet_db, &[account_id.clone()]).is_empty());
+        assert_eq!(
+            Account::get(&account_id, &conn).unwrap().next_block_index,
+            5
+        );
+    }
+
     // #[test_with_logger]
     // fn test_sync_view_only_account(logger: Logger) {
     //     let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
@@ -5,27 +5,44 @@
 use crate::{
     db::{
         account::{AccountID, AccountModel},
-        models::{Account, TransactionLog},
+        assigned_subaddress::AssignedSubaddressModel,
+        idempotency_key::{idempotency_key_cutoff, unix_timestamp_now, IdempotencyKeyModel},
+        models::{Account, AssignedSubaddress, IdempotencyKey, MonitoredTxo, TransactionLog, Txo},
+        monitored_txo::MonitoredTxoModel,
         transaction,
-        transaction_log::{AssociatedTxos, TransactionLogModel, ValueMap},
-        WalletDbError,
+        transaction_log::{AssociatedTxos, TransactionID, TransactionLogModel, TxStatus, ValueMap},
+        txo::{TxoModel, TxoStatus},
+        Conn, InsufficientFundsAmounts, InsufficientInputFundsAmounts, WalletDbError,
     },
     error::WalletTransactionBuilderError,
     json_rpc::v2::models::amount::Amount as AmountJSON,
     service::{
-        ledger::LedgerService, models::tx_proposal::TxProposal,
-        transaction_builder::WalletTransactionBuilder, WalletService,
+        ledger::LedgerService,
+        models::tx_proposal::{InputTxo, OutputTxo, TxProposal},
+        transaction_builder::{BuildPreview, WalletTransactionBuilder},
+        transaction_report::TransactionReportServiceError,
+        WalletService,
+    },
+    util::{
+        b58::{b58_decode_public_address, B58Error},
+        constants::AUTO_REBUILD_INTERVAL_SECONDS,
     },
-    util::b58::{b58_decode_public_address, B58Error},
 };
 use mc_account_keys::AccountKey;
-use mc_common::logger::log;
+use mc_common::{
+    logger::{log, Logger},
+    ResponderId,
+};
 use mc_connection::{BlockchainConnection, RetryableUserTxConnection, UserTxConnection};
+use mc_crypto_digestible::{Digestible, MerlinTranscript};
 use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
 use mc_transaction_core::{
     constants::{MAX_INPUTS, MAX_OUTPUTS},
+    ring_signature::KeyImage,
     tokens::Mob,
-    Amount, Token, TokenId,
+    validation::TransactionValidationError,
+    Amount, BlockVersion, Token, TokenId,
 };
 use mc_transaction_std::{
     BurnRedemptionMemo, BurnRedemptionMemoBuilder, MemoBuilder, RTHMemoBuilder,
@@ -36,11 +53,22 @@ use crate::{
     fog_resolver::FullServiceFogResolver,
     service::address::{AddressService, AddressServiceError},
     unsigned_tx::UnsignedTx,
+    util::build_guard::BuildBusy,
 };
 use displaydoc::Display;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
-use std::{convert::TryFrom, iter::empty, sync::atomic::Ordering};
+use std::{
+    convert::TryFrom,
+    fmt,
+    iter::empty,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
 /// Errors for the Transaction Service.
 #[derive(Display, Debug)]
@@ -69,6 +97,9 @@ pub enum TransactionServiceError {
     /// No peers configured.
     NoPeersConfigured,
 
+    /// Error reporting the transaction: {0}
+    TransactionReport(TransactionReportServiceError),
+
     /// Error converting to/from API protos: {0}
     ProtoConversion(mc_api::ConversionError),
 
@@ -78,6 +109,9 @@ pub enum TransactionServiceError {
     /// Cannot complete this action in offline mode.
     Offline,
 
+    /// Dry runs need the wallet to be connected through a validator service
+    DryRunUnavailable,
+
     /// Connection Error
     Connection(retry::Error<mc_connection::Error>),
 
@@ -107,6 +141,79 @@ pub enum TransactionServiceError {
 
     /// mc_util_serial decode error: {0}
     Decode(mc_util_serial::DecodeError),
+
+    /// An idempotency key can only be used when submitting for an account
+    IdempotencyKeyRequiresAccount,
+
+    /// Missing confirmation number for output txo: {0}
+    MissingConfirmationNumber(String),
+
+    /// Too many transaction builds are queued for this account, retry after {0:?}
+    BuildQueueFull(Duration),
+
+    /// Timed out waiting for other transaction builds to finish, retry after {0:?}
+    BuildWaitTimedOut(Duration),
+
+    /// Block version mismatch, rebuild required: built for {proposal}, network is at {network}
+    BlockVersionMismatch { proposal: u32, network: u32 },
+
+    /// Debug build reports are not enabled on this wallet
+    DebugBuildReportDisabled,
+
+    /// Recipient {0} may not be on this wallet's network, pass allow_cross_network to send anyway: {1}
+    CrossNetworkAddress(String, String),
+
+    /// Account {0} cannot sign transactions in this wallet, because its keys are from a {1} source. Build an unsigned transaction and sign it where the spend key is held
+    AccountCannotSignLocally(String, String),
+
+    /// Invalid selection token: {0}
+    InvalidSelectionToken(String),
+
+    /// The selection token was made for account {0}
+    SelectionTokenAccountMismatch(String),
+
+    /// Txos of the selection token can no longer be spent, preview the transaction again: {0}
+    SelectionNoLongerSpendable(String),
+}
+
+impl TransactionServiceError {
+    /// The amounts involved, if this error was caused by insufficient funds.
+    pub fn insufficient_funds(&self) -> Option<&InsufficientFundsAmounts> {
+        match self {
+            Self::Database(e) => e.insufficient_funds(),
+            Self::TransactionBuilder(e) => e.insufficient_funds(),
+            _ => None,
+        }
+    }
+
+    /// The amounts involved, if this error was caused by the chosen inputs
+    /// not covering the transaction.
+    pub fn insufficient_input_funds(&self) -> Option<&InsufficientInputFundsAmounts> {
+        match self {
+            Self::TransactionBuilder(e) => e.insufficient_input_funds(),
+            _ => None,
+        }
+    }
+
+    /// How long the client should wait before retrying, if this error was
+    /// caused by the service being busy.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::BuildQueueFull(retry_after) | Self::BuildWaitTimedOut(retry_after) => {
+                Some(*retry_after)
+            }
+            _ => None,
+        }
+    }
+
+    /// What the client should do about a transaction the network did not
+    /// accept, if this error was caused by submitting it.
+    pub fn submission_error_category(&self) -> Option<SubmissionErrorCategory> {
+        match self {
+            Self::Connection(e) => Some(SubmissionErrorCategory::from(e)),
+            _ => None,
+        }
+    }
 }
 
 impl From<WalletDbError> for TransactionServiceError {
@@ -175,11 +282,149 @@ impl From<mc_util_serial::DecodeError> for TransactionServiceError {
     }
 }
 
+impl From<TransactionReportServiceError> for TransactionServiceError {
+    fn from(src: TransactionReportServiceError) -> Self {
+        Self::TransactionReport(src)
+    }
+}
+
+impl From<BuildBusy> for TransactionServiceError {
+    fn from(src: BuildBusy) -> Self {
+        match src {
+            BuildBusy::QueueFull { retry_after } => Self::BuildQueueFull(retry_after),
+            BuildBusy::WaitTimedOut { retry_after } => Self::BuildWaitTimedOut(retry_after),
+        }
+    }
+}
+
+/// Why a peer did not accept a submitted transaction, grouped by what the
+/// client should do about it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SubmissionErrorCategory {
+    /// The peer could not be reached.
+    RetryableNetwork,
+
+    /// The fee is below the network's minimum fee.
+    NeedsRebuildFee,
+
+    /// The tombstone block has passed, or is too far in the future.
+    NeedsRebuildTombstone,
+
+    /// An input has already been spent.
+    PermanentDoubleSpend,
+
+    /// The secure connection to the peer's enclave failed.
+    AttestationFailure,
+
+    /// Any other rejection.
+    Unknown,
+}
+
+impl SubmissionErrorCategory {
+    /// The machine readable code of the category.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::RetryableNetwork => "retryable_network",
+            Self::NeedsRebuildFee => "needs_rebuild_fee",
+            Self::NeedsRebuildTombstone => "needs_rebuild_tombstone",
+            Self::PermanentDoubleSpend => "permanent_double_spend",
+            Self::AttestationFailure => "attestation_failure",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// What the client should do next.
+    pub fn suggested_action(&self) -> &'static str {
+        match self {
+            Self::RetryableNetwork => "Submit the same transaction again.",
+            Self::NeedsRebuildFee => {
+                "Build the transaction again with the network's current minimum fee."
+            }
+            Self::NeedsRebuildTombstone => {
+                "Build the transaction again with a new tombstone block."
+            }
+            Self::PermanentDoubleSpend => {
+                "Do not retry. Sync the account and check whether the payment was already made."
+            }
+            Self::AttestationFailure => {
+                "Submit the same transaction again. If it persists, check the consensus enclave measurements the wallet is configured with."
+            }
+            Self::Unknown => "Do not retry without investigating the details.",
+        }
+    }
+
+    /// Whether the same transaction may be accepted by another peer.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RetryableNetwork | Self::AttestationFailure)
+    }
+}
+
+impl From<&retry::Error<mc_connection::Error>> for SubmissionErrorCategory {
+    fn from(src: &retry::Error<mc_connection::Error>) -> Self {
+        let error = match src {
+            retry::Error::Operation { error, .. } => error,
+            retry::Error::Internal(_) => return Self::Unknown,
+        };
+        match error {
+            mc_connection::Error::Grpc(_) => Self::RetryableNetwork,
+            mc_connection::Error::Attestation(_) | mc_connection::Error::Cipher(_) => {
+                Self::AttestationFailure
+            }
+            mc_connection::Error::TransactionValidation(err) => match err {
+                TransactionValidationError::TxFeeError => Self::NeedsRebuildFee,
+                TransactionValidationError::TombstoneBlockExceeded
+                | TransactionValidationError::TombstoneBlockTooFar => Self::NeedsRebuildTombstone,
+                TransactionValidationError::ContainsSpentKeyImage => Self::PermanentDoubleSpend,
+                _ => Self::Unknown,
+            },
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Proposes a transaction to the peer at the given offset into the responder
+/// ids. When more than one peer is configured, a failure which another peer
+/// may not have is retried once, on the next peer.
+fn propose_with_failover<F>(
+    responder_ids: &[ResponderId],
+    offset: usize,
+    logger: &Logger,
+    mut propose: F,
+) -> Result<u64, TransactionServiceError>
+where
+    F: FnMut(&ResponderId) -> Result<u64, TransactionServiceError>,
+{
+    let responder_id = &responder_ids[offset % responder_ids.len()];
+    match propose(responder_id) {
+        Err(err)
+            if responder_ids.len() > 1
+                && err
+                    .submission_error_category()
+                    .map_or(false, |category| category.is_retryable()) =>
+        {
+            let retry_responder_id = &responder_ids[(offset + 1) % responder_ids.len()];
+            log::warn!(
+                logger,
+                "Submitting to {} failed, retrying on {}: {}",
+                responder_id,
+                retry_responder_id,
+                err
+            );
+            propose(retry_responder_id)
+        }
+        result => result,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub enum TransactionMemo {
     /// Recoverable Transaction History memo.
     RTH,
 
+    /// Recoverable Transaction History memo, with the id of the payment
+    /// request being paid.
+    RTHWithPaymentRequestId(u64),
+
     /// Burn Redemption memo, with an optional 64 byte redemption memo hex
     /// string.
     #[serde(with = "BigArray")]
@@ -195,6 +440,13 @@ impl TransactionMemo {
                 memo_builder.enable_destination_memo();
                 Box::new(memo_builder)
             }
+            Self::RTHWithPaymentRequestId(payment_request_id) => {
+                let mut memo_builder = RTHMemoBuilder::default();
+                memo_builder.set_sender_credential(SenderMemoCredential::from(account_key));
+                memo_builder.set_payment_request_id(*payment_request_id);
+                memo_builder.enable_destination_memo();
+                Box::new(memo_builder)
+            }
             Self::BurnRedemption(memo_data) => {
                 let mut memo_builder = BurnRedemptionMemoBuilder::new(*memo_data);
                 memo_builder.enable_destination_memo();
@@ -202,6 +454,97 @@ impl TransactionMemo {
             }
         }
     }
+
+    /// The payment request id put in the memos, if any.
+    pub fn payment_request_id(&self) -> Option<u64> {
+        match self {
+            Self::RTHWithPaymentRequestId(payment_request_id) => Some(*payment_request_id),
+            _ => None,
+        }
+    }
+
+    /// The burn redemption memo data, if any.
+    pub fn burn_redemption_memo(&self) -> Option<&[u8]> {
+        match self {
+            Self::BurnRedemption(memo_data) => Some(memo_data),
+            _ => None,
+        }
+    }
+
+    /// The memo recorded for a transaction log built by this wallet, so that
+    /// a rebuild puts the same memos on chain.
+    pub fn for_transaction_log(transaction_log: &TransactionLog) -> Self {
+        if let Some(payment_request_id) = transaction_log.payment_request_id {
+            return Self::RTHWithPaymentRequestId(payment_request_id as u64);
+        }
+        match &transaction_log.burn_redemption_memo {
+            Some(memo) if memo.len() == BurnRedemptionMemo::MEMO_DATA_LEN => {
+                let mut memo_data = [0; BurnRedemptionMemo::MEMO_DATA_LEN];
+                memo_data.copy_from_slice(memo);
+                Self::BurnRedemption(memo_data)
+            }
+            _ => Self::RTH,
+        }
+    }
+}
+
+/// The inputs, fee, change and tombstone a transaction would have, from
+/// preview_transaction.
+#[derive(Clone, Debug)]
+pub struct TransactionPreview {
+    /// What the transaction would spend and pay.
+    pub build_preview: BuildPreview,
+
+    /// Opaque token which builds the transaction from exactly the previewed
+    /// inputs, when given to resolve_selection_token.
+    pub selection_token: String,
+}
+
+/// The contents of a selection token, which is the hex encoded JSON of this.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct SelectionToken {
+    account_id: String,
+    txo_ids: Vec<String>,
+}
+
+impl SelectionToken {
+    fn encode(&self) -> Result<String, TransactionServiceError> {
+        let json = serde_json::to_vec(self)
+            .map_err(|e| TransactionServiceError::InvalidSelectionToken(e.to_string()))?;
+        Ok(hex::encode(json))
+    }
+
+    fn decode(selection_token: &str) -> Result<Self, TransactionServiceError> {
+        let json = hex::decode(selection_token)
+            .map_err(|e| TransactionServiceError::InvalidSelectionToken(e.to_string()))?;
+        serde_json::from_slice(&json)
+            .map_err(|e| TransactionServiceError::InvalidSelectionToken(e.to_string()))
+    }
+}
+
+/// Whether submit_transaction broadcast a transaction, or found that the
+/// account had already submitted it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubmitOutcome {
+    /// The transaction was submitted to consensus.
+    Submitted,
+
+    /// The transaction was already submitted and has not landed yet, so it
+    /// was not submitted again.
+    AlreadyPending,
+
+    /// The transaction was already submitted and has landed in the ledger.
+    AlreadyLanded,
+}
+
+impl fmt::Display for SubmitOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitOutcome::Submitted => write!(f, "submitted"),
+            SubmitOutcome::AlreadyPending => write!(f, "already_pending"),
+            SubmitOutcome::AlreadyLanded => write!(f, "already_landed"),
+        }
+    }
 }
 
 /// Trait defining the ways in which the wallet can interact with and manage
@@ -217,6 +560,8 @@ pub trait TransactionService {
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
         memo: TransactionMemo,
     ) -> Result<(UnsignedTx, FullServiceFogResolver), TransactionServiceError>;
 
@@ -230,16 +575,70 @@ pub trait TransactionService {
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
+        comment: Option<String>,
         memo: TransactionMemo,
     ) -> Result<TxProposal, TransactionServiceError>;
 
+    /// Selects the inputs of a transaction and works out its fee, change and
+    /// tombstone as build_transaction would, without fetching membership
+    /// proofs or sampling rings.
+    #[allow(clippy::too_many_arguments)]
+    fn preview_transaction(
+        &self,
+        account_id_hex: &str,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
+    ) -> Result<TransactionPreview, TransactionServiceError>;
+
+    /// The txo ids of a selection token from preview_transaction, to build
+    /// with as inputs. Fails if the token was made for another account, or if
+    /// any of its txos has since been spent or become part of a pending
+    /// transaction.
+    fn resolve_selection_token(
+        &self,
+        account_id_hex: &str,
+        selection_token: &str,
+    ) -> Result<Vec<String>, TransactionServiceError>;
+
     /// Submits a pre-built TxProposal to the MobileCoin Consensus Network.
+    ///
+    /// If an idempotency key is given, a retry with the same key returns the
+    /// transaction log from the first submission instead of submitting again.
+    ///
+    /// When an account is given, a transaction which the account has already
+    /// submitted is not submitted again unless force_resubmit is set. Its
+    /// transaction log is returned instead, with whether it is still pending
+    /// or has landed.
     fn submit_transaction(
         &self,
         tx_proposal: &TxProposal,
         comment: Option<String>,
         account_id_hex: Option<String>,
-    ) -> Result<Option<(TransactionLog, AssociatedTxos, ValueMap)>, TransactionServiceError>;
+        idempotency_key: Option<String>,
+        force_resubmit: bool,
+    ) -> Result<
+        Option<(TransactionLog, AssociatedTxos, ValueMap, SubmitOutcome)>,
+        TransactionServiceError,
+    >;
+
+    /// Checks a pre-built TxProposal with the validator service the wallet
+    /// syncs through, as consensus would check it, without submitting it to
+    /// the network. Returns the number of blocks in the validator's ledger.
+    /// Rejections are the same errors as when submitting.
+    fn propose_tx_dry_run(&self, tx_proposal: &TxProposal) -> Result<u64, TransactionServiceError>;
+
+    /// Builds, signs and submits a transaction.
+    ///
+    /// If an idempotency key is given, a retry with the same key returns the
+    /// transaction log from the first request instead of building a new
+    /// transaction.
 
     #[allow(clippy::too_many_arguments)]
     fn build_sign_and_submit_transaction(
@@ -251,9 +650,42 @@ pub trait TransactionService {
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
         comment: Option<String>,
         memo: TransactionMemo,
+        idempotency_key: Option<String>,
     ) -> Result<(TransactionLog, AssociatedTxos, ValueMap, TxProposal), TransactionServiceError>;
+
+    /// Checks that each recipient is on the network the wallet is connected
+    /// to, failing on the first which is not unless cross network sends are
+    /// allowed. Recipients which cannot be checked are logged and allowed.
+    fn check_recipient_networks(
+        &self,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        allow_cross_network: bool,
+    ) -> Result<(), TransactionServiceError>;
+
+    /// Have a submitted transaction rebuilt and resubmitted up to
+    /// max_attempts times if it expires before landing in the ledger, raising
+    /// the fee by fee_bump_percent each time.
+    fn set_auto_rebuild(
+        &self,
+        transaction_log_id: &str,
+        max_attempts: u32,
+        fee_bump_percent: u32,
+    ) -> Result<TransactionLog, TransactionServiceError>;
+
+    /// Rebuild and resubmit the expired transactions which have automatic
+    /// rebuilds left, paying the same outlays from fresh inputs. A rebuild is
+    /// not retried once the account has insufficient funds for it. Returns
+    /// the transaction logs of the resubmitted transactions.
+    ///
+    /// A transaction is only rebuilt once its account has synced past its
+    /// tombstone block and the ledger holds neither its inputs' key images
+    /// nor its outputs, so that a payment which landed is never paid twice.
+    /// One found in the ledger is marked succeeded instead.
+    fn rebuild_expired_transactions(&self) -> Result<Vec<TransactionLog>, TransactionServiceError>;
 }
 
 impl<T, FPR> TransactionService for WalletService<T, FPR>
@@ -270,56 +702,30 @@ where
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
         memo: TransactionMemo,
     ) -> Result<(UnsignedTx, FullServiceFogResolver), TransactionServiceError> {
         validate_number_inputs(input_txo_ids.unwrap_or(&Vec::new()).len() as u64)?;
         validate_number_outputs(addresses_and_amounts.len() as u64)?;
 
+        let _build_permit = self.build_guard.acquire(account_id_hex)?;
+        let logger = self.request_logger();
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
-            let mut builder = WalletTransactionBuilder::new(
-                account_id_hex.to_string(),
-                self.ledger_db.clone(),
-                self.fog_resolver_factory.clone(),
-            );
-
-            let mut default_fee_token_id = Mob::ID;
-
-            for (recipient_public_address, amount) in addresses_and_amounts {
-                if !self.verify_address(recipient_public_address)? {
-                    return Err(TransactionServiceError::InvalidPublicAddress(
-                        recipient_public_address.to_string(),
-                    ));
-                };
-                let recipient = b58_decode_public_address(recipient_public_address)?;
-                let amount =
-                    Amount::try_from(amount).map_err(TransactionServiceError::InvalidAmount)?;
-                builder.add_recipient(recipient, amount.value, amount.token_id)?;
-                default_fee_token_id = amount.token_id;
-            }
-
-            if let Some(tombstone) = tombstone_block {
-                builder.set_tombstone(tombstone.parse::<u64>()?)?;
-            } else {
-                builder.set_tombstone(0)?;
-            }
-
-            let fee_token_id = match fee_token_id {
-                Some(t) => TokenId::from(t.parse::<u64>()?),
-                None => default_fee_token_id,
-            };
-
-            let fee_value = match fee_value {
-                Some(f) => f.parse::<u64>()?,
-                None => *self.get_network_fees().get(&fee_token_id).ok_or(
-                    TransactionServiceError::DefaultFeeNotFoundForToken(fee_token_id),
-                )?,
-            };
-
-            builder.set_fee(fee_value, fee_token_id)?;
-
-            builder.set_block_version(self.get_network_block_version());
-
+            let mut builder = self.configured_builder(
+                account_id_hex,
+                addresses_and_amounts,
+                fee_value,
+                fee_token_id,
+                tombstone_block,
+                source_subaddress_b58,
+                change_to_source,
+                &logger,
+                &conn,
+            )?;
+
+            let selection_start = Instant::now();
             if let Some(inputs) = input_txo_ids {
                 builder.set_txos(&conn, inputs)?;
             } else {
@@ -330,6 +736,12 @@ where
                 };
                 builder.select_txos(&conn, max_spendable)?;
             }
+            log::debug!(
+                logger,
+                "Selected inputs for account {} in {:?}",
+                account_id_hex,
+                selection_start.elapsed()
+            );
 
             let fog_resolver = builder.get_fs_fog_resolver(&conn)?;
             let unsigned_tx = builder.build(memo)?;
@@ -347,8 +759,20 @@ where
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
+        comment: Option<String>,
         memo: TransactionMemo,
     ) -> Result<TxProposal, TransactionServiceError> {
+        // Fail before building when the transaction could not be signed.
+        check_can_sign_locally(&Account::get(
+            &AccountID(account_id_hex.to_string()),
+            &self.wallet_db.get_conn()?,
+        )?)?;
+
+        // Hold the permit until the proposal is logged, so that the next build
+        // for this account sees its inputs as pending.
+        let _build_permit = self.build_guard.acquire(account_id_hex)?;
         let (unsigned_tx, fog_resolver) = self.build_transaction(
             account_id_hex,
             addresses_and_amounts,
@@ -357,81 +781,230 @@ where
             fee_token_id,
             tombstone_block,
             max_spendable_value,
-            memo,
+            source_subaddress_b58,
+            change_to_source,
+            memo.clone(),
         )?;
         let conn = self.wallet_db.get_conn()?;
         transaction(&conn, || {
             let account = Account::get(&AccountID(account_id_hex.to_string()), &conn)?;
-            let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
-
-            let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver)?;
+            let tx_proposal = self.sign_transaction(unsigned_tx, fog_resolver, &account, &conn)?;
 
-            TransactionLog::log_built(tx_proposal.clone(), "".to_string(), account_id_hex, &conn)?;
+            TransactionLog::log_built(
+                tx_proposal.clone(),
+                comment.clone().unwrap_or_default(),
+                &memo,
+                account_id_hex,
+                &conn,
+            )?;
 
             Ok(tx_proposal)
         })
     }
 
+    fn preview_transaction(
+        &self,
+        account_id_hex: &str,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
+    ) -> Result<TransactionPreview, TransactionServiceError> {
+        validate_number_outputs(addresses_and_amounts.len() as u64)?;
+
+        let logger = self.request_logger();
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let mut builder = self.configured_builder(
+                account_id_hex,
+                addresses_and_amounts,
+                fee_value,
+                fee_token_id,
+                tombstone_block,
+                source_subaddress_b58,
+                change_to_source,
+                &logger,
+                &conn,
+            )?;
+
+            let max_spendable = match max_spendable_value {
+                Some(msv) => Some(msv.parse::<u64>()?),
+                None => None,
+            };
+            builder.select_txos(&conn, max_spendable)?;
+            let build_preview = builder.preview()?;
+
+            let selection_token = SelectionToken {
+                account_id: account_id_hex.to_string(),
+                txo_ids: build_preview
+                    .inputs
+                    .iter()
+                    .map(|txo| txo.id.clone())
+                    .collect(),
+            }
+            .encode()?;
+
+            Ok(TransactionPreview {
+                build_preview,
+                selection_token,
+            })
+        })
+    }
+
+    fn resolve_selection_token(
+        &self,
+        account_id_hex: &str,
+        selection_token: &str,
+    ) -> Result<Vec<String>, TransactionServiceError> {
+        let selection_token = SelectionToken::decode(selection_token)?;
+        if selection_token.account_id != account_id_hex {
+            return Err(TransactionServiceError::SelectionTokenAccountMismatch(
+                selection_token.account_id,
+            ));
+        }
+
+        let conn = self.wallet_db.get_conn()?;
+        let mut unspendable = Vec::new();
+        for txo_id in selection_token.txo_ids.iter() {
+            let status = match Txo::get(txo_id, &conn) {
+                Ok(txo) if txo.account_id.as_deref() == Some(account_id_hex) => {
+                    txo.status(&conn)?
+                }
+                Ok(_) | Err(WalletDbError::TxoNotFound(_)) => {
+                    unspendable.push(format!("{} is not found", txo_id));
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+            // Unverified txos are those of view only accounts, which are
+            // spent by signing elsewhere.
+            if status != TxoStatus::Unspent && status != TxoStatus::Unverified {
+                unspendable.push(format!("{} is {}", txo_id, status));
+            }
+        }
+        if !unspendable.is_empty() {
+            return Err(TransactionServiceError::SelectionNoLongerSpendable(
+                unspendable.join(", "),
+            ));
+        }
+
+        Ok(selection_token.txo_ids)
+    }
+
     fn submit_transaction(
         &self,
         tx_proposal: &TxProposal,
         comment: Option<String>,
         account_id_hex: Option<String>,
-    ) -> Result<Option<(TransactionLog, AssociatedTxos, ValueMap)>, TransactionServiceError> {
-        if self.offline {
-            return Err(TransactionServiceError::Offline);
+        idempotency_key: Option<String>,
+        force_resubmit: bool,
+    ) -> Result<
+        Option<(TransactionLog, AssociatedTxos, ValueMap, SubmitOutcome)>,
+        TransactionServiceError,
+    > {
+        let _build_permit = match &account_id_hex {
+            Some(account_id_hex) => Some(self.build_guard.acquire(account_id_hex)?),
+            None => None,
+        };
+
+        if let (Some(account_id_hex), false) = (&account_id_hex, force_resubmit) {
+            if let Some(submitted) = self.get_submitted_duplicate(tx_proposal, account_id_hex)? {
+                return Ok(Some(submitted));
+            }
         }
 
-        // Pick a peer to submit to.
-        let responder_ids = self.peer_manager.responder_ids();
-        if responder_ids.is_empty() {
-            return Err(TransactionServiceError::NoPeersConfigured);
+        let submitted = |result: Option<(TransactionLog, AssociatedTxos, ValueMap)>| {
+            result.map(|(transaction_log, associated_txos, value_map)| {
+                (
+                    transaction_log,
+                    associated_txos,
+                    value_map,
+                    SubmitOutcome::Submitted,
+                )
+            })
+        };
+
+        let (idempotency_key, account_id_hex) = match (idempotency_key, account_id_hex) {
+            (Some(idempotency_key), Some(account_id_hex)) => (idempotency_key, account_id_hex),
+            (Some(_), None) => return Err(TransactionServiceError::IdempotencyKeyRequiresAccount),
+            (None, account_id_hex) => {
+                return self
+                    .propose_transaction(tx_proposal, comment, account_id_hex)
+                    .map(submitted)
+                    .map_err(TransactionServiceError::from)
+            }
+        };
+
+        let request_fingerprint =
+            request_fingerprint(&(TransactionID::from(&tx_proposal.tx).to_string(), &comment));
+        if let Some(transaction_log) =
+            self.claim_idempotency_key(&account_id_hex, &idempotency_key, &request_fingerprint)?
+        {
+            let conn = self.wallet_db.get_conn()?;
+            let associated_txos = transaction_log.get_associated_txos(&conn)?;
+            let value_map = transaction_log.value_map(&conn)?;
+            return Ok(Some((
+                transaction_log,
+                associated_txos,
+                value_map,
+                SubmitOutcome::Submitted,
+            )));
         }
 
-        let idx = self.submit_node_offset.fetch_add(1, Ordering::SeqCst);
-        let responder_id = &responder_ids[idx % responder_ids.len()];
+        let result =
+            self.propose_transaction(tx_proposal, comment, Some(account_id_hex.to_string()));
+        self.complete_idempotency_key(
+            &account_id_hex,
+            &idempotency_key,
+            submission_outcome(&result, tx_proposal),
+        )?;
+        result.map(submitted).map_err(TransactionServiceError::from)
+    }
+
+    fn propose_tx_dry_run(&self, tx_proposal: &TxProposal) -> Result<u64, TransactionServiceError> {
+        if self.offline {
+            return Err(TransactionServiceError::Offline);
+        }
+        let validator_conn = self
+            .validator_conn
+            .as_ref()
+            .ok_or(TransactionServiceError::DryRunUnavailable)?;
+
+        if let (Some(block_version), Some(network_block_version)) = (
+            tx_proposal.block_version,
+            self.get_known_network_block_version(),
+        ) {
+            if block_version != network_block_version {
+                return Err(TransactionServiceError::BlockVersionMismatch {
+                    proposal: *block_version,
+                    network: *network_block_version,
+                });
+            }
+        }
 
-        let block_index = self
-            .peer_manager
-            .conn(responder_id)
-            .ok_or(TransactionServiceError::NodeNotFound)?
-            .propose_tx(&tx_proposal.tx, empty())
-            .map_err(TransactionServiceError::from)?;
+        // Report rejections the way propose_tx does, so that they can be
+        // handled the same as when submitting.
+        let block_count = validator_conn
+            .validate_tx(&tx_proposal.tx)
+            .map_err(|error| {
+                TransactionServiceError::Connection(retry::Error::Operation {
+                    error,
+                    total_delay: Duration::default(),
+                    tries: 1,
+                })
+            })?;
 
         log::trace!(
-            self.logger,
-            "Tx {:?} submitted at block height {}",
+            self.request_logger(),
+            "Tx {:?} passed a dry run at block height {}",
             tx_proposal.tx,
-            block_index
+            block_count
         );
 
-        if let Some(account_id_hex) = account_id_hex {
-            let conn = self.wallet_db.get_conn()?;
-            let account_id = AccountID(account_id_hex.to_string());
-
-            transaction(&conn, || {
-                if Account::get(&account_id, &conn).is_ok() {
-                    let transaction_log = TransactionLog::log_submitted(
-                        tx_proposal,
-                        block_index,
-                        comment.unwrap_or_else(|| "".to_string()),
-                        &account_id_hex,
-                        &conn,
-                    )?;
-
-                    let associated_txos = transaction_log.get_associated_txos(&conn)?;
-                    let value_map = transaction_log.value_map(&conn)?;
-
-                    Ok(Some((transaction_log, associated_txos, value_map)))
-                } else {
-                    Err(TransactionServiceError::Database(
-                        WalletDbError::AccountNotFound(account_id_hex),
-                    ))
-                }
-            })
-        } else {
-            Ok(None)
-        }
+        Ok(block_count)
     }
 
     fn build_sign_and_submit_transaction(
@@ -443,11 +1016,40 @@ where
         fee_token_id: Option<String>,
         tombstone_block: Option<String>,
         max_spendable_value: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
         comment: Option<String>,
         memo: TransactionMemo,
+        idempotency_key: Option<String>,
     ) -> Result<(TransactionLog, AssociatedTxos, ValueMap, TxProposal), TransactionServiceError>
     {
-        let tx_proposal = self.build_and_sign_transaction(
+        let _build_permit = self.build_guard.acquire(account_id_hex)?;
+        if let Some(idempotency_key) = &idempotency_key {
+            let request_fingerprint = request_fingerprint(&(
+                addresses_and_amounts,
+                input_txo_ids,
+                &fee_value,
+                &fee_token_id,
+                &tombstone_block,
+                &max_spendable_value,
+                source_subaddress_b58,
+                change_to_source,
+                &comment,
+                &memo,
+            ));
+            if let Some(transaction_log) =
+                self.claim_idempotency_key(account_id_hex, idempotency_key, &request_fingerprint)?
+            {
+                let conn = self.wallet_db.get_conn()?;
+                let associated_txos = transaction_log.get_associated_txos(&conn)?;
+                let value_map = transaction_log.value_map(&conn)?;
+                let tx_proposal =
+                    tx_proposal_from_transaction_log(&transaction_log, &associated_txos)?;
+                return Ok((transaction_log, associated_txos, value_map, tx_proposal));
+            }
+        }
+
+        let tx_proposal = match self.build_and_sign_transaction(
             account_id_hex,
             addresses_and_amounts,
             input_txo_ids,
@@ -455,25 +1057,702 @@ where
             fee_token_id,
             tombstone_block,
             max_spendable_value,
+            source_subaddress_b58,
+            change_to_source,
+            comment,
             memo,
-        )?;
+        ) {
+            Ok(tx_proposal) => tx_proposal,
+            Err(e) => {
+                if let Some(idempotency_key) = &idempotency_key {
+                    self.complete_idempotency_key(
+                        account_id_hex,
+                        idempotency_key,
+                        IdempotentRequestOutcome::NotSubmitted,
+                    )?;
+                }
+                return Err(e);
+            }
+        };
 
-        if let Some(transaction_log_and_associated_txos) =
-            self.submit_transaction(&tx_proposal, comment, Some(account_id_hex.to_string()))?
-        {
-            Ok((
-                transaction_log_and_associated_txos.0,
-                transaction_log_and_associated_txos.1,
-                transaction_log_and_associated_txos.2,
-                tx_proposal,
-            ))
-        } else {
-            Err(TransactionServiceError::MissingAccountOnSubmit)
+        let result = self.propose_transaction(&tx_proposal, None, Some(account_id_hex.to_string()));
+        if let Some(idempotency_key) = &idempotency_key {
+            self.complete_idempotency_key(
+                account_id_hex,
+                idempotency_key,
+                submission_outcome(&result, &tx_proposal),
+            )?;
+        }
+        match result? {
+            Some((transaction_log, associated_txos, value_map)) => {
+                Ok((transaction_log, associated_txos, value_map, tx_proposal))
+            }
+            None => Err(TransactionServiceError::MissingAccountOnSubmit),
         }
     }
+
+    fn check_recipient_networks(
+        &self,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        allow_cross_network: bool,
+    ) -> Result<(), TransactionServiceError> {
+        for (address, _) in addresses_and_amounts {
+            let check = self.check_address_network(address)?;
+            if !check.passed && !allow_cross_network {
+                return Err(TransactionServiceError::CrossNetworkAddress(
+                    address.clone(),
+                    check.reasons.join("; "),
+                ));
+            }
+            if !check.passed || check.warning {
+                log::warn!(
+                    self.request_logger(),
+                    "Sending to {} without confirming its network: {}",
+                    address,
+                    check.reasons.join("; ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn set_auto_rebuild(
+        &self,
+        transaction_log_id: &str,
+        max_attempts: u32,
+        fee_bump_percent: u32,
+    ) -> Result<TransactionLog, TransactionServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let transaction_log_id = TransactionID(transaction_log_id.to_string());
+        TransactionLog::get(&transaction_log_id, &conn)?.update_auto_rebuild(
+            max_attempts,
+            fee_bump_percent,
+            &conn,
+        )?;
+        Ok(TransactionLog::get(&transaction_log_id, &conn)?)
+    }
+
+    fn rebuild_expired_transactions(&self) -> Result<Vec<TransactionLog>, TransactionServiceError> {
+        if self.offline {
+            return Err(TransactionServiceError::Offline);
+        }
+
+        let conn = self.wallet_db.get_conn()?;
+        let mut rebuilt = Vec::new();
+        for expired in TransactionLog::list_awaiting_rebuild(&conn)? {
+            let account = match Account::get(&AccountID(expired.account_id.clone()), &conn) {
+                Ok(account) => account,
+                Err(WalletDbError::AccountNotFound(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let tombstone_block_index = expired.tombstone_block_index.unwrap_or_default();
+            if (account.next_block_index as u64) <= tombstone_block_index as u64 {
+                continue;
+            }
+            if let Some(block_index) = self.landed_block_index(&expired, &conn)? {
+                log::warn!(
+                    self.logger,
+                    "Expired transaction {} landed in block {}, marking it succeeded instead of rebuilding it",
+                    expired.id,
+                    block_index
+                );
+                expired.update_failed_to_succeeded(block_index, &conn)?;
+                continue;
+            }
+
+            let attempts_left = expired.auto_rebuild_attempts_left.unwrap_or_default() as u32;
+            let fee_bump_percent = expired.auto_rebuild_fee_bump_percent.unwrap_or_default() as u32;
+
+            let addresses_and_amounts: Vec<(String, AmountJSON)> = expired
+                .get_associated_txos(&conn)?
+                .outputs
+                .iter()
+                .map(|(txo, recipient)| (recipient.clone(), AmountJSON::from(&txo.amount())))
+                .collect();
+
+            let fee_token_id = TokenId::from(expired.fee_token_id as u64);
+            let mut fee_value = bumped_fee(expired.fee_value as u64, fee_bump_percent);
+            if let Some(network_fee) = self.get_network_fees().get(&fee_token_id) {
+                fee_value = fee_value.max(*network_fee);
+            }
+
+            match self.build_sign_and_submit_transaction(
+                &expired.account_id,
+                &addresses_and_amounts,
+                None,
+                Some(fee_value.to_string()),
+                Some((*fee_token_id).to_string()),
+                None,
+                None,
+                None,
+                false,
+                Some(expired.comment.clone()),
+                TransactionMemo::for_transaction_log(&expired),
+                None,
+            ) {
+                Ok((transaction_log, _, _, _)) => {
+                    transaction(&conn, || {
+                        transaction_log.update_auto_rebuild(
+                            attempts_left - 1,
+                            fee_bump_percent,
+                            &conn,
+                        )?;
+                        expired.update_superseded_by(&transaction_log.id, &conn)
+                    })?;
+                    log::info!(
+                        self.logger,
+                        "Rebuilt expired transaction {} as {}, with a fee of {}",
+                        expired.id,
+                        transaction_log.id,
+                        fee_value
+                    );
+                    rebuilt.push(TransactionLog::get(
+                        &TransactionID::from(&transaction_log),
+                        &conn,
+                    )?);
+                }
+                Err(e)
+                    if e.insufficient_funds().is_some()
+                        || e.insufficient_input_funds().is_some() =>
+                {
+                    log::warn!(
+                        self.logger,
+                        "Not rebuilding expired transaction {}: {}",
+                        expired.id,
+                        e
+                    );
+                    expired.update_auto_rebuild(0, fee_bump_percent, &conn)?;
+                }
+                Err(e) => log::error!(
+                    self.logger,
+                    "Could not rebuild expired transaction {}, will retry: {}",
+                    expired.id,
+                    e
+                ),
+            }
+        }
+
+        Ok(rebuilt)
+    }
+}
+
+impl<T, FPR> WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    /// The block a transaction landed in, if the ledger holds the key image of
+    /// one of its inputs or one of its watched outputs.
+    fn landed_block_index(
+        &self,
+        transaction_log: &TransactionLog,
+        conn: &Conn,
+    ) -> Result<Option<u64>, TransactionServiceError> {
+        for input_txo in transaction_log.get_associated_txos(conn)?.inputs {
+            if let Some(key_image) = &input_txo.key_image {
+                let key_image: KeyImage = mc_util_serial::decode(key_image)?;
+                if let Some(block_index) = self.ledger_db.check_key_image(&key_image)? {
+                    return Ok(Some(block_index));
+                }
+            }
+        }
+
+        for monitored_txo in MonitoredTxo::list_for_transaction_log(&transaction_log.id, conn)? {
+            match self
+                .ledger_db
+                .get_tx_out_index_by_public_key(&monitored_txo.public_key()?)
+            {
+                Ok(tx_out_index) => {
+                    return Ok(Some(
+                        self.ledger_db
+                            .get_block_index_by_tx_out_index(tx_out_index)?,
+                    ))
+                }
+                Err(mc_ledger_db::Error::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// A builder for a transaction from the account to the recipients, with
+    /// its fee, tombstone, block version and source subaddress set.
+    #[allow(clippy::too_many_arguments)]
+    fn configured_builder(
+        &self,
+        account_id_hex: &str,
+        addresses_and_amounts: &[(String, AmountJSON)],
+        fee_value: Option<String>,
+        fee_token_id: Option<String>,
+        tombstone_block: Option<String>,
+        source_subaddress_b58: Option<&str>,
+        change_to_source: bool,
+        logger: &Logger,
+        conn: &Conn,
+    ) -> Result<WalletTransactionBuilder<FPR>, TransactionServiceError> {
+        let mut builder = WalletTransactionBuilder::new(
+            account_id_hex.to_string(),
+            self.ledger_db.clone(),
+            self.fog_resolver_factory.clone(),
+        );
+        builder.set_logger(logger.clone());
+        builder.set_fog_pubkey_cache(self.fog_pubkey_cache.clone());
+
+        let account = Account::get(&AccountID(account_id_hex.to_string()), conn)?;
+        builder.set_split_change_into(account.split_change_into as usize);
+        builder.set_dust_thresholds(self.get_dust_thresholds());
+        builder.set_min_confirmations(self.min_confirmations_for_account(&account));
+
+        let mut default_fee_token_id = Mob::ID;
+
+        for (recipient_public_address, amount) in addresses_and_amounts {
+            if !self.verify_address(recipient_public_address)? {
+                return Err(TransactionServiceError::InvalidPublicAddress(
+                    recipient_public_address.to_string(),
+                ));
+            };
+            let recipient = b58_decode_public_address(recipient_public_address)?;
+            let amount =
+                Amount::try_from(amount).map_err(TransactionServiceError::InvalidAmount)?;
+            builder.add_recipient(recipient, amount.value, amount.token_id)?;
+            default_fee_token_id = amount.token_id;
+        }
+
+        // Parameters given with the request override the account's
+        // defaults, which override the wallet's.
+        let tombstone = match tombstone_block {
+            Some(tombstone) => tombstone.parse::<u64>()?,
+            None => account_default_tombstone_block(&account, self.ledger_db.num_blocks()?),
+        };
+        builder.set_tombstone(tombstone)?;
+
+        let fee_token_id = match fee_token_id {
+            Some(t) => TokenId::from(t.parse::<u64>()?),
+            None => account_default_fee_token_id(&account, default_fee_token_id),
+        };
+
+        let fee_value = match fee_value {
+            Some(f) => f.parse::<u64>()?,
+            None => match account_default_fee(&account, fee_token_id) {
+                Some(f) => f,
+                None => *self.get_network_fees().get(&fee_token_id).ok_or(
+                    TransactionServiceError::DefaultFeeNotFoundForToken(fee_token_id),
+                )?,
+            },
+        };
+
+        builder.set_fee(fee_value, fee_token_id)?;
+
+        builder.set_block_version(self.get_network_block_version());
+
+        if let Some(source_subaddress_b58) = source_subaddress_b58 {
+            builder.set_source_subaddress(conn, source_subaddress_b58)?;
+        }
+        builder.set_change_to_source(change_to_source);
+
+        Ok(builder)
+    }
+
+    /// Signs a transaction with the account's key.
+    ///
+    /// Inputs may be at subaddresses which are not assigned in this wallet,
+    /// such as when the transaction was built by another instance with a
+    /// larger subaddress gap. Their spend keys are derived from the account
+    /// key, and the subaddresses are logged as a warning.
+    pub(crate) fn sign_transaction(
+        &self,
+        unsigned_tx: UnsignedTx,
+        fog_resolver: FullServiceFogResolver,
+        account: &Account,
+        conn: &Conn,
+    ) -> Result<TxProposal, TransactionServiceError> {
+        check_can_sign_locally(account)?;
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
+
+        let mut unassigned_subaddress_indices = Vec::new();
+        for subaddress_index in unsigned_tx.input_subaddress_indices() {
+            match AssignedSubaddress::get_for_account_by_index(
+                &account.id,
+                subaddress_index as i64,
+                conn,
+            ) {
+                Ok(_) => {}
+                Err(WalletDbError::Diesel(diesel::result::Error::NotFound)) => {
+                    unassigned_subaddress_indices.push(subaddress_index)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let tx_proposal = unsigned_tx.sign(&account_key, fog_resolver)?;
+
+        if !unassigned_subaddress_indices.is_empty() {
+            log::warn!(
+                self.request_logger(),
+                "Signed inputs of account {} at subaddress indices {:?}, which are not assigned in this wallet",
+                account.id,
+                unassigned_subaddress_indices
+            );
+        }
+
+        Ok(tx_proposal)
+    }
+
+    /// Proposes a transaction to a peer, and logs it for the account if one is
+    /// given.
+    ///
+    /// Errors say whether the transaction may have reached the network
+    /// before they occurred.
+    fn propose_transaction(
+        &self,
+        tx_proposal: &TxProposal,
+        comment: Option<String>,
+        account_id_hex: Option<String>,
+    ) -> Result<Option<(TransactionLog, AssociatedTxos, ValueMap)>, ProposeError> {
+        let not_submitted = |error| ProposeError {
+            error,
+            maybe_submitted: false,
+        };
+        let maybe_submitted = |error| ProposeError {
+            error,
+            maybe_submitted: true,
+        };
+
+        if self.offline {
+            return Err(not_submitted(TransactionServiceError::Offline));
+        }
+
+        // A transaction built for another block version would only be rejected
+        // by consensus, so ask for a rebuild instead. When no peer answers, the
+        // network's block version is not known, and consensus is left to judge.
+        if let (Some(block_version), Some(network_block_version)) = (
+            tx_proposal.block_version,
+            self.get_known_network_block_version(),
+        ) {
+            if block_version != network_block_version {
+                return Err(not_submitted(
+                    TransactionServiceError::BlockVersionMismatch {
+                        proposal: *block_version,
+                        network: *network_block_version,
+                    },
+                ));
+            }
+        }
+
+        // Pick the peers to submit to, in order of preference.
+        let responder_ids = self.submission_peers();
+        if responder_ids.is_empty() {
+            return Err(not_submitted(TransactionServiceError::NoPeersConfigured));
+        }
+
+        // Whether a peer which failed to answer received the transaction can
+        // not be told, so every failure from here on may follow a submission.
+
+        let block_index =
+            propose_with_failover(&responder_ids, 0, &self.request_logger(), |responder_id| {
+                let conn = self
+                    .peer_manager
+                    .conn(responder_id)
+                    .ok_or(TransactionServiceError::NodeNotFound)?;
+                self.peer_health
+                    .call(responder_id, || conn.propose_tx(&tx_proposal.tx, empty()))
+                    .map_err(TransactionServiceError::from)
+            })
+            .map_err(maybe_submitted)?;
+
+        log::trace!(
+            self.request_logger(),
+            "Tx {:?} submitted at block height {}",
+            tx_proposal.tx,
+            block_index
+        );
+
+        if let Some(account_id_hex) = account_id_hex {
+            let conn = self
+                .wallet_db
+                .get_conn()
+                .map_err(|e| maybe_submitted(e.into()))?;
+            let account_id = AccountID(account_id_hex.to_string());
+
+            transaction(&conn, || {
+                if Account::get(&account_id, &conn).is_ok() {
+                    let transaction_log = TransactionLog::log_submitted(
+                        tx_proposal,
+                        block_index,
+                        comment.unwrap_or_else(|| "".to_string()),
+                        &account_id_hex,
+                        &conn,
+                    )?;
+
+                    for payload_txo in tx_proposal.payload_txos.iter() {
+                        MonitoredTxo::create(payload_txo, &transaction_log.id, &conn)?;
+                    }
+
+                    if let Some(reporter) = self.transaction_reporter.as_ref() {
+                        reporter.enqueue(
+                            &account_id_hex,
+                            &transaction_log.id,
+                            tx_proposal,
+                            block_index,
+                            &conn,
+                        )?;
+                    }
+
+                    let associated_txos = transaction_log.get_associated_txos(&conn)?;
+                    let value_map = transaction_log.value_map(&conn)?;
+
+                    Ok(Some((transaction_log, associated_txos, value_map)))
+                } else {
+                    Err(TransactionServiceError::Database(
+                        WalletDbError::AccountNotFound(account_id_hex),
+                    ))
+                }
+            })
+            .map_err(maybe_submitted)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Finds the account's transaction log for a transaction it has already
+    /// submitted, which is pending or has landed. The transaction log is
+    /// identified by the hash of the transaction.
+    fn get_submitted_duplicate(
+        &self,
+        tx_proposal: &TxProposal,
+        account_id_hex: &str,
+    ) -> Result<
+        Option<(TransactionLog, AssociatedTxos, ValueMap, SubmitOutcome)>,
+        TransactionServiceError,
+    > {
+        let conn = self.wallet_db.get_conn()?;
+        let transaction_log =
+            match TransactionLog::get(&TransactionID::from(&tx_proposal.tx), &conn) {
+                Ok(transaction_log) if transaction_log.account_id == account_id_hex => {
+                    transaction_log
+                }
+                Ok(_) | Err(WalletDbError::TransactionLogNotFound(_)) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            };
+
+        let outcome = match transaction_log.status() {
+            TxStatus::Pending => SubmitOutcome::AlreadyPending,
+            TxStatus::Succeeded => SubmitOutcome::AlreadyLanded,
+            TxStatus::Built | TxStatus::Failed => return Ok(None),
+        };
+
+        log::info!(
+            self.request_logger(),
+            "Not resubmitting transaction {}, which is {}",
+            transaction_log.id,
+            transaction_log.status()
+        );
+
+        let associated_txos = transaction_log.get_associated_txos(&conn)?;
+        let value_map = transaction_log.value_map(&conn)?;
+        Ok(Some((transaction_log, associated_txos, value_map, outcome)))
+    }
+
+    /// Claims an idempotency key for a request.
+    ///
+    /// Returns the transaction log produced by an earlier request made with
+    /// the same key, or None if this is the first request with the key and it
+    /// should proceed.
+    fn claim_idempotency_key(
+        &self,
+        account_id_hex: &str,
+        idempotency_key: &str,
+        request_fingerprint: &str,
+    ) -> Result<Option<TransactionLog>, TransactionServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let created_at_cutoff = idempotency_key_cutoff(self.idempotency_key_ttl);
+
+        transaction(&conn, || {
+            match IdempotencyKey::get(account_id_hex, idempotency_key, &conn) {
+                Ok(claimed) if claimed.is_expired(created_at_cutoff) => claimed.delete(&conn)?,
+                Ok(claimed) => {
+                    if claimed.request_fingerprint != request_fingerprint {
+                        return Err(WalletDbError::IdempotencyKeyMismatch(
+                            idempotency_key.to_string(),
+                        )
+                        .into());
+                    }
+                    return match (claimed.transaction_log_id, claimed.submission_error) {
+                        (Some(transaction_log_id), _) => Ok(Some(TransactionLog::get(
+                            &TransactionID(transaction_log_id),
+                            &conn,
+                        )?)),
+                        (None, Some(submission_error)) => {
+                            Err(WalletDbError::IdempotencyKeySubmissionUnknown(
+                                idempotency_key.to_string(),
+                                submission_error,
+                            )
+                            .into())
+                        }
+                        (None, None) => Err(WalletDbError::IdempotencyKeyInProgress(
+                            idempotency_key.to_string(),
+                        )
+                        .into()),
+                    };
+                }
+                Err(WalletDbError::IdempotencyKeyNotFound(_)) => (),
+                Err(e) => return Err(e.into()),
+            }
+
+            IdempotencyKey::create(
+                account_id_hex,
+                idempotency_key,
+                request_fingerprint,
+                unix_timestamp_now(),
+                &conn,
+            )?;
+            Ok(None)
+        })
+    }
+
+    /// Records how a request made with an idempotency key ended. If the
+    /// request failed before its transaction could reach the network, the key
+    /// is released so that the request can be retried. If it failed after,
+    /// the key is kept with the failure, as a retry could pay twice.
+    fn complete_idempotency_key(
+        &self,
+        account_id_hex: &str,
+        idempotency_key: &str,
+        outcome: IdempotentRequestOutcome,
+    ) -> Result<(), TransactionServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let claimed = IdempotencyKey::get(account_id_hex, idempotency_key, &conn)?;
+        match outcome {
+            IdempotentRequestOutcome::Logged(transaction_log_id) => {
+                claimed.update_transaction_log_id(transaction_log_id, &conn)?
+            }
+            IdempotentRequestOutcome::NotSubmitted => claimed.delete(&conn)?,
+            IdempotentRequestOutcome::MaybeSubmitted(submission_error) => {
+                log::warn!(
+                    self.request_logger(),
+                    "Keeping idempotency key {} of account {}, as its request may have been submitted: {}",
+                    idempotency_key,
+                    account_id_hex,
+                    submission_error
+                );
+                claimed.update_submission_error(&submission_error, &conn)?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An error proposing a transaction, with whether the transaction may have
+/// reached the network before it occurred.
+struct ProposeError {
+    error: TransactionServiceError,
+    maybe_submitted: bool,
+}
+
+impl From<ProposeError> for TransactionServiceError {
+    fn from(src: ProposeError) -> Self {
+        src.error
+    }
+}
+
+/// How a request made with an idempotency key ended.
+enum IdempotentRequestOutcome<'a> {
+    /// The request produced the transaction log with this ID.
+    Logged(&'a str),
+
+    /// The request failed before its transaction could reach the network.
+    NotSubmitted,
+
+    /// The request failed after its transaction may have reached the network,
+    /// for this reason.
+    MaybeSubmitted(String),
+}
+
+/// How a request made with an idempotency key ended, from the result of
+/// proposing its transaction.
+fn submission_outcome<'a>(
+    result: &'a Result<Option<(TransactionLog, AssociatedTxos, ValueMap)>, ProposeError>,
+    tx_proposal: &TxProposal,
+) -> IdempotentRequestOutcome<'a> {
+    let transaction_id = TransactionID::from(&tx_proposal.tx);
+    match result {
+        Ok(Some((transaction_log, _, _))) => IdempotentRequestOutcome::Logged(&transaction_log.id),
+        Ok(None) => IdempotentRequestOutcome::MaybeSubmitted(format!(
+            "transaction {} was submitted but not logged",
+            transaction_id
+        )),
+        Err(e) if e.maybe_submitted => IdempotentRequestOutcome::MaybeSubmitted(format!(
+            "submitting transaction {} failed: {}",
+            transaction_id, e.error
+        )),
+        Err(_) => IdempotentRequestOutcome::NotSubmitted,
+    }
+}
+
+/// A digest of the parameters of a request, used to detect an idempotency key
+/// being reused for a different request.
+fn request_fingerprint<S: Serialize>(request: &S) -> String {
+    let request_json = serde_json::to_string(request).unwrap_or_default();
+    let digest: [u8; 32] = request_json.digest32::<MerlinTranscript>(b"idempotency_request");
+    hex::encode(digest)
+}
+
+/// Reconstructs the TxProposal for a logged transaction, so that a retried
+/// request returns the same response as the original.
+fn tx_proposal_from_transaction_log(
+    transaction_log: &TransactionLog,
+    associated_txos: &AssociatedTxos,
+) -> Result<TxProposal, TransactionServiceError> {
+    let tx = mc_util_serial::decode(&transaction_log.tx)?;
+
+    let mut input_txos = Vec::new();
+    for txo in associated_txos.inputs.iter() {
+        let key_image = txo
+            .key_image
+            .as_ref()
+            .ok_or(WalletDbError::MissingKeyImage)?;
+        input_txos.push(InputTxo {
+            tx_out: mc_util_serial::decode(&txo.txo)?,
+            subaddress_index: txo.subaddress_index.unwrap_or_default() as u64,
+            key_image: mc_util_serial::decode(key_image)?,
+            amount: Amount::new(txo.value as u64, TokenId::from(txo.token_id as u64)),
+        });
+    }
+
+    Ok(TxProposal {
+        tx,
+        input_txos,
+        payload_txos: output_txos_from_transaction_log(&associated_txos.outputs)?,
+        change_txos: output_txos_from_transaction_log(&associated_txos.change)?,
+        block_version: transaction_log
+            .block_version
+            .and_then(|v| BlockVersion::try_from(v as u32).ok()),
+    })
+}
+
+fn output_txos_from_transaction_log(
+    outputs: &[(Txo, String)],
+) -> Result<Vec<OutputTxo>, TransactionServiceError> {
+    let mut output_txos = Vec::new();
+    for (txo, recipient_public_address_b58) in outputs.iter() {
+        // The confirmation number is stored in the shared_secret column for
+        // outputs we have sent.
+        let confirmation_number = txo
+            .shared_secret
+            .as_ref()
+            .ok_or_else(|| TransactionServiceError::MissingConfirmationNumber(txo.id.clone()))?;
+        output_txos.push(OutputTxo {
+            tx_out: mc_util_serial::decode(&txo.txo)?,
+            recipient_public_address: b58_decode_public_address(recipient_public_address_b58)?,
+            confirmation_number: mc_util_serial::decode(confirmation_number)?,
+            amount: Amount::new(txo.value as u64, TokenId::from(txo.token_id as u64)),
+        });
+    }
+    Ok(output_txos)
 }
 
-fn validate_number_inputs(num_inputs: u64) -> Result<(), TransactionServiceError> {
+pub(crate) fn validate_number_inputs(num_inputs: u64) -> Result<(), TransactionServiceError> {
     if num_inputs > MAX_INPUTS {
         return Err(TransactionServiceError::TransactionBuilder(WalletTransactionBuilderError::InvalidArgument(
             format!("Invalid number of input txos. {:?} txo ids provided but maximum allowed number of inputs is {:?}", num_inputs, MAX_INPUTS)
@@ -482,7 +1761,7 @@ fn validate_number_inputs(num_inputs: u64) -> Result<(), TransactionServiceError
     Ok(())
 }
 
-fn validate_number_outputs(num_outputs: u64) -> Result<(), TransactionServiceError> {
+pub(crate) fn validate_number_outputs(num_outputs: u64) -> Result<(), TransactionServiceError> {
     // maximum number of outputs is 16 but we reserve 1 for change
     let max_outputs = MAX_OUTPUTS - 1;
     if num_outputs > max_outputs {
@@ -493,26 +1772,157 @@ fn validate_number_outputs(num_outputs: u64) -> Result<(), TransactionServiceErr
     Ok(())
 }
 
+/// Check that the wallet holds the spend key of an account, so that it can
+/// sign the account's transactions.
+pub(crate) fn check_can_sign_locally(account: &Account) -> Result<(), TransactionServiceError> {
+    let key_source = account.key_source()?;
+    if !key_source.can_sign_locally() {
+        return Err(TransactionServiceError::AccountCannotSignLocally(
+            account.id.clone(),
+            key_source.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The fee of a rebuilt transaction, raised by a percentage of the fee it
+/// replaces.
+pub(crate) fn bumped_fee(fee_value: u64, fee_bump_percent: u32) -> u64 {
+    fee_value.saturating_add(fee_value.saturating_mul(fee_bump_percent as u64) / 100)
+}
+
+/// The tombstone block of a transaction built by an account without one given,
+/// when the ledger has num_blocks blocks. Zero leaves it to the builder.
+pub(crate) fn account_default_tombstone_block(account: &Account, num_blocks: u64) -> u64 {
+    account
+        .default_tombstone_offset
+        .map_or(0, |offset| num_blocks + offset as u64)
+}
+
+/// The token in which a transaction built by an account without a fee token
+/// given pays its fee, falling back to the token of its recipients.
+pub(crate) fn account_default_fee_token_id(
+    account: &Account,
+    recipient_token_id: TokenId,
+) -> TokenId {
+    account
+        .default_fee_token_id
+        .map_or(recipient_token_id, |t| TokenId::from(t as u64))
+}
+
+/// The account's default fee, if it has one for fees paid in this token.
+pub(crate) fn account_default_fee(account: &Account, fee_token_id: TokenId) -> Option<u64> {
+    match (account.default_fee_value, account.default_fee_token_id) {
+        (Some(f), Some(t)) if TokenId::from(t as u64) == fee_token_id => Some(f as u64),
+        _ => None,
+    }
+}
+
+/// Periodically rebuilds and resubmits the expired transactions which were
+/// submitted with automatic rebuilds.
+pub struct AutoRebuildThread {
+    join_handle: Option<thread::JoinHandle<()>>,
+
+    /// Stop trigger, used to signal the thread to terminate.
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl AutoRebuildThread {
+    pub fn start<T, FPR>(service: Arc<WalletService<T, FPR>>) -> Self
+    where
+        T: BlockchainConnection + UserTxConnection + 'static,
+        FPR: FogPubkeyResolver + Send + Sync + 'static,
+    {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let thread_stop_requested = stop_requested.clone();
+
+        let join_handle = Some(
+            thread::Builder::new()
+                .name("auto_rebuild".to_string())
+                .spawn(move || {
+                    log::debug!(service.logger, "Auto rebuild thread started.");
+
+                    let mut last_attempt: Option<Instant> = None;
+                    while !thread_stop_requested.load(Ordering::SeqCst) {
+                        if last_attempt.map_or(true, |attempted| {
+                            attempted.elapsed()
+                                >= Duration::from_secs(AUTO_REBUILD_INTERVAL_SECONDS)
+                        }) {
+                            if let Err(e) = service.rebuild_expired_transactions() {
+                                log::error!(
+                                    service.logger,
+                                    "Error rebuilding expired transactions:\n{:?}",
+                                    e
+                                );
+                            }
+                            last_attempt = Some(Instant::now());
+                        }
+
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                    log::debug!(service.logger, "Auto rebuild thread stopped.");
+                })
+                .expect("failed starting auto rebuild thread"),
+        );
+
+        Self {
+            join_handle,
+            stop_requested,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle.join().expect("AutoRebuildThread join failed");
+        }
+    }
+}
+
+impl Drop for AutoRebuildThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        db::{account::AccountID, models::Txo, txo::TxoModel},
+        db::{
+            account::AccountID,
+            models::Txo,
+            transaction_log::{TransactionLogFilter, TxStatus},
+            txo::{TxoID, TxoModel},
+        },
+        json_rpc::v2::models::tx_proposal::TxProposal as TxProposalJSON,
         service::{
-            account::AccountService, address::AddressService, balance::BalanceService,
+            account::AccountService,
+            address::{AddressNetworkPolicy, AddressService},
+            balance::BalanceService,
             transaction_log::TransactionLogService,
         },
         test_utils::{
-            add_block_from_transaction_log, add_block_to_ledger_db, get_test_ledger,
-            manually_sync_account, setup_wallet_service, MOB,
+            add_block_from_transaction_log, add_block_to_ledger_db, generate_ledger_db,
+            get_test_ledger, manually_sync_account, setup_wallet_service,
+            setup_wallet_service_read_only, DEFAULT_PER_RECIPIENT_AMOUNT, MOB,
         },
-        util::b58::b58_encode_public_address,
+        util::{b58::b58_encode_public_address, constants::MNEMONIC_KEY_DERIVATION_VERSION},
     };
+    use bip39::{Language, Mnemonic};
     use mc_account_keys::{AccountKey, PublicAddress};
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_rand::rand_core::RngCore;
+    use mc_ledger_db::LedgerDB;
     use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
     use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        collections::{BTreeSet, HashSet},
+        path::PathBuf,
+        sync::Arc,
+        thread,
+    };
+    use tempdir::TempDir;
 
     #[test_with_logger]
     fn test_build_transaction_and_log(logger: Logger) {
@@ -539,7 +1949,12 @@ mod tests {
         let alice_public_address = alice_account_key.default_subaddress();
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(0, tx_logs.len());
@@ -555,7 +1970,12 @@ mod tests {
         manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(0, tx_logs.len());
@@ -597,13 +2017,21 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .unwrap();
         log::info!(logger, "Built transaction from Alice");
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(1, tx_logs.len());
@@ -625,13 +2053,21 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .unwrap();
         log::info!(logger, "Built transaction from Alice");
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(2, tx_logs.len());
@@ -653,15 +2089,23 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .unwrap();
         log::info!(logger, "Built transaction from Alice");
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
-            .unwrap();
-
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
+            .unwrap();
+
         assert_eq!(3, tx_logs.len());
     }
 
@@ -738,7 +2182,10 @@ mod tests {
                 None,
                 None,
                 None,
+                false,
+                None,
                 TransactionMemo::RTH,
+                None,
             )
             .unwrap();
         log::info!(logger, "Built and submitted transaction from Alice");
@@ -811,7 +2258,10 @@ mod tests {
                 None,
                 None,
                 None,
+                false,
+                None,
                 TransactionMemo::RTH,
+                None,
             )
             .unwrap();
 
@@ -846,9 +2296,8 @@ mod tests {
         );
     }
 
-    // Building a transaction for an invalid public address should fail.
     #[test_with_logger]
-    fn test_invalid_public_address_fails(logger: Logger) {
+    fn test_build_sign_and_submit_with_idempotency_key(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
 
         let known_recipients: Vec<PublicAddress> = Vec::new();
@@ -856,7 +2305,6 @@ mod tests {
 
         let service = setup_wallet_service(ledger_db.clone(), logger.clone());
 
-        // Create our main account for the wallet
         let alice = service
             .create_account(
                 Some("Alice's Main Account".to_string()),
@@ -865,8 +2313,6 @@ mod tests {
                 "".to_string(),
             )
             .unwrap();
-
-        // Add a block with a transaction for Alice
         let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
         let alice_account_id = AccountID::from(&alice_account_key);
         let alice_public_address = alice_account_key.default_subaddress();
@@ -877,29 +2323,122 @@ mod tests {
             &vec![KeyImage::from(rng.next_u64())],
             &mut rng,
         );
-
         manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
 
-        match service.build_and_sign_transaction(
+        let addresses_and_amounts = vec![(
+            b58_encode_public_address(&alice_public_address).unwrap(),
+            AmountJSON::new(42 * MOB, Mob::ID),
+        )];
+        let build_sign_and_submit = |addresses_and_amounts: &[(String, AmountJSON)],
+                                     idempotency_key: &str| {
+            service.build_sign_and_submit_transaction(
+                &alice.id,
+                addresses_and_amounts,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                Some(idempotency_key.to_string()),
+            )
+        };
+
+        let (transaction_log, _associated_txos, _value_map, tx_proposal) =
+            build_sign_and_submit(&addresses_and_amounts, "payout-1").unwrap();
+
+        // A retry after success returns the original transaction, while it is
+        // still pending, rather than building a new one.
+        let (retried_log, _associated_txos, _value_map, retried_tx_proposal) =
+            build_sign_and_submit(&addresses_and_amounts, "payout-1").unwrap();
+        assert_eq!(retried_log.id, transaction_log.id);
+        assert_eq!(retried_log.status(), TxStatus::Pending);
+        assert_eq!(retried_tx_proposal.tx, tx_proposal.tx);
+        assert_eq!(
+            retried_tx_proposal.input_txos.len(),
+            tx_proposal.input_txos.len()
+        );
+        assert_eq!(retried_tx_proposal.payload_txos, tx_proposal.payload_txos);
+        assert_eq!(retried_tx_proposal.change_txos, tx_proposal.change_txos);
+
+        // Reusing the key for a different request is rejected.
+        let other_addresses_and_amounts = vec![(
+            b58_encode_public_address(&alice_public_address).unwrap(),
+            AmountJSON::new(43 * MOB, Mob::ID),
+        )];
+        match build_sign_and_submit(&other_addresses_and_amounts, "payout-1") {
+            Err(TransactionServiceError::Database(WalletDbError::IdempotencyKeyMismatch(_))) => {}
+            Ok(_) => panic!("Should not reuse an idempotency key for a different request"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+
+        // A retry while the first request is still being built is rejected
+        // until the first request completes.
+        let request_fingerprint = request_fingerprint(&(
+            &other_addresses_and_amounts[..],
+            None::<&Vec<String>>,
+            &None::<String>,
+            &None::<String>,
+            &None::<String>,
+            &None::<String>,
+            None::<&str>,
+            false,
+            &None::<String>,
+            &TransactionMemo::RTH,
+        ));
+        let conn = service.wallet_db.get_conn().unwrap();
+        let in_progress = IdempotencyKey::create(
             &alice.id,
-            &vec![("NOTB58".to_string(), AmountJSON::new(42 * MOB, Mob::ID))],
-            None,
-            None,
-            None,
-            None,
-            None,
-            TransactionMemo::RTH,
-        ) {
-            Ok(_) => {
-                panic!("Should not be able to build transaction to invalid b58 public address")
+            "payout-2",
+            &request_fingerprint,
+            unix_timestamp_now(),
+            &conn,
+        )
+        .unwrap();
+        match build_sign_and_submit(&other_addresses_and_amounts, "payout-2") {
+            Err(TransactionServiceError::Database(WalletDbError::IdempotencyKeyInProgress(_))) => {}
+            Ok(_) => panic!("Should not build while a request with the same key is in progress"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+
+        // A request which failed after its transaction may have reached the
+        // network keeps its key, and retries report the failure rather than
+        // paying again.
+        in_progress
+            .update_submission_error("submitting transaction failed: timed out", &conn)
+            .unwrap();
+        match build_sign_and_submit(&other_addresses_and_amounts, "payout-2") {
+            Err(TransactionServiceError::Database(
+                WalletDbError::IdempotencyKeySubmissionUnknown(key, submission_error),
+            )) => {
+                assert_eq!(key, "payout-2");
+                assert_eq!(submission_error, "submitting transaction failed: timed out");
             }
-            Err(TransactionServiceError::InvalidPublicAddress(_)) => {}
+            Ok(_) => panic!("Should not build again after a submission with an unknown outcome"),
             Err(e) => panic!("Unexpected error {:?}", e),
-        };
+        }
+
+        // A request which failed before submitting releases its key.
+        let too_much = vec![(
+            b58_encode_public_address(&alice_public_address).unwrap(),
+            AmountJSON::new(1_000 * MOB, Mob::ID),
+        )];
+        assert!(build_sign_and_submit(&too_much, "payout-3").is_err());
+        match IdempotencyKey::get(&alice.id, "payout-3", &conn) {
+            Err(WalletDbError::IdempotencyKeyNotFound(_)) => {}
+            Ok(_) => panic!("A request which failed before submitting should release its key"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
     }
 
+    // A proposal built for another block version than the network's should be
+    // rejected before it reaches consensus, and logs should record the block
+    // version of the transactions they hold.
     #[test_with_logger]
-    fn test_maximum_inputs_and_outputs(logger: Logger) {
+    fn test_submit_rejects_block_version_mismatch(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
 
         let known_recipients: Vec<PublicAddress> = Vec::new();
@@ -907,7 +2446,6 @@ mod tests {
 
         let service = setup_wallet_service(ledger_db.clone(), logger.clone());
 
-        // Create our main account for the wallet
         let alice = service
             .create_account(
                 Some("Alice's Main Account".to_string()),
@@ -916,8 +2454,6 @@ mod tests {
                 "".to_string(),
             )
             .unwrap();
-
-        // Add a block with a transaction for Alice
         let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
         let alice_account_id = AccountID::from(&alice_account_key);
         let alice_public_address = alice_account_key.default_subaddress();
@@ -928,66 +2464,1728 @@ mod tests {
             &vec![KeyImage::from(rng.next_u64())],
             &mut rng,
         );
-
         manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
 
-        // test ouputs
-        let mut outputs = Vec::new();
-        for _ in 0..17 {
-            outputs.push((
-                b58_encode_public_address(&alice_public_address).unwrap(),
-                AmountJSON::new(42 * MOB, Mob::ID),
-            ));
-        }
-        match service.build_and_sign_transaction(
-            &alice.id,
-            &outputs,
-            None,
-            None,
-            None,
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&alice_public_address).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        let network_block_version = service.get_network_block_version();
+        assert_eq!(tx_proposal.block_version, Some(network_block_version));
+
+        // The block version survives a round trip through the JSON model.
+        let tx_proposal_json = TxProposalJSON::try_from(&tx_proposal).unwrap();
+        assert_eq!(
+            tx_proposal_json.block_version,
+            Some(network_block_version.to_string())
+        );
+        let round_tripped = TxProposal::try_from(&tx_proposal_json).unwrap();
+        assert_eq!(round_tripped.tx, tx_proposal.tx);
+        assert_eq!(round_tripped.block_version, tx_proposal.block_version);
+
+        let mut stale_tx_proposal = tx_proposal.clone();
+        stale_tx_proposal.block_version =
+            Some(BlockVersion::try_from(*network_block_version - 1).unwrap());
+        match service.submit_transaction(
+            &stale_tx_proposal,
             None,
+            Some(alice.id.clone()),
             None,
-            TransactionMemo::RTH,
+            false,
         ) {
-            Ok(_) => {
-                panic!("Should not be able to build transaction with too many ouputs")
+            Err(TransactionServiceError::BlockVersionMismatch { proposal, network }) => {
+                assert_eq!(proposal, *network_block_version - 1);
+                assert_eq!(network, *network_block_version);
             }
-            Err(TransactionServiceError::TransactionBuilder(
-                WalletTransactionBuilderError::InvalidArgument(_),
-            )) => {}
+            Ok(_) => panic!("Should not submit a proposal for another block version"),
             Err(e) => panic!("Unexpected error {:?}", e),
-        };
-
-        // test inputs
-        let mut outputs = Vec::new();
-        for _ in 0..2 {
-            outputs.push((
-                b58_encode_public_address(&alice_public_address).unwrap(),
-                AmountJSON::new(42 * MOB, Mob::ID),
-            ));
-        }
-        let mut inputs = Vec::new();
-        for _ in 0..17 {
-            inputs.push("fake txo id".to_string());
         }
-        match service.build_and_sign_transaction(
-            &alice.id,
-            &outputs,
-            Some(&inputs),
-            None,
-            None,
-            None,
-            None,
-            TransactionMemo::RTH,
-        ) {
-            Ok(_) => {
-                panic!("Should not be able to build transaction with too many inputs")
+
+        let (transaction_log, _associated_txos, _value_map, _outcome) = service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            transaction_log.block_version,
+            Some(*network_block_version as i64)
+        );
+    }
+
+    #[test_with_logger]
+    fn test_submit_transaction_twice(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let conn = service.wallet_db.get_conn().unwrap();
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        let transaction_log_id = TransactionID::from(&tx_proposal.tx).to_string();
+
+        // A built transaction is submitted.
+        let (transaction_log, _, _, outcome) = service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome, SubmitOutcome::Submitted);
+        assert_eq!(transaction_log.id, transaction_log_id);
+        let submitted_block_index = transaction_log.submitted_block_index;
+        assert!(submitted_block_index.is_some());
+
+        // While it is pending, it is not submitted again.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![AccountKey::random(&mut rng).default_subaddress()],
+            MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let (transaction_log, _, _, outcome) = service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome, SubmitOutcome::AlreadyPending);
+        assert_eq!(transaction_log.id, transaction_log_id);
+        assert_eq!(transaction_log.submitted_block_index, submitted_block_index);
+        assert_eq!(transaction_log.status(), TxStatus::Pending);
+
+        // Unless the resubmit is forced.
+        let (transaction_log, _, _, outcome) = service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome, SubmitOutcome::Submitted);
+        assert_eq!(transaction_log.id, transaction_log_id);
+        assert_eq!(
+            transaction_log.submitted_block_index,
+            submitted_block_index.map(|i| i + 1)
+        );
+
+        // Once it has landed, the original transaction log is returned.
+        add_block_from_transaction_log(&mut ledger_db, &conn, &transaction_log, &mut rng);
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+        let (transaction_log, _, _, outcome) = service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(outcome, SubmitOutcome::AlreadyLanded);
+        assert_eq!(transaction_log.id, transaction_log_id);
+        assert_eq!(transaction_log.status(), TxStatus::Succeeded);
+
+        // Without an account, nothing is logged to check against.
+        assert!(service
+            .submit_transaction(&tx_proposal, None, None, None, false)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test_with_logger]
+    fn test_parallel_builds_for_one_account_do_not_share_inputs(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = Arc::new(setup_wallet_service(ledger_db.clone(), logger.clone()));
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        let alice_public_address = alice_account_key.default_subaddress();
+
+        // Give Alice several txos, so that each build needs only one of them.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_public_address.clone(); 8],
+            10 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient = b58_encode_public_address(&alice_public_address).unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let service = service.clone();
+                let account_id_hex = alice.id.clone();
+                let recipient = recipient.clone();
+                thread::spawn(move || {
+                    service
+                        .build_sign_and_submit_transaction(
+                            &account_id_hex,
+                            &[(recipient, AmountJSON::new(MOB, Mob::ID))],
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            None,
+                            false,
+                            None,
+                            TransactionMemo::RTH,
+                            None,
+                        )
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        // Builds are serialized, and each sees the inputs of earlier builds as
+        // pending, so no input is spent twice.
+        let mut key_images = HashSet::new();
+        for handle in handles {
+            let (_transaction_log, _associated_txos, _value_map, tx_proposal) =
+                handle.join().unwrap();
+            for input_txo in tx_proposal.input_txos {
+                assert!(key_images.insert(input_txo.key_image));
             }
-            Err(TransactionServiceError::TransactionBuilder(
-                WalletTransactionBuilderError::InvalidArgument(_),
-            )) => {}
-            Err(e) => panic!("Unexpected error {:?}", e),
-        };
+        }
+        assert_eq!(service.build_guard.active_builds(), 0);
+    }
+
+    #[test_with_logger]
+    fn test_pending_inputs_are_not_selected_until_log_fails(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            60 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+
+        // The first payment spends only the 60 MOB txo, and is left pending.
+        let (transaction_log, associated_txos, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(recipient.clone(), AmountJSON::new(50 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        assert_eq!(associated_txos.inputs.len(), 1);
+        assert_eq!(associated_txos.inputs[0].value as u64, 60 * MOB);
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        assert_eq!(
+            Txo::pending_txo_ids(&alice.id, &conn).unwrap(),
+            vec![associated_txos.inputs[0].id.clone()]
+        );
+
+        // A second payment which needs both txos cannot use the pending one.
+        let second_payment = || {
+            service.build_and_sign_transaction(
+                &alice.id,
+                &[(recipient.clone(), AmountJSON::new(120 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+        };
+        let err = second_payment().unwrap_err();
+        assert!(err.insufficient_funds().is_some(), "{:?}", err);
+
+        // Once the pending log expires, its input can be spent again.
+        TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
+            &alice.id,
+            transaction_log.tombstone_block_index.unwrap() as u64 + 1,
+            &conn,
+        )
+        .unwrap();
+        assert!(Txo::pending_txo_ids(&alice.id, &conn).unwrap().is_empty());
+
+        let tx_proposal = second_payment().unwrap();
+        assert_eq!(tx_proposal.input_txos.len(), 2);
+    }
+
+    #[test_with_logger]
+    fn test_preview_then_build_with_selection_token(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+
+        for value in [100 * MOB, 60 * MOB] {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_account_key.default_subaddress()],
+                value,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        let addresses_and_amounts = [(recipient, AmountJSON::new(50 * MOB, Mob::ID))];
+
+        let preview = service
+            .preview_transaction(
+                &alice.id,
+                &addresses_and_amounts,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        let build_preview = &preview.build_preview;
+        assert_eq!(build_preview.inputs.len(), 1);
+        let input_value = build_preview.inputs[0].value as u64;
+        assert_eq!(build_preview.fee, Mob::MINIMUM_FEE);
+        assert_eq!(build_preview.fee_token_id, Mob::ID);
+        assert_eq!(
+            build_preview.change_per_token.get(&Mob::ID),
+            Some(&(input_value - 50 * MOB - Mob::MINIMUM_FEE))
+        );
+        let previewed_ids: Vec<String> = build_preview
+            .inputs
+            .iter()
+            .map(|txo| txo.id.clone())
+            .collect();
+
+        // The token is only valid for the account it was made for.
+        match service.resolve_selection_token(
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            &preview.selection_token,
+        ) {
+            Err(TransactionServiceError::SelectionTokenAccountMismatch(account_id)) => {
+                assert_eq!(account_id, alice.id);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // Building with the token spends exactly the previewed inputs.
+        let input_txo_ids = service
+            .resolve_selection_token(&alice.id, &preview.selection_token)
+            .unwrap();
+        assert_eq!(input_txo_ids, previewed_ids);
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &addresses_and_amounts,
+                Some(&input_txo_ids),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        let built_ids: Vec<String> = tx_proposal
+            .input_txos
+            .iter()
+            .map(|input| TxoID::from(&input.tx_out).to_string())
+            .collect();
+        assert_eq!(built_ids, previewed_ids);
+        assert_eq!(tx_proposal.tx.prefix.fee, build_preview.fee);
+        assert_eq!(
+            tx_proposal.tx.prefix.tombstone_block,
+            build_preview.tombstone_block_index
+        );
+
+        // Once the inputs are pending in a submitted transaction, the token
+        // can no longer be used.
+        service
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
+            .unwrap();
+        match service.resolve_selection_token(&alice.id, &preview.selection_token) {
+            Err(TransactionServiceError::SelectionNoLongerSpendable(details)) => {
+                assert_eq!(details, format!("{} is pending", previewed_ids[0]));
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_builds_for_other_accounts_are_not_blocked(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = Arc::new(setup_wallet_service(ledger_db.clone(), logger.clone()));
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_account_key: AccountKey = mc_util_serial::decode(&bob.account_key).unwrap();
+        let bob_account_id = AccountID::from(&bob_account_key);
+        let bob_public_address = bob_account_key.default_subaddress();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![bob_public_address.clone()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &bob_account_id, &logger);
+
+        // While a build for Alice is in progress, Bob can still build.
+        let _alice_permit = service.build_guard.acquire(&alice.id).unwrap();
+
+        let bob_service = service.clone();
+        let bob_id = bob.id.clone();
+        let recipient = b58_encode_public_address(&bob_public_address).unwrap();
+        let built = thread::spawn(move || {
+            bob_service
+                .build_and_sign_transaction(
+                    &bob_id,
+                    &[(recipient, AmountJSON::new(42 * MOB, Mob::ID))],
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    TransactionMemo::RTH,
+                )
+                .is_ok()
+        })
+        .join()
+        .unwrap();
+        assert!(built);
+        assert_eq!(service.build_guard.active_builds(), 1);
+    }
+
+    #[test_with_logger]
+    fn test_build_with_account_transaction_defaults(logger: Logger) {
+        use crate::service::account::AccountServiceError;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        let alice_public_address = alice_account_key.default_subaddress();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_public_address.clone(), alice_public_address.clone()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+        let num_blocks = ledger_db.num_blocks().unwrap();
+
+        // Defaults are validated like the builder's parameters.
+        match service.update_account_transaction_defaults(&alice_account_id, Some(0), Some(0), None)
+        {
+            Err(AccountServiceError::InvalidTransactionDefault(_)) => {}
+            other => panic!("Expected InvalidTransactionDefault, got {:?}", other),
+        }
+        match service.update_account_transaction_defaults(&alice_account_id, None, None, Some(0)) {
+            Err(AccountServiceError::InvalidTransactionDefault(_)) => {}
+            other => panic!("Expected InvalidTransactionDefault, got {:?}", other),
+        }
+
+        let account = service
+            .update_account_transaction_defaults(
+                &alice_account_id,
+                Some(Mob::MINIMUM_FEE * 2),
+                Some(*Mob::ID),
+                Some(25),
+            )
+            .unwrap();
+        assert_eq!(
+            account.default_fee_value,
+            Some((Mob::MINIMUM_FEE * 2) as i64)
+        );
+        assert_eq!(account.default_fee_token_id, Some(*Mob::ID as i64));
+        assert_eq!(account.default_tombstone_offset, Some(25));
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+
+        // A build without a fee or tombstone uses the account's defaults.
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(recipient.clone(), AmountJSON::new(10 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.tx.prefix.fee, Mob::MINIMUM_FEE * 2);
+        assert_eq!(tx_proposal.tx.prefix.tombstone_block, num_blocks + 25);
+
+        // Parameters given with the request override the defaults.
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(recipient, AmountJSON::new(10 * MOB, Mob::ID))],
+                None,
+                Some((Mob::MINIMUM_FEE * 3).to_string()),
+                None,
+                Some((num_blocks + 5).to_string()),
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.tx.prefix.fee, Mob::MINIMUM_FEE * 3);
+        assert_eq!(tx_proposal.tx.prefix.tombstone_block, num_blocks + 5);
+
+        // Clearing the defaults returns to the wallet's.
+        let account = service
+            .update_account_transaction_defaults(&alice_account_id, None, None, None)
+            .unwrap();
+        assert_eq!(account.default_fee_value, None);
+        assert_eq!(account.default_fee_token_id, None);
+        assert_eq!(account.default_tombstone_offset, None);
+    }
+
+    // Building a transaction for an invalid public address should fail.
+    #[test_with_logger]
+    fn test_invalid_public_address_fails(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        // Create our main account for the wallet
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+
+        // Add a block with a transaction for Alice
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        let alice_public_address = alice_account_key.default_subaddress();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_public_address.clone()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &vec![("NOTB58".to_string(), AmountJSON::new(42 * MOB, Mob::ID))],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Ok(_) => {
+                panic!("Should not be able to build transaction to invalid b58 public address")
+            }
+            Err(TransactionServiceError::InvalidPublicAddress(_)) => {}
+            Err(e) => panic!("Unexpected error {:?}", e),
+        };
+    }
+
+    #[test_with_logger]
+    fn test_maximum_inputs_and_outputs(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        // Create our main account for the wallet
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+
+        // Add a block with a transaction for Alice
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        let alice_public_address = alice_account_key.default_subaddress();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_public_address.clone()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // test ouputs
+        let mut outputs = Vec::new();
+        for _ in 0..17 {
+            outputs.push((
+                b58_encode_public_address(&alice_public_address).unwrap(),
+                AmountJSON::new(42 * MOB, Mob::ID),
+            ));
+        }
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &outputs,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Ok(_) => {
+                panic!("Should not be able to build transaction with too many ouputs")
+            }
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::InvalidArgument(_),
+            )) => {}
+            Err(e) => panic!("Unexpected error {:?}", e),
+        };
+
+        // test inputs
+        let mut outputs = Vec::new();
+        for _ in 0..2 {
+            outputs.push((
+                b58_encode_public_address(&alice_public_address).unwrap(),
+                AmountJSON::new(42 * MOB, Mob::ID),
+            ));
+        }
+        let mut inputs = Vec::new();
+        for _ in 0..17 {
+            inputs.push("fake txo id".to_string());
+        }
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &outputs,
+            Some(&inputs),
+            None,
+            None,
+            None,
+            None,
+            None,
+            false,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Ok(_) => {
+                panic!("Should not be able to build transaction with too many inputs")
+            }
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::InvalidArgument(_),
+            )) => {}
+            Err(e) => panic!("Unexpected error {:?}", e),
+        };
+    }
+
+    #[test_with_logger]
+    fn test_check_recipient_networks(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let mut service = setup_wallet_service(ledger_db, logger);
+        service.address_network_policy =
+            AddressNetworkPolicy::new(Some("mainnet".to_string()), vec![], vec![], vec![]);
+
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let testnet_recipient = PublicAddress::new_with_fog(
+            recipient.spend_public_key(),
+            recipient.view_public_key(),
+            "fog://fog.test.mobilecoin.com",
+            "".to_string(),
+            vec![1u8; 64],
+        );
+        let addresses_and_amounts = vec![
+            (
+                b58_encode_public_address(&recipient).unwrap(),
+                AmountJSON::new(42 * MOB, Mob::ID),
+            ),
+            (
+                b58_encode_public_address(&testnet_recipient).unwrap(),
+                AmountJSON::new(42 * MOB, Mob::ID),
+            ),
+        ];
+
+        // Recipients without fog are allowed, but a fog recipient on another
+        // network fails the build unless it is overridden.
+        assert!(service
+            .check_recipient_networks(&addresses_and_amounts[..1], false)
+            .is_ok());
+        match service.check_recipient_networks(&addresses_and_amounts, false) {
+            Err(TransactionServiceError::CrossNetworkAddress(address, _)) => {
+                assert_eq!(address, addresses_and_amounts[1].0)
+            }
+            other => panic!("Expected a cross network error, got {:?}", other),
+        }
+        assert!(service
+            .check_recipient_networks(&addresses_and_amounts, true)
+            .is_ok());
+    }
+
+    #[test_with_logger]
+    fn test_propose_tx_dry_run_requires_validator(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice_account_id.to_string(),
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+
+        // Without a validator connection there is nothing to check against,
+        // and nothing is submitted.
+        match service.propose_tx_dry_run(&tx_proposal) {
+            Err(TransactionServiceError::DryRunUnavailable) => {}
+            other => panic!("Expected DryRunUnavailable, got {:?}", other),
+        }
+        assert!(service
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
+            .unwrap()
+            .iter()
+            .all(|(transaction_log, _, _)| transaction_log.submitted_block_index.is_none()));
+    }
+
+    #[test_with_logger]
+    fn test_rebuild_expired_transactions(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        let (original, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(recipient.clone(), AmountJSON::new(42 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                Some("rent".to_string()),
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        let original = service.set_auto_rebuild(&original.id, 2, 50).unwrap();
+        assert_eq!(original.auto_rebuild_attempts_left, Some(2));
+
+        // Nothing is rebuilt while the transaction is pending.
+        assert!(service.rebuild_expired_transactions().unwrap().is_empty());
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        let expire = |transaction_log: &TransactionLog| {
+            let past_tombstone = transaction_log.tombstone_block_index.unwrap() as u64 + 1;
+            TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
+                &alice.id,
+                past_tombstone,
+                &conn,
+            )
+            .unwrap();
+            Account::get(&alice_account_id, &conn)
+                .unwrap()
+                .update_next_block_index(past_tombstone, &conn)
+                .unwrap();
+        };
+
+        // Once it expires, the same payment is rebuilt from the released
+        // inputs with the fee raised, and linked to the log it replaces.
+        expire(&original);
+        let rebuilt = service.rebuild_expired_transactions().unwrap();
+        assert_eq!(rebuilt.len(), 1);
+        let first_rebuild = &rebuilt[0];
+        assert_ne!(first_rebuild.id, original.id);
+        assert_eq!(first_rebuild.status(), TxStatus::Pending);
+        assert_eq!(first_rebuild.comment, "rent");
+        assert_eq!(
+            first_rebuild.fee_value as u64,
+            bumped_fee(original.fee_value as u64, 50)
+        );
+        assert_eq!(first_rebuild.auto_rebuild_attempts_left, Some(1));
+
+        let associated_txos = first_rebuild.get_associated_txos(&conn).unwrap();
+        assert_eq!(associated_txos.outputs.len(), 1);
+        assert_eq!(associated_txos.outputs[0].0.value as u64, 42 * MOB);
+        assert_eq!(associated_txos.outputs[0].1, recipient);
+
+        let original = TransactionLog::get(&TransactionID::from(&original), &conn).unwrap();
+        assert_eq!(original.status(), TxStatus::Failed);
+        assert_eq!(original.superseded_by, Some(first_rebuild.id.clone()));
+
+        // The last attempt raises the fee again.
+        expire(first_rebuild);
+        let rebuilt = service.rebuild_expired_transactions().unwrap();
+        assert_eq!(rebuilt.len(), 1);
+        let second_rebuild = &rebuilt[0];
+        assert_eq!(
+            second_rebuild.fee_value as u64,
+            bumped_fee(first_rebuild.fee_value as u64, 50)
+        );
+        assert_eq!(second_rebuild.auto_rebuild_attempts_left, Some(0));
+
+        // The chain runs from the original to the latest, from any of them.
+        let chain = vec![
+            original.id.clone(),
+            first_rebuild.id.clone(),
+            second_rebuild.id.clone(),
+        ];
+        for transaction_log_id in chain.iter() {
+            assert_eq!(
+                service
+                    .get_transaction_log_rebuild_chain(transaction_log_id)
+                    .unwrap(),
+                chain
+            );
+        }
+
+        // With no attempts left, an expired rebuild is left failed.
+        expire(second_rebuild);
+        assert!(service.rebuild_expired_transactions().unwrap().is_empty());
+        let second_rebuild =
+            TransactionLog::get(&TransactionID::from(second_rebuild), &conn).unwrap();
+        assert_eq!(second_rebuild.status(), TxStatus::Failed);
+        assert_eq!(second_rebuild.superseded_by, None);
+    }
+
+    #[test_with_logger]
+    fn test_rebuild_stops_on_insufficient_funds(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Spend nearly everything, leaving too little to raise the fee.
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        let fee = Mob::MINIMUM_FEE;
+        let (original, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(recipient, AmountJSON::new(100 * MOB - fee, Mob::ID))],
+                None,
+                Some(fee.to_string()),
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        service.set_auto_rebuild(&original.id, 3, 100).unwrap();
+
+        let conn = service.wallet_db.get_conn().unwrap();
+        let past_tombstone = original.tombstone_block_index.unwrap() as u64 + 1;
+        TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
+            &alice.id,
+            past_tombstone,
+            &conn,
+        )
+        .unwrap();
+        Account::get(&alice_account_id, &conn)
+            .unwrap()
+            .update_next_block_index(past_tombstone, &conn)
+            .unwrap();
+
+        assert!(service.rebuild_expired_transactions().unwrap().is_empty());
+        let original = TransactionLog::get(&TransactionID::from(&original), &conn).unwrap();
+        assert_eq!(original.auto_rebuild_attempts_left, Some(0));
+        assert_eq!(original.superseded_by, None);
+
+        // It is not tried again.
+        assert!(TransactionLog::list_awaiting_rebuild(&conn)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_rebuild_skips_transactions_which_landed(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        let bob = service
+            .create_account(None, "".to_string(), "".to_string(), "".to_string())
+            .unwrap();
+        let bob_account_key: AccountKey = mc_util_serial::decode(&bob.account_key).unwrap();
+        let bob_account_id = AccountID::from(&bob_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        let (original, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(recipient, AmountJSON::new(42 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        service.set_auto_rebuild(&original.id, 2, 50).unwrap();
+
+        // The transaction lands, and the ledger grows past its tombstone.
+        let conn = service.wallet_db.get_conn().unwrap();
+        let landed_block_index =
+            add_block_from_transaction_log(&mut ledger_db, &conn, &original, &mut rng) - 1;
+        while ledger_db.num_blocks().unwrap() <= original.tombstone_block_index.unwrap() as u64 + 1
+        {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![bob_account_key.default_subaddress()],
+                MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+
+        // Bob syncing past the tombstone does not fail Alice's transaction,
+        // which she has not synced far enough to see land.
+        manually_sync_account(&ledger_db, &service.wallet_db, &bob_account_id, &logger);
+        let original = TransactionLog::get(&TransactionID::from(&original), &conn).unwrap();
+        assert_eq!(original.status(), TxStatus::Pending);
+
+        // Even if it was marked failed, it is not rebuilt while Alice is
+        // behind its tombstone.
+        TransactionLog::update_pending_exceeding_tombstone_block_index_to_failed(
+            &alice.id,
+            original.tombstone_block_index.unwrap() as u64 + 1,
+            &conn,
+        )
+        .unwrap();
+        assert!(service.rebuild_expired_transactions().unwrap().is_empty());
+        let original = TransactionLog::get(&TransactionID::from(&original), &conn).unwrap();
+        assert_eq!(original.status(), TxStatus::Failed);
+
+        // Nor once she has synced, as the ledger holds it. It is marked
+        // succeeded instead of being paid again.
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+        assert!(service.rebuild_expired_transactions().unwrap().is_empty());
+        let original = TransactionLog::get(&TransactionID::from(&original), &conn).unwrap();
+        assert_eq!(original.status(), TxStatus::Succeeded);
+        assert_eq!(
+            original.finalized_block_index,
+            Some(landed_block_index as i64)
+        );
+        assert_eq!(original.superseded_by, None);
+        assert!(TransactionLog::list_awaiting_rebuild(&conn)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_build_from_source_subaddress(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+
+        // Fund two subaddresses with two txos each.
+        let source = service
+            .assign_address_for_account(&alice_account_id, Some("Source"))
+            .unwrap();
+        let other = service
+            .assign_address_for_account(&alice_account_id, Some("Other"))
+            .unwrap();
+        for subaddress in [&source, &other, &source, &other] {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_account_key.subaddress(subaddress.subaddress_index as u64)],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_address = service
+            .assign_address_for_account(&AccountID(bob.id.clone()), None)
+            .unwrap();
+        let payment = [(
+            bob_address.public_address_b58.clone(),
+            AmountJSON::new(150 * MOB, Mob::ID),
+        )];
+
+        // Explicit inputs from another subaddress are rejected.
+        let conn = service.wallet_db.get_conn().unwrap();
+        let other_txo_ids: Vec<String> = Txo::list_for_address(
+            &other.public_address_b58,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap()
+        .iter()
+        .map(|txo| txo.id.clone())
+        .collect();
+        assert_eq!(other_txo_ids.len(), 2);
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &payment,
+            Some(&other_txo_ids),
+            None,
+            None,
+            None,
+            None,
+            Some(source.public_address_b58.as_str()),
+            false,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::InputsOutsideSourceSubaddress(txo_ids),
+            )) => assert_eq!(txo_ids.len(), 2),
+            result => panic!(
+                "Expected InputsOutsideSourceSubaddress, got {:?}",
+                result.map(|_| ())
+            ),
+        }
+
+        // A subaddress of another account is not a source.
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &payment,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(bob_address.public_address_b58.as_str()),
+            false,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::SourceSubaddressNotInAccount(_),
+            )) => {}
+            result => panic!(
+                "Expected SourceSubaddressNotInAccount, got {:?}",
+                result.map(|_| ())
+            ),
+        }
+
+        let (transaction_log, _associated_txos, _value_map, tx_proposal) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &payment,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(source.public_address_b58.as_str()),
+                true,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+
+        // Both inputs come from the source subaddress, and the change returns
+        // to it.
+        assert_eq!(tx_proposal.input_txos.len(), 2);
+        for input_txo in tx_proposal.input_txos.iter() {
+            assert_eq!(input_txo.subaddress_index, source.subaddress_index as u64);
+        }
+        assert_eq!(tx_proposal.change_txos.len(), 1);
+        assert_eq!(
+            tx_proposal.change_txos[0].recipient_public_address,
+            alice_account_key.subaddress(source.subaddress_index as u64)
+        );
+
+        add_block_from_transaction_log(&mut ledger_db, &conn, &transaction_log, &mut rng);
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let fee = tx_proposal.tx.prefix.fee;
+        let source_balance = service
+            .get_balance_for_address(&source.public_address_b58)
+            .unwrap();
+        assert_eq!(
+            source_balance.get(&Mob::ID).unwrap().unspent,
+            (50 * MOB - fee) as u128
+        );
+        let other_balance = service
+            .get_balance_for_address(&other.public_address_b58)
+            .unwrap();
+        assert_eq!(
+            other_balance.get(&Mob::ID).unwrap().unspent,
+            200 * MOB as u128
+        );
+    }
+
+    #[test_with_logger]
+    fn test_change_to_source_requires_source_subaddress(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &service.wallet_db,
+            &AccountID::from(&alice_account_key),
+            &logger,
+        );
+
+        let recipient =
+            b58_encode_public_address(&AccountKey::random(&mut rng).default_subaddress()).unwrap();
+        match service.build_and_sign_transaction(
+            &alice.id,
+            &[(recipient, AmountJSON::new(42 * MOB, Mob::ID))],
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            true,
+            None,
+            TransactionMemo::RTH,
+        ) {
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::ChangeToSourceWithoutSourceSubaddress,
+            )) => {}
+            result => panic!(
+                "Expected ChangeToSourceWithoutSourceSubaddress, got {:?}",
+                result.map(|_| ())
+            ),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_sign_inputs_at_unassigned_subaddress(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+
+        // Fund subaddress 999, which is assigned on this wallet, and build a
+        // transaction spending it.
+        let address = service
+            .assign_address_at_index(&alice_account_id, 999, None)
+            .unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.subaddress(999)],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let (unsigned_tx, fog_resolver) = service
+            .build_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    AmountJSON::new(50 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(
+            unsigned_tx.input_subaddress_indices(),
+            BTreeSet::from([999])
+        );
+
+        // Sign it on another wallet, which only has subaddresses 0 to 2 of
+        // the account assigned.
+        let signing_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let mnemonic =
+            Mnemonic::from_entropy(alice.entropy.as_ref().unwrap(), Language::English).unwrap();
+        signing_service
+            .import_account(
+                mnemonic.phrase().to_string(),
+                MNEMONIC_KEY_DERIVATION_VERSION,
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        let assigned = signing_service
+            .assign_address_for_account(&alice_account_id, None)
+            .unwrap();
+        assert_eq!(assigned.subaddress_index, 2);
+
+        let conn = signing_service.wallet_db.get_conn().unwrap();
+        assert!(AssignedSubaddress::get_for_account_by_index(&alice.id, 999, &conn).is_err());
+        let account = Account::get(&alice_account_id, &conn).unwrap();
+
+        // An input is not signed with the spend key of a subaddress it is not
+        // at.
+        let mut mislabeled_tx = unsigned_tx.clone();
+        mislabeled_tx.inputs_and_real_indices_and_subaddress_indices[0].2 = 2;
+        match signing_service.sign_transaction(mislabeled_tx, fog_resolver.clone(), &account, &conn)
+        {
+            Err(TransactionServiceError::TransactionBuilder(
+                WalletTransactionBuilderError::InputSubaddressIndexMismatch(2),
+            )) => {}
+            result => panic!(
+                "Expected InputSubaddressIndexMismatch, got {:?}",
+                result.map(|_| ())
+            ),
+        }
+
+        let tx_proposal = signing_service
+            .sign_transaction(unsigned_tx, fog_resolver, &account, &conn)
+            .unwrap();
+        assert_eq!(tx_proposal.input_txos.len(), 1);
+        assert_eq!(tx_proposal.input_txos[0].subaddress_index, 999);
+
+        // The input was spent with the key image the building wallet derived
+        // for it.
+        let txos = Txo::list_for_address(
+            &address.public_address_b58,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &service.wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(txos.len(), 1);
+        assert_eq!(
+            txos[0].key_image,
+            Some(mc_util_serial::encode(&tx_proposal.input_txos[0].key_image))
+        );
+    }
+
+    #[test_with_logger]
+    fn test_build_with_read_only_ledger(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        // The ledger is written through one handle, as the process which owns
+        // it would, and read through another.
+        let ledger_db_tmp = TempDir::new("ledger_db").unwrap();
+        let ledger_db_path = ledger_db_tmp.path().to_str().unwrap();
+        let mut ledger_db = generate_ledger_db(ledger_db_path);
+        let recipients: Vec<PublicAddress> = (0..5)
+            .map(|_| AccountKey::random(&mut rng).default_subaddress())
+            .collect();
+        for block_index in 0..12 {
+            let key_images = if block_index == 0 {
+                vec![]
+            } else {
+                vec![KeyImage::from(rng.next_u64())]
+            };
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &recipients,
+                DEFAULT_PER_RECIPIENT_AMOUNT,
+                &key_images,
+                &mut rng,
+            );
+        }
+
+        let read_only_ledger_db = LedgerDB::open(&PathBuf::from(ledger_db_path)).unwrap();
+        let service = setup_wallet_service_read_only(read_only_ledger_db, logger.clone());
+
+        let status = service.get_wallet_status(None).unwrap();
+        assert!(status.ledger_read_only);
+        assert_eq!(status.local_block_height, 12);
+        assert_eq!(status.ledger_height_lag, 0);
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+
+        // Append a block paying Alice through the writing handle.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+
+        // The read-only service observes the new block.
+        assert_eq!(service.ledger_db.num_blocks().unwrap(), 13);
+        {
+            let mut network_state = service.network_state.write().unwrap();
+            network_state.poll();
+        }
+        let status = service.get_wallet_status(None).unwrap();
+        assert_eq!(status.local_block_height, 13);
+        assert_eq!(status.network_block_height, 13);
+        assert_eq!(status.ledger_height_lag, 0);
+
+        manually_sync_account(
+            &service.ledger_db,
+            &service.wallet_db,
+            &alice_account_id,
+            &logger,
+        );
+        let balance = service.get_balance_for_account(&alice_account_id).unwrap();
+        assert_eq!(balance.get(&Mob::ID).unwrap().unspent, 100 * MOB as u128);
+
+        // And builds with the txo it received in the new block.
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    AmountJSON::new(42 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.input_txos.len(), 1);
+        assert_eq!(tx_proposal.input_txos[0].amount.value, 100 * MOB);
+    }
+
+    fn connection_error(error: mc_connection::Error) -> TransactionServiceError {
+        TransactionServiceError::Connection(retry::Error::Operation {
+            error,
+            total_delay: Duration::default(),
+            tries: 1,
+        })
+    }
+
+    fn unavailable() -> mc_connection::Error {
+        mc_connection::Error::Grpc(grpcio::Error::RpcFailure(grpcio::RpcStatus::new(
+            grpcio::RpcStatusCode::UNAVAILABLE,
+        )))
+    }
+
+    fn rejected(err: TransactionValidationError) -> mc_connection::Error {
+        mc_connection::Error::TransactionValidation(err)
+    }
+
+    #[test]
+    fn test_submission_error_categories() {
+        let category = |error| connection_error(error).submission_error_category().unwrap();
+
+        assert_eq!(
+            category(unavailable()),
+            SubmissionErrorCategory::RetryableNetwork
+        );
+        assert_eq!(
+            category(rejected(TransactionValidationError::TxFeeError)),
+            SubmissionErrorCategory::NeedsRebuildFee
+        );
+        assert_eq!(
+            category(rejected(TransactionValidationError::TombstoneBlockExceeded)),
+            SubmissionErrorCategory::NeedsRebuildTombstone
+        );
+        assert_eq!(
+            category(rejected(TransactionValidationError::TombstoneBlockTooFar)),
+            SubmissionErrorCategory::NeedsRebuildTombstone
+        );
+        assert_eq!(
+            category(rejected(TransactionValidationError::ContainsSpentKeyImage)),
+            SubmissionErrorCategory::PermanentDoubleSpend
+        );
+        assert_eq!(
+            category(rejected(TransactionValidationError::InvalidRangeProof)),
+            SubmissionErrorCategory::Unknown
+        );
+        assert_eq!(
+            SubmissionErrorCategory::from(&retry::Error::Internal("timeout".to_string())),
+            SubmissionErrorCategory::Unknown
+        );
+        assert!(SubmissionErrorCategory::RetryableNetwork.is_retryable());
+        assert!(SubmissionErrorCategory::AttestationFailure.is_retryable());
+        assert!(!SubmissionErrorCategory::NeedsRebuildFee.is_retryable());
+        assert!(!SubmissionErrorCategory::PermanentDoubleSpend.is_retryable());
+
+        // Errors from before the transaction reached a peer have no category.
+        assert!(TransactionServiceError::NoPeersConfigured
+            .submission_error_category()
+            .is_none());
+    }
+
+    #[test_with_logger]
+    fn test_propose_with_failover(logger: Logger) {
+        let peers: Vec<ResponderId> = vec![
+            "peer1.test.com:443".parse().unwrap(),
+            "peer2.test.com:443".parse().unwrap(),
+        ];
+
+        // A network failure is retried once, on the next peer.
+        let mut attempts = Vec::new();
+        let block_index = propose_with_failover(&peers, 0, &logger, |responder_id| {
+            attempts.push(responder_id.clone());
+            if attempts.len() == 1 {
+                Err(connection_error(unavailable()))
+            } else {
+                Ok(12)
+            }
+        })
+        .unwrap();
+        assert_eq!(block_index, 12);
+        assert_eq!(attempts, vec![peers[0].clone(), peers[1].clone()]);
+
+        // The retry wraps around to the first peer.
+        let mut attempts = Vec::new();
+        let result = propose_with_failover(&peers, 1, &logger, |responder_id| {
+            attempts.push(responder_id.clone());
+            Err(connection_error(unavailable()))
+        });
+        assert_eq!(
+            result.unwrap_err().submission_error_category(),
+            Some(SubmissionErrorCategory::RetryableNetwork)
+        );
+        assert_eq!(attempts, vec![peers[1].clone(), peers[0].clone()]);
+
+        // A rejection of the transaction itself is not retried.
+        let mut attempts = Vec::new();
+        let result = propose_with_failover(&peers, 0, &logger, |responder_id| {
+            attempts.push(responder_id.clone());
+            Err(connection_error(rejected(
+                TransactionValidationError::ContainsSpentKeyImage,
+            )))
+        });
+        assert_eq!(
+            result.unwrap_err().submission_error_category(),
+            Some(SubmissionErrorCategory::PermanentDoubleSpend)
+        );
+        assert_eq!(attempts.len(), 1);
+
+        // Nor is a network failure when there is no other peer.
+        let mut attempts = Vec::new();
+        let result = propose_with_failover(&peers[..1], 0, &logger, |responder_id| {
+            attempts.push(responder_id.clone());
+            Err(connection_error(unavailable()))
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts.len(), 1);
     }
 
     // FIXME: Test with 0 change transactions
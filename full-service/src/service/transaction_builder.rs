@@ -12,22 +12,25 @@ use crate::{
     db::{
         account::{AccountID, AccountModel},
         assigned_subaddress::AssignedSubaddressModel,
-        models::{Account, Txo},
-        txo::TxoModel,
-        Conn,
+        models::{Account, AssignedSubaddress, Txo},
+        txo::{unconfirmed_block_index, TxoModel, TxoStatus},
+        Conn, InsufficientInputFundsAmounts, WalletDbError,
     },
     error::WalletTransactionBuilderError,
-    fog_resolver::{FullServiceFogResolver, FullServiceFullyValidatedFogPubkey},
-    service::transaction::TransactionMemo,
+    fog_resolver::{FogPubkeyCache, FullServiceFogResolver, FullServiceFullyValidatedFogPubkey},
+    service::{ledger::invalid_membership_proofs, transaction::TransactionMemo},
     unsigned_tx::UnsignedTx,
-    util::b58::b58_encode_public_address,
+    util::{b58::b58_encode_public_address, constants::UNSIGNED_TX_SCHEMA_VERSION},
 };
 use mc_account_keys::PublicAddress;
-use mc_common::{HashMap, HashSet};
+use mc_common::{
+    logger::{log, Logger},
+    HashMap, HashSet,
+};
 use mc_fog_report_validation::FogPubkeyResolver;
 use mc_ledger_db::{Ledger, LedgerDB};
 use mc_transaction_core::{
-    constants::RING_SIZE,
+    constants::{MAX_INPUTS, MAX_OUTPUTS, RING_SIZE},
     tokens::Mob,
     tx::{TxIn, TxOut, TxOutMembershipProof},
     BlockVersion, Token, TokenId,
@@ -36,13 +39,38 @@ use mc_transaction_core::{
 use mc_util_uri::FogUri;
 
 use rand::Rng;
-use std::{collections::BTreeMap, str::FromStr, sync::Arc};
+use std::{collections::BTreeMap, str::FromStr, sync::Arc, time::Instant};
 
 /// Default number of blocks used for calculating transaction tombstone block
 /// number.
 // TODO support for making this configurable
 pub const DEFAULT_NEW_TX_BLOCK_ATTEMPTS: u64 = 10;
 
+/// Default smallest value of a change output produced by splitting change. A
+/// piece smaller than the fee needed to spend it is not worth creating.
+pub const DEFAULT_CHANGE_DUST_FLOOR: u64 = Mob::MINIMUM_FEE;
+
+/// What a transaction would spend and pay, worked out before any membership
+/// proofs are fetched or rings sampled.
+#[derive(Clone, Debug)]
+pub struct BuildPreview {
+    /// The txos which would be spent.
+    pub inputs: Vec<Txo>,
+
+    /// The fee, including any change of the fee token below its dust
+    /// threshold.
+    pub fee: u64,
+
+    /// The token the fee is paid in.
+    pub fee_token_id: TokenId,
+
+    /// The change of each token among the inputs.
+    pub change_per_token: BTreeMap<TokenId, u64>,
+
+    /// The block after which the transaction would be invalid.
+    pub tombstone_block_index: u64,
+}
+
 /// A builder of transactions constructed from this wallet.
 pub struct WalletTransactionBuilder<FPR: FogPubkeyResolver + 'static> {
     /// Account ID (hex-encoded) from which to construct a transaction.
@@ -71,6 +99,57 @@ pub struct WalletTransactionBuilder<FPR: FogPubkeyResolver + 'static> {
     /// This is abstracted because in tests, we don't want to form grpc
     /// connections to fog.
     fog_resolver_factory: Arc<dyn Fn(&[FogUri]) -> Result<FPR, String> + Send + Sync>,
+
+    /// Optional cache of validated fog pubkeys shared across builds, so that
+    /// fog reports are only fetched for recipients which are not cached.
+    fog_pubkey_cache: Option<Arc<FogPubkeyCache>>,
+
+    /// Optional logger used to report how long each stage of the build took.
+    logger: Option<Logger>,
+
+    /// Whether to verify proofs of membership against the ledger root before
+    /// building, so that a corrupt ledger fails fast.
+    verify_membership_proofs: bool,
+
+    /// The number of outputs the change of each token is divided between, so
+    /// that the account is not left holding a single large txo which is
+    /// locked by every build.
+    split_change_into: usize,
+
+    /// Change is not split into outputs smaller than this value.
+    change_dust_floor: u64,
+
+    /// Whether to leave out the change output of a token whose change is
+    /// zero, instead of adding a zero value output.
+    omit_zero_change: bool,
+
+    /// The smallest value of an output of each token. Tokens without a
+    /// threshold are not checked.
+    dust_thresholds: BTreeMap<TokenId, u64>,
+
+    /// Whether to allow outputs below the dust thresholds.
+    allow_dust: bool,
+
+    /// Number of blocks a received txo must be in the ledger for before it
+    /// is selected as an input.
+    min_confirmations: u64,
+
+    /// Whether to select txos which do not have min_confirmations yet.
+    include_unconfirmed: bool,
+
+    /// Whether to avoid sampling the account's own txos as decoys in the
+    /// rings.
+    exclude_own_txos_from_rings: bool,
+
+    /// Ledger indices of the account's txos, loaded when the inputs are set.
+    own_tx_out_indices: Vec<u64>,
+
+    /// The subaddress all inputs must have been received at, if any.
+    source_subaddress: Option<AssignedSubaddress>,
+
+    /// Whether change goes to the source subaddress rather than the account's
+    /// change subaddress.
+    change_to_source: bool,
 }
 
 impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
@@ -88,11 +167,134 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             fee: None,
             block_version: None,
             fog_resolver_factory,
+            fog_pubkey_cache: None,
+            logger: None,
+            verify_membership_proofs: true,
+            split_change_into: 1,
+            change_dust_floor: DEFAULT_CHANGE_DUST_FLOOR,
+            omit_zero_change: false,
+            dust_thresholds: BTreeMap::new(),
+            allow_dust: false,
+            min_confirmations: 0,
+            include_unconfirmed: false,
+            exclude_own_txos_from_rings: true,
+            own_tx_out_indices: vec![],
+            source_subaddress: None,
+            change_to_source: false,
+        }
+    }
+
+    /// Sets the cache of validated fog pubkeys shared across builds.
+    pub fn set_fog_pubkey_cache(&mut self, fog_pubkey_cache: Arc<FogPubkeyCache>) {
+        self.fog_pubkey_cache = Some(fog_pubkey_cache);
+    }
+
+    /// Sets the logger used to report build timings.
+    pub fn set_logger(&mut self, logger: Logger) {
+        self.logger = Some(logger);
+    }
+
+    /// Sets whether proofs of membership are verified against the ledger root
+    /// before building. Defaults to true.
+    pub fn set_verify_membership_proofs(&mut self, verify_membership_proofs: bool) {
+        self.verify_membership_proofs = verify_membership_proofs;
+    }
+
+    /// Sets the number of outputs the change of each token is divided between.
+    /// Fewer outputs are used when the change is too small to split into
+    /// pieces above the dust floor, or the transaction would exceed the
+    /// maximum number of outputs. Defaults to 1.
+    pub fn set_split_change_into(&mut self, split_change_into: usize) {
+        self.split_change_into = split_change_into.max(1);
+    }
+
+    /// Sets the smallest value of a piece of split change. Defaults to
+    /// DEFAULT_CHANGE_DUST_FLOOR.
+    pub fn set_change_dust_floor(&mut self, change_dust_floor: u64) {
+        self.change_dust_floor = change_dust_floor;
+    }
+
+    /// Sets whether a token whose inputs exactly cover its outlays and fee gets
+    /// no change output, rather than a zero value one. The transaction still
+    /// has the outputs to its recipients. Defaults to false.
+    pub fn set_omit_zero_change(&mut self, omit_zero_change: bool) {
+        self.omit_zero_change = omit_zero_change;
+    }
+
+    /// Sets the smallest value of an output of each token. Recipients below
+    /// the threshold of their token are rejected, change is not split into
+    /// pieces below it, and change of the fee token below it is paid as part
+    /// of the fee instead of being returned. Must be set before adding
+    /// recipients.
+    pub fn set_dust_thresholds(&mut self, dust_thresholds: BTreeMap<TokenId, u64>) {
+        self.dust_thresholds = dust_thresholds;
+    }
+
+    /// Allows outputs below the dust thresholds, e.g. for testing.
+    pub fn set_allow_dust(&mut self, allow_dust: bool) {
+        self.allow_dust = allow_dust;
+    }
+
+    /// Sets the number of blocks a received txo must be in the ledger for
+    /// before it is selected as an input. The txo's own block counts as the
+    /// first confirmation. Defaults to 0.
+    pub fn set_min_confirmations(&mut self, min_confirmations: u64) {
+        self.min_confirmations = min_confirmations;
+    }
+
+    /// Sets whether txos without min_confirmations are selected anyway.
+    /// Defaults to false.
+    pub fn set_include_unconfirmed(&mut self, include_unconfirmed: bool) {
+        self.include_unconfirmed = include_unconfirmed;
+    }
+
+    /// The dust threshold of a token, if one is enforced.
+    fn dust_threshold(&self, token_id: TokenId) -> Option<u64> {
+        if self.allow_dust {
+            return None;
         }
+        self.dust_thresholds.get(&token_id).copied()
+    }
+
+    /// Sets whether the account's own txos are kept out of the rings, so that
+    /// they are not used as decoys. When the ledger is too small to fill the
+    /// rings without them, they are sampled as usual. Defaults to true, and
+    /// must be set before the inputs.
+    pub fn set_exclude_own_txos_from_rings(&mut self, exclude_own_txos_from_rings: bool) {
+        self.exclude_own_txos_from_rings = exclude_own_txos_from_rings;
+    }
+
+    /// Restricts the inputs to txos received at one of the account's
+    /// subaddresses, so that funds held at different subaddresses are never
+    /// spent together. Must be set before the inputs.
+    pub fn set_source_subaddress(
+        &mut self,
+        conn: &Conn,
+        subaddress_b58: &str,
+    ) -> Result<(), WalletTransactionBuilderError> {
+        let subaddress = match AssignedSubaddress::get(subaddress_b58, conn) {
+            Ok(subaddress) if subaddress.account_id == self.account_id_hex => subaddress,
+            Ok(_) | Err(WalletDbError::AssignedSubaddressNotFound(_)) => {
+                return Err(WalletTransactionBuilderError::SourceSubaddressNotInAccount(
+                    subaddress_b58.to_string(),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.source_subaddress = Some(subaddress);
+        Ok(())
+    }
+
+    /// Sets whether change is returned to the source subaddress instead of
+    /// the account's change subaddress. Requires a source subaddress when the
+    /// transaction is built. Defaults to false.
+    pub fn set_change_to_source(&mut self, change_to_source: bool) {
+        self.change_to_source = change_to_source;
     }
 
     /// Sets inputs to the txos associated with the given txo_ids. Only unspent
-    /// txos are included.
+    /// txos are included. When a source subaddress is set, every txo must
+    /// have been received at it.
     pub fn set_txos(
         &mut self,
         conn: &Conn,
@@ -100,6 +302,20 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
     ) -> Result<(), WalletTransactionBuilderError> {
         let txos = Txo::select_by_id(input_txo_ids, conn)?;
 
+        if let Some(subaddress) = &self.source_subaddress {
+            let outside: Vec<String> = txos
+                .iter()
+                .filter(|txo| {
+                    txo.account_id.as_deref() != Some(subaddress.account_id.as_str())
+                        || txo.subaddress_index != Some(subaddress.subaddress_index)
+                })
+                .map(|txo| txo.id.clone())
+                .collect();
+            if !outside.is_empty() {
+                return Err(WalletTransactionBuilderError::InputsOutsideSourceSubaddress(outside));
+            }
+        }
+
         let unspent: Vec<Txo> = txos
             .iter()
             .filter(|txo| txo.spent_block_index == None)
@@ -112,10 +328,11 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
 
         self.inputs = unspent;
 
-        Ok(())
+        self.load_own_tx_out_indices(conn)
     }
 
-    /// Selects Txos from the account.
+    /// Selects Txos from the account, or from the source subaddress if one is
+    /// set.
     pub fn select_txos(
         &mut self,
         conn: &Conn,
@@ -137,6 +354,12 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             .and_modify(|v| *v += fee_value as u128)
             .or_insert(fee_value as u128);
 
+        let unconfirmed_block_index = if self.include_unconfirmed {
+            None
+        } else {
+            unconfirmed_block_index(self.ledger_db.num_blocks()?, self.min_confirmations)
+        };
+
         for (token_id, target_value) in outlay_value_sum_map {
             if target_value > u64::MAX as u128 {
                 return Err(WalletTransactionBuilderError::OutboundValueTooLarge);
@@ -152,11 +375,63 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
                 &self.account_id_hex,
                 target_value as u64,
                 max_spendable_value,
+                self.source_subaddress
+                    .as_ref()
+                    .map(|subaddress| subaddress.public_address_b58.as_str()),
                 *token_id,
                 fee_value,
+                unconfirmed_block_index,
+                conn,
+            )?;
+        }
+
+        self.load_own_tx_out_indices(conn)
+    }
+
+    /// Loads the ledger indices of the account's unspent and pending txos, so
+    /// that they can be kept out of the rings. Spent txos no longer reveal
+    /// anything new when sampled as decoys.
+    fn load_own_tx_out_indices(
+        &mut self,
+        conn: &Conn,
+    ) -> Result<(), WalletTransactionBuilderError> {
+        if !self.exclude_own_txos_from_rings {
+            return Ok(());
+        }
+
+        let mut own_tx_out_indices = Vec::new();
+        for status in [TxoStatus::Unspent, TxoStatus::Pending] {
+            let txos = Txo::list_for_account(
+                &self.account_id_hex,
+                Some(status),
+                None,
+                None,
+                None,
+                None,
+                None,
                 conn,
             )?;
+
+            // Txos which were built but never landed are not in the ledger.
+            for txo in txos.iter().filter(|txo| txo.received_block_index.is_some()) {
+                if let Some(global_index) = txo.global_index {
+                    own_tx_out_indices.push(global_index as u64);
+                    continue;
+                }
+
+                // The index of txos synced before it was stored is filled in
+                // by a background job, until then it is looked up.
+                match self
+                    .ledger_db
+                    .get_tx_out_index_by_public_key(&txo.public_key()?)
+                {
+                    Ok(index) => own_tx_out_indices.push(index),
+                    Err(mc_ledger_db::Error::NotFound) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
         }
+        self.own_tx_out_indices = own_tx_out_indices;
 
         Ok(())
     }
@@ -167,6 +442,16 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
         value: u64,
         token_id: TokenId,
     ) -> Result<(), WalletTransactionBuilderError> {
+        if let Some(minimum) = self.dust_threshold(token_id) {
+            if value < minimum {
+                return Err(WalletTransactionBuilderError::OutputBelowDustThreshold {
+                    token_id: *token_id,
+                    value,
+                    minimum,
+                });
+            }
+        }
+
         // Verify that the maximum output value of this transaction remains under
         // u64::MAX for the given Token Id
         let cur_sum = self
@@ -183,6 +468,18 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
         if cur_sum > u64::MAX as u128 {
             return Err(WalletTransactionBuilderError::OutboundValueTooLarge);
         }
+
+        // Leave room for the change, so that a recipient which cannot fit is
+        // rejected here rather than when the transaction is built.
+        let num_outputs =
+            self.outlays.len() as u64 + 1 + self.min_change_outputs(&[token_id], self.fee);
+        if num_outputs > MAX_OUTPUTS {
+            return Err(WalletTransactionBuilderError::TooManyOutputs {
+                current: num_outputs,
+                max: MAX_OUTPUTS,
+            });
+        }
+
         self.outlays.push((recipient, value, token_id));
         Ok(())
     }
@@ -216,6 +513,11 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
         Ok(())
     }
 
+    /// The block after which the transaction is invalid, once set.
+    pub fn tombstone(&self) -> u64 {
+        self.tombstone
+    }
+
     pub fn get_fs_fog_resolver(
         &self,
         conn: &Conn,
@@ -224,23 +526,47 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
         let change_subaddress = account.change_subaddress(conn)?;
         let change_public_address = change_subaddress.public_address()?;
 
+        // Recipients whose pubkeys are cached do not need their fog reports
+        // fetched.
+        let mut fully_validated_fog_pubkeys: HashMap<String, FullServiceFullyValidatedFogPubkey> =
+            HashMap::default();
+        let mut uncached_outlays = Vec::new();
+        for (public_address, _, _) in self.outlays.iter() {
+            let b58_public_address = b58_encode_public_address(public_address)?;
+            if fully_validated_fog_pubkeys.contains_key(&b58_public_address) {
+                continue;
+            }
+            let cached_fog_pubkey = match (&self.fog_pubkey_cache, public_address.fog_report_url())
+            {
+                (Some(cache), Some(_)) => cache.get(public_address, self.tombstone),
+                _ => None,
+            };
+            match cached_fog_pubkey {
+                Some(fs_fog_pubkey) => {
+                    fully_validated_fog_pubkeys.insert(b58_public_address, fs_fog_pubkey);
+                }
+                None => uncached_outlays.push((b58_public_address, public_address)),
+            }
+        }
+
+        let has_uncached_fog_outlays = uncached_outlays
+            .iter()
+            .any(|(_, public_address)| public_address.fog_report_url().is_some());
+        if self.fog_pubkey_cache.is_some() && !has_uncached_fog_outlays {
+            return Ok(FullServiceFogResolver(fully_validated_fog_pubkeys));
+        }
+
         let fog_resolver = {
             let fog_uris = core::slice::from_ref(&change_public_address)
                 .iter()
-                .chain(self.outlays.iter().map(|(receiver, _, _)| receiver))
+                .chain(uncached_outlays.iter().map(|(_, receiver)| *receiver))
                 .filter_map(|x| extract_fog_uri(x).transpose())
                 .collect::<Result<Vec<_>, _>>()?;
             (self.fog_resolver_factory)(&fog_uris)
                 .map_err(WalletTransactionBuilderError::FogPubkeyResolver)?
         };
 
-        let mut fully_validated_fog_pubkeys: HashMap<String, FullServiceFullyValidatedFogPubkey> =
-            HashMap::default();
-        for (public_address, _, _) in self.outlays.iter() {
-            let b58_public_address = b58_encode_public_address(public_address)?;
-            if fully_validated_fog_pubkeys.contains_key(&b58_public_address) {
-                continue;
-            }
+        for (b58_public_address, public_address) in uncached_outlays {
             let fog_pubkey = match fog_resolver.get_fog_pubkey(public_address) {
                 Ok(fog_pubkey) => Some(fog_pubkey),
                 Err(_) => None,
@@ -248,6 +574,9 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
 
             if let Some(fog_pubkey) = fog_pubkey {
                 let fs_fog_pubkey = FullServiceFullyValidatedFogPubkey::from(fog_pubkey);
+                if let Some(cache) = &self.fog_pubkey_cache {
+                    cache.insert(public_address, fs_fog_pubkey.clone());
+                }
                 fully_validated_fog_pubkeys.insert(b58_public_address, fs_fog_pubkey);
             }
         }
@@ -255,19 +584,34 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
         Ok(FullServiceFogResolver(fully_validated_fog_pubkeys))
     }
 
+    /// The inputs, fee and change of the transaction, worked out without
+    /// fetching membership proofs or sampling rings.
+    pub fn preview(&self) -> Result<BuildPreview, WalletTransactionBuilderError> {
+        self.check_limits()?;
+
+        if self.outlays.is_empty() {
+            return Err(WalletTransactionBuilderError::NoRecipient);
+        }
+
+        let (fee, fee_token_id, change_per_token) = self.fee_and_change()?;
+
+        Ok(BuildPreview {
+            inputs: self.inputs.clone(),
+            fee,
+            fee_token_id,
+            change_per_token,
+            tombstone_block_index: self.tombstone,
+        })
+    }
+
     pub fn build(
         &self,
         memo: TransactionMemo,
     ) -> Result<UnsignedTx, WalletTransactionBuilderError> {
-        if self.tombstone == 0 {
-            return Err(WalletTransactionBuilderError::TombstoneNotSet);
-        }
-
-        if self.inputs.is_empty() {
-            return Err(WalletTransactionBuilderError::NoInputs);
-        }
+        let change_subaddress_index = self.check_limits()?;
 
         // Get membership proofs for our inputs
+        let proof_fetch_start = Instant::now();
         let indexes = self
             .inputs
             .iter()
@@ -277,6 +621,21 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             })
             .collect::<Result<Vec<u64>, mc_ledger_db::Error>>()?;
         let proofs = self.ledger_db.get_tx_out_proof_of_memberships(&indexes)?;
+        if self.verify_membership_proofs {
+            let tx_outs = self
+                .inputs
+                .iter()
+                .map(|utxo| mc_util_serial::decode(&utxo.txo))
+                .collect::<Result<Vec<TxOut>, _>>()?;
+            self.check_membership_proofs(
+                indexes
+                    .iter()
+                    .copied()
+                    .zip(tx_outs.iter())
+                    .zip(proofs.iter())
+                    .map(|((index, tx_out), proof)| (index, tx_out, proof)),
+            )?;
+        }
 
         let inputs_and_proofs: Vec<(Txo, TxOutMembershipProof)> = self
             .inputs
@@ -295,7 +654,12 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             })
             .collect::<Result<Vec<u64>, WalletTransactionBuilderError>>()?;
 
+        let proof_fetch_duration = proof_fetch_start.elapsed();
+
+        let ring_sampling_start = Instant::now();
         let rings = self.get_rings(inputs_and_proofs.len(), &excluded_tx_out_indices)?;
+        let ring_sampling_duration = ring_sampling_start.elapsed();
+        let builder_start = Instant::now();
 
         if rings.len() != inputs_and_proofs.len() {
             return Err(WalletTransactionBuilderError::RingSizeMismatch);
@@ -365,30 +729,111 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             ));
         }
 
-        let (fee, fee_token_id) = self.fee.unwrap_or((Mob::MINIMUM_FEE, Mob::ID));
+        let mut outlays_string = Vec::new();
+        for (receiver, amount, token_id) in self.outlays.iter() {
+            let b58_address = b58_encode_public_address(receiver)?;
+            outlays_string.push((b58_address, *amount, **token_id));
+        }
+
+        let (fee, fee_token_id, change_per_token) = self.fee_and_change()?;
+        let change_outputs_per_token = self.change_outputs_per_token(&change_per_token);
+
+        if let Some(logger) = &self.logger {
+            log::debug!(
+                logger,
+                "Built unsigned tx for account {}: proof fetch {:?}, ring sampling {:?}, builder {:?}",
+                self.account_id_hex,
+                proof_fetch_duration,
+                ring_sampling_duration,
+                builder_start.elapsed(),
+            );
+        }
+
+        Ok(UnsignedTx {
+            schema_version: UNSIGNED_TX_SCHEMA_VERSION,
+            inputs_and_real_indices_and_subaddress_indices,
+            outlays: outlays_string,
+            fee,
+            fee_token_id: *fee_token_id,
+            tombstone_block_index: self.tombstone,
+            block_version: self.block_version.unwrap_or(BlockVersion::MAX),
+            memo,
+            change_outputs_per_token,
+            change_subaddress_index,
+        })
+    }
+
+    /// Checks the builder is ready to build and that the transaction is within
+    /// the consensus limits, returning the subaddress index change goes to if
+    /// it is not the account's change subaddress.
+    fn check_limits(&self) -> Result<Option<u64>, WalletTransactionBuilderError> {
+        if self.tombstone == 0 {
+            return Err(WalletTransactionBuilderError::TombstoneNotSet);
+        }
+
+        if self.inputs.is_empty() {
+            return Err(WalletTransactionBuilderError::NoInputs);
+        }
+
+        let change_subaddress_index = match (&self.source_subaddress, self.change_to_source) {
+            (Some(subaddress), true) => Some(subaddress.subaddress_index as u64),
+            (None, true) => {
+                return Err(WalletTransactionBuilderError::ChangeToSourceWithoutSourceSubaddress)
+            }
+            (_, false) => None,
+        };
+
+        // Check the consensus limits before doing any work in the ledger.
+        if self.inputs.len() as u64 > MAX_INPUTS {
+            return Err(WalletTransactionBuilderError::TooManyInputs {
+                current: self.inputs.len() as u64,
+                max: MAX_INPUTS,
+            });
+        }
+        let num_outputs = self.outlays.len() as u64
+            + self.min_change_outputs(&[], Some(self.fee.unwrap_or((Mob::MINIMUM_FEE, Mob::ID))));
+        if num_outputs > MAX_OUTPUTS {
+            return Err(WalletTransactionBuilderError::TooManyOutputs {
+                current: num_outputs,
+                max: MAX_OUTPUTS,
+            });
+        }
+
+        Ok(change_subaddress_index)
+    }
+
+    /// The fee, with any change of the fee token below its dust threshold
+    /// added, the fee token, and the change of each token among the inputs.
+    fn fee_and_change(
+        &self,
+    ) -> Result<(u64, TokenId, BTreeMap<TokenId, u64>), WalletTransactionBuilderError> {
+        let (mut fee, fee_token_id) = self.fee.unwrap_or((Mob::MINIMUM_FEE, Mob::ID));
 
         let mut total_value_per_token = BTreeMap::new();
         total_value_per_token.insert(fee_token_id, fee);
 
-        let mut outlays_string = Vec::new();
-        for (receiver, amount, token_id) in self.outlays.clone().into_iter() {
-            let b58_address = b58_encode_public_address(&receiver)?;
-            outlays_string.push((b58_address, amount, *token_id));
+        for (_, amount, token_id) in self.outlays.iter() {
             total_value_per_token
-                .entry(token_id)
+                .entry(*token_id)
                 .and_modify(|value| *value += amount)
-                .or_insert(amount);
+                .or_insert(*amount);
         }
 
-        let input_value_per_token =
-            inputs_and_proofs
-                .iter()
-                .fold(BTreeMap::new(), |mut acc, (utxo, _proof)| {
-                    acc.entry(TokenId::from(utxo.token_id as u64))
-                        .and_modify(|value| *value += utxo.value as u64)
-                        .or_insert(utxo.value as u64);
-                    acc
-                });
+        let input_value_per_token = self.inputs.iter().fold(BTreeMap::new(), |mut acc, utxo| {
+            acc.entry(TokenId::from(utxo.token_id as u64))
+                .and_modify(|value| *value += utxo.value as u64)
+                .or_insert(utxo.value as u64);
+            acc
+        });
+
+        // Every token which is sent, and the fee token, must be funded by the
+        // inputs. Tokens which only appear among the inputs are returned in
+        // full as change.
+        if !input_value_per_token.contains_key(&fee_token_id) {
+            return Err(WalletTransactionBuilderError::MissingInputsForFeeTokenId(
+                fee_token_id.to_string(),
+            ));
+        }
 
         for (token_id, total_value) in total_value_per_token.iter() {
             let input_value = input_value_per_token.get(token_id).ok_or_else(|| {
@@ -396,24 +841,131 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             })?;
 
             if total_value > input_value {
-                return Err(WalletTransactionBuilderError::InsufficientInputFunds(format!(
-                    "Total value required to send transaction {:?}, but only {:?} in inputs for token_id {:?}",
-                    total_value,
-                    input_value,
-                    token_id.to_string(),
-                )));
+                return Err(WalletTransactionBuilderError::InsufficientInputFunds(
+                    InsufficientInputFundsAmounts {
+                        token_id: **token_id,
+                        requested: *total_value as u128,
+                        input_value: *input_value as u128,
+                        shortfall: (*total_value - *input_value) as u128,
+                    },
+                ));
             }
         }
 
-        Ok(UnsignedTx {
-            inputs_and_real_indices_and_subaddress_indices,
-            outlays: outlays_string,
-            fee,
-            fee_token_id: *fee_token_id,
-            tombstone_block_index: self.tombstone,
-            block_version: self.block_version.unwrap_or(BlockVersion::MAX),
-            memo,
-        })
+        let mut change_per_token: BTreeMap<TokenId, u64> = input_value_per_token
+            .iter()
+            .map(|(token_id, input_value)| {
+                let total_value = total_value_per_token.get(token_id).unwrap_or(&0);
+                (*token_id, input_value - total_value)
+            })
+            .collect();
+
+        // Change of the fee token below its dust threshold is paid as part of
+        // the fee rather than returned as an output which costs more to spend
+        // than it is worth. Change of other tokens has nowhere else to go, so
+        // it is returned whole.
+        if let Some(minimum) = self.dust_threshold(fee_token_id) {
+            if let Some(change) = change_per_token.get_mut(&fee_token_id) {
+                if *change > 0 && *change < minimum {
+                    fee += *change;
+                    *change = 0;
+                }
+            }
+        }
+        Ok((fee, fee_token_id, change_per_token))
+    }
+
+    /// The fewest change outputs the transaction can need: one for each token
+    /// among the inputs, the outlays, any extra outlay tokens given, and the
+    /// fee. When zero change is omitted, only tokens which the inputs are
+    /// known to leave change of are counted.
+    fn min_change_outputs(
+        &self,
+        extra_outlay_token_ids: &[TokenId],
+        fee: Option<(u64, TokenId)>,
+    ) -> u64 {
+        let mut spent_per_token: BTreeMap<TokenId, u128> = BTreeMap::new();
+        for token_id in extra_outlay_token_ids {
+            spent_per_token.entry(*token_id).or_default();
+        }
+        for (_, value, token_id) in self.outlays.iter() {
+            *spent_per_token.entry(*token_id).or_default() += *value as u128;
+        }
+        if let Some((fee_value, fee_token_id)) = fee {
+            *spent_per_token.entry(fee_token_id).or_default() += fee_value as u128;
+        }
+
+        let mut input_per_token: BTreeMap<TokenId, u128> = BTreeMap::new();
+        for txo in self.inputs.iter() {
+            *input_per_token
+                .entry(TokenId::from(txo.token_id as u64))
+                .or_default() += txo.value as u128;
+        }
+
+        let token_ids: HashSet<TokenId> = spent_per_token
+            .keys()
+            .chain(input_per_token.keys())
+            .copied()
+            .collect();
+        token_ids
+            .into_iter()
+            .filter(|token_id| {
+                if !self.omit_zero_change {
+                    return true;
+                }
+                let input_value = match input_per_token.get(token_id) {
+                    Some(input_value) => *input_value,
+                    None => return false,
+                };
+                let change =
+                    input_value.saturating_sub(*spent_per_token.get(token_id).unwrap_or(&0));
+                // Fee token change below its dust threshold is paid as fee.
+                let is_fee_token = fee.map_or(false, |(_, fee_token_id)| fee_token_id == *token_id);
+                let paid_as_fee = is_fee_token
+                    && self
+                        .dust_threshold(*token_id)
+                        .map_or(false, |minimum| change < minimum as u128);
+                change > 0 && !paid_as_fee
+            })
+            .count() as u64
+    }
+
+    /// The number of change outputs for each token, keyed by token id. Every
+    /// token gets at least one, unless its change is zero and zero change is
+    /// omitted, and extra outputs are handed out in token id order while the
+    /// transaction stays within MAX_OUTPUTS.
+    fn change_outputs_per_token(
+        &self,
+        change_per_token: &BTreeMap<TokenId, u64>,
+    ) -> BTreeMap<u64, u64> {
+        let omitted = |change_value: u64| self.omit_zero_change && change_value == 0;
+        let num_change_tokens = change_per_token
+            .values()
+            .filter(|change_value| !omitted(**change_value))
+            .count();
+        let mut free_outputs =
+            (MAX_OUTPUTS as usize).saturating_sub(self.outlays.len() + num_change_tokens);
+
+        change_per_token
+            .iter()
+            .map(|(token_id, change_value)| {
+                if omitted(*change_value) {
+                    return (**token_id, 0);
+                }
+                let dust_floor = self
+                    .change_dust_floor
+                    .max(self.dust_threshold(*token_id).unwrap_or(0));
+                let max_pieces = match dust_floor {
+                    0 => u64::MAX,
+                    dust_floor => (change_value / dust_floor).max(1),
+                };
+                let extra_outputs = (self.split_change_into as u64 - 1)
+                    .min(max_pieces - 1)
+                    .min(free_outputs as u64);
+                free_outputs -= extra_outputs as usize;
+                (**token_id, 1 + extra_outputs)
+            })
+            .collect()
     }
 
     /// Get rings.
@@ -436,13 +988,28 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
             return Err(WalletTransactionBuilderError::InsufficientTxOuts);
         }
 
+        let mut excluded: HashSet<u64> = excluded_tx_out_indices.iter().copied().collect();
+        if self.exclude_own_txos_from_rings {
+            let mut excluded_with_own = excluded.clone();
+            excluded_with_own.extend(self.own_tx_out_indices.iter().copied());
+            if num_requested as u64 <= num_txos.saturating_sub(excluded_with_own.len() as u64) {
+                excluded = excluded_with_own;
+            } else if let Some(logger) = &self.logger {
+                log::warn!(
+                    logger,
+                    "Too few tx outs in the ledger to keep the txos of account {} out of the rings, sampling them as decoys",
+                    self.account_id_hex,
+                );
+            }
+        }
+
         // Randomly sample `num_requested` TxOuts, without replacement and convert into
         // a Vec<u64>
         let mut rng = rand::thread_rng();
         let mut sampled_indices: HashSet<u64> = HashSet::default();
         while sampled_indices.len() < num_requested {
             let index = rng.gen_range(0..num_txos);
-            if excluded_tx_out_indices.contains(&index) {
+            if excluded.contains(&index) {
                 continue;
             }
             sampled_indices.insert(index);
@@ -460,6 +1027,7 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
 
         // Convert that into a Vec<Vec<TxOut, TxOutMembershipProof>>
         let mut rings_with_proofs = Vec::new();
+        let mut ring_indices = Vec::new();
 
         for _ in 0..num_rings {
             let mut ring = Vec::new();
@@ -468,12 +1036,36 @@ impl<FPR: FogPubkeyResolver + 'static> WalletTransactionBuilder<FPR> {
                 let tx_out = self.ledger_db.get_tx_out_by_index(index)?;
 
                 ring.push((tx_out, proof));
+                ring_indices.push(index);
             }
             rings_with_proofs.push(ring);
         }
 
+        if self.verify_membership_proofs {
+            self.check_membership_proofs(
+                ring_indices
+                    .into_iter()
+                    .zip(rings_with_proofs.iter().flatten())
+                    .map(|(index, (tx_out, proof))| (index, tx_out, proof)),
+            )?;
+        }
+
         Ok(rings_with_proofs)
     }
+
+    /// Check proofs of membership against the current ledger root, failing
+    /// with the index of the first tx out whose proof does not verify.
+    fn check_membership_proofs<'a>(
+        &self,
+        tx_outs_and_proofs: impl IntoIterator<Item = (u64, &'a TxOut, &'a TxOutMembershipProof)>,
+    ) -> Result<(), WalletTransactionBuilderError> {
+        match invalid_membership_proofs(&self.ledger_db, tx_outs_and_proofs)?.first() {
+            Some(index) => {
+                Err(WalletTransactionBuilderError::InvalidMembershipProof { index: *index })
+            }
+            None => Ok(()),
+        }
+    }
 }
 
 // Helper which extracts FogUri from PublicAddress or returns None, or returns
@@ -490,16 +1082,29 @@ fn extract_fog_uri(addr: &PublicAddress) -> Result<Option<FogUri>, WalletTransac
 mod tests {
     use super::*;
     use crate::{
-        db::WalletDbError,
+        db::{
+            models::TransactionLog, transaction_log::TransactionLogModel, WalletDb, WalletDbError,
+        },
+        fog_resolver::FogPubkeyCacheStats,
+        json_rpc::v2::models::tx_proposal::TxProposal as TxProposalJSON,
         service::sync::SyncThread,
         test_utils::{
-            builder_for_random_recipient, get_test_ledger, random_account_with_seed_values,
+            add_block_with_tx_outs, builder_for_random_recipient, create_test_txo_for_recipient,
+            get_test_ledger, manually_sync_account, random_account_with_seed_values,
             WalletDbTestContext, MOB,
         },
     };
     use mc_account_keys::AccountKey;
     use mc_common::logger::{test_with_logger, Logger};
-    use rand::{rngs::StdRng, SeedableRng};
+    use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
+    use mc_fog_report_validation::{FullyValidatedFogPubkey, MockFogPubkeyResolver};
+    use mc_transaction_core::{ring_signature::KeyImage, validation::validate_signature, Amount};
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use std::{
+        convert::TryFrom,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
 
     #[test_with_logger]
     fn test_build_with_utxos(logger: Logger) {
@@ -683,9 +1288,46 @@ mod tests {
         assert_eq!(proposal.tx.prefix.outputs.len(), 2); // self and change
     }
 
-    // Test max_spendable correctly filters out txos above max_spendable
+    const EUSD_TOKEN_ID: u64 = 1;
+
+    /// Adds a block paying the account a txo of a second token, and returns
+    /// the txo once synced.
+    fn add_eusd_txo(
+        account_key: &AccountKey,
+        value: u64,
+        wallet_db: &WalletDb,
+        ledger_db: &mut LedgerDB,
+        rng: &mut StdRng,
+        logger: &Logger,
+    ) -> Txo {
+        let (tx_out, _key_image) = create_test_txo_for_recipient(
+            account_key,
+            0,
+            Amount::new(value, TokenId::from(EUSD_TOKEN_ID)),
+            rng,
+        );
+        add_block_with_tx_outs(ledger_db, &[tx_out], &[KeyImage::from(rng.next_u64())], rng);
+        manually_sync_account(ledger_db, wallet_db, &AccountID::from(account_key), logger);
+
+        Txo::list_for_account(
+            &AccountID::from(account_key).to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(EUSD_TOKEN_ID),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap()
+        .pop()
+        .unwrap()
+    }
+
+    // Tokens which only appear among the set txos should be returned in full
+    // as change.
     #[test_with_logger]
-    fn test_max_spendable(logger: Logger) {
+    fn test_setting_txos_with_input_only_token(logger: Logger) {
         let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
 
         let db_test_context = WalletDbTestContext::default();
@@ -693,28 +1335,142 @@ mod tests {
         let known_recipients: Vec<PublicAddress> = Vec::new();
         let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
 
-        // Start sync thread
-        let _sync_thread = SyncThread::start(ledger_db.clone(), wallet_db.clone(), logger.clone());
-
         let account_key = random_account_with_seed_values(
             &wallet_db,
             &mut ledger_db,
-            &vec![70 * MOB, 80 * MOB, 90 * MOB],
+            &vec![70 * MOB],
             &mut rng,
             &logger,
         );
-
-        let conn = wallet_db.get_conn().unwrap();
-        let (recipient, mut builder) =
-            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
-
-        // Setting value to exactly the input will fail because you need funds for fee
-        builder
-            .add_recipient(recipient.clone(), 80 * MOB, Mob::ID)
-            .unwrap();
-
-        // Test that selecting Txos with max_spendable < all our txo values fails
-        match builder.select_txos(&conn, Some(10)) {
+        let eusd_txo = add_eusd_txo(
+            &account_key,
+            50 * MOB,
+            &wallet_db,
+            &mut ledger_db,
+            &mut rng,
+            &logger,
+        );
+        let mob_txo = Txo::list_for_account(
+            &AccountID::from(&account_key).to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(*Mob::ID),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+
+        let conn = wallet_db.get_conn().unwrap();
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder
+            .add_recipient(recipient.clone(), 10 * MOB, Mob::ID)
+            .unwrap();
+        builder
+            .set_txos(&conn, &vec![mob_txo.id.clone(), eusd_txo.id.clone()])
+            .unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+        validate_signature(BlockVersion::MAX, &proposal.tx, &mut rng).unwrap();
+
+        assert_eq!(proposal.tx.prefix.inputs.len(), 2);
+        assert_eq!(proposal.payload_txos.len(), 1);
+        assert_eq!(proposal.payload_txos[0].amount.value, 10 * MOB);
+
+        let change_per_token: BTreeMap<TokenId, u64> = proposal
+            .change_txos
+            .iter()
+            .map(|change| (change.amount.token_id, change.amount.value))
+            .collect();
+        assert_eq!(change_per_token.len(), 2);
+        assert_eq!(change_per_token[&Mob::ID], 60 * MOB - Mob::MINIMUM_FEE);
+        assert_eq!(change_per_token[&TokenId::from(EUSD_TOKEN_ID)], 50 * MOB);
+    }
+
+    // Building without any inputs of the fee token should fail, even when
+    // other inputs are set.
+    #[test_with_logger]
+    fn test_setting_txos_without_fee_token(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let eusd_txo = add_eusd_txo(
+            &account_key,
+            50 * MOB,
+            &wallet_db,
+            &mut ledger_db,
+            &mut rng,
+            &logger,
+        );
+
+        let conn = wallet_db.get_conn().unwrap();
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder
+            .add_recipient(recipient.clone(), 10 * MOB, Mob::ID)
+            .unwrap();
+        builder.set_txos(&conn, &vec![eusd_txo.id.clone()]).unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        match builder.build(TransactionMemo::RTH) {
+            Ok(_) => panic!("Should not be able to build without inputs for the fee"),
+            Err(WalletTransactionBuilderError::MissingInputsForFeeTokenId(token_id)) => {
+                assert_eq!(token_id, Mob::ID.to_string());
+            }
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    // Test max_spendable correctly filters out txos above max_spendable
+    #[test_with_logger]
+    fn test_max_spendable(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        // Start sync thread
+        let _sync_thread = SyncThread::start(ledger_db.clone(), wallet_db.clone(), logger.clone());
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB, 80 * MOB, 90 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        let conn = wallet_db.get_conn().unwrap();
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+
+        // Setting value to exactly the input will fail because you need funds for fee
+        builder
+            .add_recipient(recipient.clone(), 80 * MOB, Mob::ID)
+            .unwrap();
+
+        // Test that selecting Txos with max_spendable < all our txo values fails
+        match builder.select_txos(&conn, Some(10)) {
             Ok(_) => panic!("Should not be able to construct tx when max_spendable < all txos"),
             Err(WalletTransactionBuilderError::WalletDb(WalletDbError::NoSpendableTxos)) => {}
             Err(e) => panic!("Unexpected error {:?}", e),
@@ -958,6 +1714,449 @@ mod tests {
                                                          // self
     }
 
+    #[test_with_logger]
+    fn test_omit_zero_change(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        // Start sync thread
+        let _sync_thread = SyncThread::start(ledger_db.clone(), wallet_db.clone(), logger.clone());
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB, 70 * MOB, 70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id_hex = AccountID::from(&account_key).to_string();
+
+        let conn = wallet_db.get_conn().unwrap();
+
+        for omit_zero_change in [false, true] {
+            let (recipient, mut builder) =
+                builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+            builder.set_omit_zero_change(omit_zero_change);
+
+            // Consume the whole TXO, leaving no change.
+            let value = 70 * MOB - Mob::MINIMUM_FEE;
+            builder
+                .add_recipient(recipient.clone(), value, Mob::ID)
+                .unwrap();
+            builder.select_txos(&conn, Some(70 * MOB)).unwrap();
+            builder.set_tombstone(0).unwrap();
+
+            let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+            let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+            let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+            validate_signature(BlockVersion::MAX, &proposal.tx, &mut rng).unwrap();
+
+            assert_eq!(proposal.tx.prefix.inputs.len(), 1);
+            assert_eq!(proposal.payload_txos.len(), 1);
+            assert_eq!(proposal.payload_txos[0].amount.value, value);
+
+            let proposal_json = TxProposalJSON::try_from(&proposal).unwrap();
+            assert!(!proposal_json.payload_txos[0].is_zero_change);
+
+            if omit_zero_change {
+                assert_eq!(proposal.tx.prefix.outputs.len(), 1);
+                assert!(proposal.change_txos.is_empty());
+                assert!(proposal_json.change_txos.is_empty());
+            } else {
+                assert_eq!(proposal.tx.prefix.outputs.len(), 2);
+                assert_eq!(proposal.change_txos.len(), 1);
+                assert_eq!(proposal.change_txos[0].amount.value, 0);
+                assert!(proposal_json.change_txos[0].is_zero_change);
+            }
+
+            // The transaction log records whichever change outputs were made.
+            let num_change_txos = proposal.change_txos.len();
+            let transaction_log = TransactionLog::log_built(
+                proposal,
+                "".to_string(),
+                &TransactionMemo::RTH,
+                &account_id_hex,
+                &conn,
+            )
+            .unwrap();
+            let associated_txos = transaction_log.get_associated_txos(&conn).unwrap();
+            assert_eq!(associated_txos.outputs.len(), 1);
+            assert_eq!(associated_txos.change.len(), num_change_txos);
+        }
+
+        // Change which is not zero is kept when zero change is omitted.
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.set_omit_zero_change(true);
+        builder
+            .add_recipient(recipient.clone(), 10 * MOB, Mob::ID)
+            .unwrap();
+        builder.select_txos(&conn, None).unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+        assert_eq!(unsigned_tx.schema_version, UNSIGNED_TX_SCHEMA_VERSION);
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        let proposal = unsigned_tx
+            .clone()
+            .sign(&account_key, fog_resolver.clone())
+            .unwrap();
+        validate_signature(BlockVersion::MAX, &proposal.tx, &mut rng).unwrap();
+        assert_eq!(proposal.change_txos.len(), 1);
+        assert!(proposal.change_txos[0].amount.value > 0);
+
+        // A signer does not sign transactions from a newer wallet, whose
+        // fields it may not understand.
+        let mut newer_unsigned_tx = unsigned_tx;
+        newer_unsigned_tx.schema_version = UNSIGNED_TX_SCHEMA_VERSION + 1;
+        assert!(matches!(
+            newer_unsigned_tx.sign(&account_key, fog_resolver),
+            Err(WalletTransactionBuilderError::UnsupportedUnsignedTxSchemaVersion { .. })
+        ));
+    }
+
+    /// The public keys of the decoys in the rings of a transaction, leaving
+    /// out the real inputs.
+    fn decoy_public_keys(unsigned_tx: &UnsignedTx) -> Vec<CompressedRistrettoPublic> {
+        unsigned_tx
+            .inputs_and_real_indices_and_subaddress_indices
+            .iter()
+            .flat_map(|(tx_in, real_index, _)| {
+                tx_in
+                    .ring
+                    .iter()
+                    .enumerate()
+                    .filter(move |(i, _)| *i as u64 != *real_index)
+                    .map(|(_, tx_out)| tx_out.public_key)
+            })
+            .collect()
+    }
+
+    fn own_public_keys(account_key: &AccountKey, conn: &Conn) -> Vec<CompressedRistrettoPublic> {
+        Txo::list_for_account(
+            &AccountID::from(account_key).to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            conn,
+        )
+        .unwrap()
+        .iter()
+        .map(|txo| txo.public_key().unwrap())
+        .collect()
+    }
+
+    #[test_with_logger]
+    fn test_recipient_below_dust_threshold(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        let mut dust_thresholds = BTreeMap::new();
+        dust_thresholds.insert(Mob::ID, Mob::MINIMUM_FEE);
+        builder.set_dust_thresholds(dust_thresholds);
+
+        match builder.add_recipient(recipient.clone(), Mob::MINIMUM_FEE - 1, Mob::ID) {
+            Err(WalletTransactionBuilderError::OutputBelowDustThreshold {
+                token_id,
+                value,
+                minimum,
+            }) => {
+                assert_eq!(token_id, *Mob::ID);
+                assert_eq!(value, Mob::MINIMUM_FEE - 1);
+                assert_eq!(minimum, Mob::MINIMUM_FEE);
+            }
+            Ok(_) => panic!("Should not add a recipient below the dust threshold"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+
+        // A value at the threshold is not dust.
+        builder
+            .add_recipient(recipient.clone(), Mob::MINIMUM_FEE, Mob::ID)
+            .unwrap();
+
+        // Tokens without a threshold are not checked.
+        builder
+            .add_recipient(recipient.clone(), 1, TokenId::from(EUSD_TOKEN_ID))
+            .unwrap();
+
+        // Dust is allowed when asked for explicitly.
+        builder.set_allow_dust(true);
+        builder.add_recipient(recipient, 1, Mob::ID).unwrap();
+    }
+
+    // Change of the fee token below its dust threshold is paid as part of the
+    // fee, leaving a zero value change output.
+    #[test_with_logger]
+    fn test_change_below_dust_threshold_is_added_to_fee(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        let conn = wallet_db.get_conn().unwrap();
+        let dust = Mob::MINIMUM_FEE / 2;
+        let value = 70 * MOB - Mob::MINIMUM_FEE - dust;
+
+        for allow_dust in [false, true] {
+            let (recipient, mut builder) =
+                builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+            let mut dust_thresholds = BTreeMap::new();
+            dust_thresholds.insert(Mob::ID, Mob::MINIMUM_FEE);
+            builder.set_dust_thresholds(dust_thresholds);
+            builder.set_allow_dust(allow_dust);
+
+            builder
+                .add_recipient(recipient.clone(), value, Mob::ID)
+                .unwrap();
+            builder.select_txos(&conn, None).unwrap();
+            builder.set_tombstone(0).unwrap();
+
+            let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+            let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+            let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+            validate_signature(BlockVersion::MAX, &proposal.tx, &mut rng).unwrap();
+
+            assert_eq!(proposal.change_txos.len(), 1);
+            if allow_dust {
+                assert_eq!(proposal.tx.prefix.fee, Mob::MINIMUM_FEE);
+                assert_eq!(proposal.change_txos[0].amount.value, dust);
+            } else {
+                assert_eq!(proposal.tx.prefix.fee, Mob::MINIMUM_FEE + dust);
+                assert_eq!(proposal.change_txos[0].amount.value, 0);
+            }
+        }
+    }
+
+    #[test_with_logger]
+    fn test_rings_exclude_own_txos(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![10 * MOB; 10],
+            &mut rng,
+            &logger,
+        );
+        let conn = wallet_db.get_conn().unwrap();
+        let own_public_keys = own_public_keys(&account_key, &conn);
+
+        for _ in 0..5 {
+            let (recipient, mut builder) =
+                builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+            builder.add_recipient(recipient, 25 * MOB, Mob::ID).unwrap();
+            builder.select_txos(&conn, None).unwrap();
+            builder.set_tombstone(0).unwrap();
+            let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+
+            assert_eq!(
+                unsigned_tx
+                    .inputs_and_real_indices_and_subaddress_indices
+                    .len(),
+                3
+            );
+            for public_key in decoy_public_keys(&unsigned_tx) {
+                assert!(!own_public_keys.contains(&public_key));
+            }
+        }
+    }
+
+    #[test_with_logger]
+    fn test_own_tx_out_indices_are_stored_global_indices(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![10 * MOB; 3],
+            &mut rng,
+            &logger,
+        );
+        let conn = wallet_db.get_conn().unwrap();
+
+        let mut global_indices: Vec<u64> = Txo::list_for_account(
+            &AccountID::from(&account_key).to_string(),
+            Some(TxoStatus::Unspent),
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )
+        .unwrap()
+        .iter()
+        .map(|txo| txo.global_index.unwrap() as u64)
+        .collect();
+        global_indices.sort_unstable();
+        assert_eq!(global_indices.len(), 3);
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.add_recipient(recipient, 5 * MOB, Mob::ID).unwrap();
+        builder.select_txos(&conn, None).unwrap();
+
+        let mut own_tx_out_indices = builder.own_tx_out_indices.clone();
+        own_tx_out_indices.sort_unstable();
+        assert_eq!(own_tx_out_indices, global_indices);
+    }
+
+    #[test_with_logger]
+    fn test_rings_include_own_txos_when_ledger_is_too_small(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(1, &known_recipients, 5, &mut rng);
+
+        // The account owns 10 of the 15 txos in the ledger, too many to fill a
+        // ring without them.
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![10 * MOB; 10],
+            &mut rng,
+            &logger,
+        );
+        let conn = wallet_db.get_conn().unwrap();
+        let own_public_keys = own_public_keys(&account_key, &conn);
+        assert_eq!(ledger_db.num_txos().unwrap(), 15);
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.set_logger(logger.clone());
+        builder.add_recipient(recipient, 5 * MOB, Mob::ID).unwrap();
+        builder.select_txos(&conn, None).unwrap();
+        builder.set_tombstone(0).unwrap();
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+
+        // Only 5 txos belong to others, so at least 5 of the 10 decoys are the
+        // account's own.
+        let own_decoys = decoy_public_keys(&unsigned_tx)
+            .iter()
+            .filter(|public_key| own_public_keys.contains(public_key))
+            .count();
+        assert!(own_decoys >= RING_SIZE - 1 - 5);
+
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+    }
+
+    #[test_with_logger]
+    fn test_split_change(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        // Start sync thread
+        let _sync_thread = SyncThread::start(ledger_db.clone(), wallet_db.clone(), logger.clone());
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        let conn = wallet_db.get_conn().unwrap();
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.set_split_change_into(4);
+
+        let value = 10 * MOB;
+        builder
+            .add_recipient(recipient.clone(), value, Mob::ID)
+            .unwrap();
+        builder.select_txos(&conn, None).unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+
+        let expected_change = 70 * MOB - value - Mob::MINIMUM_FEE;
+        assert_eq!(proposal.payload_txos.len(), 1);
+        assert_eq!(proposal.change_txos.len(), 4);
+        assert_eq!(
+            proposal
+                .change_txos
+                .iter()
+                .map(|txo| txo.amount.value)
+                .sum::<u64>(),
+            expected_change
+        );
+        for change_txo in proposal.change_txos.iter() {
+            assert_eq!(change_txo.amount.token_id, Mob::ID);
+            assert!(change_txo.amount.value >= expected_change / 4);
+        }
+        assert_eq!(proposal.tx.prefix.outputs.len(), 5);
+
+        // Change too small to split above the dust floor stays in one output.
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.set_split_change_into(4);
+        builder.set_change_dust_floor(MOB);
+
+        let value = 70 * MOB - Mob::MINIMUM_FEE - MOB / 2;
+        builder
+            .add_recipient(recipient.clone(), value, Mob::ID)
+            .unwrap();
+        builder.select_txos(&conn, None).unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+        assert_eq!(proposal.change_txos.len(), 1);
+        assert_eq!(proposal.change_txos[0].amount.value, MOB / 2);
+    }
+
     // We should be able to add multiple TxOuts to the same recipient, not to
     // multiple
     #[test_with_logger]
@@ -1098,4 +2297,299 @@ mod tests {
             .add_recipient(second_recipient.clone(), 40 * MOB, Mob::ID)
             .unwrap();
     }
+
+    // A recipient which would leave no room for the change is rejected when
+    // it is added.
+    #[test_with_logger]
+    fn test_recipient_limit_leaves_room_for_change(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        for _ in 0..MAX_OUTPUTS - 1 {
+            builder
+                .add_recipient(recipient.clone(), MOB, Mob::ID)
+                .unwrap();
+        }
+
+        match builder.add_recipient(recipient, MOB, Mob::ID) {
+            Err(WalletTransactionBuilderError::TooManyOutputs { current, max }) => {
+                assert_eq!(current, MAX_OUTPUTS + 1);
+                assert_eq!(max, MAX_OUTPUTS);
+            }
+            other => panic!("Expected TooManyOutputs, got {:?}", other),
+        }
+    }
+
+    // With zero change omitted, a transaction which spends its inputs exactly
+    // can use every output for recipients.
+    #[test_with_logger]
+    fn test_build_at_output_limit_without_change(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![70 * MOB, 80 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id_hex = AccountID::from(&account_key).to_string();
+
+        let conn = wallet_db.get_conn().unwrap();
+        let txos =
+            Txo::list_for_account(&account_id_hex, None, None, None, None, None, None, &conn)
+                .unwrap();
+        let txo_id_with_value = |value: u64| {
+            txos.iter()
+                .find(|txo| txo.value as u64 == value)
+                .map(|txo| txo.id.clone())
+                .unwrap()
+        };
+
+        let value = 4 * MOB;
+        let fee = 70 * MOB - MAX_OUTPUTS * value;
+        let builder_with_inputs = |input_value: u64, rng: &mut StdRng| {
+            let (recipient, mut builder) =
+                builder_for_random_recipient(&account_key, &ledger_db, rng);
+            builder.set_omit_zero_change(true);
+            for _ in 0..MAX_OUTPUTS {
+                builder
+                    .add_recipient(recipient.clone(), value, Mob::ID)
+                    .unwrap();
+            }
+            builder.set_fee(fee, Mob::ID).unwrap();
+            builder
+                .set_txos(&conn, &[txo_id_with_value(input_value)])
+                .unwrap();
+            builder.set_tombstone(0).unwrap();
+            builder
+        };
+
+        let builder = builder_with_inputs(70 * MOB, &mut rng);
+        let unsigned_tx = builder.build(TransactionMemo::RTH).unwrap();
+        let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+        let proposal = unsigned_tx.sign(&account_key, fog_resolver).unwrap();
+        assert_eq!(proposal.tx.prefix.outputs.len() as u64, MAX_OUTPUTS);
+        assert!(proposal.change_txos.is_empty());
+
+        // Inputs which leave change need one more output than is allowed.
+        let builder = builder_with_inputs(80 * MOB, &mut rng);
+        match builder.build(TransactionMemo::RTH) {
+            Err(WalletTransactionBuilderError::TooManyOutputs { current, max }) => {
+                assert_eq!(current, MAX_OUTPUTS + 1);
+                assert_eq!(max, MAX_OUTPUTS);
+            }
+            other => panic!("Expected TooManyOutputs, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_build_rejects_too_many_inputs(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![MOB; MAX_INPUTS as usize + 1],
+            &mut rng,
+            &logger,
+        );
+        let account_id_hex = AccountID::from(&account_key).to_string();
+
+        let conn = wallet_db.get_conn().unwrap();
+        let txo_ids: Vec<String> =
+            Txo::list_for_account(&account_id_hex, None, None, None, None, None, None, &conn)
+                .unwrap()
+                .into_iter()
+                .map(|txo| txo.id)
+                .collect();
+        assert_eq!(txo_ids.len() as u64, MAX_INPUTS + 1);
+
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+        builder.add_recipient(recipient, 10 * MOB, Mob::ID).unwrap();
+        builder.set_txos(&conn, &txo_ids).unwrap();
+        builder.set_tombstone(0).unwrap();
+
+        match builder.build(TransactionMemo::RTH) {
+            Err(WalletTransactionBuilderError::TooManyInputs { current, max }) => {
+                assert_eq!(current, MAX_INPUTS + 1);
+                assert_eq!(max, MAX_INPUTS);
+            }
+            other => panic!("Expected TooManyInputs, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_corrupt_membership_proof_is_rejected(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![11 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let (recipient, mut builder) =
+            builder_for_random_recipient(&account_key, &ledger_db, &mut rng);
+
+        let indices = vec![2, 5];
+        let tx_outs = indices
+            .iter()
+            .map(|index| ledger_db.get_tx_out_by_index(*index).unwrap())
+            .collect::<Vec<TxOut>>();
+        let mut proofs = ledger_db.get_tx_out_proof_of_memberships(&indices).unwrap();
+        let tx_outs_and_proofs = |proofs: &[TxOutMembershipProof]| {
+            indices
+                .iter()
+                .copied()
+                .zip(tx_outs.iter())
+                .zip(proofs.iter())
+                .map(|((index, tx_out), proof)| (index, tx_out, proof))
+                .collect::<Vec<_>>()
+        };
+        builder
+            .check_membership_proofs(tx_outs_and_proofs(&proofs))
+            .unwrap();
+
+        proofs[1].elements[0].hash.0[0] ^= 1;
+        match builder.check_membership_proofs(tx_outs_and_proofs(&proofs)) {
+            Err(WalletTransactionBuilderError::InvalidMembershipProof { index }) => {
+                assert_eq!(index, 5)
+            }
+            result => panic!("Expected InvalidMembershipProof, got {:?}", result),
+        }
+
+        // Proofs fetched from an intact ledger verify during the build.
+        let conn = wallet_db.get_conn().unwrap();
+        builder.add_recipient(recipient, 10 * MOB, Mob::ID).unwrap();
+        builder.select_txos(&conn, None).unwrap();
+        builder.set_tombstone(0).unwrap();
+        builder.build(TransactionMemo::RTH).unwrap();
+    }
+
+    fn counting_fog_resolver_factory(
+        pubkey_expiry: u64,
+        num_calls: Arc<AtomicUsize>,
+        rng: &mut StdRng,
+    ) -> Arc<dyn Fn(&[FogUri]) -> Result<MockFogPubkeyResolver, String> + Send + Sync> {
+        let pubkey = RistrettoPublic::from_random(rng);
+        Arc::new(move |_| -> Result<MockFogPubkeyResolver, String> {
+            num_calls.fetch_add(1, Ordering::SeqCst);
+            let mut fog_pubkey_resolver = MockFogPubkeyResolver::new();
+            fog_pubkey_resolver
+                .expect_get_fog_pubkey()
+                .returning(move |_| {
+                    Ok(FullyValidatedFogPubkey {
+                        pubkey,
+                        pubkey_expiry,
+                    })
+                });
+            Ok(fog_pubkey_resolver)
+        })
+    }
+
+    fn fog_recipient(rng: &mut StdRng) -> PublicAddress {
+        PublicAddress::new_with_fog(
+            &RistrettoPublic::from_random(rng),
+            &RistrettoPublic::from_random(rng),
+            "fog://fog.example.com",
+            "".to_string(),
+            vec![1u8; 64],
+        )
+    }
+
+    #[test_with_logger]
+    fn test_fog_pubkey_cache(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger.clone());
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let account_key = random_account_with_seed_values(
+            &wallet_db,
+            &mut ledger_db,
+            &vec![11 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let conn = wallet_db.get_conn().unwrap();
+
+        let num_calls = Arc::new(AtomicUsize::new(0));
+        let fog_resolver_factory = counting_fog_resolver_factory(100, num_calls.clone(), &mut rng);
+        let cache = Arc::new(FogPubkeyCache::new(Duration::from_secs(600)));
+
+        // Two recipients of the same fog service.
+        let recipients = vec![fog_recipient(&mut rng), fog_recipient(&mut rng)];
+        let resolve = |recipient: &PublicAddress, tombstone: u64, cache: &Arc<FogPubkeyCache>| {
+            let mut builder = WalletTransactionBuilder::new(
+                AccountID::from(&account_key).to_string(),
+                ledger_db.clone(),
+                fog_resolver_factory.clone(),
+            );
+            builder.set_fog_pubkey_cache(cache.clone());
+            builder
+                .add_recipient(recipient.clone(), MOB, Mob::ID)
+                .unwrap();
+            builder.set_tombstone(tombstone).unwrap();
+            let fog_resolver = builder.get_fs_fog_resolver(&conn).unwrap();
+            assert!(fog_resolver.get_fog_pubkey(recipient).is_ok());
+        };
+
+        // Repeated builds within the TTL only fetch the report once.
+        resolve(&recipients[0], 50, &cache);
+        resolve(&recipients[1], 50, &cache);
+        resolve(&recipients[0], 50, &cache);
+        assert_eq!(num_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            cache.stats(),
+            FogPubkeyCacheStats {
+                num_entries: 1,
+                hits: 2,
+                misses: 1,
+            }
+        );
+
+        // A pubkey expiring before the tombstone block is not used.
+        resolve(&recipients[0], 150, &cache);
+        assert_eq!(num_calls.load(Ordering::SeqCst), 2);
+
+        // Flushing the cache fetches the report again.
+        assert_eq!(cache.flush(), 1);
+        resolve(&recipients[0], 50, &cache);
+        assert_eq!(num_calls.load(Ordering::SeqCst), 3);
+
+        // Entries are not reused after the TTL.
+        let short_cache = Arc::new(FogPubkeyCache::new(Duration::from_millis(10)));
+        resolve(&recipients[0], 50, &short_cache);
+        std::thread::sleep(Duration::from_millis(20));
+        resolve(&recipients[0], 50, &short_cache);
+        assert_eq!(num_calls.load(Ordering::SeqCst), 5);
+    }
 }
@@ -4,16 +4,36 @@
 
 use crate::{
     db::{
-        models::TransactionLog,
-        transaction_log::{AssociatedTxos, TransactionID, TransactionLogModel, ValueMap},
-        WalletDbError,
+        account::{AccountID, AccountModel},
+        models::{Account, MonitoredTxo, TransactionLog, TransactionOutputTxo, Txo},
+        monitored_txo::MonitoredTxoModel,
+        transaction,
+        transaction_log::{
+            AssociatedTxos, InputRing, ReconstructedTransaction, TransactionID,
+            TransactionLogFilter, TransactionLogModel, ValueMap,
+        },
+        txo::TxoModel,
+        WalletDb, WalletDbError,
     },
     error::WalletServiceError,
+    service::{
+        job::{Job, JobContext, JobServiceError},
+        ledger::LedgerService,
+    },
+    util::constants::LEGACY_CHANGE_SUBADDRESS_INDEX,
     WalletService,
 };
+use chrono::NaiveDateTime;
 use displaydoc::Display;
+use mc_account_keys::CHANGE_SUBADDRESS_INDEX;
+use mc_common::logger::log;
 use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate, RistrettoPublic};
 use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::{Ledger, LedgerDB};
+use mc_transaction_core::{get_tx_out_shared_secret, tx::TxOut, Amount, TokenId};
+use mc_transaction_std::{DestinationMemo, MemoType};
+use std::{collections::BTreeMap, convert::TryFrom};
 
 /// Errors for the Transaction Log Service.
 #[derive(Display, Debug)]
@@ -24,6 +44,18 @@ pub enum TransactionLogServiceError {
 
     /// Diesel Error: {0}
     Diesel(diesel::result::Error),
+
+    /// Error decoding from hex: {0}
+    HexDecode(hex::FromHexError),
+
+    /// Error decoding the public key: {0}
+    ProstDecode(mc_util_serial::DecodeError),
+
+    /// Error interacting with the ledger: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Error with the job service: {0}
+    JobService(JobServiceError),
 }
 
 impl From<WalletDbError> for TransactionLogServiceError {
@@ -38,17 +70,61 @@ impl From<diesel::result::Error> for TransactionLogServiceError {
     }
 }
 
+impl From<hex::FromHexError> for TransactionLogServiceError {
+    fn from(src: hex::FromHexError) -> Self {
+        Self::HexDecode(src)
+    }
+}
+
+impl From<mc_util_serial::DecodeError> for TransactionLogServiceError {
+    fn from(src: mc_util_serial::DecodeError) -> Self {
+        Self::ProstDecode(src)
+    }
+}
+
+impl From<mc_ledger_db::Error> for TransactionLogServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<JobServiceError> for TransactionLogServiceError {
+    fn from(src: JobServiceError) -> Self {
+        Self::JobService(src)
+    }
+}
+
+/// The fees an account has paid in a token, within a calendar month (UTC) of
+/// the blocks its transactions landed in when grouped by month.
+#[derive(Debug, PartialEq)]
+pub struct FeeTotal {
+    pub token_id: TokenId,
+    /// The month as YYYY-MM. Blocks without a signed timestamp have no month.
+    pub month: Option<String>,
+    pub value: u128,
+}
+
 /// Trait defining the ways in which the wallet can interact with and manage
 /// transaction logs.
 pub trait TransactionLogService {
-    /// List all transactions associated with the given Account ID.
+    /// List the transactions associated with the given Account ID which match
+    /// the filter.
     fn list_transaction_logs(
         &self,
         account_id: Option<String>,
         offset: Option<u64>,
         limit: Option<u64>,
-        min_block_index: Option<u64>,
-        max_block_index: Option<u64>,
+        filter: &TransactionLogFilter,
+    ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletServiceError>;
+
+    /// Get a page of the transaction logs for an account, ordered by id and
+    /// starting after the given transaction log id, for streaming through
+    /// large wallets.
+    fn list_transaction_logs_after(
+        &self,
+        account_id: &str,
+        after_transaction_log_id: Option<&str>,
+        limit: u64,
     ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletServiceError>;
 
     /// Get a specific transaction log.
@@ -57,6 +133,61 @@ pub trait TransactionLogService {
         transaction_id_hex: &str,
     ) -> Result<(TransactionLog, AssociatedTxos, ValueMap), TransactionLogServiceError>;
 
+    /// Get the ids of the transaction logs which were automatically rebuilt
+    /// from one another along with the given one, from the original to the
+    /// latest.
+    fn get_transaction_log_rebuild_chain(
+        &self,
+        transaction_id_hex: &str,
+    ) -> Result<Vec<String>, TransactionLogServiceError>;
+
+    /// Get a submitted transaction log along with its payload outputs, each
+    /// with the block it landed in once it has been seen in the ledger.
+    fn get_payment_status(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<(TransactionLog, Vec<MonitoredTxo>), TransactionLogServiceError>;
+
+    /// Get the input Txos of a transaction log along with the rings they were
+    /// signed with, as recorded when the transaction was logged. The rings
+    /// reveal which ring member was real, so they are private to the wallet.
+    #[allow(clippy::type_complexity)]
+    fn get_transaction_ring_details(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<(TransactionLog, Vec<(Txo, Option<InputRing>)>), TransactionLogServiceError>;
+
+    /// Get the transaction log which produced the output with the given
+    /// public key, along with that output, for reconciling payments. The
+    /// public key is hex, either as the 32 raw bytes or in the encoded form
+    /// returned for txos by the API.
+    #[allow(clippy::type_complexity)]
+    fn get_transaction_log_by_tx_out_public_key(
+        &self,
+        public_key_hex: &str,
+    ) -> Result<
+        (
+            TransactionLog,
+            AssociatedTxos,
+            ValueMap,
+            Txo,
+            TransactionOutputTxo,
+        ),
+        TransactionLogServiceError,
+    >;
+
+    /// Get the total fees an account has paid for its transactions which
+    /// landed in the ledger, per token, optionally only for one token and
+    /// within a range of block indices, and split by month.
+    fn get_fee_totals(
+        &self,
+        account_id: &AccountID,
+        token_id: Option<u64>,
+        min_block_index: Option<u64>,
+        max_block_index: Option<u64>,
+        group_by_month: bool,
+    ) -> Result<Vec<FeeTotal>, WalletServiceError>;
+
     /// Get all transaction logs for a given block.
     fn get_all_transaction_logs_for_block(
         &self,
@@ -67,6 +198,14 @@ pub trait TransactionLogService {
     fn get_all_transaction_logs_ordered_by_block(
         &self,
     ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletServiceError>;
+
+    /// Start a job recreating the transaction logs of an account's outgoing
+    /// transactions from the ledger, such as for an account restored from its
+    /// keys on a new wallet.
+    fn reconstruct_transaction_history(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Job, TransactionLogServiceError>;
 }
 
 impl<T, FPR> TransactionLogService for WalletService<T, FPR>
@@ -79,16 +218,25 @@ where
         account_id: Option<String>,
         offset: Option<u64>,
         limit: Option<u64>,
-        min_block_index: Option<u64>,
-        max_block_index: Option<u64>,
+        filter: &TransactionLogFilter,
     ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletServiceError> {
         let conn = &self.wallet_db.get_conn()?;
         Ok(TransactionLog::list_all(
+            account_id, offset, limit, filter, conn,
+        )?)
+    }
+
+    fn list_transaction_logs_after(
+        &self,
+        account_id: &str,
+        after_transaction_log_id: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<(TransactionLog, AssociatedTxos, ValueMap)>, WalletServiceError> {
+        let conn = &self.wallet_db.get_conn()?;
+        Ok(TransactionLog::list_for_account_after(
             account_id,
-            offset,
+            after_transaction_log_id,
             limit,
-            min_block_index,
-            max_block_index,
             conn,
         )?)
     }
@@ -106,6 +254,117 @@ where
         Ok((transaction_log, associated, value_map))
     }
 
+    fn get_transaction_log_rebuild_chain(
+        &self,
+        transaction_id_hex: &str,
+    ) -> Result<Vec<String>, TransactionLogServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let transaction_log =
+            TransactionLog::get(&TransactionID(transaction_id_hex.to_string()), &conn)?;
+        Ok(transaction_log.get_rebuild_chain(&conn)?)
+    }
+
+    fn get_payment_status(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<(TransactionLog, Vec<MonitoredTxo>), TransactionLogServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let transaction_log =
+            TransactionLog::get(&TransactionID(transaction_log_id.to_string()), &conn)?;
+        let monitored_txos = MonitoredTxo::list_for_transaction_log(&transaction_log.id, &conn)?;
+
+        Ok((transaction_log, monitored_txos))
+    }
+
+    fn get_transaction_ring_details(
+        &self,
+        transaction_log_id: &str,
+    ) -> Result<(TransactionLog, Vec<(Txo, Option<InputRing>)>), TransactionLogServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let transaction_log =
+            TransactionLog::get(&TransactionID(transaction_log_id.to_string()), &conn)?;
+        let input_rings = transaction_log.get_input_rings(&conn)?;
+
+        Ok((transaction_log, input_rings))
+    }
+
+    fn get_transaction_log_by_tx_out_public_key(
+        &self,
+        public_key_hex: &str,
+    ) -> Result<
+        (
+            TransactionLog,
+            AssociatedTxos,
+            ValueMap,
+            Txo,
+            TransactionOutputTxo,
+        ),
+        TransactionLogServiceError,
+    > {
+        let public_key_bytes = hex::decode(public_key_hex)?;
+        let public_key = match CompressedRistrettoPublic::try_from(&public_key_bytes[..]) {
+            Ok(public_key) => public_key,
+            Err(_) => mc_util_serial::decode(&public_key_bytes)?,
+        };
+
+        let conn = self.wallet_db.get_conn()?;
+        let (transaction_log, txo, transaction_output_txo) =
+            TransactionLog::get_by_output_public_key(&public_key, &conn)?;
+        let associated = transaction_log.get_associated_txos(&conn)?;
+        let value_map = transaction_log.value_map(&conn)?;
+
+        Ok((
+            transaction_log,
+            associated,
+            value_map,
+            txo,
+            transaction_output_txo,
+        ))
+    }
+
+    fn get_fee_totals(
+        &self,
+        account_id: &AccountID,
+        token_id: Option<u64>,
+        min_block_index: Option<u64>,
+        max_block_index: Option<u64>,
+        group_by_month: bool,
+    ) -> Result<Vec<FeeTotal>, WalletServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        let totals = TransactionLog::get_fee_totals(
+            &account.id,
+            token_id,
+            min_block_index,
+            max_block_index,
+            group_by_month,
+            &conn,
+        )?;
+
+        // Merge the blocks in each month. Without grouping there is already a
+        // single total per token.
+        let mut by_month: BTreeMap<(Option<String>, TokenId), u128> = BTreeMap::new();
+        for total in totals {
+            let month = match total.block_index {
+                Some(block_index) => self
+                    .get_block_timestamp(block_index)?
+                    .and_then(|timestamp| NaiveDateTime::from_timestamp_opt(timestamp as i64, 0))
+                    .map(|date_time| date_time.format("%Y-%m").to_string()),
+                None => None,
+            };
+            *by_month.entry((month, total.token_id)).or_default() += total.value;
+        }
+
+        Ok(by_month
+            .into_iter()
+            .map(|((month, token_id), value)| FeeTotal {
+                token_id,
+                month,
+                value,
+            })
+            .collect())
+    }
+
     fn get_all_transaction_logs_for_block(
         &self,
         block_index: u64,
@@ -138,16 +397,166 @@ where
         }
         Ok(res)
     }
+
+    fn reconstruct_transaction_history(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<Job, TransactionLogServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Account::get(account_id, &conn)?;
+
+        let ledger_db = self.ledger_db.clone();
+        let wallet_db = self.wallet_db.clone();
+        let account_id_hex = account_id.to_string();
+        let job = self
+            .jobs
+            .submit("reconstruct_transaction_history", move |context| {
+                let num_transaction_logs = reconstruct_transaction_history(
+                    &ledger_db,
+                    &wallet_db,
+                    &account_id_hex,
+                    context,
+                )
+                .map_err(|e| e.to_string())?;
+                Ok(Some(serde_json::json!({
+                    "account_id": account_id_hex,
+                    "num_transaction_logs_reconstructed": num_transaction_logs.to_string(),
+                })))
+            })?;
+        log::info!(
+            self.request_logger(),
+            "Reconstructing transaction history of account {} in job {}",
+            account_id,
+            job.id
+        );
+
+        Ok(job)
+    }
+}
+
+/// Recreate the transaction logs of an account's outgoing transactions from
+/// the ledger. The account's spent txos are grouped by the block they were
+/// spent in and their token, and each group becomes a transaction log along
+/// with the change the account received in that block. The destination memos
+/// on the change give the fee, the value sent and the recipient. Without them,
+/// only the value spent net of change is known, and the fee is left at zero.
+///
+/// Txos which already belong to a transaction log are skipped, so that logs
+/// recorded when the transactions were built are never changed, and running
+/// this again only adds logs for spends found since. Returns the number of
+/// transaction logs created.
+pub fn reconstruct_transaction_history(
+    ledger_db: &LedgerDB,
+    wallet_db: &WalletDb,
+    account_id_hex: &str,
+    job: &JobContext,
+) -> Result<u64, TransactionLogServiceError> {
+    let conn = wallet_db.get_conn()?;
+    let account = Account::get(&AccountID(account_id_hex.to_string()), &conn)?;
+    let view_private_key = account.view_private_key()?;
+
+    let logged_txo_ids = TransactionLog::list_associated_txo_ids(account_id_hex, &conn)?;
+    let txos: Vec<Txo> =
+        Txo::list_for_account(account_id_hex, None, None, None, None, None, None, &conn)?
+            .into_iter()
+            .filter(|txo| !logged_txo_ids.contains(&txo.id))
+            .collect();
+
+    let mut spends: BTreeMap<(u64, u64), Vec<Txo>> = BTreeMap::new();
+    for txo in &txos {
+        if let Some(spent_block_index) = txo.spent_block_index {
+            spends
+                .entry((spent_block_index as u64, txo.token_id as u64))
+                .or_default()
+                .push(txo.clone());
+        }
+    }
+
+    let num_spends = spends.len() as u64;
+    let mut num_transaction_logs = 0;
+    for ((block_index, token_id), inputs) in spends {
+        if job.is_cancelled() {
+            break;
+        }
+
+        let change: Vec<Txo> = txos
+            .iter()
+            .filter(|txo| {
+                txo.received_block_index == Some(block_index as i64)
+                    && txo.token_id as u64 == token_id
+                    && matches!(
+                        txo.subaddress_index.map(|index| index as u64),
+                        Some(CHANGE_SUBADDRESS_INDEX) | Some(LEGACY_CHANGE_SUBADDRESS_INDEX)
+                    )
+            })
+            .cloned()
+            .collect();
+
+        let memos: Vec<DestinationMemo> = change
+            .iter()
+            .filter_map(|txo| destination_memo(txo, &view_private_key))
+            .collect();
+        let (fee, value) = if memos.is_empty() {
+            let input_value: u64 = inputs.iter().map(|txo| txo.value as u64).sum();
+            let change_value: u64 = change.iter().map(|txo| txo.value as u64).sum();
+            (0, input_value.saturating_sub(change_value))
+        } else {
+            let fee: u64 = memos.iter().map(|memo| memo.get_fee()).sum();
+            let total_outlay: u64 = memos.iter().map(|memo| memo.get_total_outlay()).sum();
+            (fee, total_outlay.saturating_sub(fee))
+        };
+        let recipient_address_hash = match memos.as_slice() {
+            [memo] if memo.get_num_recipients() == 1 => {
+                Some(memo.get_address_hash().as_ref().to_vec())
+            }
+            _ => None,
+        };
+
+        let reconstructed = ReconstructedTransaction {
+            block_index,
+            block_version: ledger_db.get_block(block_index)?.version as u64,
+            fee: Amount::new(fee, TokenId::from(token_id)),
+            value,
+            recipient_address_hash,
+            inputs,
+            change,
+        };
+        transaction(&conn, || {
+            TransactionLog::log_reconstructed(account_id_hex, &reconstructed, &conn)
+        })?;
+
+        num_transaction_logs += 1;
+        job.set_progress(num_transaction_logs, Some(num_spends));
+    }
+
+    Ok(num_transaction_logs)
+}
+
+/// Get the destination memo a txo carries, if it is change with a memo.
+fn destination_memo(txo: &Txo, view_private_key: &RistrettoPrivate) -> Option<DestinationMemo> {
+    let tx_out: TxOut = mc_util_serial::decode(&txo.txo).ok()?;
+    let tx_public_key = RistrettoPublic::try_from(&tx_out.public_key).ok()?;
+    let shared_secret = get_tx_out_shared_secret(view_private_key, &tx_public_key);
+    match MemoType::try_from(&tx_out.decrypt_memo(&shared_secret)) {
+        Ok(MemoType::Destination(memo)) => Some(memo),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::{
-        db::account::AccountID,
+        db::{
+            account::AccountID,
+            transaction_log::{TransactionLogModel, TxStatus},
+        },
         json_rpc::v2::models::amount::Amount,
         service::{
             account::AccountService,
             address::AddressService,
+            job::{JobService, JobStatus},
+            sync::sync_monitored_txos,
             transaction::{TransactionMemo, TransactionService},
             transaction_log::TransactionLogService,
         },
@@ -155,11 +564,15 @@ mod tests {
             add_block_from_transaction_log, add_block_to_ledger_db, get_test_ledger,
             manually_sync_account, setup_wallet_service, MOB,
         },
+        util::{b58::b58_encode_public_address, constants::MNEMONIC_KEY_DERIVATION_VERSION},
     };
+    use bip39::{Language, Mnemonic};
     use mc_account_keys::{AccountKey, PublicAddress};
     use mc_common::logger::{test_with_logger, Logger};
     use mc_crypto_rand::rand_core::RngCore;
-    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, tx::Tx, Token};
+    use mc_transaction_std::ShortAddressHash;
+    use mc_util_from_random::FromRandom;
     use rand::{rngs::StdRng, SeedableRng};
 
     #[test_with_logger]
@@ -186,7 +599,12 @@ mod tests {
         let alice_public_address = alice_account_key.default_subaddress();
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(0, tx_logs.len());
@@ -222,7 +640,10 @@ mod tests {
                     None,
                     None,
                     None,
+                    false,
+                    None,
                     TransactionMemo::RTH,
+                    None,
                 )
                 .unwrap();
 
@@ -235,7 +656,12 @@ mod tests {
         }
 
         let tx_logs = service
-            .list_transaction_logs(Some(alice_account_id.to_string()), None, None, None, None)
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
             .unwrap();
 
         assert_eq!(5, tx_logs.len());
@@ -245,8 +671,10 @@ mod tests {
                 Some(alice_account_id.to_string()),
                 None,
                 None,
-                Some(20),
-                None,
+                &TransactionLogFilter {
+                    min_block_index: Some(20),
+                    ..Default::default()
+                },
             )
             .unwrap();
 
@@ -257,8 +685,10 @@ mod tests {
                 Some(alice_account_id.to_string()),
                 None,
                 None,
-                None,
-                Some(18),
+                &TransactionLogFilter {
+                    max_block_index: Some(18),
+                    ..Default::default()
+                },
             )
             .unwrap();
 
@@ -269,11 +699,473 @@ mod tests {
                 Some(alice_account_id.to_string()),
                 None,
                 None,
-                Some(18),
-                Some(20),
+                &TransactionLogFilter {
+                    min_block_index: Some(18),
+                    max_block_index: Some(20),
+                    ..Default::default()
+                },
             )
             .unwrap();
 
         assert_eq!(3, tx_logs.len());
     }
+
+    #[test_with_logger]
+    fn test_payment_status_records_landed_outputs(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Pay two counterparties whose keys the wallet does not have.
+        let bob_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let carol_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let (transaction_log, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice_account_id.to_string(),
+                &[
+                    (bob_b58.clone(), Amount::new(10 * MOB, Mob::ID)),
+                    (carol_b58.clone(), Amount::new(20 * MOB, Mob::ID)),
+                ],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+
+        let (_, outputs) = service.get_payment_status(&transaction_log.id).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert!(outputs
+            .iter()
+            .all(|output| output.landed_block_index.is_none()));
+
+        // Nothing has landed yet.
+        assert_eq!(
+            sync_monitored_txos(&ledger_db, &service.wallet_db).unwrap(),
+            0
+        );
+
+        let landed_block_index = {
+            let conn = service.wallet_db.get_conn().unwrap();
+            add_block_from_transaction_log(&mut ledger_db, &conn, &transaction_log, &mut rng)
+        };
+        assert_eq!(
+            sync_monitored_txos(&ledger_db, &service.wallet_db).unwrap(),
+            2
+        );
+
+        // The payment is confirmed without syncing the account.
+        let (transaction_log, outputs) = service.get_payment_status(&transaction_log.id).unwrap();
+        assert_eq!(transaction_log.status(), TxStatus::Succeeded);
+        assert_eq!(
+            transaction_log.finalized_block_index,
+            Some(landed_block_index as i64)
+        );
+        let mut recipients: Vec<(String, i64, Option<i64>)> = outputs
+            .iter()
+            .map(|output| {
+                (
+                    output.recipient_public_address_b58.clone(),
+                    output.value,
+                    output.landed_block_index,
+                )
+            })
+            .collect();
+        recipients.sort();
+        let mut expected = vec![
+            (bob_b58, (10 * MOB) as i64, Some(landed_block_index as i64)),
+            (
+                carol_b58,
+                (20 * MOB) as i64,
+                Some(landed_block_index as i64),
+            ),
+        ];
+        expected.sort();
+        assert_eq!(recipients, expected);
+
+        // Landed outputs are not checked again.
+        assert_eq!(
+            sync_monitored_txos(&ledger_db, &service.wallet_db).unwrap(),
+            0
+        );
+    }
+
+    #[test_with_logger]
+    fn test_transaction_ring_details(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        for _ in 0..2 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_account_key.default_subaddress()],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Spend both txos, so that the transaction has two rings.
+        let bob_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let (transaction_log, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice_account_id.to_string(),
+                &[(bob_b58, Amount::new(150 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+
+        let (_, input_rings) = service
+            .get_transaction_ring_details(&transaction_log.id)
+            .unwrap();
+        assert_eq!(input_rings.len(), 2);
+
+        let tx: Tx = mc_util_serial::decode(&transaction_log.tx).unwrap();
+        for (txo, ring) in input_rings.iter() {
+            let ring = ring.as_ref().unwrap();
+            let public_key = txo.public_key().unwrap();
+            let tx_in = tx
+                .prefix
+                .inputs
+                .iter()
+                .find(|tx_in| {
+                    tx_in
+                        .ring
+                        .iter()
+                        .any(|member| member.public_key == public_key)
+                })
+                .unwrap();
+            assert_eq!(ring.member_indices.len(), tx_in.ring.len());
+
+            // Each recorded index is that of the ring member in the ledger.
+            for (member, index) in tx_in.ring.iter().zip(ring.member_indices.iter()) {
+                assert_eq!(
+                    ledger_db
+                        .get_tx_out_index_by_public_key(&member.public_key)
+                        .unwrap(),
+                    *index
+                );
+            }
+            assert_eq!(tx_in.ring[ring.real_position].public_key, public_key);
+            assert_eq!(
+                ledger_db
+                    .get_tx_out_index_by_public_key(&tx_in.ring[ring.real_position].public_key)
+                    .unwrap(),
+                ring.real_index()
+            );
+        }
+
+        // The ring survives being stored.
+        let ring = input_rings[0].1.clone().unwrap();
+        assert_eq!(InputRing::from_bytes(&ring.to_bytes()).unwrap(), ring);
+    }
+
+    #[test_with_logger]
+    fn test_get_transaction_log_by_tx_out_public_key(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let bob_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let (transaction_log, _, _, tx_proposal) = service
+            .build_sign_and_submit_transaction(
+                &alice_account_id.to_string(),
+                &[(bob_b58, Amount::new(10 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+
+        let outputs = tx_proposal
+            .payload_txos
+            .iter()
+            .map(|output| (output, false))
+            .chain(tx_proposal.change_txos.iter().map(|output| (output, true)));
+        for (output, is_change) in outputs {
+            let raw_public_key = RistrettoPublic::try_from(&output.tx_out.public_key)
+                .unwrap()
+                .to_bytes();
+            let (found_log, _, _, txo, transaction_output_txo) = service
+                .get_transaction_log_by_tx_out_public_key(&hex::encode(raw_public_key))
+                .unwrap();
+            assert_eq!(found_log.id, transaction_log.id);
+            assert_eq!(transaction_output_txo.is_change, is_change);
+            assert_eq!(
+                transaction_output_txo.recipient_public_address_b58,
+                b58_encode_public_address(&output.recipient_public_address).unwrap()
+            );
+            assert_eq!(txo.amount(), output.amount);
+
+            // The encoded form returned for txos by the API is accepted too.
+            let encoded_public_key = mc_util_serial::encode(&output.tx_out.public_key);
+            let (found_log, ..) = service
+                .get_transaction_log_by_tx_out_public_key(&hex::encode(encoded_public_key))
+                .unwrap();
+            assert_eq!(found_log.id, transaction_log.id);
+        }
+
+        let unknown_public_key = RistrettoPublic::from_random(&mut rng).to_bytes();
+        match service.get_transaction_log_by_tx_out_public_key(&hex::encode(unknown_public_key)) {
+            Err(TransactionLogServiceError::Database(
+                WalletDbError::TransactionOutputNotFound(_),
+            )) => {}
+            Ok(_) => panic!("Should not find a transaction log for an unknown output"),
+            Err(e) => panic!("Unexpected error {:?}", e),
+        }
+    }
+
+    #[test_with_logger]
+    fn test_reconstruct_transaction_history(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        for _ in 0..3 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_account_key.default_subaddress()],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Pay Bob a few times, and Bob and Carol together once.
+        let bob_address = AccountKey::from_random(&mut rng).default_subaddress();
+        let bob_b58 = b58_encode_public_address(&bob_address).unwrap();
+        let carol_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let payments = vec![
+            vec![(bob_b58.clone(), Amount::new(10 * MOB, Mob::ID))],
+            vec![(bob_b58.clone(), Amount::new(25 * MOB, Mob::ID))],
+            vec![
+                (bob_b58.clone(), Amount::new(5 * MOB, Mob::ID)),
+                (carol_b58, Amount::new(7 * MOB, Mob::ID)),
+            ],
+        ];
+        for addresses_and_amounts in payments {
+            let (transaction_log, _, _, _) = service
+                .build_sign_and_submit_transaction(
+                    &alice_account_id.to_string(),
+                    &addresses_and_amounts,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    false,
+                    None,
+                    TransactionMemo::RTH,
+                    None,
+                )
+                .unwrap();
+            {
+                let conn = service.wallet_db.get_conn().unwrap();
+                add_block_from_transaction_log(&mut ledger_db, &conn, &transaction_log, &mut rng);
+            }
+            manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+        }
+        let originals = service
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(originals.len(), 3);
+
+        // Restore Alice's account on a new wallet, which has none of her
+        // transaction logs.
+        let restored_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let mnemonic =
+            Mnemonic::from_entropy(alice.entropy.as_ref().unwrap(), Language::English).unwrap();
+        restored_service
+            .import_account(
+                mnemonic.phrase().to_string(),
+                MNEMONIC_KEY_DERIVATION_VERSION,
+                None,
+                None,
+                None,
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                false,
+            )
+            .unwrap();
+        manually_sync_account(
+            &ledger_db,
+            &restored_service.wallet_db,
+            &alice_account_id,
+            &logger,
+        );
+
+        let run_reconstruction = |service: &WalletService<_, _>| {
+            let job = service
+                .reconstruct_transaction_history(&alice_account_id)
+                .unwrap();
+            assert_eq!(job.kind, "reconstruct_transaction_history");
+            let mut job = service.get_job_status(&job.id).unwrap();
+            for _ in 0..500 {
+                if job.status.is_finished() {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                job = service.get_job_status(&job.id).unwrap();
+            }
+            assert_eq!(job.status, JobStatus::Completed);
+            job.result.unwrap()["num_transaction_logs_reconstructed"].clone()
+        };
+
+        assert_eq!(run_reconstruction(&restored_service), "3");
+
+        let reconstructed = restored_service
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(reconstructed.len(), 3);
+        for (original, _, original_values) in &originals {
+            let (log, associated_txos, values) = reconstructed
+                .iter()
+                .find(|(log, _, _)| log.finalized_block_index == original.finalized_block_index)
+                .unwrap();
+            assert!(log.reconstructed);
+            assert_eq!(log.fee_amount(), original.fee_amount());
+            assert_eq!(values.0, original_values.0);
+            assert_eq!(associated_txos.inputs.len(), 1);
+            assert_eq!(associated_txos.change.len(), 1);
+            assert!(associated_txos.outputs.is_empty());
+
+            let expected_hash = if original_values.0[&Mob::ID] == 12 * MOB {
+                None
+            } else {
+                Some(ShortAddressHash::from(&bob_address).as_ref().to_vec())
+            };
+            assert_eq!(log.recipient_address_hash, expected_hash);
+        }
+
+        // Running again finds nothing new, and the transaction logs recorded
+        // when building are left alone.
+        assert_eq!(run_reconstruction(&restored_service), "0");
+        assert_eq!(run_reconstruction(&service), "0");
+        let logs = service
+            .list_transaction_logs(
+                Some(alice_account_id.to_string()),
+                None,
+                None,
+                &TransactionLogFilter::default(),
+            )
+            .unwrap();
+        assert_eq!(logs.len(), 3);
+        assert!(logs.iter().all(|(log, _, _)| !log.reconstructed));
+    }
 }
@@ -0,0 +1,1044 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Reporting of submitted transactions for compliance.
+//!
+//! When a transaction paying at least the configured threshold of a token is
+//! submitted, a record of it is queued in the wallet database alongside the
+//! transaction log. A background thread delivers queued records to the
+//! configured sink, retrying failed deliveries, so that a slow or unavailable
+//! sink never holds up a submission.
+//!
+//! Deliveries to a URL are signed with HMAC-SHA256 when a secret is
+//! configured, so that the receiver can check that they came from this
+//! wallet. Each report carries an event id which is kept across redeliveries,
+//! so that the receiver can drop duplicates.
+
+use crate::{
+    db::{
+        idempotency_key::unix_timestamp_now, models::TransactionReport,
+        transaction_report::TransactionReportModel, Conn, WalletDb, WalletDbError,
+    },
+    service::models::tx_proposal::TxProposal,
+    util::{b58::b58_encode_public_address, constants::TRANSACTION_REPORT_RETRY_INTERVAL_SECONDS},
+    WalletService,
+};
+use displaydoc::Display;
+use hmac::{Hmac, Mac};
+use mc_common::logger::{log, Logger};
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_rand::rand_core::RngCore;
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_transaction_core::TokenId;
+use reqwest::{
+    blocking::Client,
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE},
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Header carrying the signature of a delivery, as hex.
+pub const SIGNATURE_HEADER: &str = "X-Full-Service-Signature";
+
+/// Header carrying the id of a delivery. Ids increase with every delivery,
+/// including redeliveries of the same report.
+pub const DELIVERY_ID_HEADER: &str = "X-Full-Service-Delivery-Id";
+
+/// Header carrying the time of a delivery, in seconds since the Unix epoch.
+pub const TIMESTAMP_HEADER: &str = "X-Full-Service-Timestamp";
+
+/// Event type of transaction reports.
+pub const TRANSACTION_REPORT_EVENT: &str = "transaction_report";
+
+/// Event type of pings sent to check that the receiver verifies deliveries.
+pub const PING_EVENT: &str = "ping";
+
+/// Errors for the Transaction Report Service.
+#[derive(Display, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum TransactionReportServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+
+    /// Diesel Error: {0}
+    Diesel(diesel::result::Error),
+
+    /// Error serializing the report: {0}
+    Json(serde_json::Error),
+
+    /// Error encoding the recipient address: {0}
+    B58(crate::util::b58::B58Error),
+
+    /// Transaction reporting is not configured
+    ReportingDisabled,
+
+    /// Delivery to the report sink failed: {0}
+    DeliveryFailed(String),
+}
+
+impl From<WalletDbError> for TransactionReportServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+impl From<diesel::result::Error> for TransactionReportServiceError {
+    fn from(src: diesel::result::Error) -> Self {
+        Self::Diesel(src)
+    }
+}
+
+impl From<serde_json::Error> for TransactionReportServiceError {
+    fn from(src: serde_json::Error) -> Self {
+        Self::Json(src)
+    }
+}
+
+impl From<crate::util::b58::B58Error> for TransactionReportServiceError {
+    fn from(src: crate::util::b58::B58Error) -> Self {
+        Self::B58(src)
+    }
+}
+
+/// A payment made by a reported transaction.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TransactionReportOutput {
+    /// The b58 address of the recipient.
+    pub recipient_address: String,
+
+    /// The value paid, in the smallest unit of the token.
+    pub value: String,
+
+    pub token_id: String,
+}
+
+/// The record delivered to the sink for a reported transaction. Change
+/// outputs are not included.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TransactionReportRecord {
+    /// Unique id of the report, which stays the same when it is redelivered.
+    /// Reports queued before event ids were recorded use their transaction
+    /// log id.
+    #[serde(default)]
+    pub event_id: String,
+
+    #[serde(default = "transaction_report_event")]
+    pub event_type: String,
+
+    pub transaction_log_id: String,
+    pub account_id: String,
+    pub submitted_block_index: String,
+    pub tombstone_block_index: String,
+    pub fee_value: String,
+    pub fee_token_id: String,
+    pub outputs: Vec<TransactionReportOutput>,
+}
+
+fn transaction_report_event() -> String {
+    TRANSACTION_REPORT_EVENT.to_string()
+}
+
+/// An event sent to the sink on request, so that the receiver can check its
+/// verification of deliveries before any transaction is reported.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ReportSinkPing {
+    pub event_id: String,
+    pub event_type: String,
+
+    /// When the ping was sent, in seconds since the Unix epoch.
+    pub created_at: String,
+}
+
+impl ReportSinkPing {
+    pub fn new() -> Self {
+        Self {
+            event_id: new_event_id(),
+            event_type: PING_EVENT.to_string(),
+            created_at: unix_timestamp_now().to_string(),
+        }
+    }
+}
+
+impl Default for ReportSinkPing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A random, unique event id.
+fn new_event_id() -> String {
+    let mut event_id = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut event_id);
+    hex::encode(event_id)
+}
+
+/// The signature of a delivery: the HMAC-SHA256, keyed by the secret, of the
+/// delivery id, the timestamp and the body joined by '.', as hex.
+pub fn sign_delivery(secret: &[u8], delivery_id: u64, timestamp: i64, body: &str) -> String {
+    hex::encode(
+        delivery_mac(secret, delivery_id, timestamp, body)
+            .finalize()
+            .into_bytes(),
+    )
+}
+
+/// Check the signature of a delivery, in constant time.
+pub fn verify_delivery(
+    secret: &[u8],
+    delivery_id: u64,
+    timestamp: i64,
+    body: &str,
+    signature_hex: &str,
+) -> bool {
+    match hex::decode(signature_hex) {
+        Ok(signature) => delivery_mac(secret, delivery_id, timestamp, body)
+            .verify_slice(&signature)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn delivery_mac(secret: &[u8], delivery_id: u64, timestamp: i64, body: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC can take a key of any size");
+    mac.update(format!("{}.{}.{}", delivery_id, timestamp, body).as_bytes());
+    mac
+}
+
+/// A destination for transaction reports.
+pub trait ReportSink: Send + Sync {
+    /// Deliver a record, returning a description of the failure if it could
+    /// not be delivered.
+    fn deliver(&self, record: &TransactionReportRecord) -> Result<(), String>;
+
+    /// Deliver a ping, the same way as a record.
+    fn ping(&self, ping: &ReportSinkPing) -> Result<(), String>;
+}
+
+/// Delivers reports as JSON POST requests to a URL. Any response other than
+/// a success status is treated as a failed delivery.
+pub struct HttpReportSink {
+    url: String,
+    client: Client,
+
+    /// The secret deliveries are signed with, if any.
+    secret: Option<String>,
+
+    /// The id of the last delivery. Ids are taken from the clock in
+    /// milliseconds, so that they keep increasing across restarts.
+    last_delivery_id: AtomicU64,
+}
+
+impl HttpReportSink {
+    pub fn new(url: &str, secret: Option<String>) -> Result<Self, String> {
+        let client = Client::builder()
+            .gzip(true)
+            .use_rustls_tls()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            url: url.to_string(),
+            client,
+            secret,
+            last_delivery_id: AtomicU64::new(0),
+        })
+    }
+
+    fn next_delivery_id(&self) -> u64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        let next = |last: u64| now.max(last + 1);
+        let last = self
+            .last_delivery_id
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(next(last)))
+            .unwrap_or_else(|last| last);
+        next(last)
+    }
+
+    fn post(&self, body: String) -> Result<(), String> {
+        let delivery_id = self.next_delivery_id();
+        let timestamp = unix_timestamp_now();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert(DELIVERY_ID_HEADER, HeaderValue::from(delivery_id));
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+        if let Some(secret) = &self.secret {
+            let signature = sign_delivery(secret.as_bytes(), delivery_id, timestamp, &body);
+            headers.insert(
+                SIGNATURE_HEADER,
+                HeaderValue::from_str(&signature).map_err(|e| e.to_string())?,
+            );
+        }
+
+        self.client
+            .post(&self.url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+impl ReportSink for HttpReportSink {
+    fn deliver(&self, record: &TransactionReportRecord) -> Result<(), String> {
+        self.post(serde_json::to_string(record).map_err(|e| e.to_string())?)
+    }
+
+    fn ping(&self, ping: &ReportSinkPing) -> Result<(), String> {
+        self.post(serde_json::to_string(ping).map_err(|e| e.to_string())?)
+    }
+}
+
+/// Appends reports to a file, one JSON object per line.
+pub struct FileReportSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileReportSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+}
+
+impl FileReportSink {
+    fn append(&self, mut line: String) -> Result<(), String> {
+        line.push('\n');
+
+        let _lock = self.lock.lock().expect("FileReportSink mutex poisoned");
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed opening {:?}: {}", self.path, e))?;
+        file.write_all(line.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("Failed writing {:?}: {}", self.path, e))
+    }
+}
+
+impl ReportSink for FileReportSink {
+    fn deliver(&self, record: &TransactionReportRecord) -> Result<(), String> {
+        self.append(serde_json::to_string(record).map_err(|e| e.to_string())?)
+    }
+
+    fn ping(&self, ping: &ReportSinkPing) -> Result<(), String> {
+        self.append(serde_json::to_string(ping).map_err(|e| e.to_string())?)
+    }
+}
+
+/// Decides which transactions are reported, and where the reports go.
+pub struct TransactionReporter {
+    /// The value of a token paid by a transaction at or above which the
+    /// transaction is reported. Tokens without a threshold are never
+    /// reported.
+    thresholds: BTreeMap<TokenId, u64>,
+
+    sink: Arc<dyn ReportSink>,
+}
+
+impl TransactionReporter {
+    pub fn new(thresholds: BTreeMap<TokenId, u64>, sink: Arc<dyn ReportSink>) -> Self {
+        Self { thresholds, sink }
+    }
+
+    pub fn sink(&self) -> &dyn ReportSink {
+        self.sink.as_ref()
+    }
+
+    /// The record for a submitted transaction, or None if the total paid to
+    /// recipients is below the threshold for every token.
+    pub fn record_for(
+        &self,
+        account_id_hex: &str,
+        transaction_log_id: &str,
+        tx_proposal: &TxProposal,
+        submitted_block_index: u64,
+    ) -> Result<Option<TransactionReportRecord>, TransactionReportServiceError> {
+        let mut totals: BTreeMap<TokenId, u128> = BTreeMap::new();
+        for output in tx_proposal.payload_txos.iter() {
+            *totals.entry(output.amount.token_id).or_default() += output.amount.value as u128;
+        }
+
+        let exceeds_threshold = totals.iter().any(|(token_id, total)| {
+            self.thresholds
+                .get(token_id)
+                .map_or(false, |threshold| *total >= *threshold as u128)
+        });
+        if !exceeds_threshold {
+            return Ok(None);
+        }
+
+        let outputs = tx_proposal
+            .payload_txos
+            .iter()
+            .map(|output| {
+                Ok(TransactionReportOutput {
+                    recipient_address: b58_encode_public_address(&output.recipient_public_address)?,
+                    value: output.amount.value.to_string(),
+                    token_id: output.amount.token_id.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, TransactionReportServiceError>>()?;
+
+        let prefix = &tx_proposal.tx.prefix;
+        Ok(Some(TransactionReportRecord {
+            event_id: new_event_id(),
+            event_type: transaction_report_event(),
+            transaction_log_id: transaction_log_id.to_string(),
+            account_id: account_id_hex.to_string(),
+            submitted_block_index: submitted_block_index.to_string(),
+            tombstone_block_index: prefix.tombstone_block.to_string(),
+            fee_value: prefix.fee.to_string(),
+            fee_token_id: prefix.fee_token_id.to_string(),
+            outputs,
+        }))
+    }
+
+    /// Queue the report for a submitted transaction, if it should be
+    /// reported. This is intended to run in the same database transaction
+    /// which logs the submission, so that a logged transaction is never left
+    /// unreported.
+    pub fn enqueue(
+        &self,
+        account_id_hex: &str,
+        transaction_log_id: &str,
+        tx_proposal: &TxProposal,
+        submitted_block_index: u64,
+        conn: &Conn,
+    ) -> Result<Option<TransactionReport>, TransactionReportServiceError> {
+        let record = match self.record_for(
+            account_id_hex,
+            transaction_log_id,
+            tx_proposal,
+            submitted_block_index,
+        )? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+
+        Ok(Some(TransactionReport::create(
+            transaction_log_id,
+            account_id_hex,
+            &serde_json::to_string(&record)?,
+            unix_timestamp_now(),
+            conn,
+        )?))
+    }
+}
+
+/// Attempt delivery of every undelivered report, returning the number
+/// delivered and the number which failed.
+pub fn deliver_pending_reports(
+    wallet_db: &WalletDb,
+    sink: &dyn ReportSink,
+) -> Result<(usize, usize), TransactionReportServiceError> {
+    let conn = wallet_db.get_conn()?;
+    let mut num_delivered = 0;
+    let mut num_failed = 0;
+
+    for report in TransactionReport::list_undelivered(None, &conn)? {
+        let result = serde_json::from_str::<TransactionReportRecord>(&report.record)
+            .map_err(|e| format!("Invalid stored record: {}", e))
+            .and_then(|mut record| {
+                if record.event_id.is_empty() {
+                    record.event_id = report.transaction_log_id.clone();
+                }
+                sink.deliver(&record)
+            });
+        match result {
+            Ok(()) => {
+                report.mark_delivered(unix_timestamp_now(), &conn)?;
+                num_delivered += 1;
+            }
+            Err(e) => {
+                report.mark_failed(&e, &conn)?;
+                num_failed += 1;
+            }
+        }
+    }
+
+    Ok((num_delivered, num_failed))
+}
+
+/// Background thread which delivers queued reports.
+pub struct TransactionReportThread {
+    join_handle: Option<thread::JoinHandle<()>>,
+
+    /// Stop trigger, used to signal the thread to terminate.
+    stop_requested: Arc<AtomicBool>,
+}
+
+impl TransactionReportThread {
+    pub fn start(wallet_db: WalletDb, reporter: Arc<TransactionReporter>, logger: Logger) -> Self {
+        let stop_requested = Arc::new(AtomicBool::new(false));
+        let thread_stop_requested = stop_requested.clone();
+
+        let join_handle = Some(
+            thread::Builder::new()
+                .name("transaction_report".to_string())
+                .spawn(move || {
+                    log::debug!(logger, "Transaction report thread started.");
+
+                    let mut last_attempt: Option<Instant> = None;
+                    while !thread_stop_requested.load(Ordering::SeqCst) {
+                        if last_attempt.map_or(true, |attempted| {
+                            attempted.elapsed()
+                                >= Duration::from_secs(TRANSACTION_REPORT_RETRY_INTERVAL_SECONDS)
+                        }) {
+                            match deliver_pending_reports(&wallet_db, reporter.sink()) {
+                                Ok((_, 0)) => (),
+                                Ok((num_delivered, num_failed)) => log::warn!(
+                                    &logger,
+                                    "Delivered {} transaction reports, {} failed and will be retried",
+                                    num_delivered,
+                                    num_failed
+                                ),
+                                Err(e) => log::error!(
+                                    &logger,
+                                    "Error delivering transaction reports:\n{:?}",
+                                    e
+                                ),
+                            }
+                            last_attempt = Some(Instant::now());
+                        }
+
+                        thread::sleep(Duration::from_secs(1));
+                    }
+                    log::debug!(logger, "Transaction report thread stopped.");
+                })
+                .expect("failed starting transaction report thread"),
+        );
+
+        Self {
+            join_handle,
+            stop_requested,
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        if let Some(join_handle) = self.join_handle.take() {
+            join_handle
+                .join()
+                .expect("TransactionReportThread join failed");
+        }
+    }
+}
+
+impl Drop for TransactionReportThread {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Trait defining the ways in which the wallet can interact with transaction
+/// reports.
+pub trait TransactionReportService {
+    /// List the reports which have not yet been delivered, including those
+    /// whose delivery failed, optionally for a single account.
+    fn list_undelivered_transaction_reports(
+        &self,
+        account_id: Option<String>,
+    ) -> Result<Vec<TransactionReport>, TransactionReportServiceError>;
+
+    /// Attempt delivery of every undelivered report now, rather than waiting
+    /// for the background thread. Returns the number delivered and the number
+    /// which failed.
+    fn retry_transaction_reports(&self) -> Result<(usize, usize), TransactionReportServiceError>;
+
+    /// Send a ping to the report sink, signed like a report, so that the
+    /// receiver can check its verification. Pings are not retried.
+    fn ping_transaction_report_sink(&self)
+        -> Result<ReportSinkPing, TransactionReportServiceError>;
+}
+
+impl<T, FPR> TransactionReportService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn list_undelivered_transaction_reports(
+        &self,
+        account_id: Option<String>,
+    ) -> Result<Vec<TransactionReport>, TransactionReportServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        Ok(TransactionReport::list_undelivered(
+            account_id.as_deref(),
+            &conn,
+        )?)
+    }
+
+    fn retry_transaction_reports(&self) -> Result<(usize, usize), TransactionReportServiceError> {
+        let reporter = self
+            .transaction_reporter
+            .as_ref()
+            .ok_or(TransactionReportServiceError::ReportingDisabled)?;
+        deliver_pending_reports(&self.wallet_db, reporter.sink())
+    }
+
+    fn ping_transaction_report_sink(
+        &self,
+    ) -> Result<ReportSinkPing, TransactionReportServiceError> {
+        let reporter = self
+            .transaction_reporter
+            .as_ref()
+            .ok_or(TransactionReportServiceError::ReportingDisabled)?;
+        let ping = ReportSinkPing::new();
+        reporter
+            .sink()
+            .ping(&ping)
+            .map_err(TransactionReportServiceError::DeliveryFailed)?;
+        Ok(ping)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{account::AccountID, WalletDbTestContext},
+        json_rpc::v2::models::amount::Amount as AmountJSON,
+        service::{
+            account::AccountService,
+            address::AddressService,
+            transaction::{TransactionMemo, TransactionService},
+        },
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account,
+            setup_wallet_service_with_reporter, MOB,
+        },
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        io::{BufRead, BufReader, Read},
+        net::TcpListener,
+    };
+
+    /// Records delivered reports, and fails deliveries while `failing` is set.
+    #[derive(Default)]
+    struct MockReportSink {
+        delivered: Mutex<Vec<TransactionReportRecord>>,
+        pings: Mutex<Vec<ReportSinkPing>>,
+        failing: AtomicBool,
+    }
+
+    impl ReportSink for MockReportSink {
+        fn deliver(&self, record: &TransactionReportRecord) -> Result<(), String> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err("sink unavailable".to_string());
+            }
+            self.delivered.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+
+        fn ping(&self, ping: &ReportSinkPing) -> Result<(), String> {
+            if self.failing.load(Ordering::SeqCst) {
+                return Err("sink unavailable".to_string());
+            }
+            self.pings.lock().unwrap().push(ping.clone());
+            Ok(())
+        }
+    }
+
+    /// A request received by `serve_webhooks`.
+    struct ReceivedWebhook {
+        delivery_id: u64,
+        timestamp: i64,
+        signature: Option<String>,
+        body: String,
+    }
+
+    /// Receives webhooks over HTTP, answering each with the next of the given
+    /// statuses, and returns its URL and the requests received.
+    fn serve_webhooks(statuses: Vec<&'static str>) -> (String, Arc<Mutex<Vec<ReceivedWebhook>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/webhook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let thread_received = received.clone();
+        thread::spawn(move || {
+            for (stream, status) in listener.incoming().zip(statuses) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut headers = BTreeMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                    }
+                }
+                let content_length: usize = headers["content-length"].parse().unwrap();
+                let mut body = vec![0u8; content_length];
+                reader.read_exact(&mut body).unwrap();
+                thread_received.lock().unwrap().push(ReceivedWebhook {
+                    delivery_id: headers[&DELIVERY_ID_HEADER.to_lowercase()].parse().unwrap(),
+                    timestamp: headers[&TIMESTAMP_HEADER.to_lowercase()].parse().unwrap(),
+                    signature: headers.get(&SIGNATURE_HEADER.to_lowercase()).cloned(),
+                    body: String::from_utf8(body).unwrap(),
+                });
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn verify(secret: &[u8], webhook: &ReceivedWebhook) -> bool {
+        verify_delivery(
+            secret,
+            webhook.delivery_id,
+            webhook.timestamp,
+            &webhook.body,
+            webhook.signature.as_deref().unwrap_or_default(),
+        )
+    }
+
+    #[test_with_logger]
+    fn test_transaction_reports_above_threshold(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let sink = Arc::new(MockReportSink::default());
+        sink.failing.store(true, Ordering::SeqCst);
+        let mut thresholds = BTreeMap::new();
+        thresholds.insert(Mob::ID, 50 * MOB);
+        let reporter = TransactionReporter::new(thresholds, sink.clone());
+
+        let service =
+            setup_wallet_service_with_reporter(ledger_db.clone(), Some(reporter), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_public_address = alice_account_key.subaddress(alice.main_subaddress_index as u64);
+
+        // Two txos, so that the second payment does not wait on change from the
+        // first.
+        for _ in 0..2 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_public_address.clone()],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(
+            &ledger_db,
+            &service.wallet_db,
+            &AccountID(alice.id.to_string()),
+            &logger,
+        );
+
+        let bob = service
+            .create_account(
+                Some("Bob's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let bob_address = service
+            .assign_address_for_account(&AccountID(bob.id.clone()), None)
+            .unwrap()
+            .public_address_b58;
+
+        // A payment below the threshold is not reported.
+        let (small_log, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(bob_address.clone(), AmountJSON::new(10 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        let conn = service.wallet_db.get_conn().unwrap();
+        assert!(TransactionReport::get(&small_log.id, &conn).is_err());
+
+        // A payment at the threshold is queued, and stays queued while the
+        // sink is failing.
+        let (large_log, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(bob_address.clone(), AmountJSON::new(50 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        assert_eq!(service.retry_transaction_reports().unwrap(), (0, 1));
+        let undelivered = service
+            .list_undelivered_transaction_reports(Some(alice.id.clone()))
+            .unwrap();
+        assert_eq!(undelivered.len(), 1);
+        assert_eq!(undelivered[0].transaction_log_id, large_log.id);
+        assert_eq!(undelivered[0].attempts, 1);
+        assert_eq!(
+            undelivered[0].last_error,
+            Some("sink unavailable".to_string())
+        );
+        assert!(sink.delivered.lock().unwrap().is_empty());
+
+        // Once the sink recovers, the report is delivered exactly once.
+        sink.failing.store(false, Ordering::SeqCst);
+        assert_eq!(service.retry_transaction_reports().unwrap(), (1, 0));
+        assert_eq!(service.retry_transaction_reports().unwrap(), (0, 0));
+        assert!(service
+            .list_undelivered_transaction_reports(None)
+            .unwrap()
+            .is_empty());
+
+        let delivered = sink.delivered.lock().unwrap();
+        assert_eq!(delivered.len(), 1);
+        assert_eq!(delivered[0].transaction_log_id, large_log.id);
+        assert_eq!(delivered[0].account_id, alice.id);
+        assert_eq!(
+            delivered[0].outputs,
+            vec![TransactionReportOutput {
+                recipient_address: bob_address,
+                value: (50 * MOB).to_string(),
+                token_id: Mob::ID.to_string(),
+            }]
+        );
+    }
+
+    #[test_with_logger]
+    fn test_transaction_reports_ignore_tokens_without_threshold(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        let mut thresholds = BTreeMap::new();
+        thresholds.insert(TokenId::from(1), 1);
+        let reporter = TransactionReporter::new(thresholds, Arc::new(MockReportSink::default()));
+
+        let service =
+            setup_wallet_service_with_reporter(ledger_db.clone(), Some(reporter), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_public_address = alice_account_key.subaddress(alice.main_subaddress_index as u64);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_public_address.clone()],
+            200 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(
+            &ledger_db,
+            &service.wallet_db,
+            &AccountID(alice.id.to_string()),
+            &logger,
+        );
+
+        // MOB has no threshold, so even a large payment is not reported.
+        service
+            .build_sign_and_submit_transaction(
+                &alice.id,
+                &[(
+                    b58_encode_public_address(&alice_public_address).unwrap(),
+                    AmountJSON::new(150 * MOB, Mob::ID),
+                )],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+                None,
+            )
+            .unwrap();
+        assert!(service
+            .list_undelivered_transaction_reports(None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_file_report_sink_appends_lines() {
+        let tempdir = tempdir::TempDir::new("transaction-reports").unwrap();
+        let path = tempdir.path().join("reports.ndjson");
+        let sink = FileReportSink::new(path.clone());
+
+        let record = TransactionReportRecord {
+            event_id: "event-1".to_string(),
+            event_type: TRANSACTION_REPORT_EVENT.to_string(),
+            transaction_log_id: "log-1".to_string(),
+            account_id: "account".to_string(),
+            submitted_block_index: "10".to_string(),
+            tombstone_block_index: "20".to_string(),
+            fee_value: "400000000".to_string(),
+            fee_token_id: "0".to_string(),
+            outputs: vec![],
+        };
+        sink.deliver(&record).unwrap();
+        sink.deliver(&TransactionReportRecord {
+            transaction_log_id: "log-2".to_string(),
+            ..record.clone()
+        })
+        .unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<TransactionReportRecord> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], record);
+        assert_eq!(lines[1].transaction_log_id, "log-2");
+    }
+
+    #[test_with_logger]
+    fn test_http_report_sink_signs_deliveries(logger: Logger) {
+        let db_test_context = WalletDbTestContext::default();
+        let wallet_db = db_test_context.get_db_instance(logger);
+        let secret = b"webhook secret";
+
+        // The receiver fails the first delivery, and accepts the second.
+        let (url, received) = serve_webhooks(vec!["500 Internal Server Error", "200 OK"]);
+        let sink = HttpReportSink::new(&url, Some("webhook secret".to_string())).unwrap();
+
+        let record = TransactionReportRecord {
+            event_id: "event-1".to_string(),
+            event_type: TRANSACTION_REPORT_EVENT.to_string(),
+            transaction_log_id: "log-1".to_string(),
+            account_id: "account".to_string(),
+            submitted_block_index: "10".to_string(),
+            tombstone_block_index: "20".to_string(),
+            fee_value: "400000000".to_string(),
+            fee_token_id: "0".to_string(),
+            outputs: vec![],
+        };
+        TransactionReport::create(
+            "log-1",
+            "account",
+            &serde_json::to_string(&record).unwrap(),
+            unix_timestamp_now(),
+            &wallet_db.get_conn().unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(deliver_pending_reports(&wallet_db, &sink).unwrap(), (0, 1));
+        assert_eq!(deliver_pending_reports(&wallet_db, &sink).unwrap(), (1, 0));
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+
+        // The redelivery is the same event, under a new delivery id.
+        for webhook in received.iter() {
+            assert!(verify(secret, webhook));
+            let delivered: TransactionReportRecord = serde_json::from_str(&webhook.body).unwrap();
+            assert_eq!(delivered, record);
+        }
+        assert!(received[1].delivery_id > received[0].delivery_id);
+
+        // Signatures do not verify with another secret, or for a tampered
+        // body or replayed under another delivery id.
+        assert!(!verify(b"another secret", &received[1]));
+        assert!(!verify(
+            secret,
+            &ReceivedWebhook {
+                body: received[1].body.replace("log-1", "log-2"),
+                signature: received[1].signature.clone(),
+                ..received[1]
+            }
+        ));
+        assert!(!verify(
+            secret,
+            &ReceivedWebhook {
+                delivery_id: received[1].delivery_id + 1,
+                body: received[1].body.clone(),
+                signature: received[1].signature.clone(),
+                ..received[1]
+            }
+        ));
+    }
+
+    #[test]
+    fn test_http_report_sink_ping() {
+        let (url, received) = serve_webhooks(vec!["200 OK"]);
+        let sink = HttpReportSink::new(&url, Some("webhook secret".to_string())).unwrap();
+
+        let ping = ReportSinkPing::new();
+        sink.ping(&ping).unwrap();
+
+        let received = received.lock().unwrap();
+        assert!(verify(b"webhook secret", &received[0]));
+        let delivered: ReportSinkPing = serde_json::from_str(&received[0].body).unwrap();
+        assert_eq!(delivered, ping);
+        assert_eq!(delivered.event_type, PING_EVENT);
+    }
+
+    #[test]
+    fn test_http_report_sink_without_secret_is_unsigned() {
+        let (url, received) = serve_webhooks(vec!["200 OK"]);
+        let sink = HttpReportSink::new(&url, None).unwrap();
+        sink.ping(&ReportSinkPing::new()).unwrap();
+        assert_eq!(received.lock().unwrap()[0].signature, None);
+    }
+}
@@ -7,21 +7,37 @@ use crate::{
         account::{AccountID, AccountModel},
         assigned_subaddress::AssignedSubaddressModel,
         models::{Account, AssignedSubaddress, Txo},
+        transaction,
         txo::{TxoID, TxoModel, TxoStatus},
-        WalletDbError,
+        Conn, WalletDb, WalletDbError,
     },
     error::WalletTransactionBuilderError,
     json_rpc::v2::models::amount::Amount,
     service::{
+        job::{Job, JobContext, JobServiceError},
         models::tx_proposal::TxProposal,
+        sync::{decode_amount, recover_subaddress_spend_public_key, SyncKeys},
         transaction::{TransactionMemo, TransactionService, TransactionServiceError},
     },
+    util::constants::{
+        IDENTIFY_TX_OUT_OWNER_TIMEOUT_SECONDS, TXO_GLOBAL_INDEX_BACKFILL_BATCH_SIZE,
+    },
     WalletService,
 };
 use displaydoc::Display;
-use mc_account_keys::AccountKey;
+use mc_common::logger::log;
 use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
 use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::{Ledger, LedgerDB};
+use mc_transaction_core::{
+    onetime_keys::recover_onetime_private_key, ring_signature::KeyImage, tx::TxOut,
+};
+use rayon::prelude::*;
+use std::{
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
 
 /// Errors for the Txo Service.
 #[derive(Display, Debug)]
@@ -62,6 +78,27 @@ pub enum TxoServiceError {
 
     /// From String Error: {0}
     From(String),
+
+    /// Error with the Job Service: {0}
+    JobService(JobServiceError),
+
+    /// Error decoding hex: {0}
+    FromHex(hex::FromHexError),
+
+    /// TxOut not found in the wallet or the ledger: {0}
+    TxOutNotFound(String),
+
+    /// Txo {0} is not owned by account {1}
+    TxoNotOwnedByAccount(String, String),
+
+    /// Identifying the owner of the TxOut took longer than {0:?}
+    IdentifyTxOutOwnerTimeout(Duration),
+}
+
+impl From<JobServiceError> for TxoServiceError {
+    fn from(src: JobServiceError) -> Self {
+        Self::JobService(src)
+    }
 }
 
 impl From<WalletDbError> for TxoServiceError {
@@ -106,12 +143,33 @@ impl From<mc_crypto_keys::KeyError> for TxoServiceError {
     }
 }
 
+impl From<hex::FromHexError> for TxoServiceError {
+    fn from(src: hex::FromHexError) -> Self {
+        Self::FromHex(src)
+    }
+}
+
 impl From<String> for TxoServiceError {
     fn from(src: String) -> Self {
         Self::From(src)
     }
 }
 
+/// The account which owns a TxOut, found by identify_tx_out_owner.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxOutOwner {
+    pub account_id: AccountID,
+
+    /// The subaddress which received the TxOut, if it is assigned or within
+    /// the account's subaddress gap limit.
+    pub subaddress_index: Option<u64>,
+
+    pub amount: mc_transaction_core::Amount,
+
+    /// The id of the Txo, if the wallet already tracks the TxOut.
+    pub txo_id: Option<String>,
+}
+
 /// Trait defining the ways in which the wallet can interact with and manage
 /// Txos.
 pub trait TxoService {
@@ -129,9 +187,69 @@ pub trait TxoService {
         limit: Option<u64>,
     ) -> Result<Vec<(Txo, TxoStatus)>, TxoServiceError>;
 
+    /// Get a page of the Txos for an account, ordered by id and starting after
+    /// the given Txo id, for streaming through large wallets.
+    fn list_txos_after(
+        &self,
+        account_id: &str,
+        after_txo_id: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<(Txo, TxoStatus)>, TxoServiceError>;
+
+    /// Get the Txos of an account which were created or changed since the
+    /// given cursor, in the order of their changes, for incremental exports.
+    ///
+    /// A Txo is listed again each time it changes, such as when it is spent,
+    /// its subaddress is recovered or its key image is set.
+    ///
+    /// # Returns
+    /// * The Txos and their statuses, and the cursor to pass to the next call,
+    ///   which is the given cursor if nothing has changed.
+    fn list_txos_modified_since(
+        &self,
+        account_id: &str,
+        since_cursor: Option<&str>,
+        limit: u64,
+    ) -> Result<(Vec<(Txo, TxoStatus)>, Option<String>), TxoServiceError>;
+
     /// Get a Txo from the wallet.
     fn get_txo(&self, txo_id: &TxoID) -> Result<(Txo, TxoStatus), TxoServiceError>;
 
+    /// Get a Txo from the wallet by its index among all txos in the ledger.
+    fn get_txo_by_global_index(
+        &self,
+        global_index: u64,
+    ) -> Result<(Txo, TxoStatus), TxoServiceError>;
+
+    /// Start a job recording the ledger global index of every Txo received
+    /// before global indices were stored.
+    fn backfill_txo_global_indices(&self) -> Result<Job, TxoServiceError>;
+
+    /// Find the account and subaddress which own a TxOut, given as hex of
+    /// either its protobuf encoding or its public key. The wallet's Txos are
+    /// checked first. Otherwise the TxOut is matched against the view key of
+    /// every account, and against its assigned subaddresses and those within
+    /// its subaddress gap limit.
+    ///
+    /// Returns None if no account in the wallet owns the TxOut.
+    fn identify_tx_out_owner(
+        &self,
+        tx_out_proto_hex: Option<&str>,
+        tx_out_public_key_hex: Option<&str>,
+    ) -> Result<Option<TxOutOwner>, TxoServiceError>;
+
+    /// Import a Txo the wallet does not know about, such as after losing the
+    /// wallet database, from the ledger by its public key. The Txo is matched
+    /// against the account's keys as when syncing, and its key image is
+    /// checked against the ledger, so that it can be spent like any other.
+    ///
+    /// Importing a Txo the account already has returns it unchanged.
+    fn import_txo(
+        &self,
+        account_id: &AccountID,
+        tx_out_public_key_hex: &str,
+    ) -> Result<(Txo, TxoStatus), TxoServiceError>;
+
     /// Split a Txo
     fn split_txo(
         &self,
@@ -209,6 +327,54 @@ where
         Ok(txos_and_statuses)
     }
 
+    fn list_txos_after(
+        &self,
+        account_id: &str,
+        after_txo_id: Option<&str>,
+        limit: u64,
+    ) -> Result<Vec<(Txo, TxoStatus)>, TxoServiceError> {
+        let conn = &self.wallet_db.get_conn()?;
+        Txo::list_for_account_after(account_id, after_txo_id, limit, conn)?
+            .into_iter()
+            .map(|txo| {
+                let status = txo.status(conn)?;
+                Ok((txo, status))
+            })
+            .collect()
+    }
+
+    fn list_txos_modified_since(
+        &self,
+        account_id: &str,
+        since_cursor: Option<&str>,
+        limit: u64,
+    ) -> Result<(Vec<(Txo, TxoStatus)>, Option<String>), TxoServiceError> {
+        let since = since_cursor.map(decode_modified_cursor).transpose()?;
+
+        let conn = &self.wallet_db.get_conn()?;
+        let txos = Txo::list_modified_since(
+            account_id,
+            since.as_ref().map(|(seq, id)| (*seq, id.as_str())),
+            limit,
+            conn,
+        )?;
+
+        let next_cursor = match txos.last() {
+            Some(txo) => Some(encode_modified_cursor(txo)),
+            None => since_cursor.map(|cursor| cursor.to_string()),
+        };
+
+        let txos_and_statuses = txos
+            .into_iter()
+            .map(|txo| {
+                let status = txo.status(conn)?;
+                Ok((txo, status))
+            })
+            .collect::<Result<Vec<(Txo, TxoStatus)>, TxoServiceError>>()?;
+
+        Ok((txos_and_statuses, next_cursor))
+    }
+
     fn get_txo(&self, txo_id: &TxoID) -> Result<(Txo, TxoStatus), TxoServiceError> {
         let conn = self.wallet_db.get_conn()?;
         let txo = Txo::get(&txo_id.to_string(), &conn)?;
@@ -216,6 +382,202 @@ where
         Ok((txo, status))
     }
 
+    fn get_txo_by_global_index(
+        &self,
+        global_index: u64,
+    ) -> Result<(Txo, TxoStatus), TxoServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let txo = Txo::get_by_global_index(global_index, &conn)?;
+        let status = txo.status(&conn)?;
+        Ok((txo, status))
+    }
+
+    fn identify_tx_out_owner(
+        &self,
+        tx_out_proto_hex: Option<&str>,
+        tx_out_public_key_hex: Option<&str>,
+    ) -> Result<Option<TxOutOwner>, TxoServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+
+        let (tx_out, tracked_txo) = match (tx_out_proto_hex, tx_out_public_key_hex) {
+            (Some(tx_out_proto_hex), None) => {
+                let tx_out: TxOut = mc_util_serial::decode(&hex::decode(tx_out_proto_hex)?)?;
+                let tracked_txo = Txo::select_by_id(&[TxoID::from(&tx_out).to_string()], &conn)?;
+                (tx_out, tracked_txo.into_iter().next())
+            }
+            (None, Some(tx_out_public_key_hex)) => {
+                let public_key = decode_tx_out_public_key(tx_out_public_key_hex)?;
+                match Txo::select_by_public_key(&[&public_key], &conn)?
+                    .into_iter()
+                    .next()
+                {
+                    Some(txo) => (mc_util_serial::decode(&txo.txo)?, Some(txo)),
+                    None => {
+                        let (tx_out, _) =
+                            get_tx_out_from_ledger(&self.ledger_db, tx_out_public_key_hex)?;
+                        (tx_out, None)
+                    }
+                }
+            }
+            _ => {
+                return Err(TxoServiceError::InvalidQuery(
+                    "Must query with either a tx_out_proto_hex or a tx_out_public_key_hex"
+                        .to_string(),
+                ))
+            }
+        };
+
+        if let Some(txo) = &tracked_txo {
+            if let Some(account_id) = &txo.account_id {
+                return Ok(Some(TxOutOwner {
+                    account_id: AccountID(account_id.clone()),
+                    subaddress_index: txo.subaddress_index.map(|index| index as u64),
+                    amount: txo.amount(),
+                    txo_id: Some(txo.id.clone()),
+                }));
+            }
+        }
+
+        let timeout = Duration::from_secs(IDENTIFY_TX_OUT_OWNER_TIMEOUT_SECONDS);
+        let deadline = Instant::now() + timeout;
+        let accounts = Account::list_all(&conn, None, None)?
+            .into_iter()
+            .map(|account| -> Result<_, TxoServiceError> {
+                let keys = SyncKeys::decode(&account)?;
+                Ok((account, keys))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Only the accounts whose view key decodes the amount can own the
+        // TxOut. Several can, when accounts share a view key.
+        let matches = accounts
+            .par_iter()
+            .map(|(account, keys)| {
+                if Instant::now() > deadline {
+                    return Err(TxoServiceError::IdentifyTxOutOwnerTimeout(timeout));
+                }
+                Ok(decode_amount(&tx_out, keys.view_private_key())
+                    .map(|amount| (account, keys, amount)))
+            })
+            .collect::<Result<Vec<_>, TxoServiceError>>()?;
+
+        let mut owner = None;
+        for (account, keys, amount) in matches.into_iter().flatten() {
+            let subaddress_index =
+                find_tx_out_subaddress_index(&tx_out, account, keys, deadline, timeout, &conn)?;
+            let candidate = TxOutOwner {
+                account_id: AccountID(account.id.clone()),
+                subaddress_index,
+                amount,
+                txo_id: tracked_txo.as_ref().map(|txo| txo.id.clone()),
+            };
+            if subaddress_index.is_some() {
+                return Ok(Some(candidate));
+            }
+            owner.get_or_insert(candidate);
+        }
+        Ok(owner)
+    }
+
+    fn import_txo(
+        &self,
+        account_id: &AccountID,
+        tx_out_public_key_hex: &str,
+    ) -> Result<(Txo, TxoStatus), TxoServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        let (tx_out, global_index) =
+            get_tx_out_from_ledger(&self.ledger_db, tx_out_public_key_hex)?;
+
+        let txo_id = TxoID::from(&tx_out).to_string();
+        if let Some(txo) = Txo::select_by_id(&[txo_id.clone()], &conn)?
+            .into_iter()
+            .find(|txo| txo.account_id.as_deref() == Some(account.id.as_str()))
+        {
+            let status = txo.status(&conn)?;
+            return Ok((txo, status));
+        }
+
+        let keys = SyncKeys::decode(&account)?;
+        let amount = decode_amount(&tx_out, keys.view_private_key()).ok_or_else(|| {
+            TxoServiceError::TxoNotOwnedByAccount(txo_id.clone(), account.id.clone())
+        })?;
+        let timeout = Duration::from_secs(IDENTIFY_TX_OUT_OWNER_TIMEOUT_SECONDS);
+        let subaddress_index = find_tx_out_subaddress_index(
+            &tx_out,
+            &account,
+            &keys,
+            Instant::now() + timeout,
+            timeout,
+            &conn,
+        )?;
+        let key_image = match (&keys, subaddress_index) {
+            (SyncKeys::Full(account_key), Some(subaddress_index)) => {
+                let onetime_private_key = recover_onetime_private_key(
+                    &RistrettoPublic::try_from(&tx_out.public_key)?,
+                    account_key.view_private_key(),
+                    &account_key.subaddress_spend_private(subaddress_index),
+                );
+                Some(KeyImage::from(&onetime_private_key))
+            }
+            _ => None,
+        };
+        let spent_block_index = match &key_image {
+            Some(key_image) => self.ledger_db.check_key_image(key_image)?,
+            None => None,
+        };
+        let received_block_index = self
+            .ledger_db
+            .get_block_index_by_tx_out_index(global_index)?;
+
+        transaction(&conn, || {
+            Txo::create_received(
+                tx_out.clone(),
+                subaddress_index,
+                key_image,
+                amount,
+                received_block_index,
+                Some(global_index),
+                &account.id,
+                &conn,
+            )?;
+            if let Some(spent_block_index) = spent_block_index {
+                Txo::update_spent_block_index(&txo_id, spent_block_index, &conn)?;
+            }
+            Ok::<(), TxoServiceError>(())
+        })?;
+
+        let txo = Txo::get(&txo_id, &conn)?;
+        let status = txo.status(&conn)?;
+        Ok((txo, status))
+    }
+
+    fn backfill_txo_global_indices(&self) -> Result<Job, TxoServiceError> {
+        let ledger_db = self.ledger_db.clone();
+        let wallet_db = self.wallet_db.clone();
+        let job = self
+            .jobs
+            .submit("backfill_txo_global_indices", move |context| {
+                let num_txos_updated = backfill_txo_global_indices(
+                    &ledger_db,
+                    &wallet_db,
+                    TXO_GLOBAL_INDEX_BACKFILL_BATCH_SIZE,
+                    context,
+                )
+                .map_err(|e| e.to_string())?;
+                Ok(Some(serde_json::json!({
+                    "num_txos_updated": num_txos_updated.to_string(),
+                })))
+            })?;
+        log::info!(
+            self.logger,
+            "Backfilling txo global indices in job {}",
+            job.id
+        );
+
+        Ok(job)
+    }
+
     fn split_txo(
         &self,
         txo_id: &TxoID,
@@ -248,6 +610,7 @@ where
                 Amount {
                     value: output_value.to_string(),
                     token_id: txo_details.token_id.to_string(),
+                    ..Default::default()
                 },
             ))
         }
@@ -260,14 +623,148 @@ where
             fee_token_id,
             tombstone_block,
             None,
+            None,
+            false,
             TransactionMemo::RTH,
         )?;
 
         let account = Account::get(&AccountID(account_id_hex), &conn)?;
-        let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
 
-        Ok(unsigned_tx.sign(&account_key, fog_resolver)?)
+        Ok(self.sign_transaction(unsigned_tx, fog_resolver, &account, &conn)?)
+    }
+}
+
+/// Decode a TxOut public key from hex of either the key or its protobuf
+/// encoding.
+fn decode_tx_out_public_key(
+    tx_out_public_key_hex: &str,
+) -> Result<CompressedRistrettoPublic, TxoServiceError> {
+    let public_key_bytes = hex::decode(tx_out_public_key_hex)?;
+    match CompressedRistrettoPublic::try_from(&public_key_bytes[..]) {
+        Ok(public_key) => Ok(public_key),
+        Err(_) => Ok(mc_util_serial::decode(&public_key_bytes)?),
+    }
+}
+
+/// Get a TxOut from the ledger by its public key, with its index among all
+/// tx outs in the ledger.
+fn get_tx_out_from_ledger(
+    ledger_db: &LedgerDB,
+    tx_out_public_key_hex: &str,
+) -> Result<(TxOut, u64), TxoServiceError> {
+    let public_key = decode_tx_out_public_key(tx_out_public_key_hex)?;
+    let global_index = match ledger_db.get_tx_out_index_by_public_key(&public_key) {
+        Ok(global_index) => global_index,
+        Err(mc_ledger_db::Error::NotFound) => {
+            return Err(TxoServiceError::TxOutNotFound(
+                tx_out_public_key_hex.to_string(),
+            ))
+        }
+        Err(e) => return Err(e.into()),
+    };
+    Ok((ledger_db.get_tx_out_by_index(global_index)?, global_index))
+}
+
+/// Find the subaddress of an account which received a TxOut, among the
+/// account's assigned subaddresses and the unassigned subaddresses within its
+/// gap limit.
+fn find_tx_out_subaddress_index(
+    tx_out: &TxOut,
+    account: &Account,
+    keys: &SyncKeys,
+    deadline: Instant,
+    timeout: Duration,
+    conn: &Conn,
+) -> Result<Option<u64>, TxoServiceError> {
+    let subaddress_spend_public_key =
+        match recover_subaddress_spend_public_key(tx_out, keys.view_private_key()) {
+            Some(subaddress_spend_public_key) => subaddress_spend_public_key,
+            None => return Ok(None),
+        };
+
+    let spend_public_key_bytes = subaddress_spend_public_key.to_bytes().to_vec();
+    let assigned = AssignedSubaddress::list_all(Some(account.id.clone()), None, None, conn)?
+        .into_iter()
+        .find(|subaddress| subaddress.spend_public_key == spend_public_key_bytes);
+    if let Some(subaddress) = assigned {
+        return Ok(Some(subaddress.subaddress_index as u64));
+    }
+
+    let first_unassigned_index = account.clone().next_subaddress_index(conn)?;
+    let gap_end = first_unassigned_index + account.subaddress_gap_limit as u64;
+    for index in first_unassigned_index..gap_end {
+        if Instant::now() > deadline {
+            return Err(TxoServiceError::IdentifyTxOutOwnerTimeout(timeout));
+        }
+        if keys.subaddress_spend_public_key(index) == subaddress_spend_public_key {
+            return Ok(Some(index));
+        }
+    }
+    Ok(None)
+}
+
+/// Encode the cursor which resumes a listing of modified Txos after the given
+/// Txo's latest change.
+fn encode_modified_cursor(txo: &Txo) -> String {
+    hex::encode(format!("{}:{}", txo.modified_seq, txo.id))
+}
+
+/// Decode a cursor from encode_modified_cursor.
+fn decode_modified_cursor(cursor: &str) -> Result<(i64, String), TxoServiceError> {
+    let invalid = || TxoServiceError::InvalidQuery(format!("Invalid cursor: {}", cursor));
+    let decoded =
+        String::from_utf8(hex::decode(cursor).map_err(|_| invalid())?).map_err(|_| invalid())?;
+    let (modified_seq, txo_id) = decoded.split_once(':').ok_or_else(invalid)?;
+    Ok((
+        modified_seq.parse::<i64>().map_err(|_| invalid())?,
+        txo_id.to_string(),
+    ))
+}
+
+/// Record the ledger global index of Txos which do not have one yet, looking
+/// them up in the ledger a batch at a time. Txos not in the ledger, such as
+/// pending outputs, are left without an index. Each batch is committed on its
+/// own, so a cancelled or interrupted backfill picks up where it left off when
+/// run again. Returns the number of Txos updated.
+pub fn backfill_txo_global_indices(
+    ledger_db: &LedgerDB,
+    wallet_db: &WalletDb,
+    batch_size: u64,
+    job: &JobContext,
+) -> Result<u64, TxoServiceError> {
+    let conn = wallet_db.get_conn()?;
+    let mut after_txo_id: Option<String> = None;
+    let mut num_txos_checked = 0;
+    let mut num_txos_updated = 0;
+
+    while !job.is_cancelled() {
+        let txos = Txo::list_missing_global_index(after_txo_id.as_deref(), batch_size, &conn)?;
+        let last_txo = match txos.last() {
+            Some(txo) => txo.id.clone(),
+            None => break,
+        };
+
+        num_txos_updated += transaction(&conn, || {
+            let mut num_updated = 0;
+            for txo in &txos {
+                match ledger_db.get_tx_out_index_by_public_key(&txo.public_key()?) {
+                    Ok(global_index) => {
+                        txo.update_global_index(global_index, &conn)?;
+                        num_updated += 1;
+                    }
+                    Err(mc_ledger_db::Error::NotFound) => {}
+                    Err(e) => return Err(TxoServiceError::from(e)),
+                }
+            }
+            Ok(num_updated)
+        })?;
+
+        num_txos_checked += txos.len() as u64;
+        job.set_progress(num_txos_checked, None);
+        after_txo_id = Some(last_txo);
     }
+
+    Ok(num_txos_updated)
 }
 
 #[cfg(test)]
@@ -367,11 +864,14 @@ mod tests {
                 None,
                 None,
                 None,
+                None,
+                false,
+                None,
                 TransactionMemo::RTH,
             )
             .unwrap();
         let _submitted = service
-            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()))
+            .submit_transaction(&tx_proposal, None, Some(alice.id.clone()), None, false)
             .unwrap();
 
         let pending: Vec<(Txo, TxoStatus)> = service
@@ -401,4 +901,298 @@ mod tests {
         assert_eq!(balance_pmob.spent, 0);
         assert_eq!(balance_pmob.orphaned, 0);
     }
+
+    #[test_with_logger]
+    fn test_backfill_txo_global_indices(logger: Logger) {
+        use crate::{
+            db::schema::txos,
+            service::job::{JobService, JobStatus},
+            test_utils::{create_test_received_txo, random_account_with_seed_values},
+        };
+        use diesel::prelude::*;
+        use mc_transaction_core::Amount;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB, 80 * MOB, 90 * MOB],
+            &mut rng,
+            &logger,
+        );
+
+        // A txo which is not in the ledger keeps a null global index.
+        let (missing_txo_id, _, _) = create_test_received_txo(
+            &account_key,
+            0,
+            Amount::new(10 * MOB, Mob::ID),
+            12,
+            &mut rng,
+            &service.wallet_db,
+        );
+
+        // Forget the indices stored at sync, as for txos synced before global
+        // indices were stored.
+        let conn = service.wallet_db.get_conn().unwrap();
+        diesel::update(txos::table)
+            .set(txos::global_index.eq(None::<i64>))
+            .execute(&conn)
+            .unwrap();
+
+        let run_backfill = || {
+            let job = service.backfill_txo_global_indices().unwrap();
+            assert_eq!(job.kind, "backfill_txo_global_indices");
+            let mut job = service.get_job_status(&job.id).unwrap();
+            for _ in 0..500 {
+                if job.status.is_finished() {
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                job = service.get_job_status(&job.id).unwrap();
+            }
+            assert_eq!(job.status, JobStatus::Completed);
+            job.result.unwrap()["num_txos_updated"].clone()
+        };
+
+        assert_eq!(run_backfill(), "3");
+
+        let txos = Txo::list(None, None, None, None, None, None, &conn).unwrap();
+        assert_eq!(txos.len(), 4);
+        for txo in txos {
+            if txo.id == missing_txo_id {
+                assert_eq!(txo.global_index, None);
+                continue;
+            }
+            let global_index = ledger_db
+                .get_tx_out_index_by_public_key(&txo.public_key().unwrap())
+                .unwrap();
+            assert_eq!(txo.global_index, Some(global_index as i64));
+
+            let (found, _) = service.get_txo_by_global_index(global_index).unwrap();
+            assert_eq!(found.id, txo.id);
+        }
+
+        // Only txos still missing an index are looked up again.
+        assert_eq!(run_backfill(), "0");
+    }
+
+    #[test_with_logger]
+    fn test_identify_tx_out_owner(logger: Logger) {
+        use crate::test_utils::random_account_with_seed_values;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id = AccountID::from(&account_key);
+        service
+            .update_account_subaddress_gap_limit(&account_id, 10)
+            .unwrap();
+
+        // A tracked txo is found in the wallet, by its protobuf or public key.
+        let txos = service
+            .list_txos(
+                Some(account_id.to_string()),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+        assert_eq!(txos.len(), 1);
+        let (tracked, _) = &txos[0];
+        let expected = TxOutOwner {
+            account_id: account_id.clone(),
+            subaddress_index: Some(0),
+            amount: tracked.amount(),
+            txo_id: Some(tracked.id.clone()),
+        };
+        assert_eq!(
+            service
+                .identify_tx_out_owner(Some(&hex::encode(&tracked.txo)), None)
+                .unwrap(),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            service
+                .identify_tx_out_owner(None, Some(&hex::encode(&tracked.public_key)))
+                .unwrap(),
+            Some(expected)
+        );
+
+        // A txo the wallet has not synced, received at an unassigned
+        // subaddress within the gap limit, is matched by the account's keys.
+        let conn = service.wallet_db.get_conn().unwrap();
+        let next_subaddress_index = Account::get(&account_id, &conn)
+            .unwrap()
+            .next_subaddress_index(&conn)
+            .unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[account_key.subaddress(next_subaddress_index + 3)],
+            42 * MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let untracked = ledger_db
+            .get_tx_out_by_index(ledger_db.num_txos().unwrap() - 1)
+            .unwrap();
+        let untracked_proto_hex = hex::encode(mc_util_serial::encode(&untracked));
+        let owner = service
+            .identify_tx_out_owner(None, Some(&hex::encode(untracked.public_key.as_bytes())))
+            .unwrap()
+            .unwrap();
+        assert_eq!(owner.account_id, account_id);
+        assert_eq!(owner.subaddress_index, Some(next_subaddress_index + 3));
+        assert_eq!(owner.amount.value, 42 * MOB);
+        assert_eq!(owner.txo_id, None);
+        assert_eq!(
+            service
+                .identify_tx_out_owner(Some(&untracked_proto_hex), None)
+                .unwrap(),
+            Some(owner)
+        );
+
+        // A txo for another wallet is not owned.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[AccountKey::random(&mut rng).default_subaddress()],
+            42 * MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let foreign = ledger_db
+            .get_tx_out_by_index(ledger_db.num_txos().unwrap() - 1)
+            .unwrap();
+        assert_eq!(
+            service
+                .identify_tx_out_owner(None, Some(&hex::encode(foreign.public_key.as_bytes())))
+                .unwrap(),
+            None
+        );
+
+        // Exactly one of the protobuf and the public key is given.
+        assert!(matches!(
+            service.identify_tx_out_owner(None, None),
+            Err(TxoServiceError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            service.identify_tx_out_owner(None, Some(&hex::encode([7u8; 32]))),
+            Err(TxoServiceError::TxOutNotFound(_))
+        ));
+    }
+
+    #[test_with_logger]
+    fn test_import_txo(logger: Logger) {
+        use crate::{db::schema::txos, test_utils::random_account_with_seed_values};
+        use diesel::prelude::*;
+
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let mut ledger_db = get_test_ledger(5, &[], 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+        let account_key = random_account_with_seed_values(
+            &service.wallet_db,
+            &mut ledger_db,
+            &[70 * MOB],
+            &mut rng,
+            &logger,
+        );
+        let account_id = AccountID::from(&account_key);
+        let list_account_txos = || {
+            service
+                .list_txos(
+                    Some(account_id.to_string()),
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .unwrap()
+        };
+
+        // Forget the account's txo, as when the wallet database is lost, while
+        // the account stays synced past the block which contains it.
+        let (forgotten, _) = list_account_txos().pop().unwrap();
+        let conn = service.wallet_db.get_conn().unwrap();
+        diesel::delete(txos::table.filter(txos::id.eq(&forgotten.id)))
+            .execute(&conn)
+            .unwrap();
+        assert!(list_account_txos().is_empty());
+
+        let public_key_hex = hex::encode(&forgotten.public_key);
+        let (imported, status) = service.import_txo(&account_id, &public_key_hex).unwrap();
+        assert_eq!(status, TxoStatus::Unspent);
+        assert_eq!(imported.id, forgotten.id);
+        assert_eq!(imported.value, forgotten.value);
+        assert_eq!(imported.subaddress_index, forgotten.subaddress_index);
+        assert_eq!(imported.key_image, forgotten.key_image);
+        assert_eq!(
+            imported.received_block_index,
+            forgotten.received_block_index
+        );
+        assert_eq!(imported.global_index, forgotten.global_index);
+
+        // Importing the txo again leaves it unchanged.
+        let (again, _) = service.import_txo(&account_id, &public_key_hex).unwrap();
+        assert_eq!(again, imported);
+
+        // A txo the account does not own can not be imported.
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &[AccountKey::random(&mut rng).default_subaddress()],
+            42 * MOB,
+            &[KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        let foreign = ledger_db
+            .get_tx_out_by_index(ledger_db.num_txos().unwrap() - 1)
+            .unwrap();
+        assert!(matches!(
+            service.import_txo(&account_id, &hex::encode(foreign.public_key.as_bytes())),
+            Err(TxoServiceError::TxoNotOwnedByAccount(_, _))
+        ));
+
+        // The imported txo is spent like any other.
+        let recipient = AccountKey::random(&mut rng).default_subaddress();
+        let tx_proposal = service
+            .build_and_sign_transaction(
+                &account_id.to_string(),
+                &[(
+                    b58_encode_public_address(&recipient).unwrap(),
+                    Amount::new(42 * MOB, Mob::ID),
+                )],
+                Some(&vec![imported.id.clone()]),
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTH,
+            )
+            .unwrap();
+        assert_eq!(tx_proposal.input_txos.len(), 1);
+        assert_eq!(
+            TxoID::from(&tx_proposal.input_txos[0].tx_out).to_string(),
+            imported.id
+        );
+    }
 }
@@ -0,0 +1,603 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Service for syncing the spent state of view only accounts.
+//!
+//! A view only account cannot compute the key images of its txos, so it
+//! cannot tell when they are spent. The wallet holding the view only account
+//! creates a sync request listing its txos without key images, an offline
+//! wallet holding the spend key completes it with their key images, and the
+//! view only wallet imports the response.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountModel},
+        models::{Account, Txo},
+        transaction,
+        txo::TxoModel,
+        WalletDbError,
+    },
+    service::WalletService,
+    util::{
+        constants::VIEW_ONLY_SYNC_VERSION,
+        encoding_helpers::{hex_to_ristretto_public, ristretto_public_to_hex},
+    },
+};
+use displaydoc::Display;
+use mc_account_keys::AccountKey;
+use mc_common::logger::log;
+use mc_connection::{BlockchainConnection, UserTxConnection};
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPublic};
+use mc_fog_report_validation::FogPubkeyResolver;
+use mc_ledger_db::Ledger;
+use mc_transaction_core::{onetime_keys::recover_onetime_private_key, ring_signature::KeyImage};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// Errors for the View Only Sync Service.
+#[derive(Display, Debug)]
+#[allow(clippy::large_enum_variant)]
+pub enum ViewOnlySyncServiceError {
+    /// Error interacting with the database: {0}
+    Database(WalletDbError),
+
+    /// Diesel Error: {0}
+    Diesel(diesel::result::Error),
+
+    /// Error with LedgerDB: {0}
+    LedgerDB(mc_ledger_db::Error),
+
+    /// Error decoding from hex: {0}
+    HexDecode(hex::FromHexError),
+
+    /// mc_util_serial decode error: {0}
+    Decode(mc_util_serial::DecodeError),
+
+    /// Unsupported view only sync version: {0}
+    UnsupportedVersion(String),
+
+    /// Invalid view only sync request: {0}
+    InvalidSyncRequest(String),
+
+    /// Invalid view only sync response: {0}
+    InvalidSyncResponse(String),
+
+    /// Account {0} is not a view only account
+    AccountIsNotViewOnly(String),
+
+    /// Account {0} is view only, and cannot compute key images
+    AccountIsViewOnly(String),
+
+    /// Txo {0} was not sent to subaddress {1} of the account
+    SubaddressMismatch(String, String),
+}
+
+impl From<WalletDbError> for ViewOnlySyncServiceError {
+    fn from(src: WalletDbError) -> Self {
+        Self::Database(src)
+    }
+}
+
+impl From<diesel::result::Error> for ViewOnlySyncServiceError {
+    fn from(src: diesel::result::Error) -> Self {
+        Self::Diesel(src)
+    }
+}
+
+impl From<mc_ledger_db::Error> for ViewOnlySyncServiceError {
+    fn from(src: mc_ledger_db::Error) -> Self {
+        Self::LedgerDB(src)
+    }
+}
+
+impl From<hex::FromHexError> for ViewOnlySyncServiceError {
+    fn from(src: hex::FromHexError) -> Self {
+        Self::HexDecode(src)
+    }
+}
+
+impl From<mc_util_serial::DecodeError> for ViewOnlySyncServiceError {
+    fn from(src: mc_util_serial::DecodeError) -> Self {
+        Self::Decode(src)
+    }
+}
+
+/// The txos of a view only account which are missing key images.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct ViewOnlyAccountSyncRequest {
+    /// The version of the sync format.
+    pub version: String,
+
+    pub account_id: String,
+
+    pub txos: Vec<ViewOnlySyncTxo>,
+}
+
+/// A txo which is missing its key image.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ViewOnlySyncTxo {
+    pub txo_id: String,
+
+    /// The public key of the TxOut, as hex.
+    pub tx_out_public_key: String,
+
+    /// The target key of the TxOut, as hex.
+    pub target_key: String,
+
+    /// The subaddress the txo was sent to.
+    pub subaddress_index: String,
+}
+
+/// The key images of the txos in a sync request.
+#[derive(Deserialize, Serialize, Default, Debug, Clone, PartialEq)]
+pub struct ViewOnlyAccountSyncResponse {
+    /// The version of the sync format.
+    pub version: String,
+
+    pub account_id: String,
+
+    pub key_images: Vec<ViewOnlySyncKeyImage>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ViewOnlySyncKeyImage {
+    pub txo_id: String,
+
+    /// The serialized key image, as hex.
+    pub key_image: String,
+}
+
+/// The outcome of importing a sync response.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewOnlySyncResult {
+    /// The number of txos which now have key images.
+    pub num_txos_updated: usize,
+
+    /// The ids of the txos which the ledger shows as spent.
+    pub spent_txo_ids: Vec<String>,
+}
+
+/// Compute the key images for the txos in a sync request with the keys of the
+/// account.
+///
+/// This needs no ledger or wallet, so it can run wherever the spend key is
+/// kept.
+pub fn complete_view_only_account_sync_request(
+    request: &ViewOnlyAccountSyncRequest,
+    account_key: &AccountKey,
+) -> Result<ViewOnlyAccountSyncResponse, ViewOnlySyncServiceError> {
+    if request.version != VIEW_ONLY_SYNC_VERSION.to_string() {
+        return Err(ViewOnlySyncServiceError::UnsupportedVersion(
+            request.version.clone(),
+        ));
+    }
+    if request.account_id != AccountID::from(account_key).to_string() {
+        return Err(ViewOnlySyncServiceError::InvalidSyncRequest(format!(
+            "The request is for account {}, not this account",
+            request.account_id
+        )));
+    }
+
+    let key_images = request
+        .txos
+        .iter()
+        .map(|txo| {
+            let tx_out_public_key = hex_to_ristretto_public(&txo.tx_out_public_key)
+                .map_err(ViewOnlySyncServiceError::InvalidSyncRequest)?;
+            let target_key = hex_to_ristretto_public(&txo.target_key)
+                .map_err(ViewOnlySyncServiceError::InvalidSyncRequest)?;
+            let subaddress_index = txo.subaddress_index.parse::<u64>().map_err(|_| {
+                ViewOnlySyncServiceError::InvalidSyncRequest(format!(
+                    "Invalid subaddress index {}",
+                    txo.subaddress_index
+                ))
+            })?;
+
+            let onetime_private_key = recover_onetime_private_key(
+                &tx_out_public_key,
+                account_key.view_private_key(),
+                &account_key.subaddress_spend_private(subaddress_index),
+            );
+            if RistrettoPublic::from(&onetime_private_key) != target_key {
+                return Err(ViewOnlySyncServiceError::SubaddressMismatch(
+                    txo.txo_id.clone(),
+                    txo.subaddress_index.clone(),
+                ));
+            }
+
+            Ok(ViewOnlySyncKeyImage {
+                txo_id: txo.txo_id.clone(),
+                key_image: hex::encode(mc_util_serial::encode(&KeyImage::from(
+                    &onetime_private_key,
+                ))),
+            })
+        })
+        .collect::<Result<Vec<_>, ViewOnlySyncServiceError>>()?;
+
+    Ok(ViewOnlyAccountSyncResponse {
+        version: VIEW_ONLY_SYNC_VERSION.to_string(),
+        account_id: request.account_id.clone(),
+        key_images,
+    })
+}
+
+/// Describe an unverified txo in a sync request.
+fn sync_txo(txo: &Txo, subaddress_index: i64) -> Result<ViewOnlySyncTxo, ViewOnlySyncServiceError> {
+    let decode_key = |bytes: &[u8]| -> Result<String, ViewOnlySyncServiceError> {
+        let key: CompressedRistrettoPublic = mc_util_serial::decode(bytes)?;
+        let key = RistrettoPublic::try_from(&key).map_err(|e| {
+            ViewOnlySyncServiceError::InvalidSyncRequest(format!(
+                "Invalid key in txo {}: {:?}",
+                txo.id, e
+            ))
+        })?;
+        Ok(ristretto_public_to_hex(&key))
+    };
+
+    Ok(ViewOnlySyncTxo {
+        txo_id: txo.id.clone(),
+        tx_out_public_key: decode_key(&txo.public_key)?,
+        target_key: decode_key(&txo.target_key)?,
+        subaddress_index: subaddress_index.to_string(),
+    })
+}
+
+/// Trait defining the ways in which the wallet can sync view only accounts.
+pub trait ViewOnlySyncService {
+    /// List the txos of a view only account which are missing key images.
+    fn create_view_only_account_sync_request(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<ViewOnlyAccountSyncRequest, ViewOnlySyncServiceError>;
+
+    /// Complete a sync request for an account whose keys are in this wallet,
+    /// such as an offline wallet holding the spend key of a view only account.
+    fn complete_view_only_account_sync_request(
+        &self,
+        request: &ViewOnlyAccountSyncRequest,
+    ) -> Result<ViewOnlyAccountSyncResponse, ViewOnlySyncServiceError>;
+
+    /// Store the key images in a sync response, and mark the txos whose key
+    /// images are in the ledger as spent.
+    fn import_view_only_account_sync_response(
+        &self,
+        response: &ViewOnlyAccountSyncResponse,
+    ) -> Result<ViewOnlySyncResult, ViewOnlySyncServiceError>;
+}
+
+impl<T, FPR> ViewOnlySyncService for WalletService<T, FPR>
+where
+    T: BlockchainConnection + UserTxConnection + 'static,
+    FPR: FogPubkeyResolver + Send + Sync + 'static,
+{
+    fn create_view_only_account_sync_request(
+        &self,
+        account_id: &AccountID,
+    ) -> Result<ViewOnlyAccountSyncRequest, ViewOnlySyncServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(account_id, &conn)?;
+        if !account.view_only {
+            return Err(ViewOnlySyncServiceError::AccountIsNotViewOnly(
+                account_id.to_string(),
+            ));
+        }
+
+        let unverified_txos = Txo::list_unverified(
+            Some(&account_id.to_string()),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &conn,
+        )?;
+
+        // Orphaned txos have no known subaddress, so their key images cannot
+        // be computed until the subaddress is assigned.
+        let txos = unverified_txos
+            .iter()
+            .filter_map(|txo| {
+                txo.subaddress_index
+                    .map(|subaddress_index| sync_txo(txo, subaddress_index))
+            })
+            .collect::<Result<Vec<_>, ViewOnlySyncServiceError>>()?;
+
+        Ok(ViewOnlyAccountSyncRequest {
+            version: VIEW_ONLY_SYNC_VERSION.to_string(),
+            account_id: account_id.to_string(),
+            txos,
+        })
+    }
+
+    fn complete_view_only_account_sync_request(
+        &self,
+        request: &ViewOnlyAccountSyncRequest,
+    ) -> Result<ViewOnlyAccountSyncResponse, ViewOnlySyncServiceError> {
+        let conn = self.wallet_db.get_conn()?;
+        let account = Account::get(&AccountID(request.account_id.clone()), &conn)?;
+        if account.view_only {
+            return Err(ViewOnlySyncServiceError::AccountIsViewOnly(
+                request.account_id.clone(),
+            ));
+        }
+
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key)?;
+        complete_view_only_account_sync_request(request, &account_key)
+    }
+
+    fn import_view_only_account_sync_response(
+        &self,
+        response: &ViewOnlyAccountSyncResponse,
+    ) -> Result<ViewOnlySyncResult, ViewOnlySyncServiceError> {
+        if response.version != VIEW_ONLY_SYNC_VERSION.to_string() {
+            return Err(ViewOnlySyncServiceError::UnsupportedVersion(
+                response.version.clone(),
+            ));
+        }
+
+        let conn = self.wallet_db.get_conn()?;
+        transaction(&conn, || {
+            let account = Account::get(&AccountID(response.account_id.clone()), &conn)?;
+            if !account.view_only {
+                return Err(ViewOnlySyncServiceError::AccountIsNotViewOnly(
+                    response.account_id.clone(),
+                ));
+            }
+
+            let mut spent_txo_ids = Vec::new();
+            for entry in &response.key_images {
+                let txo = Txo::get(&entry.txo_id, &conn)?;
+                if txo.account_id.as_ref() != Some(&account.id) {
+                    return Err(ViewOnlySyncServiceError::InvalidSyncResponse(format!(
+                        "Txo {} does not belong to the account",
+                        entry.txo_id
+                    )));
+                }
+
+                let key_image: KeyImage = mc_util_serial::decode(&hex::decode(&entry.key_image)?)?;
+                let spent_block_index = self.ledger_db.check_key_image(&key_image)?;
+                Txo::update_key_image(&entry.txo_id, &key_image, spent_block_index, &conn)?;
+                if spent_block_index.is_some() {
+                    spent_txo_ids.push(entry.txo_id.clone());
+                }
+            }
+
+            log::info!(
+                self.request_logger(),
+                "Imported {} key images for view only account {}, {} spent",
+                response.key_images.len(),
+                response.account_id,
+                spent_txo_ids.len()
+            );
+
+            Ok(ViewOnlySyncResult {
+                num_txos_updated: response.key_images.len(),
+                spent_txo_ids,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::txo::TxoStatus,
+        service::account::AccountService,
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            setup_wallet_service_offline, MOB,
+        },
+        util::encoding_helpers::ristretto_to_hex,
+    };
+    use mc_account_keys::{PublicAddress, ViewAccountKey};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::rand_core::RngCore;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_view_only_account_sync_round_trip(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+
+        // The offline wallet holds the full account, and the online wallet holds
+        // its view only twin.
+        let offline_service = setup_wallet_service_offline(ledger_db.clone(), logger.clone());
+        let online_service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account = offline_service
+            .create_account(
+                Some("Cold Storage".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let account_id = AccountID(account.id.clone());
+        let account_key: AccountKey = mc_util_serial::decode(&account.account_key).unwrap();
+        let view_account_key = ViewAccountKey::from(&account_key);
+
+        let view_only_account = online_service
+            .import_view_only_account(
+                ristretto_to_hex(view_account_key.view_private_key()),
+                ristretto_public_to_hex(view_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        assert_eq!(view_only_account.id, account.id);
+
+        for _ in 0..2 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![account_key.default_subaddress()],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &offline_service.wallet_db, &account_id, &logger);
+
+        // Spend one of the txos, using the key image known to the full account.
+        let offline_conn = offline_service.wallet_db.get_conn().unwrap();
+        let full_txos = Txo::list_for_account(
+            &account_id.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            &offline_conn,
+        )
+        .unwrap();
+        assert_eq!(full_txos.len(), 2);
+        let spent_txo = &full_txos[0];
+        let spent_key_image: KeyImage =
+            mc_util_serial::decode(spent_txo.key_image.as_ref().unwrap()).unwrap();
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![AccountKey::random(&mut rng).default_subaddress()],
+            90 * MOB,
+            &vec![spent_key_image],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &online_service.wallet_db, &account_id, &logger);
+
+        // The view only account cannot tell that the txo was spent.
+        let online_conn = online_service.wallet_db.get_conn().unwrap();
+        assert_eq!(
+            Txo::get(&spent_txo.id, &online_conn)
+                .unwrap()
+                .status(&online_conn)
+                .unwrap(),
+            TxoStatus::Unverified
+        );
+
+        let sync_request = online_service
+            .create_view_only_account_sync_request(&account_id)
+            .unwrap();
+        assert_eq!(sync_request.version, VIEW_ONLY_SYNC_VERSION.to_string());
+        assert_eq!(sync_request.txos.len(), 2);
+
+        // The documents survive being carried between the wallets as JSON.
+        let sync_request: ViewOnlyAccountSyncRequest =
+            serde_json::from_str(&serde_json::to_string(&sync_request).unwrap()).unwrap();
+        let sync_response = offline_service
+            .complete_view_only_account_sync_request(&sync_request)
+            .unwrap();
+        assert_eq!(
+            sync_response,
+            complete_view_only_account_sync_request(&sync_request, &account_key).unwrap()
+        );
+        let sync_response: ViewOnlyAccountSyncResponse =
+            serde_json::from_str(&serde_json::to_string(&sync_response).unwrap()).unwrap();
+
+        let result = online_service
+            .import_view_only_account_sync_response(&sync_response)
+            .unwrap();
+        assert_eq!(result.num_txos_updated, 2);
+        assert_eq!(result.spent_txo_ids, vec![spent_txo.id.clone()]);
+
+        // The key images match the ones computed by the full account.
+        for full_txo in &full_txos {
+            let view_only_txo = Txo::get(&full_txo.id, &online_conn).unwrap();
+            assert_eq!(view_only_txo.key_image, full_txo.key_image);
+        }
+        assert_eq!(
+            Txo::get(&spent_txo.id, &online_conn)
+                .unwrap()
+                .status(&online_conn)
+                .unwrap(),
+            TxoStatus::Spent
+        );
+        assert_eq!(
+            Txo::get(&full_txos[1].id, &online_conn)
+                .unwrap()
+                .status(&online_conn)
+                .unwrap(),
+            TxoStatus::Unspent
+        );
+
+        // Nothing is left to sync.
+        let sync_request = online_service
+            .create_view_only_account_sync_request(&account_id)
+            .unwrap();
+        assert!(sync_request.txos.is_empty());
+    }
+
+    #[test_with_logger]
+    fn test_view_only_account_sync_rejects_invalid_documents(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let account_key = AccountKey::random(&mut rng);
+        let view_account_key = ViewAccountKey::from(&account_key);
+        let view_only_account = service
+            .import_view_only_account(
+                ristretto_to_hex(view_account_key.view_private_key()),
+                ristretto_public_to_hex(view_account_key.spend_public_key()),
+                None,
+                None,
+                None,
+                false,
+            )
+            .unwrap();
+        let account_id = AccountID(view_only_account.id);
+
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &account_id, &logger);
+
+        let sync_request = service
+            .create_view_only_account_sync_request(&account_id)
+            .unwrap();
+        assert_eq!(sync_request.txos.len(), 1);
+
+        // A view only account cannot complete its own request.
+        match service.complete_view_only_account_sync_request(&sync_request) {
+            Err(ViewOnlySyncServiceError::AccountIsViewOnly(_)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let mut wrong_version = sync_request.clone();
+        wrong_version.version = "0".to_string();
+        match complete_view_only_account_sync_request(&wrong_version, &account_key) {
+            Err(ViewOnlySyncServiceError::UnsupportedVersion(_)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        match complete_view_only_account_sync_request(&sync_request, &AccountKey::random(&mut rng))
+        {
+            Err(ViewOnlySyncServiceError::InvalidSyncRequest(_)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let mut wrong_subaddress = sync_request.clone();
+        wrong_subaddress.txos[0].subaddress_index = "1".to_string();
+        match complete_view_only_account_sync_request(&wrong_subaddress, &account_key) {
+            Err(ViewOnlySyncServiceError::SubaddressMismatch(_, _)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let mut sync_response =
+            complete_view_only_account_sync_request(&sync_request, &account_key).unwrap();
+        sync_response.version = "2".to_string();
+        match service.import_view_only_account_sync_response(&sync_response) {
+            Err(ViewOnlySyncServiceError::UnsupportedVersion(_)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -2,8 +2,31 @@
 
 //! The Wallet Service for interacting with the wallet.
 
-use crate::{db::WalletDb, service::sync::SyncThread};
-use mc_common::logger::{log, Logger};
+use crate::{
+    db::{models::Account, WalletDb},
+    fog_resolver::FogPubkeyCache,
+    service::{
+        address::AddressNetworkPolicy,
+        confirmation::ConfirmationChallenges,
+        health::PeerResponse,
+        job::JobManager,
+        ledger_scan::LedgerScanJob,
+        peer::{PeerHealth, PeerSelectionStrategy},
+        sync::{SyncConfig, SyncStats, SyncThread},
+        transaction_report::{TransactionReportThread, TransactionReporter},
+    },
+    util::{
+        build_guard::BuildGuard,
+        constants::{
+            BLOCK_TIMESTAMP_CACHE_SIZE, DEFAULT_FOG_PUBKEY_CACHE_TTL_SECONDS,
+            DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS, JOB_QUEUE_SIZE, JOB_WORKER_THREADS,
+            LEDGER_SCAN_CACHE_SIZE,
+        },
+        lru_cache::LruCache,
+        shutdown::ShutdownCoordinator,
+    },
+};
+use mc_common::logger::{log, o, Logger};
 use mc_connection::{
     BlockchainConnection, ConnectionManager as McConnectionManager, UserTxConnection,
 };
@@ -11,8 +34,107 @@ use mc_crypto_rand::rand_core::RngCore;
 use mc_fog_report_validation::FogPubkeyResolver;
 use mc_ledger_db::LedgerDB;
 use mc_ledger_sync::PollingNetworkState;
+use mc_transaction_core::TokenId;
 use mc_util_uri::FogUri;
-use std::sync::{atomic::AtomicUsize, Arc, RwLock};
+use mc_validator_connection::ValidatorConnection;
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{atomic::AtomicUsize, Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+thread_local! {
+    /// Logger tagged with the ID of the request currently being handled on this
+    /// thread, if any.
+    static REQUEST_LOGGER: RefCell<Option<Logger>> = RefCell::new(None);
+}
+
+/// Restores the previous request logger when a request scope ends, including
+/// when it ends by unwinding.
+struct RequestLoggerGuard(Option<Logger>);
+
+impl Drop for RequestLoggerGuard {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        REQUEST_LOGGER.with(|request_logger| *request_logger.borrow_mut() = previous);
+    }
+}
+
+/// Settings of the wallet service, beyond its databases and connections.
+pub struct WalletServiceConfig {
+    /// Whether the service should run in offline mode.
+    pub offline: bool,
+
+    /// The directory holding the ledger database's files, if known, for
+    /// reporting on and compacting them.
+    pub ledger_db_path: Option<PathBuf>,
+
+    /// Connection to the validator service, when the wallet syncs through
+    /// one, used to check transactions without submitting them.
+    pub validator_conn: Option<ValidatorConnection>,
+
+    /// Whether the ledger is opened read-only, and kept in sync by another
+    /// process.
+    pub ledger_read_only: bool,
+
+    /// How long an idempotency key is honored after its first use.
+    pub idempotency_key_ttl: Duration,
+
+    /// Serializes transaction builds per account and bounds the number of
+    /// builds in progress.
+    pub build_guard: BuildGuard,
+
+    /// Whether debug reports of transaction builds can be requested.
+    pub debug_build_report_enabled: bool,
+
+    /// Reports submitted transactions above the configured thresholds, if
+    /// reporting is configured.
+    pub transaction_reporter: Option<TransactionReporter>,
+
+    /// Configured dust thresholds, overriding the network fee as the smallest
+    /// output value of a token.
+    pub dust_threshold_overrides: BTreeMap<TokenId, u64>,
+
+    /// Number of blocks a received txo must be in the ledger for before it
+    /// can be spent, for accounts which do not set their own.
+    pub min_confirmations: u64,
+
+    /// Which recipient addresses are on the network the wallet is connected
+    /// to.
+    pub address_network_policy: AddressNetworkPolicy,
+
+    /// Batching and scheduling of the sync thread.
+    pub sync_config: SyncConfig,
+
+    /// How long a validated fog pubkey is reused by later builds.
+    pub fog_pubkey_cache_ttl: Duration,
+
+    /// How the peer a transaction is submitted to is chosen.
+    pub peer_selection_strategy: PeerSelectionStrategy,
+}
+
+impl Default for WalletServiceConfig {
+    fn default() -> Self {
+        Self {
+            offline: false,
+            ledger_db_path: None,
+            validator_conn: None,
+            ledger_read_only: false,
+            idempotency_key_ttl: Duration::from_secs(DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS),
+            build_guard: BuildGuard::default(),
+            debug_build_report_enabled: false,
+            transaction_reporter: None,
+            dust_threshold_overrides: BTreeMap::new(),
+            min_confirmations: 0,
+            address_network_policy: AddressNetworkPolicy::default(),
+            sync_config: SyncConfig::default(),
+            fog_pubkey_cache_ttl: Duration::from_secs(DEFAULT_FOG_PUBKEY_CACHE_TTL_SECONDS),
+            peer_selection_strategy: PeerSelectionStrategy::default(),
+        }
+    }
+}
 
 /// Service for interacting with the wallet
 ///
@@ -29,18 +151,30 @@ pub struct WalletService<
     /// Ledger database.
     pub ledger_db: LedgerDB,
 
+    /// The directory holding the ledger database's files, if known, for
+    /// reporting on and compacting them.
+    pub ledger_db_path: Option<PathBuf>,
+
     /// Peer manager for consensus validators to query for network height.
     pub peer_manager: McConnectionManager<T>,
 
     /// Representation of the current network state.
     pub network_state: Arc<RwLock<PollingNetworkState<T>>>,
 
+    /// Connection to the validator service, when the wallet syncs through
+    /// one, used to check transactions without submitting them.
+    pub validator_conn: Option<ValidatorConnection>,
+
     /// Fog resolver factory to obtain the public key of the ingest enclave from
     /// a fog address.
     pub fog_resolver_factory: Arc<dyn Fn(&[FogUri]) -> Result<FPR, String> + Send + Sync>,
 
+    /// Fog pubkeys validated by recent builds, reused by later builds to the
+    /// same fog services.
+    pub fog_pubkey_cache: Arc<FogPubkeyCache>,
+
     /// Background ledger sync thread.
-    _sync_thread: SyncThread,
+    sync_thread: Mutex<SyncThread>,
 
     /// Monotonically increasing counter. This is used for node round-robin
     /// selection.
@@ -49,6 +183,67 @@ pub struct WalletService<
     /// Whether the service should run in offline mode.
     pub offline: bool,
 
+    /// Whether the ledger is opened read-only, and kept in sync by another
+    /// process.
+    pub ledger_read_only: bool,
+
+    /// How long an idempotency key is honored after its first use.
+    pub idempotency_key_ttl: Duration,
+
+    /// Serializes transaction builds per account and bounds the number of
+    /// builds in progress.
+    pub build_guard: BuildGuard,
+
+    /// Whether debug reports of transaction builds can be requested.
+    pub debug_build_report_enabled: bool,
+
+    /// Reports submitted transactions above the configured thresholds, if
+    /// reporting is configured.
+    pub transaction_reporter: Option<Arc<TransactionReporter>>,
+
+    /// Background thread delivering queued transaction reports.
+    transaction_report_thread: Mutex<Option<TransactionReportThread>>,
+
+    /// Configured dust thresholds, overriding the network fee as the smallest
+    /// output value of a token.
+    pub dust_threshold_overrides: BTreeMap<TokenId, u64>,
+
+    /// Number of blocks a received txo must be in the ledger for before it
+    /// can be spent, for accounts which do not set their own.
+    pub min_confirmations: u64,
+
+    /// Which recipient addresses are on the network the wallet is connected
+    /// to.
+    pub address_network_policy: AddressNetworkPolicy,
+
+    /// Cache of block timestamps by block index. Blocks without a signature
+    /// have no timestamp and are cached as None.
+    pub block_timestamp_cache: Arc<Mutex<LruCache<u64, Option<u64>>>>,
+
+    /// Recent ledger scans for view keys which are not accounts, by id.
+    pub ledger_scans: Arc<Mutex<LruCache<String, Arc<LedgerScanJob>>>>,
+
+    /// Runs long operations, such as account resyncs, in the background.
+    pub jobs: Arc<JobManager>,
+
+    /// Sync throughput per account, recorded by the sync thread.
+    pub sync_stats: Arc<SyncStats>,
+
+    /// Challenges issued to confirm irreversible requests.
+    pub confirmation_challenges: ConfirmationChallenges,
+
+    /// The last response of a peer, or the validator, to the readiness
+    /// checks.
+    pub last_peer_response: Mutex<Option<PeerResponse>>,
+
+    /// Latency and outcome of the calls to each peer, and the peers pinned or
+    /// excluded by an admin.
+    pub peer_health: Arc<PeerHealth>,
+
+    /// Tracks the requests in flight, and turns new ones away once shutdown
+    /// has begun.
+    pub shutdown_coordinator: ShutdownCoordinator,
+
     /// Logger.
     pub logger: Logger,
 }
@@ -58,29 +253,149 @@ impl<
         FPR: FogPubkeyResolver + Send + Sync + 'static,
     > WalletService<T, FPR>
 {
-    #[allow(clippy::too_many_arguments)]
     pub fn new(
         wallet_db: WalletDb,
         ledger_db: LedgerDB,
         peer_manager: McConnectionManager<T>,
         network_state: Arc<RwLock<PollingNetworkState<T>>>,
         fog_resolver_factory: Arc<dyn Fn(&[FogUri]) -> Result<FPR, String> + Send + Sync>,
-        offline: bool,
+        config: WalletServiceConfig,
         logger: Logger,
     ) -> Self {
+        let WalletServiceConfig {
+            offline,
+            ledger_db_path,
+            validator_conn,
+            ledger_read_only,
+            idempotency_key_ttl,
+            build_guard,
+            debug_build_report_enabled,
+            transaction_reporter,
+            dust_threshold_overrides,
+            min_confirmations,
+            address_network_policy,
+            sync_config,
+            fog_pubkey_cache_ttl,
+            peer_selection_strategy,
+        } = config;
         log::info!(logger, "Starting Wallet TXO Sync Task Thread");
-        let sync_thread = SyncThread::start(ledger_db.clone(), wallet_db.clone(), logger.clone());
+        let sync_stats = Arc::new(SyncStats::default());
+        let sync_thread = SyncThread::start_with_config(
+            ledger_db.clone(),
+            wallet_db.clone(),
+            idempotency_key_ttl,
+            sync_config,
+            sync_stats.clone(),
+            logger.clone(),
+        );
+        let transaction_reporter = transaction_reporter.map(Arc::new);
+        let transaction_report_thread = transaction_reporter.as_ref().map(|reporter| {
+            log::info!(logger, "Starting Transaction Report Thread");
+            TransactionReportThread::start(wallet_db.clone(), reporter.clone(), logger.clone())
+        });
+        let jobs = JobManager::start(JOB_WORKER_THREADS, JOB_QUEUE_SIZE, logger.clone())
+            .expect("Failed starting job worker threads");
         let mut rng = rand::thread_rng();
         WalletService {
             wallet_db,
             ledger_db,
+            ledger_db_path,
             peer_manager,
             network_state,
+            validator_conn,
             fog_resolver_factory,
-            _sync_thread: sync_thread,
+            fog_pubkey_cache: Arc::new(FogPubkeyCache::new(fog_pubkey_cache_ttl)),
+            sync_thread: Mutex::new(sync_thread),
             submit_node_offset: Arc::new(AtomicUsize::new(rng.next_u64() as usize)),
             offline,
+            ledger_read_only,
+            idempotency_key_ttl,
+            build_guard,
+            debug_build_report_enabled,
+            transaction_reporter,
+            transaction_report_thread: Mutex::new(transaction_report_thread),
+            dust_threshold_overrides,
+            min_confirmations,
+            address_network_policy,
+            block_timestamp_cache: Arc::new(Mutex::new(LruCache::new(BLOCK_TIMESTAMP_CACHE_SIZE))),
+            ledger_scans: Arc::new(Mutex::new(LruCache::new(LEDGER_SCAN_CACHE_SIZE))),
+            jobs: Arc::new(jobs),
+            sync_stats,
+            confirmation_challenges: ConfirmationChallenges::default(),
+            last_peer_response: Mutex::new(None),
+            peer_health: Arc::new(PeerHealth::new(peer_selection_strategy)),
+            shutdown_coordinator: ShutdownCoordinator::default(),
             logger,
         }
     }
+
+    /// Runs `f` with every line logged through `request_logger` tagged with
+    /// the given request ID, so that service and builder logs can be
+    /// correlated with the JSON RPC request which triggered them.
+    pub fn with_request_id<R>(&self, request_id: &str, f: impl FnOnce() -> R) -> R {
+        let logger = self.logger.new(o!("request_id" => request_id.to_string()));
+        let previous = REQUEST_LOGGER.with(|request_logger| request_logger.replace(Some(logger)));
+        let _guard = RequestLoggerGuard(previous);
+        f()
+    }
+
+    /// The logger for the request currently being handled, or the service
+    /// logger when called outside of a request.
+    pub fn request_logger(&self) -> Logger {
+        REQUEST_LOGGER
+            .with(|request_logger| request_logger.borrow().clone())
+            .unwrap_or_else(|| self.logger.clone())
+    }
+
+    /// Shut the service down gracefully.
+    ///
+    /// New requests are turned away, and the requests in flight are given up
+    /// to the grace period to finish, which releases the inputs held by any
+    /// builds among them. Background jobs are cancelled, and the sync thread
+    /// finishes its current batch, so that every account's next block index
+    /// matches the txos written for it. Returns whether every request in
+    /// flight finished within the grace period.
+    pub fn shutdown(&self, grace_period: Duration) -> bool {
+        log::info!(
+            self.logger,
+            "Shutting down, waiting up to {:?} for {} requests in flight",
+            grace_period,
+            self.shutdown_coordinator.in_flight()
+        );
+        let drained = self.shutdown_coordinator.drain(grace_period);
+        if !drained {
+            log::warn!(
+                self.logger,
+                "{} requests were still in flight after the shutdown grace period",
+                self.shutdown_coordinator.in_flight()
+            );
+        }
+
+        self.jobs.cancel_all();
+        self.sync_thread
+            .lock()
+            .expect("sync thread mutex poisoned")
+            .stop();
+        if let Some(transaction_report_thread) = self
+            .transaction_report_thread
+            .lock()
+            .expect("transaction report thread mutex poisoned")
+            .as_mut()
+        {
+            transaction_report_thread.stop();
+        }
+
+        log::info!(self.logger, "Shutdown complete");
+        drained
+    }
+
+    /// The number of confirmations the account's received txos need before
+    /// they can be spent.
+    pub fn min_confirmations_for_account(&self, account: &Account) -> u64 {
+        account
+            .min_confirmations
+            .map_or(self.min_confirmations, |min_confirmations| {
+                min_confirmations as u64
+            })
+    }
 }
@@ -0,0 +1,233 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! An in-process wallet for integration tests.
+//!
+//! [TestHarness] runs a [WalletService] on a temporary wallet database, with a
+//! local ledger which the test adds blocks to. Transactions submitted through
+//! the wallet stay pending until the test mines a block, which includes every
+//! pending transaction, and the wallet's accounts are synced to each new block.
+//! JSON-RPC requests are handled in process, the same way the v2 API handles
+//! them.
+//!
+//! Other crates can use the harness by enabling the `test-harness` feature.
+
+use crate::{
+    db::{
+        account::{AccountID, AccountModel},
+        models::{Account, TransactionLog},
+        transaction_log::{TransactionLogFilter, TransactionLogModel, TransactionLogStatus},
+        Conn,
+    },
+    json_rpc::{
+        capability::CapabilitySet, json_rpc_request::JsonRPCRequest,
+        v2::api::wallet::wallet_api_request, wallet::ApiKeyGuard,
+    },
+    service::{account::AccountService, WalletService},
+    test_utils::{
+        add_block_with_tx_outs, create_test_txo_for_recipient, get_test_ledger,
+        manually_sync_account, setup_wallet_service, setup_wallet_service_with_wallet_db, MOB,
+    },
+};
+use mc_account_keys::{AccountKey, DEFAULT_SUBADDRESS_INDEX};
+use mc_common::logger::Logger;
+use mc_connection_test_utils::MockBlockchainConnection;
+use mc_fog_report_validation::MockFogPubkeyResolver;
+use mc_ledger_db::LedgerDB;
+use mc_transaction_core::{
+    ring_signature::KeyImage,
+    tokens::Mob,
+    tx::{Tx, TxOut},
+    Amount, Token, TokenId,
+};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::time::Duration;
+
+/// The number of blocks in the ledger when the harness starts.
+pub const INITIAL_BLOCK_COUNT: usize = 12;
+
+/// The wallet service the harness runs, with a mock network and fog.
+pub type TestWalletService =
+    WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver>;
+
+/// A wallet and the ledger it syncs from, driven by the test.
+pub struct TestHarness {
+    /// The wallet under test.
+    pub service: TestWalletService,
+
+    /// The ledger the wallet syncs from.
+    pub ledger_db: LedgerDB,
+
+    rng: StdRng,
+    logger: Logger,
+}
+
+impl TestHarness {
+    /// Start a wallet with no accounts, on a ledger with a few blocks paying
+    /// random recipients.
+    pub fn new(logger: Logger) -> Self {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let ledger_db = get_test_ledger(5, &[], INITIAL_BLOCK_COUNT, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        Self {
+            service,
+            ledger_db,
+            rng,
+            logger,
+        }
+    }
+
+    /// Create an account, synced to the last block.
+    pub fn create_account(&mut self, name: &str) -> Account {
+        let account = self
+            .service
+            .create_account(
+                Some(name.to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .expect("Could not create account");
+        self.sync(&AccountID(account.id))
+    }
+
+    /// The key of an account in the wallet.
+    pub fn account_key(&self, account_id: &AccountID) -> AccountKey {
+        let conn = self.conn();
+        let account = Account::get(account_id, &conn).expect("Could not get account");
+        mc_util_serial::decode(&account.account_key).expect("Could not decode account key")
+    }
+
+    /// Pay a value to the default subaddress of an account in a new block.
+    /// Returns the number of blocks in the ledger.
+    pub fn fund(&mut self, account_id: &AccountID, value: u64, token_id: TokenId) -> u64 {
+        let num_blocks = self.fund_without_sync(account_id, value, token_id);
+        self.sync_all();
+        num_blocks
+    }
+
+    /// Pay a value to the default subaddress of an account in a new block,
+    /// leaving the wallet's own sync thread to find it. Returns the number of
+    /// blocks in the ledger.
+    pub fn fund_without_sync(
+        &mut self,
+        account_id: &AccountID,
+        value: u64,
+        token_id: TokenId,
+    ) -> u64 {
+        let account_key = self.account_key(account_id);
+        let (tx_out, _) = create_test_txo_for_recipient(
+            &account_key,
+            DEFAULT_SUBADDRESS_INDEX,
+            Amount::new(value, token_id),
+            &mut self.rng,
+        );
+        let key_image = KeyImage::from(self.rng.next_u64());
+        add_block_with_tx_outs(&mut self.ledger_db, &[tx_out], &[key_image], &mut self.rng)
+    }
+
+    /// Mine a block with every transaction the wallet has submitted which has
+    /// not landed yet. Returns the number of blocks in the ledger.
+    pub fn mine_block(&mut self) -> u64 {
+        let conn = self.conn();
+        let pending = TransactionLog::list_all(
+            None,
+            None,
+            None,
+            &TransactionLogFilter {
+                status: Some(TransactionLogStatus::Pending),
+                ..Default::default()
+            },
+            &conn,
+        )
+        .expect("Could not list pending transaction logs");
+
+        let mut outputs = Vec::new();
+        let mut key_images = Vec::new();
+        for (transaction_log, _, _) in pending {
+            let tx: Tx = mc_util_serial::decode(&transaction_log.tx).expect("Could not decode tx");
+            key_images.extend(tx.key_images());
+            outputs.extend(tx.prefix.outputs);
+        }
+
+        // A block needs at least one output and key image, so an empty block
+        // pays someone outside the wallet.
+        if outputs.is_empty() {
+            let (tx_out, _) = create_test_txo_for_recipient(
+                &AccountKey::random(&mut self.rng),
+                DEFAULT_SUBADDRESS_INDEX,
+                Amount::new(MOB, Mob::ID),
+                &mut self.rng,
+            );
+            outputs.push(tx_out);
+            key_images.push(KeyImage::from(self.rng.next_u64()));
+        }
+
+        self.add_block(&outputs, &key_images)
+    }
+
+    /// Handle a v2 JSON-RPC request, as the wallet's API would, and return the
+    /// response. A failed request returns its error in the response.
+    pub fn rpc(&self, method: &str, params: serde_json::Value) -> serde_json::Value {
+        let request = JsonRPCRequest {
+            method: method.to_string(),
+            params: Some(params),
+            jsonrpc: "2.0".to_string(),
+            id: serde_json::Value::from(1),
+        };
+        let response = wallet_api_request(
+            &self.service,
+            &ApiKeyGuard {
+                secrets_authorized: true,
+                capabilities: CapabilitySet::all(),
+                account_ids: None,
+            },
+            "test-harness",
+            request,
+        );
+        serde_json::to_value(response).expect("Could not serialize response")
+    }
+
+    /// Shut the wallet down, and start it again on the same wallet database,
+    /// as restarting the process would.
+    pub fn restart(&mut self) {
+        self.service.shutdown(Duration::from_secs(0));
+        self.service = setup_wallet_service_with_wallet_db(
+            self.ledger_db.clone(),
+            self.service.wallet_db.clone(),
+            self.logger.clone(),
+        );
+    }
+
+    /// Sync an account to the last block.
+    pub fn sync(&self, account_id: &AccountID) -> Account {
+        manually_sync_account(
+            &self.ledger_db,
+            &self.service.wallet_db,
+            account_id,
+            &self.logger,
+        )
+    }
+
+    /// Sync every account in the wallet to the last block.
+    pub fn sync_all(&self) {
+        let conn = self.conn();
+        for account in Account::list_all(&conn, None, None).expect("Could not list accounts") {
+            self.sync(&AccountID(account.id));
+        }
+    }
+
+    fn conn(&self) -> Conn {
+        self.service
+            .wallet_db
+            .get_conn()
+            .expect("Could not get conn")
+    }
+
+    fn add_block(&mut self, outputs: &[TxOut], key_images: &[KeyImage]) -> u64 {
+        let num_blocks =
+            add_block_with_tx_outs(&mut self.ledger_db, outputs, key_images, &mut self.rng);
+        self.sync_all();
+        num_blocks
+    }
+}
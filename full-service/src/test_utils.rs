@@ -1,17 +1,22 @@
 // Copyright (c) 2020-2021 MobileCoin Inc.
-#[cfg(test)]
+
+//! Utilities for testing the wallet: temporary wallet databases, test ledgers,
+//! and accounts seeded with txos. These are public with the `test-harness`
+//! feature, along with [crate::test_harness].
+
 use crate::{
     db::{
         account::{AccountID, AccountModel},
         models::{Account, TransactionLog, Txo},
         transaction_log::TransactionLogModel,
         txo::TxoModel,
-        WalletDb, WalletDbError,
+        ConnectionOptions, WalletDb, WalletDbError,
     },
     error::SyncError,
     service::{
         sync::sync_account, transaction::TransactionMemo,
-        transaction_builder::WalletTransactionBuilder,
+        transaction_builder::WalletTransactionBuilder, transaction_report::TransactionReporter,
+        WalletServiceConfig,
     },
     WalletService,
 };
@@ -62,6 +67,7 @@ pub const MOB: u64 = 1_000_000_000_000;
 /// The amount each recipient gets in the test ledger.
 pub const DEFAULT_PER_RECIPIENT_AMOUNT: u64 = 5_000 * MOB;
 
+/// A wallet database in a temporary file, with the migrations run.
 pub struct WalletDbTestContext {
     base_url: String,
     pub db_name: String,
@@ -97,10 +103,18 @@ impl Default for WalletDbTestContext {
 
 impl WalletDbTestContext {
     pub fn get_db_instance(&self, _logger: Logger) -> WalletDb {
+        self.get_db_instance_with_options(ConnectionOptions::default())
+    }
+
+    pub fn get_db_instance_with_options(&self, options: ConnectionOptions) -> WalletDb {
         // Note: Setting db_connections too high results in IO Error: Too many open
         // files.
-        WalletDb::new_from_url(&format!("{}/{}", self.base_url, self.db_name), 7)
-            .expect("failed creating new SqlRecoveryDb")
+        WalletDb::new_from_url_with_options(
+            &format!("{}/{}", self.base_url, self.db_name),
+            7,
+            options,
+        )
+        .expect("failed creating new SqlRecoveryDb")
     }
 }
 
@@ -377,7 +391,8 @@ pub fn add_block_with_db_txos(
     add_block_with_tx_outs(ledger_db, &outputs, key_images, rng)
 }
 
-// Sync account to most recent block
+/// Sync an account to the most recent block, retrying while the database is
+/// locked.
 pub fn manually_sync_account(
     ledger_db: &LedgerDB,
     wallet_db: &WalletDb,
@@ -500,6 +515,7 @@ pub fn create_test_received_txo(
         Some(key_image),
         amount,
         received_block_index,
+        None,
         &AccountID::from(account_key).to_string(),
         &wallet_db.get_conn().unwrap(),
     )
@@ -547,7 +563,7 @@ pub fn create_test_minted_and_change_txos(
     .unwrap()
 }
 
-// Seed a local account with some Txos in the ledger
+/// Create a random account, and add a block paying it each of the seed values.
 pub fn random_account_with_seed_values(
     wallet_db: &WalletDb,
     mut ledger_db: &mut LedgerDB,
@@ -654,29 +670,120 @@ pub fn get_resolver_factory(
     Ok(fog_pubkey_resolver_factory)
 }
 
+/// Create a wallet service on a new wallet database, with a mock network and
+/// fog.
 pub fn setup_wallet_service(
     ledger_db: LedgerDB,
     logger: Logger,
 ) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
-    setup_wallet_service_impl(ledger_db, logger, false)
+    setup_wallet_service_impl(
+        ledger_db,
+        new_wallet_db(&logger),
+        logger,
+        false,
+        false,
+        None,
+        BTreeMap::new(),
+    )
 }
 
 pub fn setup_wallet_service_offline(
     ledger_db: LedgerDB,
     logger: Logger,
 ) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
-    setup_wallet_service_impl(ledger_db, logger, true)
+    setup_wallet_service_impl(
+        ledger_db,
+        new_wallet_db(&logger),
+        logger,
+        true,
+        false,
+        None,
+        BTreeMap::new(),
+    )
+}
+
+pub fn setup_wallet_service_with_reporter(
+    ledger_db: LedgerDB,
+    transaction_reporter: Option<TransactionReporter>,
+    logger: Logger,
+) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
+    setup_wallet_service_impl(
+        ledger_db,
+        new_wallet_db(&logger),
+        logger,
+        false,
+        false,
+        transaction_reporter,
+        BTreeMap::new(),
+    )
+}
+
+pub fn setup_wallet_service_with_dust_thresholds(
+    ledger_db: LedgerDB,
+    dust_threshold_overrides: BTreeMap<TokenId, u64>,
+    logger: Logger,
+) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
+    setup_wallet_service_impl(
+        ledger_db,
+        new_wallet_db(&logger),
+        logger,
+        false,
+        false,
+        None,
+        dust_threshold_overrides,
+    )
+}
+
+/// Create a wallet service on a ledger which it opened read-only, and which is
+/// kept in sync through another handle.
+pub fn setup_wallet_service_read_only(
+    ledger_db: LedgerDB,
+    logger: Logger,
+) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
+    setup_wallet_service_impl(
+        ledger_db,
+        new_wallet_db(&logger),
+        logger,
+        false,
+        true,
+        None,
+        BTreeMap::new(),
+    )
+}
+
+/// Create a wallet service on an existing wallet database, as a wallet
+/// restarting would.
+pub fn setup_wallet_service_with_wallet_db(
+    ledger_db: LedgerDB,
+    wallet_db: WalletDb,
+    logger: Logger,
+) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
+    setup_wallet_service_impl(
+        ledger_db,
+        wallet_db,
+        logger,
+        false,
+        false,
+        None,
+        BTreeMap::new(),
+    )
+}
+
+fn new_wallet_db(logger: &Logger) -> WalletDb {
+    WalletDbTestContext::default().get_db_instance(logger.clone())
 }
 
 fn setup_wallet_service_impl(
     ledger_db: LedgerDB,
+    wallet_db: WalletDb,
     logger: Logger,
     offline: bool,
+    ledger_read_only: bool,
+    transaction_reporter: Option<TransactionReporter>,
+    dust_threshold_overrides: BTreeMap<TokenId, u64>,
 ) -> WalletService<MockBlockchainConnection<LedgerDB>, MockFogPubkeyResolver> {
     let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
 
-    let db_test_context = WalletDbTestContext::default();
-    let wallet_db = db_test_context.get_db_instance(logger.clone());
     let (peer_manager, network_state) =
         setup_peer_manager_and_network_state(ledger_db.clone(), logger.clone(), offline);
 
@@ -686,7 +793,13 @@ fn setup_wallet_service_impl(
         peer_manager,
         network_state,
         get_resolver_factory(&mut rng).unwrap(),
-        offline,
+        WalletServiceConfig {
+            offline,
+            ledger_read_only,
+            transaction_reporter,
+            dust_threshold_overrides,
+            ..Default::default()
+        },
         logger,
     )
 }
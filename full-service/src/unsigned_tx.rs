@@ -4,7 +4,7 @@ use mc_crypto_ring_signature_signer::NoKeysRingSigner;
 
 use mc_transaction_core::{
     get_tx_out_shared_secret,
-    onetime_keys::recover_onetime_private_key,
+    onetime_keys::{recover_onetime_private_key, recover_public_subaddress_spend_key},
     ring_signature::{KeyImage, Scalar},
     tx::{TxIn, TxOut},
     Amount, BlockVersion, TokenId,
@@ -12,7 +12,10 @@ use mc_transaction_core::{
 use mc_transaction_std::{InputCredentials, ReservedSubaddresses, TransactionBuilder};
 use rand::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
-use std::{collections::BTreeMap, convert::TryFrom};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 
 use crate::{
     error::WalletTransactionBuilderError,
@@ -21,11 +24,18 @@ use crate::{
         models::tx_proposal::{InputTxo, OutputTxo, TxProposal},
         transaction::TransactionMemo,
     },
-    util::b58::b58_decode_public_address,
+    util::{b58::b58_decode_public_address, constants::UNSIGNED_TX_SCHEMA_VERSION},
 };
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct UnsignedTx {
+    /// The schema version of the wallet which built the transaction. Signers
+    /// refuse versions newer than their own, whose fields they may not
+    /// understand. Transactions from before versions were recorded have
+    /// version 0.
+    #[serde(default)]
+    pub schema_version: u64,
+
     /// The fully constructed input rings
     pub inputs_and_real_indices_and_subaddress_indices: Vec<(TxIn, u64, u64)>,
 
@@ -47,14 +57,46 @@ pub struct UnsignedTx {
 
     /// Memo field that indicates what type of transaction this is.
     pub memo: TransactionMemo,
+
+    /// The number of outputs the change of each token is divided between,
+    /// keyed by token id. Tokens which are not listed get a single change
+    /// output, and a token listed with zero outputs gets none if its change
+    /// is zero.
+    #[serde(default)]
+    pub change_outputs_per_token: BTreeMap<u64, u64>,
+
+    /// The subaddress change is returned to, if not the account's change
+    /// subaddress.
+    #[serde(default)]
+    pub change_subaddress_index: Option<u64>,
 }
 
 impl UnsignedTx {
+    /// The subaddress indices the inputs are spent from.
+    pub fn input_subaddress_indices(&self) -> BTreeSet<u64> {
+        self.inputs_and_real_indices_and_subaddress_indices
+            .iter()
+            .map(|(_, _, subaddress_index)| *subaddress_index)
+            .collect()
+    }
+
+    /// Sign the transaction. The spend key of each input is derived from the
+    /// subaddress index carried with it, so inputs can be at any subaddress of
+    /// the account, whether or not it is assigned in this wallet.
     pub fn sign(
         self,
         account_key: &AccountKey,
         fog_resolver: FullServiceFogResolver,
     ) -> Result<TxProposal, WalletTransactionBuilderError> {
+        if self.schema_version > UNSIGNED_TX_SCHEMA_VERSION {
+            return Err(
+                WalletTransactionBuilderError::UnsupportedUnsignedTxSchemaVersion {
+                    version: self.schema_version,
+                    supported: UNSIGNED_TX_SCHEMA_VERSION,
+                },
+            );
+        }
+
         let mut rng = rand::thread_rng();
 
         // Create transaction builder.
@@ -77,11 +119,28 @@ impl UnsignedTx {
         {
             let tx_out = &tx_in.ring[real_index as usize];
             let tx_public_key = RistrettoPublic::try_from(&tx_out.public_key)?;
+            let tx_out_target_key = RistrettoPublic::try_from(&tx_out.target_key)?;
+
+            // Check that the input really is at the subaddress index it came
+            // with before signing with that subaddress's spend key.
+            let subaddress_spend_private = account_key.subaddress_spend_private(subaddress_index);
+            let tx_out_subaddress_spend_public_key = recover_public_subaddress_spend_key(
+                account_key.view_private_key(),
+                &tx_out_target_key,
+                &tx_public_key,
+            );
+            if tx_out_subaddress_spend_public_key
+                != RistrettoPublic::from(&subaddress_spend_private)
+            {
+                return Err(WalletTransactionBuilderError::InputSubaddressIndexMismatch(
+                    subaddress_index,
+                ));
+            }
 
             let onetime_private_key = recover_onetime_private_key(
                 &tx_public_key,
                 account_key.view_private_key(),
-                &account_key.subaddress_spend_private(subaddress_index),
+                &subaddress_spend_private,
             );
 
             let key_image = KeyImage::from(&onetime_private_key);
@@ -134,20 +193,28 @@ impl UnsignedTx {
                 .or_insert(amount);
         }
 
-        let change_txos = input_total_per_token
-            .into_iter()
-            .map(|(token_id, input_total)| {
-                let output_total = output_total_per_token.get(&token_id).unwrap_or(&0);
-                add_change_output(
+        let mut change_txos = Vec::new();
+        for (token_id, input_total) in input_total_per_token {
+            let output_total = output_total_per_token.get(&token_id).unwrap_or(&0);
+            let num_pieces = self
+                .change_outputs_per_token
+                .get(&*token_id)
+                .copied()
+                .unwrap_or(1);
+            let change_total = input_total - output_total;
+            if num_pieces == 0 && change_total == 0 {
+                continue;
+            }
+            for change_value in split_value(change_total, num_pieces) {
+                change_txos.push(add_change_output(
                     account_key,
-                    input_total,
-                    *output_total,
-                    token_id,
+                    self.change_subaddress_index,
+                    Amount::new(change_value, token_id),
                     &mut transaction_builder,
                     &mut rng,
-                )
-            })
-            .collect::<Result<Vec<_>, WalletTransactionBuilderError>>()?;
+                )?);
+            }
+        }
 
         let tx = transaction_builder.build(&NoKeysRingSigner {}, &mut rng)?;
 
@@ -156,6 +223,7 @@ impl UnsignedTx {
             input_txos,
             payload_txos,
             change_txos,
+            block_version: Some(self.block_version),
         })
     }
 }
@@ -190,18 +258,27 @@ fn add_payload_outputs<RNG: CryptoRng + RngCore>(
     Ok(outputs)
 }
 
+/// Divide a value into the given number of roughly equal pieces, with any
+/// remainder added to the first.
+fn split_value(value: u64, num_pieces: u64) -> Vec<u64> {
+    let num_pieces = num_pieces.max(1);
+    let piece = value / num_pieces;
+    let mut pieces = vec![piece; num_pieces as usize];
+    pieces[0] += value % num_pieces;
+    pieces
+}
+
 fn add_change_output<RNG: CryptoRng + RngCore>(
     account_key: &AccountKey,
-    total_input_value: u64,
-    total_output_value: u64,
-    token_id: TokenId,
+    change_subaddress_index: Option<u64>,
+    change_amount: Amount,
     transaction_builder: &mut TransactionBuilder<FullServiceFogResolver>,
     rng: &mut RNG,
 ) -> Result<OutputTxo, WalletTransactionBuilderError> {
-    let change_value = total_input_value - total_output_value;
-    let change_amount = Amount::new(change_value, token_id);
-
-    let reserved_subaddresses = ReservedSubaddresses::from(account_key);
+    let mut reserved_subaddresses = ReservedSubaddresses::from(account_key);
+    if let Some(change_subaddress_index) = change_subaddress_index {
+        reserved_subaddresses.change_subaddress = account_key.subaddress(change_subaddress_index);
+    }
     let tx_out_context =
         transaction_builder.add_change_output(change_amount, &reserved_subaddresses, rng)?;
 
@@ -0,0 +1,282 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Attestations that a public address belongs to the wallet, compact enough
+//! to share as a QR code.
+//!
+//! An attestation is the URL safe base64 of a payload followed by a Schnorrkel
+//! signature of it, made with the spend key of the subaddress in a context
+//! used for nothing else. The payload is, in order:
+//!
+//! * the version, one byte
+//! * the timestamp, in seconds since the Unix epoch, eight bytes big endian
+//! * the length of the nonce, one byte, and the nonce
+//! * the length of the b58 address, two bytes big endian, and the address
+//!
+//! Verifying only needs the attestation itself, so that it can be done without
+//! a wallet.
+
+use crate::util::b58::{b58_decode_public_address, B58Error};
+use displaydoc::Display;
+use mc_crypto_keys::{RistrettoPrivate, RistrettoSignature};
+use std::convert::TryFrom;
+
+/// Version of the attestation payload.
+pub const ADDRESS_ATTESTATION_VERSION: u8 = 1;
+
+/// Signing context of attestations, which keeps their signatures from being
+/// valid for any other message.
+pub const ADDRESS_ATTESTATION_CONTEXT: &[u8] = b"full-service-address-attestation";
+
+/// Longest nonce an attestation can carry, in bytes.
+pub const MAX_ADDRESS_ATTESTATION_NONCE_BYTES: usize = 64;
+
+const SIGNATURE_LEN: usize = 64;
+
+/// Errors creating or verifying an address attestation.
+#[derive(Display, Debug)]
+pub enum AddressAttestationError {
+    /// The nonce is longer than {0} bytes
+    NonceTooLong(usize),
+
+    /// Invalid base64: {0}
+    Base64(base64::DecodeError),
+
+    /// The attestation is malformed: {0}
+    Malformed(String),
+
+    /// Unsupported attestation version: {0}
+    UnsupportedVersion(u8),
+
+    /// Invalid public address: {0}
+    InvalidPublicAddress(B58Error),
+
+    /// The signature does not match the address in the attestation
+    InvalidSignature,
+}
+
+impl From<base64::DecodeError> for AddressAttestationError {
+    fn from(src: base64::DecodeError) -> Self {
+        Self::Base64(src)
+    }
+}
+
+impl From<B58Error> for AddressAttestationError {
+    fn from(src: B58Error) -> Self {
+        Self::InvalidPublicAddress(src)
+    }
+}
+
+/// The contents of an attestation whose signature is valid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AddressAttestation {
+    pub public_address_b58: String,
+    pub nonce: String,
+
+    /// When the attestation was made, in seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl AddressAttestation {
+    /// Whether the attestation was made more than max_age_seconds before now,
+    /// or claims to be from more than max_age_seconds after it, so that it may
+    /// be replayed.
+    pub fn is_stale(&self, now: u64, max_age_seconds: u64) -> bool {
+        if self.timestamp > now {
+            self.timestamp - now > max_age_seconds
+        } else {
+            now - self.timestamp > max_age_seconds
+        }
+    }
+
+    fn payload(&self) -> Result<Vec<u8>, AddressAttestationError> {
+        let nonce = self.nonce.as_bytes();
+        if nonce.len() > MAX_ADDRESS_ATTESTATION_NONCE_BYTES {
+            return Err(AddressAttestationError::NonceTooLong(
+                MAX_ADDRESS_ATTESTATION_NONCE_BYTES,
+            ));
+        }
+        let address = self.public_address_b58.as_bytes();
+        let address_len = u16::try_from(address.len()).map_err(|_| {
+            AddressAttestationError::Malformed("public address is too long".to_string())
+        })?;
+
+        let mut payload = vec![ADDRESS_ATTESTATION_VERSION];
+        payload.extend_from_slice(&self.timestamp.to_be_bytes());
+        payload.push(nonce.len() as u8);
+        payload.extend_from_slice(nonce);
+        payload.extend_from_slice(&address_len.to_be_bytes());
+        payload.extend_from_slice(address);
+        Ok(payload)
+    }
+
+    fn from_payload(payload: &[u8]) -> Result<Self, AddressAttestationError> {
+        let mut reader = PayloadReader(payload);
+        let version = reader.take(1)?[0];
+        if version != ADDRESS_ATTESTATION_VERSION {
+            return Err(AddressAttestationError::UnsupportedVersion(version));
+        }
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(reader.take(8)?);
+        let nonce_len = reader.take(1)?[0] as usize;
+        let nonce = reader.take_string(nonce_len)?;
+        let address_len = reader.take(2)?;
+        let address_len = u16::from_be_bytes([address_len[0], address_len[1]]) as usize;
+        let public_address_b58 = reader.take_string(address_len)?;
+        if !reader.0.is_empty() {
+            return Err(AddressAttestationError::Malformed(
+                "unexpected bytes after the public address".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            public_address_b58,
+            nonce,
+            timestamp: u64::from_be_bytes(timestamp),
+        })
+    }
+}
+
+struct PayloadReader<'a>(&'a [u8]);
+
+impl<'a> PayloadReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], AddressAttestationError> {
+        if self.0.len() < len {
+            return Err(AddressAttestationError::Malformed(
+                "the payload is truncated".to_string(),
+            ));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn take_string(&mut self, len: usize) -> Result<String, AddressAttestationError> {
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|e| AddressAttestationError::Malformed(e.to_string()))
+    }
+}
+
+/// Signs an attestation with the spend private key of the subaddress at its
+/// public address.
+pub fn create_address_attestation(
+    subaddress_spend_private: &RistrettoPrivate,
+    attestation: &AddressAttestation,
+) -> Result<String, AddressAttestationError> {
+    let mut blob = attestation.payload()?;
+    let signature = subaddress_spend_private.sign_schnorrkel(ADDRESS_ATTESTATION_CONTEXT, &blob);
+    blob.extend_from_slice(signature.as_ref());
+    Ok(base64::encode_config(blob, base64::URL_SAFE_NO_PAD))
+}
+
+/// Checks the signature of an attestation against the spend public key of the
+/// address it carries, returning its contents if it is valid. Whether it is
+/// stale is left to the caller, which knows the time.
+pub fn verify_address_attestation(
+    attestation: &str,
+) -> Result<AddressAttestation, AddressAttestationError> {
+    let blob = base64::decode_config(attestation.trim(), base64::URL_SAFE_NO_PAD)?;
+    if blob.len() < SIGNATURE_LEN {
+        return Err(AddressAttestationError::Malformed(
+            "the signature is truncated".to_string(),
+        ));
+    }
+    let (payload, signature) = blob.split_at(blob.len() - SIGNATURE_LEN);
+    let attestation = AddressAttestation::from_payload(payload)?;
+
+    let public_address = b58_decode_public_address(&attestation.public_address_b58)?;
+    let signature = RistrettoSignature::try_from(signature)
+        .map_err(|_| AddressAttestationError::InvalidSignature)?;
+    public_address
+        .spend_public_key()
+        .verify_schnorrkel(ADDRESS_ATTESTATION_CONTEXT, payload, &signature)
+        .map_err(|_| AddressAttestationError::InvalidSignature)?;
+
+    Ok(attestation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::b58::b58_encode_public_address;
+    use mc_account_keys::AccountKey;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn attestation_for(account_key: &AccountKey, index: u64) -> AddressAttestation {
+        AddressAttestation {
+            public_address_b58: b58_encode_public_address(&account_key.subaddress(index)).unwrap(),
+            nonce: "ticket-4812".to_string(),
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        let attestation = attestation_for(&account_key, 3);
+
+        let blob =
+            create_address_attestation(&account_key.subaddress_spend_private(3), &attestation)
+                .unwrap();
+        assert!(blob
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(verify_address_attestation(&blob).unwrap(), attestation);
+
+        assert!(!attestation.is_stale(attestation.timestamp + 60, 600));
+        assert!(attestation.is_stale(attestation.timestamp + 601, 600));
+        assert!(attestation.is_stale(attestation.timestamp - 601, 600));
+    }
+
+    #[test]
+    fn test_rejects_swapped_address() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        let attestation = attestation_for(&account_key, 3);
+        let blob =
+            create_address_attestation(&account_key.subaddress_spend_private(3), &attestation)
+                .unwrap();
+
+        // Put another address in the signed payload, keeping the signature.
+        let other = attestation_for(&AccountKey::random(&mut rng), 3);
+        let mut swapped = other.payload().unwrap();
+        let decoded = base64::decode_config(&blob, base64::URL_SAFE_NO_PAD).unwrap();
+        swapped.extend_from_slice(&decoded[decoded.len() - SIGNATURE_LEN..]);
+        let swapped = base64::encode_config(swapped, base64::URL_SAFE_NO_PAD);
+        assert!(matches!(
+            verify_address_attestation(&swapped),
+            Err(AddressAttestationError::InvalidSignature)
+        ));
+
+        // Signing with the key of another subaddress does not attest to this
+        // one.
+        let blob =
+            create_address_attestation(&account_key.subaddress_spend_private(4), &attestation)
+                .unwrap();
+        assert!(matches!(
+            verify_address_attestation(&blob),
+            Err(AddressAttestationError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_long_nonce_and_malformed_blobs() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        let mut attestation = attestation_for(&account_key, 0);
+        attestation.nonce = "n".repeat(MAX_ADDRESS_ATTESTATION_NONCE_BYTES + 1);
+        assert!(matches!(
+            create_address_attestation(&account_key.subaddress_spend_private(0), &attestation),
+            Err(AddressAttestationError::NonceTooLong(_))
+        ));
+
+        assert!(matches!(
+            verify_address_attestation("AQID"),
+            Err(AddressAttestationError::Malformed(_))
+        ));
+        assert!(matches!(
+            verify_address_attestation("not base64!"),
+            Err(AddressAttestationError::Base64(_))
+        ));
+    }
+}
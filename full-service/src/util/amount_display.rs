@@ -0,0 +1,111 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Human readable display of token amounts.
+//!
+//! Values are formatted from their integer representation with string
+//! arithmetic only, so that no precision is lost to floating point and the
+//! output does not depend on the locale.
+
+use mc_transaction_core::TokenId;
+
+/// Display metadata for a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub token_id: u64,
+    pub symbol: &'static str,
+    pub decimals: u32,
+}
+
+/// The tokens the wallet knows how to display.
+pub const KNOWN_TOKENS: &[TokenInfo] = &[
+    TokenInfo {
+        token_id: 0,
+        symbol: "MOB",
+        decimals: 12,
+    },
+    TokenInfo {
+        token_id: 1,
+        symbol: "eUSD",
+        decimals: 6,
+    },
+];
+
+/// The display metadata for a token, if it is known.
+pub fn token_info(token_id: TokenId) -> Option<&'static TokenInfo> {
+    KNOWN_TOKENS.iter().find(|info| info.token_id == *token_id)
+}
+
+/// Format a value in the smallest unit of a token as a decimal number of
+/// display units, with exactly `decimals` digits after the point.
+///
+/// For example, 1_500_000 with 6 decimals is "1.500000". A token with no
+/// decimals is formatted without a point.
+pub fn format_fixed_point(value: u64, decimals: u32) -> String {
+    let digits = value.to_string();
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits;
+    }
+
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    format!("{}.{}", whole, fraction)
+}
+
+/// The value of an amount in display units, and the symbol of its token, if
+/// the token is known.
+pub fn display_amount(value: u64, token_id: TokenId) -> Option<(String, &'static str)> {
+    token_info(token_id).map(|info| (format_fixed_point(value, info.decimals), info.symbol))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_fixed_point() {
+        // Zero keeps its full precision.
+        assert_eq!(format_fixed_point(0, 12), "0.000000000000");
+        assert_eq!(format_fixed_point(0, 6), "0.000000");
+        assert_eq!(format_fixed_point(0, 0), "0");
+
+        // Values smaller than one display unit.
+        assert_eq!(format_fixed_point(1, 12), "0.000000000001");
+        assert_eq!(format_fixed_point(999_999, 6), "0.999999");
+        assert_eq!(format_fixed_point(400_000_000, 12), "0.000400000000");
+
+        // Exactly one display unit, and more.
+        assert_eq!(format_fixed_point(1_000_000_000_000, 12), "1.000000000000");
+        assert_eq!(format_fixed_point(1_500_000, 6), "1.500000");
+        assert_eq!(format_fixed_point(1_234_567_890_123, 12), "1.234567890123");
+
+        // Tokens without decimals.
+        assert_eq!(format_fixed_point(1, 0), "1");
+        assert_eq!(format_fixed_point(42, 0), "42");
+
+        // The largest value, which would lose precision as a float.
+        assert_eq!(format_fixed_point(u64::MAX, 0), "18446744073709551615");
+        assert_eq!(format_fixed_point(u64::MAX, 6), "18446744073709.551615");
+        assert_eq!(format_fixed_point(u64::MAX, 12), "18446744.073709551615");
+
+        // More decimals than digits.
+        assert_eq!(format_fixed_point(u64::MAX, 20), "0.18446744073709551615");
+        assert_eq!(
+            format_fixed_point(u64::MAX, 24),
+            "0.000018446744073709551615"
+        );
+    }
+
+    #[test]
+    fn test_display_amount() {
+        assert_eq!(
+            display_amount(1_000_000_000_000, TokenId::from(0)),
+            Some(("1.000000000000".to_string(), "MOB"))
+        );
+        assert_eq!(
+            display_amount(1_000_000_000_000, TokenId::from(1)),
+            Some(("1000000.000000".to_string(), "eUSD"))
+        );
+        assert_eq!(display_amount(1, TokenId::from(8192)), None);
+    }
+}
@@ -14,6 +14,12 @@ pub enum B58Error {
     /// Not A Transfer Payload
     NotTransferPayload,
 
+    /// Payment request value must be nonzero
+    ZeroPaymentRequestValue,
+
+    /// Payment request memo is {0} bytes, longer than the limit of {1} bytes
+    PaymentRequestMemoTooLong(usize, usize),
+
     /// Transfer payload cannot have more than one entropy
     TransferPayloadRequiresSingleEntropy,
 
@@ -9,10 +9,13 @@ use mc_account_keys::{AccountKey, PublicAddress, RootEntropy, RootIdentity};
 use mc_account_keys_slip10::Slip10KeyGenerator;
 use mc_api::printable::{PaymentRequest, PrintableWrapper, TransferPayload};
 use mc_crypto_keys::CompressedRistrettoPublic;
-use mc_transaction_core::Amount;
+use mc_transaction_core::{Amount, TokenId};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 
+use crate::util::constants::PAYMENT_REQUEST_MEMO_MAX_BYTES;
+
+#[derive(Debug, PartialEq)]
 pub struct DecodedPaymentRequest {
     pub public_address: PublicAddress,
     pub value: u64,
@@ -20,6 +23,14 @@ pub struct DecodedPaymentRequest {
     pub memo: String,
 }
 
+/// What a payment can be made to: a payment request, or a plain public
+/// address which does not request a value.
+#[derive(Debug, PartialEq)]
+pub enum ParsedPaymentRequest {
+    PaymentRequest(DecodedPaymentRequest),
+    PublicAddress(PublicAddress),
+}
+
 pub struct DecodedTransferPayload {
     pub root_entropy: Option<RootEntropy>,
     pub bip39_entropy: Option<Vec<u8>>,
@@ -107,6 +118,45 @@ pub fn b58_decode_payment_request(
     })
 }
 
+/// Create a b58 payment request for a value to a b58 public address.
+pub fn create_payment_request(
+    public_address_b58: &str,
+    value: u64,
+    token_id: u64,
+    memo: &str,
+) -> Result<String, B58Error> {
+    if value == 0 {
+        return Err(B58Error::ZeroPaymentRequestValue);
+    }
+    if memo.len() > PAYMENT_REQUEST_MEMO_MAX_BYTES {
+        return Err(B58Error::PaymentRequestMemoTooLong(
+            memo.len(),
+            PAYMENT_REQUEST_MEMO_MAX_BYTES,
+        ));
+    }
+
+    let public_address = b58_decode_public_address(public_address_b58)?;
+    b58_encode_payment_request(
+        &public_address,
+        &Amount::new(value, TokenId::from(token_id)),
+        memo.to_string(),
+    )
+}
+
+/// Parse a b58 payment request. A plain b58 public address is also accepted,
+/// so that a wallet can scan either.
+pub fn parse_payment_request(b58_code: &str) -> Result<ParsedPaymentRequest, B58Error> {
+    match b58_printable_wrapper_type(b58_code.to_string())? {
+        PrintableWrapperType::PaymentRequest => Ok(ParsedPaymentRequest::PaymentRequest(
+            b58_decode_payment_request(b58_code.to_string())?,
+        )),
+        PrintableWrapperType::PublicAddress => Ok(ParsedPaymentRequest::PublicAddress(
+            b58_decode_public_address(b58_code)?,
+        )),
+        PrintableWrapperType::TransferPayload => Err(B58Error::NotPaymentRequest),
+    }
+}
+
 pub fn b58_encode_transfer_payload(
     bip_39_entropy_bytes: Vec<u8>,
     proto_tx_pubkey: mc_api::external::CompressedRistretto,
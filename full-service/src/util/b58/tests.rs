@@ -2,10 +2,14 @@
 mod tests {
     use crate::{
         test_utils::create_test_txo_for_recipient,
-        util::b58::{
-            b58_decode_payment_request, b58_decode_public_address, b58_decode_transfer_payload,
-            b58_encode_payment_request, b58_encode_public_address, b58_encode_transfer_payload,
-            b58_printable_wrapper_type, B58Error, PrintableWrapperType,
+        util::{
+            b58::{
+                b58_decode_payment_request, b58_decode_public_address, b58_decode_transfer_payload,
+                b58_encode_payment_request, b58_encode_public_address, b58_encode_transfer_payload,
+                b58_printable_wrapper_type, create_payment_request, parse_payment_request,
+                B58Error, DecodedPaymentRequest, ParsedPaymentRequest, PrintableWrapperType,
+            },
+            constants::PAYMENT_REQUEST_MEMO_MAX_BYTES,
         },
     };
     use bip39::{Language, Mnemonic};
@@ -101,6 +105,94 @@ mod tests {
         assert_eq!(decoded.memo, "This is a memo".to_string());
     }
 
+    #[test]
+    fn create_and_parse_payment_request_round_trips() {
+        let mut rng: StdRng = SeedableRng::from_seed([91u8; 32]);
+        let public_address = get_public_address(&mut rng);
+        let public_address_b58 = b58_encode_public_address(&public_address).unwrap();
+
+        let payment_request_b58 =
+            create_payment_request(&public_address_b58, 12_500_000_000_000, 1, "INV-1001").unwrap();
+        assert_eq!(
+            b58_printable_wrapper_type(payment_request_b58.clone()).unwrap(),
+            PrintableWrapperType::PaymentRequest
+        );
+        assert_eq!(
+            parse_payment_request(&payment_request_b58).unwrap(),
+            ParsedPaymentRequest::PaymentRequest(DecodedPaymentRequest {
+                public_address: public_address.clone(),
+                value: 12_500_000_000_000,
+                token_id: 1,
+                memo: "INV-1001".to_string(),
+            })
+        );
+
+        // A plain public address parses, distinguished from a payment request.
+        assert_eq!(
+            parse_payment_request(&public_address_b58).unwrap(),
+            ParsedPaymentRequest::PublicAddress(public_address)
+        );
+    }
+
+    #[test]
+    fn create_payment_request_validates_value_and_memo() {
+        let mut rng: StdRng = SeedableRng::from_seed([91u8; 32]);
+        let public_address_b58 = b58_encode_public_address(&get_public_address(&mut rng)).unwrap();
+
+        assert_eq!(
+            create_payment_request(&public_address_b58, 0, *Mob::ID, "").err(),
+            Some(B58Error::ZeroPaymentRequestValue)
+        );
+
+        let memo = "m".repeat(PAYMENT_REQUEST_MEMO_MAX_BYTES);
+        let payment_request_b58 =
+            create_payment_request(&public_address_b58, 1, *Mob::ID, &memo).unwrap();
+        match parse_payment_request(&payment_request_b58).unwrap() {
+            ParsedPaymentRequest::PaymentRequest(decoded) => assert_eq!(decoded.memo, memo),
+            parsed => panic!("Expected a payment request, got {:?}", parsed),
+        }
+
+        let memo = "m".repeat(PAYMENT_REQUEST_MEMO_MAX_BYTES + 1);
+        assert_eq!(
+            create_payment_request(&public_address_b58, 1, *Mob::ID, &memo).err(),
+            Some(B58Error::PaymentRequestMemoTooLong(
+                PAYMENT_REQUEST_MEMO_MAX_BYTES + 1,
+                PAYMENT_REQUEST_MEMO_MAX_BYTES
+            ))
+        );
+
+        // Only a public address can be requested to.
+        let payment_request_b58 =
+            create_payment_request(&public_address_b58, 1, *Mob::ID, "").unwrap();
+        assert_eq!(
+            create_payment_request(&payment_request_b58, 1, *Mob::ID, "").err(),
+            Some(B58Error::NotPublicAddress)
+        );
+    }
+
+    #[test]
+    fn parsing_transfer_payload_as_payment_request_returns_error() {
+        let (account_key, bip39_entropy_bytes) = get_account_and_entropy_bytes();
+        let mut rng: StdRng = SeedableRng::from_seed([91u8; 32]);
+        let (txo, _key_image) = create_test_txo_for_recipient(
+            &account_key,
+            0,
+            Amount::new(1_000_000_000_000, Mob::ID),
+            &mut rng,
+        );
+        let transfer_payload_b58 = b58_encode_transfer_payload(
+            bip39_entropy_bytes,
+            (&txo.public_key).into(),
+            "".to_string(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            parse_payment_request(&transfer_payload_b58).err(),
+            Some(B58Error::NotPaymentRequest)
+        );
+    }
+
     #[test]
     fn decoding_transfer_payload_succeeds() {
         let mut rng: StdRng = SeedableRng::from_seed([91u8; 32]);
@@ -0,0 +1,298 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Limits on concurrent transaction builds. Builds for one account are
+//! serialized, so that they cannot select overlapping inputs, while builds for
+//! different accounts proceed in parallel up to a global limit. A build which
+//! can not start within a bounded wait is turned away as busy.
+
+use crate::util::constants::{
+    BUILD_BUSY_RETRY_AFTER_SECONDS, DEFAULT_BUILD_WAIT_TIMEOUT_SECONDS,
+    DEFAULT_MAX_CONCURRENT_BUILDS, DEFAULT_MAX_QUEUED_BUILDS_PER_ACCOUNT,
+};
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+/// Returned when a build can not start, and the client should retry later.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BuildBusy {
+    /// The account already has the maximum number of builds waiting.
+    QueueFull { retry_after: Duration },
+
+    /// The build waited the maximum time for its turn without getting it.
+    WaitTimedOut { retry_after: Duration },
+}
+
+impl BuildBusy {
+    /// Suggested delay before the client retries.
+    pub fn retry_after(&self) -> Duration {
+        match self {
+            Self::QueueFull { retry_after } | Self::WaitTimedOut { retry_after } => *retry_after,
+        }
+    }
+}
+
+#[derive(Default)]
+struct AccountBuilds {
+    /// The thread currently building for this account, if any.
+    holder: Option<ThreadId>,
+
+    /// Number of nested permits held by the holder thread.
+    depth: usize,
+
+    /// Number of threads waiting to build for this account.
+    waiting: usize,
+}
+
+#[derive(Default)]
+struct BuildGuardState {
+    /// Number of accounts with a build in progress.
+    active_builds: usize,
+
+    accounts: HashMap<String, AccountBuilds>,
+}
+
+pub struct BuildGuard {
+    /// Maximum number of builds in progress across all accounts.
+    max_concurrent_builds: usize,
+
+    /// Maximum number of builds waiting behind the build in progress for an
+    /// account.
+    max_queued_builds_per_account: usize,
+
+    /// Longest a build waits for its turn before it is turned away.
+    max_wait: Duration,
+
+    state: Mutex<BuildGuardState>,
+
+    released: Condvar,
+}
+
+/// Held for the duration of a build. Dropping the permit lets the next build
+/// for the account proceed.
+pub struct BuildPermit<'a> {
+    guard: &'a BuildGuard,
+    account_id: String,
+}
+
+impl Default for BuildGuard {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_MAX_CONCURRENT_BUILDS,
+            DEFAULT_MAX_QUEUED_BUILDS_PER_ACCOUNT,
+            Duration::from_secs(DEFAULT_BUILD_WAIT_TIMEOUT_SECONDS),
+        )
+    }
+}
+
+impl BuildGuard {
+    pub fn new(
+        max_concurrent_builds: usize,
+        max_queued_builds_per_account: usize,
+        max_wait: Duration,
+    ) -> Self {
+        Self {
+            max_concurrent_builds: max_concurrent_builds.max(1),
+            max_queued_builds_per_account,
+            max_wait,
+            state: Mutex::new(BuildGuardState::default()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for the turn of the current thread to build for an account.
+    ///
+    /// Permits are reentrant, so a build which calls into another guarded
+    /// build for the same account does not deadlock. Gives up once the
+    /// account's queue is full, or after waiting `max_wait` for the build in
+    /// progress for the account or for a free slot among all accounts.
+    pub fn acquire(&self, account_id: &str) -> Result<BuildPermit, BuildBusy> {
+        let current_thread = thread::current().id();
+        let mut state = self.state.lock().expect("BuildGuard mutex poisoned");
+
+        let account = state.accounts.entry(account_id.to_string()).or_default();
+        if account.holder == Some(current_thread) {
+            account.depth += 1;
+            return Ok(self.permit(account_id));
+        }
+        if account.holder.is_some() && account.waiting >= self.max_queued_builds_per_account {
+            return Err(BuildBusy::QueueFull {
+                retry_after: Duration::from_secs(BUILD_BUSY_RETRY_AFTER_SECONDS),
+            });
+        }
+
+        account.waiting += 1;
+        let deadline = Instant::now() + self.max_wait;
+        loop {
+            let active_builds = state.active_builds;
+            let account = state
+                .accounts
+                .get_mut(account_id)
+                .expect("waiting account is always present");
+            if account.holder.is_none() && active_builds < self.max_concurrent_builds {
+                account.waiting -= 1;
+                account.holder = Some(current_thread);
+                account.depth = 1;
+                state.active_builds += 1;
+                return Ok(self.permit(account_id));
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                account.waiting -= 1;
+                if account.holder.is_none() && account.waiting == 0 {
+                    state.accounts.remove(account_id);
+                }
+                return Err(BuildBusy::WaitTimedOut {
+                    retry_after: Duration::from_secs(BUILD_BUSY_RETRY_AFTER_SECONDS),
+                });
+            }
+            state = self
+                .released
+                .wait_timeout(state, remaining)
+                .expect("BuildGuard mutex poisoned")
+                .0;
+        }
+    }
+
+    /// Number of accounts with a build in progress.
+    pub fn active_builds(&self) -> usize {
+        self.state
+            .lock()
+            .expect("BuildGuard mutex poisoned")
+            .active_builds
+    }
+
+    fn permit(&self, account_id: &str) -> BuildPermit {
+        BuildPermit {
+            guard: self,
+            account_id: account_id.to_string(),
+        }
+    }
+
+    fn release(&self, account_id: &str) {
+        let mut state = self.state.lock().expect("BuildGuard mutex poisoned");
+        let account = match state.accounts.get_mut(account_id) {
+            Some(account) => account,
+            None => return,
+        };
+        account.depth -= 1;
+        if account.depth > 0 {
+            return;
+        }
+        account.holder = None;
+        if account.waiting == 0 {
+            state.accounts.remove(account_id);
+        }
+        state.active_builds -= 1;
+        drop(state);
+        self.released.notify_all();
+    }
+}
+
+impl Drop for BuildPermit<'_> {
+    fn drop(&mut self) {
+        self.guard.release(&self.account_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Barrier,
+    };
+
+    #[test]
+    fn test_build_guard_is_reentrant() {
+        let guard = BuildGuard::new(1, 0, Duration::from_secs(1));
+        let outer = guard.acquire("a").unwrap();
+        let inner = guard.acquire("a").unwrap();
+        assert_eq!(guard.active_builds(), 1);
+        drop(inner);
+        assert_eq!(guard.active_builds(), 1);
+        drop(outer);
+        assert_eq!(guard.active_builds(), 0);
+    }
+
+    #[test]
+    fn test_build_guard_rejects_when_queue_full() {
+        let guard = Arc::new(BuildGuard::new(4, 0, Duration::from_secs(1)));
+        let _permit = guard.acquire("a").unwrap();
+
+        let other_thread_guard = guard.clone();
+        let result = thread::spawn(move || other_thread_guard.acquire("a").map(|_| ()))
+            .join()
+            .unwrap();
+        assert_eq!(
+            result,
+            Err(BuildBusy::QueueFull {
+                retry_after: Duration::from_secs(BUILD_BUSY_RETRY_AFTER_SECONDS),
+            })
+        );
+
+        // Other accounts are unaffected.
+        let other_thread_guard = guard.clone();
+        assert!(thread::spawn(move || other_thread_guard.acquire("b").is_ok())
+            .join()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_build_guard_limits_concurrent_builds() {
+        let guard = Arc::new(BuildGuard::new(2, 8, Duration::from_secs(10)));
+        let in_progress = Arc::new(AtomicUsize::new(0));
+        let max_in_progress = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(6));
+
+        let handles: Vec<_> = (0..6)
+            .map(|i| {
+                let guard = guard.clone();
+                let in_progress = in_progress.clone();
+                let max_in_progress = max_in_progress.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    let _permit = guard.acquire(&format!("account-{}", i)).unwrap();
+                    let now = in_progress.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_progress.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(20));
+                    in_progress.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(max_in_progress.load(Ordering::SeqCst), 2);
+        assert_eq!(guard.active_builds(), 0);
+    }
+
+    #[test]
+    fn test_build_guard_times_out_waiting_for_a_slot() {
+        let guard = Arc::new(BuildGuard::new(1, 8, Duration::from_millis(50)));
+        let permit = guard.acquire("a").unwrap();
+
+        let other_thread_guard = guard.clone();
+        let result = thread::spawn(move || other_thread_guard.acquire("b").map(|_| ()))
+            .join()
+            .unwrap();
+        assert_eq!(
+            result,
+            Err(BuildBusy::WaitTimedOut {
+                retry_after: Duration::from_secs(BUILD_BUSY_RETRY_AFTER_SECONDS),
+            })
+        );
+        assert_eq!(guard.active_builds(), 1);
+
+        // The timed out build left nothing behind for the account.
+        drop(permit);
+        assert!(guard.state.lock().unwrap().accounts.is_empty());
+        assert!(guard.acquire("b").is_ok());
+    }
+}
@@ -3,3 +3,139 @@ pub const ROOT_ENTROPY_KEY_DERIVATION_VERSION: u8 = 1;
 pub const MNEMONIC_KEY_DERIVATION_VERSION: u8 = 2;
 pub const DEFAULT_NEXT_SUBADDRESS_INDEX: u64 = 2;
 pub const LEGACY_CHANGE_SUBADDRESS_INDEX: u64 = 1;
+pub const BLOCK_TIMESTAMP_CACHE_SIZE: usize = 10_000;
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL_SECONDS: u64 = 86_400;
+pub const DEFAULT_MAX_CONCURRENT_BUILDS: usize = 8;
+pub const DEFAULT_MAX_QUEUED_BUILDS_PER_ACCOUNT: usize = 4;
+pub const BUILD_BUSY_RETRY_AFTER_SECONDS: u64 = 1;
+pub const DEFAULT_BUILD_WAIT_TIMEOUT_SECONDS: u64 = 30;
+pub const SHUTTING_DOWN_RETRY_AFTER_SECONDS: u64 = 5;
+pub const DEFAULT_STREAM_PAGE_SIZE: u64 = 1_000;
+pub const DEFAULT_TXOS_MODIFIED_SINCE_LIMIT: u64 = 1_000;
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 50;
+/// Largest request body accepted by routes without their own limit, by
+/// default.
+pub const DEFAULT_REQUEST_SIZE_LIMIT_BYTES: u64 = 1024 * 1024;
+/// Largest request body accepted by the wallet endpoints, by default, which
+/// fits several TxProposals with the maximum number of inputs.
+pub const DEFAULT_WALLET_REQUEST_SIZE_LIMIT_BYTES: u64 = 16 * 1024 * 1024;
+/// Size of a TxProposal in a response above which a warning is logged, as
+/// clients may not be able to submit it back.
+pub const TX_PROPOSAL_SIZE_WARNING_BYTES: usize = 4 * 1024 * 1024;
+pub const DEBUG_BUILD_REPORT_MAX_TXOS: usize = 100;
+pub const TRANSACTION_REPORT_RETRY_INTERVAL_SECONDS: u64 = 30;
+/// How often expired transactions are checked for automatic rebuilds.
+pub const AUTO_REBUILD_INTERVAL_SECONDS: u64 = 10;
+/// Most times an expired transaction can be automatically rebuilt.
+pub const MAX_AUTO_REBUILD_ATTEMPTS: u64 = 10;
+/// Largest percentage by which the fee can be raised on each rebuild.
+pub const MAX_AUTO_REBUILD_FEE_BUMP_PERCENT: u64 = 100;
+/// Accounts with more blocks than this left to sync are counted as behind in
+/// the wallet status rollup.
+pub const DEFAULT_ACCOUNT_BEHIND_THRESHOLD_BLOCKS: u64 = 100;
+/// How long after its removal an account can be restored. Past this window the
+/// sync thread purges it, deleting its keys along with its history.
+pub const REMOVED_ACCOUNT_RETENTION_SECONDS: u64 = 30 * 86_400;
+
+/// Version of the serialized UnsignedTx returned by build_unsigned_transaction.
+/// Increment when its fields, or how they are signed, change, so that offline
+/// signers can detect an incompatible wallet.
+///
+/// * 3: records schema_version, which signers check, and a token listed with
+///   zero change outputs gets no change output when its change is zero.
+/// * 4: change_subaddress_index, where change is returned to instead of the
+///   account's change subaddress.
+pub const UNSIGNED_TX_SCHEMA_VERSION: u64 = 4;
+
+/// Version of the account backup document produced by export_account.
+pub const ACCOUNT_BACKUP_VERSION: u64 = 1;
+/// PBKDF2 rounds used to derive the key which encrypts the secrets in an
+/// account backup from its passphrase.
+pub const ACCOUNT_BACKUP_KDF_ROUNDS: u32 = 100_000;
+/// Most PBKDF2 rounds an imported account backup may ask for, so that a
+/// crafted backup can not tie up the wallet deriving its key.
+pub const MAX_ACCOUNT_BACKUP_KDF_ROUNDS: u32 = 10 * ACCOUNT_BACKUP_KDF_ROUNDS;
+
+/// Version of the view only account sync request and response documents.
+pub const VIEW_ONLY_SYNC_VERSION: u64 = 1;
+
+/// Most blocks a single ledger scan for a view key can cover.
+pub const MAX_LEDGER_SCAN_BLOCKS: u64 = 100_000;
+/// Most subaddresses a single ledger scan for a view key can match against.
+pub const MAX_LEDGER_SCAN_SUBADDRESSES: u64 = 10_000;
+/// Number of ledger scans kept for polling. Older scans are forgotten.
+pub const LEDGER_SCAN_CACHE_SIZE: usize = 32;
+
+/// Most key images a single check_key_images request can check.
+pub const MAX_CHECK_KEY_IMAGES: usize = 10_000;
+/// Number of key images each worker thread checks at a time.
+pub const CHECK_KEY_IMAGES_BATCH_SIZE: usize = 500;
+
+/// Longest identify_tx_out_owner may spend matching a TxOut against the
+/// wallet's accounts before giving up.
+pub const IDENTIFY_TX_OUT_OWNER_TIMEOUT_SECONDS: u64 = 30;
+
+/// Number of worker threads running background jobs.
+pub const JOB_WORKER_THREADS: usize = 2;
+/// Most background jobs which can wait for a worker. Further jobs are refused.
+pub const JOB_QUEUE_SIZE: usize = 16;
+/// Number of jobs kept for polling. The oldest finished jobs are forgotten.
+pub const JOB_HISTORY_SIZE: usize = 100;
+
+/// How long a challenge confirming an irreversible request can be used for.
+pub const CONFIRMATION_CHALLENGE_TTL_SECONDS: u64 = 300;
+/// Most unused confirmation challenges kept. The oldest are forgotten first.
+pub const CONFIRMATION_CHALLENGE_CACHE_SIZE: usize = 64;
+
+/// Number of txos looked up in the ledger per batch when backfilling their
+/// global indices.
+pub const TXO_GLOBAL_INDEX_BACKFILL_BATCH_SIZE: u64 = 500;
+
+/// Longest a validated fog pubkey is reused across builds, by default.
+pub const DEFAULT_FOG_PUBKEY_CACHE_TTL_SECONDS: u64 = 600;
+
+/// Longest the health checks wait for a wallet db connection, by default.
+pub const DEFAULT_HEALTH_TIMEOUT_SECONDS: u64 = 2;
+/// Most blocks the ledger may be behind the network for the wallet to be
+/// ready, by default.
+pub const DEFAULT_READY_MAX_LEDGER_LAG: u64 = 10;
+/// Longest since a peer or the validator last responded for the wallet to be
+/// ready, by default.
+pub const DEFAULT_READY_PEER_RESPONSE_WINDOW_SECONDS: u64 = 60;
+
+/// Number of calls to a peer which must fail in a row for it to be unhealthy.
+pub const PEER_UNHEALTHY_CONSECUTIVE_FAILURES: u64 = 3;
+/// How long an unhealthy peer is left alone before it is tried again.
+pub const PEER_UNHEALTHY_RETRY_SECONDS: u64 = 30;
+/// Weight of the latest call in a peer's average latency.
+pub const PEER_LATENCY_SAMPLE_WEIGHT_PERCENT: u32 = 20;
+/// Largest percentage by which a peer's score is randomly worsened when
+/// choosing a peer, so that peers with similar scores share submissions.
+pub const PEER_SELECTION_JITTER_PERCENT: u64 = 20;
+
+/// Accounts imported with a first block index within this many blocks of the
+/// ledger height are warned about, as the index is likely to be too late.
+pub const RECENT_FIRST_BLOCK_INDEX_WARNING_BLOCKS: u64 = 1_000;
+
+/// How often an account migration checks whether its sweeps have landed.
+pub const ACCOUNT_MIGRATION_POLL_INTERVAL_MILLIS: u64 = 1000;
+
+/// Fog domains of mainnet recipients, used to check recipient addresses when
+/// the wallet is configured for mainnet.
+pub const MAINNET_FOG_DOMAINS: &[&str] = &["prod.mobilecoinww.com"];
+/// Fog domains of testnet recipients.
+pub const TESTNET_FOG_DOMAINS: &[&str] = &["test.mobilecoin.com"];
+
+/// Longest memo a payment request can carry, in bytes.
+pub const PAYMENT_REQUEST_MEMO_MAX_BYTES: usize = 255;
+
+/// Address attestations made longer ago than this, or claiming to be from
+/// further in the future, are flagged as stale when verified.
+pub const ADDRESS_ATTESTATION_MAX_AGE_SECONDS: u64 = 86_400;
+
+/// Most named databases the ledger's LMDB environment is opened with when
+/// reporting on or copying it.
+pub const LEDGER_DB_MAX_DBS: u32 = 32;
+/// Number of records written to a compacted copy of the ledger per
+/// transaction.
+pub const LEDGER_DB_COMPACTION_BATCH_SIZE: usize = 100_000;
@@ -41,3 +41,53 @@ pub fn ristretto_to_hex(key: &RistrettoPrivate) -> String {
 pub fn ristretto_public_to_hex(key: &RistrettoPublic) -> String {
     vec_to_hex(&ristretto_public_to_vec(key))
 }
+
+const DER_SEQUENCE: u8 = 0x30;
+const DER_BIT_STRING: u8 = 0x03;
+
+/// Whether the bytes are a DER encoded SubjectPublicKeyInfo, which is a
+/// sequence of an algorithm identifier and a bit string holding the key.
+pub fn is_subject_public_key_info(der: &[u8]) -> bool {
+    let spki = match der_element(der, DER_SEQUENCE) {
+        Some((spki, rest)) if rest.is_empty() => spki,
+        _ => return false,
+    };
+    let key = match der_element(spki, DER_SEQUENCE) {
+        Some((_algorithm, key)) => key,
+        None => return false,
+    };
+    matches!(
+        der_element(key, DER_BIT_STRING),
+        Some((key, rest)) if !key.is_empty() && rest.is_empty()
+    )
+}
+
+/// Split a DER element with the given tag from the start of the bytes,
+/// returning its contents and the bytes after it.
+fn der_element(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    let (&element_tag, rest) = der.split_first()?;
+    if element_tag != tag {
+        return None;
+    }
+    let (&length_byte, rest) = rest.split_first()?;
+    let (length, rest) = if length_byte < 0x80 {
+        (length_byte as usize, rest)
+    } else {
+        let num_length_bytes = (length_byte & 0x7f) as usize;
+        if num_length_bytes == 0
+            || num_length_bytes > std::mem::size_of::<usize>()
+            || rest.len() < num_length_bytes
+        {
+            return None;
+        }
+        let (length_bytes, rest) = rest.split_at(num_length_bytes);
+        let length = length_bytes
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | *byte as usize);
+        (length, rest)
+    };
+    if rest.len() < length {
+        return None;
+    }
+    Some(rest.split_at(length))
+}
@@ -0,0 +1,63 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Helpers for reporting which field of a JSON object failed to convert.
+
+use std::fmt::Display;
+
+/// Prefix the error of a conversion with the path of the field it came from,
+/// such as `payload_txos[3].confirmation_number`.
+pub trait FieldContext<T> {
+    fn field_context<P: Display>(self, path: impl FnOnce() -> P) -> Result<T, String>;
+}
+
+impl<T, E: Display> FieldContext<T> for Result<T, E> {
+    fn field_context<P: Display>(self, path: impl FnOnce() -> P) -> Result<T, String> {
+        self.map_err(|e| format!("{}: {}", path(), e))
+    }
+}
+
+/// Decode a hex string which must hold exactly N bytes.
+pub fn decode_hex_array<const N: usize>(hex_str: &str) -> Result<[u8; N], String> {
+    if hex_str.len() != 2 * N {
+        return Err(format!(
+            "expected {} hex chars, got {}",
+            2 * N,
+            hex_str.len()
+        ));
+    }
+    let mut bytes = [0u8; N];
+    hex::decode_to_slice(hex_str, &mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_context() {
+        let result: Result<u64, String> = "12x".parse::<u64>().field_context(|| "value");
+        assert_eq!(
+            result,
+            Err("value: invalid digit found in string".to_string())
+        );
+
+        let result: Result<u64, String> = "12"
+            .parse::<u64>()
+            .field_context(|| format!("txos[{}].value", 3));
+        assert_eq!(result, Ok(12));
+    }
+
+    #[test]
+    fn test_decode_hex_array() {
+        assert_eq!(decode_hex_array::<2>("0aff"), Ok([0x0a, 0xff]));
+        assert_eq!(
+            decode_hex_array::<32>(&"ab".repeat(31)),
+            Err("expected 64 hex chars, got 62".to_string())
+        );
+        assert_eq!(
+            decode_hex_array::<2>("0afg"),
+            Err("Invalid character 'g' at position 3".to_string())
+        );
+    }
+}
@@ -0,0 +1,452 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Log drains which redact secrets from log records, and which write records
+//! as JSON lines.
+//!
+//! Errors are logged with their Display or Debug formatting, which can carry
+//! key material from deep in an error chain. Every string in a record is
+//! redacted before it is written: runs of hex characters as long as a hex
+//! encoded key are masked, as are the middles of b58 strings as long as an
+//! address. Values logged under the names in REDACTION_ALLOWED_KEYS, either as
+//! a record value or as `name=value` or `name: value` in the message, are
+//! written as they are.
+
+use chrono::NaiveDateTime;
+use serde_json::{Map, Value};
+use slog::{BorrowedKV, Drain, Key, OwnedKVList, Record, RecordStatic, Serializer, KV};
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Shortest run of hex characters which is masked, the length of a hex
+/// encoded 32 byte key.
+pub const MIN_REDACTED_HEX_LEN: usize = 64;
+
+/// Shortest b58 string whose middle is masked. Public addresses and gift codes
+/// are longer.
+pub const MIN_REDACTED_B58_LEN: usize = 64;
+
+/// Number of characters kept at each end of a masked b58 string.
+const B58_KEPT_CHARS: usize = 6;
+
+/// What a masked run of hex characters is replaced with.
+pub const REDACTED_HEX: &str = "[redacted hex]";
+
+/// Names of values which are safe to log unredacted. Tx out public keys are
+/// in the ledger for anyone to see.
+pub const REDACTION_ALLOWED_KEYS: &[&str] = &["tx_out_public_key", "txo_public_key"];
+
+fn is_b58_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() && !matches!(c, '0' | 'O' | 'I' | 'l')
+}
+
+/// Whether the text before a value names it as safe to log, as in
+/// `tx_out_public_key=` or `tx_out_public_key: `.
+fn is_allowed_value(preceding: &str) -> bool {
+    let preceding = preceding.trim_end_matches(&[' ', '"'][..]);
+    let preceding = match preceding
+        .strip_suffix('=')
+        .or_else(|| preceding.strip_suffix(':'))
+    {
+        Some(preceding) => preceding.trim_end_matches('"'),
+        None => return false,
+    };
+    REDACTION_ALLOWED_KEYS
+        .iter()
+        .any(|allowed| preceding.ends_with(allowed))
+}
+
+/// Mask the secrets a string may contain: runs of at least
+/// MIN_REDACTED_HEX_LEN hex characters, and all but the ends of b58 strings of
+/// at least MIN_REDACTED_B58_LEN characters.
+pub fn redact(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        redacted.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        if is_allowed_value(&redacted) {
+            redacted.push_str(word);
+        } else {
+            redact_word(word, &mut redacted);
+        }
+        rest = &rest[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Redact a run of ascii alphanumeric characters.
+fn redact_word(word: &str, redacted: &mut String) {
+    let mut hex_runs = Vec::new();
+    let mut rest = word;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_hexdigit()) {
+        let end = rest[start..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .map_or(rest.len(), |end| start + end);
+        hex_runs.push((&rest[..start], &rest[start..end]));
+        rest = &rest[end..];
+    }
+
+    if hex_runs
+        .iter()
+        .any(|(_, hex)| hex.len() >= MIN_REDACTED_HEX_LEN)
+    {
+        for (before, hex) in hex_runs {
+            redacted.push_str(before);
+            if hex.len() >= MIN_REDACTED_HEX_LEN {
+                redacted.push_str(REDACTED_HEX);
+            } else {
+                redacted.push_str(hex);
+            }
+        }
+        redacted.push_str(rest);
+    } else if word.len() >= MIN_REDACTED_B58_LEN && word.chars().all(is_b58_char) {
+        redacted.push_str(&word[..B58_KEPT_CHARS]);
+        redacted.push_str("...");
+        redacted.push_str(&word[word.len() - B58_KEPT_CHARS..]);
+    } else {
+        redacted.push_str(word);
+    }
+}
+
+/// A drain which redacts the message and values of each record before passing
+/// it on. Values of the logger itself, set when it is created, are passed on
+/// as they are.
+pub struct RedactingDrain<D: Drain>(pub D);
+
+impl<D: Drain> Drain for RedactingDrain<D> {
+    type Ok = D::Ok;
+    type Err = D::Err;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<D::Ok, D::Err> {
+        let msg = redact(&record.msg().to_string());
+        let kv = RedactedKV(record.kv());
+        let record_static = RecordStatic {
+            location: record.location(),
+            tag: record.tag(),
+            level: record.level(),
+        };
+        self.0.log(
+            &Record::new(&record_static, &format_args!("{}", msg), BorrowedKV(&kv)),
+            values,
+        )
+    }
+}
+
+struct RedactedKV<'a>(BorrowedKV<'a>);
+
+impl<'a> KV for RedactedKV<'a> {
+    fn serialize(&self, record: &Record, serializer: &mut dyn Serializer) -> slog::Result {
+        let BorrowedKV(kv) = self.0;
+        kv.serialize(record, &mut RedactingSerializer(serializer))
+    }
+}
+
+/// Redacts string values on their way to another serializer. Numbers and
+/// values under REDACTION_ALLOWED_KEYS are passed on as they are.
+struct RedactingSerializer<'a>(&'a mut dyn Serializer);
+
+impl<'a> Serializer for RedactingSerializer<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        if REDACTION_ALLOWED_KEYS.contains(&key) {
+            self.0.emit_arguments(key, val)
+        } else {
+            self.0.emit_str(key, &redact(&val.to_string()))
+        }
+    }
+
+    fn emit_str(&mut self, key: Key, val: &str) -> slog::Result {
+        if REDACTION_ALLOWED_KEYS.contains(&key) {
+            self.0.emit_str(key, val)
+        } else {
+            self.0.emit_str(key, &redact(val))
+        }
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.0.emit_bool(key, val)
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.0.emit_u64(key, val)
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.0.emit_i64(key, val)
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.0.emit_f64(key, val)
+    }
+}
+
+/// A drain which writes each record as a line of JSON, with its time, level,
+/// message, module and values.
+pub struct JsonDrain<W: Write> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write> JsonDrain<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write> Drain for JsonDrain<W> {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let ts = NaiveDateTime::from_timestamp_opt(now.as_secs() as i64, now.subsec_nanos())
+            .map(|ts| format!("{}Z", ts.format("%Y-%m-%dT%H:%M:%S%.6f")));
+
+        let mut object = Map::new();
+        object.insert("ts".to_string(), ts.into());
+        object.insert("level".to_string(), record.level().as_str().into());
+        object.insert("msg".to_string(), record.msg().to_string().into());
+        object.insert("module".to_string(), record.module().into());
+        let mut serializer = JsonSerializer(&mut object);
+        values.serialize(record, &mut serializer)?;
+        record.kv().serialize(record, &mut serializer)?;
+
+        let mut writer = self.writer.lock().expect("log writer lock poisoned");
+        serde_json::to_writer(&mut *writer, &Value::Object(object))?;
+        writeln!(writer)?;
+        writer.flush()
+    }
+}
+
+struct JsonSerializer<'a>(&'a mut Map<String, Value>);
+
+impl<'a> Serializer for JsonSerializer<'a> {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), val.to_string().into());
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.0.insert(key.to_string(), val.into());
+        Ok(())
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.0.insert(key.to_string(), val.into());
+        Ok(())
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.0.insert(key.to_string(), val.into());
+        Ok(())
+    }
+
+    fn emit_f64(&mut self, key: Key, val: f64) -> slog::Result {
+        self.0.insert(key.to_string(), val.into());
+        Ok(())
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        self.0.insert(key.to_string(), Value::Null);
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.0.insert(key.to_string(), Value::Null);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{o, Logger};
+    use std::{fs, path::Path, sync::Arc};
+
+    /// A writer which collects what is written, for reading back.
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let key = "a3".repeat(32);
+        let proto_key = format!("0a20{}", key);
+        let address = "8vbEtknX7zNtmN5epTYU95do3fDfsmecDu9kUbW66XGkKBX87n8AyqiiH9CMrueo5H7yiBEPXPoQHhEBLFHZJLcB2g7DZJ3tUZ9ArVgBu3a";
+
+        assert_eq!(
+            redact(&format!("Invalid key {}.", key)),
+            "Invalid key [redacted hex]."
+        );
+        assert_eq!(
+            redact(&format!("key: {:?}", proto_key)),
+            "key: \"[redacted hex]\""
+        );
+        assert_eq!(
+            redact(&format!("Sent to {}", address)),
+            format!(
+                "Sent to {}...{}",
+                &address[..6],
+                &address[address.len() - 6..]
+            )
+        );
+        assert_eq!(
+            redact("Short hex abcdef01 and words"),
+            "Short hex abcdef01 and words"
+        );
+
+        // Tx out public keys are kept when they are named as such.
+        assert_eq!(
+            redact(&format!("tx_out_public_key={}, spend: {}", key, key)),
+            format!("tx_out_public_key={}, spend: [redacted hex]", key)
+        );
+        assert_eq!(
+            redact(&format!("{{\"txo_public_key\": \"{}\"}}", key)),
+            format!("{{\"txo_public_key\": \"{}\"}}", key)
+        );
+    }
+
+    #[test]
+    fn test_json_logging_redacts_errors() {
+        let spend_key = "5f".repeat(32);
+        let tx_out_public_key = "c1".repeat(32);
+
+        let buffer = SharedBuffer::default();
+        let logger = Logger::root(
+            RedactingDrain(JsonDrain::new(buffer.clone())).fuse(),
+            o!("service" => "full-service"),
+        );
+
+        // An error whose Debug formatting carries a secret deep in its chain.
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        enum Inner {
+            Key(String),
+        }
+        #[derive(Debug)]
+        #[allow(dead_code)]
+        enum Outer {
+            Build(Inner),
+        }
+        let err = Outer::Build(Inner::Key(spend_key.clone()));
+        slog::error!(
+            logger,
+            "Failed building transaction: {:?}", err;
+            "cause" => format!("{:?}", err),
+            "tx_out_public_key" => tx_out_public_key.clone(),
+            "attempt" => 2u64
+        );
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(!written.contains(&spend_key));
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let record: Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(record["level"], "ERROR");
+        assert_eq!(
+            record["msg"],
+            "Failed building transaction: Build(Key(\"[redacted hex]\"))"
+        );
+        assert_eq!(record["cause"], "Build(Key(\"[redacted hex]\"))");
+        assert_eq!(record["tx_out_public_key"], tx_out_public_key.as_str());
+        assert_eq!(record["attempt"], 2);
+        assert_eq!(record["service"], "full-service");
+        assert!(record["ts"].as_str().unwrap().ends_with('Z'));
+    }
+
+    /// Collect the non-test code of the rust files under a path.
+    fn non_test_sources(path: &Path, sources: &mut Vec<(String, String)>) {
+        if path.is_dir() {
+            for entry in fs::read_dir(path).unwrap() {
+                non_test_sources(&entry.unwrap().path(), sources);
+            }
+        } else if path.extension().map_or(false, |ext| ext == "rs") {
+            let source = fs::read_to_string(path).unwrap();
+            let code = source
+                .split("#[cfg(test)]\nmod tests")
+                .next()
+                .unwrap()
+                .to_string();
+            sources.push((path.display().to_string(), code));
+        }
+    }
+
+    /// Private key material must not be Debug formatted into logs or errors
+    /// by the services, nor held by types which derive Debug.
+    #[test]
+    fn test_no_debug_formatting_of_private_keys() {
+        const SECRET_NAMES: &[&str] = &[
+            "account_key",
+            "spend_private",
+            "view_private_key",
+            "entropy",
+            "mnemonic",
+            "RistrettoPrivate",
+        ];
+        const SECRET_TYPES: &[&str] = &[
+            ": RistrettoPrivate",
+            ": AccountKey",
+            ": RootEntropy",
+            "(AccountKey)",
+        ];
+
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut sources = Vec::new();
+        for path in &["service", "db", "error.rs", "util"] {
+            non_test_sources(&src.join(path), &mut sources);
+        }
+        assert!(!sources.is_empty());
+
+        let mut violations = Vec::new();
+        for (path, code) in &sources {
+            let lines: Vec<&str> = code.lines().collect();
+            for (i, line) in lines.iter().enumerate() {
+                // The arguments of a Debug formatted string, up to the end of
+                // the statement, without the contents of string literals.
+                if line.contains("{:?}") || line.contains("{:#?}") {
+                    for statement_line in &lines[i..] {
+                        let code: String = statement_line.split('"').step_by(2).collect();
+                        if SECRET_NAMES.iter().any(|name| code.contains(name)) {
+                            violations.push(format!("{}:{}: {}", path, i + 1, line.trim()));
+                        }
+                        if statement_line.trim_end().ends_with(&[';', '{', '}'][..]) {
+                            break;
+                        }
+                    }
+                }
+
+                if line.trim_start().starts_with("#[derive(") && line.contains("Debug") {
+                    for body_line in &lines[i..] {
+                        if SECRET_TYPES.iter().any(|ty| body_line.contains(ty)) {
+                            violations.push(format!("{}:{}: {}", path, i + 1, line.trim()));
+                        }
+                        if body_line.starts_with('}') || body_line.trim_end().ends_with(");") {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(violations.is_empty(), "{:#?}", violations);
+    }
+}
@@ -0,0 +1,156 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! A small least-recently-used cache, used to avoid repeated ledger lookups.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+/// A fixed-capacity map which evicts the least recently used entry when full.
+pub struct LruCache<K, V> {
+    /// Maximum number of entries held by the cache.
+    capacity: usize,
+
+    /// Entries, along with the tick at which they were last used.
+    entries: HashMap<K, (V, u64)>,
+
+    /// Keys ordered by the tick at which they were last used.
+    recency: BTreeMap<u64, K>,
+
+    /// Monotonically increasing usage counter.
+    tick: u64,
+
+    /// Number of lookups which found an entry.
+    hits: u64,
+
+    /// Number of lookups which did not find an entry.
+    misses: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Get the value for a key, marking it as most recently used.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+        match self.entries.get_mut(key) {
+            Some((value, last_used)) => {
+                self.recency.remove(last_used);
+                self.recency.insert(tick, key.clone());
+                *last_used = tick;
+                self.hits += 1;
+                Some(value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Insert a value, evicting the least recently used entry if the cache is
+    /// full.
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_used)) = self.entries.remove(&key) {
+            self.recency.remove(&last_used);
+        } else if self.entries.len() >= self.capacity {
+            let oldest = self.recency.keys().next().copied();
+            if let Some(oldest) = oldest {
+                if let Some(evicted) = self.recency.remove(&oldest) {
+                    self.entries.remove(&evicted);
+                }
+            }
+        }
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// Remove the value for a key, returning it if it was present.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, last_used) = self.entries.remove(key)?;
+        self.recency.remove(&last_used);
+        Some(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_cache_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1u64, "one");
+        cache.insert(2u64, "two");
+
+        // Touch 1 so that 2 becomes the least recently used entry.
+        assert_eq!(cache.get(&1), Some("one"));
+        cache.insert(3u64, "three");
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("one"));
+        assert_eq!(cache.get(&3), Some("three"));
+        assert_eq!(cache.hits(), 3);
+        assert_eq!(cache.misses(), 1);
+    }
+
+    #[test]
+    fn test_lru_cache_overwrite_does_not_evict() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1u64, 10);
+        cache.insert(2u64, 20);
+        cache.insert(1u64, 11);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&1), Some(11));
+        assert_eq!(cache.get(&2), Some(20));
+    }
+
+    #[test]
+    fn test_lru_cache_remove() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1u64, 10);
+        cache.insert(2u64, 20);
+
+        assert_eq!(cache.remove(&1), Some(10));
+        assert_eq!(cache.remove(&1), None);
+        assert_eq!(cache.len(), 1);
+
+        // The removed entry no longer takes up capacity.
+        cache.insert(3u64, 30);
+        assert_eq!(cache.get(&2), Some(20));
+        assert_eq!(cache.get(&3), Some(30));
+    }
+}
@@ -1,3 +1,12 @@
+pub mod address_attestation;
+pub mod amount_display;
 pub mod b58;
+pub mod build_guard;
 pub mod constants;
 pub mod encoding_helpers;
+pub mod field_context;
+pub mod logging;
+pub mod lru_cache;
+pub mod sender_memo;
+pub mod shutdown;
+pub mod tx_validation;
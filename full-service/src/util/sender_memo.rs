@@ -0,0 +1,315 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Recognizing the sender of a payment from the authenticated sender memo it
+//! carries, as written by the RTH memo builder.
+//!
+//! The memo names the sender by the short hash of their public address, so a
+//! client shows "payment from <contact>" by hashing the addresses of its
+//! contacts, and checking the memo's HMAC against the one it matches. Neither
+//! needs a wallet.
+
+use crate::util::b58::{b58_decode_public_address, B58Error};
+use displaydoc::Display;
+use mc_crypto_keys::{CompressedRistrettoPublic, RistrettoPrivate};
+use mc_transaction_core::MemoPayload;
+use mc_transaction_std::{MemoType, ShortAddressHash};
+use std::convert::TryFrom;
+
+/// Length of a memo payload: two bytes of type followed by the memo data.
+pub const MEMO_PAYLOAD_LEN: usize = 66;
+
+/// Errors decoding a sender memo.
+#[derive(Display, Debug)]
+pub enum SenderMemoError {
+    /// Error decoding hex: {0}
+    HexDecode(hex::FromHexError),
+
+    /// The memo payload is {0} bytes, expected {1}
+    InvalidMemoPayloadLength(usize, usize),
+
+    /// Invalid key: {0}
+    InvalidKey(String),
+
+    /// Invalid public address: {0}
+    InvalidPublicAddress(B58Error),
+}
+
+impl From<hex::FromHexError> for SenderMemoError {
+    fn from(src: hex::FromHexError) -> Self {
+        Self::HexDecode(src)
+    }
+}
+
+impl From<B58Error> for SenderMemoError {
+    fn from(src: B58Error) -> Self {
+        Self::InvalidPublicAddress(src)
+    }
+}
+
+/// A decoded memo, from the point of view of finding its sender.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecodedSenderMemo {
+    /// The memo is unused, so the sender is not known.
+    Unused,
+
+    /// The memo is of a type which does not name a sender, or of a type this
+    /// wallet does not know. Carries the memo type, as hex.
+    NotSenderMemo(String),
+
+    /// An authenticated sender memo.
+    Sender(SenderMemo),
+}
+
+/// The contents of an authenticated sender memo.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SenderMemo {
+    /// Whether the memo's HMAC shows that it was written by the claimed
+    /// sender, for this output.
+    pub valid: bool,
+
+    /// The short hash of the sender's address carried by the memo, as hex.
+    pub address_hash_hex: String,
+
+    /// The id of the payment request being paid, if the memo carries one.
+    pub payment_request_id: Option<u64>,
+}
+
+/// The short hash of a public address, as hex, as it appears in memos.
+pub fn compute_address_hash(public_address_b58: &str) -> Result<String, SenderMemoError> {
+    let public_address = b58_decode_public_address(public_address_b58)?;
+    Ok(hex::encode(ShortAddressHash::from(&public_address)))
+}
+
+/// Decode a memo payload, and check a sender memo against the claimed sender.
+///
+/// The memo is of the output with the given public key, received at the
+/// subaddress whose view private key is given.
+pub fn decode_sender_memo(
+    memo_payload_hex: &str,
+    tx_out_public_key_hex: &str,
+    receiving_subaddress_view_private_hex: &str,
+    sender_b58: &str,
+) -> Result<DecodedSenderMemo, SenderMemoError> {
+    let memo_payload = hex::decode(memo_payload_hex)?;
+    if memo_payload.len() != MEMO_PAYLOAD_LEN {
+        return Err(SenderMemoError::InvalidMemoPayloadLength(
+            memo_payload.len(),
+            MEMO_PAYLOAD_LEN,
+        ));
+    }
+    let mut memo_type = [0u8; 2];
+    memo_type.copy_from_slice(&memo_payload[..2]);
+    let mut memo_data = [0u8; 64];
+    memo_data.copy_from_slice(&memo_payload[2..]);
+    let memo_payload = MemoPayload::new(memo_type, memo_data);
+
+    let tx_out_public_key =
+        CompressedRistrettoPublic::try_from(&hex::decode(tx_out_public_key_hex)?[..])
+            .map_err(|e| SenderMemoError::InvalidKey(format!("tx out public key: {:?}", e)))?;
+    let receiving_subaddress_view_private =
+        RistrettoPrivate::try_from(&hex::decode(receiving_subaddress_view_private_hex)?[..])
+            .map_err(|e| SenderMemoError::InvalidKey(format!("view private key: {:?}", e)))?;
+    let sender = b58_decode_public_address(sender_b58)?;
+
+    let (valid, address_hash, payment_request_id) = match MemoType::try_from(&memo_payload) {
+        Ok(MemoType::Unused(_)) => return Ok(DecodedSenderMemo::Unused),
+        Ok(MemoType::AuthenticatedSender(memo)) => (
+            memo.validate(
+                &sender,
+                &receiving_subaddress_view_private,
+                &tx_out_public_key,
+            ),
+            memo.sender_address_hash(),
+            None,
+        ),
+        Ok(MemoType::AuthenticatedSenderWithPaymentRequestId(memo)) => (
+            memo.validate(
+                &sender,
+                &receiving_subaddress_view_private,
+                &tx_out_public_key,
+            ),
+            memo.sender_address_hash(),
+            Some(memo.payment_request_id()),
+        ),
+        _ => return Ok(DecodedSenderMemo::NotSenderMemo(hex::encode(memo_type))),
+    };
+
+    Ok(DecodedSenderMemo::Sender(SenderMemo {
+        valid: bool::from(valid),
+        address_hash_hex: hex::encode(address_hash),
+        payment_request_id,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::{account::AccountID, models::Txo},
+        json_rpc::v2::models::amount::Amount,
+        service::{
+            account::AccountService,
+            transaction::{TransactionMemo, TransactionService},
+            transaction_log::TransactionLogService,
+        },
+        test_utils::{
+            add_block_to_ledger_db, get_test_ledger, manually_sync_account, setup_wallet_service,
+            MOB,
+        },
+        util::b58::b58_encode_public_address,
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_keys::RistrettoPublic;
+    use mc_crypto_rand::rand_core::RngCore;
+    use mc_transaction_core::{
+        get_tx_out_shared_secret, ring_signature::KeyImage, tokens::Mob, tx::TxOut, Token,
+    };
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn memo_payload_hex(txo: &Txo, account_key: &AccountKey) -> (String, String) {
+        let tx_out: TxOut = mc_util_serial::decode(&txo.txo).unwrap();
+        let tx_public_key = RistrettoPublic::try_from(&tx_out.public_key).unwrap();
+        let shared_secret =
+            get_tx_out_shared_secret(account_key.view_private_key(), &tx_public_key);
+        let memo_payload = tx_out.decrypt_memo(&shared_secret);
+        let memo_payload = [
+            &memo_payload.get_memo_type()[..],
+            &memo_payload.get_memo_data()[..],
+        ]
+        .concat();
+        (
+            hex::encode(memo_payload),
+            hex::encode(tx_out.public_key.as_bytes()),
+        )
+    }
+
+    #[test_with_logger]
+    fn test_decode_rth_memos(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![alice_account_key.default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        // Pay Bob, whose keys are not in the wallet, with the RTH memo builder.
+        let bob_account_key = AccountKey::from_random(&mut rng);
+        let bob_b58 = b58_encode_public_address(&bob_account_key.default_subaddress()).unwrap();
+        let (transaction_log, _, _, _) = service
+            .build_sign_and_submit_transaction(
+                &alice_account_id.to_string(),
+                &[(bob_b58.clone(), Amount::new(10 * MOB, Mob::ID))],
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                false,
+                None,
+                TransactionMemo::RTHWithPaymentRequestId(7),
+                None,
+            )
+            .unwrap();
+        let (_, associated_txos, _) = service.get_transaction_log(&transaction_log.id).unwrap();
+
+        let alice_b58 = b58_encode_public_address(&alice_account_key.default_subaddress()).unwrap();
+        let alice_hash = compute_address_hash(&alice_b58).unwrap();
+        assert_eq!(alice_hash.len(), 32);
+
+        let (memo_hex, public_key_hex) =
+            memo_payload_hex(&associated_txos.outputs[0].0, &bob_account_key);
+        let bob_view_private_hex =
+            hex::encode(bob_account_key.subaddress_view_private(0).to_bytes());
+        assert_eq!(
+            decode_sender_memo(
+                &memo_hex,
+                &public_key_hex,
+                &bob_view_private_hex,
+                &alice_b58
+            )
+            .unwrap(),
+            DecodedSenderMemo::Sender(SenderMemo {
+                valid: true,
+                address_hash_hex: alice_hash.clone(),
+                payment_request_id: Some(7),
+            })
+        );
+
+        // The memo does not authenticate another sender.
+        assert_eq!(
+            decode_sender_memo(&memo_hex, &public_key_hex, &bob_view_private_hex, &bob_b58)
+                .unwrap(),
+            DecodedSenderMemo::Sender(SenderMemo {
+                valid: false,
+                address_hash_hex: alice_hash,
+                payment_request_id: Some(7),
+            })
+        );
+
+        // Change carries a destination memo, which names no sender.
+        let (memo_hex, public_key_hex) =
+            memo_payload_hex(&associated_txos.change[0].0, &alice_account_key);
+        assert!(matches!(
+            decode_sender_memo(
+                &memo_hex,
+                &public_key_hex,
+                &bob_view_private_hex,
+                &alice_b58
+            )
+            .unwrap(),
+            DecodedSenderMemo::NotSenderMemo(_)
+        ));
+    }
+
+    #[test]
+    fn test_decode_unused_and_malformed_memos() {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+        let account_key = AccountKey::random(&mut rng);
+        let sender_b58 = b58_encode_public_address(&account_key.default_subaddress()).unwrap();
+        let public_key_hex =
+            hex::encode(CompressedRistrettoPublic::from_random(&mut rng).as_bytes());
+        let view_private_hex = hex::encode(account_key.subaddress_view_private(0).to_bytes());
+
+        assert_eq!(
+            decode_sender_memo(
+                &"00".repeat(MEMO_PAYLOAD_LEN),
+                &public_key_hex,
+                &view_private_hex,
+                &sender_b58
+            )
+            .unwrap(),
+            DecodedSenderMemo::Unused
+        );
+
+        assert!(matches!(
+            decode_sender_memo(
+                &"00".repeat(MEMO_PAYLOAD_LEN - 1),
+                &public_key_hex,
+                &view_private_hex,
+                &sender_b58
+            ),
+            Err(SenderMemoError::InvalidMemoPayloadLength(65, 66))
+        ));
+    }
+}
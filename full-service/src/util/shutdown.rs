@@ -0,0 +1,181 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Coordinates a graceful shutdown with the requests in flight. Once shutdown
+//! begins, new requests are turned away with a retriable error, and the
+//! shutdown waits for the requests already running to finish.
+
+use crate::util::constants::SHUTTING_DOWN_RETRY_AFTER_SECONDS;
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Returned when a request arrives after shutdown has begun.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ShuttingDown {
+    /// Suggested delay before the client retries, by which time another
+    /// instance should be serving.
+    pub retry_after: Duration,
+}
+
+#[derive(Default)]
+struct ShutdownState {
+    /// Whether shutdown has begun.
+    shutting_down: bool,
+
+    /// Number of requests being handled.
+    in_flight: usize,
+}
+
+#[derive(Default)]
+pub struct ShutdownCoordinator {
+    state: Mutex<ShutdownState>,
+
+    finished: Condvar,
+}
+
+/// Held while a request is handled. Dropping the permit lets a shutdown which
+/// is waiting for the request proceed.
+pub struct RequestPermit<'a> {
+    coordinator: &'a ShutdownCoordinator,
+}
+
+impl ShutdownCoordinator {
+    /// Admit a request, unless shutdown has begun.
+    pub fn begin_request(&self) -> Result<RequestPermit, ShuttingDown> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned");
+        if state.shutting_down {
+            return Err(ShuttingDown {
+                retry_after: Duration::from_secs(SHUTTING_DOWN_RETRY_AFTER_SECONDS),
+            });
+        }
+        state.in_flight += 1;
+        Ok(RequestPermit { coordinator: self })
+    }
+
+    /// Stop admitting requests.
+    pub fn begin_shutdown(&self) {
+        self.state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned")
+            .shutting_down = true;
+    }
+
+    /// Whether shutdown has begun.
+    pub fn is_shutting_down(&self) -> bool {
+        self.state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned")
+            .shutting_down
+    }
+
+    /// Number of requests being handled.
+    pub fn in_flight(&self) -> usize {
+        self.state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned")
+            .in_flight
+    }
+
+    /// Stop admitting requests, and wait up to the grace period for those in
+    /// flight to finish. Returns whether they all finished.
+    pub fn drain(&self, grace_period: Duration) -> bool {
+        let deadline = Instant::now() + grace_period;
+        let mut state = self
+            .state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned");
+        state.shutting_down = true;
+        while state.in_flight > 0 {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self
+                .finished
+                .wait_timeout(state, deadline - now)
+                .expect("ShutdownCoordinator mutex poisoned")
+                .0;
+        }
+        true
+    }
+
+    fn finish_request(&self) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("ShutdownCoordinator mutex poisoned");
+        state.in_flight -= 1;
+        drop(state);
+        self.finished.notify_all();
+    }
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        self.coordinator.finish_request();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{mpsc, Arc},
+        thread,
+    };
+
+    #[test]
+    fn test_shutdown_rejects_new_requests() {
+        let coordinator = ShutdownCoordinator::default();
+        let permit = coordinator.begin_request().unwrap();
+        assert_eq!(coordinator.in_flight(), 1);
+        assert!(!coordinator.is_shutting_down());
+
+        coordinator.begin_shutdown();
+        assert!(coordinator.is_shutting_down());
+        assert_eq!(
+            coordinator.begin_request().err(),
+            Some(ShuttingDown {
+                retry_after: Duration::from_secs(SHUTTING_DOWN_RETRY_AFTER_SECONDS),
+            })
+        );
+
+        // Requests admitted before shutdown still finish.
+        drop(permit);
+        assert_eq!(coordinator.in_flight(), 0);
+        assert!(coordinator.drain(Duration::from_secs(0)));
+    }
+
+    #[test]
+    fn test_shutdown_waits_for_requests_in_flight() {
+        let coordinator = Arc::new(ShutdownCoordinator::default());
+        let (started_sender, started) = mpsc::channel();
+
+        let request_coordinator = coordinator.clone();
+        let request = thread::spawn(move || {
+            let _permit = request_coordinator.begin_request().unwrap();
+            started_sender.send(()).unwrap();
+            thread::sleep(Duration::from_millis(100));
+        });
+        started.recv().unwrap();
+
+        assert!(coordinator.drain(Duration::from_secs(10)));
+        assert_eq!(coordinator.in_flight(), 0);
+        request.join().unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_gives_up_after_grace_period() {
+        let coordinator = ShutdownCoordinator::default();
+        let _permit = coordinator.begin_request().unwrap();
+
+        let start = Instant::now();
+        assert!(!coordinator.drain(Duration::from_millis(50)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(coordinator.in_flight(), 1);
+    }
+}
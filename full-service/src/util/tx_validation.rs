@@ -0,0 +1,191 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Checks of a transaction against a local ledger, made the way consensus
+//! checks a proposed transaction before accepting it. Used by the validator
+//! service to check transactions for a dry run without proposing them to the
+//! network.
+
+use mc_blockchain_types::BlockVersion;
+use mc_crypto_rand::McRng;
+use mc_ledger_db::Ledger;
+use mc_transaction_core::{
+    tx::Tx,
+    validation::{
+        validate, validate_tombstone, TransactionValidationError, TransactionValidationResult,
+    },
+};
+
+/// Check a transaction against the ledger, and the network's block version and
+/// minimum fee for its fee token. The tombstone block, key images and output
+/// public keys are checked against the ledger first, and then the transaction
+/// is validated in full against the ledger's membership proofs for its rings.
+pub fn validate_tx<L: Ledger>(
+    ledger: &L,
+    tx: &Tx,
+    block_version: BlockVersion,
+    minimum_fee: u64,
+) -> TransactionValidationResult<()> {
+    let num_blocks = ledger.num_blocks().map_err(ledger_error)?;
+    validate_tombstone(num_blocks, tx.prefix.tombstone_block)?;
+
+    for key_image in tx.key_images() {
+        if ledger
+            .contains_key_image(&key_image)
+            .map_err(ledger_error)?
+        {
+            return Err(TransactionValidationError::ContainsSpentKeyImage);
+        }
+    }
+    for public_key in tx.output_public_keys() {
+        if ledger
+            .contains_tx_out_public_key(&public_key)
+            .map_err(ledger_error)?
+        {
+            return Err(TransactionValidationError::ContainsExistingOutputPublicKey);
+        }
+    }
+
+    // As in consensus, each ring is checked against the ledger's proof for
+    // its highest indexed member.
+    let highest_indices: Vec<u64> = tx
+        .prefix
+        .inputs
+        .iter()
+        .map(|tx_in| {
+            tx_in
+                .proofs
+                .iter()
+                .map(|proof| proof.index)
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let root_proofs = ledger
+        .get_tx_out_proof_of_memberships(&highest_indices)
+        .map_err(ledger_error)?;
+
+    validate(
+        tx,
+        num_blocks,
+        block_version,
+        &root_proofs,
+        minimum_fee,
+        &mut McRng::default(),
+    )
+}
+
+fn ledger_error(err: mc_ledger_db::Error) -> TransactionValidationError {
+    TransactionValidationError::Ledger(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::account::AccountID,
+        json_rpc::v2::models::amount::Amount,
+        service::{
+            account::AccountService,
+            ledger::LedgerService,
+            transaction::{TransactionMemo, TransactionService},
+        },
+        test_utils::{
+            add_block_to_ledger_db, add_block_with_tx, get_test_ledger, manually_sync_account,
+            setup_wallet_service, MOB,
+        },
+        util::b58::b58_encode_public_address,
+    };
+    use mc_account_keys::{AccountKey, PublicAddress};
+    use mc_common::logger::{test_with_logger, Logger};
+    use mc_crypto_rand::RngCore;
+    use mc_transaction_core::{ring_signature::KeyImage, tokens::Mob, Token};
+    use mc_util_from_random::FromRandom;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test_with_logger]
+    fn test_validate_tx(logger: Logger) {
+        let mut rng: StdRng = SeedableRng::from_seed([20u8; 32]);
+
+        let known_recipients: Vec<PublicAddress> = Vec::new();
+        let mut ledger_db = get_test_ledger(5, &known_recipients, 12, &mut rng);
+        let service = setup_wallet_service(ledger_db.clone(), logger.clone());
+
+        let alice = service
+            .create_account(
+                Some("Alice's Main Account".to_string()),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+        let alice_account_key: AccountKey = mc_util_serial::decode(&alice.account_key).unwrap();
+        let alice_account_id = AccountID::from(&alice_account_key);
+        for _ in 0..2 {
+            add_block_to_ledger_db(
+                &mut ledger_db,
+                &vec![alice_account_key.default_subaddress()],
+                100 * MOB,
+                &vec![KeyImage::from(rng.next_u64())],
+                &mut rng,
+            );
+        }
+        manually_sync_account(&ledger_db, &service.wallet_db, &alice_account_id, &logger);
+
+        let bob_b58 =
+            b58_encode_public_address(&AccountKey::from_random(&mut rng).default_subaddress())
+                .unwrap();
+        let block_version = service.get_network_block_version();
+        let build_paying_bob = |tombstone_block: Option<String>| {
+            service
+                .build_and_sign_transaction(
+                    &alice_account_id.to_string(),
+                    &[(bob_b58.clone(), Amount::new(10 * MOB, Mob::ID))],
+                    None,
+                    None,
+                    None,
+                    tombstone_block,
+                    None,
+                    None,
+                    false,
+                    None,
+                    TransactionMemo::RTH,
+                )
+                .unwrap()
+                .tx
+        };
+
+        let tx = build_paying_bob(None);
+        assert_eq!(
+            validate_tx(&ledger_db, &tx, block_version, Mob::MINIMUM_FEE),
+            Ok(())
+        );
+
+        // A fee below the network minimum is rejected.
+        assert_eq!(
+            validate_tx(&ledger_db, &tx, block_version, tx.prefix.fee + 1),
+            Err(TransactionValidationError::TxFeeError)
+        );
+
+        // Once its tombstone block is in the ledger, the transaction expires.
+        let num_blocks = ledger_db.num_blocks().unwrap();
+        let expiring_tx = build_paying_bob(Some((num_blocks + 1).to_string()));
+        add_block_to_ledger_db(
+            &mut ledger_db,
+            &vec![AccountKey::from_random(&mut rng).default_subaddress()],
+            100 * MOB,
+            &vec![KeyImage::from(rng.next_u64())],
+            &mut rng,
+        );
+        assert_eq!(
+            validate_tx(&ledger_db, &expiring_tx, block_version, Mob::MINIMUM_FEE),
+            Err(TransactionValidationError::TombstoneBlockExceeded)
+        );
+
+        // Once the transaction has landed, checking it again is a double spend.
+        add_block_with_tx(&mut ledger_db, tx.clone(), &mut rng);
+        assert_eq!(
+            validate_tx(&ledger_db, &tx, block_version, Mob::MINIMUM_FEE),
+            Err(TransactionValidationError::ContainsSpentKeyImage)
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2020-2022 MobileCoin Inc.
+
+//! Sends and receives through the test harness, as a crate depending on
+//! full-service with the `test-harness` feature would.
+
+use mc_common::logger::{test_with_logger, Logger};
+use mc_full_service::{db::account::AccountID, test_harness::TestHarness, test_utils::MOB};
+use mc_transaction_core::{tokens::Mob, Token};
+use serde_json::json;
+use std::time::Duration;
+
+#[test_with_logger]
+fn test_send_and_receive(logger: Logger) {
+    let mut harness = TestHarness::new(logger);
+    let alice = harness.create_account("Alice");
+    let bob = harness.create_account("Bob");
+    harness.fund(&AccountID(alice.id.clone()), 100 * MOB, Mob::ID);
+
+    let res = harness.rpc("get_account_status", json!({ "account_id": bob.id }));
+    let bob_address = res["result"]["account"]["main_address"].clone();
+
+    let res = harness.rpc(
+        "build_and_submit_transaction",
+        json!({
+            "account_id": alice.id,
+            "recipient_public_address": bob_address,
+            "amount": { "value": (42 * MOB).to_string(), "token_id": "0" },
+        }),
+    );
+    assert!(res["error"].is_null(), "{}", res);
+    let transaction_log_id = res["result"]["transaction_log"]["id"].clone();
+
+    harness.mine_block();
+
+    let res = harness.rpc(
+        "get_transaction_log",
+        json!({ "transaction_log_id": transaction_log_id }),
+    );
+    assert_eq!(res["result"]["transaction_log"]["status"], "succeeded");
+
+    let res = harness.rpc("get_account_status", json!({ "account_id": bob.id }));
+    assert_eq!(
+        res["result"]["balance_per_token"]["0"]["unspent"],
+        (42 * MOB).to_string()
+    );
+
+    let res = harness.rpc("get_account_status", json!({ "account_id": alice.id }));
+    assert_eq!(
+        res["result"]["balance_per_token"]["0"]["unspent"],
+        (58 * MOB - Mob::MINIMUM_FEE).to_string()
+    );
+}
+
+#[test_with_logger]
+fn test_shutdown_leaves_sync_consistent(logger: Logger) {
+    let mut harness = TestHarness::new(logger);
+    let alice = harness.create_account("Alice");
+    let alice_id = AccountID(alice.id.clone());
+
+    // Add blocks for the wallet's sync thread to find, and shut down while it
+    // is working through them.
+    let num_funded_blocks = 200;
+    let first_funded_block_index = harness.fund_without_sync(&alice_id, MOB, Mob::ID) - 1;
+    for _ in 1..num_funded_blocks {
+        harness.fund_without_sync(&alice_id, MOB, Mob::ID);
+    }
+    assert!(harness.service.shutdown(Duration::from_secs(5)));
+
+    // New requests are turned away, but the status still answers.
+    let res = harness.rpc("get_account_status", json!({ "account_id": alice.id }));
+    assert_eq!(res["error"]["code"], -32000, "{}", res);
+    assert_eq!(
+        res["error"]["data"]["details"],
+        "The wallet is shutting down"
+    );
+    let res = harness.rpc("get_wallet_status", json!({}));
+    assert_eq!(res["result"]["wallet_status"]["shutting_down"], true);
+
+    // Every block the account synced before shutdown is fully recorded.
+    harness.restart();
+    let res = harness.rpc("get_account_status", json!({ "account_id": alice.id }));
+    assert!(res["error"].is_null(), "{}", res);
+    let next_block_index: u64 = res["result"]["account"]["next_block_index"]
+        .as_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    let synced_funded_blocks = next_block_index.saturating_sub(first_funded_block_index);
+    assert_eq!(
+        res["result"]["balance_per_token"]["0"]["unspent"],
+        (synced_funded_blocks * MOB).to_string()
+    );
+
+    // After the restart the account picks up where it left off, and each txo
+    // is recorded once.
+    harness.sync_all();
+    let res = harness.rpc("get_txos", json!({ "account_id": alice.id }));
+    assert_eq!(
+        res["result"]["txo_ids"].as_array().unwrap().len(),
+        num_funded_blocks as usize
+    );
+    let res = harness.rpc("get_account_status", json!({ "account_id": alice.id }));
+    assert_eq!(
+        res["result"]["balance_per_token"]["0"]["unspent"],
+        (num_funded_blocks * MOB).to_string()
+    );
+    let res = harness.rpc("get_wallet_status", json!({}));
+    assert_eq!(res["result"]["wallet_status"]["shutting_down"], false);
+}
@@ -118,6 +118,28 @@ impl ValidatorConnection {
         }
     }
 
+    /// Check a transaction against the validator's ledger and the network's
+    /// fees without proposing it. Returns the number of blocks in the
+    /// validator's ledger, and rejections as the same errors as propose_tx.
+    pub fn validate_tx(&self, tx: &Tx) -> ConnectionResult<u64> {
+        let response = self
+            .validator_api_client
+            .validate_tx(&tx.into())
+            .map_err(|err| {
+                log::warn!(
+                    self.logger,
+                    "validator validate_tx RPC call failed: {}",
+                    err
+                );
+                err
+            })?;
+        if response.get_result() == ProposeTxResult::Ok {
+            Ok(response.get_block_count())
+        } else {
+            Err(response.get_result().into())
+        }
+    }
+
     /// Fetch multiple fog reports.
     pub fn fetch_fog_reports(
         &self,
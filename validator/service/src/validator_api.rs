@@ -3,13 +3,16 @@
 //! Validator API GRPC service implementation.
 
 use grpcio::{EnvBuilder, RpcContext, RpcStatus, Service, UnarySink};
+use mc_blockchain_types::BlockVersion;
 use mc_common::logger::{log, Logger};
 use mc_connection::{
-    ConnectionManager, Error as ConnectionError, RetryError, RetryableUserTxConnection,
-    UserTxConnection,
+    BlockchainConnection, ConnectionManager, Error as ConnectionError, RetryError,
+    RetryableBlockchainConnection, RetryableUserTxConnection, UserTxConnection,
 };
 use mc_fog_report_connection::{Error as FogConnectionError, GrpcFogReportConnection};
+use mc_full_service::util::tx_validation::validate_tx;
 use mc_ledger_db::{Ledger, LedgerDB};
+use mc_transaction_core::{validation::TransactionValidationError, TokenId};
 use mc_util_grpc::{
     rpc_database_err, rpc_internal_error, rpc_invalid_arg_error, rpc_logger, rpc_permissions_error,
     send_result,
@@ -22,6 +25,7 @@ use mc_validator_api::{
     validator_api::{FetchFogReportRequest, FetchFogReportResponse, FetchFogReportResult},
     validator_api_grpc::{create_validator_api, ValidatorApi as GrpcValidatorApi},
 };
+use rayon::prelude::*; // For par_iter
 use std::{
     convert::TryFrom,
     str::FromStr,
@@ -34,7 +38,7 @@ use std::{
 /// Maximal number of blocks we will return in a single request.
 pub const MAX_BLOCKS_PER_REQUEST: u32 = 1000;
 
-pub struct ValidatorApi<UTC: UserTxConnection + 'static> {
+pub struct ValidatorApi<UTC: BlockchainConnection + UserTxConnection + 'static> {
     /// Ledger DB.
     ledger_db: LedgerDB,
 
@@ -52,7 +56,7 @@ pub struct ValidatorApi<UTC: UserTxConnection + 'static> {
     logger: Logger,
 }
 
-impl<UTC: UserTxConnection + 'static> Clone for ValidatorApi<UTC> {
+impl<UTC: BlockchainConnection + UserTxConnection + 'static> Clone for ValidatorApi<UTC> {
     fn clone(&self) -> Self {
         Self {
             ledger_db: self.ledger_db.clone(),
@@ -64,7 +68,7 @@ impl<UTC: UserTxConnection + 'static> Clone for ValidatorApi<UTC> {
     }
 }
 
-impl<UTC: UserTxConnection + 'static> ValidatorApi<UTC> {
+impl<UTC: BlockchainConnection + UserTxConnection + 'static> ValidatorApi<UTC> {
     pub fn new(ledger_db: LedgerDB, conn_manager: ConnectionManager<UTC>, logger: Logger) -> Self {
         Self {
             ledger_db,
@@ -190,6 +194,54 @@ impl<UTC: UserTxConnection + 'static> ValidatorApi<UTC> {
         Ok(result)
     }
 
+    fn validate_tx_impl(&self, tx: Tx, logger: &Logger) -> Result<ProposeTxResponse, RpcStatus> {
+        // Convert Protobuf/GRPC Tx to Prost Tx
+        let tx = mc_transaction_core::tx::Tx::try_from(&tx)
+            .map_err(|_| rpc_invalid_arg_error("validate_tx", "tx", logger))?;
+
+        // The block version and minimum fee come from the network, as for the
+        // blockchain API.
+        let block_infos: Vec<_> = self
+            .conn_manager
+            .conns()
+            .par_iter()
+            .filter_map(|conn| conn.fetch_block_info(std::iter::empty()).ok())
+            .collect();
+        let block_version = block_infos
+            .iter()
+            .map(|block_info| block_info.network_block_version)
+            .max()
+            .ok_or_else(|| rpc_internal_error("validate_tx", "no block info from peers", logger))?;
+        let block_version = BlockVersion::try_from(block_version)
+            .map_err(|err| rpc_internal_error("validate_tx", format!("{:?}", err), logger))?;
+        let fee_token_id = TokenId::from(tx.prefix.fee_token_id);
+        let minimum_fee = block_infos
+            .iter()
+            .filter_map(|block_info| block_info.minimum_fee_or_none(&fee_token_id))
+            .max();
+
+        let mut result = ProposeTxResponse::new();
+        let validation_result = match minimum_fee {
+            Some(minimum_fee) => validate_tx(&self.ledger_db, &tx, block_version, minimum_fee),
+            None => Err(TransactionValidationError::TokenNotYetConfigured),
+        };
+        match validation_result {
+            Ok(()) => {
+                let num_blocks = self
+                    .ledger_db
+                    .num_blocks()
+                    .map_err(|err| rpc_database_err(err, logger))?;
+                result.set_block_count(num_blocks);
+            }
+            Err(err) => {
+                log::debug!(logger, "validate_tx rejected tx: {}", err);
+                result.set_result(err.into());
+            }
+        }
+
+        Ok(result)
+    }
+
     fn fetch_fog_report_impl(
         &self,
         request: FetchFogReportRequest,
@@ -223,7 +275,9 @@ impl<UTC: UserTxConnection + 'static> ValidatorApi<UTC> {
     }
 }
 
-impl<UTC: UserTxConnection + 'static> GrpcValidatorApi for ValidatorApi<UTC> {
+impl<UTC: BlockchainConnection + UserTxConnection + 'static> GrpcValidatorApi
+    for ValidatorApi<UTC>
+{
     fn get_archive_blocks(
         &mut self,
         ctx: RpcContext,
@@ -246,6 +300,12 @@ impl<UTC: UserTxConnection + 'static> GrpcValidatorApi for ValidatorApi<UTC> {
         })
     }
 
+    fn validate_tx(&mut self, ctx: RpcContext, request: Tx, sink: UnarySink<ProposeTxResponse>) {
+        mc_common::logger::scoped_global_logger(&rpc_logger(&ctx, &self.logger), |logger| {
+            send_result(ctx, sink, self.validate_tx_impl(request, logger), logger)
+        })
+    }
+
     fn fetch_fog_report(
         &mut self,
         ctx: RpcContext,